@@ -2,7 +2,17 @@ import bpy
 from bpy.types import Context, Menu
 
 from . import preferences, importer, tools, benchmark
-from .importer import ImportMdl, ImportVmf, ImportVmt, ImportVtf
+from .importer import (
+    ImportDmx,
+    ImportMdl,
+    ImportVcd,
+    ImportVmat,
+    ImportVmf,
+    ImportVmt,
+    ImportVtex,
+    ImportVtf,
+    ImportWad,
+)
 from .tools import IMPORT_MT_plumber_browse
 
 
@@ -17,12 +27,27 @@ class IMPORT_MT_plumber(Menu):
         self.layout.operator(
             ImportMdl.bl_idname, text="Source Model (.mdl)"
         ).from_game_fs = False
+        self.layout.operator(
+            ImportDmx.bl_idname, text="DataModel Exchange Model (.dmx)"
+        ).from_game_fs = False
         self.layout.operator(
             ImportVmt.bl_idname, text="Valve Material Type (.vmt)"
         ).from_game_fs = False
         self.layout.operator(
             ImportVtf.bl_idname, text="Valve Texture Format (.vtf)"
         ).from_game_fs = False
+        self.layout.operator(
+            ImportWad.bl_idname, text="GoldSrc Texture Archive (.wad)"
+        ).from_game_fs = False
+        self.layout.operator(
+            ImportVcd.bl_idname, text="Choreography Scene (.vcd)"
+        ).from_game_fs = False
+        self.layout.operator(
+            ImportVmat.bl_idname, text="Source 2 Material (.vmat, .vmat_c)"
+        ).from_game_fs = False
+        self.layout.operator(
+            ImportVtex.bl_idname, text="Source 2 Texture (.vtex_c)"
+        ).from_game_fs = False
 
         self.layout.menu(IMPORT_MT_plumber_browse.bl_idname)
 
@@ -1,9 +1,14 @@
-from typing import Optional
+from typing import Optional, Tuple
+import bpy
 from bpy.types import Context, Collection
 
 from ..plumber import (
     BuiltBrushEntity,
     BuiltOverlay,
+    ChoreoScene,
+    Cubemap,
+    DetailScatter,
+    DetailSprites,
     LoadedProp,
     Material,
     Model,
@@ -12,21 +17,44 @@ from ..plumber import (
     SkyCamera,
     SpotLight,
     SkyEqui,
+    SkyFaces,
     Texture,
+    TrackPath,
     UnknownEntity,
+    Wind,
+    Precipitation,
+    FogController,
+    Sprite,
+    Sound,
+    VisCluster,
 )
-from .material import import_material, import_texture
+from .material import import_material, import_texture, import_texture_material
 from .model import ModelTracker
 from .brush import import_brush
 from .overlay import import_overlay
-from .prop import apply_armatures, import_prop
+from .detail import import_detail_scatter, import_detail_sprites
+from .prop import apply_armatures, import_prop, merge_ragdolls
 from .light import import_light, import_spot_light, import_env_light
-from .sky_camera import import_sky_camera
-from .sky_equi import import_sky_equi
+from .sky_camera import assemble_skybox, import_sky_camera
+from .sky_equi import import_sky_equi, import_sky_faces
+from .cubemap import import_cubemap
+from .vis_cluster import import_vis_cluster
+from .track import import_track_path
+from .choreo import import_choreo_scene
 from .unknown_entity import import_unknown_entity
+from .gameplay import mark_gameplay_object
+from .sprite import import_sprite
+from .sound import SoundFiles, import_sound
+from .weather import import_fog_controller, import_precipitation, import_wind
+from .bake import generate_bake_uvs
+from .namespace import apply_namespace
+from .cleanup import cleanup_decompiled
+from .utils import truncate_name
 
 
 class AssetCallbacks:
+    # the callbacks of assets return whether the asset is completely imported, so that
+    # resuming an interrupted import only skips the complete ones
     def __init__(
         self,
         context: Context,
@@ -37,6 +65,15 @@ class AssetCallbacks:
         light_collection: Optional[Collection] = None,
         entity_collection: Optional[Collection] = None,
         apply_armatures: bool = False,
+        merge_ragdolls: bool = False,
+        bake_preset: bool = False,
+        decompile_cleanup: bool = False,
+        import_blend_factors: bool = False,
+        assemble_skybox: bool = False,
+        save_material_scripts: bool = False,
+        gameplay_markers: bool = False,
+        name_prefix: str = "",
+        offset: Tuple[float, float, float] = (0.0, 0.0, 0.0),
     ) -> None:
         self.context = context
         self.model_tracker = ModelTracker()
@@ -48,50 +85,221 @@ class AssetCallbacks:
         self.prop_collection = prop_collection or self.main_collection
         self.light_collection = light_collection or self.main_collection
         self.entity_collection = entity_collection or self.main_collection
+        self.vis_cluster_collection = None
+        self.gameplay_collection = None
 
         self.apply_armatures = apply_armatures
 
-    def material(self, material: Material) -> None:
+        self.merge_ragdolls = merge_ragdolls
+        self.ragdoll_groups = {}
+
+        self.bake_preset = bake_preset
+        self.name_prefix = name_prefix
+        self.offset = offset
+        self.existing_objects = (
+            set(bpy.data.objects)
+            if bake_preset or name_prefix or any(offset)
+            else set()
+        )
+        # the new objects are only unwrapped, renamed and moved in `finish`
+        self.objects_complete = not (bake_preset or name_prefix or any(offset))
+
+        self.decompile_cleanup = decompile_cleanup
+        self.brush_objects = []
+
+        self.import_blend_factors = import_blend_factors
+
+        self.assemble_skybox = assemble_skybox
+        self.skybox_objects = []
+        self.skybox_transform = None
+
+        self.save_material_scripts = save_material_scripts
+
+        self.gameplay_markers = gameplay_markers
+
+        self.sound_files = SoundFiles()
+
+    def material(self, material: Material) -> bool:
+        if self.save_material_scripts:
+            name = truncate_name(material.name())
+            text = bpy.data.texts.get(name + ".py") or bpy.data.texts.new(name + ".py")
+            # the scripts use the helpers of the importer
+            text.from_string(material.python_script(name, import_material.__module__))
+
         import_material(material)
+        return True
 
-    def texture(self, texture: Texture) -> None:
+    def texture(self, texture: Texture) -> bool:
         import_texture(texture)
+        return True
+
+    def texture_material(self, texture: Texture) -> None:
+        import_texture_material(texture)
 
-    def model(self, model: Model) -> None:
+    def model(self, model: Model) -> bool:
         self.model_tracker.import_model(model, self.prop_collection)
+        return True
 
-    def brush(self, brush: BuiltBrushEntity) -> None:
-        import_brush(brush, self.brush_collection)
+    def brush(self, brush: BuiltBrushEntity) -> bool:
+        class_name = brush.class_name()
+        marker = brush.gameplay_marker() if self.gameplay_markers else None
+        collection = (
+            self.brush_collection if marker is None else self.get_gameplay_collection()
+        )
+        objects = import_brush(brush, collection, self.import_blend_factors)
 
-    def overlay(self, overlay: BuiltOverlay) -> None:
+        if marker is not None:
+            for obj in objects:
+                mark_gameplay_object(obj, marker, brush.gameplay_label(), brush.id())
+
+        if self.decompile_cleanup:
+            self.brush_objects.extend((class_name, obj) for obj in objects)
+
+        skybox_objects = [o for o in objects if o.get("in_3d_skybox")]
+        if self.assemble_skybox:
+            self.skybox_objects.extend(skybox_objects)
+
+        return (
+            self.objects_complete
+            and not self.decompile_cleanup
+            and not (self.assemble_skybox and skybox_objects)
+        )
+
+    def overlay(self, overlay: BuiltOverlay) -> bool:
         import_overlay(overlay, self.overlay_collection)
+        return self.objects_complete
+
+    def detail_sprites(self, sprites: DetailSprites) -> None:
+        import_detail_sprites(sprites, self.prop_collection)
 
-    def prop(self, prop: LoadedProp) -> None:
-        import_prop(
+    def detail_scatter(self, scatter: DetailScatter) -> None:
+        import_detail_scatter(scatter, self.prop_collection)
+
+    def prop(self, prop: LoadedProp) -> bool:
+        ragdoll_group = prop.ragdoll_group() if self.merge_ragdolls else None
+
+        # grouped armatures are applied after they are merged
+        obj = import_prop(
             prop,
             self.prop_collection,
             self.model_tracker,
-            self.apply_armatures,
+            self.apply_armatures and ragdoll_group is None,
             self.armatures_to_apply,
         )
 
-    def light(self, light: Light) -> None:
+        grouped = ragdoll_group is not None and obj.type == "ARMATURE"
+        if grouped:
+            self.ragdoll_groups.setdefault(ragdoll_group, []).append(obj)
+
+        in_skybox = self.assemble_skybox and obj.get("in_3d_skybox")
+        if in_skybox:
+            self.skybox_objects.append(obj)
+
+        return self.objects_complete and not grouped and not in_skybox
+
+    def light(self, light: Light) -> bool:
         import_light(light, self.light_collection)
+        return self.objects_complete
 
-    def spot_light(self, light: SpotLight) -> None:
+    def spot_light(self, light: SpotLight) -> bool:
         import_spot_light(light, self.light_collection)
+        return self.objects_complete
 
-    def env_light(self, light: EnvLight) -> None:
+    def env_light(self, light: EnvLight) -> bool:
         import_env_light(light, self.context, self.light_collection)
+        return self.objects_complete
 
-    def sky_camera(self, sky_camera: SkyCamera) -> None:
+    def sky_camera(self, sky_camera: SkyCamera) -> bool:
         import_sky_camera(sky_camera, self.context, self.main_collection)
 
-    def sky_equi(self, sky_equi: SkyEqui) -> None:
+        if self.skybox_transform is None:
+            self.skybox_transform = (sky_camera.skybox_offset(), sky_camera.scale())
+
+        return self.objects_complete
+
+    def sky_equi(self, sky_equi: SkyEqui) -> bool:
         import_sky_equi(sky_equi, self.context)
+        return True
+
+    def sky_faces(self, sky_faces: SkyFaces) -> bool:
+        import_sky_faces(sky_faces)
+        return True
+
+    def cubemap(self, cubemap: Cubemap) -> None:
+        import_cubemap(cubemap, self.entity_collection)
+
+    def vis_cluster(self, cluster: VisCluster) -> None:
+        if self.vis_cluster_collection is None:
+            self.vis_cluster_collection = bpy.data.collections.new("vis_clusters")
+            self.main_collection.children.link(self.vis_cluster_collection)
+
+        import_vis_cluster(cluster, self.vis_cluster_collection)
+
+    def unknown_entity(self, entity: UnknownEntity) -> bool:
+        marker = entity.gameplay_marker() if self.gameplay_markers else None
+        if marker is None:
+            import_unknown_entity(entity, self.entity_collection)
+            return self.objects_complete
+
+        obj = import_unknown_entity(entity, self.get_gameplay_collection())
+        mark_gameplay_object(obj, marker, entity.gameplay_label(), entity.id())
+        return self.objects_complete
+
+    def get_gameplay_collection(self) -> Collection:
+        if self.gameplay_collection is None:
+            self.gameplay_collection = bpy.data.collections.new("gameplay")
+            self.main_collection.children.link(self.gameplay_collection)
+
+        return self.gameplay_collection
+
+    def wind(self, wind: Wind) -> bool:
+        import_wind(wind, self.entity_collection)
+        return self.objects_complete
 
-    def unknown_entity(self, entity: UnknownEntity) -> None:
-        import_unknown_entity(entity, self.entity_collection)
+    def precipitation(self, precipitation: Precipitation) -> bool:
+        import_precipitation(precipitation, self.entity_collection)
+        return self.objects_complete
+
+    def fog_controller(self, fog: FogController) -> bool:
+        import_fog_controller(fog, self.entity_collection)
+        return self.objects_complete
+
+    def sprite(self, sprite: Sprite) -> bool:
+        import_sprite(sprite, self.entity_collection)
+        return self.objects_complete
+
+    def sound(self, sound: Sound) -> bool:
+        import_sound(sound, self.entity_collection, self.sound_files)
+        return self.objects_complete
+
+    def track_path(self, path: TrackPath) -> None:
+        import_track_path(path, self.entity_collection)
+
+    def choreo_scene(self, scene: ChoreoScene) -> None:
+        import_choreo_scene(scene, self.context)
 
     def finish(self) -> None:
+        merged = merge_ragdolls(self.ragdoll_groups)
+        if self.apply_armatures:
+            self.armatures_to_apply.extend(merged)
+
         apply_armatures(self.armatures_to_apply)
+
+        # the sky camera may be imported after the skybox contents
+        if self.skybox_transform is not None and self.skybox_objects:
+            assemble_skybox(
+                self.skybox_transform, self.skybox_objects, self.main_collection
+            )
+
+        if self.decompile_cleanup:
+            cleanup_decompiled(self.context, self.brush_objects)
+
+        new_objects = set(bpy.data.objects) - self.existing_objects
+
+        if self.bake_preset:
+            generate_bake_uvs(self.context, new_objects)
+
+        if self.name_prefix or any(self.offset):
+            apply_namespace(
+                self.main_collection, new_objects, self.name_prefix, self.offset
+            )
@@ -0,0 +1,50 @@
+from typing import Iterable, List
+
+import bpy
+from bpy.types import Context, Object
+
+
+BAKE_UV_LAYER_NAME = "Bake"
+
+
+def generate_bake_uvs(context: Context, objects: Iterable[Object]) -> None:
+    """Adds a non-overlapping second UV map to the given mesh objects for texture baking."""
+
+    mesh_objects: List[Object] = []
+    meshes = set()
+
+    for obj in objects:
+        if obj.type != "MESH" or obj.data in meshes:
+            continue
+
+        meshes.add(obj.data)
+        mesh_objects.append(obj)
+
+    if not mesh_objects:
+        return
+
+    for obj in mesh_objects:
+        mesh = obj.data
+        uv_layer = mesh.uv_layers.get(BAKE_UV_LAYER_NAME)
+        if uv_layer is None:
+            uv_layer = mesh.uv_layers.new(name=BAKE_UV_LAYER_NAME)
+        mesh.uv_layers.active = uv_layer
+
+    view_layer = context.view_layer
+
+    for obj in view_layer.objects.selected:
+        obj.select_set(False)
+
+    for obj in mesh_objects:
+        obj.select_set(True)
+
+    view_layer.objects.active = mesh_objects[0]
+
+    bpy.ops.object.mode_set(mode="EDIT")
+    bpy.ops.mesh.select_all(action="SELECT")
+    bpy.ops.uv.lightmap_pack(PREF_CONTEXT="ALL_FACES", PREF_PACK_IN_ONE=False)
+    bpy.ops.object.mode_set(mode="OBJECT")
+
+    for obj in mesh_objects:
+        # keep the original texture coordinates as the active ones for rendering
+        obj.data.uv_layers.active_index = 0
@@ -1,24 +1,67 @@
+from math import pi
+from typing import Dict, List, Optional, Set, Tuple
+
 import bpy
-from bpy.types import Collection
+from bpy.types import Collection, Mesh, Object
 
 from .utils import truncate_name
 from ..plumber import BuiltBrushEntity, BuiltSolid, MergedSolids
 
 
-def import_brush(brush: BuiltBrushEntity, collection: Collection) -> None:
+DISPLACEMENT_ATTRIBUTE = "displacement"
+
+
+def import_brush(
+    brush: BuiltBrushEntity, collection: Collection, blend_factors: bool = False
+) -> List[Object]:
     id = brush.id()
     class_name = brush.class_name()
     brush_name = f"{class_name}_{id}"
 
+    objects = []
+
     merged_solids = brush.merged_solids()
     if merged_solids is not None:
-        import_merged_solids(collection, brush_name, merged_solids)
+        objects.append(
+            import_merged_solids(collection, brush_name, merged_solids, blend_factors)
+        )
+
+    skybox_merged_solids = brush.skybox_merged_solids()
+    if skybox_merged_solids is not None:
+        objects.append(
+            import_merged_solids(
+                collection, f"{brush_name}_skybox", skybox_merged_solids, blend_factors
+            )
+        )
 
     for solid in brush.solids():
-        import_solid(collection, brush_name, solid)
+        objects.append(import_solid(collection, brush_name, solid, blend_factors))
+
+    import_solidity(brush, objects)
+
+    return objects
+
+
+def import_solidity(brush: BuiltBrushEntity, objects: List[Object]) -> None:
+    """Store whether the brush collides, for physics and navigation scenes."""
+    solidity = brush.solidity()
+    collision_group = brush.collision_group()
+
+    for obj in objects:
+        obj["solid"] = brush.solid()
+        obj["start_disabled"] = brush.start_disabled()
+        obj["spawnflags"] = brush.spawnflags()
 
+        if solidity is not None:
+            obj["solidity"] = solidity
 
-def import_solid(collection: Collection, brush_name: str, solid: BuiltSolid) -> None:
+        if collision_group is not None:
+            obj["collision_group"] = collision_group
+
+
+def import_solid(
+    collection: Collection, brush_name: str, solid: BuiltSolid, blend_factors: bool
+) -> Object:
     id = solid.id()
     solid_name = f"{brush_name}_{id}"
     mesh = bpy.data.meshes.new(solid_name)
@@ -37,12 +80,20 @@ def import_solid(collection: Collection, brush_name: str, solid: BuiltSolid) ->
 
     mesh.update()
 
+    import_smoothing_groups(mesh, solid.polygon_smoothing_groups())
+
     uv_layer = mesh.uv_layers.new()
     uv_layer.data.foreach_set("uv", solid.loop_uvs())
 
+    import_lightmap_uvs(mesh, solid.loop_lightmap_uvs())
+    import_displacements(mesh, solid.polygon_displacements())
+
     color_layer = mesh.vertex_colors.new(name="Col", do_init=False)
     color_layer.data.foreach_set("color", solid.loop_colors())
 
+    if blend_factors:
+        import_blend_factors(mesh, solid.loop_blend_factors())
+
     for material in solid.materials():
         material_data = bpy.data.materials.get(truncate_name(material))
         if material_data is None:
@@ -52,12 +103,19 @@ def import_solid(collection: Collection, brush_name: str, solid: BuiltSolid) ->
     obj = bpy.data.objects.new(solid_name, object_data=mesh)
     obj.location = solid.position()
     obj.scale = solid.scale()
+    if solid.in_3d_skybox():
+        obj["in_3d_skybox"] = True
     collection.objects.link(obj)
 
+    return obj
+
 
 def import_merged_solids(
-    collection: Collection, brush_name: str, merged_solids: MergedSolids
-) -> None:
+    collection: Collection,
+    brush_name: str,
+    merged_solids: MergedSolids,
+    blend_factors: bool,
+) -> Object:
     mesh = bpy.data.meshes.new(brush_name)
 
     vertices = merged_solids.vertices()
@@ -76,12 +134,20 @@ def import_merged_solids(
 
     mesh.update()
 
+    import_smoothing_groups(mesh, merged_solids.polygon_smoothing_groups())
+
     uv_layer = mesh.uv_layers.new()
     uv_layer.data.foreach_set("uv", merged_solids.loop_uvs())
 
+    import_lightmap_uvs(mesh, merged_solids.loop_lightmap_uvs())
+    import_displacements(mesh, merged_solids.polygon_displacements())
+
     color_layer = mesh.vertex_colors.new(name="Col", do_init=False)
     color_layer.data.foreach_set("color", merged_solids.loop_colors())
 
+    if blend_factors:
+        import_blend_factors(mesh, merged_solids.loop_blend_factors())
+
     for material in merged_solids.materials():
         material_data = bpy.data.materials.get(truncate_name(material))
         if material_data is None:
@@ -91,4 +157,61 @@ def import_merged_solids(
     obj = bpy.data.objects.new(brush_name, object_data=mesh)
     obj.location = merged_solids.position()
     obj.scale = merged_solids.scale()
+    if merged_solids.in_3d_skybox():
+        obj["in_3d_skybox"] = True
     collection.objects.link(obj)
+
+    return obj
+
+
+def import_blend_factors(mesh: Mesh, factors: Optional[List[float]]) -> None:
+    if factors is None:
+        return
+
+    # stored linearly, so repainted factors can drive the blend node groups as is
+    attribute = mesh.attributes.new("blend_factor", "FLOAT_COLOR", "CORNER")
+    attribute.data.foreach_set("color", factors)
+
+
+def import_lightmap_uvs(mesh: Mesh, uvs: Optional[List[float]]) -> None:
+    if uvs is None:
+        return
+
+    uv_layer = mesh.uv_layers.new(name="Lightmap", do_init=False)
+    uv_layer.data.foreach_set("uv", uvs)
+
+
+def import_displacements(mesh: Mesh, displacements: Optional[List[bool]]) -> None:
+    if displacements is None or not any(displacements):
+        return
+
+    # lets the cleanup of decompiled maps weld only the seams of displacements
+    attribute = mesh.attributes.new(DISPLACEMENT_ATTRIBUTE, "BOOLEAN", "FACE")
+    attribute.data.foreach_set("value", displacements)
+
+
+def import_smoothing_groups(mesh: Mesh, groups: Optional[List[int]]) -> None:
+    if groups is None:
+        return
+
+    edge_groups: Dict[Tuple[int, int], List[int]] = {}
+
+    for polygon, polygon_groups in zip(mesh.polygons, groups):
+        polygon.use_smooth = polygon_groups != 0
+        for edge_key in polygon.edge_keys:
+            edge_groups.setdefault(edge_key, []).append(polygon_groups)
+
+    # edges are only smooth between two faces sharing a smoothing group
+    smooth_edges: Set[Tuple[int, int]] = {
+        edge_key
+        for edge_key, polygon_groups in edge_groups.items()
+        if len(polygon_groups) == 2 and polygon_groups[0] & polygon_groups[1] != 0
+    }
+
+    for edge in mesh.edges:
+        edge.use_edge_sharp = edge.key not in smooth_edges
+
+    # older versions only use sharp edges with auto smooth
+    if hasattr(mesh, "use_auto_smooth"):
+        mesh.use_auto_smooth = True
+        mesh.auto_smooth_angle = pi
@@ -0,0 +1,64 @@
+from bpy.types import Context
+
+from ..plumber import ChoreoScene
+
+# strip colors of the common event types
+EVENT_COLORS = {
+    "speak": (0.2, 0.5, 0.9),
+    "gesture": (0.9, 0.6, 0.2),
+    "sequence": (0.8, 0.3, 0.3),
+    "lookat": (0.3, 0.8, 0.4),
+    "face": (0.3, 0.7, 0.7),
+    "moveto": (0.7, 0.4, 0.8),
+    "expression": (0.9, 0.4, 0.7),
+}
+DEFAULT_COLOR = (0.5, 0.5, 0.5)
+
+
+def import_choreo_scene(scene: ChoreoScene, context: Context) -> None:
+    """Add the events of a choreography scene to the sequencer of the current scene,
+    as a strip for each timed event on the channel of its actor, and a timeline marker
+    at the start of every event."""
+    blender_scene = context.scene
+    fps = blender_scene.render.fps / blender_scene.render.fps_base
+    frame_start = blender_scene.frame_current
+
+    sequence_editor = blender_scene.sequence_editor_create()
+    # each actor gets a channel above the existing strips
+    first_channel = 1 + max((s.channel for s in sequence_editor.sequences_all), default=0)
+    actors = scene.actors()
+    channels = {actor: first_channel + i for i, actor in enumerate(actors)}
+    actorless_channel = first_channel + len(actors)
+
+    last_frame = frame_start
+
+    for event in scene.events():
+        actor = event.actor()
+        label = f"{event.kind()} {event.name()}"
+        if actor is not None:
+            label = f"{actor}: {label}"
+
+        start = frame_start + round(event.start() * fps)
+        blender_scene.timeline_markers.new(label, frame=start)
+
+        end = event.end()
+        if end is None:
+            last_frame = max(last_frame, start)
+            continue
+
+        end = max(frame_start + round(end * fps), start + 1)
+        last_frame = max(last_frame, end)
+
+        strip = sequence_editor.sequences.new_effect(
+            name=label,
+            type="COLOR",
+            channel=channels.get(actor, actorless_channel),
+            frame_start=start,
+            frame_end=end,
+        )
+        strip.color = EVENT_COLORS.get(event.kind(), DEFAULT_COLOR)
+        strip["choreo_scene"] = scene.name()
+        strip["parameter"] = event.parameter()
+        strip["parameter2"] = event.parameter2()
+
+    blender_scene.frame_end = max(blender_scene.frame_end, last_frame)
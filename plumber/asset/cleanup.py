@@ -0,0 +1,119 @@
+from math import radians
+from typing import Dict, FrozenSet, List, Tuple
+
+import bmesh
+import bpy
+from bpy.types import Context, Object
+
+from .brush import DISPLACEMENT_ATTRIBUTE
+
+
+# in Source units, the geometry isn't scaled yet
+WELD_DISTANCE = 0.01
+SEAM_PRECISION = 2
+DISSOLVE_ANGLE = radians(0.1)
+
+CLEANUP_CLASSES = {"worldspawn", "func_detail"}
+
+
+def cleanup_decompiled(context: Context, brushes: List[Tuple[str, Object]]) -> None:
+    """Cleans up world brushes of decompiled maps.
+
+    World and func_detail geometry is joined into one object, then the seams of split
+    displacements are welded, the faces hidden between touching brushes are removed and
+    split coplanar faces are dissolved back together. The importer already recombines
+    displacements within each object by stitching them.
+    """
+
+    objects = [
+        obj
+        for class_name, obj in brushes
+        if class_name in CLEANUP_CLASSES and obj.type == "MESH"
+    ]
+
+    if not objects:
+        return
+
+    # prefer joining into the world geometry
+    objects.sort(key=lambda obj: not obj.name.startswith("worldspawn"))
+    target = objects[0]
+
+    if len(objects) > 1:
+        join_objects(context, target, objects)
+
+    mesh = target.data
+    bm = bmesh.new()
+    bm.from_mesh(mesh)
+
+    weld_displacements(bm)
+    remove_seam_faces(bm)
+    bmesh.ops.dissolve_limit(
+        bm,
+        angle_limit=DISSOLVE_ANGLE,
+        verts=bm.verts,
+        edges=bm.edges,
+        delimit={"MATERIAL", "UV"},
+    )
+
+    bm.to_mesh(mesh)
+    bm.free()
+
+    attribute = mesh.attributes.get(DISPLACEMENT_ATTRIBUTE)
+    if attribute is not None:
+        mesh.attributes.remove(attribute)
+
+    mesh.update()
+
+
+def join_objects(context: Context, target: Object, objects: List[Object]) -> None:
+    view_layer = context.view_layer
+
+    for obj in view_layer.objects.selected:
+        obj.select_set(False)
+
+    for obj in objects:
+        obj.select_set(True)
+
+    view_layer.objects.active = target
+    bpy.ops.object.join()
+    target.select_set(False)
+
+
+def weld_displacements(bm: bmesh.types.BMesh) -> None:
+    """Welds the vertices of displacement faces, closing the seams of displacements
+    split between the joined objects. Other faces are left alone, since welding them
+    would merge the vertices of unrelated brushes.
+    """
+
+    layer = bm.faces.layers.bool.get(DISPLACEMENT_ATTRIBUTE)
+    if layer is None:
+        return
+
+    verts = {vert for face in bm.faces if face[layer] for vert in face.verts}
+
+    if verts:
+        bmesh.ops.remove_doubles(bm, verts=list(verts), dist=WELD_DISTANCE)
+
+
+def remove_seam_faces(bm: bmesh.types.BMesh) -> None:
+    """Removes faces which have all of their vertices at the same positions as
+    another face.
+
+    Such faces are left where two brushes touch, and are never visible. The faces are
+    compared by position, since the vertices of different brushes aren't welded.
+    """
+
+    faces_by_verts: Dict[FrozenSet[Tuple[float, ...]], List[bmesh.types.BMFace]] = {}
+
+    for face in bm.faces:
+        key = frozenset(
+            tuple(round(c, SEAM_PRECISION) for c in vert.co) for vert in face.verts
+        )
+        faces_by_verts.setdefault(key, []).append(face)
+
+    seam_faces = [
+        face for faces in faces_by_verts.values() if len(faces) > 1 for face in faces
+    ]
+
+    if seam_faces:
+        bmesh.ops.delete(bm, geom=seam_faces, context="FACES")
@@ -0,0 +1,35 @@
+import bpy
+from bpy.types import Collection
+
+from .utils import truncate_name
+from ..plumber import Cubemap
+
+
+def import_cubemap(cubemap: Cubemap, collection: Collection) -> None:
+    name = f"env_cubemap_{cubemap.index()}"
+
+    # renamed from CUBE in Blender 4.1
+    probe = bpy.data.lightprobes.new(name, "SPHERE")
+    obj = bpy.data.objects.new(name, object_data=probe)
+    obj.location = cubemap.position()
+    obj["cubemap_size"] = cubemap.size()
+
+    if cubemap.has_image():
+        format = cubemap.format()
+        image_data = bpy.data.images.new(
+            truncate_name(f"{name}.{format}"), cubemap.width(), cubemap.height()
+        )
+
+        if format == "exr":
+            image_data.file_format = "OPEN_EXR"
+        else:
+            image_data.file_format = "TARGA_RAW"
+
+        image_data.source = "FILE"
+        bytes = cubemap.bytes()
+        image_data.pack(data=bytes, data_len=len(bytes))
+
+        # usable as an environment texture for lighting the area around the cubemap
+        obj["cubemap_image"] = image_data
+
+    collection.objects.link(obj)
@@ -0,0 +1,64 @@
+from typing import Dict, List, Tuple
+
+import bpy
+from bpy.types import Collection
+
+from .model import get_materials
+from .utils import truncate_name
+from ..plumber import DetailScatter, DetailSprites
+
+
+def import_detail_sprites(
+    sprites: DetailSprites, collection: Collection, name: str = "detail_sprites"
+) -> None:
+    mesh = bpy.data.meshes.new(name)
+
+    vertices = sprites.vertices()
+    mesh.vertices.add(len(vertices) // 3)
+    mesh.loops.add(sprites.loops_len())
+    mesh.polygons.add(sprites.polygons_len())
+    mesh.vertices.foreach_set("co", vertices)
+    mesh.polygons.foreach_set("loop_total", sprites.polygon_loop_totals())
+    mesh.polygons.foreach_set("loop_start", sprites.polygon_loop_starts())
+    mesh.polygons.foreach_set("vertices", sprites.polygon_vertices())
+    mesh.update()
+    uv_layer = mesh.uv_layers.new()
+    uv_layer.data.foreach_set("uv", sprites.loop_uvs())
+
+    mesh.materials.append(get_materials([sprites.material()])[0])
+
+    obj = bpy.data.objects.new(name, object_data=mesh)
+    obj.scale = sprites.scale()
+    collection.objects.link(obj)
+
+
+def import_detail_scatter(scatter: DetailScatter, collection: Collection) -> None:
+    sprites = scatter.sprites()
+    if sprites is not None:
+        import_detail_sprites(sprites, collection, "detail_scatter_sprites")
+
+    points: Dict[str, List[Tuple[List[float], List[float], int]]] = {}
+    for model, position, rotation, side_id in zip(
+        scatter.model_names(),
+        scatter.model_positions(),
+        scatter.model_rotations(),
+        scatter.model_side_ids(),
+    ):
+        points.setdefault(model, []).append((position, rotation, side_id))
+
+    # the models are left for instancing, with a point for each placement
+    for model, placements in points.items():
+        model_name = truncate_name(f"detail/{model}")
+        mesh = bpy.data.meshes.new(model_name)
+        mesh.vertices.add(len(placements))
+        mesh.vertices.foreach_set("co", [c for p, _, _ in placements for c in p])
+
+        rotations = mesh.attributes.new("rotation", "FLOAT_VECTOR", "POINT")
+        rotations.data.foreach_set("vector", [c for _, r, _ in placements for c in r])
+
+        side_ids = mesh.attributes.new("side_id", "INT", "POINT")
+        side_ids.data.foreach_set("value", [s for _, _, s in placements])
+
+        obj = bpy.data.objects.new(model_name, object_data=mesh)
+        obj["model"] = model
+        collection.objects.link(obj)
@@ -0,0 +1,37 @@
+from bpy.types import Object
+
+# viewport colors of the kinds of gameplay markers
+MARKER_COLORS = {
+    "player_start": (0.8, 0.8, 0.8, 1.0),
+    "terrorist_spawn": (0.9, 0.55, 0.1, 1.0),
+    "counterterrorist_spawn": (0.15, 0.35, 0.9, 1.0),
+    "hostage": (0.9, 0.85, 0.2, 1.0),
+    "hostage_rescue": (0.2, 0.8, 0.3, 1.0),
+    "bombsite": (0.9, 0.1, 0.1, 1.0),
+}
+
+# empty display types of point markers, spawns showing the direction they face
+MARKER_EMPTY_DISPLAY_TYPES = {
+    "player_start": "ARROWS",
+    "terrorist_spawn": "ARROWS",
+    "counterterrorist_spawn": "ARROWS",
+    "hostage": "SPHERE",
+}
+
+
+def mark_gameplay_object(obj: Object, marker: str, label: str, id: int) -> None:
+    """Label an imported object of an entity marking a gameplay location.
+    Volumes are displayed as wireframes and hidden from renders."""
+    obj.name = f"{label} {id}"
+    obj.show_name = True
+    obj["gameplay_marker"] = marker
+
+    color = MARKER_COLORS.get(marker)
+    if color is not None:
+        obj.color = color
+
+    if obj.type == "EMPTY":
+        obj.empty_display_type = MARKER_EMPTY_DISPLAY_TYPES.get(marker, "CUBE")
+    else:
+        obj.display_type = "WIRE"
+        obj.hide_render = True
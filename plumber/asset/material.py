@@ -1,20 +1,30 @@
-from typing import List
+from typing import Iterable, List, Optional, Sequence
 
 import bpy
-from bpy.types import ShaderNode
+from bpy.types import Image, NodeSocket, NodeTree, ShaderNode
 
 from .utils import truncate_name
-from ..plumber import Material, Texture, TextureRef
+from ..plumber import (
+    AnimatedVec,
+    BuiltNode,
+    BuiltNodeGroup,
+    Material,
+    NodeTreeRef,
+    Texture,
+    TextureRef,
+)
 
 
 FORMAT_MAP = {
     ".tga": "TARGA_RAW",
     ".png": "PNG",
+    ".exr": "OPEN_EXR",
 }
 
-NODE_INPUT_SOCKET_MAP = {
-    "Specular": "Specular IOR Level",
-    "Emission": "Emission Color",
+GROUP_SOCKET_TYPES = {
+    "VALUE": "NodeSocketFloat",
+    "VECTOR": "NodeSocketVector",
+    "RGBA": "NodeSocketColor",
 }
 
 
@@ -26,12 +36,65 @@ def import_texture(texture: Texture) -> None:
     if image_data is None:
         width = texture.width()
         height = texture.height()
-        image_data = bpy.data.images.new(texture_name, width, height, alpha=True)
-        image_data.file_format = FORMAT_MAP[format_ext]
-        image_data.source = "FILE"
-        bytes = texture.bytes()
-        image_data.pack(data=bytes, data_len=len(bytes))
+        image_data = bpy.data.images.new(
+            texture_name,
+            width,
+            height,
+            alpha=True,
+            float_buffer=texture.float_buffer(),
+        )
+
+        if texture.is_raw():
+            pixels = memoryview(texture.bytes()).cast("f")
+            image_data.pixels.foreach_set(pixels)
+            # generated images are lost on save unless packed
+            image_data.pack()
+        else:
+            image_data.file_format = FORMAT_MAP[format_ext]
+            image_data.source = "FILE"
+            bytes = texture.bytes()
+            image_data.pack(data=bytes, data_len=len(bytes))
+
         image_data.alpha_mode = "CHANNEL_PACKED"
+        image_data["path_id"] = texture.name() + format_ext
+
+        original_size = texture.original_size()
+        if original_size != [width, height]:
+            image_data["original_size"] = original_size
+
+
+def import_texture_material(texture: Texture) -> None:
+    """Import a texture with a simple material of the same name,
+    for textures which aren't referenced by any vmt, like the ones embedded in GoldSrc models."""
+    import_texture(texture)
+
+    material_name = truncate_name(texture.name())
+    if bpy.data.materials.get(material_name) is not None:
+        return
+
+    material_data = bpy.data.materials.new(material_name)
+    material_data["path_id"] = texture.name()
+    material_data.use_nodes = True
+    # cut out like the alpha tested materials built by the importer,
+    # blend_method was replaced by surface_render_method in Blender 4.2
+    if hasattr(material_data, "surface_render_method"):
+        material_data.surface_render_method = "DITHERED"
+        material_data.use_transparent_shadow = True
+    else:
+        material_data.blend_method = "HASHED"
+        material_data.shadow_method = "HASHED"
+
+    nt = material_data.node_tree
+    bsdf = nt.nodes["Principled BSDF"]
+
+    image_node: ShaderNode = nt.nodes.new("ShaderNodeTexImage")
+    image_node.image = bpy.data.images.get(
+        truncate_name(texture.name() + texture.format_ext())
+    )
+    image_node.location = (-300, 300)
+
+    nt.links.new(image_node.outputs["Color"], bsdf.inputs["Base Color"])
+    nt.links.new(image_node.outputs["Alpha"], bsdf.inputs["Alpha"])
 
 
 def import_material(material: Material) -> None:
@@ -42,9 +105,15 @@ def import_material(material: Material) -> None:
         material_data = bpy.data.materials.new(material_name)
         material_data["path_id"] = material.name()
 
+    material_data["tool_material"] = material.tool_material()
+    # editor metadata like %keywords, for tagging the materials with scripts
+    material_data["plumber_metadata"] = material.metadata()
+
     material_data.use_nodes = True
     nt = material_data.node_tree
     nt.nodes.clear()
+    # drivers of the removed nodes
+    nt.animation_data_clear()
 
     out_node = nt.nodes.new("ShaderNodeOutputMaterial")
     out_node.location = (300, 0)
@@ -55,9 +124,58 @@ def import_material(material: Material) -> None:
     for property, value in built_data.properties().items():
         setattr(material_data, property, resolve_value(value, texture_ext))
 
+    # shared node groups are only created once, so manual edits apply to every material
+    for group_name, group in built_data.node_groups().items():
+        if bpy.data.node_groups.get(group_name) is None:
+            import_node_group(group_name, group)
+
+    built_nodes = import_nodes(nt, built_data.nodes(), texture_ext)
+
+    shader_node = built_nodes[-1]
+
+    nt.links.new(shader_node.outputs["BSDF"], out_node.inputs["Surface"])
+
+    displacement = built_data.displacement()
+    if displacement is not None:
+        target_node = built_nodes[displacement.node_index()]
+        target_socket = target_node.outputs[displacement.socket()]
+        nt.links.new(target_socket, out_node.inputs["Displacement"])
+
+        # moved out of the cycles settings in Blender 4.1
+        if hasattr(material_data, "displacement_method"):
+            material_data.displacement_method = "BOTH"
+        else:
+            material_data.cycles.displacement_method = "BOTH"
+
+    volume = built_data.volume()
+    if volume is not None:
+        target_node = built_nodes[volume.node_index()]
+        target_socket = target_node.outputs[volume.socket()]
+        nt.links.new(target_socket, out_node.inputs["Volume"])
+
+    for property, value in built_data.custom_properties().items():
+        resolved = resolve_value(value, texture_ext)
+        if resolved is not None:
+            material_data[property] = resolved
+
+    for texture_name, color_space in built_data.texture_color_spaces().items():
+        image_name = truncate_name(texture_name + texture_ext)
+        image = bpy.data.images[image_name]
+        image.colorspace_settings.name = color_space
+
+    # used by the bake preset to know how each texture is routed
+    material_data["plumber_texture_roles"] = {
+        truncate_name(texture_name + texture_ext): ",".join(sorted(roles))
+        for texture_name, roles in built_data.texture_roles().items()
+    }
+
+
+def import_nodes(
+    nt: NodeTree, nodes: Iterable[BuiltNode], texture_ext: str
+) -> List[ShaderNode]:
     built_nodes: List[ShaderNode] = []
 
-    for node in built_data.nodes():
+    for node in nodes:
         built_node = nt.nodes.new(node.blender_id())
         built_node.location = node.position()
 
@@ -65,35 +183,107 @@ def import_material(material: Material) -> None:
             setattr(built_node, property, resolve_value(value, texture_ext))
 
         for socket, value in node.socket_values().items():
-            socket = resolve_input_socket(socket)
-            built_node.inputs[socket].default_value = resolve_value(value, texture_ext)
+            if isinstance(value, AnimatedVec):
+                animate_socket(built_node.inputs[socket], value)
+            else:
+                built_node.inputs[socket].default_value = resolve_value(
+                    value, texture_ext
+                )
 
         for socket, link in node.socket_links().items():
             target_node: ShaderNode = built_nodes[link.node_index()]
             target_socket = target_node.outputs[link.socket()]
 
-            socket = resolve_input_socket(socket)
             nt.links.new(built_node.inputs[socket], target_socket)
 
         built_nodes.append(built_node)
 
-    shader_node = built_nodes[-1]
+    return built_nodes
 
-    nt.links.new(shader_node.outputs["BSDF"], out_node.inputs["Surface"])
 
-    for texture_name, color_space in built_data.texture_color_spaces().items():
-        image_name = truncate_name(texture_name + texture_ext)
-        image = bpy.data.images[image_name]
-        image.colorspace_settings.name = color_space
+def import_node_group(name: str, group: BuiltNodeGroup) -> None:
+    nt = bpy.data.node_groups.new(name, "ShaderNodeTree")
+    built_nodes = import_nodes(nt, group.nodes(), "")
+
+    inputs = [
+        (input_name, built_nodes[target.node_index()].inputs[target.socket()])
+        for input_name, target in group.inputs()
+    ]
+    outputs = [
+        (output_name, built_nodes[source.node_index()].outputs[source.socket()])
+        for output_name, source in group.outputs()
+    ]
+
+    # the sockets are created before the group input and output nodes, which copy them
+    created = set()
+    for input_name, socket in inputs:
+        if input_name not in created:
+            new_group_socket(nt, input_name, "INPUT", socket)
+            created.add(input_name)
+
+    for output_name, socket in outputs:
+        new_group_socket(nt, output_name, "OUTPUT", socket)
+
+    x_min = min((node.location[0] for node in built_nodes), default=0)
+    x_max = max((node.location[0] + node.width for node in built_nodes), default=0)
+
+    in_node = nt.nodes.new("NodeGroupInput")
+    in_node.location = (x_min - 200, 0)
+
+    out_node = nt.nodes.new("NodeGroupOutput")
+    out_node.location = (x_max + 50, 0)
+
+    for input_name, socket in inputs:
+        nt.links.new(in_node.outputs[input_name], socket)
+
+    for output_name, socket in outputs:
+        nt.links.new(socket, out_node.inputs[output_name])
+
+
+def new_group_socket(nt: NodeTree, name: str, in_out: str, socket: NodeSocket) -> None:
+    """Add a socket like `socket` to the interface of a node group.
+    Also used by the material scripts."""
+    socket_type = GROUP_SOCKET_TYPES.get(socket.type, "NodeSocketFloat")
+
+    # replaced by the interface in Blender 4.0
+    if hasattr(nt, "interface"):
+        nt.interface.new_socket(name, in_out=in_out, socket_type=socket_type)
+    elif in_out == "INPUT":
+        nt.inputs.new(socket_type, name)
+    else:
+        nt.outputs.new(socket_type, name)
+
+
+def animate_socket(socket: NodeSocket, value: AnimatedVec) -> None:
+    animate(socket, value.value(), value.rate())
+
+
+def animate(socket: NodeSocket, value: Sequence[float], rate: Sequence[float]) -> None:
+    """Set the value of a socket, changing at a constant rate per second.
+    Also used by the material scripts."""
+    socket.default_value = value
+
+    render = bpy.context.scene.render
+    fps = render.fps / render.fps_base
+
+    # simple expressions are evaluated without enabling scripts
+    for index, (start, change) in enumerate(zip(value, rate)):
+        if change != 0:
+            fcurve = socket.driver_add("default_value", index)
+            fcurve.driver.expression = f"{start} + {change} * frame / {fps}"
+
+
+def find_image(name: str) -> Optional[Image]:
+    """Find an imported texture by its name with the extension.
+    Also used by the material scripts."""
+    return bpy.data.images.get(truncate_name(name))
 
 
 def resolve_value(value, texture_ext: str):
     if isinstance(value, TextureRef):
-        texture_name = truncate_name(value.path() + texture_ext)
-        return bpy.data.images.get(texture_name)
-
-    return value
+        return find_image(value.path() + texture_ext)
 
+    if isinstance(value, NodeTreeRef):
+        return bpy.data.node_groups.get(value.name())
 
-def resolve_input_socket(socket: str):
-    return NODE_INPUT_SOCKET_MAP.get(socket, socket)
+    return value
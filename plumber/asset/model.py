@@ -1,4 +1,4 @@
-from typing import Dict, List, Optional
+from typing import Dict, List, Optional, Tuple
 
 import bpy
 from bpy.types import (
@@ -15,7 +15,9 @@ from mathutils import Euler, Vector, Matrix
 from .utils import find_armature_modifier, get_unknown_material, truncate_name
 from ..plumber import (
     BoneRestData,
+    Hitbox,
     LoadedAnimation,
+    LoadedAttachment,
     LoadedBone,
     LoadedMesh,
     Model,
@@ -26,12 +28,19 @@ from ..plumber import (
 
 class ModelState:
     def __init__(
-        self, model_obj: Object, children: List[Object], collection: Collection
+        self,
+        model_obj: Object,
+        children: List[Object],
+        collection: Collection,
+        skins: List[List[Material]],
     ) -> None:
         self.object = model_obj
         self.children = children
         self.used = False
         self.collection = collection
+        self.skins = skins
+        # copies with the skin and bodygroups of each prop signature applied
+        self.variants: Dict[str, Tuple[Object, List[Object]]] = {}
 
 
 class ModelTracker:
@@ -55,22 +64,18 @@ class ModelTracker:
                 collection, model_name, bones, rest_positions, bone_names
             )
 
-            animations = model.animations()
+            # sequences are what the game plays, fall back to the raw animations
+            animations = model.sequences() or model.animations()
             for animation in animations:
                 import_animation(parent_obj, bone_names, animation)
 
-        bl_materials = []
-        for material in model.materials():
-            if material is None:
-                material_data = get_unknown_material()
-            else:
-                material_original_name = material
-                material = truncate_name(material)
-                material_data = bpy.data.materials.get(material)
-                if material_data is None:
-                    material_data = bpy.data.materials.new(material)
-                    material_data["path_id"] = material_original_name
-            bl_materials.append(material_data)
+        bl_materials = get_materials(model.materials())
+
+        skins = []
+        for skin in range(1, model.skin_count()):
+            skin_materials = model.materials_for_skin(skin)
+            if skin_materials is not None:
+                skins.append(get_materials(skin_materials))
 
         meshes = model.meshes()
 
@@ -78,10 +83,26 @@ class ModelTracker:
             parent_obj = bpy.data.objects.new(model_name, object_data=None)
             collection.objects.link(parent_obj)
 
+        bodygroups = model.bodygroups()
+        if parent_obj is not None and bodygroups:
+            parent_obj["bodygroups"] = [len(models) for _, models in bodygroups]
+
         for mesh in meshes:
+            is_collision = mesh.is_collision()
             mesh_obj = import_mesh(
-                collection, model_name, bl_materials, mesh, bones if bones else None
+                collection,
+                model_name,
+                [] if is_collision else bl_materials,
+                mesh,
+                bones if bones else None,
             )
+            if is_collision:
+                mesh_obj.display_type = "WIRE"
+                mesh_obj.hide_render = True
+
+            bodygroup = mesh.bodygroup()
+            if bodygroup is not None:
+                mesh_obj["bodygroup"] = bodygroup
             if parent_obj is not None:
                 mesh_obj.parent = parent_obj
                 if parent_obj.type == "ARMATURE":
@@ -94,18 +115,58 @@ class ModelTracker:
                 # this only gets called if there is 1 mesh
                 parent_obj = mesh_obj
 
+        attachments = model.attachments()
+        hitbox_sets = model.hitbox_sets()
+        if (attachments or hitbox_sets) and parent_obj is None:
+            parent_obj = bpy.data.objects.new(model_name, object_data=None)
+            collection.objects.link(parent_obj)
+
+        for attachment in attachments:
+            attachment_obj = import_attachment(
+                collection, model_name, parent_obj, attachment
+            )
+            children.append(attachment_obj)
+
+        for set_name, hitboxes in hitbox_sets:
+            for hitbox in hitboxes:
+                hitbox_obj = import_hitbox(
+                    collection, model_name, parent_obj, set_name, hitbox
+                )
+                children.append(hitbox_obj)
+
+        if parent_obj is not None:
+            # lets missing material directories be found after the import
+            parent_obj["material_bindings"] = {
+                str(binding.slot()): {
+                    "name": binding.name(),
+                    "status": binding.status(),
+                    "path": binding.path() or "",
+                }
+                for binding in model.material_bindings()
+            }
+
         self.imported_objects[original_name.lower()] = ModelState(
-            parent_obj, children, collection
+            parent_obj, children, collection, skins
         )
 
+    def has_variant(self, model_name: str, signature: str) -> bool:
+        model_state = self.imported_objects.get(model_name.lower())
+        return model_state is not None and signature in model_state.variants
+
     def get_model_copy(
-        self, model_name: str, collection: Collection
+        self, model_name: str, collection: Collection, signature: str
     ) -> Optional[Object]:
+        """Get an object of the model for a prop. Props with the same signature are
+        copied from the first one, so their skin and bodygroups are already applied."""
         model_state = self.imported_objects.get(model_name.lower())
 
         if model_state is None:
             return None
 
+        variant = model_state.variants.get(signature)
+        if variant is not None:
+            return copy_model(*variant, collection)[0]
+
         if not model_state.used:
             model_state.used = True
 
@@ -117,24 +178,30 @@ class ModelTracker:
                     model_state.collection.objects.unlink(child)
                     collection.objects.link(child)
 
+            model_state.variants[signature] = (
+                model_state.object,
+                model_state.children,
+            )
             return model_state.object
 
         # if the original object is already used, create a copy
-        parent_copy = model_state.object.copy()
-        collection.objects.link(parent_copy)
+        parent_copy, children = copy_model(
+            model_state.object, model_state.children, collection
+        )
+        model_state.variants[signature] = (parent_copy, children)
 
-        for child in model_state.children:
-            child_copy = child.copy()
-            child_copy.parent = parent_copy
+        return parent_copy
 
-            if parent_copy.type == "ARMATURE":
-                child_armature_mod = find_armature_modifier(child_copy)
-                if child_armature_mod is not None:
-                    child_armature_mod.object = parent_copy
+    def get_skin_materials(
+        self, model_name: str, skin: int
+    ) -> Optional[List[Material]]:
+        model_state = self.imported_objects.get(model_name.lower())
 
-            collection.objects.link(child_copy)
+        # skin 0 is the default material list
+        if model_state is None or not 0 < skin <= len(model_state.skins):
+            return None
 
-        return parent_copy
+        return model_state.skins[skin - 1]
 
     def get_last_imported(self) -> Optional[Object]:
         last = next(reversed(self.imported_objects.values()), None)
@@ -145,6 +212,46 @@ class ModelTracker:
         return last.object
 
 
+def copy_model(
+    parent: Object, children: List[Object], collection: Collection
+) -> Tuple[Object, List[Object]]:
+    parent_copy = parent.copy()
+    collection.objects.link(parent_copy)
+
+    child_copies = []
+    for child in children:
+        child_copy = child.copy()
+        child_copy.parent = parent_copy
+
+        if parent_copy.type == "ARMATURE":
+            child_armature_mod = find_armature_modifier(child_copy)
+            if child_armature_mod is not None:
+                child_armature_mod.object = parent_copy
+
+        collection.objects.link(child_copy)
+        child_copies.append(child_copy)
+
+    return parent_copy, child_copies
+
+
+def get_materials(materials: List[Optional[str]]) -> List[Material]:
+    bl_materials = []
+
+    for material in materials:
+        if material is None:
+            material_data = get_unknown_material()
+        else:
+            material_original_name = material
+            material = truncate_name(material)
+            material_data = bpy.data.materials.get(material)
+            if material_data is None:
+                material_data = bpy.data.materials.new(material)
+                material_data["path_id"] = material_original_name
+        bl_materials.append(material_data)
+
+    return bl_materials
+
+
 def import_mesh(
     collection: Collection,
     model_name: str,
@@ -198,6 +305,69 @@ def import_mesh(
     return mesh_obj
 
 
+def import_attachment(
+    collection: Collection,
+    model_name: str,
+    parent_obj: Object,
+    attachment: LoadedAttachment,
+) -> Object:
+    name = truncate_name(f"{model_name}/{attachment.name()}")
+    attachment_obj = bpy.data.objects.new(name, object_data=None)
+    attachment_obj.empty_display_type = "ARROWS"
+    attachment_obj.empty_display_size = 4
+    collection.objects.link(attachment_obj)
+
+    parent_to_bone(attachment_obj, parent_obj, attachment.parent_bone_index())
+
+    attachment_obj.location = attachment.position()
+    attachment_obj.rotation_euler = attachment.rotation()
+
+    return attachment_obj
+
+
+def import_hitbox(
+    collection: Collection,
+    model_name: str,
+    parent_obj: Object,
+    set_name: str,
+    hitbox: Hitbox,
+) -> Object:
+    hitbox_name = hitbox.name() or f"hitbox.{hitbox.bone_index()}"
+    name = truncate_name(f"{model_name}/{set_name}/{hitbox_name}")
+    hitbox_obj = bpy.data.objects.new(name, object_data=None)
+    hitbox_obj.empty_display_type = "CUBE"
+    hitbox_obj["hitbox_group"] = hitbox.group()
+    collection.objects.link(hitbox_obj)
+
+    parent_to_bone(hitbox_obj, parent_obj, hitbox.parent_bone_index())
+
+    bone_matrix = Matrix.Translation(Vector(hitbox.position()))
+    bone_matrix @= Euler(hitbox.rotation()).to_matrix().to_4x4()
+
+    min_bounds = Vector(hitbox.min())
+    max_bounds = Vector(hitbox.max())
+    center = (min_bounds + max_bounds) / 2
+    half_extents = (max_bounds - min_bounds) / 2
+
+    matrix = bone_matrix @ Matrix.Translation(center)
+    for axis in range(3):
+        matrix.col[axis] *= half_extents[axis]
+    hitbox_obj.matrix_basis = matrix
+
+    return hitbox_obj
+
+
+def parent_to_bone(obj: Object, parent_obj: Object, bone_index: Optional[int]) -> None:
+    obj.parent = parent_obj
+
+    if bone_index is not None and parent_obj.type == "ARMATURE":
+        bone = parent_obj.data.bones[bone_index]
+        obj.parent_type = "BONE"
+        obj.parent_bone = bone.name
+        # bone parenting is relative to the bone tail
+        obj.matrix_parent_inverse = Matrix.Translation((0, -bone.length, 0))
+
+
 def import_armature(
     collection: Collection,
     model_name: str,
@@ -237,6 +407,21 @@ def import_armature(
 
     bpy.ops.object.mode_set(mode="OBJECT")
 
+    for bone, bone_name in zip(bones, bone_names):
+        jiggle = bone.jiggle()
+        if jiggle is not None:
+            armature.pose.bones[bone_name]["jiggle"] = {
+                "flexible": jiggle.is_flexible(),
+                "length": jiggle.length(),
+                "tip_mass": jiggle.tip_mass(),
+                "yaw_stiffness": jiggle.yaw_stiffness(),
+                "yaw_damping": jiggle.yaw_damping(),
+                "pitch_stiffness": jiggle.pitch_stiffness(),
+                "pitch_damping": jiggle.pitch_damping(),
+                "along_stiffness": jiggle.along_stiffness(),
+                "along_damping": jiggle.along_damping(),
+            }
+
     for bone_i, rest_data in rest_positions.items():
         bone_name = bone_names[bone_i]
         bl_bone = armature.pose.bones[bone_name]
@@ -264,6 +449,15 @@ def import_animation(
     action = bpy.data.actions.new(name)
     animation_data.action = action
 
+    action.use_frame_range = True
+    action.frame_start = 1
+    action.frame_end = max(animation.frame_end(), 1)
+
+    activity = animation.activity()
+    if activity is not None:
+        action["activity"] = activity
+        action["activity_weight"] = animation.activity_weight()
+
     data = animation.data()
     looping = animation.looping()
 
@@ -0,0 +1,56 @@
+import re
+from typing import Iterable, Iterator, Tuple
+
+from bpy.types import Collection, Object
+from mathutils import Vector
+
+from .utils import truncate_name
+
+# suffix blender adds to names which are already taken
+_DUPLICATE_SUFFIX = re.compile(r"\.\d{3,}$")
+
+# custom properties holding absolute positions, which move with the map
+_POSITION_PROPERTIES = ("lighting_origin_position",)
+
+
+def _prefixed(name: str, prefix: str) -> str:
+    # maps imported before without a prefix may have taken the name,
+    # which is free again once prefixed
+    return truncate_name(prefix + _DUPLICATE_SUFFIX.sub("", name), 63)
+
+
+def _child_collections(collection: Collection) -> Iterator[Collection]:
+    for child in collection.children:
+        yield child
+        yield from _child_collections(child)
+
+
+def apply_namespace(
+    collection: Collection,
+    objects: Iterable[Object],
+    prefix: str,
+    offset: Tuple[float, float, float],
+) -> None:
+    """Prefixes the names of a map's objects and collections, and moves the map
+    by the offset, so several maps can be imported into one scene side by side.
+    Absolute positions stored in custom properties are moved too."""
+
+    objects = list(objects)
+
+    if prefix:
+        for child in _child_collections(collection):
+            child.name = _prefixed(child.name, prefix)
+
+        for obj in objects:
+            obj.name = _prefixed(obj.name, prefix)
+
+    if any(offset):
+        for obj in objects:
+            # children follow their parents
+            if obj.parent is None:
+                obj.location = Vector(obj.location) + Vector(offset)
+
+            for key in _POSITION_PROPERTIES:
+                position = obj.get(key)
+                if position is not None:
+                    obj[key] = (Vector(position) + Vector(offset))[:]
@@ -1,8 +1,8 @@
-from typing import List
+from typing import Dict, List
 import bpy
-from bpy.types import Collection, Object
+from bpy.types import Collection, Material, Object
 
-from .utils import find_armature_modifier
+from .utils import find_armature_modifier, truncate_name
 from .model import ModelTracker
 from ..plumber import LoadedProp, log_info
 
@@ -13,11 +13,12 @@ def import_prop(
     model_tracker: ModelTracker,
     apply_armatures: bool,
     armatures_to_apply: List[Object],
-) -> None:
+) -> Object:
     model_name = prop.model()
-    obj = model_tracker.get_model_copy(model_name, collection)
-    obj["path_id"] = model_name
-    obj["props"] = prop.properties()
+    signature = prop.signature()
+    # props of an already imported variant are copied with its skin and bodygroups
+    new_variant = not model_tracker.has_variant(model_name, signature)
+    obj = model_tracker.get_model_copy(model_name, collection, signature)
 
     name = f"{prop.class_name()}_{prop.id()}"
 
@@ -27,14 +28,94 @@ def import_prop(
     else:
         obj.name = name
 
+    obj["path_id"] = model_name
+    obj["props"] = prop.properties()
+    obj["signature"] = signature
+
     obj.location = prop.position()
     obj.rotation_euler = prop.rotation()
     obj.scale = prop.scale()
     obj.color = prop.color()
+    # the copies of a variant keep the properties of the prop they were copied from
+    set_optional(obj, "in_3d_skybox", True if prop.in_3d_skybox() else None)
+
+    if new_variant:
+        apply_bodygroups(obj, prop.body())
+
+    for shadow_obj in [obj, *obj.children]:
+        shadow_obj.visible_shadow = not prop.disable_shadows()
+
+    set_optional(obj, "fade_max_distance", prop.fade_max_distance())
+    set_optional(obj, "fade_min_distance", prop.fade_min_distance())
+
+    obj["solid"] = prop.solid()
+
+    set_optional(obj, "lighting_origin", prop.lighting_origin())
+    set_optional(obj, "lighting_origin_position", prop.lighting_origin_position())
+    set_optional(obj, "vertex_lighting", prop.vertex_lighting())
+
+    if new_variant:
+        skin_materials = model_tracker.get_skin_materials(model_name, prop.skin())
+        if skin_materials is not None:
+            apply_skin(obj, skin_materials)
+        else:
+            # the model may have been copied from a prop with another skin
+            reset_skin(obj)
 
     if apply_armatures and obj.type == "ARMATURE":
         armatures_to_apply.append(obj)
 
+    return obj
+
+
+def set_optional(obj: Object, key: str, value) -> None:
+    if value is None:
+        obj.pop(key, None)
+    else:
+        obj[key] = value
+
+
+def apply_bodygroups(obj: Object, body: int) -> None:
+    counts = obj.get("bodygroups")
+    if counts is None:
+        return
+
+    for child in obj.children:
+        bodygroup = child.get("bodygroup")
+        if bodygroup is None:
+            continue
+
+        part, model = bodygroup
+
+        # body is a mixed radix number, with one digit per body part
+        base = 1
+        for count in counts[:part]:
+            base *= count
+
+        visible = body // base % counts[part] == model
+        child.hide_viewport = not visible
+        child.hide_render = not visible
+
+
+def apply_skin(obj: Object, materials: List[Material]) -> None:
+    for mesh_obj in [obj, *obj.children]:
+        if mesh_obj.type != "MESH":
+            continue
+
+        # link the materials to the object, since the mesh data is shared between props
+        for slot, material in zip(mesh_obj.material_slots, materials):
+            slot.link = "OBJECT"
+            slot.material = material
+
+
+def reset_skin(obj: Object) -> None:
+    for mesh_obj in [obj, *obj.children]:
+        if mesh_obj.type != "MESH":
+            continue
+
+        for slot in mesh_obj.material_slots:
+            slot.link = "DATA"
+
 
 def apply_armatures(armatures_to_apply: List[Object]):
     if not armatures_to_apply:
@@ -85,3 +166,66 @@ def apply_armature(obj: Object):
         child.select_set(False)
 
     bpy.data.objects.remove(obj)
+
+
+def merge_ragdolls(groups: Dict[int, List[Object]]) -> List[Object]:
+    """Merge the armatures of each group of constrained props into one armature.
+    Returns the merged armatures."""
+    groups = [armatures for armatures in groups.values() if len(armatures) > 1]
+    if not groups:
+        return []
+
+    log_info(f"merging armatures of {len(groups)} constrained prop groups...")
+
+    selected_objects = bpy.context.selected_objects
+    active_object = bpy.context.view_layer.objects.active
+
+    for selected_obj in selected_objects:
+        selected_obj.select_set(False)
+
+    merged = [merge_armatures(armatures) for armatures in groups]
+
+    for selected_obj in selected_objects:
+        selected_obj.select_set(True)
+
+    bpy.context.view_layer.objects.active = active_object
+
+    log_info("armatures merged")
+
+    return merged
+
+
+def merge_armatures(armatures: List[Object]) -> Object:
+    for armature in armatures:
+        armature.select_set(True)
+        for child in armature.children:
+            child.select_set(True)
+
+    # the bones and vertex groups are renamed, so they can't be shared with other props
+    bpy.ops.object.make_single_user(type="SELECTED_OBJECTS", object=True, obdata=True)
+
+    children = []
+    for armature in armatures:
+        # prefixed so that the bones of each prop stay separate
+        for bone in armature.data.bones:
+            bone.name = truncate_name(f"{armature.name}/{bone.name}", 63)
+
+        for child in armature.children:
+            children.append((child, child.matrix_world.copy()))
+            child.select_set(False)
+
+    target = armatures[0]
+    bpy.context.view_layer.objects.active = target
+    bpy.ops.object.join()
+
+    for child, matrix_world in children:
+        child.parent = target
+        child.matrix_world = matrix_world
+
+        modifier = find_armature_modifier(child)
+        if modifier is not None:
+            modifier.object = target
+
+    target.select_set(False)
+
+    return target
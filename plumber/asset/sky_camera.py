@@ -1,5 +1,7 @@
+from typing import List, Tuple
+
 import bpy
-from bpy.types import Context, Collection
+from bpy.types import Context, Collection, Object
 
 from ..plumber import SkyCamera
 
@@ -16,3 +18,27 @@ def import_sky_camera(
 
     obj.select_set(True)
     context.view_layer.objects.active = obj
+
+
+def assemble_skybox(
+    transform: Tuple[Tuple[float, float, float], Tuple[float, float, float]],
+    objects: List[Object],
+    collection: Collection,
+) -> None:
+    """Moves the objects of the 3D skybox to their own collection, parented to an
+    empty which scales and positions them around the main map."""
+    skybox_collection = bpy.data.collections.new("3d_skybox")
+    collection.children.link(skybox_collection)
+
+    root = bpy.data.objects.new("3d_skybox", object_data=None)
+    root.location, root.scale = transform
+    skybox_collection.objects.link(root)
+
+    for obj in objects:
+        for moved in [obj, *obj.children_recursive]:
+            for users_collection in moved.users_collection:
+                users_collection.objects.unlink(moved)
+            skybox_collection.objects.link(moved)
+
+        if obj.parent is None:
+            obj.parent = root
@@ -1,15 +1,17 @@
+from typing import Union
+
 import bpy
-from bpy.types import Context, ShaderNode
+from bpy.types import Context, Image, ShaderNode
 
 from .utils import truncate_name
-from ..plumber import SkyEqui
+from ..plumber import SkyEqui, SkyFace, SkyFaces
 
 
-def import_sky_equi(sky_equi: SkyEqui, context: Context) -> None:
-    width = sky_equi.width()
-    height = sky_equi.height()
-    format = sky_equi.format()
-    image_name = truncate_name(f"{sky_equi.name()}.{format}")
+def import_sky_image(sky_image: Union[SkyEqui, SkyFace]) -> Image:
+    width = sky_image.width()
+    height = sky_image.height()
+    format = sky_image.format()
+    image_name = truncate_name(f"{sky_image.name()}.{format}")
 
     image_data = bpy.data.images.new(image_name, width, height)
 
@@ -19,9 +21,19 @@ def import_sky_equi(sky_equi: SkyEqui, context: Context) -> None:
         image_data.file_format = "TARGA_RAW"
 
     image_data.source = "FILE"
-    bytes = sky_equi.bytes()
+    bytes = sky_image.bytes()
     image_data.pack(data=bytes, data_len=len(bytes))
 
+    return image_data
+
+
+def import_sky_equi(sky_equi: SkyEqui, context: Context) -> None:
+    image_data = import_sky_image(sky_equi)
+
+    # only panoramas can be used as the world texture
+    if sky_equi.projection() != "EQUI":
+        return
+
     if context.scene.world is None:
         context.scene.world = bpy.data.worlds.new("World")
 
@@ -37,3 +49,8 @@ def import_sky_equi(sky_equi: SkyEqui, context: Context) -> None:
     tex_node.image = image_data
     tex_node.location = (-600, 0)
     nt.links.new(tex_node.outputs["Color"], bg_node.inputs["Color"])
+
+
+def import_sky_faces(sky_faces: SkyFaces) -> None:
+    for face in sky_faces.faces():
+        import_sky_image(face)
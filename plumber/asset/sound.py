@@ -0,0 +1,78 @@
+import os
+from tempfile import mkstemp
+from typing import Dict, List, Optional
+
+import bpy
+from bpy.types import Collection, Sound as BlenderSound, Speaker
+
+from ..plumber import Sound
+
+
+def _load_sound(path: str, data: bytes) -> BlenderSound:
+    # blender only loads sounds from files, so the data is packed from a temporary file
+    fd, temp_path = mkstemp(suffix=os.path.splitext(path)[1])
+    try:
+        with os.fdopen(fd, "wb") as f:
+            f.write(data)
+
+        sound = bpy.data.sounds.load(temp_path)
+        sound.pack()
+    finally:
+        os.remove(temp_path)
+
+    sound.name = path
+    return sound
+
+
+class SoundFiles:
+    """Loaded sound files by their full path, since Blender truncates the names.
+
+    The data of a file is only sent with the first sound playing it, which may arrive
+    after other sounds playing it, so speakers wait for the file if it's not loaded yet.
+    """
+
+    def __init__(self) -> None:
+        self.sounds: Dict[str, BlenderSound] = {}
+        self.waiting: Dict[str, List[Speaker]] = {}
+
+    def assign(self, speaker: Speaker, path: str, data: Optional[bytes]) -> None:
+        if data is not None:
+            sound = _load_sound(path, data)
+            self.sounds[path] = sound
+
+            for waiting in self.waiting.pop(path, []):
+                waiting.sound = sound
+
+        sound = self.sounds.get(path)
+        if sound is not None:
+            speaker.sound = sound
+        else:
+            self.waiting.setdefault(path, []).append(speaker)
+
+
+def import_sound(sound: Sound, collection: Collection, files: SoundFiles) -> None:
+    class_name = "env_soundscape" if sound.kind() == "SOUNDSCAPE" else "ambient_generic"
+    name = f"{class_name}_{sound.id()}"
+
+    speaker = bpy.data.speakers.new(name)
+    speaker.volume = sound.volume()
+
+    radius: Optional[float] = sound.radius()
+    if radius is not None:
+        speaker.distance_max = radius
+    else:
+        # sounds heard everywhere don't attenuate
+        speaker.attenuation = 0.0
+
+    path = sound.path()
+    if path is not None:
+        files.assign(speaker, path, sound.data())
+
+    obj = bpy.data.objects.new(name, object_data=speaker)
+    obj.location = sound.position()
+    obj.hide_render = not sound.enabled()
+
+    obj["sound_name"] = sound.name()
+    obj["props"] = sound.properties()
+
+    collection.objects.link(obj)
@@ -0,0 +1,82 @@
+from math import cos, pi, sin
+
+import bpy
+from bpy.types import Collection, Mesh
+
+from ..plumber import Sprite
+from .model import get_materials
+
+_BEAM_SEGMENTS = 16
+
+
+def _glow_card(name: str, size: float) -> Mesh:
+    half = size / 2
+    # the card faces the local x axis, like sprites facing their forward direction
+    vertices = [
+        (0.0, half, -half),
+        (0.0, -half, -half),
+        (0.0, -half, half),
+        (0.0, half, half),
+    ]
+
+    mesh = bpy.data.meshes.new(name)
+    mesh.from_pydata(vertices, [], [(0, 1, 2, 3)])
+
+    uv_layer = mesh.uv_layers.new()
+    for loop, uv in zip(uv_layer.data, [(0, 0), (1, 0), (1, 1), (0, 1)]):
+        loop.uv = uv
+
+    return mesh
+
+
+def _beam(name: str, length: float, width: float) -> Mesh:
+    vertices = []
+    # the beam widens towards its end
+    for x, radius in ((0.0, width / 2), (length, width)):
+        for i in range(_BEAM_SEGMENTS):
+            angle = 2 * pi * i / _BEAM_SEGMENTS
+            vertices.append((x, cos(angle) * radius, sin(angle) * radius))
+
+    faces = []
+    for i in range(_BEAM_SEGMENTS):
+        next_i = (i + 1) % _BEAM_SEGMENTS
+        faces.append((i, next_i, next_i + _BEAM_SEGMENTS, i + _BEAM_SEGMENTS))
+
+    mesh = bpy.data.meshes.new(name)
+    mesh.from_pydata(vertices, [], faces)
+    return mesh
+
+
+def import_sprite(sprite: Sprite, collection: Collection) -> None:
+    class_name = "point_spotlight" if sprite.kind() == "SPOTLIGHT" else "env_sprite"
+    name = f"{class_name}_{sprite.id()}"
+
+    mesh = _glow_card(name, sprite.size())
+    mesh.materials.append(get_materials([sprite.material()])[0])
+
+    obj = bpy.data.objects.new(name, object_data=mesh)
+    obj.location = sprite.position()
+    obj.rotation_euler = sprite.rotation()
+    obj.color = (*sprite.color(), sprite.alpha())
+    obj.hide_render = not sprite.enabled()
+
+    obj["render_mode"] = sprite.render_mode()
+    obj["hdr_color_scale"] = sprite.hdr_color_scale()
+    obj["glow_proxy_size"] = sprite.glow_proxy_size()
+    obj["props"] = sprite.properties()
+
+    collection.objects.link(obj)
+
+    beam_material = sprite.beam_material()
+    if beam_material is None:
+        return
+
+    beam_mesh = _beam(f"{name}_beam", sprite.beam_length(), sprite.beam_width())
+    beam_mesh.materials.append(get_materials([beam_material])[0])
+
+    beam_obj = bpy.data.objects.new(f"{name}_beam", object_data=beam_mesh)
+    beam_obj.parent = obj
+    beam_obj.color = obj.color
+    beam_obj.hide_render = obj.hide_render
+
+    collection.objects.link(beam_obj)
@@ -0,0 +1,27 @@
+import bpy
+from bpy.types import Collection
+
+from ..plumber import TrackPath
+
+
+def import_track_path(path: TrackPath, collection: Collection) -> None:
+    name = path.name() or f"{path.class_name()}_{path.id()}"
+
+    curve = bpy.data.curves.new(f"{name}_path", "CURVE")
+    curve.dimensions = "3D"
+
+    positions = path.point_positions()
+    spline = curve.splines.new("POLY")
+    spline.points.add(len(positions) - 1)
+    for point, position in zip(spline.points, positions):
+        point.co = (*position, 1.0)
+    spline.use_cyclic_u = path.closed()
+
+    obj = bpy.data.objects.new(f"{name}_path", object_data=curve)
+    obj["train"] = name
+    obj["speed"] = path.speed()
+    # a speed of 0 keeps the speed the train had before the point
+    obj["point_speeds"] = path.point_speeds()
+    obj["point_names"] = path.point_names()
+
+    collection.objects.link(obj)
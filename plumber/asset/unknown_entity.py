@@ -1,10 +1,10 @@
 import bpy
-from bpy.types import Collection
+from bpy.types import Collection, Object
 
 from ..plumber import UnknownEntity
 
 
-def import_unknown_entity(entity: UnknownEntity, collection: Collection) -> None:
+def import_unknown_entity(entity: UnknownEntity, collection: Collection) -> Object:
     name = f"{entity.class_name()}_{entity.id()}"
 
     obj = bpy.data.objects.new(name, object_data=None)
@@ -13,4 +13,36 @@ def import_unknown_entity(entity: UnknownEntity, collection: Collection) -> None
     obj.scale = entity.scale()
     obj["props"] = entity.properties()
 
+    connections = entity.connections()
+    if connections:
+        obj["connections"] = {
+            output: [
+                {
+                    "target": target,
+                    "input": input,
+                    "parameter": parameter,
+                    "delay": delay,
+                    "times_to_fire": times_to_fire,
+                }
+                for target, input, parameter, delay, times_to_fire in outputs
+            ]
+            for output, outputs in connections.items()
+        }
+
+    kind = entity.kind()
+    if kind is not None:
+        obj["entity_kind"] = kind
+        obj["prop_types"] = entity.property_types()
+
+    color = entity.color()
+    if color is not None:
+        obj.color = (*color, 1.0)
+
+    model = entity.model()
+    if model is not None:
+        obj["model"] = model
+        obj.empty_display_type = "CUBE"
+
     collection.objects.link(obj)
+
+    return obj
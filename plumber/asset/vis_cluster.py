@@ -0,0 +1,27 @@
+import bpy
+from bpy.types import Collection
+
+from ..plumber import VisCluster
+
+
+def import_vis_cluster(cluster: VisCluster, collection: Collection) -> None:
+    name = f"vis_cluster_{cluster.index()}"
+    mesh = bpy.data.meshes.new(name)
+
+    vertices = cluster.vertices()
+    mesh.vertices.add(len(vertices) // 3)
+    mesh.loops.add(cluster.loops_len())
+    mesh.polygons.add(cluster.polygons_len())
+    mesh.vertices.foreach_set("co", vertices)
+    mesh.polygons.foreach_set("loop_total", cluster.polygon_loop_totals())
+    mesh.polygons.foreach_set("loop_start", cluster.polygon_loop_starts())
+    mesh.polygons.foreach_set("vertices", cluster.polygon_vertices())
+    mesh.update()
+
+    obj = bpy.data.objects.new(name, object_data=mesh)
+    obj.scale = cluster.scale()
+    # only for inspecting the vis partitioning, so not rendered
+    obj.display_type = "WIRE"
+    obj.hide_render = True
+    obj["visible_clusters"] = cluster.visible_clusters()
+    collection.objects.link(obj)
@@ -0,0 +1,66 @@
+from math import pi
+
+import bpy
+from bpy.types import Collection
+
+from ..plumber import FogController, Precipitation, Wind
+
+
+def import_wind(wind: Wind, collection: Collection) -> None:
+    name = f"env_wind_{wind.id()}"
+
+    obj = bpy.data.objects.new(name, object_data=None)
+    obj.location = wind.position()
+    # the arrow points along the local z axis, so it's turned to the wind direction
+    obj.rotation_euler = (pi / 2, 0.0, wind.direction() + pi / 2)
+    obj.empty_display_type = "SINGLE_ARROW"
+
+    obj["min_speed"] = wind.min_speed()
+    obj["max_speed"] = wind.max_speed()
+    obj["min_gust_speed"] = wind.min_gust_speed()
+    obj["max_gust_speed"] = wind.max_gust_speed()
+    obj["min_gust_delay"] = wind.min_gust_delay()
+    obj["max_gust_delay"] = wind.max_gust_delay()
+    obj["gust_duration"] = wind.gust_duration()
+    obj["gust_direction_change"] = wind.gust_direction_change()
+    obj["props"] = wind.properties()
+
+    collection.objects.link(obj)
+
+
+def import_precipitation(precipitation: Precipitation, collection: Collection) -> None:
+    name = f"func_precipitation_{precipitation.id()}_data"
+
+    obj = bpy.data.objects.new(name, object_data=None)
+    obj.color = (*precipitation.color(), 1.0)
+
+    # the volume is imported as the brush of the same entity id
+    obj["brush_id"] = precipitation.id()
+    obj["kind"] = precipitation.kind()
+    obj["density"] = precipitation.density()
+    obj["props"] = precipitation.properties()
+
+    collection.objects.link(obj)
+
+
+def import_fog_controller(fog: FogController, collection: Collection) -> None:
+    name = f"env_fog_controller_{fog.id()}"
+
+    obj = bpy.data.objects.new(name, object_data=None)
+    obj.location = fog.position()
+    obj.color = (*fog.color(), 1.0)
+
+    obj["enabled"] = fog.enabled()
+    obj["fog_color"] = fog.color()
+    secondary_color = fog.secondary_color()
+    if secondary_color is not None:
+        obj["fog_color_secondary"] = secondary_color
+    obj["fog_start"] = fog.start()
+    obj["fog_end"] = fog.end()
+    obj["max_density"] = fog.max_density()
+    far_z = fog.far_z()
+    if far_z is not None:
+        obj["far_z"] = far_z
+    obj["props"] = fog.properties()
+
+    collection.objects.link(obj)
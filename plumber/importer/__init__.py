@@ -1,11 +1,17 @@
-from typing import Set
+from typing import Dict, Optional, Set, Tuple
 from os.path import basename, dirname
 
 import bpy
-from bpy.props import EnumProperty, BoolProperty, StringProperty
+from bpy.props import (
+    EnumProperty,
+    BoolProperty,
+    FloatProperty,
+    IntProperty,
+    StringProperty,
+)
 from bpy.types import Context, Operator, Panel, UILayout
 
-from ..plumber import FileSystem
+from ..plumber import FileSystem, filesystem_from_asset_path
 from ..preferences import AddonPreferences
 
 from .. import __package__ as ADDON_NAME
@@ -25,15 +31,44 @@ class ImporterOperatorProps:
         options={"HIDDEN"},
     )
 
+    detect_root: BoolProperty(
+        name="Detect asset root",
+        description="Use the asset directory structure containing the file instead of a game",
+        options={"HIDDEN", "SKIP_SAVE"},
+    )
+
 
 class ImporterOperator(Operator, ImporterOperatorProps):
     def get_game_fs(self, context: Context):
-        if self.game == "NONE":
+        preferences = context.preferences.addons[ADDON_NAME].preferences
+
+        if self.detect_root:
+            fs = filesystem_from_asset_path(self.filepath)
+        elif self.game == "NONE":
             return FileSystem.empty()
         else:
-            preferences = context.preferences.addons[ADDON_NAME].preferences
             game = preferences.games[int(self.game)]
-            return game.get_file_system()
+            fs = game.get_file_system()
+
+        if preferences.case_insensitive_dirs:
+            try:
+                fs = fs.with_case_insensitive_dirs()
+            except OSError as err:
+                self.report({"WARNING"}, f"could not index directories: {err}")
+
+        return fs
+
+    def get_texture_color_correction(self, context: Context) -> Dict[str, float]:
+        if self.detect_root or self.game == "NONE":
+            return {}
+
+        preferences = context.preferences.addons[ADDON_NAME].preferences
+        game = preferences.games[int(self.game)]
+
+        return {
+            "texture_brightness": game.texture_brightness,
+            "texture_gamma": game.texture_gamma,
+        }
 
     def get_threads_suggestion(self, context: Context) -> int:
         preferences = context.preferences.addons[ADDON_NAME].preferences
@@ -44,6 +79,9 @@ class ImporterOperator(Operator, ImporterOperatorProps):
         scene = context.scene
         return scene.render.fps / scene.render.fps_base
 
+    def get_target_version(self) -> Tuple[int, int]:
+        return bpy.app.version[:2]
+
     def invoke(self, context: Context, event) -> Set[str]:
         context.window_manager.fileselect_add(self)
         return {"RUNNING_MODAL"}
@@ -52,10 +90,23 @@ class ImporterOperator(Operator, ImporterOperatorProps):
         pass
 
 
-def update_recent_entries(context: Context, game: str, path: str):
+def update_recent_entries(
+    context: Context, game: str, path: str, imported: Optional[str] = None
+):
     if game == "NONE":
         return
 
+    file_browser = update_recent_entries.file_browser
+    if context.scene.plumber_browser.game == game:
+        file_browser = type(context.scene.plumber_browser).browser
+
+    # recent imports are persisted across sessions, the directories only in the scene
+    if imported is not None and file_browser is not None:
+        try:
+            file_browser.add_recent_import(imported)
+        except OSError as err:
+            print(f"[Plumber] [WARN] could not save recent imports: {err}")
+
     recent_entries = context.scene.plumber_recent_entries.get(game)
 
     if recent_entries is None:
@@ -77,6 +128,7 @@ def update_recent_entries(context: Context, game: str, path: str):
 
 
 update_recent_entries.browser_operator_entries = None
+update_recent_entries.file_browser = None
 
 
 def add_recent_entry(path: str, recent_entries):
@@ -97,7 +149,9 @@ class GameFileImporterOperator(
 ):
     def invoke(self, context: Context, event) -> Set[str]:
         if self.from_game_fs:
-            update_recent_entries(context, self.game, dirname(self.filepath))
+            update_recent_entries(
+                context, self.game, dirname(self.filepath), imported=self.filepath
+            )
             return context.window_manager.invoke_props_dialog(self)
         else:
             context.window_manager.fileselect_add(self)
@@ -143,10 +197,21 @@ class MaterialImporterOperatorProps:
         items=[
             ("Tga", "TGA", "Truevision TGA"),
             ("Png", "PNG", "Portable Network Graphigs"),
+            ("Exr", "EXR", "OpenEXR, keeps the range of HDR textures"),
+            ("Raw", "Raw", "Unencoded pixels, faster to import but larger when saved"),
         ],
         default="Png",
     )
 
+    texture_max_resolution: IntProperty(
+        name="Max texture resolution",
+        description="Downscale larger textures to reduce memory usage. 0 keeps the original resolution",
+        default=0,
+        min=0,
+        soft_max=8192,
+        subtype="PIXEL",
+    )
+
     texture_interpolation: EnumProperty(
         name="Texture interpolation",
         description="Interpolation type to use for image textures",
@@ -159,6 +224,50 @@ class MaterialImporterOperatorProps:
         default="Linear",
     )
 
+    height_maps: EnumProperty(
+        name="Height maps",
+        description="How to render the height maps of parallax materials",
+        items=[
+            ("Bump", "Bump", "Add the height to the normal of the surface"),
+            (
+                "Displacement",
+                "Displacement",
+                "Displace the surface, which is only rendered by Cycles",
+            ),
+        ],
+        default="Bump",
+    )
+
+    ssbump_height: BoolProperty(
+        name="Self-shadowing bump height",
+        description=(
+            "Derive a height map from self-shadowing bump maps, "
+            "which darkens some of the materials using them"
+        ),
+        default=False,
+    )
+
+    base_alpha: EnumProperty(
+        name="Base texture alpha",
+        description="How the alpha channel of base textures is interpreted",
+        items=[
+            ("Preserve", "Preserve", "Keep the alpha as an independent channel"),
+            ("Strip", "Strip", "Ignore the alpha, so that nothing becomes transparent"),
+            (
+                "Premultiplied",
+                "Premultiplied",
+                "The colors are already multiplied by the alpha, so they are divided by it "
+                "in the material, which avoids dark halos around transparent edges",
+            ),
+            (
+                "Auto",
+                "Auto",
+                "Strip the alpha if the material doesn't use it, preserve it otherwise",
+            ),
+        ],
+        default="Preserve",
+    )
+
     allow_culling: BoolProperty(
         name="Allow backface culling",
         description="Enable backface culling for materials which don't disable it",
@@ -171,6 +280,17 @@ class MaterialImporterOperatorProps:
         default=False,
     )
 
+    emission_strength: FloatProperty(
+        name="Emission strength",
+        description="Strength of the emission of self-illuminated materials",
+        default=1.0,
+        min=0.0,
+        soft_max=10.0,
+    )
+
+    def get_texture_max_resolution(self) -> Optional[int]:
+        return self.texture_max_resolution if self.texture_max_resolution != 0 else None
+
     @staticmethod
     def draw_props(
         layout: UILayout, operator: "MaterialImporterOperatorProps", context: Context
@@ -178,9 +298,14 @@ class MaterialImporterOperatorProps:
         layout.use_property_split = True
         layout.prop(operator, "simple_materials")
         layout.prop(operator, "texture_format")
+        layout.prop(operator, "texture_max_resolution")
         layout.prop(operator, "texture_interpolation")
+        layout.prop(operator, "height_maps")
+        layout.prop(operator, "ssbump_height")
+        layout.prop(operator, "base_alpha")
         layout.prop(operator, "allow_culling")
         layout.prop(operator, "editor_materials")
+        layout.prop(operator, "emission_strength")
 
 
 class MaterialToggleOperatorProps(MaterialImporterOperatorProps):
@@ -227,12 +352,39 @@ class PLUMBER_PT_importer_materials(Panel):
 
 class ModelImporterOperatorProps:
     import_animations: BoolProperty(name="Import animations", default=True)
+    resample_animations: BoolProperty(
+        name="Resample animations",
+        description="Interpolate animations onto whole frames at the scene frame rate, "
+        + "instead of keeping the original keyframes at subframe times",
+        default=False,
+    )
+    import_physics: BoolProperty(
+        name="Import collision",
+        description="Import the collision meshes from the model's .phy file",
+        default=False,
+    )
+    import_attachments: BoolProperty(
+        name="Import attachments",
+        description="Import the model's attachment points as empties",
+        default=False,
+    )
+    import_hitboxes: BoolProperty(
+        name="Import hitboxes",
+        description="Import the model's hitboxes as box empties",
+        default=False,
+    )
 
     @staticmethod
     def draw_props(
         layout: UILayout, operator: "ModelImporterOperatorProps", context: Context
     ):
         layout.prop(operator, "import_animations")
+        col = layout.column()
+        col.enabled = operator.import_animations
+        col.prop(operator, "resample_animations")
+        layout.prop(operator, "import_physics")
+        layout.prop(operator, "import_attachments")
+        layout.prop(operator, "import_hitboxes")
 
 
 from .vmf import (
@@ -245,8 +397,13 @@ from .vmf import (
     PLUMBER_PT_vmf_sky,
 )
 from .mdl import ImportMdl, PLUMBER_PT_mdl_main
+from .dmx import ImportDmx
 from .vmt import ImportVmt, PLUMBER_PT_vmt_main
 from .vtf import ImportVtf
+from .wad import ImportWad
+from .vcd import ImportVcd
+from .source2 import ImportVmat, ImportVtex
+from .path import ImportPath, PLUMBER_FH_import_path
 
 
 CLASSES = [
@@ -262,8 +419,15 @@ CLASSES = [
     PLUMBER_PT_mdl_main,
     ImportVmf,
     ImportMdl,
+    ImportDmx,
     ImportVmt,
     ImportVtf,
+    ImportWad,
+    ImportVcd,
+    ImportVmat,
+    ImportVtex,
+    ImportPath,
+    PLUMBER_FH_import_path,
 ]
 
 
@@ -0,0 +1,75 @@
+from typing import Set
+
+from bpy.types import Context
+from bpy.props import StringProperty, FloatProperty
+
+from . import (
+    GameFileImporterOperator,
+    GameFileImporterOperatorProps,
+    ImporterOperatorProps,
+    ModelImporterOperatorProps,
+)
+from ..asset import AssetCallbacks
+from ..plumber import Importer
+
+
+class ImportDmx(
+    GameFileImporterOperator,
+    ImporterOperatorProps,
+    GameFileImporterOperatorProps,
+    ModelImporterOperatorProps,
+):
+    """Import binary DMX model and animations, including Source Filmmaker sessions"""
+
+    bl_idname = "import_scene.plumber_dmx"
+    bl_label = "Import DMX"
+    bl_options = {"REGISTER", "UNDO"}
+
+    filename_ext = ".dmx"
+
+    filter_glob: StringProperty(
+        default="*.dmx",
+        options={"HIDDEN"},
+        maxlen=255,
+    )
+
+    scale: FloatProperty(
+        name="Scale",
+        default=0.01,
+        min=1e-6,
+        max=1e6,
+        soft_min=0.001,
+        soft_max=1.0,
+    )
+
+    def execute(self, context: Context) -> Set[str]:
+        fs = self.get_game_fs(context)
+        asset_callbacks = AssetCallbacks(context)
+
+        try:
+            importer = Importer(
+                fs,
+                asset_callbacks,
+                self.get_threads_suggestion(context),
+                target_version=self.get_target_version(),
+                target_fps=self.get_target_fps(context),
+                resample_animations=self.resample_animations,
+            )
+        except OSError as err:
+            self.report({"ERROR"}, f"could not open file system: {err}")
+            return {"CANCELLED"}
+
+        try:
+            importer.import_dmx(
+                self.filepath,
+                self.from_game_fs,
+                import_animations=self.import_animations,
+            )
+        except OSError as err:
+            self.report({"ERROR"}, f"could not import dmx: {err}")
+            return {"CANCELLED"}
+
+        imported_obj = asset_callbacks.model_tracker.get_last_imported()
+        imported_obj.scale = (self.scale, self.scale, self.scale)
+
+        return {"FINISHED"}
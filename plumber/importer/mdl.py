@@ -13,6 +13,9 @@ from . import (
 from ..asset import AssetCallbacks
 from ..plumber import Importer
 
+# longer sequence lists don't fit in the dialog
+MAX_PREVIEWED_SEQUENCES = 20
+
 
 class ImportMdl(
     GameFileImporterOperator,
@@ -44,6 +47,19 @@ class ImportMdl(
         soft_max=1.0,
     )
 
+    sequence_previews = []
+
+    def invoke(self, context: Context, event) -> Set[str]:
+        if self.from_game_fs:
+            try:
+                self.sequence_previews = self.get_game_fs(context).sequence_previews(
+                    self.filepath, True
+                )
+            except (OSError, ValueError) as err:
+                print(f"[Plumber] [WARN] could not preview sequences: {err}")
+
+        return super().invoke(context, event)
+
     def execute(self, context: Context) -> Set[str]:
         fs = self.get_game_fs(context)
         asset_callbacks = AssetCallbacks(context)
@@ -53,13 +69,24 @@ class ImportMdl(
                 fs,
                 asset_callbacks,
                 self.get_threads_suggestion(context),
+                target_version=self.get_target_version(),
                 import_materials=self.import_materials,
                 target_fps=self.get_target_fps(context),
+                resample_animations=self.resample_animations,
+                import_physics=self.import_physics,
+                import_attachments=self.import_attachments,
+                import_hitboxes=self.import_hitboxes,
                 simple_materials=self.simple_materials,
                 allow_culling=self.allow_culling,
                 editor_materials=self.editor_materials,
+                emission_strength=self.emission_strength,
                 texture_format=self.texture_format,
+                texture_max_resolution=self.get_texture_max_resolution(),
+                **self.get_texture_color_correction(context),
                 texture_interpolation=self.texture_interpolation,
+                height_maps=self.height_maps,
+                ssbump_height=self.ssbump_height,
+                base_alpha=self.base_alpha,
                 root_search=None if self.from_game_fs else (self.filepath, "models"),
             )
         except OSError as err:
@@ -88,6 +115,23 @@ class ImportMdl(
 
             self.layout.prop(self, "scale")
 
+            if self.import_animations and self.sequence_previews:
+                self.draw_sequence_previews()
+
+    def draw_sequence_previews(self):
+        box = self.layout.box()
+        box.label(text=f"Sequences ({len(self.sequence_previews)}):")
+
+        for preview in self.sequence_previews[:MAX_PREVIEWED_SEQUENCES]:
+            row = box.row()
+            row.label(text=preview.name(), icon="ACTION")
+            row.label(text=f"{preview.duration():.2f} s")
+            row.label(text=f"{len(preview.events())} events")
+
+        hidden = len(self.sequence_previews) - MAX_PREVIEWED_SEQUENCES
+        if hidden > 0:
+            box.label(text=f"and {hidden} more")
+
 
 class PLUMBER_PT_mdl_main(Panel):
     bl_space_type = "FILE_BROWSER"
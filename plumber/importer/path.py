@@ -0,0 +1,76 @@
+from typing import Set
+from os.path import join, splitext
+
+import bpy
+from bpy.props import CollectionProperty, StringProperty
+from bpy.types import Context, FileHandler, Operator, OperatorFileListElement
+
+# importer operators by the extension of the files they import
+PATH_IMPORTERS = {
+    ".mdl": "plumber_mdl",
+    ".vmt": "plumber_vmt",
+    ".vtf": "plumber_vtf",
+    ".wad": "plumber_wad",
+    ".dmx": "plumber_dmx",
+    ".vcd": "plumber_vcd",
+    ".vmat": "plumber_vmat",
+    ".vmat_c": "plumber_vmat",
+    ".vtex_c": "plumber_vtex",
+    ".vmf": "plumber_vmf",
+}
+
+
+def import_path(path: str) -> Set[str]:
+    """Import a loose asset file using the asset directory structure containing it,
+    so that its dependencies are found without configuring a game."""
+    importer = PATH_IMPORTERS.get(splitext(path)[1].lower())
+    if importer is None:
+        raise ValueError(f"unsupported file type: {path}")
+
+    operator = getattr(bpy.ops.import_scene, importer)
+    return operator(
+        "EXEC_DEFAULT",
+        filepath=path,
+        game="NONE",
+        from_game_fs=False,
+        detect_root=True,
+    )
+
+
+class ImportPath(Operator):
+    """Import Source Engine assets using the asset directory structure they are in"""
+
+    bl_idname = "import_scene.plumber_path"
+    bl_label = "Import Source assets"
+    bl_options = {"REGISTER", "UNDO"}
+
+    filepath: StringProperty(subtype="FILE_PATH", options={"HIDDEN", "SKIP_SAVE"})
+    directory: StringProperty(subtype="DIR_PATH", options={"HIDDEN", "SKIP_SAVE"})
+    files: CollectionProperty(
+        type=OperatorFileListElement, options={"HIDDEN", "SKIP_SAVE"}
+    )
+
+    def execute(self, context: Context) -> Set[str]:
+        if self.files and self.directory:
+            paths = [join(self.directory, file.name) for file in self.files]
+        else:
+            paths = [self.filepath]
+
+        for path in paths:
+            try:
+                import_path(path)
+            except (ValueError, OSError) as err:
+                self.report({"ERROR"}, f"could not import {path}: {err}")
+
+        return {"FINISHED"}
+
+
+class PLUMBER_FH_import_path(FileHandler):
+    bl_idname = "PLUMBER_FH_import_path"
+    bl_label = "Source assets"
+    bl_import_operator = "import_scene.plumber_path"
+    bl_file_extensions = ";".join(PATH_IMPORTERS)
+
+    @classmethod
+    def poll_drop(cls, context: Context) -> bool:
+        return context.area is not None and context.area.type == "VIEW_3D"
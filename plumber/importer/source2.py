@@ -0,0 +1,96 @@
+from typing import Set
+
+from bpy.types import Context
+from bpy.props import StringProperty
+
+from . import (
+    GameFileImporterOperator,
+    GameFileImporterOperatorProps,
+    ImporterOperatorProps,
+)
+from ..asset import AssetCallbacks
+from ..plumber import Importer
+
+
+class ImportVmat(
+    GameFileImporterOperator,
+    ImporterOperatorProps,
+    GameFileImporterOperatorProps,
+):
+    """Import Source 2 material source or compiled material"""
+
+    bl_idname = "import_scene.plumber_vmat"
+    bl_label = "Import VMAT"
+    bl_options = {"REGISTER", "UNDO"}
+
+    filename_ext = ".vmat"
+
+    filter_glob: StringProperty(
+        default="*.vmat;*.vmat_c",
+        options={"HIDDEN"},
+        maxlen=255,
+    )
+
+    def execute(self, context: Context) -> Set[str]:
+        fs = self.get_game_fs(context)
+
+        try:
+            importer = Importer(
+                fs,
+                AssetCallbacks(context),
+                self.get_threads_suggestion(context),
+                target_version=self.get_target_version(),
+            )
+        except OSError as err:
+            self.report({"ERROR"}, f"could not open file system: {err}")
+            return {"CANCELLED"}
+
+        try:
+            importer.import_vmat(self.filepath, self.from_game_fs)
+        except OSError as err:
+            self.report({"ERROR"}, f"could not import vmat: {err}")
+            return {"CANCELLED"}
+
+        return {"FINISHED"}
+
+
+class ImportVtex(
+    GameFileImporterOperator,
+    ImporterOperatorProps,
+    GameFileImporterOperatorProps,
+):
+    """Import Source 2 compiled texture"""
+
+    bl_idname = "import_scene.plumber_vtex"
+    bl_label = "Import VTEX"
+    bl_options = {"REGISTER", "UNDO"}
+
+    filename_ext = ".vtex_c"
+
+    filter_glob: StringProperty(
+        default="*.vtex_c",
+        options={"HIDDEN"},
+        maxlen=255,
+    )
+
+    def execute(self, context: Context) -> Set[str]:
+        fs = self.get_game_fs(context)
+
+        try:
+            importer = Importer(
+                fs,
+                AssetCallbacks(context),
+                self.get_threads_suggestion(context),
+                target_version=self.get_target_version(),
+            )
+        except OSError as err:
+            self.report({"ERROR"}, f"could not open file system: {err}")
+            return {"CANCELLED"}
+
+        try:
+            importer.import_vtex(self.filepath, self.from_game_fs)
+        except OSError as err:
+            self.report({"ERROR"}, f"could not import vtex: {err}")
+            return {"CANCELLED"}
+
+        return {"FINISHED"}
@@ -0,0 +1,54 @@
+from typing import Set
+
+from bpy.types import Context
+from bpy.props import StringProperty
+
+from . import (
+    GameFileImporterOperator,
+    GameFileImporterOperatorProps,
+    ImporterOperatorProps,
+)
+from ..asset import AssetCallbacks
+from ..plumber import Importer
+
+
+class ImportVcd(
+    GameFileImporterOperator,
+    ImporterOperatorProps,
+    GameFileImporterOperatorProps,
+):
+    """Import Source Engine VCD choreography scene"""
+
+    bl_idname = "import_scene.plumber_vcd"
+    bl_label = "Import VCD"
+    bl_options = {"REGISTER", "UNDO"}
+
+    filename_ext = ".vcd"
+
+    filter_glob: StringProperty(
+        default="*.vcd",
+        options={"HIDDEN"},
+        maxlen=255,
+    )
+
+    def execute(self, context: Context) -> Set[str]:
+        fs = self.get_game_fs(context)
+
+        try:
+            importer = Importer(
+                fs,
+                AssetCallbacks(context),
+                self.get_threads_suggestion(context),
+                target_version=self.get_target_version(),
+            )
+        except OSError as err:
+            self.report({"ERROR"}, f"could not open file system: {err}")
+            return {"CANCELLED"}
+
+        try:
+            importer.import_vcd(self.filepath, self.from_game_fs)
+        except OSError as err:
+            self.report({"ERROR"}, f"could not import vcd: {err}")
+            return {"CANCELLED"}
+
+        return {"FINISHED"}
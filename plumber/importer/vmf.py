@@ -1,10 +1,14 @@
-from typing import Set
-from os.path import basename, splitext, isdir, isabs, dirname, join
+from typing import List, Set
+from os.path import basename, splitext, isdir, isabs, dirname, join, exists
+from hashlib import sha1
+import os
+import tempfile
 
 from bpy.props import (
     BoolProperty,
     EnumProperty,
     FloatProperty,
+    FloatVectorProperty,
     StringProperty,
     IntProperty,
 )
@@ -27,7 +31,7 @@ class ImportVmf(
     GameFileImporterOperatorProps,
     MaterialToggleOperatorProps,
 ):
-    """Import Source Engine VMF map"""
+    """Import Source Engine VMF map, or reconstruct one from a compiled BSP map"""
 
     bl_idname = "import_scene.plumber_vmf"
     bl_label = "Import VMF"
@@ -36,7 +40,7 @@ class ImportVmf(
     filename_ext = ".vmf"
 
     filter_glob: StringProperty(
-        default="*.vmf",
+        default="*.vmf;*.bsp",
         options={"HIDDEN"},
         maxlen=255,
     )
@@ -56,6 +60,12 @@ class ImportVmf(
         default=True,
     )
 
+    import_decals: BoolProperty(
+        name="Decals",
+        description="Project decal entities onto the brushes they are placed on",
+        default=True,
+    )
+
     epsilon: FloatProperty(
         name="Epsilon",
         description="Equality threshold for building geometry",
@@ -100,6 +110,25 @@ class ImportVmf(
         default="SKIP",
     )
 
+    decompile_cleanup: BoolProperty(
+        name="Clean up decompiled geometry",
+        description=(
+            "Join world and func_detail brushes, recombine displacements split by the "
+            "decompiler, remove faces hidden between touching brushes and dissolve "
+            "split coplanar faces"
+        ),
+        default=False,
+    )
+
+    import_blend_factors: BoolProperty(
+        name="Blend factor attribute",
+        description=(
+            "Store the raw blend factors of displacements in a separate color "
+            "attribute, which can be repainted to drive the blend of the materials"
+        ),
+        default=False,
+    )
+
     import_props: BoolProperty(
         name="Props",
         default=True,
@@ -125,6 +154,24 @@ class ImportVmf(
         default="NORMAL",
     )
 
+    merge_ragdolls: BoolProperty(
+        name="Merge constrained props",
+        description=(
+            "Merge the armatures of props of the same model which are constrained "
+            "together, like the parts of a vehicle, into one armature"
+        ),
+        default=False,
+    )
+
+    import_detail_props: BoolProperty(
+        name="Scatter detail props",
+        description=(
+            "Scatter the detail sprites and models of displacements from the detail "
+            "types of their materials, like the map compiler does"
+        ),
+        default=False,
+    )
+
     import_lights: BoolProperty(
         name="Lights",
         default=True,
@@ -168,11 +215,110 @@ class ImportVmf(
         default=True,
     )
 
+    assemble_skybox: BoolProperty(
+        name="Assemble 3D skybox",
+        description="Scale and position the 3D skybox around the map",
+        default=False,
+    )
+
     import_sky: BoolProperty(
         name="Sky",
         default=True,
     )
 
+    import_cubemaps: BoolProperty(
+        name="Cubemaps",
+        description=(
+            "Import the cubemaps built into compiled maps as reflection probes, "
+            "with their images converted for world lighting"
+        ),
+        default=False,
+    )
+
+    import_gameplay_markers: BoolProperty(
+        name="Gameplay markers",
+        description=(
+            "Import spawn points, hostages, rescue zones and bombsites as labeled empties "
+            "and wireframe volumes in a separate collection, for gameplay overviews"
+        ),
+        default=False,
+    )
+
+    import_vis_clusters: BoolProperty(
+        name="Vis clusters",
+        description=(
+            "Import the visibility clusters of compiled maps as wireframe boxes of their leaves, "
+            "for inspecting the vis partitioning"
+        ),
+        default=False,
+    )
+
+    include_materials: StringProperty(
+        name="Include materials",
+        description="Comma-separated patterns of brush face materials to import, such as nature/*. * matches within a folder and ** any folders. Empty imports all",
+        default="",
+    )
+
+    exclude_materials: StringProperty(
+        name="Exclude materials",
+        description="Comma-separated patterns of brush face materials to skip, such as tools/*, dev/*, **/*nodraw",
+        default="",
+    )
+
+    lightmap_uvs: BoolProperty(
+        name="Lightmap UVs",
+        description="Add a second UV map laid out like the lightmaps of the map, for baking lighting",
+        default=False,
+    )
+
+    stitch_displacements: BoolProperty(
+        name="Stitch displacements",
+        description="Weld the edges of neighboring displacements, removing the cracks between them",
+        default=True,
+    )
+
+    import_track_paths: BoolProperty(
+        name="Train paths",
+        description="Import the paths of trains as curves with their speeds",
+        default=True,
+    )
+
+    import_weather: BoolProperty(
+        name="Weather",
+        description="Import wind, precipitation and fog entities as empties with their settings",
+        default=True,
+    )
+
+    import_sprites: BoolProperty(
+        name="Sprites",
+        description="Import sprites and spotlights as glow cards, with placeholders for spotlight beams",
+        default=True,
+    )
+
+    import_sounds: BoolProperty(
+        name="Sounds",
+        description="Import ambient sounds and soundscapes as speakers, packing the sound files they play",
+        default=False,
+    )
+
+    sky_projection: EnumProperty(
+        name="Sky projection",
+        items=[
+            (
+                "EQUI",
+                "Equirectangular",
+                "Convert the sky into a panorama used as the world texture",
+            ),
+            ("FACES", "Faces", "Import the sky faces as separate images"),
+            (
+                "CROSS",
+                "Cross",
+                "Arrange the sky faces into a horizontal cross image",
+            ),
+        ],
+        default="EQUI",
+    )
+
     sky_equi_height: IntProperty(
         name="Sky output height",
         default=0,
@@ -190,6 +336,13 @@ class ImportVmf(
         default=False,
     )
 
+    fgd_path: StringProperty(
+        name="FGD file",
+        description="Game entity definitions used to classify unknown entities",
+        default="",
+        subtype="FILE_PATH",
+    )
+
     scale: FloatProperty(
         name="Scale",
         default=0.01,
@@ -199,9 +352,52 @@ class ImportVmf(
         soft_max=1.0,
     )
 
+    name_prefix: StringProperty(
+        name="Name prefix",
+        description="Prefix the names of the imported objects and collections, "
+        + "to import several maps into one scene without name collisions",
+        default="",
+    )
+
+    offset: FloatVectorProperty(
+        name="Offset",
+        description="Move the imported map by this offset, "
+        + "to place several maps side by side",
+        default=(0.0, 0.0, 0.0),
+        subtype="TRANSLATION",
+        size=3,
+    )
+
+    bake_preset: BoolProperty(
+        name="Prepare for baking",
+        description="Generate a second non-overlapping UV map on imported meshes "
+        + "for baking textures and lighting, for exporting to other engines",
+        default=False,
+    )
+
+    resume_import: BoolProperty(
+        name="Resume interrupted import",
+        description="Skip assets already imported by a previous import of this map "
+        + "which didn't finish, for example because Blender crashed",
+        default=False,
+    )
+
+    report_search_paths: BoolProperty(
+        name="Report search path conflicts",
+        description="After importing, list the imported files which are contained in "
+        + "several search paths, to find out which files override the others",
+        default=False,
+    )
+
     def execute(self, context: Context) -> Set[str]:
         fs = self.get_game_fs(context)
 
+        checkpoint_path = get_checkpoint_path(self.filepath)
+        skip_assets = read_checkpoint(checkpoint_path) if self.resume_import else []
+
+        if not skip_assets and exists(checkpoint_path):
+            os.remove(checkpoint_path)
+
         if self.map_data_path == "":
             map_data_path = None
         else:
@@ -219,7 +415,7 @@ class ImportVmf(
 
         map_name = splitext(basename(self.filepath))[0]
 
-        map_collection = bpy.data.collections.new(map_name)
+        map_collection = bpy.data.collections.new(self.name_prefix + map_name)
         context.collection.children.link(map_collection)
 
         if self.import_brushes:
@@ -228,7 +424,7 @@ class ImportVmf(
         else:
             brush_collection = None
 
-        if self.import_brushes and self.import_overlays:
+        if self.import_brushes and (self.import_overlays or self.import_decals):
             overlay_collection = bpy.data.collections.new("overlays")
             map_collection.children.link(overlay_collection)
         else:
@@ -246,7 +442,13 @@ class ImportVmf(
         else:
             light_collection = None
 
-        if self.import_unknown_entities:
+        if (
+            self.import_unknown_entities
+            or self.import_weather
+            or self.import_sprites
+            or self.import_sounds
+            or self.import_track_paths
+        ):
             entity_collection = bpy.data.collections.new("entities")
             map_collection.children.link(entity_collection)
         else:
@@ -261,6 +463,14 @@ class ImportVmf(
             light_collection=light_collection,
             entity_collection=entity_collection,
             apply_armatures=self.dynamic_props == "REMOVE_ARM",
+            merge_ragdolls=self.merge_ragdolls,
+            bake_preset=self.bake_preset,
+            decompile_cleanup=self.import_brushes and self.decompile_cleanup,
+            import_blend_factors=self.import_blend_factors,
+            assemble_skybox=self.import_sky_camera and self.assemble_skybox,
+            gameplay_markers=self.import_gameplay_markers,
+            name_prefix=self.name_prefix,
+            offset=tuple(self.offset),
         )
 
         try:
@@ -268,27 +478,45 @@ class ImportVmf(
                 fs,
                 asset_callbacks,
                 self.get_threads_suggestion(context),
+                target_version=self.get_target_version(),
                 import_materials=self.import_materials,
                 import_lights=self.import_lights,
                 light_factor=self.light_factor,
                 sun_factor=self.sun_factor,
                 ambient_factor=self.ambient_factor,
                 import_sky_camera=self.import_sky_camera,
+                import_weather=self.import_weather,
+                import_sprites=self.import_sprites,
+                import_sounds=self.import_sounds,
+                sky_projection=self.sky_projection,
                 sky_equi_height=self.sky_equi_height
                 if self.sky_equi_height != 0
                 else None,
                 import_unknown_entities=self.import_unknown_entities,
+                include_materials=split_patterns(self.include_materials),
+                exclude_materials=split_patterns(self.exclude_materials),
+                fgd_path=bpy.path.abspath(self.fgd_path)
+                if self.import_unknown_entities and self.fgd_path
+                else None,
                 scale=self.scale,
                 target_fps=self.get_target_fps(context),
                 remove_animations=self.dynamic_props in ("REMOVE_ANIM", "REMOVE_ARM"),
                 simple_materials=self.simple_materials,
                 allow_culling=self.allow_culling,
                 editor_materials=self.editor_materials,
+                emission_strength=self.emission_strength,
                 texture_interpolation=self.texture_interpolation,
+                height_maps=self.height_maps,
+                ssbump_height=self.ssbump_height,
+                base_alpha=self.base_alpha,
                 texture_format=self.texture_format,
+                texture_max_resolution=self.get_texture_max_resolution(),
+                **self.get_texture_color_correction(context),
                 # automatic map data path detection happens here
                 vmf_path=self.filepath if map_data_path is None else None,
                 map_data_path=map_data_path,
+                checkpoint_path=checkpoint_path,
+                skip_assets=skip_assets,
             )
         except OSError as err:
             self.report({"ERROR"}, f"Could not open file system: {err}")
@@ -300,13 +528,29 @@ class ImportVmf(
                 self.from_game_fs,
                 import_brushes=self.import_brushes,
                 import_overlays=self.import_overlays,
+                import_decals=self.import_brushes and self.import_decals,
                 epsilon=self.epsilon,
                 cut_threshold=self.cut_threshold,
                 merge_solids=self.merge_solids,
                 invisible_solids=self.invisible_solids,
                 import_props=self.import_props,
-                import_entities=self.import_lights or self.import_sky_camera,
+                import_entities=self.import_lights
+                or self.import_sky_camera
+                or self.import_weather
+                or self.import_sprites
+                or self.import_sounds,
                 import_sky=self.import_sky,
+                import_track_paths=self.import_track_paths,
+                lightmap_uvs=self.lightmap_uvs,
+                # decompile cleanup recombines the split displacements by stitching them
+                stitch_displacements=self.stitch_displacements
+                or (self.import_brushes and self.decompile_cleanup),
+                assemble_skybox=self.import_sky_camera and self.assemble_skybox,
+                merge_ragdolls=self.import_props and self.merge_ragdolls,
+                import_detail_props=self.import_props and self.import_detail_props,
+                import_cubemaps=self.import_cubemaps,
+                import_vis_clusters=self.import_vis_clusters,
+                gameplay_markers=self.import_gameplay_markers,
                 scale=self.scale,
             )
         except OSError as err:
@@ -315,8 +559,49 @@ class ImportVmf(
 
         asset_callbacks.finish()
 
+        error_counts = importer.error_counts()
+        if error_counts:
+            summary = ", ".join(
+                f"{count} {kind}" for kind, count in sorted(error_counts.items())
+            )
+            self.report({"WARNING"}, f"Some assets could not be imported: {summary}")
+
+        orphan_textures = importer.orphan_textures()
+        if orphan_textures:
+            self.report(
+                {"INFO"},
+                f"{len(orphan_textures)} textures were converted but are unused, "
+                "they are removed when the file is saved and reopened",
+            )
+
+        if self.report_search_paths:
+            self.report_search_path_conflicts(importer)
+
+        # the import finished, nothing to resume anymore
+        del importer
+        os.remove(checkpoint_path)
+
         return {"FINISHED"}
 
+    def report_search_path_conflicts(self, importer: Importer) -> None:
+        conflicts = [
+            lookup for lookup in importer.search_path_report() if lookup.is_conflict()
+        ]
+
+        for lookup in conflicts:
+            kind, path = lookup.served_by()
+            shadowed = ", ".join(path for _, path in lookup.shadowed())
+            print(
+                f"[Plumber] [INFO] `{lookup.path()}` is read from {kind} `{path}`, "
+                f"overriding {shadowed}"
+            )
+
+        self.report(
+            {"INFO"},
+            f"{len(conflicts)} imported files are contained in several search paths, "
+            "see the console for details",
+        )
+
     def draw(self, context: Context):
         if self.from_game_fs:
             draw_map_data_props(self.layout, self, context)
@@ -338,6 +623,31 @@ class ImportVmf(
             draw_main_props(self.layout, self, context)
 
 
+def get_checkpoint_path(vmf_path: str) -> str:
+    checkpoint_dir = join(tempfile.gettempdir(), "plumber_checkpoints")
+    os.makedirs(checkpoint_dir, exist_ok=True)
+
+    name = sha1(vmf_path.encode("utf-8")).hexdigest()
+    return join(checkpoint_dir, f"{name}.txt")
+
+
+def read_checkpoint(checkpoint_path: str) -> List[str]:
+    if not exists(checkpoint_path):
+        return []
+
+    with open(checkpoint_path, encoding="utf-8") as f:
+        # models are imported again, since props are instanced from them
+        return [
+            line.rstrip("\n")
+            for line in f
+            if line.strip() and not line.startswith("model\t")
+        ]
+
+
+def split_patterns(patterns: str) -> List[str]:
+    return [pattern.strip() for pattern in patterns.split(",") if pattern.strip()]
+
+
 def draw_map_data_props(layout: UILayout, operator: ImportVmf, context: Context):
     layout.use_property_split = True
     layout.use_property_decorate = False
@@ -364,10 +674,17 @@ def draw_geometry_props(layout: UILayout, operator: ImportVmf, context: Context)
     layout.use_property_split = True
     layout.enabled = operator.import_brushes
     layout.prop(operator, "import_overlays")
+    layout.prop(operator, "import_decals")
     layout.prop(operator, "epsilon")
     layout.prop(operator, "cut_threshold")
     layout.prop(operator, "merge_solids", expand=True)
     layout.prop(operator, "invisible_solids", expand=True)
+    layout.prop(operator, "decompile_cleanup")
+    layout.prop(operator, "import_blend_factors")
+    layout.prop(operator, "lightmap_uvs")
+    layout.prop(operator, "stitch_displacements")
+    layout.prop(operator, "include_materials")
+    layout.prop(operator, "exclude_materials")
 
 
 class PLUMBER_PT_vmf_geometry(Panel):
@@ -423,7 +740,10 @@ class PLUMBER_PT_vmf_lights(Panel):
 def draw_sky_props(layout: UILayout, operator: ImportVmf, context: Context):
     layout.use_property_split = True
     layout.enabled = operator.import_sky
-    layout.prop(operator, "sky_equi_height")
+    layout.prop(operator, "sky_projection")
+    col = layout.column()
+    col.enabled = operator.sky_projection == "EQUI"
+    col.prop(operator, "sky_equi_height")
 
 
 class PLUMBER_PT_vmf_sky(Panel):
@@ -451,6 +771,8 @@ def draw_props_props(layout: UILayout, operator: ImportVmf, context: Context):
     layout.use_property_split = True
     layout.enabled = operator.import_props
     layout.prop(operator, "dynamic_props")
+    layout.prop(operator, "merge_ragdolls")
+    layout.prop(operator, "import_detail_props")
 
 
 class PLUMBER_PT_vmf_props(Panel):
@@ -477,8 +799,26 @@ class PLUMBER_PT_vmf_props(Panel):
 def draw_main_props(layout: UILayout, operator: ImportVmf, context: Context):
     layout.use_property_split = True
     layout.prop(operator, "import_sky_camera")
+    col = layout.column()
+    col.enabled = operator.import_sky_camera
+    col.prop(operator, "assemble_skybox")
+    layout.prop(operator, "import_cubemaps")
+    layout.prop(operator, "import_vis_clusters")
+    layout.prop(operator, "import_gameplay_markers")
+    layout.prop(operator, "import_weather")
+    layout.prop(operator, "import_sprites")
+    layout.prop(operator, "import_sounds")
+    layout.prop(operator, "import_track_paths")
     layout.prop(operator, "import_unknown_entities")
+    col = layout.column()
+    col.enabled = operator.import_unknown_entities
+    col.prop(operator, "fgd_path")
     layout.prop(operator, "scale")
+    layout.prop(operator, "name_prefix")
+    layout.prop(operator, "offset")
+    layout.prop(operator, "bake_preset")
+    layout.prop(operator, "resume_import")
+    layout.prop(operator, "report_search_paths")
 
 
 class PLUMBER_PT_vmf_main(Panel):
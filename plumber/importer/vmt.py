@@ -1,7 +1,7 @@
 from typing import Set
 
 from bpy.types import Context, Panel
-from bpy.props import StringProperty
+from bpy.props import BoolProperty, StringProperty
 
 from . import (
     GameFileImporterOperator,
@@ -33,20 +33,33 @@ class ImportVmt(
         maxlen=255,
     )
 
+    save_script: BoolProperty(
+        name="Save as Python script",
+        description="Also save a script recreating the material as a text datablock",
+        default=False,
+    )
+
     def execute(self, context: Context) -> Set[str]:
         fs = self.get_game_fs(context)
 
         try:
             importer = Importer(
                 fs,
-                AssetCallbacks(context),
+                AssetCallbacks(context, save_material_scripts=self.save_script),
                 self.get_threads_suggestion(context),
+                target_version=self.get_target_version(),
                 import_materials=True,
                 simple_materials=self.simple_materials,
                 allow_culling=self.allow_culling,
                 editor_materials=self.editor_materials,
+                emission_strength=self.emission_strength,
                 texture_interpolation=self.texture_interpolation,
+                height_maps=self.height_maps,
+                ssbump_height=self.ssbump_height,
+                base_alpha=self.base_alpha,
                 texture_format=self.texture_format,
+                texture_max_resolution=self.get_texture_max_resolution(),
+                **self.get_texture_color_correction(context),
                 root_search=None if self.from_game_fs else (self.filepath, "materials"),
             )
         except OSError as err:
@@ -64,6 +77,7 @@ class ImportVmt(
     def draw(self, context: Context):
         if self.from_game_fs:
             MaterialImporterOperatorProps.draw_props(self.layout, self, context)
+        self.layout.prop(self, "save_script")
 
 
 class PLUMBER_PT_vmt_main(Panel):
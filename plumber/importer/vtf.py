@@ -39,6 +39,8 @@ class ImportVtf(
                 fs,
                 AssetCallbacks(context),
                 self.get_threads_suggestion(context),
+                target_version=self.get_target_version(),
+                **self.get_texture_color_correction(context),
             )
         except OSError as err:
             self.report({"ERROR"}, f"could not open file system: {err}")
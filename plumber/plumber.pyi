@@ -1,23 +1,69 @@
-from typing import Any, Dict, List, Optional, Tuple, Union
+from typing import Any, Dict, List, Optional, Set, Tuple, Union
 
 class FileSystem:
     def __init__(self, name: str, search_paths: List[Tuple[str, str]]) -> None: ...
     @staticmethod
     def empty() -> "FileSystem": ...
+    def app_id(self) -> Optional[int]: ...
+    def install_dir(self) -> Optional[str]: ...
+    def icon_path(self) -> Optional[str]: ...
+    def header_path(self) -> Optional[str]: ...
     def name(self) -> str: ...
     def search_paths(self) -> List[Tuple[str, str]]: ...
     def with_search_path(self, search_path: Tuple[str, str]) -> "FileSystem": ...
+    def with_inserted_search_path(
+        self, index: int, search_path: Tuple[str, str]
+    ) -> "FileSystem": ...
+    def without_search_path(self, index: int) -> "FileSystem": ...
+    def with_moved_search_path(self, index: int, new_index: int) -> "FileSystem": ...
+    def to_profile(self) -> str: ...
+    @staticmethod
+    def from_profile(json: str) -> "FileSystem": ...
+    def with_case_insensitive_dirs(self) -> "FileSystem": ...
     def browse(self) -> "FileBrowser": ...
+    @staticmethod
+    def parse_vdf(text: str) -> Dict[str, Any]: ...
+    def read_vdf(self, path: str) -> Dict[str, Any]: ...
+    def sequence_previews(self, path: str, from_game: bool) -> List["SequencePreview"]: ...
+    def texture_lights(self) -> Dict[str, Tuple[List[float], float]]: ...
+    def particle_manifest(self, map_name: Optional[str] = None) -> List[Tuple[str, bool]]: ...
     def extract(self, path: str, is_dir: bool, target_dir: str): ...
 
 def discover_filesystems() -> List[FileSystem]: ...
 def filesystem_from_gameinfo(path: str) -> FileSystem: ...
+def filesystem_from_directory(path: str) -> FileSystem: ...
+def filesystem_from_asset_path(path: str) -> FileSystem: ...
+def find_workshop_item(
+    install_dir: str, app_id: int, workshop_id: int
+) -> Optional["WorkshopItem"]: ...
+def workshop_map_items(install_dir: str, app_id: int) -> List["WorkshopItem"]: ...
+def build_cubemap_vtf(faces: List[bytes]) -> bytes: ...
 def log_error(error: str) -> None: ...
 def log_info(info: str) -> None: ...
+def set_log_filter(filter: str) -> None: ...
+def last_crash_report() -> Optional[str]: ...
 def version() -> str: ...
 
+class WorkshopItem:
+    def dir(self) -> str: ...
+    def search_paths(self) -> List[Tuple[str, str]]: ...
+    def maps(self) -> List[str]: ...
+    def addons(self) -> List[str]: ...
+
 class FileBrowser:
-    def read_dir(self, dir: str) -> List["FileBrowserEntry"]: ...
+    def read_dir(
+        self,
+        dir: str,
+        extensions: Optional[List[str]] = None,
+        search: Optional[str] = None,
+        offset: int = 0,
+        limit: Optional[int] = None,
+    ) -> Tuple[int, List["FileBrowserEntry"]]: ...
+    def find(self, pattern: str, limit: Optional[int] = None) -> List[str]: ...
+    def bookmarks(self) -> List[str]: ...
+    def set_bookmarked(self, path: str, bookmarked: bool): ...
+    def recent_imports(self) -> List[str]: ...
+    def add_recent_import(self, path: str): ...
 
 class FileBrowserEntry:
     def name(self) -> str: ...
@@ -26,22 +72,63 @@ class FileBrowserEntry:
 
 class SkyEqui:
     def name(self) -> str: ...
+    def projection(self) -> str: ...
+    def width(self) -> int: ...
+    def height(self) -> int: ...
+    def format(self) -> str: ...
+    def bytes(self) -> bytes: ...
+
+class SkyFaces:
+    def name(self) -> str: ...
+    def faces(self) -> List[SkyFace]: ...
+
+class SkyFace:
+    def name(self) -> str: ...
+    def suffix(self) -> str: ...
     def width(self) -> int: ...
     def height(self) -> int: ...
     def format(self) -> str: ...
     def bytes(self) -> bytes: ...
 
+class Cubemap:
+    def index(self) -> int: ...
+    def position(self) -> List[float]: ...
+    def size(self) -> int: ...
+    def has_image(self) -> bool: ...
+    def width(self) -> Optional[int]: ...
+    def height(self) -> Optional[int]: ...
+    def format(self) -> Optional[str]: ...
+    def bytes(self) -> Optional[bytes]: ...
+
+class VisCluster:
+    def index(self) -> int: ...
+    def scale(self) -> List[float]: ...
+    def visible_clusters(self) -> List[int]: ...
+    def vertices(self) -> List[float]: ...
+    def loops_len(self) -> int: ...
+    def polygons_len(self) -> int: ...
+    def polygon_loop_totals(self) -> List[int]: ...
+    def polygon_loop_starts(self) -> List[int]: ...
+    def polygon_vertices(self) -> List[int]: ...
+
 class Texture:
     def name(self) -> str: ...
     def width(self) -> int: ...
     def height(self) -> int: ...
     def format_ext(self) -> str: ...
+    def original_size(self) -> List[int]: ...
+    def is_raw(self) -> bool: ...
+    def float_buffer(self) -> bool: ...
     def bytes(self) -> bytes: ...
 
 class Material:
     def name(self) -> str: ...
     def data(self) -> BuiltMaterialData: ...
     def texture_ext(self) -> str: ...
+    def tool_material(self) -> str: ...
+    def metadata(self) -> Dict[str, str]: ...
+    def average_color(self) -> Optional[List[float]]: ...
+    def python_script(self, blender_name: str, helpers: str) -> str: ...
 
 Value = Union[
     bool,
@@ -49,14 +136,21 @@ Value = Union[
     List[float],
     str,
     "TextureRef",
+    "NodeTreeRef",
+    "AnimatedVec",
 ]
 
 NodeSocketId = Union[int, str]
 
 class BuiltMaterialData:
     def properties(self) -> Dict[str, Value]: ...
+    def custom_properties(self) -> Dict[str, Value]: ...
     def nodes(self) -> List["BuiltNode"]: ...
+    def node_groups(self) -> Dict[str, "BuiltNodeGroup"]: ...
+    def displacement(self) -> Optional["BuiltNodeSocketRef"]: ...
+    def volume(self) -> Optional["BuiltNodeSocketRef"]: ...
     def texture_color_spaces(self) -> Dict[str, str]: ...
+    def texture_roles(self) -> Dict[str, Set[str]]: ...
 
 class BuiltNode:
     def blender_id(self) -> str: ...
@@ -69,9 +163,21 @@ class BuiltNodeSocketRef:
     def node_index(self) -> int: ...
     def socket(self) -> NodeSocketId: ...
 
+class BuiltNodeGroup:
+    def nodes(self) -> List[BuiltNode]: ...
+    def inputs(self) -> List[Tuple[str, BuiltNodeSocketRef]]: ...
+    def outputs(self) -> List[Tuple[str, BuiltNodeSocketRef]]: ...
+
 class TextureRef:
     def path(self) -> str: ...
 
+class NodeTreeRef:
+    def name(self) -> str: ...
+
+class AnimatedVec:
+    def value(self) -> List[float]: ...
+    def rate(self) -> List[float]: ...
+
 class LoadedProp:
     def model(self) -> str: ...
     def class_name(self) -> str: ...
@@ -80,6 +186,19 @@ class LoadedProp:
     def rotation(self) -> List[float]: ...
     def scale(self) -> List[float]: ...
     def color(self) -> List[float]: ...
+    def body(self) -> int: ...
+    def skin(self) -> int: ...
+    def disable_shadows(self) -> bool: ...
+    def fade_min_distance(self) -> Optional[float]: ...
+    def fade_max_distance(self) -> Optional[float]: ...
+    def solid(self) -> int: ...
+    def lighting_origin(self) -> Optional[str]: ...
+    def lighting_origin_position(self) -> Optional[List[float]]: ...
+    def vertex_lighting(self) -> Optional[str]: ...
+    def uniform_scale(self) -> float: ...
+    def in_3d_skybox(self) -> bool: ...
+    def signature(self) -> str: ...
+    def ragdoll_group(self) -> Optional[int]: ...
     def properties(self) -> Dict[str, str]: ...
 
 class QuaternionData:
@@ -107,12 +226,54 @@ class LoadedAnimation:
     def name(self) -> str: ...
     def data(self) -> Dict[int, BoneAnimationData]: ...
     def looping(self) -> bool: ...
+    def frame_end(self) -> float: ...
+    def activity(self) -> Optional[str]: ...
+    def activity_weight(self) -> int: ...
+
+class SequencePreview:
+    def name(self) -> str: ...
+    def activity(self) -> Optional[str]: ...
+    def looping(self) -> bool: ...
+    def delta(self) -> bool: ...
+    def fps(self) -> float: ...
+    def frame_count(self) -> int: ...
+    def duration(self) -> float: ...
+    def root_motion(self) -> List[Tuple[float, List[float]]]: ...
+    def events(self) -> List[Tuple[float, str, str]]: ...
+
+class JiggleBone:
+    def is_flexible(self) -> bool: ...
+    def length(self) -> float: ...
+    def tip_mass(self) -> float: ...
+    def yaw_stiffness(self) -> float: ...
+    def yaw_damping(self) -> float: ...
+    def pitch_stiffness(self) -> float: ...
+    def pitch_damping(self) -> float: ...
+    def along_stiffness(self) -> float: ...
+    def along_damping(self) -> float: ...
 
 class LoadedBone:
     def name(self) -> str: ...
     def parent_bone_index(self) -> Optional[int]: ...
     def position(self) -> List[float]: ...
     def rotation(self) -> List[float]: ...
+    def jiggle(self) -> Optional[JiggleBone]: ...
+
+class LoadedAttachment:
+    def name(self) -> str: ...
+    def parent_bone_index(self) -> Optional[int]: ...
+    def position(self) -> List[float]: ...
+    def rotation(self) -> List[float]: ...
+
+class Hitbox:
+    def name(self) -> str: ...
+    def bone_index(self) -> int: ...
+    def parent_bone_index(self) -> Optional[int]: ...
+    def group(self) -> int: ...
+    def min(self) -> List[float]: ...
+    def max(self) -> List[float]: ...
+    def position(self) -> List[float]: ...
+    def rotation(self) -> List[float]: ...
 
 class LoadedMesh:
     def name(self) -> str: ...
@@ -126,14 +287,31 @@ class LoadedMesh:
     def loop_uvs(self) -> List[float]: ...
     def normals(self) -> List[List[float]]: ...
     def weight_groups(self) -> Dict[int, Dict[int, float]]: ...
+    def is_collision(self) -> bool: ...
+    def bodygroup(self) -> Optional[Tuple[int, int]]: ...
+
+class MaterialBinding:
+    def slot(self) -> int: ...
+    def name(self) -> str: ...
+    def status(self) -> str: ...
+    def path(self) -> Optional[str]: ...
 
 class Model:
     def name(self) -> str: ...
     def meshes(self) -> List[LoadedMesh]: ...
     def materials(self) -> List[Optional[str]]: ...
+    def material_dirs(self) -> List[str]: ...
+    def material_bindings(self) -> List[MaterialBinding]: ...
+    def material_resolutions(self) -> List[Tuple[str, str, Optional[str]]]: ...
+    def skin_count(self) -> int: ...
+    def materials_for_skin(self, index: int) -> Optional[List[Optional[str]]]: ...
     def bones(self) -> List[LoadedBone]: ...
     def animations(self) -> List[LoadedAnimation]: ...
+    def sequences(self) -> List[LoadedAnimation]: ...
     def rest_positions(self) -> Dict[int, BoneRestData]: ...
+    def bodygroups(self) -> List[Tuple[str, List[str]]]: ...
+    def attachments(self) -> List[LoadedAttachment]: ...
+    def hitbox_sets(self) -> List[Tuple[str, List[Hitbox]]]: ...
 
 class MergedSolids:
     def no_draw(self) -> bool: ...
@@ -148,7 +326,12 @@ class MergedSolids:
     def polygon_material_indices(self) -> List[int]: ...
     def loop_uvs(self) -> List[float]: ...
     def loop_colors(self) -> List[float]: ...
+    def loop_blend_factors(self) -> Optional[List[float]]: ...
     def materials(self) -> List[str]: ...
+    def polygon_smoothing_groups(self) -> Optional[List[int]]: ...
+    def loop_lightmap_uvs(self) -> Optional[List[float]]: ...
+    def polygon_displacements(self) -> Optional[List[bool]]: ...
+    def in_3d_skybox(self) -> bool: ...
 
 class BuiltSolid:
     def id(self) -> int: ...
@@ -164,12 +347,25 @@ class BuiltSolid:
     def polygon_material_indices(self) -> List[int]: ...
     def loop_uvs(self) -> List[float]: ...
     def loop_colors(self) -> List[float]: ...
+    def loop_blend_factors(self) -> Optional[List[float]]: ...
     def materials(self) -> List[str]: ...
+    def polygon_smoothing_groups(self) -> Optional[List[int]]: ...
+    def loop_lightmap_uvs(self) -> Optional[List[float]]: ...
+    def polygon_displacements(self) -> Optional[List[bool]]: ...
+    def in_3d_skybox(self) -> bool: ...
 
 class BuiltBrushEntity:
     def id(self) -> int: ...
     def class_name(self) -> str: ...
+    def gameplay_marker(self) -> Optional[str]: ...
+    def gameplay_label(self) -> Optional[str]: ...
+    def solid(self) -> bool: ...
+    def solidity(self) -> Optional[int]: ...
+    def start_disabled(self) -> bool: ...
+    def spawnflags(self) -> int: ...
+    def collision_group(self) -> Optional[int]: ...
     def merged_solids(self) -> Optional[MergedSolids]: ...
+    def skybox_merged_solids(self) -> Optional[MergedSolids]: ...
     def solids(self) -> List[BuiltSolid]: ...
 
 class BuiltOverlay:
@@ -186,6 +382,24 @@ class BuiltOverlay:
     def loop_uvs(self) -> List[float]: ...
     def material(self) -> str: ...
 
+class DetailSprites:
+    def material(self) -> str: ...
+    def scale(self) -> List[float]: ...
+    def vertices(self) -> List[float]: ...
+    def loops_len(self) -> int: ...
+    def polygons_len(self) -> int: ...
+    def polygon_loop_totals(self) -> List[int]: ...
+    def polygon_loop_starts(self) -> List[int]: ...
+    def polygon_vertices(self) -> List[int]: ...
+    def loop_uvs(self) -> List[float]: ...
+
+class DetailScatter:
+    def sprites(self) -> Optional[DetailSprites]: ...
+    def model_names(self) -> List[str]: ...
+    def model_positions(self) -> List[List[float]]: ...
+    def model_rotations(self) -> List[List[float]]: ...
+    def model_side_ids(self) -> List[int]: ...
+
 class Light:
     def id(self) -> int: ...
     def position(self) -> List[float]: ...
@@ -218,6 +432,7 @@ class SkyCamera:
     def id(self) -> int: ...
     def position(self) -> List[float]: ...
     def scale(self) -> List[float]: ...
+    def skybox_offset(self) -> List[float]: ...
 
 class UnknownEntity:
     def class_name(self) -> str: ...
@@ -226,6 +441,103 @@ class UnknownEntity:
     def rotation(self) -> List[float]: ...
     def scale(self) -> List[float]: ...
     def properties(self) -> Dict[str, str]: ...
+    def property_types(self) -> Dict[str, str]: ...
+    def keyvalues(self) -> Dict[str, str]: ...
+    def connections(self) -> Dict[str, List[Tuple[str, str, str, float, int]]]: ...
+    def kind(self) -> Optional[str]: ...
+    def color(self) -> Optional[List[float]]: ...
+    def model(self) -> Optional[str]: ...
+    def gameplay_marker(self) -> Optional[str]: ...
+    def gameplay_label(self) -> Optional[str]: ...
+
+class Wind:
+    def id(self) -> int: ...
+    def position(self) -> List[float]: ...
+    def direction(self) -> float: ...
+    def min_speed(self) -> float: ...
+    def max_speed(self) -> float: ...
+    def min_gust_speed(self) -> float: ...
+    def max_gust_speed(self) -> float: ...
+    def min_gust_delay(self) -> float: ...
+    def max_gust_delay(self) -> float: ...
+    def gust_duration(self) -> float: ...
+    def gust_direction_change(self) -> float: ...
+    def properties(self) -> Dict[str, str]: ...
+
+class Precipitation:
+    def id(self) -> int: ...
+    def kind(self) -> str: ...
+    def density(self) -> float: ...
+    def color(self) -> List[float]: ...
+    def properties(self) -> Dict[str, str]: ...
+
+class FogController:
+    def id(self) -> int: ...
+    def position(self) -> List[float]: ...
+    def enabled(self) -> bool: ...
+    def color(self) -> List[float]: ...
+    def secondary_color(self) -> Optional[List[float]]: ...
+    def start(self) -> float: ...
+    def end(self) -> float: ...
+    def max_density(self) -> float: ...
+    def far_z(self) -> Optional[float]: ...
+    def properties(self) -> Dict[str, str]: ...
+
+class Sprite:
+    def id(self) -> int: ...
+    def kind(self) -> str: ...
+    def position(self) -> List[float]: ...
+    def rotation(self) -> List[float]: ...
+    def material(self) -> str: ...
+    def size(self) -> float: ...
+    def color(self) -> List[float]: ...
+    def alpha(self) -> float: ...
+    def render_mode(self) -> int: ...
+    def hdr_color_scale(self) -> float: ...
+    def glow_proxy_size(self) -> float: ...
+    def enabled(self) -> bool: ...
+    def beam_material(self) -> Optional[str]: ...
+    def beam_length(self) -> Optional[float]: ...
+    def beam_width(self) -> Optional[float]: ...
+    def properties(self) -> Dict[str, str]: ...
+
+class Sound:
+    def id(self) -> int: ...
+    def kind(self) -> str: ...
+    def name(self) -> str: ...
+    def path(self) -> Optional[str]: ...
+    def data(self) -> Optional[bytes]: ...
+    def position(self) -> List[float]: ...
+    def volume(self) -> float: ...
+    def radius(self) -> Optional[float]: ...
+    def enabled(self) -> bool: ...
+    def properties(self) -> Dict[str, str]: ...
+
+class TrackPath:
+    def id(self) -> int: ...
+    def class_name(self) -> str: ...
+    def name(self) -> str: ...
+    def speed(self) -> float: ...
+    def closed(self) -> bool: ...
+    def point_names(self) -> List[str]: ...
+    def point_positions(self) -> List[List[float]]: ...
+    def point_speeds(self) -> List[float]: ...
+
+class ChoreoEvent:
+    def kind(self) -> str: ...
+    def name(self) -> str: ...
+    def actor(self) -> Optional[str]: ...
+    def channel(self) -> Optional[str]: ...
+    def start(self) -> float: ...
+    def end(self) -> Optional[float]: ...
+    def parameter(self) -> str: ...
+    def parameter2(self) -> str: ...
+
+class ChoreoScene:
+    def name(self) -> str: ...
+    def fps(self) -> Optional[int]: ...
+    def actors(self) -> List[str]: ...
+    def events(self) -> List[ChoreoEvent]: ...
 
 class Importer:
     def __init__(
@@ -236,8 +548,53 @@ class Importer:
         **kwargs
     ) -> None: ...
     def import_vmf(self, path: str, from_game: bool, **kwargs) -> None: ...
+    def scan_vmf(self, path: str, from_game: bool) -> ScanManifest: ...
+    def entity_report(
+        self,
+        path: str,
+        from_game: bool,
+        format: str = "csv",
+        keys: Optional[List[str]] = None,
+    ) -> str: ...
+    def displacement_neighbors(
+        self, path: str, from_game: bool
+    ) -> List[Tuple[int, int, int, int]]: ...
     def import_mdl(self, path: str, from_game: bool, **kwargs) -> None: ...
+    def import_dmx(
+        self, path: str, from_game: bool, import_animations: bool = True
+    ) -> None: ...
+    def import_vcd(self, path: str, from_game: bool) -> None: ...
     def import_vmt(self, path: str, from_game: bool) -> None: ...
     def import_vtf(self, path: str, from_game: bool) -> None: ...
+    def import_wad(self, path: str, from_game: bool) -> None: ...
+    def import_vmat(self, path: str, from_game: bool) -> None: ...
+    def import_vtex(self, path: str, from_game: bool) -> None: ...
     def stage_mdl(self, path: str, **kwargs) -> None: ...
     def import_assets(self) -> None: ...
+    def errors(self) -> List[AssetError]: ...
+    def error_counts(self) -> Dict[str, int]: ...
+    def orphan_textures(self) -> List[str]: ...
+    def search_path_report(self) -> List[PathLookup]: ...
+
+class AssetError:
+    def kind(self) -> str: ...
+    def path(self) -> Optional[str]: ...
+    def error(self) -> str: ...
+
+class ScannedFile:
+    def kind(self) -> str: ...
+    def path(self) -> str: ...
+    def found(self) -> bool: ...
+    def references(self) -> int: ...
+
+class ScanManifest:
+    def files(self) -> List[ScannedFile]: ...
+    def missing(self) -> List[ScannedFile]: ...
+    def missing_counts(self) -> Dict[str, int]: ...
+
+class PathLookup:
+    def path(self) -> str: ...
+    def found(self) -> bool: ...
+    def served_by(self) -> Optional[Tuple[str, str]]: ...
+    def shadowed(self) -> List[Tuple[str, str]]: ...
+    def is_conflict(self) -> bool: ...
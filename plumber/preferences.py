@@ -1,8 +1,17 @@
-from .plumber import discover_filesystems, FileSystem, filesystem_from_gameinfo
+from .plumber import (
+    discover_filesystems,
+    FileSystem,
+    filesystem_from_directory,
+    filesystem_from_gameinfo,
+    find_workshop_item,
+    set_log_filter,
+    workshop_map_items,
+)
 
 from typing import List, Set, Tuple
-from os.path import isdir
+from os.path import isdir, isfile
 import os
+import sys
 
 from bpy.types import (
     Context,
@@ -15,11 +24,22 @@ from bpy.types import (
 from bpy.props import (
     CollectionProperty,
     EnumProperty,
+    FloatProperty,
     IntProperty,
     StringProperty,
     BoolProperty,
 )
 import bpy
+import bpy.utils.previews
+
+
+# images of the games cached by Steam, shown as icons in the game lists
+game_previews = None
+game_enum_items_cache = []
+# downloaded workshop items with maps, kept referenced while the enum is shown.
+# scanned once per game when the mount dialog is opened, not on every redraw
+workshop_enum_items_cache = []
+workshop_enum_items_game = None
 
 
 class GameSearchPath(PropertyGroup):
@@ -33,6 +53,12 @@ class GameSearchPath(PropertyGroup):
             self.kind = "DIR"
         elif value.lower().endswith(".vpk"):
             self.kind = "VPK"
+        elif value.lower().endswith(".gma"):
+            self.kind = "GMA"
+        elif value.lower().endswith(".bsp"):
+            self.kind = "BSP"
+        elif value.lower().endswith(".wad"):
+            self.kind = "WAD"
 
     path: StringProperty(
         subtype="FILE_PATH",
@@ -46,6 +72,9 @@ class GameSearchPath(PropertyGroup):
             ("DIR", "Directory", "", "FILE_FOLDER", 0),
             ("VPK", "VPK Archive", "", "PACKAGE", 1),
             ("WILDCARD", "Wildcard Directory", "", "FOLDER_REDIRECT", 2),
+            ("GMA", "GMA Addon", "Garry's Mod addon archive, extracted when mounted", "PACKAGE", 3),
+            ("BSP", "BSP Pakfile", "Files embedded in a compiled map, extracted when mounted", "PACKAGE", 4),
+            ("WAD", "WAD Textures", "GoldSrc texture archive, extracted as materials when mounted", "TEXTURE", 5),
         ),
         name="Type",
     )
@@ -155,6 +184,101 @@ class MoveGameSearchPathOperator(Operator):
         return {"FINISHED"}
 
 
+def get_workshop_map_items(self, context: Context) -> List[Tuple[str, str, str]]:
+    global workshop_enum_items_cache, workshop_enum_items_game
+
+    preferences: AddonPreferences = context.preferences.addons[__package__].preferences
+    game: Game = preferences.games[preferences.game_index]
+
+    game_key = (game.install_dir, game.app_id)
+    if workshop_enum_items_game == game_key:
+        return workshop_enum_items_cache
+
+    workshop_enum_items_cache = [("", "Enter ID", "Enter the ID of the item")]
+    for item in workshop_map_items(game.install_dir, game.app_id):
+        workshop_id = os.path.basename(item.dir())
+        maps = ", ".join(os.path.basename(path) for path in item.maps())
+        workshop_enum_items_cache.append((workshop_id, f"{workshop_id}: {maps}", ""))
+
+    workshop_enum_items_game = game_key
+
+    return workshop_enum_items_cache
+
+
+def reload_workshop_map_items() -> None:
+    global workshop_enum_items_game
+
+    workshop_enum_items_game = None
+
+
+class MountWorkshopItemOperator(Operator):
+    """Add the search paths of a downloaded Steam Workshop item to the selected game"""
+
+    bl_idname = "plumber.game_workshop_item_mount"
+    bl_label = "Mount a workshop item"
+    bl_options = {"REGISTER"}
+
+    downloaded: EnumProperty(
+        name="Downloaded Map",
+        description="Downloaded workshop item with maps",
+        items=get_workshop_map_items,
+    )
+
+    workshop_id: StringProperty(
+        name="Workshop ID",
+        description="ID of the item, found at the end of its workshop page URL",
+    )
+
+    @classmethod
+    def poll(cls, context: Context) -> bool:
+        preferences: AddonPreferences = context.preferences.addons[
+            __package__
+        ].preferences
+        return bool(preferences.games) and bool(
+            preferences.games[preferences.game_index].app_id
+        )
+
+    def invoke(self, context: Context, event) -> Set[str]:
+        # items downloaded since the dialog was last opened are listed
+        reload_workshop_map_items()
+        return context.window_manager.invoke_props_dialog(self)
+
+    def execute(self, context: Context) -> Set[str]:
+        preferences: AddonPreferences = context.preferences.addons[
+            __package__
+        ].preferences
+        game: Game = preferences.games[preferences.game_index]
+
+        workshop_id = self.workshop_id.strip() or self.downloaded
+        if not workshop_id.isdigit():
+            self.report({"ERROR"}, "workshop ID must be a number")
+            return {"CANCELLED"}
+
+        item = find_workshop_item(game.install_dir, game.app_id, int(workshop_id))
+        if item is None:
+            self.report({"ERROR"}, f"workshop item {workshop_id} is not downloaded")
+            return {"CANCELLED"}
+
+        existing = {(path.kind, path.path) for path in game.search_paths}
+        addons = [("GMA", path) for path in item.addons()]
+        for kind, path in item.search_paths() + addons:
+            if (kind, path) in existing:
+                continue
+            search_path = game.search_paths.add()
+            search_path.path = path
+            search_path.kind = kind
+
+        game.search_path_index = len(game.search_paths) - 1
+
+        maps = item.maps()
+        if maps:
+            self.report({"INFO"}, f"mounted maps: {', '.join(maps)}")
+        else:
+            self.report({"INFO"}, f"mounted {item.dir()}")
+
+        return {"FINISHED"}
+
+
 class Game(PropertyGroup):
     def get_name(self) -> str:
         return self.get("name", "")
@@ -188,9 +312,47 @@ class Game(PropertyGroup):
         set=set_name,
     )
 
+    def get_icon(self) -> int:
+        if game_previews is None or not isfile(self.icon_path):
+            return 0
+
+        if self.icon_path not in game_previews:
+            game_previews.load(self.icon_path, self.icon_path, "IMAGE")
+
+        return game_previews[self.icon_path].icon_id
+
+    def get_description(self) -> str:
+        if not self.install_dir:
+            return ""
+
+        if self.app_id:
+            return f"{self.install_dir} (app {self.app_id})"
+
+        return self.install_dir
+
     search_paths: CollectionProperty(type=GameSearchPath)
     search_path_index: IntProperty(name="Search Path")
 
+    app_id: IntProperty(name="Steam app id", default=0)
+    install_dir: StringProperty(name="Install directory", default="")
+    icon_path: StringProperty(name="Icon", default="", subtype="FILE_PATH")
+
+    texture_brightness: FloatProperty(
+        name="Texture brightness",
+        description="Multiplier for the colors of imported textures",
+        default=1.0,
+        min=0.0,
+        soft_max=4.0,
+    )
+    texture_gamma: FloatProperty(
+        name="Texture gamma",
+        description="Gamma correction of the colors of imported textures, higher values brighten the midtones",
+        default=1.0,
+        min=0.01,
+        soft_min=0.1,
+        soft_max=4.0,
+    )
+
 
 class GameList(UIList):
     bl_idname = "PLUMBER_UL_game_list"
@@ -205,6 +367,8 @@ class GameList(UIList):
         active_data: int,
         active_propname: str,
     ) -> None:
+        icon = item.get_icon() or icon
+
         if self.layout_type in {"DEFAULT", "COMPACT"}:
             layout.prop(item, "name", text="", emboss=False, icon_value=icon)
         elif self.layout_type in {"GRID"}:
@@ -301,11 +465,25 @@ def detect_games(context: Context):
 
     for filesystem in filesystems:
         name = filesystem.name()
+
+        # separate installs of the same game are told apart by their install dirs
+        install_dir = filesystem.install_dir()
+        if install_dir is not None and any(
+            other is not filesystem
+            and other.name() == name
+            and other.install_dir() != install_dir
+            for other in filesystems
+        ):
+            name = f"{name} ({install_dir})"
+
         if any(name == game.name for game in preferences.games):
             continue
         search_paths = filesystem.search_paths()
         game: Game = preferences.games.add()
         game.name = name
+        game.app_id = filesystem.app_id() or 0
+        game.install_dir = install_dir or ""
+        game.icon_path = filesystem.icon_path() or filesystem.header_path() or ""
         for kind, path in search_paths:
             search_path: GameSearchPath = game.search_paths.add()
             search_path.path = path
@@ -346,10 +524,39 @@ class DetectGameinfoOperator(Operator):
         return {"FINISHED"}
 
 
+class DetectDirectoryOperator(Operator):
+    """Add a game from a folder of extracted assets, which has no gameinfo.txt"""
+
+    bl_idname = "plumber.detect_directory"
+    bl_label = """Add a game from a folder of extracted assets"""
+    bl_options = {"REGISTER"}
+
+    directory: StringProperty(
+        name="Directory",
+        maxlen=1024,
+        subtype="DIR_PATH",
+        options={"HIDDEN"},
+    )
+
+    def invoke(self, context: Context, event) -> Set[str]:
+        context.window_manager.fileselect_add(self)
+        return {"RUNNING_MODAL"}
+
+    def execute(self, context: Context) -> Set[str]:
+        try:
+            add_game(context, filesystem_from_directory(self.directory))
+        except (ValueError, OSError) as err:
+            self.report({"ERROR"}, f"could not add the folder: {err}")
+
+        return {"FINISHED"}
+
+
 def detect_gameinfo(path: str, context: Context):
-    preferences: AddonPreferences = context.preferences.addons[__package__].preferences
+    add_game(context, filesystem_from_gameinfo(path))
 
-    filesystem = filesystem_from_gameinfo(path)
+
+def add_game(context: Context, filesystem: FileSystem):
+    preferences: AddonPreferences = context.preferences.addons[__package__].preferences
 
     name = filesystem.name()
     search_paths = filesystem.search_paths()
@@ -406,19 +613,45 @@ class AddonPreferences(AddonPreferences):
         update=update_enable_benchmarking,
     )
 
+    case_insensitive_dirs: BoolProperty(
+        name="Case-insensitive directories",
+        description="Find files in directory search paths regardless of their case, "
+        "like on Windows. Only has an effect on Linux",
+        default=True,
+    )
+
+    def update_log_filter(self, context: Context):
+        try:
+            set_log_filter(self.log_filter)
+        except (ValueError, RuntimeError) as err:
+            print(f"[Plumber] [WARN] {err}")
+
+    log_filter: StringProperty(
+        name="Log filter",
+        description="Which messages are logged to the console, like debug or info,plumber=debug. "
+        "Leave empty to use the PLUMBER_LOG environment variable or the default",
+        default="",
+        update=update_log_filter,
+    )
+
     @staticmethod
     def game_enum_items(
         self: EnumProperty, context: Context
-    ) -> List[Tuple[str, str, str]]:
+    ) -> List[Tuple[str, str, str, int, int]]:
         if context is None:
             context = bpy.context
         preferences: AddonPreferences = context.preferences.addons[
             __package__
         ].preferences
         items = [
-            (str(i), game.name, "") for i, game in enumerate(preferences.games.values())
+            (str(i), game.name, game.get_description(), game.get_icon(), i)
+            for i, game in enumerate(preferences.games.values())
         ]
-        items.append(("NONE", "None", ""))
+        items.append(("NONE", "None", "", 0, len(items)))
+
+        # Blender requires Python to keep references to the generated strings
+        global game_enum_items_cache
+        game_enum_items_cache = items
         return items
 
     def draw(self, context: Context) -> None:
@@ -426,6 +659,9 @@ class AddonPreferences(AddonPreferences):
         layout.prop(self, "enable_file_browser_panel")
         layout.prop(self, "enable_benchmarking")
         layout.prop(self, "threads")
+        layout.prop(self, "log_filter")
+        if sys.platform.startswith("linux"):
+            layout.prop(self, "case_insensitive_dirs")
 
         layout.separator()
         row = layout.row()
@@ -437,6 +673,10 @@ class AddonPreferences(AddonPreferences):
             DetectGameinfoOperator.bl_idname,
             text="Detect from gameinfo.txt",
         )
+        row.operator(
+            DetectDirectoryOperator.bl_idname,
+            text="Add extracted folder",
+        )
 
         layout.label(text="Game Definitions:")
         row = layout.row()
@@ -473,6 +713,12 @@ class AddonPreferences(AddonPreferences):
                 MoveGameSearchPathOperator.bl_idname, text="", icon="TRIA_DOWN"
             ).direction = "DOWN"
 
+            layout.operator(MountWorkshopItemOperator.bl_idname, icon="WORLD")
+
+            row = layout.row()
+            row.prop(game, "texture_brightness")
+            row.prop(game, "texture_gamma")
+
 
 class OpenPreferencesOperator(Operator):
     """Open the preferences of the VMF importer"""
@@ -492,6 +738,7 @@ classes = (
     AddGameSearchPathOperator,
     RemoveGameSearchPathOperator,
     MoveGameSearchPathOperator,
+    MountWorkshopItemOperator,
     Game,
     GameList,
     AddGameOperator,
@@ -499,12 +746,16 @@ classes = (
     MoveGameOperator,
     DetectGamesOperator,
     DetectGameinfoOperator,
+    DetectDirectoryOperator,
     AddonPreferences,
     OpenPreferencesOperator,
 )
 
 
 def register():
+    global game_previews
+    game_previews = bpy.utils.previews.new()
+
     for cls in classes:
         bpy.utils.register_class(cls)
 
@@ -515,10 +766,18 @@ def register():
     if preferences.threads == 0:
         preferences.threads = max(2, os.cpu_count() or 0)
 
+    if preferences.log_filter:
+        preferences.update_log_filter(bpy.context)
+
     if not preferences.games:
         detect_games(bpy.context)
 
 
 def unregister():
+    global game_previews
+
     for cls in reversed(classes):
         bpy.utils.unregister_class(cls)
+
+    bpy.utils.previews.remove(game_previews)
+    game_previews = None
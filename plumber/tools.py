@@ -1,4 +1,5 @@
-from os.path import isabs, dirname
+from math import ceil
+from os.path import basename, isabs, dirname
 from typing import Collection, Optional, Set
 
 from bpy.types import Operator, Context, UIList, UILayout, PropertyGroup, Menu, Panel
@@ -17,6 +18,7 @@ from .importer import (
     GameFileImporterOperator,
     GameFileImporterOperatorProps,
     ImporterOperatorProps,
+    add_recent_entry,
     update_recent_entries,
 )
 
@@ -53,10 +55,17 @@ def object_menu_func(self: bpy.types.Menu, context: bpy.types.Context) -> None:
 
 
 FILE_IMPORTERS = {
+    "bsp": "import_scene.plumber_vmf",
     "mdl": "import_scene.plumber_mdl",
     "vmt": "import_scene.plumber_vmt",
     "vmf": "import_scene.plumber_vmf",
     "vtf": "import_scene.plumber_vtf",
+    "wad": "import_scene.plumber_wad",
+    "dmx": "import_scene.plumber_dmx",
+    "vcd": "import_scene.plumber_vcd",
+    "vmat": "import_scene.plumber_vmat",
+    "vmat_c": "import_scene.plumber_vmat",
+    "vtex_c": "import_scene.plumber_vtex",
 }
 
 
@@ -233,9 +242,16 @@ class GameRecentEntriesItem(PropertyGroup):
     recent_entries: CollectionProperty(type=RecentEntry)
 
 
+# large directories are listed in pages to keep the ui responsive
+BROWSER_PAGE_SIZE = 500
+
+
 class GameFileBrowser:
     browser: Optional[FileBrowser] = None
     path: str
+    search: str
+    page: int
+    bookmarked: bool
 
     def __init_subclass__(cls) -> None:
         # unfortunately the self passed to property updates
@@ -243,6 +259,29 @@ class GameFileBrowser:
         # so we need to make update_path browser reading
         # not dependent on self
 
+        def load_entries(self: GameFileBrowser) -> int:
+            count, entries = cls.browser.read_dir(
+                self.path,
+                search=self.search or None,
+                offset=(self.page - 1) * BROWSER_PAGE_SIZE,
+                limit=BROWSER_PAGE_SIZE,
+            )
+            self.page_count = max(1, ceil(count / BROWSER_PAGE_SIZE))
+            # pages past the end are read from the last page,
+            # assign directly to not trigger the update
+            self["page"] = min(self.page, self.page_count)
+            self.entries.clear()
+
+            for entry in entries:
+                bl_entry: DirEntry = self.entries.add()
+                bl_entry.name = entry.name()
+                bl_entry.kind = entry.kind()
+                bl_entry.path = f"{self.path}/{bl_entry.name}"
+
+            self.entry_index = -1
+
+            return count
+
         def update_path(self: GameFileBrowser, context: Context):
             if isabs(self.path):
                 self.path = ""
@@ -253,18 +292,10 @@ class GameFileBrowser:
                 self.path = normalized
                 return
 
-            entries = cls.browser.read_dir(self.path)
-            self.entries.clear()
-
-            for entry in entries:
-                bl_entry: DirEntry = self.entries.add()
-                bl_entry.name = entry.name()
-                bl_entry.kind = entry.kind()
-                bl_entry.path = f"{self.path}/{bl_entry.name}"
+            self["page"] = 1
+            self["search"] = ""
 
-            self.entry_index = -1
-
-            if len(entries) == 0:
+            if load_entries(self) == 0:
                 for ext, get_operator in FILE_IMPORTER_OPERATORS.items():
                     if self.path.endswith(ext):
                         get_operator()(
@@ -276,12 +307,51 @@ class GameFileBrowser:
                         self.path = dirname(self.path)
                         return
 
+        def update_listing(self: GameFileBrowser, context: Context):
+            load_entries(self)
+
+        def load_bookmarks(self: GameFileBrowser):
+            self.bookmarks_temp.clear()
+
+            for path in cls.browser.bookmarks():
+                entry: RecentEntry = self.bookmarks_temp.add()
+                entry.name = basename(path)
+                entry.path = path
+
+        def get_bookmarked(self: GameFileBrowser) -> bool:
+            return any(entry.path == self.path for entry in self.bookmarks_temp)
+
+        def set_bookmarked(self: GameFileBrowser, value: bool):
+            try:
+                cls.browser.set_bookmarked(self.path, value)
+            except OSError as err:
+                print(f"[Plumber] [WARN] could not save bookmarks: {err}")
+
+            load_bookmarks(self)
+
+        cls.load_bookmarks = load_bookmarks
+        cls.__annotations__["bookmarked"] = BoolProperty(
+            name="Bookmark",
+            description="Bookmark the current directory for all sessions",
+            get=get_bookmarked,
+            set=set_bookmarked,
+        )
+
         cls.update_path = update_path
         cls.__annotations__["path"] = StringProperty(name="Path", update=update_path)
+        cls.__annotations__["search"] = StringProperty(
+            name="Search",
+            description="Only list the entries of this directory containing this text",
+            update=update_listing,
+        )
+        cls.__annotations__["page"] = IntProperty(
+            name="Page", default=1, min=1, update=update_listing
+        )
 
     game_id: IntProperty(default=-1)
 
     entries: CollectionProperty(type=DirEntry)
+    page_count: IntProperty(default=1)
 
     def update_entry_index(self, context: Context):
         if self.entry_index != -1:
@@ -326,6 +396,20 @@ class GameFileBrowser:
         update=update_recent_entry_index,
     )
 
+    bookmarks_temp: CollectionProperty(type=RecentEntry)
+
+    def update_bookmark_index(self, context: Context):
+        if self.bookmark_index != -1:
+            entry: RecentEntry = self.bookmarks_temp[self.bookmark_index]
+            self.path = entry.path
+            self.bookmark_index = -1
+
+    bookmark_index: IntProperty(
+        default=-1,
+        name="Bookmark",
+        update=update_bookmark_index,
+    )
+
     def open_game(self, context: Context):
         preferences: AddonPreferences = context.preferences.addons[
             __package__
@@ -347,6 +431,14 @@ class GameFileBrowser:
                 recent_entry_temp.name = recent_entry.name
                 recent_entry_temp.path = recent_entry.path
 
+        # directories of imports in previous sessions
+        for path in reversed(type(self).browser.recent_imports()):
+            directory = dirname(path)
+            if all(entry.path != directory for entry in self.recent_entries_temp):
+                add_recent_entry(directory, self.recent_entries_temp)
+
+        self.load_bookmarks()
+
     def draw_browser(self, layout: UILayout):
         layout.label(text="Files:")
         row = layout.row()
@@ -357,6 +449,15 @@ class GameFileBrowser:
 
         row.prop(self, "path", text="")
 
+        button_layout = row.column()
+        button_layout.enabled = bool(self.path)
+        button_layout.prop(
+            self,
+            "bookmarked",
+            icon="BOOKMARKS" if self.bookmarked else "ADD",
+            icon_only=True,
+        )
+
         layout.template_list(
             DirEntryList.bl_idname,
             "",
@@ -368,6 +469,11 @@ class GameFileBrowser:
             maxrows=15,
         )
 
+        row = layout.row()
+        row.prop(self, "search", text="", icon="VIEWZOOM")
+        if self.page_count > 1:
+            row.prop(self, "page", text=f"Page (of {self.page_count})")
+
         operator: ExtractGameDirectory = layout.operator(
             ExtractGameDirectory.bl_idname, text="Extract all"
         )
@@ -395,6 +501,18 @@ class GameFileBrowser:
             sort_lock=True,
         )
 
+        layout.label(text="Bookmarks:")
+        layout.template_list(
+            RecentEntryList.bl_idname,
+            "bookmarks",
+            self,
+            "bookmarks_temp",
+            self,
+            "bookmark_index",
+            rows=5,
+            maxrows=10,
+        )
+
 
 class GameFileBrowserPropertyGroup(PropertyGroup, GameFileBrowser):
     def update_game(self, context: Context):
@@ -475,6 +593,7 @@ class GameFileBrowserOperator(Operator, GameFileBrowser):
         self.open_game(context)
 
         update_recent_entries.browser_operator_entries = self.recent_entries_temp
+        update_recent_entries.file_browser = type(self).browser
 
         return context.window_manager.invoke_props_dialog(self)
 
@@ -483,6 +602,7 @@ class GameFileBrowserOperator(Operator, GameFileBrowser):
 
     def execute(self, context: Context) -> Set[str]:
         update_recent_entries.browser_operator_entries = None
+        update_recent_entries.file_browser = None
         return {"CANCELLED"}
 
 
@@ -0,0 +1,395 @@
+//! Decodes the BC4, BC5 and BC7 blocks of compiled Source 2 textures, which vtfs don't
+//! use. BC7 blocks have one of 8 modes, which split the block into up to 3 subsets with
+//! their own endpoints.
+
+use image::RgbaImage;
+
+use super::cubemap::{decode_blocks, decode_channel_block};
+
+/// Pixels of the second subset of the 2-subset partitions, as bits from the first pixel.
+const PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80, 0xC800, 0xFFEC, 0xFE80, 0xE800,
+    0xFFE8, 0xFF00, 0xFFF0, 0xF000, 0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C, 0xAAAA, 0xF0F0, 0x5A5A, 0x33CC,
+    0x3C3C, 0x55AA, 0x9696, 0xA55A, 0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C, 0x9336, 0x9CC6, 0x817E, 0xE718,
+    0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+/// Subsets of each pixel of the 3-subset partitions.
+const PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// Pixels whose index has one bit less, in the second subset of 2-subset partitions.
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor pixels of the second subset of 3-subset partitions.
+const ANCHORS_3_SECOND: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5,
+    15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5,
+    10, 8, 13, 15, 12, 3, 3,
+];
+
+/// Anchor pixels of the third subset of 3-subset partitions.
+const ANCHORS_3_THIRD: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6,
+    10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+const WEIGHTS_2: [u16; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u16; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u16; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+struct Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    /// A p-bit for each endpoint, or only for each subset.
+    endpoint_p_bits: bool,
+    shared_p_bits: bool,
+    index_bits: u32,
+    /// Bits of the separate alpha indices of modes 4 and 5.
+    secondary_index_bits: u32,
+}
+
+#[rustfmt::skip]
+const MODES: [Mode; 8] = [
+    Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 3, secondary_index_bits: 0 },
+    Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: true, index_bits: 3, secondary_index_bits: 0 },
+    Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+    Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+    Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 3 },
+    Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 2 },
+    Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7, endpoint_p_bits: true, shared_p_bits: false, index_bits: 4, secondary_index_bits: 0 },
+    Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+];
+
+/// Decodes BC4 blocks, with the single channel as a gray color.
+pub fn decode_bc4(data: &[u8], width: u32, height: u32) -> Option<RgbaImage> {
+    decode_blocks(data, width, height, 8, |block, out| {
+        decode_channel_block(block, 0, out);
+
+        for pixel in out.iter_mut() {
+            *pixel = [pixel[0], pixel[0], pixel[0], u8::MAX];
+        }
+    })
+}
+
+/// Decodes BC5 blocks of a normal map, with the Z of the normals reconstructed into the
+/// blue channel.
+pub fn decode_bc5(data: &[u8], width: u32, height: u32) -> Option<RgbaImage> {
+    decode_blocks(data, width, height, 16, |block, out| {
+        decode_channel_block(&block[..8], 0, out);
+        decode_channel_block(&block[8..], 1, out);
+
+        for pixel in out.iter_mut() {
+            let [x, y] = [pixel[0], pixel[1]].map(|c| f32::from(c) / 127.5 - 1.0);
+            let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let z = ((z + 1.0) * 127.5).round() as u8;
+            pixel[2] = z;
+            pixel[3] = u8::MAX;
+        }
+    })
+}
+
+pub fn decode_bc7(data: &[u8], width: u32, height: u32) -> Option<RgbaImage> {
+    decode_blocks(data, width, height, 16, decode_bc7_block)
+}
+
+struct Bits {
+    bits: u128,
+    pos: u32,
+}
+
+impl Bits {
+    #[allow(clippy::cast_possible_truncation)]
+    fn take(&mut self, count: u32) -> u8 {
+        let value = (self.bits >> self.pos) & ((1 << count) - 1);
+        self.pos += count;
+
+        value as u8
+    }
+}
+
+fn decode_bc7_block(block: &[u8], out: &mut [[u8; 4]; 16]) {
+    let bits = u128::from_le_bytes(block.try_into().expect("block should be 16 bytes"));
+    let mode_index = bits.trailing_zeros();
+
+    // reserved modes decode as transparent black
+    let Some(mode) = MODES.get(mode_index as usize) else {
+        *out = [[0; 4]; 16];
+        return;
+    };
+
+    let mut bits = Bits {
+        bits,
+        pos: mode_index + 1,
+    };
+
+    let partition = usize::from(bits.take(mode.partition_bits));
+    let rotation = bits.take(mode.rotation_bits);
+    let index_selection = bits.take(mode.index_selection_bits);
+
+    let endpoints = read_endpoints(mode, &mut bits);
+
+    let subset_of = |pixel: usize| -> usize {
+        match mode.subsets {
+            2 => usize::from((PARTITIONS_2[partition] >> pixel) & 1),
+            3 => usize::from(PARTITIONS_3[partition][pixel]),
+            _ => 0,
+        }
+    };
+
+    let is_anchor = |pixel: usize| -> bool {
+        pixel == 0
+            || match mode.subsets {
+                2 => pixel == usize::from(ANCHORS_2[partition]),
+                3 => {
+                    pixel == usize::from(ANCHORS_3_SECOND[partition])
+                        || pixel == usize::from(ANCHORS_3_THIRD[partition])
+                }
+                _ => false,
+            }
+    };
+
+    let mut indices = [0_u8; 16];
+    for (pixel, index) in indices.iter_mut().enumerate() {
+        *index = bits.take(mode.index_bits - u32::from(is_anchor(pixel)));
+    }
+
+    let mut secondary_indices = [0_u8; 16];
+    if mode.secondary_index_bits > 0 {
+        for (pixel, index) in secondary_indices.iter_mut().enumerate() {
+            *index = bits.take(mode.secondary_index_bits - u32::from(pixel == 0));
+        }
+    }
+
+    for (pixel, color) in out.iter_mut().enumerate() {
+        let [start, end] = endpoints[subset_of(pixel)];
+
+        let (color_weight, alpha_weight) = if mode.secondary_index_bits == 0 {
+            let weight = weight(mode.index_bits, indices[pixel]);
+            (weight, weight)
+        } else {
+            let primary = weight(mode.index_bits, indices[pixel]);
+            let secondary = weight(mode.secondary_index_bits, secondary_indices[pixel]);
+
+            if index_selection == 0 {
+                (primary, secondary)
+            } else {
+                (secondary, primary)
+            }
+        };
+
+        for (channel, value) in color.iter_mut().enumerate() {
+            let weight = if channel == 3 {
+                alpha_weight
+            } else {
+                color_weight
+            };
+
+            *value = interpolate(start[channel], end[channel], weight);
+        }
+
+        match rotation {
+            1 => color.swap(0, 3),
+            2 => color.swap(1, 3),
+            3 => color.swap(2, 3),
+            _ => {}
+        }
+    }
+}
+
+/// Reads the endpoints of the subsets, by subset, endpoint and channel, expanded
+/// into 8 bits.
+fn read_endpoints(mode: &Mode, bits: &mut Bits) -> [[[u8; 4]; 2]; 3] {
+    let mut endpoints = [[[0_u8; 4]; 2]; 3];
+
+    for channel in 0..4 {
+        let channel_bits = if channel == 3 {
+            mode.alpha_bits
+        } else {
+            mode.color_bits
+        };
+
+        for subset in &mut endpoints[..mode.subsets] {
+            for endpoint in subset.iter_mut() {
+                endpoint[channel] = bits.take(channel_bits);
+            }
+        }
+    }
+
+    let mut p_bits = [[0_u8; 2]; 3];
+
+    if mode.endpoint_p_bits {
+        for subset in &mut p_bits[..mode.subsets] {
+            for p_bit in subset.iter_mut() {
+                *p_bit = bits.take(1);
+            }
+        }
+    } else if mode.shared_p_bits {
+        for subset in &mut p_bits[..mode.subsets] {
+            *subset = [bits.take(1); 2];
+        }
+    }
+
+    let has_p_bits = mode.endpoint_p_bits || mode.shared_p_bits;
+
+    for (subset, p_bits) in endpoints.iter_mut().zip(p_bits) {
+        for (endpoint, p_bit) in subset.iter_mut().zip(p_bits) {
+            for (channel, value) in endpoint.iter_mut().enumerate() {
+                let mut channel_bits = if channel == 3 {
+                    mode.alpha_bits
+                } else {
+                    mode.color_bits
+                };
+
+                if channel_bits == 0 {
+                    *value = u8::MAX;
+                    continue;
+                }
+
+                if has_p_bits {
+                    *value = (*value << 1) | p_bit;
+                    channel_bits += 1;
+                }
+
+                *value = expand(*value, channel_bits);
+            }
+        }
+    }
+
+    endpoints
+}
+
+/// Expands an endpoint channel of `bits` bits into 8 bits, replicating its high bits.
+#[allow(clippy::cast_possible_truncation)]
+fn expand(value: u8, bits: u32) -> u8 {
+    let value = u16::from(value) << (8 - bits);
+
+    (value | (value >> bits)) as u8
+}
+
+fn weight(bits: u32, index: u8) -> u16 {
+    let index = usize::from(index);
+
+    match bits {
+        2 => WEIGHTS_2[index],
+        3 => WEIGHTS_3[index],
+        _ => WEIGHTS_4[index],
+    }
+}
+
+fn interpolate(start: u8, end: u8, weight: u16) -> u8 {
+    let value = ((64 - weight) * u16::from(start) + weight * u16::from(end) + 32) >> 6;
+    u8::try_from(value).unwrap_or(u8::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_bc7_mode_6() {
+        let mut block = 1_u128 << 6;
+        let mut pos = 7;
+        let mut push = |value: u128, bits: u32| {
+            block |= value << pos;
+            pos += bits;
+        };
+
+        // each channel goes from 0 to 127, which is 255 with the p-bit
+        for _ in 0..4 {
+            push(0, 7);
+            push(127, 7);
+        }
+        push(0, 1);
+        push(1, 1);
+
+        // the first pixel is the start, the second the end, the rest in between
+        push(0, 3);
+        push(15, 4);
+        for _ in 2..16 {
+            push(7, 4);
+        }
+
+        let image = decode_bc7(&block.to_le_bytes(), 4, 4).unwrap();
+        assert_eq!(image.get_pixel(0, 0).0, [0; 4]);
+        assert_eq!(image.get_pixel(1, 0).0, [255; 4]);
+        assert_eq!(image.get_pixel(2, 0).0, [120; 4]);
+    }
+}
@@ -5,7 +5,16 @@ use itertools::Either;
 use plumber_core::vmf::builder::{BuiltBrushEntity, BuiltSolid, MergedSolids, SolidFace};
 use pyo3::{prelude::*, types::PyList};
 
-use super::utils::linear_to_srgb;
+use crate::{
+    brush_solidity::{BrushSolidities, BrushSolidity},
+    disp_neighbors::{DispNeighbors, StitchMesh},
+    gameplay::GameplayMarker,
+    lightmap_uvs::{lightmap_uvs, LightmapSides},
+    skybox::SkyboxArea,
+    smoothing_groups::{polygon_normal, SmoothingGroups},
+};
+
+use super::{material_filter::MaterialFilter, utils::linear_to_srgb};
 
 #[pyclass(module = "plumber", name = "MergedSolids")]
 pub struct PyMergedSolids {
@@ -18,6 +27,10 @@ pub struct PyMergedSolids {
     flat_polygon_vertice_indices: Vec<usize>,
     flat_loop_uvs: Vec<f32>,
     flat_loop_colors: Vec<f32>,
+    smoothing_groups: Option<Vec<u32>>,
+    flat_loop_lightmap_uvs: Option<Vec<f32>>,
+    displacements: Option<Vec<bool>>,
+    in_3d_skybox: bool,
 }
 
 #[pymethods]
@@ -79,35 +92,133 @@ impl PyMergedSolids {
         mem::take(&mut self.flat_loop_colors)
     }
 
+    fn loop_blend_factors(&self) -> Option<Vec<f32>> {
+        get_flat_loop_blend_factors(&self.faces)
+    }
+
     fn materials(&mut self) -> Vec<String> {
         mem::take(&mut self.materials)
     }
-}
 
-impl PyMergedSolids {
-    fn new(merged: MergedSolids) -> Self {
-        let flat_vertices = merged.vertices.iter().flat_map(Vec3::to_array).collect();
+    fn polygon_smoothing_groups(&mut self) -> Option<Vec<u32>> {
+        self.smoothing_groups.take()
+    }
 
-        let flat_polygon_vertice_indices = get_flat_polygon_vertice_indices(&merged.faces);
-        let flat_loop_uvs = get_flat_loop_uvs(&merged.faces);
-        let flat_loop_colors = get_flat_loop_colors(&merged.faces);
+    fn loop_lightmap_uvs(&mut self) -> Option<Vec<f32>> {
+        self.flat_loop_lightmap_uvs.take()
+    }
 
-        Self {
-            no_draw: merged.materials.iter().all(|m| m.info.no_draw()),
-            position: [0.0, 0.0, 0.0],
-            scale: [merged.scale, merged.scale, merged.scale],
-            faces: merged.faces,
-            materials: merged
+    fn polygon_displacements(&mut self) -> Option<Vec<bool>> {
+        self.displacements.take()
+    }
+
+    fn in_3d_skybox(&self) -> bool {
+        self.in_3d_skybox
+    }
+}
+
+impl PyMergedSolids {
+    fn new(mut merged: MergedSolids, filter: &MaterialFilter) -> Self {
+        strip_faces(
+            &mut merged.vertices,
+            &mut merged.faces,
+            &mut merged.materials,
+            |m| filter.keeps(&m.name.to_string()),
+        );
+
+        let no_draw = merged.materials.iter().all(|m| m.info.no_draw());
+
+        Self::from_parts(
+            &merged.vertices,
+            merged.faces,
+            merged
                 .materials
                 .into_iter()
                 .map(|m| m.name.into_string())
                 .collect(),
+            no_draw,
+            merged.scale,
+        )
+    }
+
+    fn from_parts(
+        vertices: &[Vec3],
+        faces: Vec<SolidFace>,
+        materials: Vec<String>,
+        no_draw: bool,
+        scale: f32,
+    ) -> Self {
+        let flat_vertices = vertices.iter().flat_map(Vec3::to_array).collect();
+
+        let flat_polygon_vertice_indices = get_flat_polygon_vertice_indices(&faces);
+        let flat_loop_uvs = get_flat_loop_uvs(&faces);
+        let flat_loop_colors = get_flat_loop_colors(&faces);
+
+        Self {
+            no_draw,
+            position: [0.0, 0.0, 0.0],
+            scale: [scale, scale, scale],
+            faces,
+            materials,
             flat_vertices,
             flat_polygon_vertice_indices,
             flat_loop_uvs,
             flat_loop_colors,
+            smoothing_groups: None,
+            flat_loop_lightmap_uvs: None,
+            displacements: None,
+            in_3d_skybox: false,
         }
     }
+
+    /// Moves the faces inside the skybox area to separate merged solids.
+    /// Returns `None` if the faces don't need to be split.
+    fn split_skybox(&mut self, area: &SkyboxArea) -> Option<Self> {
+        let mut vertices: Vec<Vec3> = self
+            .flat_vertices
+            .chunks_exact(3)
+            .map(Vec3::from_slice)
+            .collect();
+
+        let (mut skybox_faces, mut faces): (Vec<_>, Vec<_>) = mem::take(&mut self.faces)
+            .into_iter()
+            .partition(|f| area.contains(face_center(&vertices, f)));
+
+        if skybox_faces.is_empty() {
+            self.faces = faces;
+            return None;
+        }
+
+        if faces.is_empty() {
+            self.faces = skybox_faces;
+            self.in_3d_skybox = true;
+            return None;
+        }
+
+        let mut skybox_vertices = vertices.clone();
+        let mut skybox_materials = self.materials.clone();
+        compact_faces(
+            &mut skybox_vertices,
+            &mut skybox_faces,
+            &mut skybox_materials,
+        );
+
+        let mut materials = mem::take(&mut self.materials);
+        compact_faces(&mut vertices, &mut faces, &mut materials);
+
+        *self = Self::from_parts(&vertices, faces, materials, self.no_draw, self.scale[0]);
+
+        Some(Self {
+            in_3d_skybox: true,
+            ..Self::from_parts(
+                &skybox_vertices,
+                skybox_faces,
+                skybox_materials,
+                self.no_draw,
+                self.scale[0],
+            )
+        })
+    }
 }
 
 #[pyclass(module = "plumber", name = "BuiltSolid")]
@@ -122,6 +233,10 @@ pub struct PyBuiltSolid {
     flat_polygon_vertice_indices: Vec<usize>,
     flat_loop_uvs: Vec<f32>,
     flat_loop_colors: Vec<f32>,
+    smoothing_groups: Option<Vec<u32>>,
+    flat_loop_lightmap_uvs: Option<Vec<f32>>,
+    displacements: Option<Vec<bool>>,
+    in_3d_skybox: bool,
 }
 
 #[pymethods]
@@ -187,13 +302,40 @@ impl PyBuiltSolid {
         mem::take(&mut self.flat_loop_colors)
     }
 
+    fn loop_blend_factors(&self) -> Option<Vec<f32>> {
+        get_flat_loop_blend_factors(&self.faces)
+    }
+
     fn materials(&mut self) -> Vec<String> {
         mem::take(&mut self.materials)
     }
+
+    fn polygon_smoothing_groups(&mut self) -> Option<Vec<u32>> {
+        self.smoothing_groups.take()
+    }
+
+    fn loop_lightmap_uvs(&mut self) -> Option<Vec<f32>> {
+        self.flat_loop_lightmap_uvs.take()
+    }
+
+    fn polygon_displacements(&mut self) -> Option<Vec<bool>> {
+        self.displacements.take()
+    }
+
+    fn in_3d_skybox(&self) -> bool {
+        self.in_3d_skybox
+    }
 }
 
 impl PyBuiltSolid {
-    fn new(solid: BuiltSolid) -> Self {
+    fn new(mut solid: BuiltSolid, filter: &MaterialFilter) -> Self {
+        strip_faces(
+            &mut solid.vertices,
+            &mut solid.faces,
+            &mut solid.materials,
+            |m| filter.keeps(&m.name.to_string()),
+        );
+
         let flat_vertices = solid.vertices.iter().flat_map(Vec3::to_array).collect();
 
         let flat_polygon_vertice_indices = get_flat_polygon_vertice_indices(&solid.faces);
@@ -215,6 +357,10 @@ impl PyBuiltSolid {
             flat_polygon_vertice_indices,
             flat_loop_uvs,
             flat_loop_colors,
+            smoothing_groups: None,
+            flat_loop_lightmap_uvs: None,
+            displacements: None,
+            in_3d_skybox: false,
         }
     }
 }
@@ -223,7 +369,10 @@ impl PyBuiltSolid {
 pub struct PyBuiltBrushEntity {
     pub id: i32,
     class_name: String,
+    gameplay_marker: Option<GameplayMarker>,
+    solidity: BrushSolidity,
     merged_solids: Option<PyMergedSolids>,
+    skybox_merged_solids: Option<PyMergedSolids>,
     solids: Vec<PyBuiltSolid>,
 }
 
@@ -237,24 +386,365 @@ impl PyBuiltBrushEntity {
         &self.class_name
     }
 
+    /// Kind of gameplay location the entity marks, like `bombsite`.
+    fn gameplay_marker(&self) -> Option<&str> {
+        self.gameplay_marker.map(GameplayMarker::name)
+    }
+
+    fn gameplay_label(&self) -> Option<&str> {
+        self.gameplay_marker.map(GameplayMarker::label)
+    }
+
+    /// Whether the brush collides when the map starts.
+    fn solid(&self) -> bool {
+        self.solidity.solid
+    }
+
+    /// `Solidity` of a `func_brush`, 0 toggling with the entity, 1 never and 2 always
+    /// solid.
+    fn solidity(&self) -> Option<i32> {
+        self.solidity.solidity
+    }
+
+    fn start_disabled(&self) -> bool {
+        self.solidity.start_disabled
+    }
+
+    fn spawnflags(&self) -> u32 {
+        self.solidity.spawnflags
+    }
+
+    fn collision_group(&self) -> Option<i32> {
+        self.solidity.collision_group
+    }
+
     fn merged_solids(&mut self) -> Option<PyMergedSolids> {
         self.merged_solids.take()
     }
 
+    /// Merged solids split off into the 3D skybox, if the rest of the merged solids
+    /// are outside of it.
+    fn skybox_merged_solids(&mut self) -> Option<PyMergedSolids> {
+        self.skybox_merged_solids.take()
+    }
+
     fn solids(&mut self) -> Vec<PyBuiltSolid> {
         mem::take(&mut self.solids)
     }
 }
 
 impl PyBuiltBrushEntity {
-    pub fn new(brush: BuiltBrushEntity) -> Self {
+    pub fn new(brush: BuiltBrushEntity, filter: &MaterialFilter) -> Self {
         Self {
             id: brush.id,
             class_name: brush.class_name.to_owned(),
-            merged_solids: brush.merged_solids.map(PyMergedSolids::new),
-            solids: brush.solids.into_iter().map(PyBuiltSolid::new).collect(),
+            gameplay_marker: GameplayMarker::from_class_name(brush.class_name),
+            solidity: BrushSolidity::default(),
+            merged_solids: brush
+                .merged_solids
+                .map(|merged| PyMergedSolids::new(merged, filter))
+                .filter(|merged| !merged.faces.is_empty()),
+            skybox_merged_solids: None,
+            solids: brush
+                .solids
+                .into_iter()
+                .map(|solid| PyBuiltSolid::new(solid, filter))
+                .filter(|solid| !solid.faces.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Marks the solids inside the 3D skybox, splitting the merged solids if needed.
+    /// Must be applied before the other vmf data, which is computed per mesh.
+    pub fn apply_skybox_area(&mut self, area: &SkyboxArea) {
+        if let Some(merged) = &mut self.merged_solids {
+            self.skybox_merged_solids = merged.split_skybox(area);
+        }
+
+        for solid in &mut self.solids {
+            solid.in_3d_skybox = area.contains(Vec3::from(solid.position) / solid.scale[0]);
+        }
+    }
+
+    pub fn apply_brush_solidities(&mut self, solidities: &BrushSolidities) {
+        self.solidity = solidities.get(self.id, &self.class_name);
+    }
+
+    /// Stitches the displacements of the brush to their neighbors, welding the vertices
+    /// of merged solids, and marks the faces of the displacements. Must be applied before
+    /// the data computed from the vertices.
+    pub fn apply_disp_neighbors(&mut self, neighbors: &DispNeighbors) {
+        if !neighbors.has_displacements() {
+            return;
         }
+
+        let mut meshes: Vec<StitchMesh> = self
+            .merged_solids
+            .iter()
+            .chain(&self.skybox_merged_solids)
+            .map(|merged| {
+                stitch_mesh(
+                    &merged.flat_vertices,
+                    &merged.faces,
+                    &merged.materials,
+                    Vec3::ZERO,
+                )
+            })
+            .chain(self.solids.iter().map(|solid| {
+                let offset = Vec3::from(solid.position) / solid.scale[0];
+                stitch_mesh(&solid.flat_vertices, &solid.faces, &solid.materials, offset)
+            }))
+            .collect();
+
+        let remaps = neighbors.stitch(&mut meshes);
+        let mut displacements = neighbors.displacement_faces(&meshes).into_iter();
+        let mut stitched = meshes.into_iter().map(|m| m.vertices).zip(remaps);
+
+        for (merged, (vertices, remap)) in self
+            .merged_solids
+            .iter_mut()
+            .chain(&mut self.skybox_merged_solids)
+            .zip(&mut stitched)
+        {
+            apply_stitched(
+                vertices,
+                &remap,
+                Vec3::ZERO,
+                &mut merged.flat_vertices,
+                &mut merged.faces,
+                &mut merged.materials,
+            );
+            merged.flat_polygon_vertice_indices = get_flat_polygon_vertice_indices(&merged.faces);
+            merged.displacements = displacements.next();
+        }
+
+        for (solid, (vertices, remap)) in self.solids.iter_mut().zip(stitched) {
+            let offset = Vec3::from(solid.position) / solid.scale[0];
+
+            apply_stitched(
+                vertices,
+                &remap,
+                offset,
+                &mut solid.flat_vertices,
+                &mut solid.faces,
+                &mut solid.materials,
+            );
+            solid.flat_polygon_vertice_indices = get_flat_polygon_vertice_indices(&solid.faces);
+            solid.displacements = displacements.next();
+        }
+    }
+
+    /// Looks up the smoothing groups of the faces, which need to be matched to the vmf sides.
+    pub fn apply_smoothing_groups(&mut self, groups: &SmoothingGroups) {
+        if groups.is_empty() {
+            return;
+        }
+
+        for merged in self
+            .merged_solids
+            .iter_mut()
+            .chain(&mut self.skybox_merged_solids)
+        {
+            merged.smoothing_groups = get_smoothing_groups(
+                groups,
+                None,
+                &merged.flat_vertices,
+                &merged.faces,
+                Vec3::ZERO,
+            );
+        }
+
+        for solid in &mut self.solids {
+            // solid vertices are relative to the solid, which is positioned after scaling
+            let offset = Vec3::from(solid.position) / solid.scale[0];
+
+            solid.smoothing_groups = get_smoothing_groups(
+                groups,
+                Some(solid.id),
+                &solid.flat_vertices,
+                &solid.faces,
+                offset,
+            );
+        }
+    }
+
+    /// Lays out a lightmap for each merged solid or solid of the brush.
+    pub fn apply_lightmap_uvs(&mut self, sides: &LightmapSides) {
+        for merged in self
+            .merged_solids
+            .iter_mut()
+            .chain(&mut self.skybox_merged_solids)
+        {
+            merged.flat_loop_lightmap_uvs = Some(lightmap_uvs(
+                sides,
+                None,
+                &get_face_vertices(&merged.flat_vertices, &merged.faces, Vec3::ZERO),
+            ));
+        }
+
+        for solid in &mut self.solids {
+            let offset = Vec3::from(solid.position) / solid.scale[0];
+
+            solid.flat_loop_lightmap_uvs = Some(lightmap_uvs(
+                sides,
+                Some(solid.id),
+                &get_face_vertices(&solid.flat_vertices, &solid.faces, offset),
+            ));
+        }
+    }
+
+    /// Returns if all faces of the brush were filtered out.
+    pub fn is_empty(&self) -> bool {
+        self.merged_solids.is_none() && self.solids.is_empty()
+    }
+}
+
+/// Removes the faces whose material isn't kept,
+/// along with the vertices and materials no longer used by any face.
+fn strip_faces<M>(
+    vertices: &mut Vec<Vec3>,
+    faces: &mut Vec<SolidFace>,
+    materials: &mut Vec<M>,
+    keep: impl Fn(&M) -> bool,
+) {
+    let kept: Vec<bool> = materials.iter().map(keep).collect();
+
+    if kept.iter().all(|&k| k) {
+        return;
+    }
+
+    faces.retain(|f| kept[f.material_index]);
+    compact_faces(vertices, faces, materials);
+}
+
+/// Removes the vertices and materials not used by any face.
+fn compact_faces<M>(vertices: &mut Vec<Vec3>, faces: &mut [SolidFace], materials: &mut Vec<M>) {
+    let mut material_indices = vec![None; materials.len()];
+    let mut vertice_indices = vec![None; vertices.len()];
+    let mut new_materials = Vec::new();
+    let mut new_vertices = Vec::new();
+    let mut old_materials: Vec<_> = mem::take(materials).into_iter().map(Some).collect();
+
+    for face in faces.iter_mut() {
+        let material_index = face.material_index;
+
+        face.material_index = *material_indices[material_index].get_or_insert_with(|| {
+            new_materials.push(old_materials[material_index].take().unwrap());
+            new_materials.len() - 1
+        });
+
+        for index in &mut face.vertice_indices {
+            let vertice_index = *index;
+
+            *index = *vertice_indices[vertice_index].get_or_insert_with(|| {
+                new_vertices.push(vertices[vertice_index]);
+                new_vertices.len() - 1
+            });
+        }
+    }
+
+    *materials = new_materials;
+    *vertices = new_vertices;
+}
+
+/// Returns the vertices of a mesh in vmf units, with the faces and their materials.
+fn stitch_mesh<'a>(
+    flat_vertices: &[f32],
+    faces: &'a [SolidFace],
+    materials: &'a [String],
+    offset: Vec3,
+) -> StitchMesh<'a> {
+    StitchMesh {
+        vertices: flat_vertices
+            .chunks_exact(3)
+            .map(|v| Vec3::from_slice(v) + offset)
+            .collect(),
+        faces: faces.iter().map(|f| f.vertice_indices.as_slice()).collect(),
+        materials: faces
+            .iter()
+            .map(|f| materials[f.material_index].as_str())
+            .collect(),
+    }
+}
+
+/// Writes the stitched vertices back, removing the vertices welded to others.
+fn apply_stitched(
+    mut vertices: Vec<Vec3>,
+    remap: &[usize],
+    offset: Vec3,
+    flat_vertices: &mut Vec<f32>,
+    faces: &mut [SolidFace],
+    materials: &mut Vec<String>,
+) {
+    for index in faces.iter_mut().flat_map(|f| &mut f.vertice_indices) {
+        *index = remap[*index];
     }
+
+    compact_faces(&mut vertices, faces, materials);
+
+    *flat_vertices = vertices
+        .iter()
+        .flat_map(|&v| (v - offset).to_array())
+        .collect();
+}
+
+/// Returns the average of the vertices of a face.
+fn face_center(vertices: &[Vec3], face: &SolidFace) -> Vec3 {
+    #[allow(clippy::cast_precision_loss)]
+    let count = face.vertice_indices.len() as f32;
+
+    face.vertice_indices
+        .iter()
+        .map(|&i| vertices[i])
+        .sum::<Vec3>()
+        / count
+}
+
+/// Returns the smoothing groups of the faces, or `None` if none of the faces are smoothed.
+/// The faces of merged solids, without a `solid` id, are matched by their position.
+fn get_smoothing_groups(
+    groups: &SmoothingGroups,
+    solid: Option<i32>,
+    flat_vertices: &[f32],
+    faces: &[SolidFace],
+    offset: Vec3,
+) -> Option<Vec<u32>> {
+    let vertex = |i: usize| Vec3::from_slice(&flat_vertices[i * 3..i * 3 + 3]) + offset;
+
+    let face_groups: Vec<u32> = faces
+        .iter()
+        .map(|f| {
+            let vertices = f.vertice_indices.iter().map(|&i| vertex(i));
+
+            polygon_normal(vertices.clone()).map_or(0, |normal| {
+                let distance = normal.dot(vertex(f.vertice_indices[0]));
+
+                match solid {
+                    Some(id) => groups.find_in_solid(id, normal, distance),
+                    None => {
+                        #[allow(clippy::cast_precision_loss)]
+                        let center = vertices.sum::<Vec3>() / f.vertice_indices.len() as f32;
+                        groups.find(normal, distance, center)
+                    }
+                }
+            })
+        })
+        .collect();
+
+    face_groups.iter().any(|&g| g != 0).then_some(face_groups)
+}
+
+/// Returns the vertices of each face, in vmf units.
+fn get_face_vertices(flat_vertices: &[f32], faces: &[SolidFace], offset: Vec3) -> Vec<Vec<Vec3>> {
+    faces
+        .iter()
+        .map(|f| {
+            f.vertice_indices
+                .iter()
+                .map(|&i| Vec3::from_slice(&flat_vertices[i * 3..i * 3 + 3]) + offset)
+                .collect()
+        })
+        .collect()
 }
 
 fn get_flat_polygon_vertice_indices(faces: &[SolidFace]) -> Vec<usize> {
@@ -295,3 +785,32 @@ fn get_flat_loop_colors(faces: &[SolidFace]) -> Vec<f32> {
         })
         .collect()
 }
+
+/// Returns the blend factors of the faces without the color space conversion of `loop_colors`,
+/// or `None` if none of the faces blend.
+fn get_flat_loop_blend_factors(faces: &[SolidFace]) -> Option<Vec<f32>> {
+    let blends = faces
+        .iter()
+        .any(|f| f.vertice_multiblends.is_some() || f.vertice_alphas.iter().any(|&a| a != 0.0));
+
+    if !blends {
+        return None;
+    }
+
+    let factors = faces
+        .iter()
+        .flat_map(|f| {
+            if let Some(multiblends) = &f.vertice_multiblends {
+                Either::Left(multiblends.iter().copied().flatten())
+            } else {
+                Either::Right(f.vertice_alphas.iter().flat_map(|&a| {
+                    let fac = a / 255.;
+
+                    [fac, fac, fac, 1.0]
+                }))
+            }
+        })
+        .collect();
+
+    Some(factors)
+}
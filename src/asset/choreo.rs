@@ -0,0 +1,87 @@
+use std::mem;
+
+use pyo3::prelude::*;
+
+use crate::choreo::{ChoreoEvent, ChoreoScene};
+
+/// A choreography scene, with its events sorted by start time.
+#[pyclass(module = "plumber", name = "ChoreoScene")]
+pub struct PyChoreoScene {
+    name: String,
+    fps: Option<u32>,
+    actors: Vec<String>,
+    events: Vec<PyChoreoEvent>,
+}
+
+#[pymethods]
+impl PyChoreoScene {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Frame rate the scene was authored at, if it snaps to frames.
+    fn fps(&self) -> Option<u32> {
+        self.fps
+    }
+
+    fn actors(&mut self) -> Vec<String> {
+        mem::take(&mut self.actors)
+    }
+
+    fn events(&mut self) -> Vec<PyChoreoEvent> {
+        mem::take(&mut self.events)
+    }
+}
+
+impl PyChoreoScene {
+    pub fn new(name: String, scene: ChoreoScene) -> Self {
+        Self {
+            name,
+            fps: scene.fps,
+            actors: scene.actors,
+            events: scene.events.into_iter().map(PyChoreoEvent).collect(),
+        }
+    }
+}
+
+#[pyclass(module = "plumber", name = "ChoreoEvent")]
+#[derive(Clone)]
+pub struct PyChoreoEvent(ChoreoEvent);
+
+#[pymethods]
+impl PyChoreoEvent {
+    /// Type of the event, like `speak`, `gesture` or `lookat`.
+    fn kind(&self) -> &str {
+        &self.0.kind
+    }
+
+    fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    fn actor(&self) -> Option<&str> {
+        self.0.actor.as_deref()
+    }
+
+    fn channel(&self) -> Option<&str> {
+        self.0.channel.as_deref()
+    }
+
+    fn start(&self) -> f32 {
+        self.0.start
+    }
+
+    /// End time in seconds, `None` for instantaneous events.
+    fn end(&self) -> Option<f32> {
+        self.0.end
+    }
+
+    /// Sound of speak events, sequence of gestures or target of looks.
+    fn parameter(&self) -> &str {
+        &self.0.parameter
+    }
+
+    fn parameter2(&self) -> &str {
+        &self.0.parameter2
+    }
+}
@@ -0,0 +1,704 @@
+use image::{
+    imageops::{self, FilterType},
+    Rgba, Rgba32FImage, RgbaImage,
+};
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::{
+    bsp::cubemaps::Cubemap,
+    bytes::read_le,
+    vtf_header::{self, VtfHeader},
+};
+
+use super::{sky::cubemap_to_equi, utils::srgb_to_linear};
+
+const VTF_HEADER_SIZE: u32 = 80;
+
+pub(super) const IMAGE_FORMAT_RGBA8888: i32 = 0;
+const IMAGE_FORMAT_ABGR8888: i32 = 1;
+const IMAGE_FORMAT_RGB888: i32 = 2;
+const IMAGE_FORMAT_BGR888: i32 = 3;
+const IMAGE_FORMAT_ARGB8888: i32 = 11;
+pub(super) const IMAGE_FORMAT_BGRA8888: i32 = 12;
+pub(super) const IMAGE_FORMAT_DXT1: i32 = 13;
+const IMAGE_FORMAT_DXT3: i32 = 14;
+pub(super) const IMAGE_FORMAT_DXT5: i32 = 15;
+const IMAGE_FORMAT_BGRX8888: i32 = 16;
+const IMAGE_FORMAT_DXT1_ONEBITALPHA: i32 = 20;
+const IMAGE_FORMAT_RGBA16161616F: i32 = 24;
+const IMAGE_FORMAT_ATI2N: i32 = 34;
+const IMAGE_FORMAT_NONE: i32 = -1;
+
+/// Resource tag of the high resolution image data, in vtf 7.3 and later.
+const HIGH_RES_IMAGE_TAG: [u8; 3] = [0x30, 0, 0];
+/// Brightness of compressed hdr colors with a full alpha.
+const COMPRESSED_HDR_SCALE: f32 = 16.0;
+
+const TEXTUREFLAGS_CLAMPS: u32 = 0x0000_0004;
+const TEXTUREFLAGS_CLAMPT: u32 = 0x0000_0008;
+const TEXTUREFLAGS_EIGHTBITALPHA: u32 = 0x0000_2000;
+const TEXTUREFLAGS_ENVMAP: u32 = 0x0000_4000;
+
+/// Face names in the order they are expected, matching the Source skybox naming.
+pub const FACE_NAMES: [&str; 6] = ["rt", "lf", "bk", "ft", "up", "dn"];
+
+/// Faces of a cubemap in the order the sky converter expects:
+/// left, right, up, down, front, back.
+pub enum CubemapFaces {
+    Sdr([RgbaImage; 6]),
+    Hdr([Rgba32FImage; 6]),
+}
+
+/// A cubemap sample of a compiled map, with its texture converted into an
+/// equirectangular image.
+#[pyclass(module = "plumber", name = "Cubemap")]
+pub struct PyCubemap {
+    pub index: usize,
+    position: [f32; 3],
+    size: i32,
+    image: Option<(u32, u32, &'static str, Vec<u8>)>,
+}
+
+#[pymethods]
+impl PyCubemap {
+    fn index(&self) -> usize {
+        self.index
+    }
+
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    /// Resolution setting of the cubemap, 0 being the default.
+    fn size(&self) -> i32 {
+        self.size
+    }
+
+    /// Whether a texture was built for the cubemap.
+    fn has_image(&self) -> bool {
+        self.image.is_some()
+    }
+
+    fn width(&self) -> Option<u32> {
+        self.image.as_ref().map(|&(width, _, _, _)| width)
+    }
+
+    fn height(&self) -> Option<u32> {
+        self.image.as_ref().map(|&(_, height, _, _)| height)
+    }
+
+    fn format(&self) -> Option<&str> {
+        self.image.as_ref().map(|&(_, _, format, _)| format)
+    }
+
+    fn bytes(&self) -> Option<&[u8]> {
+        self.image.as_ref().map(|(_, _, _, data)| data.as_slice())
+    }
+}
+
+impl PyCubemap {
+    /// Converts the ldr texture of the cubemap, or the hdr one if it's stored as floats
+    /// or the ldr one is missing.
+    pub fn new(index: usize, cubemap: &Cubemap, scale: f32, out_height: Option<u32>) -> Self {
+        let faces = cubemap
+            .hdr_vtf
+            .as_deref()
+            .and_then(read_cubemap_vtf)
+            .filter(|f| matches!(f, CubemapFaces::Hdr(_)))
+            .or_else(|| cubemap.vtf.as_deref().and_then(read_cubemap_vtf))
+            .or_else(|| cubemap.hdr_vtf.as_deref().and_then(read_cubemap_vtf));
+
+        Self {
+            index,
+            position: cubemap.origin.map(|c| c as f32 * scale),
+            size: cubemap.size,
+            image: faces.map(|faces| {
+                let equi = cubemap_to_equi(faces, out_height);
+                let (width, height) = equi.dimensions();
+                let (format, data) = equi.encode();
+                (width, height, format, data)
+            }),
+        }
+    }
+}
+
+/// Reads the faces of the first frame of a cubemap VTF. Returns `None` if the texture
+/// isn't a cubemap or uses an unsupported format.
+pub fn read_cubemap_vtf(data: &[u8]) -> Option<CubemapFaces> {
+    let image = read_vtf_image(data)?;
+
+    if !image.envmap || image.width != image.height {
+        return None;
+    }
+
+    let (width, height) = (image.width, image.height);
+
+    if image.format == IMAGE_FORMAT_RGBA16161616F {
+        let faces: Vec<Rgba32FImage> = image
+            .faces
+            .iter()
+            .map(|face| decode_hdr(image.format, face, width, height, false))
+            .collect::<Option<_>>()?;
+
+        Some(CubemapFaces::Hdr(sky_faces(faces.try_into().ok()?)))
+    } else {
+        let faces: Vec<RgbaImage> = image
+            .faces
+            .iter()
+            .map(|face| decode_ldr(image.format, face, width, height))
+            .collect::<Option<_>>()?;
+
+        Some(CubemapFaces::Sdr(sky_faces(faces.try_into().ok()?)))
+    }
+}
+
+/// Reads the first frame of a 2D VTF as an hdr image. The 8-bit `BGRA8888` format is
+/// only accepted if `compressed` is set, in which case it's decoded as compressed hdr.
+pub fn read_hdr_vtf(data: &[u8], compressed: bool) -> Option<Rgba32FImage> {
+    let image = read_vtf_image(data)?;
+
+    if image.envmap || (image.format == IMAGE_FORMAT_BGRA8888) != compressed {
+        return None;
+    }
+
+    decode_hdr(
+        image.format,
+        image.faces.first()?,
+        image.width,
+        image.height,
+        compressed,
+    )
+}
+
+/// Whether a VTF uses the two-channel `ATI2N` (BC5) format, which stores only the
+/// X and Y of normal maps.
+pub fn is_two_channel_vtf(data: &[u8]) -> bool {
+    vtf_header::format(data) == Some(IMAGE_FORMAT_ATI2N)
+}
+
+/// The largest mipmap of the first frame of a VTF.
+struct VtfImage<'a> {
+    format: i32,
+    width: u32,
+    height: u32,
+    envmap: bool,
+    /// Six faces for cubemaps, one otherwise.
+    faces: Vec<&'a [u8]>,
+}
+
+fn read_vtf_image(data: &[u8]) -> Option<VtfImage<'_>> {
+    let VtfHeader {
+        minor_version,
+        header_size,
+        width,
+        height,
+        flags,
+        frames,
+        first_frame,
+        format,
+        mipmap_count,
+        low_res_format,
+        low_res_width,
+        low_res_height,
+        depth,
+    } = VtfHeader::read(data)?;
+
+    let envmap = flags & TEXTUREFLAGS_ENVMAP != 0;
+
+    // older versions have an additional spheremap face
+    let faces = if !envmap {
+        1
+    } else if minor_version < 5 && first_frame != u16::MAX {
+        7
+    } else {
+        6
+    };
+
+    let mut offset = if minor_version >= 3 {
+        let resource_count = usize::try_from(read_le::<u32>(data, 68)?).ok()?;
+
+        (0..resource_count).find_map(|i| {
+            let entry = 80 + i * 8;
+            (data.get(entry..entry + 3)? == HIGH_RES_IMAGE_TAG)
+                .then(|| usize::try_from(read_le::<u32>(data, entry + 4)?).ok())
+                .flatten()
+        })?
+    } else {
+        header_size + image_size(low_res_format, low_res_width, low_res_height)?
+    };
+
+    // mipmaps are stored from the smallest to the largest
+    for mip in (1..mipmap_count).rev() {
+        let mip_width = (width >> mip).max(1);
+        let mip_height = (height >> mip).max(1);
+        offset += frames * faces * depth * image_size(format, mip_width, mip_height)?;
+    }
+
+    let face_size = image_size(format, width, height)?;
+    let faces = (0..faces.min(6))
+        .map(|i| data.get(offset + i * face_size..offset + (i + 1) * face_size))
+        .collect::<Option<_>>()?;
+
+    Some(VtfImage {
+        format,
+        width,
+        height,
+        envmap,
+        faces,
+    })
+}
+
+/// Reorders the faces from the vtf order into the sky order,
+/// undoing the rotation done for envmaps in [`orient_faces`].
+fn sky_faces<P: image::Pixel + 'static>(
+    faces: [image::ImageBuffer<P, Vec<P::Subpixel>>; 6],
+) -> [image::ImageBuffer<P, Vec<P::Subpixel>>; 6] {
+    let [rt, lf, bk, ft, up, dn] = faces;
+
+    [
+        lf,
+        rt,
+        imageops::rotate270(&up),
+        imageops::rotate90(&dn),
+        ft,
+        bk,
+    ]
+}
+
+/// Returns the size of an image in bytes, or `None` if the format is unsupported.
+fn image_size(format: i32, width: u32, height: u32) -> Option<usize> {
+    let pixels = usize::try_from(width * height).ok()?;
+    let blocks = usize::try_from(width.div_ceil(4).max(1) * height.div_ceil(4).max(1)).ok()?;
+
+    match format {
+        IMAGE_FORMAT_NONE => Some(0),
+        IMAGE_FORMAT_RGBA8888
+        | IMAGE_FORMAT_ABGR8888
+        | IMAGE_FORMAT_ARGB8888
+        | IMAGE_FORMAT_BGRA8888
+        | IMAGE_FORMAT_BGRX8888 => Some(pixels * 4),
+        IMAGE_FORMAT_RGB888 | IMAGE_FORMAT_BGR888 => Some(pixels * 3),
+        IMAGE_FORMAT_DXT1 | IMAGE_FORMAT_DXT1_ONEBITALPHA => Some(blocks * 8),
+        IMAGE_FORMAT_DXT3 | IMAGE_FORMAT_DXT5 => Some(blocks * 16),
+        IMAGE_FORMAT_RGBA16161616F => Some(pixels * 8),
+        _ => None,
+    }
+}
+
+pub(super) fn decode_ldr(format: i32, data: &[u8], width: u32, height: u32) -> Option<RgbaImage> {
+    let pixels: Vec<u8> = match format {
+        IMAGE_FORMAT_RGBA8888 => data.to_vec(),
+        IMAGE_FORMAT_ABGR8888 => swizzle(data, 4, [3, 2, 1, 0]),
+        IMAGE_FORMAT_ARGB8888 => swizzle(data, 4, [1, 2, 3, 0]),
+        IMAGE_FORMAT_BGRA8888 => swizzle(data, 4, [2, 1, 0, 3]),
+        IMAGE_FORMAT_BGRX8888 => swizzle(data, 4, [2, 1, 0, 4]),
+        IMAGE_FORMAT_RGB888 => swizzle(data, 3, [0, 1, 2, 4]),
+        IMAGE_FORMAT_BGR888 => swizzle(data, 3, [2, 1, 0, 4]),
+        IMAGE_FORMAT_DXT1 | IMAGE_FORMAT_DXT1_ONEBITALPHA => {
+            return decode_blocks(data, width, height, 8, |block, out| {
+                decode_color_block(block, true, out);
+            });
+        }
+        IMAGE_FORMAT_DXT3 => {
+            return decode_blocks(data, width, height, 16, |block, out| {
+                decode_color_block(&block[8..], false, out);
+
+                for (i, pixel) in out.iter_mut().enumerate() {
+                    pixel[3] = (block[i / 2] >> (i % 2 * 4) & 0xF) * 17;
+                }
+            });
+        }
+        IMAGE_FORMAT_DXT5 => {
+            return decode_blocks(data, width, height, 16, |block, out| {
+                decode_color_block(&block[8..], false, out);
+                decode_channel_block(&block[..8], 3, out);
+            });
+        }
+        _ => return None,
+    };
+
+    RgbaImage::from_raw(width, height, pixels)
+}
+
+/// Reorders the channels of each pixel into rgba. A channel index past the pixel
+/// is an opaque alpha.
+fn swizzle(data: &[u8], channels: usize, order: [usize; 4]) -> Vec<u8> {
+    data.chunks_exact(channels)
+        .flat_map(|p| order.map(|i| p.get(i).copied().unwrap_or(u8::MAX)))
+        .collect()
+}
+
+pub(super) fn decode_blocks(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    block_size: usize,
+    decode: impl Fn(&[u8], &mut [[u8; 4]; 16]),
+) -> Option<RgbaImage> {
+    let blocks_x = width.div_ceil(4).max(1);
+    let mut image = RgbaImage::new(width, height);
+
+    for (i, block) in data.chunks_exact(block_size).enumerate() {
+        let i = u32::try_from(i).ok()?;
+        let (bx, by) = (i % blocks_x * 4, i / blocks_x * 4);
+        let mut pixels = [[0; 4]; 16];
+        decode(block, &mut pixels);
+
+        for (j, pixel) in (0..).zip(pixels) {
+            let (x, y) = (bx + j % 4, by + j / 4);
+            if x < width && y < height {
+                image.put_pixel(x, y, Rgba(pixel));
+            }
+        }
+    }
+
+    Some(image)
+}
+
+fn decode_color_block(block: &[u8], allow_transparent: bool, out: &mut [[u8; 4]; 16]) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let [a, b] = [c0, c1].map(rgb565);
+
+    let mix = |x: [u8; 4], y: [u8; 4], wx: u16, wy: u16| -> [u8; 4] {
+        let channel = |i: usize| {
+            let value = (u16::from(x[i]) * wx + u16::from(y[i]) * wy) / (wx + wy);
+            u8::try_from(value).unwrap_or(u8::MAX)
+        };
+        [channel(0), channel(1), channel(2), u8::MAX]
+    };
+
+    let colors = if c0 > c1 || !allow_transparent {
+        [a, b, mix(a, b, 2, 1), mix(a, b, 1, 2)]
+    } else {
+        [a, b, mix(a, b, 1, 1), [0; 4]]
+    };
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for (i, pixel) in out.iter_mut().enumerate() {
+        *pixel = colors[(indices >> (i * 2)) as usize & 0b11];
+    }
+}
+
+/// Decodes an interpolated single channel block, like the alpha of DXT5, into `channel`.
+pub(super) fn decode_channel_block(block: &[u8], channel: usize, out: &mut [[u8; 4]; 16]) {
+    let [a0, a1] = [u16::from(block[0]), u16::from(block[1])];
+
+    let alpha = |index: u16| -> u16 {
+        match index {
+            0 => a0,
+            1 => a1,
+            _ if a0 > a1 => (a0 * (8 - index) + a1 * (index - 1)) / 7,
+            6 => 0,
+            7 => 255,
+            _ => (a0 * (6 - index) + a1 * (index - 1)) / 5,
+        }
+    };
+
+    let indices = block[2..8]
+        .iter()
+        .rev()
+        .fold(0u64, |bits, &b| (bits << 8) | u64::from(b));
+
+    for (i, pixel) in out.iter_mut().enumerate() {
+        #[allow(clippy::cast_possible_truncation)]
+        let index = ((indices >> (i * 3)) & 0b111) as u16;
+        pixel[channel] = u8::try_from(alpha(index)).unwrap_or(u8::MAX);
+    }
+}
+
+fn rgb565(color: u16) -> [u8; 4] {
+    let expand = |value: u16, bits: u32| -> u8 {
+        let max = (1 << bits) - 1;
+        u8::try_from(value * 255 / max).unwrap_or(u8::MAX)
+    };
+
+    [
+        expand(color >> 11, 5),
+        expand((color >> 5) & 0x3F, 6),
+        expand(color & 0x1F, 5),
+        u8::MAX,
+    ]
+}
+
+/// Decodes float textures, and compressed hdr textures which store the brightness
+/// of the color in the alpha channel.
+fn decode_hdr(
+    format: i32,
+    data: &[u8],
+    width: u32,
+    height: u32,
+    compressed: bool,
+) -> Option<Rgba32FImage> {
+    let pixels: Vec<f32> = match format {
+        IMAGE_FORMAT_RGBA16161616F => data
+            .chunks_exact(2)
+            .map(|c| half_to_f32(u16::from_le_bytes([c[0], c[1]])))
+            .collect(),
+        IMAGE_FORMAT_BGRA8888 if compressed => data
+            .chunks_exact(4)
+            .flat_map(|c| {
+                let scale = f32::from(c[3]) / 255.0 * COMPRESSED_HDR_SCALE;
+                [c[2], c[1], c[0]]
+                    .map(|v| f32::from(v) / 255.0 * scale)
+                    .into_iter()
+                    .chain([1.0])
+            })
+            .collect(),
+        _ => return None,
+    };
+
+    Rgba32FImage::from_raw(width, height, pixels)
+}
+
+fn half_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 == 0 { 1.0 } else { -1.0 };
+    let exponent = i32::from((half >> 10) & 0x1F);
+    let mantissa = half & 0x3FF;
+
+    sign * match exponent {
+        0 => f32::from(mantissa) * 2f32.powi(-24),
+        0x1F if mantissa == 0 => f32::INFINITY,
+        0x1F => f32::NAN,
+        _ => (1.0 + f32::from(mantissa) / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+/// Decodes six encoded face images (in [`FACE_NAMES`] order) and assembles them into a cubemap VTF.
+pub fn build_cubemap_vtf(faces: &[&[u8]]) -> PyResult<Vec<u8>> {
+    if faces.len() != FACE_NAMES.len() {
+        return Err(PyValueError::new_err(format!(
+            "expected {} cubemap faces, got {}",
+            FACE_NAMES.len(),
+            faces.len()
+        )));
+    }
+
+    let mut images = Vec::with_capacity(faces.len());
+
+    for (&face, name) in faces.iter().zip(FACE_NAMES) {
+        let image = image::load_from_memory(face)
+            .map_err(|err| {
+                PyValueError::new_err(format!("could not decode cubemap face `{name}`: {err}"))
+            })?
+            .into_rgba8();
+
+        images.push(image);
+    }
+
+    let images: [RgbaImage; 6] = images.try_into().expect("face count was checked");
+
+    Ok(write_cubemap_vtf(orient_faces(images)?))
+}
+
+/// Rotates the up and down faces into the orientation the engine expects for envmaps.
+fn orient_faces(faces: [RgbaImage; 6]) -> PyResult<[RgbaImage; 6]> {
+    let size = faces[0].width();
+
+    for (face, name) in faces.iter().zip(FACE_NAMES) {
+        if face.width() != size || face.height() != size {
+            return Err(PyValueError::new_err(format!(
+                "cubemap face `{name}` must be {size}x{size}, got {}x{}",
+                face.width(),
+                face.height()
+            )));
+        }
+    }
+
+    if !size.is_power_of_two() {
+        return Err(PyValueError::new_err(format!(
+            "cubemap face size must be a power of two, got {size}"
+        )));
+    }
+
+    let [rt, lf, bk, ft, up, dn] = faces;
+
+    Ok([
+        rt,
+        lf,
+        bk,
+        ft,
+        imageops::rotate90(&up),
+        imageops::rotate270(&dn),
+    ])
+}
+
+fn write_cubemap_vtf(faces: [RgbaImage; 6]) -> Vec<u8> {
+    let size = faces[0].width();
+    let mipmap_count = size.trailing_zeros() + 1;
+
+    let has_alpha = faces
+        .iter()
+        .any(|face| face.pixels().any(|pixel| pixel.0[3] != u8::MAX));
+
+    let mut flags = TEXTUREFLAGS_ENVMAP | TEXTUREFLAGS_CLAMPS | TEXTUREFLAGS_CLAMPT;
+
+    if has_alpha {
+        flags |= TEXTUREFLAGS_EIGHTBITALPHA;
+    }
+
+    let mut data = Vec::new();
+
+    data.extend_from_slice(b"VTF\0");
+    // version 7.2
+    data.extend_from_slice(&7u32.to_le_bytes());
+    data.extend_from_slice(&2u32.to_le_bytes());
+    data.extend_from_slice(&VTF_HEADER_SIZE.to_le_bytes());
+    #[allow(clippy::cast_possible_truncation)]
+    let size_u16 = size as u16;
+    data.extend_from_slice(&size_u16.to_le_bytes());
+    data.extend_from_slice(&size_u16.to_le_bytes());
+    data.extend_from_slice(&flags.to_le_bytes());
+    // frame count
+    data.extend_from_slice(&1u16.to_le_bytes());
+    // a first frame of 0xFFFF means there is no spheremap face
+    data.extend_from_slice(&u16::MAX.to_le_bytes());
+    data.extend_from_slice(&[0; 4]);
+
+    for channel in reflectivity(&faces) {
+        data.extend_from_slice(&channel.to_le_bytes());
+    }
+
+    data.extend_from_slice(&[0; 4]);
+    // bumpmap scale
+    data.extend_from_slice(&1.0f32.to_le_bytes());
+    data.extend_from_slice(&IMAGE_FORMAT_RGBA8888.to_le_bytes());
+    #[allow(clippy::cast_possible_truncation)]
+    data.push(mipmap_count as u8);
+    // no low resolution image
+    data.extend_from_slice(&IMAGE_FORMAT_NONE.to_le_bytes());
+    data.push(0);
+    data.push(0);
+    // depth
+    data.extend_from_slice(&1u16.to_le_bytes());
+
+    data.resize(VTF_HEADER_SIZE as usize, 0);
+
+    // mipmaps are stored from the smallest to the largest, each containing all faces
+    for mip in (0..mipmap_count).rev() {
+        let mip_size = size >> mip;
+
+        for face in &faces {
+            if mip == 0 {
+                data.extend_from_slice(face.as_raw());
+            } else {
+                let resized = imageops::resize(face, mip_size, mip_size, FilterType::Triangle);
+                data.extend_from_slice(resized.as_raw());
+            }
+        }
+    }
+
+    data
+}
+
+/// Average linear color of all faces, used by vrad for bounced light.
+fn reflectivity(faces: &[RgbaImage; 6]) -> [f32; 3] {
+    let mut sum = [0.0f64; 3];
+    let mut count = 0.0f64;
+
+    for face in faces {
+        for pixel in face.pixels() {
+            for (sum, &channel) in sum.iter_mut().zip(&pixel.0[..3]) {
+                *sum += f64::from(srgb_to_linear(f32::from(channel) / 255.0));
+            }
+
+            count += 1.0;
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    sum.map(|s| (s / count) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    #[test]
+    fn cubemap_vtf_layout() {
+        let faces = [(); 6].map(|_| RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255])));
+
+        let data = write_cubemap_vtf(faces);
+
+        // 3 mipmaps (4x4, 2x2, 1x1) of 6 rgba faces
+        let image_size = (16 + 4 + 1) * 6 * 4;
+
+        assert_eq!(data.len(), VTF_HEADER_SIZE as usize + image_size);
+        assert_eq!(&data[..4], b"VTF\0");
+        assert_eq!(data[56], 3);
+    }
+
+    #[test]
+    fn read_written_cubemap_vtf() {
+        let faces = [0, 1, 2, 3, 4, 5].map(|i| {
+            let mut face = RgbaImage::from_pixel(4, 4, Rgba([i * 40, 0, 0, 255]));
+            face.put_pixel(0, 0, Rgba([0, 255, 0, 255]));
+            face
+        });
+
+        let data = write_cubemap_vtf(orient_faces(faces.clone()).unwrap());
+
+        let Some(CubemapFaces::Sdr(read)) = read_cubemap_vtf(&data) else {
+            panic!("cubemap should be read");
+        };
+
+        let [rt, lf, bk, ft, up, dn] = faces;
+        assert_eq!(read, [lf, rt, up, dn, ft, bk]);
+
+        let mut not_cubemap = data.clone();
+        not_cubemap[20..24].copy_from_slice(&0u32.to_le_bytes());
+        assert!(read_cubemap_vtf(&not_cubemap).is_none());
+    }
+
+    #[test]
+    fn decode_dxt_blocks() {
+        // red and blue endpoints, the first row using the interpolated colors
+        let block = [0x00, 0xF8, 0x1F, 0x00, 0b1110_0100, 0, 0, 0];
+        let image = decode_ldr(IMAGE_FORMAT_DXT1, &block, 4, 4).unwrap();
+
+        assert_eq!(image.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(image.get_pixel(1, 0), &Rgba([0, 0, 255, 255]));
+        assert_eq!(image.get_pixel(2, 0), &Rgba([170, 0, 85, 255]));
+        assert_eq!(image.get_pixel(3, 0), &Rgba([85, 0, 170, 255]));
+        assert_eq!(image.get_pixel(0, 1), &Rgba([255, 0, 0, 255]));
+
+        let mut block = [0; 16];
+        block[0] = 255;
+        // the second pixel uses the second alpha endpoint, 0
+        block[2] = 0b0000_1000;
+        let image = decode_ldr(IMAGE_FORMAT_DXT5, &block, 4, 4).unwrap();
+
+        assert_eq!(image.get_pixel(0, 0)[3], 255);
+        assert_eq!(image.get_pixel(1, 0)[3], 0);
+    }
+
+    #[test]
+    fn decode_compressed_hdr() {
+        let image = decode_hdr(IMAGE_FORMAT_BGRA8888, &[0, 51, 255, 128], 1, 1, true).unwrap();
+        let [r, g, b, a] = image.get_pixel(0, 0).0;
+        let scale = 128.0 / 255.0 * 16.0;
+
+        assert!((r - scale).abs() < 1e-5);
+        assert!((g - 0.2 * scale).abs() < 1e-5);
+        assert!(b.abs() < 1e-5);
+        assert!((a - 1.0).abs() < 1e-5);
+
+        assert!(decode_hdr(IMAGE_FORMAT_BGRA8888, &[0; 4], 1, 1, false).is_none());
+    }
+
+    #[test]
+    fn decode_dxt3_alpha() {
+        let mut block = [0; 16];
+        // four bits of alpha per pixel, the low bits first
+        block[0] = 0xF0;
+        block[1] = 0x08;
+        let image = decode_ldr(IMAGE_FORMAT_DXT3, &block, 4, 4).unwrap();
+
+        assert_eq!(image.get_pixel(0, 0)[3], 0);
+        assert_eq!(image.get_pixel(1, 0)[3], 255);
+        assert_eq!(image.get_pixel(2, 0)[3], 136);
+        assert_eq!(image.get_pixel(0, 1)[3], 0);
+    }
+}
@@ -0,0 +1,146 @@
+use std::mem;
+
+use pyo3::{prelude::*, types::PyList};
+
+use crate::{
+    bsp::detail_props::{self, DetailSpriteQuads},
+    detail_scatter::DetailScatter,
+};
+
+#[pyclass(module = "plumber", name = "DetailSprites")]
+pub struct PyDetailSprites {
+    material: String,
+    scale: [f32; 3],
+    quads_len: usize,
+    flat_vertices: Vec<f32>,
+    flat_loop_uvs: Vec<f32>,
+}
+
+#[pymethods]
+impl PyDetailSprites {
+    fn material(&self) -> &str {
+        &self.material
+    }
+
+    fn scale(&self) -> [f32; 3] {
+        self.scale
+    }
+
+    fn vertices(&mut self) -> Vec<f32> {
+        mem::take(&mut self.flat_vertices)
+    }
+
+    fn loops_len(&self) -> usize {
+        self.quads_len * 4
+    }
+
+    fn polygons_len(&self) -> usize {
+        self.quads_len
+    }
+
+    fn polygon_loop_totals<'p>(&self, py: Python<'p>) -> &'p PyList {
+        PyList::new(py, (0..self.quads_len).map(|_| 4))
+    }
+
+    fn polygon_loop_starts<'p>(&self, py: Python<'p>) -> &'p PyList {
+        PyList::new(py, (0..self.quads_len).map(|i| i * 4))
+    }
+
+    fn polygon_vertices(&self) -> Vec<usize> {
+        // every quad has its own vertices
+        (0..self.quads_len * 4).collect()
+    }
+
+    fn loop_uvs(&mut self) -> Vec<f32> {
+        mem::take(&mut self.flat_loop_uvs)
+    }
+}
+
+impl PyDetailSprites {
+    pub fn new(quads: DetailSpriteQuads, scale: f32) -> Self {
+        Self {
+            material: quads.material,
+            scale: [scale, scale, scale],
+            quads_len: quads.vertices.len() / 4,
+            flat_vertices: quads.vertices.into_iter().flatten().collect(),
+            flat_loop_uvs: quads.uvs.into_iter().flatten().collect(),
+        }
+    }
+}
+
+/// Detail props scattered on the displacements of a vmf. The sprites share the detail
+/// material of the map, so they are merged into a single mesh, and the models are
+/// merged by the model, since a map can have thousands of displacements.
+#[pyclass(module = "plumber", name = "DetailScatter")]
+pub struct PyDetailScatter {
+    sprites: Option<PyDetailSprites>,
+    model_names: Vec<String>,
+    model_positions: Vec<[f32; 3]>,
+    model_rotations: Vec<[f32; 3]>,
+    model_side_ids: Vec<i32>,
+}
+
+#[pymethods]
+impl PyDetailScatter {
+    fn sprites(&mut self) -> Option<PyDetailSprites> {
+        self.sprites.take()
+    }
+
+    fn model_names(&mut self) -> Vec<String> {
+        mem::take(&mut self.model_names)
+    }
+
+    fn model_positions(&mut self) -> Vec<[f32; 3]> {
+        mem::take(&mut self.model_positions)
+    }
+
+    fn model_rotations(&mut self) -> Vec<[f32; 3]> {
+        mem::take(&mut self.model_rotations)
+    }
+
+    /// Id of the displacement side each model is placed on.
+    fn model_side_ids(&mut self) -> Vec<i32> {
+        mem::take(&mut self.model_side_ids)
+    }
+}
+
+impl PyDetailScatter {
+    pub fn new(scatter: DetailScatter, scale: f32) -> Self {
+        let mut sprites = Vec::new();
+        let mut models = Vec::new();
+
+        for displacement in scatter.displacements {
+            sprites.extend(displacement.sprites);
+            models.extend(
+                displacement
+                    .models
+                    .into_iter()
+                    .map(|m| (displacement.side_id, m)),
+            );
+        }
+
+        let sprites = (!sprites.is_empty()).then(|| {
+            PyDetailSprites::new(
+                detail_props::sprite_quads(&sprites, &scatter.material),
+                scale,
+            )
+        });
+
+        Self {
+            sprites,
+            model_positions: models
+                .iter()
+                .map(|(_, m)| m.origin.map(|c| c * scale))
+                .collect(),
+            model_rotations: models
+                .iter()
+                .map(|(_, m)| {
+                    let [pitch, yaw, roll] = m.angles.map(f32::to_radians);
+                    [roll, pitch, yaw]
+                })
+                .collect(),
+            model_side_ids: models.iter().map(|&(side_id, _)| side_id).collect(),
+            model_names: models.into_iter().map(|(_, m)| m.model).collect(),
+        }
+    }
+}
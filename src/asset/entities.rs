@@ -1,6 +1,6 @@
 use std::{collections::BTreeMap, f32::consts::FRAC_PI_2, mem};
 
-use glam::{EulerRot, Quat};
+use glam::{EulerRot, Quat, Vec3};
 use pyo3::prelude::*;
 use rgb::ComponentMap;
 
@@ -10,10 +10,24 @@ use plumber_core::{
         AngledEntity, BaseEntity, EntityParseError, EnvLight, Light, LightEntity, PointEntity,
         SkyCamera, SpotLight, Unknown,
     },
+    vmf::vmf::Entity,
+};
+
+use crate::{
+    connections::EntityConnections,
+    fgd::{Fgd, ModelSource},
+    gameplay::GameplayMarker,
+    kv_utils::parse_vector,
+    lighting_origins::LightingOrigins,
+    ragdoll_groups::RagdollGroups,
+    skybox::SkyboxArea,
 };
 
 use super::utils::srgb_to_linear;
 
+/// `(target, input, parameter, delay, times_to_fire)`
+type ConnectionTuple = (String, String, String, f32, i32);
+
 #[pyclass(module = "plumber", name = "LoadedProp")]
 pub struct PyLoadedProp {
     model: String,
@@ -23,7 +37,19 @@ pub struct PyLoadedProp {
     rotation: [f32; 3],
     scale: [f32; 3],
     color: [f32; 4],
+    body: i32,
+    skin: usize,
+    disable_shadows: bool,
+    fade_min_distance: Option<f32>,
+    fade_max_distance: Option<f32>,
+    solid: i32,
+    lighting_origin: Option<String>,
+    lighting_origin_position: Option<[f32; 3]>,
+    vertex_lighting: Option<String>,
+    uniform_scale: f32,
     properties: BTreeMap<String, String>,
+    in_3d_skybox: bool,
+    ragdoll_group: Option<i32>,
 }
 
 #[pymethods]
@@ -56,41 +82,251 @@ impl PyLoadedProp {
         self.color
     }
 
+    fn body(&self) -> i32 {
+        self.body
+    }
+
+    fn skin(&self) -> usize {
+        self.skin
+    }
+
+    fn disable_shadows(&self) -> bool {
+        self.disable_shadows
+    }
+
+    /// Distance where the prop starts fading out, scaled like the position.
+    fn fade_min_distance(&self) -> Option<f32> {
+        self.fade_min_distance
+    }
+
+    /// Distance where the prop has faded out completely, `None` if it never fades.
+    fn fade_max_distance(&self) -> Option<f32> {
+        self.fade_max_distance
+    }
+
+    /// Collision of the prop, 0 for none, 2 for its bounding box and 6 for its physics
+    /// model.
+    fn solid(&self) -> i32 {
+        self.solid
+    }
+
+    fn lighting_origin(&self) -> Option<&str> {
+        self.lighting_origin.as_deref()
+    }
+
+    /// Position of the entity named by `lighting_origin`, if it exists in the map.
+    fn lighting_origin_position(&self) -> Option<[f32; 3]> {
+        self.lighting_origin_position
+    }
+
+    /// Name of the baked vertex lighting file of a static prop in the pakfile of the
+    /// compiled map, if the prop uses vertex lighting.
+    fn vertex_lighting(&self) -> Option<&str> {
+        self.vertex_lighting.as_deref()
+    }
+
+    fn uniform_scale(&self) -> f32 {
+        self.uniform_scale
+    }
+
+    fn in_3d_skybox(&self) -> bool {
+        self.in_3d_skybox
+    }
+
+    /// Identifies the variant of the model this prop uses, so that props which look the
+    /// same can share their data. Props with the same model, skin, bodygroups and scale
+    /// have the same signature.
+    fn signature(&self) -> String {
+        let [x, y, z] = self.scale;
+        format!(
+            "{}:{}:{}:{x},{y},{z}",
+            self.model.to_lowercase(),
+            self.skin,
+            self.body
+        )
+    }
+
+    /// The lowest entity id of the props of the same model constrained together with
+    /// this prop, if any.
+    fn ragdoll_group(&self) -> Option<i32> {
+        self.ragdoll_group
+    }
+
     fn properties(&mut self) -> BTreeMap<String, String> {
         mem::take(&mut self.properties)
     }
 }
 
 impl PyLoadedProp {
-    pub fn new(prop: LoadedProp) -> Self {
-        let rotation = prop.rotation;
-        let properties = prop
-            .prop
-            .entity()
+    pub fn new(prop: LoadedProp, scale: f32) -> Self {
+        let color = prop
+            .color
+            .map_alpha(|a| f32::from(a) / 255.)
+            .map_rgb(|c| srgb_to_linear(f32::from(c) / 255.))
+            .into();
+
+        Self::from_parts(
+            prop.prop.entity(),
+            prop.model_path.into_string(),
+            prop.position.into(),
+            prop.rotation,
+            prop.scale,
+            color,
+            scale,
+        )
+    }
+
+    /// Reads a prop with a GoldSrc model from its entity, since the asset pipeline can't
+    /// load GoldSrc models. `model` is the game path of the model.
+    pub fn from_goldsrc(entity: &Entity, model: String, scale: f32) -> Self {
+        let property = |key: &str| {
+            entity
+                .properties
+                .iter()
+                .find(|(k, _)| k.as_str().eq_ignore_ascii_case(key))
+                .map(|(_, v)| v.as_str())
+        };
+
+        let position = property("origin")
+            .and_then(parse_vector)
+            .unwrap_or_default()
+            * scale;
+        let rotation = property("angles")
+            .and_then(parse_vector)
+            .unwrap_or_default()
+            .into();
+
+        let [r, g, b] = property("rendercolor")
+            .and_then(parse_vector)
+            .map_or([1.0; 3], |c| (c / 255.0).to_array().map(srgb_to_linear));
+        let alpha = property("renderamt")
+            .and_then(|v| v.trim().parse::<f32>().ok())
+            .map_or(1.0, |a| (a / 255.0).clamp(0.0, 1.0));
+
+        Self::from_parts(
+            entity,
+            model,
+            position.into(),
+            rotation,
+            [1.0; 3],
+            [r, g, b, alpha],
+            scale,
+        )
+    }
+
+    fn from_parts(
+        entity: &Entity,
+        model: String,
+        position: [f32; 3],
+        rotation: [f32; 3],
+        prop_scale: [f32; 3],
+        color: [f32; 4],
+        scale: f32,
+    ) -> Self {
+        let properties: BTreeMap<String, String> = entity
             .properties
             .iter()
             .map(|(k, v)| (k.as_str().to_owned(), v.clone()))
             .collect();
 
+        let property = |key: &str| {
+            properties
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v.trim())
+        };
+
+        // some games use `SetBodyGroup` instead of `body`
+        let body = ["body", "setbodygroup"]
+            .iter()
+            .find_map(|key| property(key).and_then(|v| v.parse().ok()))
+            .unwrap_or(0);
+
+        let skin = property("skin").and_then(|v| v.parse().ok()).unwrap_or(0);
+
+        let disable_shadows = property("disableshadows")
+            .and_then(|v| v.parse::<i32>().ok())
+            .is_some_and(|v| v != 0);
+
+        let fade_distance = |key: &str| property(key).and_then(|v| v.parse::<f32>().ok());
+
+        // a maximum distance of zero disables fading
+        let fade_max_distance = fade_distance("fademaxdist")
+            .filter(|d| *d > 0.0)
+            .map(|d| d * scale);
+        let fade_min_distance = fade_distance("fademindist")
+            .filter(|d| *d >= 0.0 && fade_max_distance.is_some())
+            .map(|d| d * scale);
+
+        let solid = property("solid").and_then(|v| v.parse().ok()).unwrap_or(6);
+
+        let lighting_origin = property("lightingorigin")
+            .filter(|v| !v.is_empty())
+            .map(str::to_owned);
+
+        // only set on props of decompiled maps
+        let disable_vertex_lighting = property("disablevertexlighting")
+            .and_then(|v| v.parse::<i32>().ok())
+            .is_some_and(|v| v != 0);
+        let vertex_lighting = property("vertexlighting")
+            .filter(|v| !v.is_empty() && !disable_vertex_lighting)
+            .map(str::to_owned);
+
+        // CS:GO static props have a uniform scale, which some entities call `modelscale`
+        let uniform_scale = ["uniformscale", "modelscale"]
+            .iter()
+            .find_map(|key| property(key).and_then(|v| v.parse::<f32>().ok()))
+            .filter(|s| *s > 0.0)
+            .unwrap_or(1.0);
+
+        let prop_scale = if prop_scale == [1.0; 3] {
+            [uniform_scale; 3]
+        } else {
+            prop_scale
+        };
+
         Self {
-            model: prop.model_path.into_string(),
-            class_name: prop.prop.entity().class_name.clone(),
-            id: prop.prop.entity().id,
-            position: prop.position.into(),
+            model,
+            class_name: entity.class_name.clone(),
+            id: entity.id,
+            position,
             rotation: [
                 rotation[2].to_radians(),
                 rotation[0].to_radians(),
                 rotation[1].to_radians(),
             ],
-            scale: prop.scale,
-            color: prop
-                .color
-                .map_alpha(|a| f32::from(a) / 255.)
-                .map_rgb(|c| srgb_to_linear(f32::from(c) / 255.))
-                .into(),
+            scale: prop_scale,
+            color,
+            body,
+            skin,
+            disable_shadows,
+            fade_min_distance,
+            fade_max_distance,
+            solid,
+            lighting_origin,
+            lighting_origin_position: None,
+            vertex_lighting,
+            uniform_scale,
             properties,
+            in_3d_skybox: false,
+            ragdoll_group: None,
         }
     }
+
+    pub fn apply_skybox_area(&mut self, area: &SkyboxArea) {
+        self.in_3d_skybox = area.contains_scaled(Vec3::from(self.position));
+    }
+
+    pub fn apply_ragdoll_groups(&mut self, groups: &RagdollGroups) {
+        self.ragdoll_group = groups.group(self.id);
+    }
+
+    pub fn apply_lighting_origins(&mut self, origins: &LightingOrigins) {
+        self.lighting_origin_position = self
+            .lighting_origin
+            .as_deref()
+            .and_then(|name| origins.position(name));
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -403,6 +639,12 @@ impl PySkyCamera {
     fn scale(&self) -> [f32; 3] {
         self.scale
     }
+
+    /// Location which places the 3D skybox around the main map, when the skybox is
+    /// scaled by the sky camera scale.
+    fn skybox_offset(&self) -> [f32; 3] {
+        (-Vec3::from(self.position) * self.scale[0]).into()
+    }
 }
 
 impl PySkyCamera {
@@ -428,6 +670,13 @@ pub struct PyUnknownEntity {
     rotation: [f32; 3],
     scale: [f32; 3],
     properties: BTreeMap<String, String>,
+    keyvalues: BTreeMap<String, String>,
+    property_types: BTreeMap<String, String>,
+    connections: EntityConnections,
+    kind: Option<&'static str>,
+    color: Option<[f32; 3]>,
+    model: Option<String>,
+    gameplay_marker: Option<GameplayMarker>,
 }
 
 #[pymethods]
@@ -455,21 +704,97 @@ impl PyUnknownEntity {
     fn properties(&mut self) -> BTreeMap<String, String> {
         mem::take(&mut self.properties)
     }
+
+    fn property_types(&mut self) -> BTreeMap<String, String> {
+        mem::take(&mut self.property_types)
+    }
+
+    /// Returns the keyvalues as they are in the map, without defaults from the fgd.
+    fn keyvalues(&mut self) -> BTreeMap<String, String> {
+        mem::take(&mut self.keyvalues)
+    }
+
+    /// Returns the outputs of the entity, as
+    /// `output: [(target, input, parameter, delay, times_to_fire)]`.
+    fn connections(&mut self) -> BTreeMap<String, Vec<ConnectionTuple>> {
+        mem::take(&mut self.connections)
+            .into_iter()
+            .map(|(output, connections)| {
+                let connections = connections
+                    .into_iter()
+                    .map(|c| (c.target, c.input, c.parameter, c.delay, c.times_to_fire))
+                    .collect();
+
+                (output, connections)
+            })
+            .collect()
+    }
+
+    fn kind(&self) -> Option<&str> {
+        self.kind
+    }
+
+    fn color(&self) -> Option<[f32; 3]> {
+        self.color
+    }
+
+    fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    /// Kind of gameplay location the entity marks, like `terrorist_spawn`.
+    fn gameplay_marker(&self) -> Option<&str> {
+        self.gameplay_marker.map(GameplayMarker::name)
+    }
+
+    fn gameplay_label(&self) -> Option<&str> {
+        self.gameplay_marker.map(GameplayMarker::label)
+    }
 }
 
 impl PyUnknownEntity {
-    pub fn new(entity: Unknown, scale: f32) -> Self {
+    pub fn new(entity: Unknown, scale: f32, fgd: Option<&Fgd>) -> Self {
         let id = entity.entity().id;
         let class_name = entity.entity().class_name.clone();
+        let gameplay_marker = GameplayMarker::from_class_name(&class_name);
 
         let position = (entity.origin().unwrap_or_default() * scale).into();
         let rotation = entity.angles().unwrap_or_default();
-        let properties = entity
+        let mut properties: BTreeMap<String, String> = entity
             .entity()
             .properties
             .iter()
             .map(|(k, v)| (k.as_str().to_owned(), v.clone()))
             .collect();
+        let keyvalues = properties.clone();
+
+        let mut property_types = BTreeMap::new();
+        let mut kind = None;
+        let mut color = None;
+        let mut model = None;
+
+        if let Some(class) = fgd.and_then(|fgd| fgd.resolve(&class_name)) {
+            kind = Some(class.kind.as_str());
+            color = class
+                .color
+                .map(|c| c.map(|c| srgb_to_linear(f32::from(c) / 255.0)));
+
+            for (name, property) in &class.properties {
+                property_types.insert(name.clone(), property.value_type.clone());
+
+                if let Some(default) = &property.default {
+                    properties
+                        .entry(name.clone())
+                        .or_insert_with(|| default.clone());
+                }
+            }
+
+            model = match class.model {
+                Some(ModelSource::Path(path)) => Some(path.clone()),
+                Some(ModelSource::Keyvalue) => properties.get("model").cloned(),
+                None => None,
+            };
+        }
 
         Self {
             class_name,
@@ -482,6 +807,17 @@ impl PyUnknownEntity {
             ],
             scale: [scale, scale, scale],
             properties,
+            keyvalues,
+            property_types,
+            connections: EntityConnections::new(),
+            kind,
+            color,
+            model,
+            gameplay_marker,
         }
     }
+
+    pub fn set_connections(&mut self, connections: EntityConnections) {
+        self.connections = connections;
+    }
 }
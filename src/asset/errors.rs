@@ -0,0 +1,104 @@
+//! Collects the errors of assets which couldn't be imported, so that scripts can react
+//! to them instead of only seeing them in the log.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use pyo3::prelude::*;
+
+#[derive(Debug, Clone)]
+#[pyclass(module = "plumber", name = "AssetError")]
+pub struct PyAssetError {
+    kind: &'static str,
+    path: Option<String>,
+    error: String,
+}
+
+#[pymethods]
+impl PyAssetError {
+    /// Kind of the asset, like `material` or `model`.
+    fn kind(&self) -> &str {
+        self.kind
+    }
+
+    /// Path of the asset, or the class name and id of an entity, if it's known.
+    fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// Errors shared between the asset handler and the importer.
+#[derive(Debug, Clone, Default)]
+pub struct ErrorLog(Arc<Mutex<Vec<PyAssetError>>>);
+
+impl ErrorLog {
+    fn errors(&self) -> MutexGuard<'_, Vec<PyAssetError>> {
+        // a panicking worker must not lose the errors of everyone else
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    pub fn push(&self, kind: &'static str, path: Option<String>, error: String) {
+        self.errors().push(PyAssetError { kind, path, error });
+    }
+
+    /// Adds an error whose path is only known from the error message. Paths are quoted
+    /// in backticks in the messages, so the first quoted part is taken as the path.
+    pub fn push_message(&self, kind: &'static str, error: String) {
+        let path = quoted_path(&error).map(str::to_owned);
+        self.push(kind, path, error);
+    }
+
+    pub fn to_vec(&self) -> Vec<PyAssetError> {
+        self.errors().clone()
+    }
+
+    /// Number of errors of each asset kind.
+    pub fn counts(&self) -> BTreeMap<&'static str, usize> {
+        let mut counts = BTreeMap::new();
+
+        for error in self.errors().iter() {
+            *counts.entry(error.kind).or_default() += 1;
+        }
+
+        counts
+    }
+}
+
+fn quoted_path(message: &str) -> Option<&str> {
+    let (_, rest) = message.split_once('`')?;
+    let (path, _) = rest.split_once('`')?;
+
+    (!path.is_empty()).then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collect_errors() {
+        let log = ErrorLog::default();
+        log.push_message(
+            "model",
+            "error loading model `models/props/crate.mdl`: file not found".to_owned(),
+        );
+        log.push_message("texture", "unexpected end of file".to_owned());
+        log.push("model", None, "invalid header".to_owned());
+
+        let errors = log.to_vec();
+        assert_eq!(errors[0].path.as_deref(), Some("models/props/crate.mdl"));
+        assert_eq!(errors[1].path, None);
+
+        let counts = log.counts();
+        assert_eq!(counts["model"], 2);
+        assert_eq!(counts["texture"], 1);
+    }
+}
@@ -0,0 +1,347 @@
+//! Reads GoldSrc studio models (`.mdl` version 10), which Half-Life 1 era games use.
+//! Unlike Source models, the vertices are stored in the space of their bone, the faces
+//! are triangle strips and fans, and the textures are embedded in the model or in a
+//! companion `<name>t.mdl`. Animations aren't read, so the models are in their rest pose.
+
+use crate::wad::{self, WadTexture, PALETTE_COLORS};
+
+use super::studio::{read_le, read_string, read_usize, read_vector, StudioError};
+
+pub const VERSION: i32 = 10;
+/// Bytes of the header needed to tell GoldSrc models apart, the id and the version.
+pub const HEADER_LEN: usize = 8;
+
+const NUM_BONES: usize = 140;
+const BONE_INDEX: usize = 144;
+const NUM_TEXTURES: usize = 180;
+const TEXTURE_INDEX: usize = 184;
+const NUM_SKIN_REF: usize = 192;
+const NUM_SKIN_FAMILIES: usize = 196;
+const SKIN_INDEX: usize = 200;
+const NUM_BODY_PARTS: usize = 204;
+const BODY_PART_INDEX: usize = 208;
+
+const BONE_SIZE: usize = 112;
+const BONE_PARENT: usize = 32;
+const BONE_VALUE: usize = 64;
+
+const TEXTURE_SIZE: usize = 80;
+const TEXTURE_FLAGS: usize = 64;
+/// Textures with this flag are transparent where the last palette color is used.
+const TEXTURE_MASKED: i32 = 0x40;
+
+const BODY_PART_SIZE: usize = 76;
+const MODEL_SIZE: usize = 112;
+const MESH_SIZE: usize = 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoldSrcBone {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub position: [f32; 3],
+    /// Euler angles in radians, applied in XYZ order.
+    pub rotation: [f32; 3],
+}
+
+/// A vertex of a triangle, with separately indexed positions and normals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriangleVertex {
+    pub vertex: usize,
+    pub normal: usize,
+    /// Texture coordinates in pixels.
+    pub s: i16,
+    pub t: i16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Triangle {
+    pub skin_ref: usize,
+    /// In the winding order of the file.
+    pub vertices: [TriangleVertex; 3],
+}
+
+/// A model of a body part. The positions and normals are relative to their bones.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GoldSrcMesh {
+    pub name: String,
+    pub body_part: String,
+    pub vertices: Vec<([f32; 3], usize)>,
+    pub normals: Vec<([f32; 3], usize)>,
+    pub triangles: Vec<Triangle>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GoldSrcModel {
+    pub bones: Vec<GoldSrcBone>,
+    pub meshes: Vec<GoldSrcMesh>,
+    pub textures: Vec<WadTexture>,
+    /// Texture index of each skin reference, per skin family.
+    pub skin_families: Vec<Vec<usize>>,
+}
+
+/// The name of the material of an embedded texture, in the directory named like the model
+/// since the texture names are only unique within a model.
+pub fn material_name(model_name: &str, texture_name: &str) -> String {
+    let model = model_name.strip_suffix(".mdl").unwrap_or(model_name);
+    let texture = texture_name
+        .rsplit_once('.')
+        .map_or(texture_name, |(stem, _)| stem);
+
+    format!("{model}/{}", texture.to_ascii_lowercase())
+}
+
+/// Whether the data is a GoldSrc model instead of a Source model.
+pub fn is_goldsrc(data: &[u8]) -> bool {
+    data.starts_with(b"IDST") && read_le::<i32>(data, 4).is_ok_and(|v| v == VERSION)
+}
+
+impl GoldSrcModel {
+    /// # Errors
+    ///
+    /// Returns `Err` if the data is not a valid GoldSrc model.
+    pub fn read(data: &[u8]) -> Result<Self, StudioError> {
+        if !is_goldsrc(data) {
+            return Err(StudioError::InvalidHeader);
+        }
+
+        let (textures, skin_families) = read_textures(data)?;
+
+        Ok(Self {
+            bones: read_bones(data)?,
+            meshes: read_meshes(data)?,
+            textures,
+            skin_families,
+        })
+    }
+
+    /// Uses the textures of the companion texture model, for models which don't embed
+    /// their textures.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the data is not a valid GoldSrc model.
+    pub fn read_external_textures(&mut self, data: &[u8]) -> Result<(), StudioError> {
+        if !is_goldsrc(data) {
+            return Err(StudioError::InvalidHeader);
+        }
+
+        (self.textures, self.skin_families) = read_textures(data)?;
+        Ok(())
+    }
+
+    /// The texture index of a skin reference in the default skin.
+    pub fn texture_index(&self, skin_ref: usize) -> usize {
+        self.skin_families
+            .first()
+            .and_then(|family| family.get(skin_ref))
+            .copied()
+            .unwrap_or(skin_ref)
+    }
+}
+
+fn read_bones(data: &[u8]) -> Result<Vec<GoldSrcBone>, StudioError> {
+    let count = read_usize(data, NUM_BONES)?;
+    let index = read_usize(data, BONE_INDEX)?;
+
+    (0..count)
+        .map(|i| {
+            let offset = index + i * BONE_SIZE;
+
+            Ok(GoldSrcBone {
+                name: read_string(data, offset)?,
+                parent: usize::try_from(read_le::<i32>(data, offset + BONE_PARENT)?).ok(),
+                position: read_vector(data, offset + BONE_VALUE)?,
+                rotation: read_vector(data, offset + BONE_VALUE + 12)?,
+            })
+        })
+        .collect()
+}
+
+fn read_textures(data: &[u8]) -> Result<(Vec<WadTexture>, Vec<Vec<usize>>), StudioError> {
+    let count = read_usize(data, NUM_TEXTURES)?;
+    let index = read_usize(data, TEXTURE_INDEX)?;
+
+    let textures = (0..count)
+        .map(|i| {
+            let offset = index + i * TEXTURE_SIZE;
+            let flags = read_le::<i32>(data, offset + TEXTURE_FLAGS)?;
+            let width = read_usize(data, offset + TEXTURE_FLAGS + 4)?;
+            let height = read_usize(data, offset + TEXTURE_FLAGS + 8)?;
+            let pixel_index = read_usize(data, offset + TEXTURE_FLAGS + 12)?;
+
+            // the palette follows the pixels
+            let pixel_count = width * height;
+            let pixels = data
+                .get(pixel_index..pixel_index + pixel_count)
+                .ok_or(StudioError::UnexpectedEof)?;
+            let palette = data
+                .get(pixel_index + pixel_count..pixel_index + pixel_count + PALETTE_COLORS * 3)
+                .ok_or(StudioError::UnexpectedEof)?;
+
+            Ok(wad::decode_palettized(
+                read_string(data, offset)?,
+                (
+                    u32::try_from(width).map_err(|_| StudioError::InvalidHeader)?,
+                    u32::try_from(height).map_err(|_| StudioError::InvalidHeader)?,
+                ),
+                pixels,
+                palette,
+                flags & TEXTURE_MASKED != 0,
+            ))
+        })
+        .collect::<Result<_, StudioError>>()?;
+
+    let skin_ref_count = read_usize(data, NUM_SKIN_REF)?;
+    let family_count = read_usize(data, NUM_SKIN_FAMILIES)?;
+    let skin_index = read_usize(data, SKIN_INDEX)?;
+
+    let skin_families = (0..family_count)
+        .map(|family| {
+            (0..skin_ref_count)
+                .map(|i| {
+                    let offset = skin_index + (family * skin_ref_count + i) * 2;
+                    read_le::<u16>(data, offset).map(usize::from)
+                })
+                .collect()
+        })
+        .collect::<Result<_, StudioError>>()?;
+
+    Ok((textures, skin_families))
+}
+
+fn read_meshes(data: &[u8]) -> Result<Vec<GoldSrcMesh>, StudioError> {
+    let count = read_usize(data, NUM_BODY_PARTS)?;
+    let index = read_usize(data, BODY_PART_INDEX)?;
+
+    let mut meshes = Vec::new();
+
+    for i in 0..count {
+        let offset = index + i * BODY_PART_SIZE;
+        let body_part = read_string(data, offset)?;
+        let model_count = read_usize(data, offset + 64)?;
+        let model_index = read_usize(data, offset + 72)?;
+
+        for j in 0..model_count {
+            let mut mesh = read_model(data, model_index + j * MODEL_SIZE)?;
+            mesh.body_part.clone_from(&body_part);
+            meshes.push(mesh);
+        }
+    }
+
+    Ok(meshes)
+}
+
+fn read_model(data: &[u8], offset: usize) -> Result<GoldSrcMesh, StudioError> {
+    let mesh_count = read_usize(data, offset + 72)?;
+    let mesh_index = read_usize(data, offset + 76)?;
+
+    let mut triangles = Vec::new();
+
+    for i in 0..mesh_count {
+        let mesh_offset = mesh_index + i * MESH_SIZE;
+        let triangle_index = read_usize(data, mesh_offset + 4)?;
+        let skin_ref = read_usize(data, mesh_offset + 8)?;
+
+        read_triangle_commands(data, triangle_index, skin_ref, &mut triangles)?;
+    }
+
+    Ok(GoldSrcMesh {
+        name: read_string(data, offset)?,
+        body_part: String::new(),
+        vertices: read_bone_vectors(data, offset + 80)?,
+        normals: read_bone_vectors(data, offset + 92)?,
+        triangles,
+    })
+}
+
+/// Reads `count, bone index, vector index` of the vertices or normals of a model.
+fn read_bone_vectors(data: &[u8], offset: usize) -> Result<Vec<([f32; 3], usize)>, StudioError> {
+    let count = read_usize(data, offset)?;
+    let bone_index = read_usize(data, offset + 4)?;
+    let index = read_usize(data, offset + 8)?;
+
+    (0..count)
+        .map(|i| {
+            let bone = data.get(bone_index + i).ok_or(StudioError::UnexpectedEof)?;
+
+            Ok((read_vector(data, index + i * 12)?, usize::from(*bone)))
+        })
+        .collect()
+}
+
+/// Reads triangle strips and fans until the terminating zero. A negative vertex count
+/// starts a fan.
+fn read_triangle_commands(
+    data: &[u8],
+    mut offset: usize,
+    skin_ref: usize,
+    triangles: &mut Vec<Triangle>,
+) -> Result<(), StudioError> {
+    loop {
+        let count = read_le::<i16>(data, offset)?;
+        offset += 2;
+
+        if count == 0 {
+            return Ok(());
+        }
+
+        let vertices = (0..count.unsigned_abs())
+            .map(|_| {
+                let vertex = TriangleVertex {
+                    vertex: usize::from(read_le::<u16>(data, offset)?),
+                    normal: usize::from(read_le::<u16>(data, offset + 2)?),
+                    s: read_le::<i16>(data, offset + 4)?,
+                    t: read_le::<i16>(data, offset + 6)?,
+                };
+                offset += 8;
+                Ok(vertex)
+            })
+            .collect::<Result<Vec<_>, StudioError>>()?;
+
+        for i in 2..vertices.len() {
+            let corners = if count < 0 {
+                [vertices[0], vertices[i - 1], vertices[i]]
+            } else if i % 2 == 0 {
+                [vertices[i - 2], vertices[i - 1], vertices[i]]
+            } else {
+                // every other triangle of a strip is flipped to keep the winding order
+                [vertices[i - 1], vertices[i - 2], vertices[i]]
+            };
+
+            triangles.push(Triangle {
+                skin_ref,
+                vertices: corners,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_triangle_strips_and_fans() {
+        let vertex = |i: u16| [i, i, 0, 0].map(u16::to_le_bytes).concat();
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&4_i16.to_le_bytes());
+        (0..4).for_each(|i| data.extend(vertex(i)));
+        data.extend_from_slice(&(-4_i16).to_le_bytes());
+        (4..8).for_each(|i| data.extend(vertex(i)));
+        data.extend_from_slice(&0_i16.to_le_bytes());
+
+        let mut triangles = Vec::new();
+        read_triangle_commands(&data, 0, 3, &mut triangles).unwrap();
+
+        let indices: Vec<[usize; 3]> = triangles
+            .iter()
+            .map(|t| t.vertices.map(|v| v.vertex))
+            .collect();
+        assert_eq!(indices, [[0, 1, 2], [2, 1, 3], [4, 5, 6], [4, 6, 7]]);
+        assert!(triangles.iter().all(|t| t.skin_ref == 3));
+
+        assert!(!is_goldsrc(b"IDST\x30\x00\x00\x00"));
+        assert!(is_goldsrc(b"IDST\x0a\x00\x00\x00"));
+    }
+}
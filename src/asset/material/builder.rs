@@ -10,6 +10,7 @@ use plumber_core::{
     asset_core::Context,
     asset_vmt::VmtHelper,
     asset_vtf::VtfConfig,
+    fs::GamePathBuf,
     uncased::AsUncased,
     vmt::{MaterialInfo, TexturePath, Transform},
 };
@@ -17,9 +18,15 @@ use plumber_core::{
 use crate::asset::BlenderAssetHandler;
 
 use super::{
-    builder_base::{ColorSpace, InputLink, MaterialBuilder},
+    builder_base::{
+        AlphaUsage, ColorSpace, InputLink, MaterialBuilder, DISPLACEMENT_SOCKET, VOLUME_SOCKET,
+    },
+    cache,
     definitions::{groups, shaders},
-    nodes::{NodeSocketId, Ref, Value},
+    loose::read_loose_texture,
+    nodes::{NodeGroup, NodeSocketId, NodeType, Ref, Value},
+    proxies::TransformAnimations,
+    rad::TexLight,
     BuiltMaterialData,
 };
 
@@ -52,7 +59,7 @@ impl Default for TextureInterpolation {
 }
 
 impl TextureInterpolation {
-    fn to_str(self) -> &'static str {
+    pub(super) fn to_str(self) -> &'static str {
         match self {
             TextureInterpolation::Linear => "Linear",
             TextureInterpolation::Closest => "Closest",
@@ -62,10 +69,142 @@ impl TextureInterpolation {
     }
 }
 
+/// How height maps are rendered.
 #[derive(Debug, Clone, Copy)]
+pub enum HeightMaps {
+    Bump,
+    /// Displaces the surface, which only Cycles renders.
+    Displacement,
+}
+
+impl FromStr for HeightMaps {
+    type Err = PyErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Bump" => Ok(Self::Bump),
+            "Displacement" => Ok(Self::Displacement),
+            _ => Err(PyValueError::new_err("invalid height map mode")),
+        }
+    }
+}
+
+impl Default for HeightMaps {
+    fn default() -> Self {
+        Self::Bump
+    }
+}
+
+/// What happens to the alpha of base textures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaseAlpha {
+    /// Kept as an independent channel.
+    Preserve,
+    /// Ignored, so that nothing becomes transparent.
+    Strip,
+    /// Already multiplied into the colors, so the colors are divided by it before the
+    /// shader blends them with the alpha, which avoids dark halos around transparent edges.
+    Premultiplied,
+    /// Stripped if it's unused, preserved otherwise.
+    Auto,
+}
+
+impl FromStr for BaseAlpha {
+    type Err = PyErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Preserve" => Ok(Self::Preserve),
+            "Strip" => Ok(Self::Strip),
+            "Premultiplied" => Ok(Self::Premultiplied),
+            "Auto" => Ok(Self::Auto),
+            _ => Err(PyValueError::new_err("invalid base alpha policy")),
+        }
+    }
+}
+
+impl Default for BaseAlpha {
+    fn default() -> Self {
+        Self::Preserve
+    }
+}
+
+impl BaseAlpha {
+    /// Resolves the policy for the way a material uses the alpha, which is never `Auto`.
+    fn resolve(self, usage: AlphaUsage) -> Self {
+        match (self, usage) {
+            (Self::Auto, AlphaUsage::Unused) => Self::Strip,
+            // the shader blends with straight alpha, like the textures are usually stored
+            (Self::Auto, AlphaUsage::Transparency | AlphaUsage::Mask) => Self::Preserve,
+            (policy, _) => policy,
+        }
+    }
+}
+
+/// Which kind of tool material a material is, if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolMaterial {
+    NoDraw,
+    Clip,
+    Trigger,
+    Hint,
+    Skip,
+    Skybox,
+    Ladder,
+    Normal,
+}
+
+impl ToolMaterial {
+    pub fn new(vmt: &VmtHelper, info: &MaterialInfo) -> Self {
+        Self::from_flags(info.no_draw(), |flag| vmt.extract_param_or_default(flag))
+    }
+
+    fn from_flags(no_draw: bool, flag: impl Fn(&str) -> bool) -> Self {
+        // tool materials usually also set `%compilenodraw`, so it's checked last
+        if flag("%compilesky") || flag("%compile2dsky") {
+            Self::Skybox
+        } else if flag("%compilehint") {
+            Self::Hint
+        } else if flag("%compileskip") {
+            Self::Skip
+        } else if flag("%compiletrigger") {
+            Self::Trigger
+        } else if ["%compileclip", "%compileplayerclip", "%compilenpcclip"]
+            .iter()
+            .any(|f| flag(f))
+        {
+            Self::Clip
+        } else if flag("%compileladder") {
+            Self::Ladder
+        } else if no_draw || flag("%compilenodraw") {
+            Self::NoDraw
+        } else {
+            Self::Normal
+        }
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            ToolMaterial::NoDraw => "NODRAW",
+            ToolMaterial::Clip => "CLIP",
+            ToolMaterial::Trigger => "TRIGGER",
+            ToolMaterial::Hint => "HINT",
+            ToolMaterial::Skip => "SKIP",
+            ToolMaterial::Skybox => "SKYBOX",
+            ToolMaterial::Ladder => "LADDER",
+            ToolMaterial::Normal => "NORMAL",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TextureFormat {
     Tga,
     Png,
+    /// 32-bit float OpenEXR, which keeps the range of HDR textures.
+    Exr,
+    /// Unencoded pixels, which are set on the Blender image directly.
+    Raw,
 }
 
 impl FromStr for TextureFormat {
@@ -75,6 +214,8 @@ impl FromStr for TextureFormat {
         match s {
             "Tga" => Ok(Self::Tga),
             "Png" => Ok(Self::Png),
+            "Exr" => Ok(Self::Exr),
+            "Raw" => Ok(Self::Raw),
             _ => Err(PyValueError::new_err("invalid texture format")),
         }
     }
@@ -91,19 +232,102 @@ impl TextureFormat {
         match self {
             TextureFormat::Tga => ".tga",
             TextureFormat::Png => ".png",
+            TextureFormat::Exr => ".exr",
+            TextureFormat::Raw => "",
+        }
+    }
+
+    /// Returns the format images are encoded in, or `None` if they aren't encoded.
+    pub fn to_output_format(self) -> Option<ImageOutputFormat> {
+        match self {
+            TextureFormat::Tga => Some(ImageOutputFormat::Tga),
+            TextureFormat::Png => Some(ImageOutputFormat::Png),
+            TextureFormat::Exr => Some(ImageOutputFormat::OpenExr),
+            TextureFormat::Raw => None,
+        }
+    }
+}
+
+/// Blender version the built node trees are targeted for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TargetVersion {
+    Blender3,
+    Blender4,
+    /// EEVEE Next, which replaced `blend_method` and `shadow_method`
+    Blender4_2,
+}
+
+impl Default for TargetVersion {
+    fn default() -> Self {
+        Self::Blender3
+    }
+}
+
+impl TargetVersion {
+    pub fn from_blender_version((major, minor): (u32, u32)) -> Self {
+        match (major, minor) {
+            (0..=3, _) => Self::Blender3,
+            (4, 0..=1) => Self::Blender4,
+            _ => Self::Blender4_2,
         }
     }
 
-    pub fn to_output_format(self) -> ImageOutputFormat {
+    pub(super) fn principled_shader(
+        self,
+    ) -> (&'static NodeType, &'static [(&'static str, &'static str)]) {
         match self {
-            TextureFormat::Tga => ImageOutputFormat::Tga,
-            TextureFormat::Png => ImageOutputFormat::Png,
+            TargetVersion::Blender3 => (&shaders::PRINCIPLED, &[]),
+            TargetVersion::Blender4 | TargetVersion::Blender4_2 => {
+                (&shaders::PRINCIPLED_4, shaders::PRINCIPLED_4_SOCKET_MAP)
+            }
+        }
+    }
+}
+
+/// How the surface of a material is transparent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Transparency {
+    Opaque,
+    /// Cut out, like alpha tested materials.
+    Clip,
+    /// Blended with what's behind it, like translucent materials.
+    Blend,
+}
+
+impl TargetVersion {
+    /// Properties of materials which set how their transparency is rendered.
+    pub(super) fn transparency_properties(
+        self,
+        transparency: Transparency,
+    ) -> [(&'static str, Value); 2] {
+        if self >= TargetVersion::Blender4_2 {
+            let method = if transparency == Transparency::Blend {
+                "BLENDED"
+            } else {
+                "DITHERED"
+            };
+
+            [
+                ("surface_render_method", Value::Enum(method)),
+                (
+                    "use_transparent_shadow",
+                    Value::Bool(transparency != Transparency::Opaque),
+                ),
+            ]
+        } else {
+            let method = Value::Enum(if transparency == Transparency::Opaque {
+                "OPAQUE"
+            } else {
+                "HASHED"
+            });
+
+            [("blend_method", method.clone()), ("shadow_method", method)]
         }
     }
 }
 
 #[allow(clippy::struct_excessive_bools)]
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy)]
 pub struct Settings {
     pub import_materials: bool,
     pub simple_materials: bool,
@@ -111,9 +335,109 @@ pub struct Settings {
     pub editor_materials: bool,
     pub texture_interpolation: TextureInterpolation,
     pub texture_format: TextureFormat,
+    /// Textures larger than this are downscaled like their mipmaps are.
+    pub texture_max_resolution: Option<u32>,
+    pub texture_color_correction: ColorCorrection,
+    pub target_version: TargetVersion,
+    /// Multiplier for the strength of self-illuminated materials.
+    pub emission_strength: f32,
+    pub height_maps: HeightMaps,
+    /// Derives a height map from self-shadowing bump maps, which darkens some of them.
+    pub ssbump_height: bool,
+    pub base_alpha: BaseAlpha,
+    /// Scale of the imported geometry, for distances given in units.
+    pub scale: f32,
+}
+
+/// Adjustment of the colors of textures, for games whose textures look too dark or too
+/// bright due to being authored for a different gamma.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorCorrection {
+    pub brightness: f32,
+    pub gamma: f32,
+}
+
+impl Default for ColorCorrection {
+    fn default() -> Self {
+        Self {
+            brightness: 1.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+impl ColorCorrection {
+    /// Whether the correction leaves the colors unchanged.
+    #[must_use]
+    pub fn is_identity(self) -> bool {
+        (self.brightness - 1.0).abs() < f32::EPSILON && (self.gamma - 1.0).abs() < f32::EPSILON
+    }
+
+    /// The correction, unless it leaves the colors unchanged.
+    #[must_use]
+    pub fn enabled(self) -> Option<Self> {
+        (!self.is_identity()).then_some(self)
+    }
+
+    /// Corrects a color channel between 0 and 1. Values above 1 of HDR textures are
+    /// kept.
+    #[must_use]
+    pub fn apply(self, value: f32) -> f32 {
+        value.max(0.0).powf(self.gamma.max(f32::EPSILON).recip()) * self.brightness
+    }
+
+    /// Corrects the color channels of a color, keeping the alpha.
+    #[must_use]
+    pub fn apply_color(self, [r, g, b, a]: [f32; 4]) -> [f32; 4] {
+        [self.apply(r), self.apply(g), self.apply(b), a]
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            import_materials: false,
+            simple_materials: false,
+            allow_culling: false,
+            editor_materials: false,
+            texture_interpolation: TextureInterpolation::default(),
+            texture_format: TextureFormat::default(),
+            texture_max_resolution: None,
+            texture_color_correction: ColorCorrection::default(),
+            target_version: TargetVersion::default(),
+            emission_strength: 1.0,
+            height_maps: HeightMaps::default(),
+            ssbump_height: false,
+            base_alpha: BaseAlpha::default(),
+            scale: 0.01,
+        }
+    }
 }
 
 impl MaterialBuilder {
+    /// Loads a texture, falling back to a loose image if the `.vtf` can't be loaded.
+    fn load_texture(
+        &mut self,
+        context: &mut Context<BlenderAssetHandler>,
+        texture_path: &GamePathBuf,
+    ) -> Result<(), String> {
+        let error = match context.depend_on(VtfConfig, texture_path.clone().into()) {
+            Ok(_) => return Ok(()),
+            Err(error) => error.to_string(),
+        };
+
+        let name = texture_path.as_str();
+        if self.loose_textures.iter().any(|t| t.name == name) {
+            return Ok(());
+        }
+
+        let texture =
+            read_loose_texture(texture_path, |path| context.fs().read(path).ok()).ok_or(error)?;
+        self.loose_textures.push(texture);
+
+        Ok(())
+    }
+
     fn handle_texture(
         &mut self,
         context: &mut Context<BlenderAssetHandler>,
@@ -123,13 +447,21 @@ impl MaterialBuilder {
         color_space: ColorSpace,
         interpolation: TextureInterpolation,
     ) -> bool {
-        self.handle_texture_inner(context, vmt, parameter, color_space, interpolation, |vmt| {
-            if let Some(transform_parameter) = transform_parameter {
-                vmt.extract_param_or_default(transform_parameter)
-            } else {
-                Transform::default()
-            }
-        })
+        self.handle_texture_inner(
+            context,
+            vmt,
+            parameter,
+            transform_parameter,
+            color_space,
+            interpolation,
+            |vmt| {
+                if let Some(transform_parameter) = transform_parameter {
+                    vmt.extract_param_or_default(transform_parameter)
+                } else {
+                    Transform::default()
+                }
+            },
+        )
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -143,21 +475,29 @@ impl MaterialBuilder {
         color_space: ColorSpace,
         interpolation: TextureInterpolation,
     ) -> bool {
-        self.handle_texture_inner(context, vmt, parameter, color_space, interpolation, |vmt| {
-            let mut transform: Transform = vmt.extract_param_or_default(transform_parameter);
-
-            let scale = vmt
-                .try_extract_param::<Vec3>(scale_parameter)
-                .map(|o| o.map(Vec3::truncate))
-                .or_else(|_| vmt.try_extract_param::<Vec2>(scale_parameter))
-                .unwrap_or_else(|_| vmt.extract_param::<f32>(scale_parameter).map(Vec2::splat));
-
-            if let Some(scale) = scale {
-                transform.scale *= scale;
-            }
+        self.handle_texture_inner(
+            context,
+            vmt,
+            parameter,
+            Some(transform_parameter),
+            color_space,
+            interpolation,
+            |vmt| {
+                let mut transform: Transform = vmt.extract_param_or_default(transform_parameter);
+
+                let scale = vmt
+                    .try_extract_param::<Vec3>(scale_parameter)
+                    .map(|o| o.map(Vec3::truncate))
+                    .or_else(|_| vmt.try_extract_param::<Vec2>(scale_parameter))
+                    .unwrap_or_else(|_| vmt.extract_param::<f32>(scale_parameter).map(Vec2::splat));
+
+                if let Some(scale) = scale {
+                    transform.scale *= scale;
+                }
 
-            transform
-        })
+                transform
+            },
+        )
     }
 
     fn handle_texture_4wayblend(
@@ -169,16 +509,24 @@ impl MaterialBuilder {
         color_space: ColorSpace,
         interpolation: TextureInterpolation,
     ) -> bool {
-        self.handle_texture_inner(context, vmt, parameter, color_space, interpolation, |vmt| {
-            let mut transform = Transform::default();
+        self.handle_texture_inner(
+            context,
+            vmt,
+            parameter,
+            None,
+            color_space,
+            interpolation,
+            |vmt| {
+                let mut transform = Transform::default();
 
-            let base_uv_scale = vmt.extract_param("$texture1_uvscale").unwrap_or(Vec2::ONE);
-            let uv_scale = vmt.extract_param(uv_scale_parameter).unwrap_or(Vec2::ONE);
+                let base_uv_scale = vmt.extract_param("$texture1_uvscale").unwrap_or(Vec2::ONE);
+                let uv_scale = vmt.extract_param(uv_scale_parameter).unwrap_or(Vec2::ONE);
 
-            transform.scale = base_uv_scale * uv_scale;
+                transform.scale = base_uv_scale * uv_scale;
 
-            transform
-        })
+                transform
+            },
+        )
     }
 
     fn handle_texture_split(
@@ -195,8 +543,8 @@ impl MaterialBuilder {
         {
             let texture_path = texture.absolute_path();
 
-            match context.depend_on(VtfConfig, texture_path.clone().into()) {
-                Ok(_) => {
+            match self.load_texture(context, &texture_path) {
+                Ok(()) => {
                     self.texture_color_spaces
                         .insert(texture_path.clone().into_string(), ColorSpace::NonColor);
 
@@ -232,11 +580,13 @@ impl MaterialBuilder {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn handle_texture_inner(
         &mut self,
         context: &mut Context<BlenderAssetHandler>,
         vmt: &VmtHelper,
         parameter: &'static str,
+        transform_parameter: Option<&'static str>,
         color_space: ColorSpace,
         interpolation: TextureInterpolation,
         get_transform: impl Fn(&VmtHelper) -> Transform,
@@ -249,13 +599,23 @@ impl MaterialBuilder {
             let mut texture_path = texture.absolute_path();
             texture_path.set_extension("");
 
-            match context.depend_on(VtfConfig, texture_path.clone().into()) {
-                Ok(_) => {
+            // base textures are marked before they are requested, since the texture is
+            // converted as soon as it's loaded
+            if parameter.starts_with("$basetexture") {
+                cache::mark_base_texture(texture_path.as_str());
+                self.base_textures
+                    .insert(texture_path.clone().into_string());
+            }
+
+            match self.load_texture(context, &texture_path) {
+                Ok(()) => {
                     self.texture_color_spaces
                         .insert(texture_path.clone().into_string(), color_space);
                     let transform: Transform = get_transform(vmt);
+                    let animation =
+                        transform_parameter.and_then(|p| self.transform_animations.get(p));
 
-                    if transform == Transform::default() {
+                    if transform == Transform::default() && animation.is_none() {
                         self.input(parameter)
                             .pipeline(vec![&groups::TEXTURE])
                             .property(&groups::TEXTURE, "image", Value::Texture(texture_path))
@@ -265,9 +625,27 @@ impl MaterialBuilder {
                                 Value::Enum(interpolation.to_str()),
                             );
                     } else {
+                        // the game scales and rotates around the center
                         let scale = transform.scale.extend(1.0).to_array();
+                        let center = transform.center.extend(0.0).to_array();
                         let rotation = [0.0, 0.0, transform.rotate];
-                        let location = transform.translate.extend(0.0).to_array();
+                        let location = (transform.center + transform.translate)
+                            .extend(0.0)
+                            .to_array();
+
+                        let (rotation, location) = match animation {
+                            Some(animation) => (
+                                Value::AnimatedVec(
+                                    rotation,
+                                    [0.0, 0.0, animation.rotate_rate.to_radians()],
+                                ),
+                                Value::AnimatedVec(
+                                    location,
+                                    animation.translate_rate.extend(0.0).to_array(),
+                                ),
+                            ),
+                            None => (Value::Vec(rotation), Value::Vec(location)),
+                        };
 
                         self.input(parameter)
                             .pipeline(vec![&groups::TRANSFORMED_TEXTURE])
@@ -282,16 +660,9 @@ impl MaterialBuilder {
                                 Value::Enum(interpolation.to_str()),
                             )
                             .link(&groups::TRANSFORMED_TEXTURE, "scale", Value::Vec(scale))
-                            .link(
-                                &groups::TRANSFORMED_TEXTURE,
-                                "rotation",
-                                Value::Vec(rotation),
-                            )
-                            .link(
-                                &groups::TRANSFORMED_TEXTURE,
-                                "location",
-                                Value::Vec(location),
-                            );
+                            .link(&groups::TRANSFORMED_TEXTURE, "center", Value::Vec(center))
+                            .link(&groups::TRANSFORMED_TEXTURE, "rotation", rotation)
+                            .link(&groups::TRANSFORMED_TEXTURE, "location", location);
                     }
 
                     true
@@ -320,24 +691,133 @@ fn build_nodraw_material() -> BuiltMaterialData {
     builder.build()
 }
 
-fn build_water_material(
+/// Eyeball radius used by most HL2 characters, which the cornea normal strength is relative to.
+const DEFAULT_EYEBALL_RADIUS: f32 = 0.5;
+
+/// Approximates `EyeRefract` and `Eyes` with the iris as the base color
+/// and a glossy coat layer as the cornea.
+fn build_eye_material(
     context: &mut Context<BlenderAssetHandler>,
     vmt: &VmtHelper,
     settings: Settings,
 ) -> BuiltMaterialData {
-    let mut builder = MaterialBuilder::new(&shaders::GLASS);
+    let (shader, socket_map) = settings.target_version.principled_shader();
+
+    let mut builder = MaterialBuilder::new(shader);
+    builder.socket_map(socket_map);
 
     builder
-        .socket_value("IOR", Value::Float(1.333))
+        .socket_value("Specular", Value::Float(0.5))
         .socket_value("Roughness", Value::Float(0.3));
 
-    if vmt.extract_param_or_default("$fogenable") {
-        if let Some(color) = vmt.extract_param::<RGB<f32>>("$fogcolor") {
-            builder.socket_value(
-                NodeSocketId::Name("Color"),
-                Value::Color(color.alpha(1.0).into()),
-            );
+    let iris = if vmt.shader().shader.as_uncased_str() == "eyes".as_uncased() {
+        "$basetexture"
+    } else {
+        "$iris"
+    };
+
+    if builder.handle_texture(
+        context,
+        vmt,
+        iris,
+        None,
+        ColorSpace::Srgb,
+        settings.texture_interpolation,
+    ) {
+        builder.output("Base Color", iris, "color");
+    }
+
+    if settings.simple_materials {
+        builder.socket_value("Roughness", Value::Float(0.1));
+        return builder.build();
+    }
+
+    builder
+        .socket_value("Clearcoat", Value::Float(1.0))
+        .socket_value("Clearcoat Roughness", Value::Float(0.03));
+
+    if builder.handle_texture(
+        context,
+        vmt,
+        "$corneatexture",
+        None,
+        ColorSpace::NonColor,
+        settings.texture_interpolation,
+    ) {
+        // the cornea bulge looks flatter on larger eyeballs
+        let radius = vmt
+            .extract_param::<f32>("$eyeballradius")
+            .filter(|&r| r > 0.0)
+            .unwrap_or(DEFAULT_EYEBALL_RADIUS);
+        let strength = (DEFAULT_EYEBALL_RADIUS / radius).min(1.0);
+
+        builder
+            .output("Normal", "$corneatexture", "color")
+            .push(&groups::DX_NORMAL_MAP_CONVERTER)
+            .link_input(&groups::DX_NORMAL_MAP_CONVERTER, "image")
+            .push(&groups::NORMAL_MAP)
+            .link(&groups::NORMAL_MAP, "strength", Value::Float(strength));
+    }
+
+    builder.build()
+}
+
+fn build_water_material(
+    context: &mut Context<BlenderAssetHandler>,
+    vmt: &VmtHelper,
+    animations: TransformAnimations,
+    settings: Settings,
+) -> BuiltMaterialData {
+    let mut builder = MaterialBuilder::new(&shaders::GLASS);
+    builder.transform_animations(animations);
+
+    builder.socket_value("IOR", Value::Float(1.333));
+
+    let fog_color = vmt
+        .extract_param_or_default::<bool>("$fogenable")
+        .then(|| vmt.extract_param::<RGB<f32>>("$fogcolor"))
+        .flatten();
+
+    // the refraction texture is what makes water see-through in the game,
+    // the surface seen from below doesn't have fog
+    let volume = !settings.simple_materials
+        && vmt
+            .shader()
+            .extract_param::<TexturePath>("$refracttexture", vmt.material_path().into())
+            .is_some()
+        && vmt.extract_param("$abovewater").unwrap_or(true);
+
+    if volume {
+        let tint = vmt
+            .extract_param::<RGB<f32>>("$refracttint")
+            .unwrap_or(RGB::new(1.0, 1.0, 1.0));
+        builder.socket_value(
+            NodeSocketId::Name("Color"),
+            Value::Color(tint.alpha(1.0).into()),
+        );
+
+        if let Some(color) = fog_color {
+            builder.push_water_fog(vmt, color, settings.scale);
         }
+    } else if let Some(color) = fog_color {
+        builder.socket_value(
+            NodeSocketId::Name("Color"),
+            Value::Color(color.alpha(1.0).into()),
+        );
+    }
+
+    let roughness = if settings.simple_materials {
+        None
+    } else {
+        vmt.extract_param::<f32>("$reflectance")
+    };
+    builder.socket_value(
+        "Roughness",
+        Value::Float(roughness.map_or(0.3, |r| 0.3 * (1.0 - r.clamp(0.0, 1.0)))),
+    );
+
+    if !settings.simple_materials {
+        builder.push_flow_map(context, vmt);
     }
 
     if builder.handle_texture(
@@ -367,6 +847,80 @@ fn build_water_material(
     builder.build()
 }
 
+/// Flow map parameters, stored on the material since the flow animation
+/// can't be reproduced with material nodes alone.
+const FLOW_PARAMETERS: &[(&str, &str)] = &[
+    ("$flow_worlduvscale", "flow_world_uv_scale"),
+    ("$flow_normaluvscale", "flow_normal_uv_scale"),
+    ("$flow_timeintervalinseconds", "flow_time_interval"),
+    ("$flow_timescale", "flow_time_scale"),
+    ("$flow_uvscrolldistance", "flow_uv_scroll_distance"),
+    ("$flow_bumpstrength", "flow_bump_strength"),
+    ("$flow_noise_scale", "flow_noise_scale"),
+];
+
+/// Fog is fully opaque at this optical depth, where less than 5% of the light is left.
+const FOG_OPTICAL_DEPTH: f32 = 3.0;
+
+impl MaterialBuilder {
+    /// Adds a volume absorbing light like the water fog, which fades linearly
+    /// between `$fogstart` and `$fogend` in the game.
+    fn push_water_fog(&mut self, vmt: &VmtHelper, color: RGB<f32>, scale: f32) {
+        let fog_start = vmt.extract_param::<f32>("$fogstart").unwrap_or(0.0);
+        let Some(fog_end) = vmt.extract_param::<f32>("$fogend") else {
+            return;
+        };
+
+        let fog_distance = (fog_end - fog_start).max(1.0) * scale;
+
+        self.input("$fogcolor")
+            .push(&groups::VOLUME_ABSORPTION)
+            .link(
+                &groups::VOLUME_ABSORPTION,
+                "color",
+                Value::Color(color.alpha(1.0).into()),
+            )
+            .link(
+                &groups::VOLUME_ABSORPTION,
+                "density",
+                Value::Float(FOG_OPTICAL_DEPTH / fog_distance),
+            );
+
+        self.output(VOLUME_SOCKET, "$fogcolor", "volume");
+    }
+
+    fn push_flow_map(&mut self, context: &mut Context<BlenderAssetHandler>, vmt: &VmtHelper) {
+        let Some(texture) = vmt
+            .shader()
+            .extract_param::<TexturePath>("$flowmap", vmt.material_path().into())
+        else {
+            return;
+        };
+
+        let texture_path = texture.absolute_path();
+
+        if let Err(err) = self.load_texture(context, &texture_path) {
+            warn!(
+                "material `{}`: parameter `$flowmap`: error loading texture `{}`: {}",
+                vmt.material_path(),
+                texture_path,
+                err
+            );
+            return;
+        }
+
+        self.texture_color_spaces
+            .insert(texture_path.clone().into_string(), ColorSpace::NonColor);
+        self.custom_property("flow_map", Value::Texture(texture_path));
+
+        for &(parameter, name) in FLOW_PARAMETERS {
+            if let Some(value) = vmt.extract_param::<f32>(parameter) {
+                self.custom_property(name, Value::Float(value));
+            }
+        }
+    }
+}
+
 struct FwbBlendData {
     lum_start: [f32; 4],
     lum_end: [f32; 4],
@@ -377,6 +931,78 @@ struct FwbBlendData {
     lum_fac: [f32; 3],
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DetailBlendMode {
+    Mod2x,
+    Additive,
+    Translucent,
+    Fade,
+    TranslucentBase,
+    Multiply,
+    MaskBaseAlpha,
+}
+
+impl DetailBlendMode {
+    fn from_param(mode: u8) -> Option<Self> {
+        match mode {
+            // 7 selects between two patterns, treat it as plain mod2x
+            0 | 7 => Some(Self::Mod2x),
+            // 5 and 6 are unlit additive, approximated as regular additive
+            1 | 5 | 6 => Some(Self::Additive),
+            2 => Some(Self::Translucent),
+            3 => Some(Self::Fade),
+            4 => Some(Self::TranslucentBase),
+            8 => Some(Self::Multiply),
+            9 => Some(Self::MaskBaseAlpha),
+            _ => None,
+        }
+    }
+
+    fn group(self) -> &'static NodeGroup {
+        match self {
+            Self::Mod2x => &groups::DETAIL_TEXTURE,
+            Self::Additive => &groups::DETAIL_TEXTURE_ADDITIVE,
+            Self::Translucent => &groups::DETAIL_TEXTURE_TRANSLUCENT,
+            Self::Fade => &groups::DETAIL_TEXTURE_FADE,
+            Self::TranslucentBase => &groups::DETAIL_TEXTURE_TRANSLUCENT_BASE,
+            Self::Multiply => &groups::DETAIL_TEXTURE_MULTIPLY,
+            Self::MaskBaseAlpha => &groups::DETAIL_ALPHA_MASK,
+        }
+    }
+}
+
+impl MaterialBuilder {
+    fn push_detail(
+        &mut self,
+        base: &'static str,
+        detail: &'static str,
+        mode: DetailBlendMode,
+        blend_fac: impl Into<InputLink>,
+    ) {
+        let group = mode.group();
+        let input = self.input(base);
+
+        input.push(group);
+
+        match mode {
+            DetailBlendMode::MaskBaseAlpha => {
+                input.link(group, "detail_alpha", Ref::new(detail, "alpha"));
+            }
+            DetailBlendMode::Translucent => {
+                input
+                    .link(group, "detail", Ref::new(detail, "color"))
+                    .link(group, "detail_alpha", Ref::new(detail, "alpha"))
+                    .link(group, "fac", blend_fac);
+            }
+            _ => {
+                input
+                    .link(group, "detail", Ref::new(detail, "color"))
+                    .link(group, "fac", blend_fac);
+            }
+        }
+    }
+}
+
 fn phong_exponent_to_roughness(exponent: f32) -> f32 {
     0.66 * (150.0 - exponent) / 150.0
 }
@@ -386,6 +1012,7 @@ struct NormalMaterialBuilder<'a, 'b, 'c, 'd> {
     context: &'a mut Context<'b, 'd, BlenderAssetHandler>,
     vmt: &'a VmtHelper<'c>,
     settings: Settings,
+    texlight: Option<TexLight>,
 }
 
 // Common methods
@@ -394,12 +1021,22 @@ impl<'a, 'b, 'c, 'd> NormalMaterialBuilder<'a, 'b, 'c, 'd> {
         context: &'a mut Context<'b, 'd, BlenderAssetHandler>,
         vmt: &'a VmtHelper<'c>,
         settings: Settings,
+        texlight: Option<TexLight>,
+        animations: TransformAnimations,
     ) -> Self {
+        let (shader, socket_map) = settings.target_version.principled_shader();
+
+        let mut builder = MaterialBuilder::new(shader);
+        builder
+            .socket_map(socket_map)
+            .transform_animations(animations);
+
         Self {
             context,
-            builder: MaterialBuilder::new(&shaders::PRINCIPLED),
+            builder,
             vmt,
             settings,
+            texlight,
         }
     }
 
@@ -446,6 +1083,16 @@ impl<'a, 'b, 'c, 'd> NormalMaterialBuilder<'a, 'b, 'c, 'd> {
         )
     }
 
+    fn set_transparency(&mut self, transparency: Transparency) {
+        for (property, value) in self
+            .settings
+            .target_version
+            .transparency_properties(transparency)
+        {
+            self.builder.property(property, value);
+        }
+    }
+
     fn handle_cull(&mut self) {
         if !self.settings.allow_culling
             || self.vmt.extract_param_or_default("$nocull")
@@ -566,10 +1213,71 @@ impl<'a, 'b, 'c, 'd> NormalMaterialBuilder<'a, 'b, 'c, 'd> {
             self.build_normal();
         }
 
+        self.handle_base_alpha();
+
+        let texlight_strength = self.handle_texlight();
+
+        // emission strength defaults to 0 since Blender 4.0, so it's always set
+        if self.builder.has_output("Emission") || texlight_strength.is_some() {
+            self.builder.socket_value(
+                "Emission Strength",
+                Value::Float(self.settings.emission_strength * texlight_strength.unwrap_or(1.0)),
+            );
+        }
+
         self.builder.build()
     }
+
+    /// Applies the alpha policy to the base texture of this material only, since the
+    /// images are shared between materials which use their alpha differently.
+    fn handle_base_alpha(&mut self) {
+        let usage = self.builder.alpha_usage("$basetexture");
+
+        match self.settings.base_alpha.resolve(usage) {
+            BaseAlpha::Strip => {
+                self.builder.strip_alpha("$basetexture");
+            }
+            BaseAlpha::Premultiplied => {
+                self.builder.unpremultiply_alpha("$basetexture");
+            }
+            // images are imported with their alpha channel packed, independent of the colors
+            BaseAlpha::Preserve | BaseAlpha::Auto => {}
+        }
+    }
+
+    /// Makes materials emit the light the compiler lit the map with,
+    /// unless they glow by themselves already. Returns the emission strength.
+    fn handle_texlight(&mut self) -> Option<f32> {
+        let texlight = self.texlight?;
+
+        if self.builder.has_output("Emission") {
+            return None;
+        }
+
+        let [r, g, b] = texlight.color;
+        let color = Value::Color([r, g, b, 1.0]);
+
+        if self.builder.has_input("$basetexture") {
+            self.builder
+                .output("Emission", "$basetexture", "color")
+                .push(&groups::SELFILLUM_TINT)
+                .link_input(&groups::SELFILLUM_TINT, "color")
+                .link(&groups::SELFILLUM_TINT, "tint", color);
+        } else {
+            self.builder.socket_value("Emission", color);
+        }
+
+        Some(texlight.brightness * TEXLIGHT_BRIGHTNESS_SCALE)
+    }
 }
 
+/// Converts texture light brightness to emission strength, so that common
+/// brightnesses of a few hundred are a few times brighter than the texture.
+const TEXLIGHT_BRIGHTNESS_SCALE: f32 = 0.01;
+
+/// Height map scale of the parallax shaders, relative to the texture size.
+const DEFAULT_HEIGHT_SCALE: f32 = 0.02;
+
 // Normal material building
 impl<'a, 'b, 'c, 'd> NormalMaterialBuilder<'a, 'b, 'c, 'd> {
     fn handle_blendmodulatetexture(&mut self) -> Ref {
@@ -690,22 +1398,20 @@ impl<'a, 'b, 'c, 'd> NormalMaterialBuilder<'a, 'b, 'c, 'd> {
         scale: &'static str,
         blend_factor: &'static str,
     ) {
-        let detail_mode_supported =
-            self.vmt.extract_param_or_default::<u8>("$detailblendmode") == 0;
+        let Some(mode) =
+            DetailBlendMode::from_param(self.vmt.extract_param_or_default("$detailblendmode"))
+        else {
+            return;
+        };
 
-        if !detail_mode_supported
-            || !self.handle_texture_scaled(detail, transform, scale, ColorSpace::NonColor)
-        {
+        if !self.handle_texture_scaled(detail, transform, scale, ColorSpace::NonColor) {
             return;
         }
 
         let blend_fac = self.vmt.extract_param(blend_factor).unwrap_or(1.0);
 
         self.builder
-            .input(base)
-            .push(&groups::DETAIL_TEXTURE)
-            .link(&groups::DETAIL_TEXTURE, "detail", Ref::new(detail, "color"))
-            .link(&groups::DETAIL_TEXTURE, "fac", Value::Float(blend_fac));
+            .push_detail(base, detail, mode, Value::Float(blend_fac));
     }
 
     fn handle_basetexture_color(&mut self) -> Option<(InputLink, InputLink)> {
@@ -781,6 +1487,69 @@ impl<'a, 'b, 'c, 'd> NormalMaterialBuilder<'a, 'b, 'c, 'd> {
         true
     }
 
+    fn handle_heightmap(&mut self) {
+        let (input, socket) =
+            if self.handle_texture("$parallaxmap", Some("$bumptransform"), ColorSpace::NonColor) {
+                ("$parallaxmap", "color")
+            } else if self.handle_texture(
+                "$heightmap",
+                Some("$bumptransform"),
+                ColorSpace::NonColor,
+            ) {
+                ("$heightmap", "color")
+            } else if self.settings.ssbump_height
+                && self.builder.has_input("$bumpmap")
+                && self.vmt.extract_param_or_default::<bool>("$ssbump")
+            {
+                self.builder
+                    .input("ssbump_height")
+                    .pipeline(vec![&groups::SSBUMP_HEIGHT])
+                    .link(
+                        &groups::SSBUMP_HEIGHT,
+                        "image",
+                        Ref::new("$bumpmap", "color"),
+                    );
+
+                ("ssbump_height", "height")
+            } else {
+                return;
+            };
+
+        let scale = self
+            .vmt
+            .extract_param("$parallaxmapscale")
+            .or_else(|| self.vmt.extract_param("$heightscale"))
+            .unwrap_or(DEFAULT_HEIGHT_SCALE);
+
+        match self.settings.height_maps {
+            HeightMaps::Displacement => {
+                self.builder
+                    .output(DISPLACEMENT_SOCKET, input, socket)
+                    .push(&groups::HEIGHT_DISPLACEMENT)
+                    .link_input(&groups::HEIGHT_DISPLACEMENT, "height")
+                    .link(&groups::HEIGHT_DISPLACEMENT, "scale", Value::Float(scale));
+            }
+            HeightMaps::Bump => {
+                if let Some(output) = self.builder.existing_output("Normal") {
+                    output
+                        .push(&groups::HEIGHT_BUMP_NORMAL)
+                        .link(
+                            &groups::HEIGHT_BUMP_NORMAL,
+                            "height",
+                            Ref::new(input, socket),
+                        )
+                        .link(&groups::HEIGHT_BUMP_NORMAL, "distance", Value::Float(scale));
+                } else {
+                    self.builder
+                        .output("Normal", input, socket)
+                        .push(&groups::HEIGHT_BUMP)
+                        .link_input(&groups::HEIGHT_BUMP, "height")
+                        .link(&groups::HEIGHT_BUMP, "distance", Value::Float(scale));
+                }
+            }
+        }
+    }
+
     fn handle_bumpmap2(&mut self, blend_input: Ref) {
         if !self.handle_texture("$bumpmap2", Some("$bumptransform2"), ColorSpace::NonColor) {
             return;
@@ -817,8 +1586,7 @@ impl<'a, 'b, 'c, 'd> NormalMaterialBuilder<'a, 'b, 'c, 'd> {
             return false;
         }
 
-        self.builder
-            .property("use_transparent_shadow", Value::Bool(true));
+        self.set_transparency(Transparency::Blend);
 
         if self.builder.has_input("$basetexture") {
             let output = self.builder.output("Alpha", "$basetexture", "alpha");
@@ -841,8 +1609,7 @@ impl<'a, 'b, 'c, 'd> NormalMaterialBuilder<'a, 'b, 'c, 'd> {
             return false;
         }
 
-        self.builder
-            .property("use_transparent_shadow", Value::Bool(true));
+        self.set_transparency(Transparency::Clip);
 
         let reference = self.vmt.extract_param("$alphatestreference").unwrap_or(0.7);
 
@@ -889,8 +1656,7 @@ impl<'a, 'b, 'c, 'd> NormalMaterialBuilder<'a, 'b, 'c, 'd> {
             return false;
         }
 
-        self.builder
-            .property("use_transparent_shadow", Value::Bool(true));
+        self.set_transparency(Transparency::Blend);
 
         let output = self.builder.output("Alpha", "vertex_color", "alpha");
 
@@ -984,23 +1750,70 @@ impl<'a, 'b, 'c, 'd> NormalMaterialBuilder<'a, 'b, 'c, 'd> {
             }
         }
 
-        if let Some((input, source)) = selfillum_input {
-            if self.builder.has_input("$basetexture") {
-                self.builder
-                    .output("Emission", "$basetexture", "color")
-                    .push(&groups::COLOR_TEXTURE)
-                    .link_input(&groups::COLOR_TEXTURE, "color")
-                    .link(&groups::COLOR_TEXTURE, "mixin", Ref::new(input, source))
-                    .link(&groups::COLOR_TEXTURE, "fac", Value::Float(1.0));
-            } else {
-                self.builder.output("Emission", input, source);
+        let Some((input, source)) = selfillum_input else {
+            return;
+        };
+
+        let tint = self.vmt.extract_param::<RGB<f32>>("$selfillumtint");
+        let fresnel = self
+            .vmt
+            .extract_param_or_default::<bool>("$selfillum_fresnel")
+            .then(|| {
+                self.vmt
+                    .extract_param::<Vec3>("$selfillumfresnelminmaxexp")
+                    .unwrap_or(Vec3::new(0.0, 1.0, 1.0))
+            });
+
+        let has_basetexture = self.builder.has_input("$basetexture");
+
+        let output = if has_basetexture {
+            self.builder
+                .output("Emission", "$basetexture", "color")
+                .push(&groups::COLOR_TEXTURE)
+                .link_input(&groups::COLOR_TEXTURE, "color")
+                .link(&groups::COLOR_TEXTURE, "mixin", Ref::new(input, source))
+                .link(&groups::COLOR_TEXTURE, "fac", Value::Float(1.0))
+        } else {
+            self.builder.output("Emission", input, source)
+        };
+
+        // the first node group takes the input, the rest are chained by their color sockets
+        let mut piped = has_basetexture;
+
+        if let Some(tint) = tint {
+            output.push(&groups::SELFILLUM_TINT).link(
+                &groups::SELFILLUM_TINT,
+                "tint",
+                Value::Color(tint.alpha(1.0).into()),
+            );
+
+            if !piped {
+                output.link_input(&groups::SELFILLUM_TINT, "color");
+                piped = true;
+            }
+        }
+
+        if let Some(fresnel) = fresnel {
+            output
+                .push(&groups::SELFILLUM_FRESNEL)
+                .link(&groups::SELFILLUM_FRESNEL, "min", Value::Float(fresnel.x))
+                .link(&groups::SELFILLUM_FRESNEL, "max", Value::Float(fresnel.y))
+                .link(
+                    &groups::SELFILLUM_FRESNEL,
+                    "exponent",
+                    Value::Float(fresnel.z),
+                );
+
+            if !piped {
+                output.link_input(&groups::SELFILLUM_FRESNEL, "color");
             }
         }
     }
 
     fn build_normal(&mut self) {
+        self.set_transparency(Transparency::Opaque);
+
         self.builder
-            .property("use_transparent_shadow", Value::Bool(false))
             .socket_value("Specular", Value::Float(0.1))
             .socket_value("Roughness", Value::Float(0.9));
 
@@ -1020,6 +1833,8 @@ impl<'a, 'b, 'c, 'd> NormalMaterialBuilder<'a, 'b, 'c, 'd> {
             self.handle_ssbump_detail();
         }
 
+        self.handle_heightmap();
+
         if !self.handle_translucent() && !self.handle_alphatest() && !self.handle_vertexalpha() {
             self.handle_alpha();
         }
@@ -1073,8 +1888,7 @@ impl<'a, 'b, 'c, 'd> NormalMaterialBuilder<'a, 'b, 'c, 'd> {
             return false;
         }
 
-        self.builder
-            .property("use_transparent_shadow", Value::Bool(true));
+        self.set_transparency(Transparency::Blend);
 
         if self.builder.has_input("$basetexture") {
             self.builder.output("Alpha", "$basetexture", "alpha");
@@ -1090,8 +1904,7 @@ impl<'a, 'b, 'c, 'd> NormalMaterialBuilder<'a, 'b, 'c, 'd> {
             return false;
         }
 
-        self.builder
-            .property("use_transparent_shadow", Value::Bool(true));
+        self.set_transparency(Transparency::Clip);
 
         if self.builder.has_input("$basetexture") {
             self.builder.output("Alpha", "$basetexture", "alpha");
@@ -1167,8 +1980,9 @@ impl<'a, 'b, 'c, 'd> NormalMaterialBuilder<'a, 'b, 'c, 'd> {
     }
 
     fn build_simple(&mut self) {
+        self.set_transparency(Transparency::Opaque);
+
         self.builder
-            .property("use_transparent_shadow", Value::Bool(false))
             .socket_value("Specular", Value::Float(0.1))
             .socket_value("Roughness", Value::Float(0.9));
 
@@ -1451,17 +2265,18 @@ impl<'a, 'b, 'c, 'd> NormalMaterialBuilder<'a, 'b, 'c, 'd> {
     fn handle_detail_fwb(&mut self, d: &FwbBlendData) {
         use groups::MULTIBLEND_VALUE as MBV;
 
-        let detail_mode_supported =
-            self.vmt.extract_param_or_default::<u8>("$detailblendmode") == 0;
+        let Some(mode) =
+            DetailBlendMode::from_param(self.vmt.extract_param_or_default("$detailblendmode"))
+        else {
+            return;
+        };
 
-        if !detail_mode_supported
-            || !self.handle_texture_scaled(
-                "$detail",
-                "$detailtexturetransform",
-                "$detailscale",
-                ColorSpace::NonColor,
-            )
-        {
+        if !self.handle_texture_scaled(
+            "$detail",
+            "$detailtexturetransform",
+            "$detailscale",
+            ColorSpace::NonColor,
+        ) {
             return;
         }
 
@@ -1478,20 +2293,13 @@ impl<'a, 'b, 'c, 'd> NormalMaterialBuilder<'a, 'b, 'c, 'd> {
             .link(&MBV, "val4", Value::Float(d.detail_fac[3]))
             .socket("val");
 
-        self.builder
-            .input("base")
-            .push(&groups::DETAIL_TEXTURE)
-            .link(
-                &groups::DETAIL_TEXTURE,
-                "detail",
-                Ref::new("$detail", "color"),
-            )
-            .link(&groups::DETAIL_TEXTURE, "fac", blend_fac);
+        self.builder.push_detail("base", "$detail", mode, blend_fac);
     }
 
     fn build_fwb(&mut self) {
+        self.set_transparency(Transparency::Opaque);
+
         self.builder
-            .property("use_transparent_shadow", Value::Bool(false))
             .socket_value("Specular", Value::Float(0.1))
             .socket_value("Roughness", Value::Float(0.9));
 
@@ -1521,6 +2329,8 @@ pub fn build_material(
     context: &mut Context<BlenderAssetHandler>,
     vmt: &VmtHelper,
     info: &MaterialInfo,
+    texlight: Option<TexLight>,
+    animations: TransformAnimations,
     settings: Settings,
 ) -> Option<BuiltMaterialData> {
     if !settings.import_materials {
@@ -1530,9 +2340,14 @@ pub fn build_material(
     Some(if info.no_draw() && !settings.editor_materials {
         build_nodraw_material()
     } else if vmt.extract_param_or_default("%compilewater") {
-        build_water_material(context, vmt, settings)
+        build_water_material(context, vmt, animations, settings)
+    } else if ["eyerefract", "eyes"]
+        .iter()
+        .any(|&s| vmt.shader().shader.as_uncased_str() == s.as_uncased())
+    {
+        build_eye_material(context, vmt, settings)
     } else {
-        NormalMaterialBuilder::new(context, vmt, settings).build()
+        NormalMaterialBuilder::new(context, vmt, settings, texlight, animations).build()
     })
 }
 
@@ -1544,4 +2359,38 @@ mod tests {
     fn build_nodraw_material_no_panic() {
         build_nodraw_material();
     }
+
+    #[test]
+    fn emit_eevee_next_transparency() {
+        let [(method, blended), (shadow, _)] =
+            TargetVersion::Blender4_2.transparency_properties(Transparency::Blend);
+        assert_eq!(method, "surface_render_method");
+        assert!(matches!(blended, Value::Enum("BLENDED")));
+        assert_eq!(shadow, "use_transparent_shadow");
+
+        let [(_, clipped), (_, shadow)] =
+            TargetVersion::Blender4_2.transparency_properties(Transparency::Clip);
+        assert!(matches!(clipped, Value::Enum("DITHERED")));
+        assert!(matches!(shadow, Value::Bool(true)));
+
+        let [(method, _), _] =
+            TargetVersion::Blender4.transparency_properties(Transparency::Opaque);
+        assert_eq!(method, "blend_method");
+    }
+
+    #[test]
+    fn classify_tool_materials() {
+        let classify = |no_draw, flags: &[&str]| {
+            ToolMaterial::from_flags(no_draw, |flag| flags.contains(&flag))
+        };
+
+        assert_eq!(classify(false, &[]), ToolMaterial::Normal);
+        assert_eq!(classify(true, &[]), ToolMaterial::NoDraw);
+        assert_eq!(
+            classify(true, &["%compilenodraw", "%compileskip"]),
+            ToolMaterial::Skip
+        );
+        assert_eq!(classify(false, &["%compileplayerclip"]), ToolMaterial::Clip);
+        assert_eq!(classify(false, &["%compile2dsky"]), ToolMaterial::Skybox);
+    }
 }
@@ -1,4 +1,7 @@
-use std::{collections::BTreeMap, iter, mem, ptr};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    iter, mem, ptr,
+};
 
 use float_ord::FloatOrd;
 use itertools::{Either, Itertools};
@@ -6,11 +9,15 @@ use pyo3::prelude::*;
 use tracing::debug;
 
 use super::{
-    definitions::NODE_MARGIN,
+    builder::ToolMaterial,
+    definitions::{groups, NODE_MARGIN},
+    loose::LooseTexture,
     nodes::{
-        BuiltNode, BuiltNodeSocketLink, BuiltNodeSocketRef, NodeGroup, NodeGroupRef, NodeSocketId,
-        NodeType, Ref, Value,
+        BuiltNode, BuiltNodeGroup, BuiltNodeSocketLink, BuiltNodeSocketRef, NodeGroup,
+        NodeGroupRef, NodeSocketId, NodeType, Ref, Value,
     },
+    optimize::optimize,
+    proxies::TransformAnimations,
 };
 
 #[derive(Debug)]
@@ -80,6 +87,26 @@ impl Input {
         Ref::new(self.id, socket)
     }
 
+    fn input_dependencies(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.links.values().filter_map(|l| {
+            if let InputLink::Input(r) = l {
+                Some(r.target)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn textures(&self) -> impl Iterator<Item = String> + '_ {
+        self.properties.values().filter_map(|v| {
+            if let Value::Texture(path) = v {
+                Some(path.clone().into_string())
+            } else {
+                None
+            }
+        })
+    }
+
     fn build(&self, inputs: &mut BTreeMap<&'static str, BuiltInput>, nodes: &mut Vec<BuiltNode>) {
         debug!("building input {}", self.id);
 
@@ -391,38 +418,93 @@ fn topological_sort_inputs<'a>(
     }
 }
 
+#[derive(Clone, Copy)]
 pub(crate) enum ColorSpace {
     Srgb,
     NonColor,
 }
 
-impl IntoPy<PyObject> for ColorSpace {
-    fn into_py(self, py: Python) -> PyObject {
+impl ColorSpace {
+    pub fn to_str(self) -> &'static str {
         match self {
-            ColorSpace::Srgb => "sRGB".into_py(py),
-            ColorSpace::NonColor => "Non-Color".into_py(py),
+            ColorSpace::Srgb => "sRGB",
+            ColorSpace::NonColor => "Non-Color",
         }
     }
 }
 
+impl IntoPy<PyObject> for ColorSpace {
+    fn into_py(self, py: Python) -> PyObject {
+        self.to_str().into_py(py)
+    }
+}
+
+/// How a material uses the alpha channel of a texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AlphaUsage {
+    Unused,
+    /// Masks something else, like the reflections.
+    Mask,
+    Transparency,
+}
+
+/// Socket of the material output, which outputs can be routed to besides the shader sockets.
+pub(crate) const DISPLACEMENT_SOCKET: NodeSocketId = NodeSocketId::Name("Displacement");
+pub(crate) const VOLUME_SOCKET: NodeSocketId = NodeSocketId::Name("Volume");
+
 pub(crate) struct MaterialBuilder {
     properties: BTreeMap<&'static str, Value>,
+    custom_properties: BTreeMap<&'static str, Value>,
     shader: &'static NodeType,
+    socket_map: &'static [(&'static str, &'static str)],
     shader_socket_values: BTreeMap<NodeSocketId, Value>,
     inputs: BTreeMap<&'static str, Input>,
     outputs: Vec<Output>,
     pub(crate) texture_color_spaces: BTreeMap<String, ColorSpace>,
+    /// Textures of `$basetexture` parameters, which get the color correction.
+    pub(crate) base_textures: BTreeSet<String>,
+    pub(crate) loose_textures: Vec<LooseTexture>,
+    pub(crate) transform_animations: TransformAnimations,
 }
 
 impl MaterialBuilder {
     pub fn new(shader: &'static NodeType) -> Self {
         Self {
             properties: BTreeMap::new(),
+            custom_properties: BTreeMap::new(),
             shader,
+            socket_map: &[],
             shader_socket_values: BTreeMap::new(),
             inputs: BTreeMap::new(),
             outputs: Vec::new(),
             texture_color_spaces: BTreeMap::new(),
+            base_textures: BTreeSet::new(),
+            loose_textures: Vec::new(),
+            transform_animations: TransformAnimations::default(),
+        }
+    }
+
+    /// Sets a map of shader socket names to rename when referenced,
+    /// used when the target shader renamed sockets between versions.
+    pub fn socket_map(&mut self, socket_map: &'static [(&'static str, &'static str)]) -> &mut Self {
+        self.socket_map = socket_map;
+        self
+    }
+
+    /// Sets the animations of the texture transforms, read from the material proxies.
+    pub fn transform_animations(&mut self, animations: TransformAnimations) -> &mut Self {
+        self.transform_animations = animations;
+        self
+    }
+
+    fn map_socket(&self, socket: NodeSocketId) -> NodeSocketId {
+        match socket {
+            NodeSocketId::Name(name) => self
+                .socket_map
+                .iter()
+                .find(|(from, _)| *from == name)
+                .map_or(socket, |&(_, to)| NodeSocketId::Name(to)),
+            NodeSocketId::Position(_) => socket,
         }
     }
 
@@ -431,11 +513,23 @@ impl MaterialBuilder {
         self
     }
 
+    /// Sets a custom property of the material, for values which aren't used by any node.
+    pub fn custom_property(&mut self, name: &'static str, value: Value) -> &mut Self {
+        self.custom_properties.insert(name, value);
+        self
+    }
+
     pub fn socket_value(&mut self, socket: impl Into<NodeSocketId>, value: Value) -> &mut Self {
-        self.shader_socket_values.insert(socket.into(), value);
+        let socket = self.map_socket(socket.into());
+        self.shader_socket_values.insert(socket, value);
         self
     }
 
+    pub fn has_output(&self, socket: impl Into<NodeSocketId>) -> bool {
+        let socket = self.map_socket(socket.into());
+        self.outputs.iter().any(|o| o.shader_socket == socket)
+    }
+
     pub fn has_input(&self, id: &'static str) -> bool {
         self.inputs.contains_key(id)
     }
@@ -449,16 +543,24 @@ impl MaterialBuilder {
         })
     }
 
+    /// Returns the output previously routed to a shader socket, to extend its pipeline.
+    pub fn existing_output(&mut self, socket: impl Into<NodeSocketId>) -> Option<&mut Output> {
+        let socket = self.map_socket(socket.into());
+        self.outputs.iter_mut().find(|o| o.shader_socket == socket)
+    }
+
     pub fn output(
         &mut self,
         socket: impl Into<NodeSocketId>,
         input: &'static str,
         source: &'static str,
     ) -> &mut Output {
+        let shader_socket = self.map_socket(socket.into());
+
         self.outputs.push(Output {
             input: Ref::new(input, source),
             pipeline: Vec::new(),
-            shader_socket: socket.into(),
+            shader_socket,
             links: BTreeMap::new(),
             properties: BTreeMap::new(),
         });
@@ -468,7 +570,164 @@ impl MaterialBuilder {
             .expect("cannot be empty, just pushed")
     }
 
-    pub fn build(self) -> BuiltMaterialData {
+    /// Collects the textures of an input and the inputs it depends on. Inputs are only
+    /// visited once, since the dependencies can form a cycle.
+    fn collect_textures(
+        &self,
+        input: &'static str,
+        visited: &mut BTreeSet<&'static str>,
+        textures: &mut BTreeSet<String>,
+    ) {
+        if !visited.insert(input) {
+            return;
+        }
+
+        if let Some(input) = self.inputs.get(input) {
+            textures.extend(input.textures());
+
+            for dependency in input.input_dependencies() {
+                self.collect_textures(dependency, visited, textures);
+            }
+        }
+    }
+
+    /// Finds how the alpha of an input is used by the outputs and the other inputs.
+    pub fn alpha_usage(&self, id: &'static str) -> AlphaUsage {
+        let alpha = Ref::new(id, "alpha");
+        let links_alpha = |links: &BTreeMap<NodeGroupRef, InputLink>| {
+            links
+                .values()
+                .any(|l| matches!(l, InputLink::Input(r) if *r == alpha))
+        };
+
+        let mut usage = if self.inputs.values().any(|i| links_alpha(&i.links)) {
+            AlphaUsage::Mask
+        } else {
+            AlphaUsage::Unused
+        };
+
+        let alpha_socket = self.map_socket("Alpha".into());
+
+        for output in &self.outputs {
+            if output.input != alpha && !links_alpha(&output.links) {
+                continue;
+            }
+
+            if output.shader_socket == alpha_socket {
+                return AlphaUsage::Transparency;
+            }
+
+            usage = AlphaUsage::Mask;
+        }
+
+        usage
+    }
+
+    /// Replaces every use of the alpha of an input with full opacity.
+    pub fn strip_alpha(&mut self, id: &'static str) -> &mut Self {
+        let alpha = Ref::new(id, "alpha");
+        let strip = |links: &mut BTreeMap<NodeGroupRef, InputLink>| {
+            for link in links.values_mut() {
+                if matches!(link, InputLink::Input(r) if *r == alpha) {
+                    *link = InputLink::Value(Value::Float(1.0));
+                }
+            }
+        };
+
+        for input in self.inputs.values_mut() {
+            strip(&mut input.links);
+        }
+
+        let mut opaque_sockets = Vec::new();
+
+        self.outputs.retain_mut(|output| {
+            strip(&mut output.links);
+
+            // outputs without nodes are routed straight from the input
+            if output.pipeline.is_empty() && output.input == alpha {
+                opaque_sockets.push(output.shader_socket);
+                false
+            } else {
+                true
+            }
+        });
+
+        let alpha_socket = self.map_socket("Alpha".into());
+
+        for socket in opaque_sockets {
+            if socket != alpha_socket {
+                self.shader_socket_values.insert(socket, Value::Float(1.0));
+            }
+        }
+
+        self
+    }
+
+    /// Divides the premultiplied color of an input by its alpha, for every use of the color.
+    pub fn unpremultiply_alpha(&mut self, id: &'static str) -> &mut Self {
+        if let Some(input) = self.inputs.get_mut(id) {
+            input.push(&groups::UNPREMULTIPLY_ALPHA);
+        }
+
+        self
+    }
+
+    /// Classifies each texture by which shader sockets it ends up routed to.
+    fn texture_roles(&self) -> BTreeMap<String, BTreeSet<&'static str>> {
+        let mut roles: BTreeMap<String, BTreeSet<&'static str>> = BTreeMap::new();
+
+        for output in &self.outputs {
+            let role = texture_role(output.shader_socket);
+            let mut visited = BTreeSet::new();
+            let mut textures = BTreeSet::new();
+
+            for dependency in output.dependencies() {
+                self.collect_textures(dependency, &mut visited, &mut textures);
+            }
+
+            for texture in textures {
+                roles.entry(texture).or_default().insert(role);
+            }
+        }
+
+        roles
+    }
+
+    /// Estimates the viewport display properties from the constant shader socket values,
+    /// so that the solid mode approximates the material without evaluating the nodes.
+    fn viewport_properties(&self) -> Vec<(&'static str, Value)> {
+        let float = |socket: &'static str, default: f32| match self
+            .shader_socket_values
+            .get(&self.map_socket(socket.into()))
+        {
+            Some(&Value::Float(value)) => value,
+            _ => default,
+        };
+
+        let mut properties = vec![
+            ("metallic", Value::Float(float("Metallic", 0.0))),
+            ("roughness", Value::Float(float("Roughness", 0.5))),
+        ];
+
+        if !self.has_output("Base Color") {
+            if let Some(&Value::Color(color)) = self
+                .shader_socket_values
+                .get(&self.map_socket("Base Color".into()))
+            {
+                properties.push(("diffuse_color", Value::Color(color)));
+            }
+        }
+
+        properties
+    }
+
+    pub fn build(mut self) -> BuiltMaterialData {
+        let texture_roles = self.texture_roles();
+
+        for (name, value) in self.viewport_properties() {
+            self.properties.entry(name).or_insert(value);
+        }
+
         let mut nodes = Vec::new();
         let mut built_inputs = BTreeMap::new();
 
@@ -481,7 +740,10 @@ impl MaterialBuilder {
 
         // resort inputs and outputs based on shader socket orders,
         // also removes unused inputs
-        for socket in self.shader.input_sockets.iter().rev() {
+        for socket in [&DISPLACEMENT_SOCKET, &VOLUME_SOCKET]
+            .into_iter()
+            .chain(self.shader.input_sockets.iter().rev())
+        {
             if let Some(output) = self.outputs.iter().find(|o| &o.shader_socket == socket) {
                 sorted_outputs_reversed.push(output);
 
@@ -517,7 +779,7 @@ impl MaterialBuilder {
         let output_x = x_max + NODE_MARGIN;
         let mut output_y = 0.0;
 
-        let shader_socket_links = sorted_outputs_reversed
+        let mut shader_socket_links: BTreeMap<_, _> = sorted_outputs_reversed
             .into_iter()
             .rev()
             .map(|output| {
@@ -535,6 +797,9 @@ impl MaterialBuilder {
             })
             .collect();
 
+        let mut displacement = shader_socket_links.remove(&DISPLACEMENT_SOCKET);
+        let mut volume = shader_socket_links.remove(&VOLUME_SOCKET);
+
         let shader_x = x_max + NODE_MARGIN;
 
         let shader_node = self.shader.build(
@@ -546,6 +811,9 @@ impl MaterialBuilder {
 
         nodes.push(shader_node);
 
+        let mut nodes = optimize(nodes, [&mut displacement, &mut volume]);
+        let node_groups = shared_node_groups(&nodes);
+
         // offset nodes so that the shader node is at (0, 0)
         // invert y-axis since it's from top to bottom to make node placement simpler
         for node in &mut nodes {
@@ -555,12 +823,50 @@ impl MaterialBuilder {
 
         BuiltMaterialData {
             properties: self.properties,
+            custom_properties: self.custom_properties,
             nodes,
+            node_groups,
+            displacement,
+            volume,
             texture_color_spaces: self.texture_color_spaces,
+            texture_roles,
+            base_textures: self.base_textures,
+            loose_textures: self.loose_textures,
+            // set by the material config, which knows the compile flags
+            tool_material: ToolMaterial::Normal,
+            metadata: BTreeMap::new(),
+            average_color: None,
         }
     }
 }
 
+/// Builds the definitions of the shared node groups the nodes reference.
+fn shared_node_groups(nodes: &[BuiltNode]) -> BTreeMap<&'static str, BuiltNodeGroup> {
+    let mut node_groups = BTreeMap::new();
+
+    for value in nodes.iter().flat_map(|n| n.properties.values()) {
+        if let Value::NodeTree(group) = value {
+            if let Some(shared) = &group.shared {
+                node_groups
+                    .entry(shared.name)
+                    .or_insert_with(|| group.build_shared_definition());
+            }
+        }
+    }
+
+    node_groups
+}
+
+fn texture_role(socket: NodeSocketId) -> &'static str {
+    match socket {
+        NodeSocketId::Name("Base Color") => "base",
+        NodeSocketId::Name("Emission" | "Emission Color") => "emissive",
+        NodeSocketId::Name("Normal") => "normal",
+        NodeSocketId::Name("Displacement") => "height",
+        _ => "other",
+    }
+}
+
 fn sort_dependencies_recursive<'a>(
     mut inputs_to_check: impl Iterator<Item = &'a Input> + Clone,
     sorted_inputs_reversed: &mut Vec<&'a Input>,
@@ -582,11 +888,41 @@ fn sort_dependencies_recursive<'a>(
     }
 }
 
+#[derive(Clone)]
 #[pyclass(module = "plumber")]
 pub struct BuiltMaterialData {
-    properties: BTreeMap<&'static str, Value>,
-    nodes: Vec<BuiltNode>,
-    texture_color_spaces: BTreeMap<String, ColorSpace>,
+    pub(crate) properties: BTreeMap<&'static str, Value>,
+    pub(crate) custom_properties: BTreeMap<&'static str, Value>,
+    pub(crate) nodes: Vec<BuiltNode>,
+    pub(crate) node_groups: BTreeMap<&'static str, BuiltNodeGroup>,
+    pub(crate) displacement: Option<BuiltNodeSocketRef>,
+    pub(crate) volume: Option<BuiltNodeSocketRef>,
+    pub(crate) texture_color_spaces: BTreeMap<String, ColorSpace>,
+    texture_roles: BTreeMap<String, BTreeSet<&'static str>>,
+    /// Textures of `$basetexture` parameters, which get the color correction.
+    pub(crate) base_textures: BTreeSet<String>,
+    /// Loose images used in place of missing textures, sent as textures of their own.
+    pub(crate) loose_textures: Vec<LooseTexture>,
+    pub(crate) tool_material: ToolMaterial,
+    pub(crate) metadata: BTreeMap<String, String>,
+    pub(crate) average_color: Option<[f32; 4]>,
+}
+
+impl BuiltMaterialData {
+    /// Returns the textures loaded from `.vtf` files, excluding the loose replacements.
+    pub(crate) fn vtf_textures(&self) -> impl Iterator<Item = &str> + '_ {
+        self.texture_color_spaces
+            .keys()
+            .map(String::as_str)
+            .filter(|&name| self.loose_textures.iter().all(|t| t.name != name))
+    }
+
+    /// Sets the viewport display color, unless the material has a constant base color.
+    pub(crate) fn set_viewport_color(&mut self, color: [f32; 4]) {
+        self.properties
+            .entry("diffuse_color")
+            .or_insert(Value::Color(color));
+    }
 }
 
 #[pymethods]
@@ -595,19 +931,42 @@ impl BuiltMaterialData {
         mem::take(&mut self.properties)
     }
 
+    fn custom_properties(&mut self) -> BTreeMap<&'static str, Value> {
+        mem::take(&mut self.custom_properties)
+    }
+
     fn nodes(&mut self) -> Vec<BuiltNode> {
         mem::take(&mut self.nodes)
     }
 
+    /// Shared node groups referenced by the nodes, by name.
+    /// Only created if a node group with the name doesn't exist yet.
+    fn node_groups(&mut self) -> BTreeMap<&'static str, BuiltNodeGroup> {
+        mem::take(&mut self.node_groups)
+    }
+
+    fn displacement(&self) -> Option<BuiltNodeSocketRef> {
+        self.displacement
+    }
+
+    fn volume(&self) -> Option<BuiltNodeSocketRef> {
+        self.volume
+    }
+
     fn texture_color_spaces(&mut self) -> BTreeMap<String, ColorSpace> {
         mem::take(&mut self.texture_color_spaces)
     }
+
+    fn texture_roles(&mut self) -> BTreeMap<String, BTreeSet<&'static str>> {
+        mem::take(&mut self.texture_roles)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::asset::material::definitions::groups;
+    use crate::asset::material::definitions::{nodes, shaders};
+    use plumber_core::fs::GamePathBuf;
 
     #[test]
     fn topological_sort_inputs_cyclic() {
@@ -752,4 +1111,186 @@ mod tests {
                 > result.iter().position(|&r| ptr::eq(r, i1)).unwrap()
         );
     }
+
+    #[test]
+    fn find_alpha_usage() {
+        let mut builder = MaterialBuilder::new(&shaders::PRINCIPLED);
+        builder
+            .input("$basetexture")
+            .push(&groups::TEXTURE)
+            .property(
+                &groups::TEXTURE,
+                "image",
+                Value::Texture(GamePathBuf::from("materials/test")),
+            );
+        builder.output("Base Color", "$basetexture", "color");
+
+        assert_eq!(builder.alpha_usage("$basetexture"), AlphaUsage::Unused);
+
+        builder
+            .output("Specular", "$basetexture", "alpha")
+            .push(&groups::INVERT_VALUE)
+            .link_input(&groups::INVERT_VALUE, "value");
+
+        assert_eq!(builder.alpha_usage("$basetexture"), AlphaUsage::Mask);
+
+        builder.output("Alpha", "$basetexture", "alpha");
+
+        assert_eq!(
+            builder.alpha_usage("$basetexture"),
+            AlphaUsage::Transparency
+        );
+
+        builder.strip_alpha("$basetexture");
+
+        assert_eq!(builder.alpha_usage("$basetexture"), AlphaUsage::Unused);
+        assert!(!builder.has_output("Alpha"));
+        assert!(builder.has_output("Specular"));
+    }
+
+    #[test]
+    fn collect_textures_of_cyclic_inputs() {
+        let mut builder = MaterialBuilder::new(&shaders::PRINCIPLED);
+        builder
+            .input("$basetexture")
+            .push(&groups::TEXTURE)
+            .property(
+                &groups::TEXTURE,
+                "image",
+                Value::Texture(GamePathBuf::from("materials/test")),
+            )
+            .link(&groups::TEXTURE, "vector", Ref::new("$detail", "color"));
+        builder
+            .input("$detail")
+            .push(&groups::TEXTURE)
+            .property(
+                &groups::TEXTURE,
+                "image",
+                Value::Texture(GamePathBuf::from("materials/test_detail")),
+            )
+            .link(
+                &groups::TEXTURE,
+                "vector",
+                Ref::new("$basetexture", "color"),
+            );
+
+        let mut textures = BTreeSet::new();
+        builder.collect_textures("$basetexture", &mut BTreeSet::new(), &mut textures);
+
+        assert_eq!(
+            textures.into_iter().collect::<Vec<_>>(),
+            ["materials/test", "materials/test_detail"]
+        );
+    }
+
+    #[test]
+    fn unpremultiply_input_alpha() {
+        let mut builder = MaterialBuilder::new(&shaders::PRINCIPLED);
+        builder
+            .input("$basetexture")
+            .push(&groups::TEXTURE)
+            .property(
+                &groups::TEXTURE,
+                "image",
+                Value::Texture(GamePathBuf::from("materials/test")),
+            )
+            .property(&groups::TEXTURE, "interpolation", Value::Enum("Linear"));
+        builder.output("Base Color", "$basetexture", "color");
+        builder.output("Alpha", "$basetexture", "alpha");
+        builder.unpremultiply_alpha("$basetexture");
+
+        let data = builder.build();
+        let shader = data
+            .nodes
+            .iter()
+            .find(|n| n.kind.blender_id == "ShaderNodeBsdfPrincipled")
+            .unwrap();
+        let link = |node: &BuiltNode, socket| {
+            let link = &node.socket_links[&NodeSocketId::Name(socket)];
+            (link.node_index, link.socket)
+        };
+
+        // the color is divided by the alpha of the same texture before it's used
+        let (divide, _) = link(shader, "Base Color");
+        let divide = &data.nodes[divide];
+        assert_eq!(divide.kind.blender_id, "ShaderNodeMixRGB");
+        assert!(matches!(
+            divide.properties.get("blend_type"),
+            Some(Value::Enum("DIVIDE"))
+        ));
+
+        let (texture, color) = link(divide, "Color1");
+        assert_eq!(data.nodes[texture].kind.blender_id, "ShaderNodeTexImage");
+        assert_eq!(color, NodeSocketId::Name("Color"));
+        assert_eq!(
+            link(divide, "Color2"),
+            (texture, NodeSocketId::Name("Alpha"))
+        );
+
+        // the shader still blends with the straight alpha
+        assert_eq!(
+            link(shader, "Alpha"),
+            (texture, NodeSocketId::Name("Alpha"))
+        );
+    }
+
+    #[test]
+    fn estimate_viewport_properties() {
+        let mut builder = MaterialBuilder::new(&shaders::PRINCIPLED);
+        builder
+            .socket_value("Roughness", Value::Float(0.9))
+            .socket_value("Base Color", Value::Color([0.5, 0.25, 0.0, 1.0]))
+            .property("roughness", Value::Float(0.2));
+
+        let properties = builder.build().properties;
+
+        assert!(matches!(properties["metallic"], Value::Float(m) if m.abs() < 1e-6));
+        // properties set by the builder are kept
+        assert!(matches!(properties["roughness"], Value::Float(r) if (r - 0.2).abs() < 1e-6));
+        assert!(matches!(
+            properties["diffuse_color"],
+            Value::Color([r, g, _, _]) if (r - 0.5).abs() < 1e-6 && (g - 0.25).abs() < 1e-6
+        ));
+    }
+
+    #[test]
+    fn reference_shared_node_groups() {
+        let mut builder = MaterialBuilder::new(&shaders::PRINCIPLED);
+        builder
+            .input("$bumpmap")
+            .push(&groups::TEXTURE)
+            .property(
+                &groups::TEXTURE,
+                "image",
+                Value::Texture(GamePathBuf::from("materials/test_normal")),
+            )
+            .property(&groups::TEXTURE, "interpolation", Value::Enum("Linear"));
+        builder
+            .output("Normal", "$bumpmap", "color")
+            .push(&groups::DX_NORMAL_MAP_CONVERTER)
+            .link_input(&groups::DX_NORMAL_MAP_CONVERTER, "image")
+            .push(&groups::NORMAL_MAP)
+            .link(&groups::NORMAL_MAP, "strength", Value::Float(1.0));
+
+        let built = builder.build();
+
+        // the converter is a single group node instead of its separate nodes
+        assert!(built
+            .nodes
+            .iter()
+            .all(|n| !ptr::eq(n.kind, &nodes::SEPARATE_RGB)));
+        let group_node = built
+            .nodes
+            .iter()
+            .find(|n| n.kind.blender_id == "ShaderNodeGroup")
+            .unwrap();
+        assert!(group_node
+            .socket_links
+            .contains_key(&NodeSocketId::Name("image")));
+
+        let group = &built.node_groups["DX Normal Map Converter"];
+        assert_eq!(group.nodes.len(), 3);
+        assert_eq!(group.inputs.len(), 1);
+        assert_eq!(group.outputs.len(), 1);
+    }
 }
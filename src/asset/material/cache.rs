@@ -0,0 +1,209 @@
+//! Caches built materials by the contents of their VMT and the settings, so that
+//! importing the same materials again, like from another map, skips building them.
+//! The cache is scoped to the file system of the import, since the textures a material
+//! refers to can differ between games even if the material itself doesn't.
+
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet, VecDeque},
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use plumber_core::{
+    asset_core::Context, asset_vtf::VtfConfig, fs::OpenFileSystem, vmt::MaterialInfo,
+};
+
+use crate::{asset::BlenderAssetHandler, game_path};
+
+use super::{
+    builder::Settings,
+    builder_base::BuiltMaterialData,
+    patch::PatchedMaterial,
+    rad::{TexLight, TexLights},
+};
+
+/// The oldest materials are evicted past this, instead of growing for the whole session.
+const MAX_ENTRIES: usize = 8192;
+
+static CACHE: Mutex<Cache> = Mutex::new(Cache {
+    scope: 0,
+    materials: BTreeMap::new(),
+    order: VecDeque::new(),
+    texlights: None,
+    base_textures: BTreeSet::new(),
+    average_colors: BTreeMap::new(),
+});
+
+struct Cache {
+    /// Identifies the file system the cached materials were loaded from.
+    scope: u64,
+    materials: BTreeMap<u64, CachedMaterial>,
+    /// Keys in insertion order, for evicting the oldest materials first.
+    order: VecDeque<u64>,
+    /// Read once per import on the first material, since every material is checked.
+    texlights: Option<Arc<TexLights>>,
+    /// Textures the materials of the import use as base textures.
+    base_textures: BTreeSet<String>,
+    /// Average colors of the loaded textures, computed when they are converted. Kept for
+    /// every texture, since a texture can be loaded before a material marks it as a base texture.
+    average_colors: BTreeMap<String, [f32; 4]>,
+}
+
+impl Cache {
+    fn clear(&mut self) {
+        self.materials.clear();
+        self.order.clear();
+        self.average_colors.clear();
+    }
+
+    fn insert(&mut self, key: u64, material: CachedMaterial) {
+        if self.materials.insert(key, material).is_some() {
+            return;
+        }
+
+        self.order.push_back(key);
+
+        while self.order.len() > MAX_ENTRIES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.materials.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct CachedMaterial {
+    pub data: Option<BuiltMaterialData>,
+    pub info: MaterialInfo,
+}
+
+fn cache() -> MutexGuard<'static, Cache> {
+    // a material panicking while building must not disable the cache for everyone else
+    CACHE
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Hashes everything a built material depends on besides the textures themselves.
+pub(crate) fn key(
+    path: &str,
+    patched: &PatchedMaterial,
+    texlight: Option<TexLight>,
+    settings: &Settings,
+) -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    path.hash(&mut hasher);
+    patched.hash(&mut hasher);
+    // floats aren't hashable, but their formatting is exact
+    format!("{texlight:?}").hash(&mut hasher);
+    format!("{settings:?}").hash(&mut hasher);
+
+    hasher.finish()
+}
+
+/// Returns a previously built material. The textures it uses are still requested, since
+/// they are imported separately and this may be a different import.
+pub(crate) fn get(key: u64, context: &mut Context<BlenderAssetHandler>) -> Option<CachedMaterial> {
+    let cached = cache().materials.get(&key).cloned()?;
+
+    if let Some(data) = &cached.data {
+        for texture in &data.base_textures {
+            mark_base_texture(texture);
+        }
+
+        for texture in data.vtf_textures() {
+            // errors were already reported when the material was built
+            let _ = context.depend_on(VtfConfig, game_path::game_path(texture).into());
+        }
+    }
+
+    Some(cached)
+}
+
+pub(crate) fn insert(key: u64, material: CachedMaterial) {
+    cache().insert(key, material);
+}
+
+/// Marks a texture as a base texture of a material, so that its colors are corrected
+/// when it's loaded.
+pub(crate) fn mark_base_texture(name: &str) {
+    let mut cache = cache();
+
+    if !cache.base_textures.contains(name) {
+        cache.base_textures.insert(name.to_owned());
+    }
+}
+
+/// Whether a loaded texture is a base texture of a material of the import.
+pub(crate) fn is_base_texture(name: &str) -> bool {
+    let name = name.strip_suffix(".vtf").unwrap_or(name);
+    cache().base_textures.contains(name)
+}
+
+/// Records the average color of a loaded texture.
+pub(crate) fn insert_average_color(name: &str, color: [f32; 4]) {
+    let name = name.strip_suffix(".vtf").unwrap_or(name);
+    cache().average_colors.insert(name.to_owned(), color);
+}
+
+/// Returns the average color of a base texture, if it has been loaded.
+pub(crate) fn average_color(name: &str) -> Option<[f32; 4]> {
+    let name = name.strip_suffix(".vtf").unwrap_or(name);
+    cache().average_colors.get(name).copied()
+}
+
+/// Returns the texture lights of the import, reading them on the first call.
+pub(crate) fn texlights(file_system: &OpenFileSystem) -> Arc<TexLights> {
+    cache()
+        .texlights
+        .get_or_insert_with(|| Arc::new(TexLights::read(file_system)))
+        .clone()
+}
+
+/// Scopes the cache to the file system of a new import, identified by `scope`.
+/// The cached materials are kept only if the file system is the same as the previous one.
+pub(crate) fn set_scope(scope: u64) {
+    let mut cache = cache();
+    // the `.rad` files may have been edited since the previous import
+    cache.texlights = None;
+    cache.base_textures.clear();
+
+    if cache.scope != scope {
+        cache.clear();
+        cache.scope = scope;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::keyvalues::KeyValue;
+
+    #[test]
+    fn key_depends_on_contents_and_settings() {
+        let patched = PatchedMaterial {
+            shader: "LightmappedGeneric".to_owned(),
+            parameters: vec![(
+                "$basetexture".to_owned(),
+                KeyValue::Value("brick/brickwall001".to_owned()),
+            )],
+            chain: Vec::new(),
+        };
+        let settings = Settings::default();
+
+        let base = key("materials/a.vmt", &patched, None, &settings);
+        assert_eq!(base, key("materials/a.vmt", &patched, None, &settings));
+
+        let mut changed = patched.clone();
+        changed.parameters[0].1 = KeyValue::Value("brick/brickwall002".to_owned());
+        assert_ne!(base, key("materials/a.vmt", &changed, None, &settings));
+
+        let scaled = Settings {
+            scale: 1.0,
+            ..settings
+        };
+        assert_ne!(base, key("materials/a.vmt", &patched, None, &scaled));
+    }
+}
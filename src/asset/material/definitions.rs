@@ -23,6 +23,33 @@ pub mod shaders {
         ..NodeType::default()
     };
 
+    /// Principled BSDF with the socket names introduced in Blender 4.0.
+    pub static PRINCIPLED_4: NodeType = NodeType {
+        blender_id: "ShaderNodeBsdfPrincipled",
+        size: [240.0, 658.0],
+        input_sockets: &[
+            Name("Base Color"),
+            Name("Metallic"),
+            Name("Specular IOR Level"),
+            Name("Specular Tint"),
+            Name("Roughness"),
+            Name("Emission Color"),
+            Name("Emission Strength"),
+            Name("Alpha"),
+            Name("Normal"),
+        ],
+        output_sockets: &[Position(0)],
+        ..NodeType::default()
+    };
+
+    /// Maps Blender 3.x Principled BSDF socket names to their Blender 4.0 equivalents.
+    pub static PRINCIPLED_4_SOCKET_MAP: &[(&str, &str)] = &[
+        ("Specular", "Specular IOR Level"),
+        ("Emission", "Emission Color"),
+        ("Clearcoat", "Coat Weight"),
+        ("Clearcoat Roughness", "Coat Roughness"),
+    ];
+
     pub static TRANSPARENT: NodeType = NodeType {
         blender_id: "ShaderNodeBsdfTransparent",
         size: [140.0, 75.0],
@@ -162,15 +189,109 @@ pub mod nodes {
         output_sockets: &[Name("X"), Name("Y"), Name("Z")],
         ..NodeType::default()
     };
+
+    pub static LAYER_WEIGHT: NodeType = NodeType {
+        blender_id: "ShaderNodeLayerWeight",
+        size: [140.0, 122.0],
+        input_sockets: &[Name("Blend"), Name("Normal")],
+        output_sockets: &[Name("Fresnel"), Name("Facing")],
+        ..NodeType::default()
+    };
+
+    pub static RGB_TO_BW: NodeType = NodeType {
+        blender_id: "ShaderNodeRGBToBW",
+        size: [140.0, 78.0],
+        input_sockets: &[Name("Color")],
+        output_sockets: &[Name("Val")],
+        ..NodeType::default()
+    };
+
+    pub static BUMP: NodeType = NodeType {
+        blender_id: "ShaderNodeBump",
+        size: [140.0, 173.0],
+        input_sockets: &[
+            Name("Strength"),
+            Name("Distance"),
+            Name("Height"),
+            Name("Normal"),
+        ],
+        output_sockets: &[Name("Normal")],
+        properties: &["invert"],
+    };
+
+    pub static DISPLACEMENT: NodeType = NodeType {
+        blender_id: "ShaderNodeDisplacement",
+        size: [140.0, 173.0],
+        input_sockets: &[
+            Name("Height"),
+            Name("Midlevel"),
+            Name("Scale"),
+            Name("Normal"),
+        ],
+        output_sockets: &[Name("Displacement")],
+        properties: &["space"],
+    };
+
+    pub static VOLUME_ABSORPTION: NodeType = NodeType {
+        blender_id: "ShaderNodeVolumeAbsorption",
+        size: [150.0, 104.0],
+        input_sockets: &[Name("Color"), Name("Density")],
+        output_sockets: &[Name("Volume")],
+        ..NodeType::default()
+    };
+}
+
+/// Group nodes referencing the shared node groups, with the group inputs and outputs
+/// as sockets.
+pub mod shared {
+    use super::super::nodes::{NodeSocketId::Name, NodeType};
+
+    pub static DX_NORMAL_MAP_CONVERTER: NodeType = NodeType {
+        blender_id: "ShaderNodeGroup",
+        size: [140.0, 100.0],
+        input_sockets: &[Name("image")],
+        output_sockets: &[Name("image")],
+        properties: &["node_tree"],
+    };
+
+    pub static SSBUMP_CONVERTER: NodeType = NodeType {
+        blender_id: "ShaderNodeGroup",
+        size: [140.0, 100.0],
+        input_sockets: &[Name("image")],
+        output_sockets: &[Name("image")],
+        properties: &["node_tree"],
+    };
+
+    pub static BLEND_TEXTURE: NodeType = NodeType {
+        blender_id: "ShaderNodeGroup",
+        size: [140.0, 232.0],
+        input_sockets: &[
+            Name("color"),
+            Name("color2"),
+            Name("alpha"),
+            Name("alpha2"),
+            Name("fac"),
+        ],
+        output_sockets: &[Name("color"), Name("alpha")],
+        properties: &["node_tree"],
+    };
+
+    pub static MODULATED_FACTOR: NodeType = NodeType {
+        blender_id: "ShaderNodeGroup",
+        size: [140.0, 122.0],
+        input_sockets: &[Name("modulate"), Name("fac")],
+        output_sockets: &[Name("fac")],
+        properties: &["node_tree"],
+    };
 }
 
 pub mod groups {
     use super::super::nodes::{
         Node, NodeGroup,
         NodeSocketId::{Name, Position},
-        NodeSocketRef, Ref, Value,
+        NodeSocketRef, Ref, SharedNodeGroup, Value,
     };
-    use super::nodes;
+    use super::{nodes, shared};
 
     pub static TEXTURE: NodeGroup = NodeGroup {
         nodes: &[Node {
@@ -196,10 +317,17 @@ pub mod groups {
                 id: "coord",
                 ..Node::default()
             },
+            Node {
+                kind: &nodes::VECTOR_MATH,
+                id: "center",
+                properties: &[("operation", Value::Enum("SUBTRACT"))],
+                links: &[(Position(0), NodeSocketRef::new("coord", Name("UV")))],
+                ..Node::default()
+            },
             Node {
                 kind: &nodes::MAPPING,
                 id: "mapping",
-                links: &[(Name("Vector"), NodeSocketRef::new("coord", Name("UV")))],
+                links: &[(Name("Vector"), NodeSocketRef::new("center", Position(0)))],
                 ..Node::default()
             },
             Node {
@@ -217,6 +345,8 @@ pub mod groups {
             ("interpolation", Ref::new("texture", "interpolation")),
         ],
         inputs: &[
+            // subtracted before scaling and rotating, and added back to the location
+            ("center", NodeSocketRef::new("center", Position(1))),
             ("scale", NodeSocketRef::new("mapping", Name("Scale"))),
             ("rotation", NodeSocketRef::new("mapping", Name("Rotation"))),
             ("location", NodeSocketRef::new("mapping", Name("Location"))),
@@ -225,6 +355,7 @@ pub mod groups {
             ("color", NodeSocketRef::new("texture", Name("Color"))),
             ("alpha", NodeSocketRef::new("texture", Name("Alpha"))),
         ],
+        ..NodeGroup::default()
     };
 
     pub static SPLIT_TEXTURE: NodeGroup = NodeGroup {
@@ -281,6 +412,10 @@ pub mod groups {
         ],
         inputs: &[("image", NodeSocketRef::new("separate", Name("Image")))],
         outputs: &[("image", NodeSocketRef::new("combine", Name("Image")))],
+        shared: Some(SharedNodeGroup {
+            name: "DX Normal Map Converter",
+            node: &shared::DX_NORMAL_MAP_CONVERTER,
+        }),
         ..NodeGroup::default()
     };
 
@@ -362,6 +497,10 @@ pub mod groups {
         ],
         inputs: &[("image", NodeSocketRef::new("sep", Name("Vector")))],
         outputs: &[("image", NodeSocketRef::new("add", Position(0)))],
+        shared: Some(SharedNodeGroup {
+            name: "SSBump Converter",
+            node: &shared::SSBUMP_CONVERTER,
+        }),
         ..NodeGroup::default()
     };
 
@@ -411,6 +550,129 @@ pub mod groups {
         ..NodeGroup::default()
     };
 
+    pub static DETAIL_TEXTURE_ADDITIVE: NodeGroup = NodeGroup {
+        nodes: &[Node {
+            kind: &nodes::MIX_RGB,
+            id: "add",
+            properties: &[("blend_type", Value::Enum("ADD"))],
+            ..Node::default()
+        }],
+        inputs: &[
+            ("color", NodeSocketRef::new("add", Name("Color1"))),
+            ("detail", NodeSocketRef::new("add", Name("Color2"))),
+            ("fac", NodeSocketRef::new("add", Name("Fac"))),
+        ],
+        outputs: &[("color", NodeSocketRef::new("add", Name("Color")))],
+        ..NodeGroup::default()
+    };
+
+    pub static DETAIL_TEXTURE_TRANSLUCENT: NodeGroup = NodeGroup {
+        nodes: &[
+            Node {
+                kind: &nodes::MATH,
+                id: "mul",
+                properties: &[("operation", Value::Enum("MULTIPLY"))],
+                ..Node::default()
+            },
+            Node {
+                kind: &nodes::MIX_RGB,
+                id: "mix",
+                properties: &[("blend_type", Value::Enum("MIX"))],
+                links: &[(Name("Fac"), NodeSocketRef::new("mul", Position(0)))],
+                ..Node::default()
+            },
+        ],
+        inputs: &[
+            ("color", NodeSocketRef::new("mix", Name("Color1"))),
+            ("detail", NodeSocketRef::new("mix", Name("Color2"))),
+            ("detail_alpha", NodeSocketRef::new("mul", Position(0))),
+            ("fac", NodeSocketRef::new("mul", Position(1))),
+        ],
+        outputs: &[("color", NodeSocketRef::new("mix", Name("Color")))],
+        ..NodeGroup::default()
+    };
+
+    pub static DETAIL_TEXTURE_FADE: NodeGroup = NodeGroup {
+        nodes: &[Node {
+            kind: &nodes::MIX_RGB,
+            id: "mix",
+            properties: &[("blend_type", Value::Enum("MIX"))],
+            ..Node::default()
+        }],
+        inputs: &[
+            ("color", NodeSocketRef::new("mix", Name("Color1"))),
+            ("detail", NodeSocketRef::new("mix", Name("Color2"))),
+            ("fac", NodeSocketRef::new("mix", Name("Fac"))),
+        ],
+        outputs: &[("color", NodeSocketRef::new("mix", Name("Color")))],
+        ..NodeGroup::default()
+    };
+
+    pub static DETAIL_TEXTURE_TRANSLUCENT_BASE: NodeGroup = NodeGroup {
+        nodes: &[
+            Node {
+                kind: &nodes::MATH,
+                id: "invert",
+                properties: &[("operation", Value::Enum("SUBTRACT"))],
+                values: &[(Position(0), Value::Float(1.0))],
+                ..Node::default()
+            },
+            Node {
+                kind: &nodes::MATH,
+                id: "mul",
+                properties: &[("operation", Value::Enum("MULTIPLY"))],
+                links: &[(Position(0), NodeSocketRef::new("invert", Position(0)))],
+                ..Node::default()
+            },
+            Node {
+                kind: &nodes::MIX_RGB,
+                id: "mix",
+                properties: &[("blend_type", Value::Enum("MIX"))],
+                links: &[(Name("Fac"), NodeSocketRef::new("mul", Position(0)))],
+                ..Node::default()
+            },
+        ],
+        inputs: &[
+            ("color", NodeSocketRef::new("mix", Name("Color1"))),
+            ("detail", NodeSocketRef::new("mix", Name("Color2"))),
+            ("alpha", NodeSocketRef::new("invert", Position(1))),
+            ("fac", NodeSocketRef::new("mul", Position(1))),
+        ],
+        outputs: &[("color", NodeSocketRef::new("mix", Name("Color")))],
+        ..NodeGroup::default()
+    };
+
+    pub static DETAIL_TEXTURE_MULTIPLY: NodeGroup = NodeGroup {
+        nodes: &[Node {
+            kind: &nodes::MIX_RGB,
+            id: "mul",
+            properties: &[("blend_type", Value::Enum("MULTIPLY"))],
+            ..Node::default()
+        }],
+        inputs: &[
+            ("color", NodeSocketRef::new("mul", Name("Color1"))),
+            ("detail", NodeSocketRef::new("mul", Name("Color2"))),
+            ("fac", NodeSocketRef::new("mul", Name("Fac"))),
+        ],
+        outputs: &[("color", NodeSocketRef::new("mul", Name("Color")))],
+        ..NodeGroup::default()
+    };
+
+    pub static DETAIL_ALPHA_MASK: NodeGroup = NodeGroup {
+        nodes: &[Node {
+            kind: &nodes::MATH,
+            id: "mul",
+            properties: &[("operation", Value::Enum("MULTIPLY"))],
+            ..Node::default()
+        }],
+        inputs: &[
+            ("alpha", NodeSocketRef::new("mul", Position(0))),
+            ("detail_alpha", NodeSocketRef::new("mul", Position(1))),
+        ],
+        outputs: &[("alpha", NodeSocketRef::new("mul", Position(0)))],
+        ..NodeGroup::default()
+    };
+
     pub static COLOR_TEXTURE: NodeGroup = NodeGroup {
         nodes: &[Node {
             kind: &nodes::MIX_RGB,
@@ -428,6 +690,171 @@ pub mod groups {
         ..NodeGroup::default()
     };
 
+    /// Multiplies a color by its alpha, so that the colors of transparent texels don't
+    /// show around the edges of translucent materials.
+    /// Divides a premultiplied color by its alpha. Blender leaves the color as it is
+    /// where the alpha is zero.
+    pub static UNPREMULTIPLY_ALPHA: NodeGroup = NodeGroup {
+        nodes: &[Node {
+            kind: &nodes::MIX_RGB,
+            id: "div",
+            properties: &[("blend_type", Value::Enum("DIVIDE"))],
+            values: &[(Name("Fac"), Value::Float(1.0))],
+            ..Node::default()
+        }],
+        inputs: &[
+            ("color", NodeSocketRef::new("div", Name("Color1"))),
+            ("alpha", NodeSocketRef::new("div", Name("Color2"))),
+        ],
+        outputs: &[("color", NodeSocketRef::new("div", Name("Color")))],
+        ..NodeGroup::default()
+    };
+
+    pub static SELFILLUM_TINT: NodeGroup = NodeGroup {
+        nodes: &[Node {
+            kind: &nodes::MIX_RGB,
+            id: "mul",
+            properties: &[("blend_type", Value::Enum("MULTIPLY"))],
+            values: &[(Name("Fac"), Value::Float(1.0))],
+            ..Node::default()
+        }],
+        inputs: &[
+            ("color", NodeSocketRef::new("mul", Name("Color1"))),
+            ("tint", NodeSocketRef::new("mul", Name("Color2"))),
+        ],
+        outputs: &[("color", NodeSocketRef::new("mul", Name("Color")))],
+        ..NodeGroup::default()
+    };
+
+    /// Scales the color by `pow(dot(normal, view), exponent)` remapped to `min..max`,
+    /// like the `$selfillum_fresnel` shader option.
+    pub static SELFILLUM_FRESNEL: NodeGroup = NodeGroup {
+        nodes: &[
+            Node {
+                kind: &nodes::LAYER_WEIGHT,
+                id: "weight",
+                values: &[(Name("Blend"), Value::Float(0.5))],
+                ..Node::default()
+            },
+            Node {
+                kind: &nodes::MATH,
+                id: "facing",
+                properties: &[("operation", Value::Enum("SUBTRACT"))],
+                values: &[(Position(0), Value::Float(1.0))],
+                links: &[(Position(1), NodeSocketRef::new("weight", Name("Facing")))],
+            },
+            Node {
+                kind: &nodes::MATH,
+                id: "pow",
+                properties: &[("operation", Value::Enum("POWER"))],
+                links: &[(Position(0), NodeSocketRef::new("facing", Position(0)))],
+                ..Node::default()
+            },
+            Node {
+                kind: &nodes::MAP_RANGE,
+                id: "map",
+                values: &[
+                    (Name("From Min"), Value::Float(0.0)),
+                    (Name("From Max"), Value::Float(1.0)),
+                ],
+                links: &[(Name("Value"), NodeSocketRef::new("pow", Position(0)))],
+                ..Node::default()
+            },
+            Node {
+                kind: &nodes::MIX_RGB,
+                id: "mul",
+                properties: &[("blend_type", Value::Enum("MULTIPLY"))],
+                values: &[(Name("Fac"), Value::Float(1.0))],
+                links: &[(Name("Color2"), NodeSocketRef::new("map", Position(0)))],
+            },
+        ],
+        inputs: &[
+            ("color", NodeSocketRef::new("mul", Name("Color1"))),
+            ("exponent", NodeSocketRef::new("pow", Position(1))),
+            ("min", NodeSocketRef::new("map", Name("To Min"))),
+            ("max", NodeSocketRef::new("map", Name("To Max"))),
+        ],
+        outputs: &[("color", NodeSocketRef::new("mul", Name("Color")))],
+        ..NodeGroup::default()
+    };
+
+    /// Approximates the height of a self-shadowed bump map by its brightness,
+    /// which is lower in the crevices.
+    pub static SSBUMP_HEIGHT: NodeGroup = NodeGroup {
+        nodes: &[Node {
+            kind: &nodes::RGB_TO_BW,
+            id: "bw",
+            ..Node::default()
+        }],
+        inputs: &[("image", NodeSocketRef::new("bw", Name("Color")))],
+        outputs: &[("height", NodeSocketRef::new("bw", Name("Val")))],
+        ..NodeGroup::default()
+    };
+
+    pub static HEIGHT_BUMP: NodeGroup = NodeGroup {
+        nodes: &[Node {
+            kind: &nodes::BUMP,
+            id: "bump",
+            values: &[(Name("Strength"), Value::Float(1.0))],
+            ..Node::default()
+        }],
+        inputs: &[
+            ("height", NodeSocketRef::new("bump", Name("Height"))),
+            ("distance", NodeSocketRef::new("bump", Name("Distance"))),
+        ],
+        outputs: &[("normal", NodeSocketRef::new("bump", Name("Normal")))],
+        ..NodeGroup::default()
+    };
+
+    /// [`HEIGHT_BUMP`] on top of a normal map.
+    pub static HEIGHT_BUMP_NORMAL: NodeGroup = NodeGroup {
+        nodes: &[Node {
+            kind: &nodes::BUMP,
+            id: "bump",
+            values: &[(Name("Strength"), Value::Float(1.0))],
+            ..Node::default()
+        }],
+        inputs: &[
+            ("height", NodeSocketRef::new("bump", Name("Height"))),
+            ("distance", NodeSocketRef::new("bump", Name("Distance"))),
+            ("normal", NodeSocketRef::new("bump", Name("Normal"))),
+        ],
+        outputs: &[("normal", NodeSocketRef::new("bump", Name("Normal")))],
+        ..NodeGroup::default()
+    };
+
+    pub static HEIGHT_DISPLACEMENT: NodeGroup = NodeGroup {
+        nodes: &[Node {
+            kind: &nodes::DISPLACEMENT,
+            id: "displacement",
+            values: &[(Name("Midlevel"), Value::Float(0.5))],
+            ..Node::default()
+        }],
+        inputs: &[
+            ("height", NodeSocketRef::new("displacement", Name("Height"))),
+            ("scale", NodeSocketRef::new("displacement", Name("Scale"))),
+        ],
+        outputs: &[(
+            "displacement",
+            NodeSocketRef::new("displacement", Name("Displacement")),
+        )],
+        ..NodeGroup::default()
+    };
+
+    pub static VOLUME_ABSORPTION: NodeGroup = NodeGroup {
+        nodes: &[Node {
+            kind: &nodes::VOLUME_ABSORPTION,
+            id: "absorption",
+            ..Node::default()
+        }],
+        inputs: &[
+            ("color", NodeSocketRef::new("absorption", Name("Color"))),
+            ("density", NodeSocketRef::new("absorption", Name("Density"))),
+        ],
+        outputs: &[("volume", NodeSocketRef::new("absorption", Name("Volume")))],
+        ..NodeGroup::default()
+    };
+
     pub static BLEND_TEXTURE: NodeGroup = NodeGroup {
         nodes: &[
             Node {
@@ -459,6 +886,10 @@ pub mod groups {
             ("color", NodeSocketRef::new("mix_color", Name("Color"))),
             ("alpha", NodeSocketRef::new("mix_alpha", Position(0))),
         ],
+        shared: Some(SharedNodeGroup {
+            name: "Blend Texture",
+            node: &shared::BLEND_TEXTURE,
+        }),
         ..NodeGroup::default()
     };
 
@@ -565,6 +996,10 @@ pub mod groups {
             ("fac", NodeSocketRef::new("map", Name("Value"))),
         ],
         outputs: &[("fac", NodeSocketRef::new("map", Position(0)))],
+        shared: Some(SharedNodeGroup {
+            name: "Modulated Blend Factor",
+            node: &shared::MODULATED_FACTOR,
+        }),
         ..NodeGroup::default()
     };
 
@@ -1050,12 +1485,13 @@ pub mod groups {
 mod tests {
     use std::collections::BTreeSet;
 
-    use super::super::nodes::{NodeGroup, NodeSocketRef, NodeType};
+    use super::super::nodes::{NodeGroup, NodeSocketId, NodeSocketRef, NodeType};
 
     use super::*;
 
     static NODES: &[&NodeType] = &[
         &shaders::PRINCIPLED,
+        &shaders::PRINCIPLED_4,
         &shaders::TRANSPARENT,
         &shaders::GLASS,
         &nodes::TEX_IMAGE,
@@ -1071,6 +1507,15 @@ mod tests {
         &nodes::MAP_RANGE,
         &nodes::VECTOR_MATH,
         &nodes::SEPARATE_XYZ,
+        &nodes::LAYER_WEIGHT,
+        &nodes::RGB_TO_BW,
+        &nodes::BUMP,
+        &nodes::DISPLACEMENT,
+        &nodes::VOLUME_ABSORPTION,
+        &shared::DX_NORMAL_MAP_CONVERTER,
+        &shared::SSBUMP_CONVERTER,
+        &shared::BLEND_TEXTURE,
+        &shared::MODULATED_FACTOR,
     ];
 
     static NODE_GROUPS: &[&NodeGroup] = &[
@@ -1081,6 +1526,12 @@ mod tests {
         &groups::SSBUMP_CONVERTER,
         &groups::NORMAL_MAP,
         &groups::DETAIL_TEXTURE,
+        &groups::DETAIL_TEXTURE_ADDITIVE,
+        &groups::DETAIL_TEXTURE_TRANSLUCENT,
+        &groups::DETAIL_TEXTURE_FADE,
+        &groups::DETAIL_TEXTURE_TRANSLUCENT_BASE,
+        &groups::DETAIL_TEXTURE_MULTIPLY,
+        &groups::DETAIL_ALPHA_MASK,
         &groups::COLOR_TEXTURE,
         &groups::BLEND_TEXTURE,
         &groups::VERTEX_COLOR,
@@ -1095,6 +1546,13 @@ mod tests {
         &groups::MULTIBLEND_VALUE,
         &groups::BLEND_3_VALUES,
         &groups::CLIP_ALPHA,
+        &groups::SELFILLUM_TINT,
+        &groups::SELFILLUM_FRESNEL,
+        &groups::SSBUMP_HEIGHT,
+        &groups::HEIGHT_BUMP,
+        &groups::HEIGHT_BUMP_NORMAL,
+        &groups::HEIGHT_DISPLACEMENT,
+        &groups::VOLUME_ABSORPTION,
     ];
 
     #[test]
@@ -1217,5 +1675,36 @@ mod tests {
                 "invalid node group output target `{target:?}`"
             );
         }
+
+        if let Some(shared) = &node_group.shared {
+            assert!(
+                node_group.properties.is_empty(),
+                "shared node group `{}` has properties",
+                shared.name
+            );
+
+            let inputs: BTreeSet<_> = node_group.inputs.iter().map(|&(name, _)| name).collect();
+            let input_sockets: BTreeSet<_> = shared
+                .node
+                .input_sockets
+                .iter()
+                .map(|s| match s {
+                    NodeSocketId::Name(name) => *name,
+                    NodeSocketId::Position(_) => panic!("shared node group sockets must be named"),
+                })
+                .collect();
+            assert_eq!(inputs, input_sockets, "shared node group input mismatch");
+
+            let output_sockets: BTreeSet<_> = shared
+                .node
+                .output_sockets
+                .iter()
+                .map(|s| match s {
+                    NodeSocketId::Name(name) => *name,
+                    NodeSocketId::Position(_) => panic!("shared node group sockets must be named"),
+                })
+                .collect();
+            assert_eq!(outputs, output_sockets, "shared node group output mismatch");
+        }
     }
 }
@@ -0,0 +1,45 @@
+//! Loads loose image files which some mods ship in place of `.vtf` textures.
+
+use image::{DynamicImage, ImageFormat};
+use tracing::warn;
+
+use plumber_core::fs::{GamePathBuf, PathBuf};
+
+/// Loose image formats accepted in place of a missing `.vtf`, in search order.
+const LOOSE_FORMATS: [(&str, ImageFormat); 4] = [
+    ("png", ImageFormat::Png),
+    ("tga", ImageFormat::Tga),
+    ("jpg", ImageFormat::Jpeg),
+    ("jpeg", ImageFormat::Jpeg),
+];
+
+/// A decoded loose image, sent along with the material which uses it.
+#[derive(Clone)]
+pub struct LooseTexture {
+    pub name: String,
+    pub image: DynamicImage,
+}
+
+/// Finds and decodes a loose image next to the missing `.vtf` of a texture.
+///
+/// The texture keeps the name of the `.vtf`, so that materials refer to it as usual.
+pub fn read_loose_texture(
+    texture_path: &GamePathBuf,
+    mut read: impl FnMut(&PathBuf) -> Option<Vec<u8>>,
+) -> Option<LooseTexture> {
+    LOOSE_FORMATS.iter().find_map(|&(extension, format)| {
+        let mut path = texture_path.clone();
+        path.set_extension(extension);
+
+        let data = read(&path.clone().into())?;
+
+        let image = image::load_from_memory_with_format(&data, format)
+            .map_err(|error| warn!("texture `{path}`: could not decode loose image: {error}"))
+            .ok()?;
+
+        Some(LooseTexture {
+            name: texture_path.clone().into_string(),
+            image,
+        })
+    })
+}
@@ -1,35 +1,77 @@
 use std::{
+    collections::BTreeMap,
     fmt::{self, Debug, Formatter},
     io::Cursor,
+    mem,
     panic::{catch_unwind, AssertUnwindSafe},
 };
 
+use image::{
+    imageops::{self, FilterType},
+    DynamicImage, ImageOutputFormat, ImageResult, Rgba32FImage, RgbaImage,
+};
 use pyo3::{exceptions::PyRuntimeError, prelude::*};
 
 use plumber_core::{
-    asset_core::{CachedAssetConfig, Context},
+    asset_core::{AssetConfig, CachedAssetConfig, Context, NoError},
     asset_vmt::{VmtConfig, VmtError, VmtErrorInner, VmtHelper},
     asset_vtf::LoadedVtf,
-    fs::PathBuf,
-    vmt::MaterialInfo,
+    fs::{GamePathBuf, PathBuf},
+    vmt::{MaterialInfo, Shader},
 };
+use tracing::error;
 
-pub use builder::{build_material, Settings, TextureFormat, TextureInterpolation};
+pub use builder::{
+    build_material, BaseAlpha, ColorCorrection, HeightMaps, Settings, TargetVersion, TextureFormat,
+    TextureInterpolation, ToolMaterial,
+};
 pub use builder_base::BuiltMaterialData;
-pub use nodes::{BuiltNode, BuiltNodeSocketRef, TextureRef};
+pub(crate) use cache::{insert_average_color, is_base_texture, set_scope as set_cache_scope};
+pub use loose::LooseTexture;
+pub use nodes::{
+    AnimatedVec, BuiltNode, BuiltNodeGroup, BuiltNodeSocketRef, NodeTreeRef, TextureRef,
+};
+pub use vmat::build_vmat_material;
 
-use super::BlenderAssetHandler;
+use crate::{
+    game_path,
+    source2::vtex::{Vtex, VtexFormat},
+    wad::WadTexture,
+    watchdog::Watch,
+};
+
+use super::{
+    bc,
+    cubemap::{
+        decode_ldr, IMAGE_FORMAT_BGRA8888, IMAGE_FORMAT_DXT1, IMAGE_FORMAT_DXT5,
+        IMAGE_FORMAT_RGBA8888,
+    },
+    utils::srgb_to_linear,
+    BlenderAssetHandler,
+};
+
+use proxies::TransformAnimations;
 
 mod builder;
 mod builder_base;
+mod cache;
 mod definitions;
+mod loose;
 mod nodes;
+mod optimize;
+pub mod patch;
+mod proxies;
+pub mod rad;
+mod script;
+mod vmat;
 
 #[pyclass(module = "plumber")]
 pub struct Texture {
     pub name: String,
     width: u32,
     height: u32,
+    original_size: [u32; 2],
+    float_buffer: bool,
     data: Vec<u8>,
     format: TextureFormat,
 }
@@ -52,36 +94,377 @@ impl Texture {
         self.format.to_ext_str()
     }
 
+    /// Size of the texture before it was downscaled to the maximum resolution.
+    fn original_size(&self) -> [u32; 2] {
+        self.original_size
+    }
+
+    /// If the pixels are raw instead of an encoded file.
+    fn is_raw(&self) -> bool {
+        self.format == TextureFormat::Raw
+    }
+
+    /// If the texture has a high dynamic range, and needs a float image.
+    fn float_buffer(&self) -> bool {
+        self.float_buffer
+    }
+
+    /// The encoded file, or for raw textures the RGBA pixels as native endian floats,
+    /// bottom row first like `Image.pixels`. The limited Python API has no buffer
+    /// protocol, so the pixels are bytes which `memoryview` can cast to floats.
     fn bytes(&self) -> &[u8] {
         &self.data
     }
 }
 
 impl Texture {
-    pub fn new(texture: &LoadedVtf, format: TextureFormat) -> Self {
-        let width = texture.data.width();
-        let height = texture.data.height();
+    /// Encodes a loose image which replaces a missing `.vtf`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the image can't be encoded in the format, like a 16-bit image as
+    /// TGA, since loose images can be in any of the decoded formats.
+    pub fn from_loose(
+        texture: &LooseTexture,
+        format: TextureFormat,
+        max_resolution: Option<u32>,
+        color_correction: Option<ColorCorrection>,
+    ) -> ImageResult<Self> {
+        Self::try_from_image(
+            texture.name.clone(),
+            &texture.image,
+            format,
+            max_resolution,
+            color_correction,
+        )
+    }
 
-        let mut data = Vec::new();
-        texture
-            .data
-            .write_to(&mut Cursor::new(&mut data), format.to_output_format())
-            .unwrap();
+    /// Creates a texture from a decoded texture of a GoldSrc texture archive.
+    pub fn from_wad(
+        texture: &WadTexture,
+        format: TextureFormat,
+        max_resolution: Option<u32>,
+        color_correction: Option<ColorCorrection>,
+    ) -> Self {
+        let image = RgbaImage::from_raw(texture.width, texture.height, texture.rgba.clone())
+            .expect("wad texture should have a pixel for its whole size");
 
-        Self {
-            name: texture.name.to_string(),
-            width,
-            height,
+        Self::from_image(
+            texture.name.clone(),
+            &DynamicImage::ImageRgba8(image),
             format,
-            data,
+            max_resolution,
+            color_correction,
+        )
+    }
+
+    /// Creates a texture from the largest mipmap of a compiled Source 2 texture.
+    /// Returns `None` if the mipmap can't be decoded.
+    pub fn from_vtex(
+        name: String,
+        texture: &Vtex,
+        format: TextureFormat,
+        max_resolution: Option<u32>,
+        color_correction: Option<ColorCorrection>,
+    ) -> Option<Self> {
+        let (width, height) = (texture.width, texture.height);
+
+        let image = match texture.format {
+            VtexFormat::PngRgba8888 | VtexFormat::PngDxt5 => {
+                image::load_from_memory_with_format(&texture.data, image::ImageFormat::Png).ok()?
+            }
+            VtexFormat::I8 => DynamicImage::ImageLuma8(image::GrayImage::from_raw(
+                width,
+                height,
+                texture.data.to_vec(),
+            )?),
+            VtexFormat::Ia88 => DynamicImage::ImageLumaA8(image::GrayAlphaImage::from_raw(
+                width,
+                height,
+                texture.data.to_vec(),
+            )?),
+            VtexFormat::Dxt1 => DynamicImage::ImageRgba8(decode_ldr(
+                IMAGE_FORMAT_DXT1,
+                &texture.data,
+                width,
+                height,
+            )?),
+            VtexFormat::Dxt5 => DynamicImage::ImageRgba8(decode_ldr(
+                IMAGE_FORMAT_DXT5,
+                &texture.data,
+                width,
+                height,
+            )?),
+            VtexFormat::Rgba8888 => DynamicImage::ImageRgba8(decode_ldr(
+                IMAGE_FORMAT_RGBA8888,
+                &texture.data,
+                width,
+                height,
+            )?),
+            VtexFormat::Bgra8888 => DynamicImage::ImageRgba8(decode_ldr(
+                IMAGE_FORMAT_BGRA8888,
+                &texture.data,
+                width,
+                height,
+            )?),
+            VtexFormat::Bc7 => {
+                DynamicImage::ImageRgba8(bc::decode_bc7(&texture.data, width, height)?)
+            }
+            VtexFormat::Ati2n => {
+                DynamicImage::ImageRgba8(bc::decode_bc5(&texture.data, width, height)?)
+            }
+            VtexFormat::Ati1n => {
+                DynamicImage::ImageRgba8(bc::decode_bc4(&texture.data, width, height)?)
+            }
+        };
+
+        Some(Self::from_image(
+            name,
+            &image,
+            format,
+            max_resolution,
+            color_correction,
+        ))
+    }
+
+    /// Creates a texture from a source image of a Source 2 material, such as a `.png` or
+    /// `.tga`. Returns `None` if the image can't be decoded.
+    pub fn from_source_image(
+        name: String,
+        data: &[u8],
+        format: TextureFormat,
+        max_resolution: Option<u32>,
+        color_correction: Option<ColorCorrection>,
+    ) -> Option<Self> {
+        let image = image::load_from_memory(data).ok()?;
+        Some(Self::from_image(
+            name,
+            &image,
+            format,
+            max_resolution,
+            color_correction,
+        ))
+    }
+
+    /// Creates a texture from a loaded VTF. Two-channel normal maps get their Z
+    /// reconstructed into the blue channel.
+    pub fn new(
+        texture: &LoadedVtf,
+        format: TextureFormat,
+        max_resolution: Option<u32>,
+        two_channel_normal: bool,
+        color_correction: Option<ColorCorrection>,
+    ) -> Self {
+        if two_channel_normal {
+            return Self::from_image(
+                texture.name.to_string(),
+                &reconstruct_normal_z(&texture.data),
+                format,
+                max_resolution,
+                None,
+            );
+        }
+
+        Self::from_image(
+            texture.name.to_string(),
+            &texture.data,
+            format,
+            max_resolution,
+            color_correction,
+        )
+    }
+
+    /// Encodes an image, correcting its colors first if it's a color texture.
+    fn from_image(
+        name: String,
+        image: &DynamicImage,
+        format: TextureFormat,
+        max_resolution: Option<u32>,
+        color_correction: Option<ColorCorrection>,
+    ) -> Self {
+        Self::try_from_image(name, image, format, max_resolution, color_correction)
+            .expect("decoded textures should be encodable")
+    }
+
+    fn try_from_image(
+        name: String,
+        image: &DynamicImage,
+        format: TextureFormat,
+        max_resolution: Option<u32>,
+        color_correction: Option<ColorCorrection>,
+    ) -> ImageResult<Self> {
+        let corrected = color_correction.map(|correction| correct_colors(image, correction));
+        let image = corrected.as_ref().unwrap_or(image);
+
+        let original_size = [image.width(), image.height()];
+        let downscaled = downscale(image, max_resolution);
+        let image = downscaled.as_ref().unwrap_or(image);
+
+        Ok(Self {
+            name,
+            width: image.width(),
+            height: image.height(),
+            original_size,
+            float_buffer: is_float(image),
+            data: encode(image, format)?,
+            format,
+        })
+    }
+}
+
+/// Halves the size of an image until it fits the maximum resolution, which gives
+/// the size of the closest mipmap. Returns `None` if the image already fits.
+fn downscale(image: &DynamicImage, max_resolution: Option<u32>) -> Option<DynamicImage> {
+    let max_resolution = max_resolution?.max(1);
+    let (mut width, mut height) = (image.width(), image.height());
+
+    if width.max(height) <= max_resolution {
+        return None;
+    }
+
+    while width.max(height) > max_resolution {
+        width = (width / 2).max(1);
+        height = (height / 2).max(1);
+    }
+
+    Some(image.resize_exact(width, height, FilterType::Triangle))
+}
+
+/// Whether the blue channel of an 8-bit image is zero everywhere, like it is when
+/// a two-channel format is decoded.
+pub fn has_empty_blue_channel(image: &DynamicImage) -> bool {
+    match image {
+        DynamicImage::ImageRgba8(image) => image.pixels().all(|p| p[2] == 0),
+        DynamicImage::ImageRgb8(image) => image.pixels().all(|p| p[2] == 0),
+        _ => false,
+    }
+}
+
+/// Returns the average linear color of an image, or `None` if it's empty.
+pub fn average_color(image: &DynamicImage) -> Option<[f32; 4]> {
+    let add = |sum: [f64; 4], pixel: [f32; 4]| [0, 1, 2, 3].map(|i| sum[i] + f64::from(pixel[i]));
+
+    let sum = if is_float(image) {
+        image.to_rgba32f().pixels().map(|p| p.0).fold([0.0; 4], add)
+    } else {
+        image
+            .to_rgba8()
+            .pixels()
+            .map(|p| {
+                let [r, g, b, a] = p.0.map(|c| f32::from(c) / 255.0);
+                [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+            })
+            .fold([0.0; 4], add)
+    };
+    let count = f64::from(image.width()) * f64::from(image.height());
+
+    // the color is only displayed, so the lost precision doesn't matter
+    #[allow(clippy::cast_possible_truncation)]
+    (count > 0.0).then(|| sum.map(|c| (c / count) as f32))
+}
+
+/// Computes `Z = sqrt(1 - X² - Y²)` of a normal map which only stores X and Y
+/// in the red and green channels.
+fn reconstruct_normal_z(image: &DynamicImage) -> DynamicImage {
+    let mut pixels = image.to_rgba8();
+
+    for pixel in pixels.pixels_mut() {
+        let [x, y] = [pixel[0], pixel[1]].map(|c| f32::from(c) / 255.0 * 2.0 - 1.0);
+        let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+
+        // always in range since z is between 0 and 1
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        {
+            pixel[2] = ((z * 0.5 + 0.5) * 255.0).round() as u8;
+        }
+    }
+
+    DynamicImage::ImageRgba8(pixels)
+}
+
+/// Applies a color correction to the color channels of an image, keeping the alpha.
+fn correct_colors(image: &DynamicImage, correction: ColorCorrection) -> DynamicImage {
+    if is_float(image) {
+        let mut pixels = image.to_rgba32f();
+        correct_hdr_colors(&mut pixels, correction);
+        return DynamicImage::ImageRgba32F(pixels);
+    }
+
+    let mut pixels = image.to_rgba8();
+    correct_ldr_colors(&mut pixels, correction);
+    DynamicImage::ImageRgba8(pixels)
+}
+
+/// Applies a color correction to an HDR image in place.
+pub(crate) fn correct_hdr_colors(pixels: &mut Rgba32FImage, correction: ColorCorrection) {
+    for pixel in pixels.pixels_mut() {
+        for channel in &mut pixel.0[..3] {
+            *channel = correction.apply(*channel);
+        }
+    }
+}
+
+/// Applies a color correction to an 8-bit image in place, through a lookup table.
+pub(crate) fn correct_ldr_colors(pixels: &mut RgbaImage, correction: ColorCorrection) {
+    let table: Vec<u8> = (0..=255_u8)
+        .map(|value| {
+            let corrected = correction.apply(f32::from(value) / 255.0).clamp(0.0, 1.0);
+
+            // always in range since it's clamped
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            {
+                (corrected * 255.0).round() as u8
+            }
+        })
+        .collect();
+
+    for pixel in pixels.pixels_mut() {
+        for channel in &mut pixel.0[..3] {
+            *channel = table[usize::from(*channel)];
+        }
+    }
+}
+
+fn is_float(image: &DynamicImage) -> bool {
+    matches!(
+        image,
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+    )
+}
+
+fn encode(image: &DynamicImage, format: TextureFormat) -> ImageResult<Vec<u8>> {
+    let mut data = Vec::new();
+
+    match format.to_output_format() {
+        // the OpenEXR encoder only accepts float images
+        Some(output_format @ ImageOutputFormat::OpenExr) => {
+            DynamicImage::ImageRgba32F(image.to_rgba32f())
+                .write_to(&mut Cursor::new(&mut data), output_format)?;
+        }
+        Some(output_format) => {
+            image.write_to(&mut Cursor::new(&mut data), output_format)?;
+        }
+        None => {
+            let mut pixels = image.to_rgba32f();
+            imageops::flip_vertical_in_place(&mut pixels);
+            data = pixels
+                .into_raw()
+                .into_iter()
+                .flat_map(f32::to_ne_bytes)
+                .collect();
         }
     }
+
+    Ok(data)
 }
 
 #[pyclass(module = "plumber")]
 pub struct Material {
     pub name: String,
     data: Option<BuiltMaterialData>,
+    tool_material: ToolMaterial,
+    metadata: BTreeMap<String, String>,
+    average_color: Option<[f32; 4]>,
     texture_format: TextureFormat,
 }
 
@@ -100,12 +483,47 @@ impl Material {
     fn texture_ext(&self) -> &str {
         self.texture_format.to_ext_str()
     }
+
+    fn tool_material(&self) -> &str {
+        self.tool_material.to_str()
+    }
+
+    /// Editor metadata of the material, like `%keywords` and `$surfaceprop`.
+    fn metadata(&mut self) -> BTreeMap<String, String> {
+        mem::take(&mut self.metadata)
+    }
+
+    /// Average linear color of the base texture, for viewport display and previews.
+    fn average_color(&self) -> Option<[f32; 4]> {
+        self.average_color
+    }
+
+    /// Blender Python script which recreates the material as `blender_name`,
+    /// assuming its textures are imported. The script imports the helpers of the
+    /// importer from the `helpers` module. Must be called before `data`.
+    fn python_script(&self, blender_name: &str, helpers: &str) -> PyResult<String> {
+        let data = self
+            .data
+            .as_ref()
+            .ok_or_else(|| PyRuntimeError::new_err("material data already consumed"))?;
+
+        Ok(script::python_script(
+            blender_name,
+            &self.name,
+            data,
+            self.texture_format.to_ext_str(),
+            helpers,
+        ))
+    }
 }
 
 impl Material {
-    pub fn new(name: &PathBuf, data: BuiltMaterialData, texture_format: TextureFormat) -> Self {
+    pub fn new(name: &PathBuf, mut data: BuiltMaterialData, texture_format: TextureFormat) -> Self {
         Self {
             name: name.to_string(),
+            tool_material: data.tool_material,
+            metadata: mem::take(&mut data.metadata),
+            average_color: data.average_color,
             data: Some(data),
             texture_format,
         }
@@ -145,11 +563,44 @@ impl CachedAssetConfig<BlenderAssetHandler> for MaterialConfig {
     ) -> Result<(Self::Output<'a>, Self::CachedOutput), Self::Error> {
         input.normalize_extension();
 
-        let vmt_helper = VmtHelper::new(&input, context.fs())?;
+        let _watch = Watch::new(format!("material `{input}`"));
+
+        // patch includes are followed without cycle detection when loading,
+        // so the chain is resolved first to report what's wrong with it
+        let patched = match resolve_patches(&input, context) {
+            Ok(patched) => patched,
+            Err(patch_error) => {
+                error!("material {patch_error}");
+
+                return Err(VmtError {
+                    path: input,
+                    error: VmtErrorInner::Custom("could not resolve patch material includes"),
+                });
+            }
+        };
+
+        let texlight = read_texlight(&input, context);
+        let cache_key = cache::key(&input.to_string(), &patched, texlight, &self.settings);
+
+        if let Some(cached) = cache::get(cache_key, context) {
+            return Ok(((input, cached.data), cached.info));
+        }
+
+        let animations = TransformAnimations::from_parameters(&patched.parameters);
+        let metadata = patched.metadata();
+
+        let vmt_helper = patched_helper(&input, &patched);
         let info = vmt_helper.get_info(context.fs())?;
 
         let built = catch_unwind(AssertUnwindSafe(|| {
-            build_material(context, &vmt_helper, &info, self.settings)
+            build_material(
+                context,
+                &vmt_helper,
+                &info,
+                texlight,
+                animations,
+                self.settings,
+            )
         }))
         .map_err(|e| {
             let error = if let Some(s) = e.downcast_ref::<&'static str>() {
@@ -164,6 +615,215 @@ impl CachedAssetConfig<BlenderAssetHandler> for MaterialConfig {
             }
         })?;
 
+        // the base texture was loaded while building, which recorded its color
+        let average_color = patched
+            .base_texture()
+            .and_then(|texture| cache::average_color(&texture))
+            .map(|color| self.settings.texture_color_correction.apply_color(color));
+
+        let built = built.map(|mut built| {
+            built.tool_material = ToolMaterial::new(&vmt_helper, &info);
+            built.metadata = metadata;
+            built.average_color = average_color;
+
+            if let Some(color) = average_color {
+                built.set_viewport_color(color);
+            }
+
+            built
+        });
+
+        cache::insert(
+            cache_key,
+            cache::CachedMaterial {
+                data: built.clone(),
+                info: info.clone(),
+            },
+        );
+
         Ok(((input, built), info))
     }
 }
+
+/// Loads a batch of materials on one executor, like the materials which the imported
+/// assets only referred to by name.
+#[derive(Clone, Copy)]
+pub struct MaterialBatchConfig {
+    pub material: MaterialConfig,
+}
+
+impl Debug for MaterialBatchConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("MaterialBatchConfig")
+    }
+}
+
+impl AssetConfig<BlenderAssetHandler> for MaterialBatchConfig {
+    type Input<'a> = Vec<PathBuf>;
+    type Output<'a> = ();
+    type Error = NoError;
+
+    fn process<'a>(
+        self,
+        input: Self::Input<'a>,
+        context: &mut Context<BlenderAssetHandler>,
+    ) -> Result<Self::Output<'a>, Self::Error> {
+        for path in input {
+            // errors are reported by the handler
+            let _ = context.depend_on(self.material, path);
+        }
+
+        Ok(())
+    }
+}
+
+fn resolve_patches(
+    input: &PathBuf,
+    context: &Context<BlenderAssetHandler>,
+) -> Result<patch::PatchedMaterial, patch::PatchError> {
+    patch::resolve(&input.to_string(), |path| {
+        context
+            .fs()
+            .read(&include_path(input, path))
+            .map(|data| String::from_utf8_lossy(&data).into_owned())
+            .map_err(|e| e.to_string())
+    })
+}
+
+/// Returns the path of a material of a patch chain, as written in the `include` parameter.
+fn include_path(input: &PathBuf, path: &str) -> PathBuf {
+    if path == input.to_string() {
+        return input.clone();
+    }
+
+    // includes are game paths, usually with the `materials` directory
+    let path = game_path::normalize(path);
+    let mut path: PathBuf = if path.starts_with("materials/") {
+        GamePathBuf::from(path).into()
+    } else {
+        GamePathBuf::from(format!("materials/{path}")).into()
+    };
+    path.normalize_extension();
+    path
+}
+
+/// Builds the helper from the patched parameters instead of re-reading the material, so
+/// that the `insert` and `replace` parameters of patches reach the builder. Textures are
+/// resolved relative to the included material, like the game does.
+fn patched_helper(input: &PathBuf, patched: &patch::PatchedMaterial) -> VmtHelper {
+    let shader = Shader {
+        shader: patched.shader.clone(),
+        parameters: patched
+            .values()
+            .map(|(key, value)| (key.to_owned().into(), value.to_owned()))
+            .collect(),
+    };
+
+    VmtHelper::from_shader(include_path(input, patched.included()), shader)
+}
+
+fn read_texlight(input: &PathBuf, context: &Context<BlenderAssetHandler>) -> Option<rad::TexLight> {
+    cache::texlights(context.fs()).get(&input.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn encode_raw_pixels() {
+        let mut image = RgbaImage::new(1, 2);
+        image.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        let image = DynamicImage::ImageRgba8(image);
+
+        let data = encode(&image, TextureFormat::Raw).unwrap();
+        let pixels: Vec<f32> = data
+            .chunks_exact(4)
+            .map(|b| f32::from_ne_bytes(b.try_into().unwrap()))
+            .collect();
+
+        // the top row is last
+        assert_eq!(pixels.len(), 8);
+        assert!(pixels[0].abs() < 1e-6);
+        assert!((pixels[4] - 1.0).abs() < 1e-6);
+        assert!((pixels[7] - 1.0).abs() < 1e-6);
+        assert!(!is_float(&image));
+    }
+
+    #[test]
+    fn reconstruct_flat_normal_z() {
+        let mut image = RgbaImage::new(2, 1);
+        image.put_pixel(0, 0, Rgba([128, 128, 0, 255]));
+        image.put_pixel(1, 0, Rgba([255, 128, 0, 255]));
+        let image = DynamicImage::ImageRgba8(image);
+
+        assert!(has_empty_blue_channel(&image));
+
+        let reconstructed = reconstruct_normal_z(&image).to_rgba8();
+        // a flat normal points straight out
+        assert_eq!(reconstructed.get_pixel(0, 0)[2], 255);
+        // a normal along X has no Z
+        assert_eq!(reconstructed.get_pixel(1, 0)[2], 128);
+        assert!(!has_empty_blue_channel(&DynamicImage::ImageRgba8(
+            reconstructed
+        )));
+    }
+
+    #[test]
+    fn correct_texture_colors() {
+        let mut image = RgbaImage::new(2, 1);
+        image.put_pixel(0, 0, Rgba([64, 128, 255, 100]));
+        image.put_pixel(1, 0, Rgba([0, 0, 0, 0]));
+        let image = DynamicImage::ImageRgba8(image);
+
+        assert!(ColorCorrection::default().is_identity());
+
+        let brighter = ColorCorrection {
+            brightness: 2.0,
+            gamma: 1.0,
+        };
+        let corrected = correct_colors(&image, brighter).to_rgba8();
+        assert_eq!(corrected.get_pixel(0, 0).0, [128, 255, 255, 100]);
+        assert_eq!(corrected.get_pixel(1, 0).0, [0, 0, 0, 0]);
+
+        let gamma = ColorCorrection {
+            brightness: 1.0,
+            gamma: 2.0,
+        };
+        let corrected = correct_colors(&image, gamma).to_rgba8();
+        // the square root of a quarter
+        assert_eq!(corrected.get_pixel(0, 0).0, [128, 181, 255, 100]);
+    }
+
+    #[test]
+    fn downscale_to_mip_size() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::new(1024, 256));
+
+        let downscaled = downscale(&image, Some(300)).unwrap();
+        assert_eq!((downscaled.width(), downscaled.height()), (256, 64));
+
+        assert!(downscale(&image, Some(1024)).is_none());
+        assert!(downscale(&image, None).is_none());
+
+        let texture =
+            Texture::from_image("t".to_owned(), &image, TextureFormat::Raw, Some(1), None);
+        assert_eq!((texture.width, texture.height), (1, 1));
+        assert_eq!(texture.original_size, [1024, 256]);
+    }
+
+    #[test]
+    fn average_linear_color() {
+        let mut image = RgbaImage::from_pixel(2, 1, Rgba([255, 0, 0, 255]));
+        image.put_pixel(1, 0, Rgba([255, 255, 0, 51]));
+
+        let [r, g, b, a] = average_color(&DynamicImage::ImageRgba8(image)).unwrap();
+        assert!((r - 1.0).abs() < 1e-6);
+        assert!((g - 0.5).abs() < 1e-6);
+        assert!(b.abs() < 1e-6);
+        assert!((a - 0.6).abs() < 1e-6);
+
+        assert!(average_color(&DynamicImage::ImageRgba8(RgbaImage::new(0, 0))).is_none());
+    }
+}
@@ -1,4 +1,4 @@
-use std::{cmp::Ordering, collections::BTreeMap, mem, ptr};
+use std::{cmp::Ordering, collections::BTreeMap, iter, mem, ptr};
 
 use plumber_core::fs::GamePathBuf;
 use pyo3::prelude::*;
@@ -76,8 +76,12 @@ pub enum Value {
     Float(f32),
     Color([f32; 4]),
     Vec([f32; 3]),
+    /// A vector changing by the rate every second.
+    AnimatedVec([f32; 3], [f32; 3]),
     Enum(&'static str),
     Texture(GamePathBuf),
+    /// A shared node group, emitted once and referenced by name.
+    NodeTree(&'static NodeGroup),
 }
 
 #[pyclass(module = "plumber")]
@@ -90,6 +94,35 @@ impl TextureRef {
     }
 }
 
+#[pyclass(module = "plumber")]
+pub struct NodeTreeRef(&'static str);
+
+#[pymethods]
+impl NodeTreeRef {
+    fn name(&self) -> &str {
+        self.0
+    }
+}
+
+#[pyclass(module = "plumber")]
+pub struct AnimatedVec {
+    value: [f32; 3],
+    rate: [f32; 3],
+}
+
+#[pymethods]
+impl AnimatedVec {
+    /// Value at the start of the animation.
+    fn value(&self) -> [f32; 3] {
+        self.value
+    }
+
+    /// Change of the value per second.
+    fn rate(&self) -> [f32; 3] {
+        self.rate
+    }
+}
+
 impl IntoPy<PyObject> for Value {
     fn into_py(self, py: Python) -> PyObject {
         match self {
@@ -97,8 +130,10 @@ impl IntoPy<PyObject> for Value {
             Value::Float(f) => f.into_py(py),
             Value::Color(c) => c.into_py(py),
             Value::Vec(v) => v.into_py(py),
+            Value::AnimatedVec(value, rate) => AnimatedVec { value, rate }.into_py(py),
             Value::Enum(e) => e.into_py(py),
             Value::Texture(t) => TextureRef(t.into_string()).into_py(py),
+            Value::NodeTree(g) => NodeTreeRef(g.shared_name()).into_py(py),
         }
     }
 }
@@ -299,8 +334,8 @@ impl Node {
 #[derive(Debug, Clone, Copy)]
 #[pyclass(module = "plumber")]
 pub struct BuiltNodeSocketRef {
-    node_index: usize,
-    socket: NodeSocketId,
+    pub(crate) node_index: usize,
+    pub(crate) socket: NodeSocketId,
 }
 
 impl BuiltNodeSocketRef {
@@ -326,13 +361,14 @@ pub enum BuiltNodeSocketLink {
     Value(Value),
 }
 
+#[derive(Clone)]
 #[pyclass(module = "plumber")]
 pub struct BuiltNode {
-    kind: &'static NodeType,
-    position: [f32; 2],
-    properties: BTreeMap<&'static str, Value>,
-    socket_values: BTreeMap<NodeSocketId, Value>,
-    socket_links: BTreeMap<NodeSocketId, BuiltNodeSocketRef>,
+    pub(crate) kind: &'static NodeType,
+    pub(crate) position: [f32; 2],
+    pub(crate) properties: BTreeMap<&'static str, Value>,
+    pub(crate) socket_values: BTreeMap<NodeSocketId, Value>,
+    pub(crate) socket_links: BTreeMap<NodeSocketId, BuiltNodeSocketRef>,
 }
 
 impl BuiltNode {
@@ -380,12 +416,24 @@ impl BuiltNode {
     }
 }
 
+/// A node group which is emitted once as a Blender node group and referenced from
+/// every material using it, instead of inlining its nodes into each material.
+/// The group can't have properties, since they would differ between materials.
+#[derive(Debug)]
+pub struct SharedNodeGroup {
+    /// Name of the Blender node group.
+    pub name: &'static str,
+    /// Group node referencing the node group, with the group inputs and outputs as sockets.
+    pub node: &'static NodeType,
+}
+
 #[derive(Debug)]
 pub struct NodeGroup {
     pub nodes: &'static [Node],
     pub properties: &'static [(&'static str, Ref)],
     pub inputs: &'static [(&'static str, NodeSocketRef)],
     pub outputs: &'static [(&'static str, NodeSocketRef)],
+    pub shared: Option<SharedNodeGroup>,
 }
 
 impl NodeGroup {
@@ -395,17 +443,44 @@ impl NodeGroup {
             properties: &[],
             inputs: &[],
             outputs: &[],
+            shared: None,
         }
     }
 
+    fn shared_name(&self) -> &'static str {
+        self.shared
+            .as_ref()
+            .expect("referenced node group should be shared")
+            .name
+    }
+
+    fn evaluate_input(
+        name: &'static str,
+        outputs: &BTreeMap<&'static str, BuiltNodeSocketRef>,
+        outside_links: &BTreeMap<&'static str, BuiltNodeSocketLink>,
+    ) -> BuiltNodeSocketLink {
+        let Some(socket) = outside_links
+            .get(name)
+            .cloned()
+            .or_else(|| outputs.get(name).map(|&l| BuiltNodeSocketLink::Link(l)))
+        else {
+            panic!("input {name} should not be unlinked");
+        };
+        socket
+    }
+
     pub fn build(
-        &self,
+        &'static self,
         nodes: &mut Vec<BuiltNode>,
         outputs: &mut BTreeMap<&'static str, BuiltNodeSocketRef>,
         outside_links: &BTreeMap<&'static str, BuiltNodeSocketLink>,
         outside_properties: &BTreeMap<&'static str, Value>,
         position: [f32; 2],
     ) -> [f32; 2] {
+        if let Some(shared) = &self.shared {
+            return self.build_shared(shared, nodes, outputs, outside_links, position);
+        }
+
         debug!("building node group");
 
         let mut local_outputs = BTreeMap::new();
@@ -418,16 +493,7 @@ impl NodeGroup {
                 .inputs
                 .iter()
                 .filter(|(_, r)| r.depends_on(node.id))
-                .map(|(name, r)| {
-                    let Some(socket) = outside_links
-                        .get(name)
-                        .cloned()
-                        .or_else(|| outputs.get(name).map(|&l| BuiltNodeSocketLink::Link(l)))
-                    else {
-                        panic!("input {name} should not be unlinked");
-                    };
-                    (r.socket, socket)
-                });
+                .map(|(name, r)| (r.socket, Self::evaluate_input(name, outputs, outside_links)));
 
             let properties = self
                 .properties
@@ -465,6 +531,149 @@ impl NodeGroup {
 
         [x_max, y_max]
     }
+
+    /// Builds a single group node referencing the shared node group.
+    fn build_shared(
+        &'static self,
+        shared: &SharedNodeGroup,
+        nodes: &mut Vec<BuiltNode>,
+        outputs: &mut BTreeMap<&'static str, BuiltNodeSocketRef>,
+        outside_links: &BTreeMap<&'static str, BuiltNodeSocketLink>,
+        position: [f32; 2],
+    ) -> [f32; 2] {
+        debug!("building shared node group {}", shared.name);
+
+        let mut socket_values = BTreeMap::new();
+        let mut socket_links = BTreeMap::new();
+
+        // an input can be linked to multiple nodes inside the group, but it's a single socket
+        for &(name, _) in self.inputs {
+            match Self::evaluate_input(name, outputs, outside_links) {
+                BuiltNodeSocketLink::Link(r) => {
+                    socket_links.insert(NodeSocketId::Name(name), r);
+                }
+                BuiltNodeSocketLink::Value(v) => {
+                    socket_values.insert(NodeSocketId::Name(name), v);
+                }
+            }
+        }
+
+        let built = shared.node.build(
+            [("node_tree", Value::NodeTree(self))].into(),
+            socket_values,
+            socket_links,
+            position,
+        );
+
+        let index = nodes.len();
+        nodes.push(built);
+
+        for &(name, _) in self.outputs {
+            outputs.insert(
+                name,
+                BuiltNodeSocketRef {
+                    node_index: index,
+                    socket: NodeSocketId::Name(name),
+                },
+            );
+        }
+
+        [
+            position[0] + shared.node.size[0],
+            position[1] + shared.node.size[1],
+        ]
+    }
+
+    /// Builds the nodes inside a shared node group, and the sockets the group inputs
+    /// and outputs are linked to.
+    pub(crate) fn build_shared_definition(&self) -> BuiltNodeGroup {
+        let mut nodes = Vec::new();
+        let mut local_outputs = BTreeMap::new();
+
+        let mut first = true;
+
+        for node in self.nodes {
+            node.build(
+                &mut nodes,
+                &mut local_outputs,
+                iter::empty(),
+                iter::empty(),
+                [0.0, 0.0],
+                !first,
+            );
+
+            first = false;
+        }
+
+        for node in &mut nodes {
+            node.invert_y();
+        }
+
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|(name, r)| {
+                let node_index = self
+                    .nodes
+                    .iter()
+                    .position(|n| n.id == r.target)
+                    .expect("input ref target should exist");
+
+                (
+                    *name,
+                    BuiltNodeSocketRef {
+                        node_index,
+                        socket: r.socket,
+                    },
+                )
+            })
+            .collect();
+
+        let outputs = self
+            .outputs
+            .iter()
+            .map(|(name, r)| {
+                let evaluated_output = local_outputs
+                    .get(r)
+                    .expect("output ref target should exist");
+
+                (*name, *evaluated_output)
+            })
+            .collect();
+
+        BuiltNodeGroup {
+            nodes,
+            inputs,
+            outputs,
+        }
+    }
+}
+
+/// Contents of a shared node group, created in Blender unless it already exists.
+#[derive(Clone)]
+#[pyclass(module = "plumber")]
+pub struct BuiltNodeGroup {
+    pub(crate) nodes: Vec<BuiltNode>,
+    pub(crate) inputs: Vec<(&'static str, BuiltNodeSocketRef)>,
+    pub(crate) outputs: Vec<(&'static str, BuiltNodeSocketRef)>,
+}
+
+#[pymethods]
+impl BuiltNodeGroup {
+    fn nodes(&mut self) -> Vec<BuiltNode> {
+        mem::take(&mut self.nodes)
+    }
+
+    /// Group inputs and the node sockets they are linked to.
+    /// The same input can be linked to multiple sockets.
+    fn inputs(&self) -> Vec<(&'static str, BuiltNodeSocketRef)> {
+        self.inputs.clone()
+    }
+
+    /// Group outputs and the node sockets linked to them.
+    fn outputs(&self) -> Vec<(&'static str, BuiltNodeSocketRef)> {
+        self.outputs.clone()
+    }
 }
 
 #[derive(Debug)]
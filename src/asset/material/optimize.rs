@@ -0,0 +1,397 @@
+//! Simplifies built node graphs before they are sent to Blender. Math nodes with only
+//! constant inputs are folded into the socket values of the nodes using them, and
+//! nodes which don't contribute to any output are removed.
+
+use std::{collections::BTreeMap, mem};
+
+use super::nodes::{BuiltNode, BuiltNodeSocketRef, NodeSocketId, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SocketKind {
+    Float,
+    Vector,
+    Color,
+}
+
+/// Folds constant nodes and removes nodes which the last node, the shader, and the
+/// `outputs` don't depend on. The outputs are updated to the new node indices.
+pub(crate) fn optimize(
+    mut nodes: Vec<BuiltNode>,
+    outputs: [&mut Option<BuiltNodeSocketRef>; 2],
+) -> Vec<BuiltNode> {
+    fold_constants(&mut nodes);
+    remove_unused(nodes, outputs)
+}
+
+/// Replaces the links from constant nodes with their values. Nodes only link to
+/// nodes built before them, so a single pass folds whole chains.
+fn fold_constants(nodes: &mut [BuiltNode]) {
+    let mut folded: BTreeMap<usize, Value> = BTreeMap::new();
+
+    for (index, node) in nodes.iter_mut().enumerate() {
+        let constant_links: Vec<(NodeSocketId, Value)> = node
+            .socket_links
+            .iter()
+            .filter_map(|(&socket, link)| {
+                let value = folded.get(&link.node_index)?;
+                let value = convert(value, socket_kind(node.kind.blender_id, socket)?)?;
+                Some((socket, value))
+            })
+            .collect();
+
+        for (socket, value) in constant_links {
+            node.socket_links.remove(&socket);
+            node.socket_values.insert(socket, value);
+        }
+
+        if node.socket_links.is_empty() {
+            if let Some(value) = evaluate(node) {
+                folded.insert(index, value);
+            }
+        }
+    }
+}
+
+/// Removes the nodes which aren't linked to the outputs, directly or indirectly.
+fn remove_unused(
+    nodes: Vec<BuiltNode>,
+    outputs: [&mut Option<BuiltNodeSocketRef>; 2],
+) -> Vec<BuiltNode> {
+    let mut used = vec![false; nodes.len()];
+    let mut stack: Vec<usize> = outputs
+        .iter()
+        .filter_map(|o| o.map(|r| r.node_index))
+        .chain(nodes.len().checked_sub(1))
+        .collect();
+
+    // nodes without outputs can't be linked, but aren't there without a reason
+    stack.extend(
+        nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| n.kind.output_sockets.is_empty())
+            .map(|(i, _)| i),
+    );
+
+    while let Some(index) = stack.pop() {
+        if mem::replace(&mut used[index], true) {
+            continue;
+        }
+
+        stack.extend(nodes[index].socket_links.values().map(|l| l.node_index));
+    }
+
+    let mut new_indices = Vec::with_capacity(nodes.len());
+    let mut next_index = 0;
+
+    for &is_used in &used {
+        new_indices.push(next_index);
+        next_index += usize::from(is_used);
+    }
+
+    for output in outputs.into_iter().flatten() {
+        output.node_index = new_indices[output.node_index];
+    }
+
+    nodes
+        .into_iter()
+        .zip(used)
+        .filter(|&(_, is_used)| is_used)
+        .map(|(mut node, _)| {
+            for link in node.socket_links.values_mut() {
+                link.node_index = new_indices[link.node_index];
+            }
+            node
+        })
+        .collect()
+}
+
+/// Returns the kind of value a socket takes, for the sockets constants can be folded into.
+fn socket_kind(blender_id: &str, socket: NodeSocketId) -> Option<SocketKind> {
+    match (blender_id, socket) {
+        ("ShaderNodeMath", NodeSocketId::Position(0..=2))
+        | ("ShaderNodeMixRGB", NodeSocketId::Name("Fac"))
+        | (
+            "ShaderNodeBsdfPrincipled" | "ShaderNodeBsdfGlass",
+            NodeSocketId::Name(
+                "Metallic" | "Specular" | "Specular IOR Level" | "Roughness" | "Emission Strength"
+                | "Alpha" | "IOR",
+            ),
+        ) => Some(SocketKind::Float),
+        ("ShaderNodeVectorMath", NodeSocketId::Position(0..=2)) => Some(SocketKind::Vector),
+        ("ShaderNodeMixRGB", NodeSocketId::Name("Color1" | "Color2"))
+        | (
+            "ShaderNodeBsdfPrincipled",
+            NodeSocketId::Name("Base Color" | "Emission" | "Emission Color"),
+        )
+        | ("ShaderNodeBsdfGlass", NodeSocketId::Name("Color")) => Some(SocketKind::Color),
+        _ => None,
+    }
+}
+
+/// Converts a value like Blender does when linking sockets of different kinds.
+/// Conversions to floats are left to Blender, since they depend on color management.
+fn convert(value: &Value, kind: SocketKind) -> Option<Value> {
+    match (value, kind) {
+        (&Value::Float(v), SocketKind::Float) => Some(Value::Float(v)),
+        (&Value::Float(v), SocketKind::Vector) => Some(Value::Vec([v; 3])),
+        (&Value::Float(v), SocketKind::Color) => Some(Value::Color([v, v, v, 1.0])),
+        (&Value::Vec(v), SocketKind::Vector) => Some(Value::Vec(v)),
+        (&Value::Vec([x, y, z]), SocketKind::Color) => Some(Value::Color([x, y, z, 1.0])),
+        (&Value::Color(c), SocketKind::Color) => Some(Value::Color(c)),
+        (&Value::Color([r, g, b, _]), SocketKind::Vector) => Some(Value::Vec([r, g, b])),
+        _ => None,
+    }
+}
+
+/// Computes the output of a math node with constant inputs. Returns `None` for other
+/// nodes, unsupported operations and unset inputs, whose defaults aren't known here.
+fn evaluate(node: &BuiltNode) -> Option<Value> {
+    let operation = match node.properties.get("operation") {
+        Some(&Value::Enum(operation)) => Some(operation),
+        _ => None,
+    };
+
+    let float = |socket: u32| match node.socket_values.get(&NodeSocketId::Position(socket)) {
+        Some(&Value::Float(v)) => Some(v),
+        _ => None,
+    };
+
+    let vector = |socket: u32| match node.socket_values.get(&NodeSocketId::Position(socket)) {
+        Some(&Value::Vec(v)) => Some(v),
+        _ => None,
+    };
+
+    match node.kind.blender_id {
+        "ShaderNodeMath" => {
+            let value = match operation? {
+                "ADD" => float(0)? + float(1)?,
+                "SUBTRACT" => float(0)? - float(1)?,
+                "MULTIPLY" => float(0)? * float(1)?,
+                "DIVIDE" => safe_divide(float(0)?, float(1)?),
+                "MULTIPLY_ADD" => float(0)?.mul_add(float(1)?, float(2)?),
+                "POWER" => safe_power(float(0)?, float(1)?),
+                "MINIMUM" => float(0)?.min(float(1)?),
+                "MAXIMUM" => float(0)?.max(float(1)?),
+                "GREATER_THAN" => f32::from(u8::from(float(0)? > float(1)?)),
+                "LESS_THAN" => f32::from(u8::from(float(0)? < float(1)?)),
+                _ => return None,
+            };
+
+            let clamp = matches!(node.properties.get("use_clamp"), Some(Value::Bool(true)));
+
+            Some(Value::Float(if clamp {
+                value.clamp(0.0, 1.0)
+            } else {
+                value
+            }))
+        }
+        "ShaderNodeVectorMath" => {
+            let per_component = |f: fn(f32, f32) -> f32| -> Option<[f32; 3]> {
+                let [a, b] = [vector(0)?, vector(1)?];
+                Some([0, 1, 2].map(|i| f(a[i], b[i])))
+            };
+
+            let value = match operation? {
+                "ADD" => per_component(|a, b| a + b)?,
+                "SUBTRACT" => per_component(|a, b| a - b)?,
+                "MULTIPLY" => per_component(|a, b| a * b)?,
+                "DIVIDE" => per_component(safe_divide)?,
+                "MULTIPLY_ADD" => {
+                    let [a, b, c] = [vector(0)?, vector(1)?, vector(2)?];
+                    [0, 1, 2].map(|i| a[i].mul_add(b[i], c[i]))
+                }
+                "NORMALIZE" => {
+                    let v = vector(0)?;
+                    let length = v.iter().map(|c| c * c).sum::<f32>().sqrt();
+                    if length > 0.0 {
+                        v.map(|c| c / length)
+                    } else {
+                        [0.0; 3]
+                    }
+                }
+                _ => return None,
+            };
+
+            Some(Value::Vec(value))
+        }
+        "ShaderNodeMixRGB" => {
+            let color =
+                |socket: &'static str| match node.socket_values.get(&NodeSocketId::Name(socket)) {
+                    Some(&Value::Color(c)) => Some(c),
+                    _ => None,
+                };
+
+            let fac = match node.socket_values.get(&NodeSocketId::Name("Fac")) {
+                Some(&Value::Float(v)) => v.clamp(0.0, 1.0),
+                _ => return None,
+            };
+            let a = color("Color1")?;
+            let b = color("Color2")?;
+
+            let blend = |f: fn(f32, f32) -> f32| {
+                let [r, g, b] = [0, 1, 2].map(|i| a[i] + (f(a[i], b[i]) - a[i]) * fac);
+                [r, g, b, a[3]]
+            };
+
+            let blend_type = match node.properties.get("blend_type") {
+                Some(&Value::Enum(blend_type)) => blend_type,
+                _ => "MIX",
+            };
+
+            let value = match blend_type {
+                "MIX" => blend(|_, b| b),
+                "ADD" => blend(|a, b| a + b),
+                "SUBTRACT" => blend(|a, b| a - b),
+                "MULTIPLY" => blend(|a, b| a * b),
+                _ => return None,
+            };
+
+            let clamp = matches!(node.properties.get("use_clamp"), Some(Value::Bool(true)));
+
+            Some(Value::Color(if clamp {
+                value.map(|c| c.clamp(0.0, 1.0))
+            } else {
+                value
+            }))
+        }
+        _ => None,
+    }
+}
+
+fn safe_divide(a: f32, b: f32) -> f32 {
+    if b == 0.0 {
+        0.0
+    } else {
+        a / b
+    }
+}
+
+/// Power like Blender computes it, which is 0 for negative bases with fractional exponents.
+fn safe_power(base: f32, exponent: f32) -> f32 {
+    if base >= 0.0 || exponent.fract() == 0.0 {
+        base.powf(exponent)
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::asset::material::{
+        definitions::{nodes, shaders},
+        nodes::{
+            NodeSocketId::{Name, Position},
+            NodeType,
+        },
+    };
+
+    fn node(
+        kind: &'static NodeType,
+        properties: &[(&'static str, Value)],
+        values: &[(NodeSocketId, Value)],
+        links: &[(NodeSocketId, usize)],
+    ) -> BuiltNode {
+        kind.build(
+            properties.iter().cloned().collect(),
+            values.iter().cloned().collect(),
+            links
+                .iter()
+                .map(|&(socket, node_index)| {
+                    (
+                        socket,
+                        BuiltNodeSocketRef {
+                            node_index,
+                            socket: Position(0),
+                        },
+                    )
+                })
+                .collect(),
+            [0.0, 0.0],
+        )
+    }
+
+    #[test]
+    fn fold_constant_chains() {
+        let nodes = vec![
+            // unused texture
+            node(&nodes::TEX_IMAGE, &[], &[], &[]),
+            node(
+                &nodes::MATH,
+                &[("operation", Value::Enum("MULTIPLY"))],
+                &[
+                    (Position(0), Value::Float(0.5)),
+                    (Position(1), Value::Float(0.5)),
+                ],
+                &[],
+            ),
+            node(
+                &nodes::MATH,
+                &[
+                    ("operation", Value::Enum("ADD")),
+                    ("use_clamp", Value::Bool(true)),
+                ],
+                &[(Position(1), Value::Float(1.0))],
+                &[(Position(0), 1)],
+            ),
+            // the displacement depends on the texture
+            node(&nodes::TEX_IMAGE, &[], &[], &[]),
+            node(
+                &shaders::PRINCIPLED,
+                &[],
+                &[],
+                &[(Name("Roughness"), 2), (Name("Base Color"), 1)],
+            ),
+        ];
+
+        let mut displacement = Some(BuiltNodeSocketRef {
+            node_index: 3,
+            socket: Name("Color"),
+        });
+
+        let optimized = optimize(nodes, [&mut displacement, &mut None]);
+
+        assert_eq!(optimized.len(), 2);
+        assert_eq!(displacement.unwrap().node_index, 0);
+
+        let shader = &optimized[1];
+        assert_eq!(shader.kind.blender_id, "ShaderNodeBsdfPrincipled");
+        assert!(shader.socket_links.is_empty());
+        assert!(matches!(
+            shader.socket_values[&Name("Roughness")],
+            Value::Float(v) if (v - 1.0).abs() < 1e-6
+        ));
+        assert!(matches!(
+            shader.socket_values[&Name("Base Color")],
+            Value::Color([r, _, _, a]) if (r - 0.25).abs() < 1e-6 && (a - 1.0).abs() < 1e-6
+        ));
+    }
+
+    #[test]
+    fn keep_unknown_inputs() {
+        let nodes = vec![
+            node(&nodes::TEX_IMAGE, &[], &[], &[]),
+            // the second input is unset, so its value is unknown
+            node(
+                &nodes::MATH,
+                &[("operation", Value::Enum("MULTIPLY"))],
+                &[(Position(0), Value::Float(0.5))],
+                &[],
+            ),
+            node(
+                &nodes::MIX_RGB,
+                &[("blend_type", Value::Enum("MULTIPLY"))],
+                &[(Name("Fac"), Value::Float(1.0))],
+                &[(Name("Color1"), 0), (Name("Color2"), 1)],
+            ),
+            node(&shaders::PRINCIPLED, &[], &[], &[(Name("Base Color"), 2)]),
+        ];
+
+        let optimized = optimize(nodes, [&mut None, &mut None]);
+
+        assert_eq!(optimized.len(), 4);
+        assert_eq!(optimized[2].socket_links.len(), 2);
+    }
+}
@@ -0,0 +1,346 @@
+//! Resolves the includes of `patch` materials, which can include other patch materials.
+//!
+//! The chain is resolved before the material is loaded, so include cycles and missing
+//! includes are reported with every material along the way instead of failing deep inside.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    game_path::material_name,
+    keyvalues::{self, KeyValue},
+};
+
+/// Parameters besides the `%` ones which only describe the material to tools.
+const METADATA_PARAMETERS: &[&str] = &["$surfaceprop", "$surfaceprop2"];
+
+/// Limits the include depth in case a cycle goes through differently spelled paths.
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchErrorKind {
+    Cycle,
+    TooDeep,
+    MissingInclude,
+    Read(String),
+    Parse(String),
+}
+
+/// An error resolving a patch material, with the chain of materials leading to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchError {
+    pub chain: Vec<String>,
+    pub kind: PatchErrorKind,
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, path) in self.chain.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" -> ")?;
+            }
+
+            write!(f, "`{path}`")?;
+        }
+
+        match &self.kind {
+            PatchErrorKind::Cycle => f.write_str(": include cycle"),
+            PatchErrorKind::TooDeep => f.write_str(": too many nested includes"),
+            PatchErrorKind::MissingInclude => f.write_str(": patch material has no include"),
+            PatchErrorKind::Read(error) => write!(f, ": could not be read: {error}"),
+            PatchErrorKind::Parse(error) => write!(f, ": could not be parsed: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// A material with its patches applied.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PatchedMaterial {
+    pub shader: String,
+    pub parameters: Vec<(String, KeyValue)>,
+    /// The patch materials followed by the material they finally include.
+    pub chain: Vec<String>,
+}
+
+impl PatchedMaterial {
+    /// Returns the editor metadata of the material, like `%keywords`, `%tooltexture`
+    /// and `$surfaceprop`. The keys are lowercase.
+    pub fn metadata(&self) -> BTreeMap<String, String> {
+        self.parameters
+            .iter()
+            .filter(|(k, _)| {
+                k.starts_with('%')
+                    || METADATA_PARAMETERS
+                        .iter()
+                        .any(|p| k.eq_ignore_ascii_case(p))
+            })
+            .filter_map(|(k, v)| Some((k.to_ascii_lowercase(), v.as_value()?.to_owned())))
+            .collect()
+    }
+
+    /// Returns the parameters which have a value, with the patches applied.
+    /// Blocks like `Proxies` are skipped.
+    pub fn values(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.parameters
+            .iter()
+            .filter_map(|(k, v)| Some((k.as_str(), v.as_value()?)))
+    }
+
+    /// Returns the path of the material the patches finally include, as written in the
+    /// `include` parameter. Relative paths of the material are resolved from it.
+    pub fn included(&self) -> &str {
+        self.chain.last().map_or("", String::as_str)
+    }
+
+    /// Returns the game path of the `$basetexture` of the material, if it has one.
+    pub fn base_texture(&self) -> Option<String> {
+        let texture = self
+            .parameters
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case("$basetexture"))
+            .find_map(|(_, v)| v.as_value())?;
+        let texture = material_name(texture, ".vtf");
+
+        (!texture.is_empty()).then(|| format!("materials/{texture}.vtf"))
+    }
+}
+
+/// Resolves a material, following patch includes recursively.
+/// `read` returns the text of a material path, as written in the `include` parameter.
+///
+/// # Errors
+///
+/// Returns `Err` if a material of the chain can't be read or parsed,
+/// or if the includes form a cycle.
+pub fn resolve(
+    path: &str,
+    mut read: impl FnMut(&str) -> Result<String, String>,
+) -> Result<PatchedMaterial, PatchError> {
+    let mut chain = Vec::new();
+    resolve_recursive(path, &mut read, &mut chain)
+}
+
+fn resolve_recursive(
+    path: &str,
+    read: &mut impl FnMut(&str) -> Result<String, String>,
+    chain: &mut Vec<String>,
+) -> Result<PatchedMaterial, PatchError> {
+    let normalized = normalize(path);
+    let is_cycle = chain.iter().any(|p| normalize(p) == normalized);
+
+    chain.push(path.to_owned());
+
+    let error = |chain: &[String], kind| PatchError {
+        chain: chain.to_vec(),
+        kind,
+    };
+
+    if is_cycle {
+        return Err(error(chain, PatchErrorKind::Cycle));
+    }
+
+    if chain.len() > MAX_DEPTH {
+        return Err(error(chain, PatchErrorKind::TooDeep));
+    }
+
+    let text = read(path).map_err(|e| error(chain, PatchErrorKind::Read(e)))?;
+    // include paths are often written with backslashes, which must not be read as escapes
+    let root = keyvalues::parse_raw(&text)
+        .map_err(|e| error(chain, PatchErrorKind::Parse(e.to_string())))?;
+
+    let Some((shader, KeyValue::Block(parameters))) = root.into_iter().next() else {
+        return Err(error(
+            chain,
+            PatchErrorKind::Parse("material has no shader block".to_owned()),
+        ));
+    };
+
+    if !shader.eq_ignore_ascii_case("patch") {
+        return Ok(PatchedMaterial {
+            shader,
+            parameters,
+            chain: chain.clone(),
+        });
+    }
+
+    let include = find(&parameters, "include")
+        .and_then(KeyValue::as_value)
+        .ok_or_else(|| error(chain, PatchErrorKind::MissingInclude))?
+        .to_owned();
+
+    let mut included = resolve_recursive(&include, read, chain)?;
+
+    // `insert` adds or overwrites parameters, `replace` only overwrites existing ones
+    if let Some(insert) = find(&parameters, "insert").and_then(KeyValue::as_block) {
+        for (key, value) in insert {
+            set(&mut included.parameters, key, value.clone(), true);
+        }
+    }
+
+    if let Some(replace) = find(&parameters, "replace").and_then(KeyValue::as_block) {
+        for (key, value) in replace {
+            set(&mut included.parameters, key, value.clone(), false);
+        }
+    }
+
+    Ok(included)
+}
+
+fn normalize(path: &str) -> String {
+    material_name(path, ".vmt")
+}
+
+fn find<'a>(parameters: &'a [(String, KeyValue)], key: &str) -> Option<&'a KeyValue> {
+    parameters
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, v)| v)
+}
+
+fn set(parameters: &mut Vec<(String, KeyValue)>, key: &str, value: KeyValue, insert: bool) {
+    if let Some((_, existing)) = parameters
+        .iter_mut()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+    {
+        *existing = value;
+    } else if insert {
+        parameters.push((key.to_owned(), value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reader<'a>(
+        files: &'a [(&'a str, &'a str)],
+    ) -> impl FnMut(&str) -> Result<String, String> + 'a {
+        move |path| {
+            files
+                .iter()
+                .find(|(p, _)| normalize(p) == normalize(path))
+                .map(|(_, text)| (*text).to_owned())
+                .ok_or_else(|| "file not found".to_owned())
+        }
+    }
+
+    #[test]
+    fn resolve_nested_patches() {
+        let files = [
+            (
+                "materials/a.vmt",
+                r#"patch { include "materials/b.vmt" replace { $basetexture "a" $missing 1 } }"#,
+            ),
+            (
+                "materials/b.vmt",
+                r#"patch { include "materials/c.vmt" insert { $bumpmap "b" } }"#,
+            ),
+            (
+                "materials/c.vmt",
+                r#"LightmappedGeneric { $basetexture "c" }"#,
+            ),
+        ];
+
+        let patched = resolve("materials/a.vmt", reader(&files)).unwrap();
+
+        assert_eq!(patched.shader, "LightmappedGeneric");
+        assert_eq!(
+            patched.parameters,
+            vec![
+                ("$basetexture".to_owned(), KeyValue::Value("a".to_owned())),
+                ("$bumpmap".to_owned(), KeyValue::Value("b".to_owned())),
+            ]
+        );
+        assert_eq!(
+            patched.chain,
+            vec!["materials/a.vmt", "materials/b.vmt", "materials/c.vmt"]
+        );
+        assert_eq!(patched.included(), "materials/c.vmt");
+        assert_eq!(
+            patched.values().collect::<Vec<_>>(),
+            vec![("$basetexture", "a"), ("$bumpmap", "b")]
+        );
+    }
+
+    #[test]
+    fn resolve_backslash_include() {
+        let files = [
+            (
+                "materials/a.vmt",
+                r#"patch { include "materials\nature\b.vmt" }"#,
+            ),
+            (
+                "materials/nature/b.vmt",
+                r#"LightmappedGeneric { $basetexture "nature\b" }"#,
+            ),
+        ];
+
+        let patched = resolve("materials/a.vmt", reader(&files)).unwrap();
+
+        assert_eq!(patched.chain[1], r"materials\nature\b.vmt");
+        assert_eq!(
+            patched.parameters,
+            vec![(
+                "$basetexture".to_owned(),
+                KeyValue::Value(r"nature\b".to_owned())
+            )]
+        );
+    }
+
+    #[test]
+    fn read_metadata() {
+        let files = [
+            (
+                "materials/a.vmt",
+                r#"patch { include "materials/b.vmt" insert { %Keywords "wood,floor" } }"#,
+            ),
+            (
+                "materials/b.vmt",
+                r#"LightmappedGeneric { $basetexture "b" $SurfaceProp "wood" %tooltexture "b_tool" %compilewater 0 Proxies { } }"#,
+            ),
+        ];
+
+        let patched = resolve("materials/a.vmt", reader(&files)).unwrap();
+        assert_eq!(patched.base_texture().as_deref(), Some("materials/b.vtf"));
+
+        let metadata = patched.metadata();
+
+        assert_eq!(
+            metadata.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("$surfaceprop".to_owned(), "wood".to_owned()),
+                ("%compilewater".to_owned(), "0".to_owned()),
+                ("%keywords".to_owned(), "wood,floor".to_owned()),
+                ("%tooltexture".to_owned(), "b_tool".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn detect_include_cycles() {
+        let files = [
+            ("materials/a.vmt", r#"patch { include "materials/B" }"#),
+            ("materials/b.vmt", r#"patch { include "materials/a.vmt" }"#),
+        ];
+
+        let error = resolve("materials/a.vmt", reader(&files)).unwrap_err();
+
+        assert_eq!(error.kind, PatchErrorKind::Cycle);
+        assert_eq!(
+            error.to_string(),
+            "`materials/a.vmt` -> `materials/B` -> `materials/a.vmt`: include cycle"
+        );
+
+        let files = [("materials/a.vmt", r#"patch { include "materials/c.vmt" }"#)];
+        let error = resolve("materials/a.vmt", reader(&files)).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "`materials/a.vmt` -> `materials/c.vmt`: could not be read: file not found"
+        );
+    }
+}
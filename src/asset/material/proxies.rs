@@ -0,0 +1,152 @@
+//! Reads the material proxies which animate texture transforms, which the material
+//! parser doesn't keep, so that the animation can be played back in Blender.
+//!
+//! Only proxies animating at a constant rate are supported: `TextureScroll`, and
+//! `LinearRamp` results used as the rotation of a `TextureTransform`.
+
+use std::collections::BTreeMap;
+
+use glam::Vec2;
+
+use crate::keyvalues::KeyValue;
+
+/// Constant change of a texture transform.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TransformAnimation {
+    /// Change of the translation per second.
+    pub translate_rate: Vec2,
+    /// Change of the rotation per second, in degrees.
+    pub rotate_rate: f32,
+}
+
+/// Animations of the texture transform parameters of a material.
+#[derive(Debug, Clone, Default)]
+pub struct TransformAnimations(BTreeMap<String, TransformAnimation>);
+
+impl TransformAnimations {
+    pub fn from_parameters(parameters: &[(String, KeyValue)]) -> Self {
+        let proxies: Vec<(&str, &[(String, KeyValue)])> = parameters
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case("proxies"))
+            .filter_map(|(_, v)| v.as_block())
+            .flatten()
+            .filter_map(|(name, v)| Some((name.as_str(), v.as_block()?)))
+            .collect();
+
+        let mut animations = BTreeMap::<String, TransformAnimation>::new();
+
+        for &(name, proxy) in &proxies {
+            if name.eq_ignore_ascii_case("texturescroll") {
+                let Some(var) = value(proxy, "texturescrollvar") else {
+                    continue;
+                };
+
+                let rate = float(proxy, "texturescrollrate").unwrap_or(1.0);
+                let angle = float(proxy, "texturescrollangle")
+                    .unwrap_or(0.0)
+                    .to_radians();
+
+                animations
+                    .entry(var.to_ascii_lowercase())
+                    .or_default()
+                    .translate_rate = Vec2::new(angle.cos(), angle.sin()) * rate;
+            } else if name.eq_ignore_ascii_case("texturetransform") {
+                let (Some(var), Some(rotate_var)) =
+                    (value(proxy, "resultvar"), value(proxy, "rotatevar"))
+                else {
+                    continue;
+                };
+
+                let ramp_rate = proxies.iter().find_map(|&(name, ramp)| {
+                    (name.eq_ignore_ascii_case("linearramp")
+                        && value(ramp, "resultvar")
+                            .is_some_and(|r| r.eq_ignore_ascii_case(rotate_var)))
+                    .then(|| float(ramp, "rate"))
+                    .flatten()
+                });
+
+                if let Some(rate) = ramp_rate {
+                    animations
+                        .entry(var.to_ascii_lowercase())
+                        .or_default()
+                        .rotate_rate = rate;
+                }
+            }
+        }
+
+        Self(animations)
+    }
+
+    /// Returns the animation of a transform parameter, like `$basetexturetransform`.
+    pub fn get(&self, transform_parameter: &str) -> Option<TransformAnimation> {
+        self.0
+            .get(&transform_parameter.to_ascii_lowercase())
+            .copied()
+    }
+}
+
+fn value<'a>(block: &'a [(String, KeyValue)], key: &str) -> Option<&'a str> {
+    block
+        .iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case(key))
+        .find_map(|(_, v)| v.as_value())
+        .map(str::trim)
+}
+
+fn float(block: &[(String, KeyValue)], key: &str) -> Option<f32> {
+    value(block, key).and_then(|v| v.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::keyvalues;
+
+    #[test]
+    fn read_transform_animations() {
+        let vmt = keyvalues::parse_raw(
+            r#"
+            LightmappedGeneric
+            {
+                $basetexture "water/scroll"
+                $angle 0
+                Proxies
+                {
+                    TextureScroll
+                    {
+                        texturescrollvar $BaseTextureTransform
+                        texturescrollrate 0.5
+                        texturescrollangle 90
+                    }
+                    LinearRamp
+                    {
+                        rate 45
+                        initialValue 0
+                        resultVar $angle
+                    }
+                    TextureTransform
+                    {
+                        rotateVar $angle
+                        resultVar $detailtexturetransform
+                    }
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+        let parameters = vmt[0].1.as_block().unwrap();
+        let animations = TransformAnimations::from_parameters(parameters);
+
+        let scroll = animations.get("$basetexturetransform").unwrap();
+        assert!(scroll.translate_rate.abs_diff_eq(Vec2::new(0.0, 0.5), 1e-6));
+        assert!(scroll.rotate_rate.abs() < 1e-6);
+
+        let rotation = animations.get("$DetailTextureTransform").unwrap();
+        assert_eq!(rotation.translate_rate, Vec2::ZERO);
+        assert!((rotation.rotate_rate - 45.0).abs() < 1e-6);
+
+        assert_eq!(animations.get("$bumptransform"), None);
+    }
+}
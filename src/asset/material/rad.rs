@@ -0,0 +1,139 @@
+//! Reads texture lights from `lights.rad` and `lights_custom.rad`, which the compiler
+//! uses to make surfaces with the listed materials emit light.
+
+use std::collections::BTreeMap;
+
+use plumber_core::fs::{GamePathBuf, OpenFileSystem};
+
+use crate::{asset::utils::srgb_to_linear, game_path::material_name};
+
+/// Game-wide light definitions, in load order. Custom definitions override the
+/// shipped ones.
+pub const RAD_PATHS: [&str; 2] = ["lights.rad", "lights_custom.rad"];
+
+/// Lines which set other options than texture lights.
+const OPTION_PREFIXES: [&str; 2] = ["noshadow", "forcetextureshadow"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TexLight {
+    /// Linear color of the emitted light.
+    pub color: [f32; 3],
+    pub brightness: f32,
+}
+
+/// Texture lights by their normalized material path.
+#[derive(Debug, Clone, Default)]
+pub struct TexLights(BTreeMap<String, TexLight>);
+
+impl TexLights {
+    /// Reads the texture lights of the file system. Not every game ships the files,
+    /// so missing ones are skipped.
+    pub fn read(file_system: &OpenFileSystem) -> Self {
+        let mut texlights = Self::default();
+
+        for path in RAD_PATHS {
+            if let Ok(data) = file_system.read(&GamePathBuf::from(path).into()) {
+                texlights.extend_from(&String::from_utf8_lossy(&data));
+            }
+        }
+
+        texlights
+    }
+
+    /// Adds the texture lights of a `.rad` file. Later definitions override earlier
+    /// ones, as in the compiler.
+    pub fn extend_from(&mut self, text: &str) {
+        self.0.extend(text.lines().filter_map(parse_line));
+    }
+
+    pub fn get(&self, material: &str) -> Option<TexLight> {
+        self.0.get(&normalize(material)).copied()
+    }
+
+    pub fn into_inner(self) -> BTreeMap<String, TexLight> {
+        self.0
+    }
+}
+
+fn parse_line(line: &str) -> Option<(String, TexLight)> {
+    let line = line.split("//").next().unwrap_or_default();
+    let mut tokens = line.split_ascii_whitespace();
+
+    let name = tokens.next()?;
+    if OPTION_PREFIXES.iter().any(|o| name.eq_ignore_ascii_case(o)) {
+        return None;
+    }
+
+    // hdr values may follow the ldr values, but aren't needed
+    let values: Vec<f32> = tokens.take(4).map_while(|t| t.parse().ok()).collect();
+
+    let (rgb, brightness) = match values[..] {
+        [r, g, b, brightness] => ([r, g, b], brightness),
+        // old files have the brightness baked into the color
+        [r, g, b] if r.max(g).max(b) > 0.0 => {
+            let max = r.max(g).max(b);
+            ([r, g, b].map(|c| c / max * 255.0), max)
+        }
+        _ => return None,
+    };
+
+    let texlight = TexLight {
+        color: rgb.map(|c| srgb_to_linear((c / 255.0).clamp(0.0, 1.0))),
+        brightness,
+    };
+
+    Some((normalize(name), texlight))
+}
+
+fn normalize(material: &str) -> String {
+    material_name(material, ".vmt")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_texture_lights() {
+        let text = "
+            // comment
+            noshadow lights/fluorescentcool001a
+            lights/fluorescentcool001a 255 255 255 200
+            LIGHTS\\WHITE001 510 0 0
+            lights/fluorescentcool001a 255 0 0 400 // override
+        ";
+
+        let mut texlights = TexLights::default();
+        texlights.extend_from(text);
+
+        let light = texlights
+            .get("materials/lights/fluorescentcool001a.vmt")
+            .unwrap();
+        assert_eq!(
+            light.color.map(f32::to_bits),
+            [1.0, 0.0, 0.0].map(f32::to_bits)
+        );
+        assert!((light.brightness - 400.0).abs() < f32::EPSILON);
+
+        let light = texlights.get("lights/white001").unwrap();
+        assert_eq!(
+            light.color.map(f32::to_bits),
+            [1.0, 0.0, 0.0].map(f32::to_bits)
+        );
+        assert!((light.brightness - 510.0).abs() < f32::EPSILON);
+
+        assert_eq!(texlights.get("lights/missing"), None);
+    }
+
+    #[test]
+    fn override_custom_texture_lights() {
+        let mut texlights = TexLights::default();
+        texlights.extend_from("lights/white001 255 255 255 200\nlights/white002 0 0 0 0");
+        texlights.extend_from("lights/white001 255 255 255 50");
+
+        let light = texlights.get("lights/white001").unwrap();
+        assert!((light.brightness - 50.0).abs() < f32::EPSILON);
+        assert!(texlights.get("lights/white002").is_some());
+        assert_eq!(texlights.into_inner().len(), 2);
+    }
+}
@@ -0,0 +1,468 @@
+//! Serializes built materials into Blender Python scripts, which recreate the material
+//! like the importer does, using its helpers. The scripts can be run in other files
+//! where the addon is enabled, or read to see what the importer does.
+
+use std::fmt::{self, Write};
+
+use super::{
+    builder_base::BuiltMaterialData,
+    nodes::{BuiltNode, BuiltNodeGroup, NodeSocketId, Value},
+};
+
+/// Writes a script which creates the material `name`, replacing its nodes if it exists.
+/// Textures are expected to be imported already, with the extension `texture_ext`.
+/// The helpers are imported from the material module of the addon, `helpers`.
+pub(crate) fn python_script(
+    name: &str,
+    path_id: &str,
+    data: &BuiltMaterialData,
+    texture_ext: &str,
+    helpers: &str,
+) -> String {
+    let mut script = String::new();
+
+    write_imports(&mut script, helpers)
+        .and_then(|()| write_script(&mut script, name, path_id, data, texture_ext))
+        .expect("writing to a string should not fail");
+
+    script
+}
+
+fn write_imports(out: &mut String, helpers: &str) -> fmt::Result {
+    writeln!(out, "import bpy")?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "from {helpers} import animate, find_image, new_group_socket"
+    )
+}
+
+fn write_script(
+    out: &mut String,
+    name: &str,
+    path_id: &str,
+    data: &BuiltMaterialData,
+    texture_ext: &str,
+) -> fmt::Result {
+    for (group_name, group) in &data.node_groups {
+        write_node_group(out, group_name, group)?;
+    }
+
+    let name = py_str(name);
+    writeln!(out)?;
+    writeln!(
+        out,
+        "material = bpy.data.materials.get({name}) or bpy.data.materials.new({name})"
+    )?;
+    writeln!(out, "material[\"path_id\"] = {}", py_str(path_id))?;
+    writeln!(out, "material.use_nodes = True")?;
+    writeln!(out, "nt = material.node_tree")?;
+    writeln!(out, "nt.nodes.clear()")?;
+    writeln!(out, "nt.animation_data_clear()")?;
+    writeln!(out)?;
+    writeln!(out, "out = nt.nodes.new(\"ShaderNodeOutputMaterial\")")?;
+    writeln!(out, "out.location = (300, 0)")?;
+
+    for (property, value) in &data.properties {
+        let value = py_value(value, texture_ext);
+        writeln!(out, "setattr(material, {}, {value})", py_str(property))?;
+    }
+
+    writeln!(out)?;
+    write_nodes(out, "", "n", &data.nodes, texture_ext)?;
+
+    if let Some(shader) = data.nodes.len().checked_sub(1) {
+        writeln!(
+            out,
+            "nt.links.new(n{shader}.outputs[\"BSDF\"], out.inputs[\"Surface\"])"
+        )?;
+    }
+
+    if let Some(displacement) = data.displacement {
+        writeln!(
+            out,
+            "nt.links.new(n{}.outputs[{}], out.inputs[\"Displacement\"])",
+            displacement.node_index,
+            py_socket(displacement.socket)
+        )?;
+        writeln!(out, "if hasattr(material, \"displacement_method\"):")?;
+        writeln!(out, "    material.displacement_method = \"BOTH\"")?;
+        writeln!(out, "else:")?;
+        writeln!(out, "    material.cycles.displacement_method = \"BOTH\"")?;
+    }
+
+    if let Some(volume) = data.volume {
+        writeln!(
+            out,
+            "nt.links.new(n{}.outputs[{}], out.inputs[\"Volume\"])",
+            volume.node_index,
+            py_socket(volume.socket)
+        )?;
+    }
+
+    for (property, value) in &data.custom_properties {
+        let value = py_value(value, texture_ext);
+        writeln!(out, "material[{}] = {value}", py_str(property))?;
+    }
+
+    for (texture, color_space) in &data.texture_color_spaces {
+        writeln!(
+            out,
+            "image = find_image({})",
+            py_str(&format!("{texture}{texture_ext}"))
+        )?;
+        writeln!(out, "if image is not None:")?;
+        writeln!(
+            out,
+            "    image.colorspace_settings.name = {}",
+            py_str(color_space.to_str())
+        )?;
+        // the alpha policies of materials are applied in their nodes
+        writeln!(out, "    image.alpha_mode = \"CHANNEL_PACKED\"")?;
+    }
+
+    Ok(())
+}
+
+fn write_node_group(out: &mut String, name: &str, group: &BuiltNodeGroup) -> fmt::Result {
+    let name = py_str(name);
+    writeln!(out)?;
+    writeln!(out, "if bpy.data.node_groups.get({name}) is None:")?;
+    writeln!(
+        out,
+        "    nt = bpy.data.node_groups.new({name}, \"ShaderNodeTree\")"
+    )?;
+    write_nodes(out, "    ", "g", &group.nodes, "")?;
+
+    let mut created = Vec::new();
+
+    for (input, target) in &group.inputs {
+        if !created.contains(input) {
+            writeln!(
+                out,
+                "    new_group_socket(nt, {}, \"INPUT\", g{}.inputs[{}])",
+                py_str(input),
+                target.node_index,
+                py_socket(target.socket)
+            )?;
+            created.push(input);
+        }
+    }
+
+    for (output, source) in &group.outputs {
+        writeln!(
+            out,
+            "    new_group_socket(nt, {}, \"OUTPUT\", g{}.outputs[{}])",
+            py_str(output),
+            source.node_index,
+            py_socket(source.socket)
+        )?;
+    }
+
+    writeln!(out, "    group_in = nt.nodes.new(\"NodeGroupInput\")")?;
+    writeln!(out, "    group_in.location = (-200, 0)")?;
+    writeln!(out, "    group_out = nt.nodes.new(\"NodeGroupOutput\")")?;
+    writeln!(
+        out,
+        "    group_out.location = (max(n.location[0] + n.width for n in nt.nodes) + 50, 0)"
+    )?;
+
+    for (input, target) in &group.inputs {
+        writeln!(
+            out,
+            "    nt.links.new(group_in.outputs[{}], g{}.inputs[{}])",
+            py_str(input),
+            target.node_index,
+            py_socket(target.socket)
+        )?;
+    }
+
+    for (output, source) in &group.outputs {
+        writeln!(
+            out,
+            "    nt.links.new(g{}.outputs[{}], group_out.inputs[{}])",
+            source.node_index,
+            py_socket(source.socket),
+            py_str(output)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Writes the nodes into the node tree `nt`, naming them by `prefix` and their index.
+fn write_nodes(
+    out: &mut String,
+    indent: &str,
+    prefix: &str,
+    nodes: &[BuiltNode],
+    texture_ext: &str,
+) -> fmt::Result {
+    for (i, node) in nodes.iter().enumerate() {
+        let var = format!("{prefix}{i}");
+        let [x, y] = node.position;
+
+        writeln!(
+            out,
+            "{indent}{var} = nt.nodes.new({})",
+            py_str(node.kind.blender_id)
+        )?;
+        writeln!(out, "{indent}{var}.location = ({x:?}, {y:?})")?;
+
+        for (property, value) in &node.properties {
+            let value = py_value(value, texture_ext);
+            writeln!(out, "{indent}{var}.{property} = {value}")?;
+        }
+
+        for (&socket, value) in &node.socket_values {
+            let socket = format!("{var}.inputs[{}]", py_socket(socket));
+
+            if let Value::AnimatedVec(value, rate) = value {
+                writeln!(
+                    out,
+                    "{indent}animate({socket}, {}, {})",
+                    py_floats(value),
+                    py_floats(rate)
+                )?;
+            } else {
+                let value = py_value(value, texture_ext);
+                writeln!(out, "{indent}{socket}.default_value = {value}")?;
+            }
+        }
+
+        for (&socket, link) in &node.socket_links {
+            writeln!(
+                out,
+                "{indent}nt.links.new({var}.inputs[{}], {prefix}{}.outputs[{}])",
+                py_socket(socket),
+                link.node_index,
+                py_socket(link.socket)
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn py_value(value: &Value, texture_ext: &str) -> String {
+    match value {
+        Value::Bool(true) => "True".to_owned(),
+        Value::Bool(false) => "False".to_owned(),
+        Value::Float(f) => py_float(*f),
+        Value::Color(c) => py_floats(c),
+        Value::Vec(v) | Value::AnimatedVec(v, _) => py_floats(v),
+        Value::Enum(e) => py_str(e),
+        Value::Texture(path) => format!("find_image({})", py_str(&format!("{path}{texture_ext}"))),
+        Value::NodeTree(group) => format!(
+            "bpy.data.node_groups.get({})",
+            py_str(group.shared.as_ref().map_or("", |s| s.name))
+        ),
+    }
+}
+
+fn py_socket(socket: NodeSocketId) -> String {
+    match socket {
+        NodeSocketId::Position(p) => p.to_string(),
+        NodeSocketId::Name(n) => py_str(n),
+    }
+}
+
+fn py_float(f: f32) -> String {
+    if f.is_finite() {
+        format!("{f:?}")
+    } else {
+        format!("float(\"{f}\")")
+    }
+}
+
+fn py_floats(floats: &[f32]) -> String {
+    let floats: Vec<String> = floats.iter().copied().map(py_float).collect();
+    format!("({})", floats.join(", "))
+}
+
+/// Quotes a string as a Python literal.
+fn py_str(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", u32::from(c));
+            }
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process::Command};
+
+    use plumber_core::fs::GamePathBuf;
+
+    use super::*;
+    use crate::asset::material::{
+        builder_base::MaterialBuilder,
+        definitions::{groups, shaders},
+    };
+
+    /// The parts of `bpy` which the scripts use, recording the created nodes and links.
+    const STUB_BPY: &str = r#"
+from types import SimpleNamespace
+
+
+class Socket:
+    default_value = None
+
+
+class Sockets(dict):
+    def __missing__(self, key):
+        self[key] = Socket()
+        return self[key]
+
+
+class Node:
+    width = 140.0
+
+    def __init__(self, bl_idname):
+        self.bl_idname = bl_idname
+        self.location = (0.0, 0.0)
+        self.inputs = Sockets()
+        self.outputs = Sockets()
+
+
+class Nodes(list):
+    def new(self, bl_idname):
+        self.append(Node(bl_idname))
+        return self[-1]
+
+
+class Links(list):
+    def new(self, a, b):
+        self.append((a, b))
+
+
+class NodeTree:
+    def __init__(self, name="", kind="ShaderNodeTree"):
+        self.nodes = Nodes()
+        self.links = Links()
+
+    def animation_data_clear(self):
+        pass
+
+
+class Material(dict):
+    def __init__(self, name):
+        self.name = name
+        self.node_tree = NodeTree()
+        self.cycles = SimpleNamespace()
+
+
+class Collection(dict):
+    def __init__(self, kind):
+        self.kind = kind
+
+    def new(self, name, *args):
+        self[name] = self.kind(name, *args)
+        return self[name]
+
+
+data = SimpleNamespace(
+    materials=Collection(Material),
+    node_groups=Collection(NodeTree),
+    images=Collection(None),
+)
+context = SimpleNamespace(
+    scene=SimpleNamespace(render=SimpleNamespace(fps=24, fps_base=1.0))
+)
+"#;
+
+    const STUB_HELPERS: &str = r#"
+import bpy
+
+
+def animate(socket, value, rate):
+    socket.default_value = value
+
+
+def find_image(name):
+    return bpy.data.images.get(name)
+
+
+def new_group_socket(nt, name, in_out, socket):
+    pass
+"#;
+
+    #[test]
+    fn run_generated_script() {
+        let mut builder = MaterialBuilder::new(&shaders::PRINCIPLED);
+        builder
+            .input("$basetexture")
+            .push(&groups::TEXTURE)
+            .property(
+                &groups::TEXTURE,
+                "image",
+                Value::Texture(GamePathBuf::from("materials/brick/brickwall001")),
+            );
+        builder.output("Base Color", "$basetexture", "color");
+        builder.output("Alpha", "$basetexture", "alpha");
+        builder.unpremultiply_alpha("$basetexture");
+        builder.property("blend_method", Value::Enum("HASHED"));
+        builder.socket_value("Emission", Value::AnimatedVec([0.0; 3], [0.0, 1.0, 0.0]));
+
+        let data = builder.build();
+        let mut script = python_script(
+            "brick/brickwall001",
+            "materials/brick/brickwall001.vmt",
+            &data,
+            ".tga",
+            "helpers",
+        );
+
+        // the material output is created in addition to the built nodes
+        writeln!(
+            script,
+            "assert len(material.node_tree.nodes) == {}",
+            data.nodes.len() + 1
+        )
+        .unwrap();
+        writeln!(script, "assert material.blend_method == \"HASHED\"").unwrap();
+
+        let dir = env::temp_dir().join(format!("plumber_script_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("bpy.py"), STUB_BPY).unwrap();
+        fs::write(dir.join("helpers.py"), STUB_HELPERS).unwrap();
+        fs::write(dir.join("material.py"), &script).unwrap();
+
+        let python = env::var_os("PYO3_PYTHON").unwrap_or_else(|| "python3".into());
+        let output = Command::new(python)
+            .arg("material.py")
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(
+            output.status.success(),
+            "{}\n{script}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    #[test]
+    fn quote_python_literals() {
+        assert_eq!(py_str("a\"b\\c"), r#""a\"b\\c""#);
+        assert_eq!(py_float(1.0), "1.0");
+        assert_eq!(py_float(f32::INFINITY), "float(\"inf\")");
+        assert_eq!(py_floats(&[0.5, 1.0]), "(0.5, 1.0)");
+        assert_eq!(py_socket(NodeSocketId::Position(2)), "2");
+    }
+}
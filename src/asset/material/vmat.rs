@@ -0,0 +1,173 @@
+//! Builds materials of Source 2 material sources, approximating the standard complex
+//! shaders with the principled shader.
+
+use plumber_core::fs::GamePathBuf;
+
+use crate::source2::vmat::{TextureRole, Vmat};
+
+use super::{
+    builder::{Settings, Transparency},
+    builder_base::{ColorSpace, MaterialBuilder},
+    definitions::groups,
+    nodes::Value,
+    BuiltMaterialData,
+};
+
+/// Alpha test reference of the standard shaders.
+const DEFAULT_ALPHA_TEST_REFERENCE: f32 = 0.5;
+
+/// Builds the material of a `.vmat`. `texture` gives the name of each texture of the
+/// material which could be loaded.
+pub fn build_vmat_material(
+    vmat: &Vmat,
+    settings: Settings,
+    texture: impl Fn(TextureRole) -> Option<GamePathBuf>,
+) -> BuiltMaterialData {
+    let (shader, socket_map) = settings.target_version.principled_shader();
+
+    let mut builder = MaterialBuilder::new(shader);
+    builder.socket_map(socket_map);
+
+    let tint = vmat
+        .vector("g_vColorTint")
+        .map(|[r, g, b, _]| [r, g, b, 1.0])
+        .filter(|&tint| tint != [1.0; 4]);
+
+    let has_color = texture_input(
+        &mut builder,
+        "g_tColor",
+        texture(TextureRole::Color),
+        ColorSpace::Srgb,
+        settings,
+    );
+
+    if has_color {
+        let output = builder.output("Base Color", "g_tColor", "color");
+
+        if let Some(tint) = tint {
+            output
+                .push(&groups::COLOR_TEXTURE)
+                .link_input(&groups::COLOR_TEXTURE, "color")
+                .link(&groups::COLOR_TEXTURE, "mixin", Value::Color(tint))
+                .link(&groups::COLOR_TEXTURE, "fac", Value::Float(1.0));
+        }
+    } else if let Some(tint) = tint {
+        builder.socket_value("Base Color", Value::Color(tint));
+    }
+
+    if texture_input(
+        &mut builder,
+        "g_tMetalness",
+        texture(TextureRole::Metalness),
+        ColorSpace::NonColor,
+        settings,
+    ) {
+        builder.output("Metallic", "g_tMetalness", "color");
+    } else if let Some(metalness) = vmat.float("g_flMetalness") {
+        builder.socket_value("Metallic", Value::Float(metalness));
+    }
+
+    if texture_input(
+        &mut builder,
+        "g_tRoughness",
+        texture(TextureRole::Roughness),
+        ColorSpace::NonColor,
+        settings,
+    ) {
+        builder.output("Roughness", "g_tRoughness", "color");
+    }
+
+    if texture_input(
+        &mut builder,
+        "g_tNormal",
+        texture(TextureRole::Normal),
+        ColorSpace::NonColor,
+        settings,
+    ) {
+        builder
+            .output("Normal", "g_tNormal", "color")
+            .push(&groups::NORMAL_MAP)
+            .link_input(&groups::NORMAL_MAP, "image")
+            .link(&groups::NORMAL_MAP, "strength", Value::Float(1.0));
+    }
+
+    let alpha_test = vmat.flag("F_ALPHA_TEST");
+
+    if has_color && (alpha_test || vmat.flag("F_TRANSLUCENT")) {
+        let transparency = if alpha_test {
+            Transparency::Clip
+        } else {
+            Transparency::Blend
+        };
+
+        for (property, value) in settings
+            .target_version
+            .transparency_properties(transparency)
+        {
+            builder.property(property, value);
+        }
+
+        let output = builder.output("Alpha", "g_tColor", "alpha");
+
+        if alpha_test {
+            let reference = vmat
+                .float("g_flAlphaTestReference")
+                .unwrap_or(DEFAULT_ALPHA_TEST_REFERENCE);
+
+            output
+                .push(&groups::CLIP_ALPHA)
+                .link_input(&groups::CLIP_ALPHA, "value")
+                .link(&groups::CLIP_ALPHA, "ref", Value::Float(reference));
+        }
+    }
+
+    let cull = settings.allow_culling && !vmat.flag("F_RENDER_BACKFACES");
+    builder.property("use_backface_culling", Value::Bool(cull));
+
+    builder.build()
+}
+
+fn texture_input(
+    builder: &mut MaterialBuilder,
+    input: &'static str,
+    path: Option<GamePathBuf>,
+    color_space: ColorSpace,
+    settings: Settings,
+) -> bool {
+    let Some(path) = path else {
+        return false;
+    };
+
+    builder
+        .texture_color_spaces
+        .insert(path.clone().into_string(), color_space);
+
+    builder
+        .input(input)
+        .pipeline(vec![&groups::TEXTURE])
+        .property(&groups::TEXTURE, "image", Value::Texture(path))
+        .property(
+            &groups::TEXTURE,
+            "interpolation",
+            Value::Enum(settings.texture_interpolation.to_str()),
+        );
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_vmat_material_no_panic() {
+        let vmat = Vmat::parse(
+            r#""Layer0" { "shader" "vr_complex.vfx" "F_ALPHA_TEST" "1" "g_vColorTint" "[1 0 0 0]" }"#,
+        )
+        .unwrap();
+
+        build_vmat_material(&vmat, Settings::default(), |role| {
+            (role == TextureRole::Color).then(|| GamePathBuf::from("materials/dev/grid_color"))
+        });
+    }
+}
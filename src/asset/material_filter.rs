@@ -0,0 +1,73 @@
+//! Filters brush faces by their material, using glob patterns like `tools/*`.
+
+use crate::{game_path::material_name, glob::Glob};
+
+/// Material patterns of brush faces to include or exclude.
+/// Exclusions win, and an empty include list includes everything.
+#[derive(Debug, Clone, Default)]
+pub struct MaterialFilter {
+    include: Vec<Glob>,
+    exclude: Vec<Glob>,
+}
+
+impl MaterialFilter {
+    pub fn new(include: Vec<String>, exclude: Vec<String>) -> Self {
+        let normalize_all = |patterns: Vec<String>| {
+            patterns
+                .iter()
+                .map(|p| normalize(p.trim()))
+                .filter(|p| !p.is_empty())
+                .map(|p| Glob::new(&p))
+                .collect()
+        };
+
+        Self {
+            include: normalize_all(include),
+            exclude: normalize_all(exclude),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Returns if faces with the material are kept.
+    pub fn keeps(&self, material: &str) -> bool {
+        let material = normalize(material);
+
+        (self.include.is_empty() || self.include.iter().any(|p| p.matches(&material)))
+            && !self.exclude.iter().any(|p| p.matches(&material))
+    }
+}
+
+/// Materials are matched by their path inside `materials`, without the extension.
+fn normalize(material: &str) -> String {
+    material_name(material, ".vmt")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_materials() {
+        let filter =
+            MaterialFilter::new(Vec::new(), vec!["Tools/*".to_owned(), " dev/* ".to_owned()]);
+
+        assert!(!filter.keeps("materials/TOOLS/toolsnodraw.vmt"));
+        assert!(!filter.keeps("dev\\dev_measuregeneric01"));
+        assert!(filter.keeps("concrete/concretefloor001a"));
+
+        let filter = MaterialFilter::new(vec!["concrete/*".to_owned()], vec!["**/*b".to_owned()]);
+
+        assert!(filter.keeps("concrete/concretefloor001a"));
+        assert!(!filter.keeps("concrete/concretefloor001b"));
+        assert!(!filter.keeps("brick/brickwall001a"));
+
+        let filter = MaterialFilter::new(Vec::new(), vec!["*nodraw".to_owned()]);
+
+        assert!(filter.keeps("tools/toolsnodraw"));
+
+        assert!(MaterialFilter::new(vec![String::new()], Vec::new()).is_empty());
+    }
+}
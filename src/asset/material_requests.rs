@@ -0,0 +1,55 @@
+//! Collects the materials which assets only refer to by name, like the materials of
+//! sprites. Nothing loads them while the assets are processed, so the importer loads
+//! them afterwards.
+
+use std::{
+    collections::BTreeSet,
+    mem,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::game_path;
+
+/// Requested material paths, shared between the asset handler and the importer.
+#[derive(Debug, Clone, Default)]
+pub struct MaterialRequests(Arc<Mutex<BTreeSet<String>>>);
+
+impl MaterialRequests {
+    fn paths(&self) -> MutexGuard<'_, BTreeSet<String>> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Requests a material by its game path, like `materials/sprites/glow01.vmt`.
+    pub fn request(&self, path: &str) {
+        self.paths().insert(game_path::normalize(path));
+    }
+
+    /// Takes the requested materials, so that each is only loaded once.
+    pub fn take(&self) -> BTreeSet<String> {
+        mem::take(&mut *self.paths())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_requests_once() {
+        let requests = MaterialRequests::default();
+        requests.request("materials/sprites/glow01.vmt");
+        requests.request("Materials\\Sprites\\Glow01.vmt");
+        requests.request("materials/sprites/light_glow03.vmt");
+
+        assert_eq!(
+            requests.take().into_iter().collect::<Vec<_>>(),
+            [
+                "materials/sprites/glow01.vmt",
+                "materials/sprites/light_glow03.vmt"
+            ]
+        );
+        assert!(requests.take().is_empty());
+    }
+}
@@ -1,14 +1,37 @@
+mod bc;
 pub mod brush;
+pub mod choreo;
+pub mod cubemap;
+pub mod detail;
 pub mod entities;
+pub mod errors;
+pub mod goldsrc;
 pub mod material;
+pub mod material_filter;
+pub mod material_requests;
 pub mod model;
+mod model_materials;
 pub mod overlay;
+mod physics;
+mod sequence;
 pub mod sky;
+pub mod sound;
+pub mod sprite;
+pub mod studio;
+pub mod texture_usage;
+pub mod track;
 mod utils;
-use std::fmt::{self, Display, Formatter};
+pub mod vis_cluster;
+pub mod weather;
+use std::{
+    fmt::{self, Display, Formatter},
+    io::Read,
+    mem,
+    sync::{Arc, OnceLock},
+};
 
 use crossbeam_channel::Sender;
-use tracing::{debug_span, error};
+use tracing::{debug_span, error, warn};
 
 use plumber_core::{
     asset_core::{Asset, Cached, Handler, NoError},
@@ -24,7 +47,7 @@ use plumber_core::{
         VmtError,
     },
     asset_vtf::{LoadedVtf, VtfConfig, VtfError},
-    fs::PathBuf,
+    fs::{GamePathBuf, OpenFileSystem, PathBuf},
     vmf::{
         builder::{BuiltBrushEntity, BuiltOverlay},
         entities::{BaseEntity, EntityParseError, TypedEntity},
@@ -32,17 +55,32 @@ use plumber_core::{
     },
 };
 
+use crate::{fgd::Fgd, game_path, lookups::LookupLog, vtf_header, watchdog::Watch};
+
 use self::{
     brush::PyBuiltBrushEntity,
+    cubemap::is_two_channel_vtf,
     entities::{
         LightSettings, PyEnvLight, PyLight, PyLoadedProp, PySkyCamera, PySpotLight, PyUnknownEntity,
     },
+    errors::ErrorLog,
     material::{
-        BuiltMaterialData, Material, MaterialConfig, Settings as MaterialSettings, Texture,
+        average_color, has_empty_blue_channel, insert_average_color, is_base_texture,
+        BuiltMaterialData, ColorCorrection, Material, MaterialBatchConfig, MaterialConfig,
+        Settings as MaterialSettings, Texture,
     },
+    material_filter::MaterialFilter,
+    material_requests::MaterialRequests,
     model::PyModel,
+    model_materials::MaterialIndex,
     overlay::PyBuiltOverlay,
-    sky::PySkyEqui,
+    physics::CollisionSolid,
+    sky::{PySkyEqui, PySkyFaces, SkyProjection},
+    sound::{PySound, SentSounds, SoundScripts},
+    sprite::PySprite,
+    studio::StudioData,
+    texture_usage::TextureUsage,
+    weather::{PyFogController, PyPrecipitation, PyWind},
 };
 
 pub enum Message {
@@ -57,7 +95,13 @@ pub enum Message {
     EnvLight(PyEnvLight),
     SkyCamera(PySkyCamera),
     SkyEqui(PySkyEqui),
+    SkyFaces(PySkyFaces),
     UnknownEntity(PyUnknownEntity),
+    Wind(PyWind),
+    Precipitation(PyPrecipitation),
+    FogController(PyFogController),
+    Sprite(PySprite),
+    Sound(PySound),
 }
 
 enum MessageId {
@@ -88,7 +132,13 @@ impl Message {
             Message::EnvLight(_) => "env light",
             Message::SkyCamera(_) => "sky camera",
             Message::SkyEqui(_) => "sky equi",
+            Message::SkyFaces(_) => "sky faces",
             Message::UnknownEntity(_) => "unknown entity",
+            Message::Wind(_) => "wind",
+            Message::Precipitation(_) => "precipitation",
+            Message::FogController(_) => "fog controller",
+            Message::Sprite(_) => "sprite",
+            Message::Sound(_) => "sound",
         }
     }
 
@@ -105,7 +155,13 @@ impl Message {
             Message::EnvLight(light) => MessageId::Int(light.id),
             Message::SkyCamera(camera) => MessageId::Int(camera.id),
             Message::SkyEqui(equi) => MessageId::String(equi.name.clone()),
+            Message::SkyFaces(faces) => MessageId::String(faces.name.clone()),
             Message::UnknownEntity(entity) => MessageId::Int(entity.id),
+            Message::Wind(wind) => MessageId::Int(wind.id),
+            Message::Precipitation(precipitation) => MessageId::Int(precipitation.id),
+            Message::FogController(fog) => MessageId::Int(fog.id),
+            Message::Sprite(sprite) => MessageId::Int(sprite.id),
+            Message::Sound(sound) => MessageId::Int(sound.id),
         }
     }
 }
@@ -116,12 +172,22 @@ pub struct HandlerSettings {
     pub import_lights: bool,
     pub light: LightSettings,
     pub import_sky_camera: bool,
+    pub import_weather: bool,
+    pub import_sprites: bool,
+    pub import_sounds: bool,
     pub sky_equi_height: Option<u32>,
+    pub sky_projection: SkyProjection,
     pub scale: f32,
     pub target_fps: f32,
+    pub resample_animations: bool,
     pub remove_animations: bool,
+    pub import_physics: bool,
+    pub import_attachments: bool,
+    pub import_hitboxes: bool,
     pub material: MaterialSettings,
     pub import_unknown_entities: bool,
+    pub fgd: Option<Arc<Fgd>>,
+    pub material_filter: MaterialFilter,
 }
 
 impl Default for HandlerSettings {
@@ -130,12 +196,22 @@ impl Default for HandlerSettings {
             import_lights: true,
             light: LightSettings::default(),
             import_sky_camera: true,
+            import_weather: true,
+            import_sprites: true,
+            import_sounds: false,
             sky_equi_height: None,
+            sky_projection: SkyProjection::default(),
             scale: 0.01,
             target_fps: 30.0,
+            resample_animations: false,
             remove_animations: false,
+            import_physics: false,
+            import_attachments: false,
+            import_hitboxes: false,
             material: MaterialSettings::default(),
             import_unknown_entities: false,
+            fgd: None,
+            material_filter: MaterialFilter::default(),
         }
     }
 }
@@ -144,6 +220,16 @@ impl Default for HandlerSettings {
 pub struct BlenderAssetHandler {
     pub sender: Sender<Message>,
     pub settings: HandlerSettings,
+    pub file_system: OpenFileSystem,
+    /// Listed on the first model with missing materials, since listing is slow.
+    pub model_material_index: Arc<OnceLock<MaterialIndex>>,
+    /// Read on the first sound entity, since most maps have none.
+    pub sound_scripts: Arc<OnceLock<SoundScripts>>,
+    pub sent_sounds: SentSounds,
+    pub errors: ErrorLog,
+    pub texture_usage: TextureUsage,
+    pub lookups: LookupLog,
+    pub material_requests: MaterialRequests,
 }
 
 impl BlenderAssetHandler {
@@ -154,13 +240,153 @@ impl BlenderAssetHandler {
             .send(asset)
             .expect("asset channel should stay connected");
     }
+
+    /// Logs the error of an asset, and collects it for the error report.
+    fn report_error(&self, kind: &'static str, error: &impl Display) {
+        error!("{error}");
+        self.errors.push_message(kind, error.to_string());
+    }
+
+    fn report_entity_error(&self, entity: &Entity, error: &EntityParseError) {
+        let id = entity.id;
+        let class_name = entity.class_name.clone();
+
+        error!("entity {class_name} `{id}`: {error}");
+        self.errors.push(
+            "entity",
+            Some(format!("{class_name} {id}")),
+            error.to_string(),
+        );
+    }
+
+    fn load_physics(&self, model: &LoadedMdl) -> Vec<CollisionSolid> {
+        let Some((stem, _)) = model.name.as_str().rsplit_once('.') else {
+            return Vec::new();
+        };
+
+        let path = GamePathBuf::from(format!("{stem}.phy"));
+
+        // most models don't have collision, so a missing file is not an error
+        let Ok(data) = self.file_system.read(&path.into()) else {
+            return Vec::new();
+        };
+
+        physics::parse_phy(&data).unwrap_or_else(|error| {
+            warn!("model `{}`: could not parse physics: {error}", model.name);
+            Vec::new()
+        })
+    }
+
+    /// Whether a texture is a normal map without a blue channel. The format is only read
+    /// from the file if the decoded blue channel is empty, which is rare otherwise.
+    fn is_two_channel_normal_map(&self, texture: &LoadedVtf) -> bool {
+        if !has_empty_blue_channel(&texture.data) {
+            return false;
+        }
+
+        self.read_vtf_header(texture)
+            .is_some_and(|header| is_two_channel_vtf(&header))
+    }
+
+    /// The color correction of a texture, unless it's disabled or the texture isn't a
+    /// base texture of a material, since other textures like normal maps aren't colors.
+    fn texture_color_correction(&self, name: &str) -> Option<ColorCorrection> {
+        self.settings
+            .material
+            .texture_color_correction
+            .enabled()
+            .filter(|_| is_base_texture(name))
+    }
+
+    /// Reads the start of the header of a texture, up to its format.
+    fn read_vtf_header(&self, texture: &LoadedVtf) -> Option<[u8; vtf_header::FORMAT_LEN]> {
+        let name = texture.name.to_string();
+        let name = name.strip_suffix(".vtf").unwrap_or(&name);
+        let path = GamePathBuf::from(format!("{name}.vtf"));
+
+        let mut header = [0; vtf_header::FORMAT_LEN];
+        self.file_system
+            .open_file(&path)
+            .and_then(|mut file| file.read_exact(&mut header))
+            .ok()?;
+
+        Some(header)
+    }
+
+    fn model_material_index(&self) -> &MaterialIndex {
+        self.model_material_index
+            .get_or_init(|| MaterialIndex::read(&self.file_system))
+    }
+
+    fn sound_scripts(&self) -> &SoundScripts {
+        self.sound_scripts
+            .get_or_init(|| SoundScripts::read(&self.file_system))
+    }
+
+    fn load_studio_data(&self, model: &LoadedMdl) -> StudioData {
+        let data = match self.file_system.read(&model.name.clone().into()) {
+            Ok(data) => data,
+            Err(error) => {
+                warn!("model `{}`: could not read model data: {error}", model.name);
+                return StudioData::default();
+            }
+        };
+
+        let (studio_data, errors) = StudioData::read(&data);
+
+        for (section, error) in errors {
+            warn!("model `{}`: could not read {section}: {error}", model.name);
+        }
+
+        studio_data
+    }
 }
 
 impl Handler<Cached<MaterialConfig>> for BlenderAssetHandler {
     fn handle(&self, output: Result<(PathBuf, Option<BuiltMaterialData>), VmtError>) {
         match output {
             Ok((name, material)) => {
-                if let Some(material) = material {
+                let _watch = Watch::new(format!("material handler `{name}`"));
+                self.lookups.record(&name.to_string());
+
+                if let Some(mut material) = material {
+                    self.texture_usage
+                        .reference(material.texture_color_spaces.keys().map(String::as_str));
+
+                    for texture in mem::take(&mut material.loose_textures) {
+                        // materials sharing a loose texture each load it
+                        if !self.texture_usage.emit(&texture.name) {
+                            continue;
+                        }
+
+                        let color_correction = self
+                            .settings
+                            .material
+                            .texture_color_correction
+                            .enabled()
+                            .filter(|_| material.base_textures.contains(&texture.name));
+
+                        match Texture::from_loose(
+                            &texture,
+                            self.settings.material.texture_format,
+                            self.settings.material.texture_max_resolution,
+                            color_correction,
+                        ) {
+                            Ok(texture) => self.send_asset(Message::Texture(texture)),
+                            Err(error) => {
+                                error!(
+                                    "texture `{}`: could not encode loose image: {error}",
+                                    texture.name
+                                );
+                                self.errors.push(
+                                    "texture",
+                                    Some(texture.name.clone()),
+                                    error.to_string(),
+                                );
+                            }
+                        }
+                    }
+
                     self.send_asset(Message::Material(Material::new(
                         &name,
                         material,
@@ -168,19 +394,46 @@ impl Handler<Cached<MaterialConfig>> for BlenderAssetHandler {
                     )));
                 }
             }
-            Err(error) => error!("{error}"),
+            Err(error) => {
+                error!("{error}");
+                self.lookups.record(&error.path.to_string());
+                self.errors
+                    .push("material", Some(error.path.to_string()), error.to_string());
+            }
         }
     }
 }
 
+impl Handler<Asset<MaterialBatchConfig>> for BlenderAssetHandler {
+    fn handle(&self, _output: Result<(), NoError>) {
+        // the materials of the batch are handled as they are loaded
+    }
+}
+
 impl Handler<Cached<VtfConfig>> for BlenderAssetHandler {
     fn handle(&self, output: Result<LoadedVtf, VtfError>) {
         match output {
-            Ok(texture) => self.send_asset(Message::Texture(Texture::new(
-                &texture,
-                self.settings.material.texture_format,
-            ))),
-            Err(error) => error!("{error}"),
+            Ok(texture) => {
+                let name = texture.name.to_string();
+                let _watch = Watch::new(format!("texture handler `{name}`"));
+                self.lookups.record_texture(&name);
+                self.texture_usage.emit(&name);
+
+                // the materials using it as a base texture show its color in the viewport,
+                // recorded for every texture since another material may load it first
+                if let Some(color) = average_color(&texture.data) {
+                    insert_average_color(&name, color);
+                }
+
+                self.send_asset(Message::Texture(Texture::new(
+                    &texture,
+                    self.settings.material.texture_format,
+                    self.settings.material.texture_max_resolution,
+                    self.is_two_channel_normal_map(&texture),
+                    self.texture_color_correction(&name),
+                )));
+            }
+            Err(error) => self.report_error("texture", &error),
         }
     }
 }
@@ -188,49 +441,141 @@ impl Handler<Cached<VtfConfig>> for BlenderAssetHandler {
 impl Handler<Cached<MdlConfig<MaterialConfig>>> for BlenderAssetHandler {
     fn handle(&self, output: Result<LoadedMdl, MdlError>) {
         match output {
-            Ok(model) => self.send_asset(Message::Model(PyModel::new(
-                model,
-                self.settings.target_fps,
-                self.settings.remove_animations,
-            ))),
-            Err(error) => error!("{error}"),
+            Ok(model) => {
+                let _watch = Watch::new(format!("model handler `{}`", model.name));
+                self.lookups.record(model.name.as_str());
+
+                let collision = if self.settings.import_physics {
+                    self.load_physics(&model)
+                } else {
+                    Vec::new()
+                };
+
+                let mut studio_data = self.load_studio_data(&model);
+
+                if !self.settings.import_attachments {
+                    studio_data.attachments.clear();
+                }
+
+                if !self.settings.import_hitboxes {
+                    studio_data.hitbox_sets.clear();
+                }
+
+                let material_index = model
+                    .materials
+                    .iter()
+                    .any(Option::is_none)
+                    .then(|| self.model_material_index());
+
+                let model = PyModel::new(
+                    model,
+                    self.settings.target_fps,
+                    self.settings.resample_animations,
+                    self.settings.remove_animations,
+                    collision,
+                    studio_data,
+                    material_index,
+                );
+
+                for material in model.fallback_materials() {
+                    self.material_requests.request(material);
+                }
+
+                self.send_asset(Message::Model(model));
+            }
+            Err(error) => self.report_error("model", &error),
         }
     }
 }
 
+fn watch_entity(entity: &Entity) -> Watch {
+    Watch::new(format!(
+        "entity handler {} `{}`",
+        entity.class_name, entity.id
+    ))
+}
+
 impl Handler<Asset<OtherEntityConfig>> for BlenderAssetHandler {
     fn handle(&self, output: Result<TypedEntity<'_>, NoError>) {
         let entity = output.unwrap();
 
         match entity {
             TypedEntity::Light(light) if self.settings.import_lights => {
+                let _watch = watch_entity(light.entity());
                 match PyLight::new(light, &self.settings.light, self.settings.scale) {
                     Ok(light) => self.send_asset(Message::Light(light)),
-                    Err(error) => log_entity_error(light.entity(), &error),
+                    Err(error) => self.report_entity_error(light.entity(), &error),
                 }
             }
             TypedEntity::SpotLight(spot_light) if self.settings.import_lights => {
+                let _watch = watch_entity(spot_light.entity());
                 match PySpotLight::new(spot_light, &self.settings.light, self.settings.scale) {
                     Ok(light) => self.send_asset(Message::SpotLight(light)),
-                    Err(error) => log_entity_error(spot_light.entity(), &error),
+                    Err(error) => self.report_entity_error(spot_light.entity(), &error),
                 }
             }
             TypedEntity::EnvLight(env_light) if self.settings.import_lights => {
+                let _watch = watch_entity(env_light.entity());
                 match PyEnvLight::new(env_light, &self.settings.light, self.settings.scale) {
                     Ok(light) => self.send_asset(Message::EnvLight(light)),
-                    Err(error) => log_entity_error(env_light.entity(), &error),
+                    Err(error) => self.report_entity_error(env_light.entity(), &error),
                 }
             }
             TypedEntity::SkyCamera(sky_camera) if self.settings.import_sky_camera => {
+                let _watch = watch_entity(sky_camera.entity());
                 match PySkyCamera::new(sky_camera, self.settings.scale) {
                     Ok(sky_camera) => self.send_asset(Message::SkyCamera(sky_camera)),
-                    Err(error) => log_entity_error(sky_camera.entity(), &error),
+                    Err(error) => self.report_entity_error(sky_camera.entity(), &error),
                 }
             }
+            TypedEntity::Unknown(entity)
+                if self.settings.import_weather
+                    && weather::is_weather_class(&entity.entity().class_name) =>
+            {
+                let _watch = watch_entity(entity.entity());
+                let class_name = entity.entity().class_name.to_ascii_lowercase();
+                let scale = self.settings.scale;
+
+                self.send_asset(match class_name.as_str() {
+                    weather::WIND_CLASS => Message::Wind(PyWind::new(&entity, scale)),
+                    weather::PRECIPITATION_CLASS => {
+                        Message::Precipitation(PyPrecipitation::new(&entity))
+                    }
+                    _ => Message::FogController(PyFogController::new(&entity, scale)),
+                });
+            }
+            TypedEntity::Unknown(entity)
+                if self.settings.import_sprites
+                    && sprite::is_sprite_class(&entity.entity().class_name) =>
+            {
+                let _watch = watch_entity(entity.entity());
+                let sprite = PySprite::new(&entity, self.settings.scale);
+
+                for material in sprite.materials() {
+                    self.material_requests.request(material);
+                }
+
+                self.send_asset(Message::Sprite(sprite));
+            }
+            TypedEntity::Unknown(entity)
+                if self.settings.import_sounds
+                    && sound::is_sound_class(&entity.entity().class_name) =>
+            {
+                let _watch = watch_entity(entity.entity());
+                self.send_asset(Message::Sound(PySound::new(
+                    &entity,
+                    self.settings.scale,
+                    self.sound_scripts(),
+                    &self.sent_sounds,
+                    &self.file_system,
+                )));
+            }
             TypedEntity::Unknown(entity) if self.settings.import_unknown_entities => {
+                let _watch = watch_entity(entity.entity());
                 self.send_asset(Message::UnknownEntity(PyUnknownEntity::new(
                     entity,
                     self.settings.scale,
+                    self.settings.fgd.as_deref(),
                 )));
             }
             _ => {}
@@ -241,16 +586,25 @@ impl Handler<Asset<OtherEntityConfig>> for BlenderAssetHandler {
 impl<'a> Handler<Asset<BrushConfig<'a, MaterialConfig>>> for BlenderAssetHandler {
     fn handle(&self, output: Result<BuiltBrushEntity<'_>, NoError>) {
         let brush = output.unwrap();
+        let _watch = Watch::new(format!("brush handler {} `{}`", brush.class_name, brush.id));
 
-        self.send_asset(Message::Brush(PyBuiltBrushEntity::new(brush)));
+        let brush = PyBuiltBrushEntity::new(brush, &self.settings.material_filter);
+
+        if !brush.is_empty() {
+            self.send_asset(Message::Brush(brush));
+        }
     }
 }
 
 impl<'a> Handler<Asset<OverlayConfig<'a, MaterialConfig>>> for BlenderAssetHandler {
     fn handle(&self, output: Result<BuiltOverlay<'_>, OverlayError>) {
         match output {
-            Ok(overlay) => self.send_asset(Message::Overlay(PyBuiltOverlay::new(overlay))),
-            Err(error) => error!("{error}"),
+            Ok(overlay) => {
+                let _watch =
+                    Watch::new(format!("overlay handler `{}`", overlay.overlay.entity().id));
+                self.send_asset(Message::Overlay(PyBuiltOverlay::new(overlay)));
+            }
+            Err(error) => self.report_error("overlay", &error),
         }
     }
 }
@@ -258,8 +612,11 @@ impl<'a> Handler<Asset<OverlayConfig<'a, MaterialConfig>>> for BlenderAssetHandl
 impl Handler<Asset<PropConfig<MaterialConfig>>> for BlenderAssetHandler {
     fn handle(&self, output: Result<LoadedProp<'_>, PropError>) {
         match output {
-            Ok(prop) => self.send_asset(Message::Prop(PyLoadedProp::new(prop))),
-            Err(error) => error!("{error}"),
+            Ok(prop) => {
+                let _watch = Watch::new(format!("prop handler `{}`", prop.prop.entity().id));
+                self.send_asset(Message::Prop(PyLoadedProp::new(prop, self.settings.scale)));
+            }
+            Err(error) => self.report_error("prop", &error),
         }
     }
 }
@@ -267,18 +624,33 @@ impl Handler<Asset<PropConfig<MaterialConfig>>> for BlenderAssetHandler {
 impl Handler<Asset<SkyBoxConfig>> for BlenderAssetHandler {
     fn handle(&self, output: Result<SkyBox, SkyBoxError>) {
         match output {
-            Ok(skybox) => self.send_asset(Message::SkyEqui(PySkyEqui::new(
-                skybox,
-                self.settings.sky_equi_height,
-            ))),
-            Err(error) => error!("{error}"),
-        }
-    }
-}
+            Ok(skybox) => {
+                let name = skybox.name.into_string();
+                let _watch = Watch::new(format!("skybox handler `{name}`"));
+                let mut faces = sky::skybox_faces(&name, skybox.data, |path| {
+                    self.file_system
+                        .read(&game_path::game_path(path).into())
+                        .ok()
+                });
 
-fn log_entity_error(entity: &Entity, error: &EntityParseError) {
-    let id = entity.id;
-    let class_name = entity.class_name.clone();
+                if let Some(correction) = self.settings.material.texture_color_correction.enabled()
+                {
+                    sky::correct_colors(&mut faces, correction);
+                }
+
+                let message = match self.settings.sky_projection {
+                    SkyProjection::Faces => Message::SkyFaces(PySkyFaces::new(name, faces)),
+                    projection => Message::SkyEqui(PySkyEqui::new(
+                        name,
+                        faces,
+                        projection,
+                        self.settings.sky_equi_height,
+                    )),
+                };
 
-    error!("entity {class_name} `{id}`: {error}");
+                self.send_asset(message);
+            }
+            Err(error) => self.report_error("skybox", &error),
+        }
+    }
 }
@@ -3,14 +3,27 @@ use std::{
     mem,
 };
 
-use glam::{Affine3A, EulerRot, Quat, Vec3};
+use glam::{Affine3A, EulerRot, Mat3, Quat, Vec3};
 use pyo3::{prelude::*, types::PyList};
 use tracing::warn;
 
 use plumber_core::{
     asset_mdl::{LoadedAnimation, LoadedBone, LoadedMdl, LoadedMesh},
-    fs::GamePathBuf,
-    mdl::{self, AnimationData, AnimationDescFlags, BoneAnimationData},
+    fs::{GamePathBuf, OpenFileSystem},
+    mdl::{AnimationData, AnimationDescFlags, BoneAnimationData},
+};
+
+use crate::{
+    dmx::model::{DmxMesh, DmxModel},
+    game_path::material_name,
+};
+
+use super::{
+    goldsrc::{self, GoldSrcMesh, GoldSrcModel},
+    model_materials::{MaterialIndex, Resolution},
+    physics::CollisionSolid,
+    sequence::{self, BlendedSequence},
+    studio::{self, Attachment, Hitbox, JiggleBone, StudioData},
 };
 
 #[pyclass(module = "plumber", name = "Model")]
@@ -18,9 +31,16 @@ pub struct PyModel {
     pub name: String,
     meshes: Vec<PyLoadedMesh>,
     materials: Vec<Option<String>>,
+    material_dirs: Vec<String>,
+    material_bindings: Vec<PyMaterialBinding>,
+    skins: Vec<Vec<Option<String>>>,
     bones: Vec<PyLoadedBone>,
     animations: Vec<PyLoadedAnimation>,
+    sequences: Vec<PyLoadedAnimation>,
     rest_positions: BTreeMap<usize, PyBoneRestData>,
+    bodygroups: Vec<(String, Vec<String>)>,
+    attachments: Vec<PyLoadedAttachment>,
+    hitbox_sets: Vec<(String, Vec<PyHitbox>)>,
 }
 
 #[pymethods]
@@ -37,6 +57,34 @@ impl PyModel {
         mem::take(&mut self.materials)
     }
 
+    /// Returns the `$cdmaterials` directories the materials are searched from.
+    fn material_dirs(&mut self) -> Vec<String> {
+        mem::take(&mut self.material_dirs)
+    }
+
+    /// Returns which material each material slot is bound to and how it was found.
+    fn material_bindings(&mut self) -> Vec<PyMaterialBinding> {
+        mem::take(&mut self.material_bindings)
+    }
+
+    /// Returns the texture name, the resolution and the path of each material slot. Kept
+    /// for scripts written before `material_bindings`, so it has to be called first.
+    fn material_resolutions(&self) -> Vec<(String, &'static str, Option<String>)> {
+        self.material_bindings
+            .iter()
+            .map(|b| (b.name.clone(), b.status, b.path.clone()))
+            .collect()
+    }
+
+    fn skin_count(&self) -> usize {
+        self.skins.len()
+    }
+
+    /// Returns the material list with the given skin family applied.
+    fn materials_for_skin(&self, index: usize) -> Option<Vec<Option<String>>> {
+        self.skins.get(index).cloned()
+    }
+
     fn bones(&mut self) -> Vec<PyLoadedBone> {
         mem::take(&mut self.bones)
     }
@@ -45,20 +93,54 @@ impl PyModel {
         mem::take(&mut self.animations)
     }
 
+    /// Returns the sequences of the model, as they are played by the game.
+    fn sequences(&mut self) -> Vec<PyLoadedAnimation> {
+        mem::take(&mut self.sequences)
+    }
+
     fn rest_positions(&mut self) -> BTreeMap<usize, PyBoneRestData> {
         mem::take(&mut self.rest_positions)
     }
+
+    fn bodygroups(&mut self) -> Vec<(String, Vec<String>)> {
+        mem::take(&mut self.bodygroups)
+    }
+
+    fn attachments(&mut self) -> Vec<PyLoadedAttachment> {
+        mem::take(&mut self.attachments)
+    }
+
+    fn hitbox_sets(&mut self) -> Vec<(String, Vec<PyHitbox>)> {
+        mem::take(&mut self.hitbox_sets)
+    }
 }
 
 impl PyModel {
-    pub fn new(m: LoadedMdl, target_fps: f32, remove_animations: bool) -> Self {
-        let bones = if m.info.static_prop {
+    pub fn new(
+        m: LoadedMdl,
+        target_fps: f32,
+        resample_animations: bool,
+        remove_animations: bool,
+        collision: Vec<CollisionSolid>,
+        studio_data: StudioData,
+        material_index: Option<&MaterialIndex>,
+    ) -> Self {
+        let bone_transforms = bone_transforms(&m.bones);
+
+        let mut bones: Vec<_> = if m.info.static_prop {
             Vec::new()
         } else {
-            m.bones.into_iter().map(PyLoadedBone::new).collect()
+            m.bones.iter().map(PyLoadedBone::new).collect()
         };
 
+        for (&index, &jiggle) in &studio_data.jiggle_bones {
+            if let Some(bone) = bones.get_mut(index) {
+                bone.jiggle = Some(PyJiggleBone(jiggle));
+            }
+        }
+
         let animations;
+        let sequences;
         let rest_positions;
 
         if remove_animations {
@@ -69,17 +151,91 @@ impl PyModel {
             }
 
             animations = Vec::new();
+            sequences = Vec::new();
         } else {
+            let animations_by_desc: Vec<_> = studio_data
+                .animation_names
+                .iter()
+                .map(|name| m.animations.iter().find(|a| a.name == *name))
+                .collect();
+
+            // delta sequences are layered on top of others, they can't be played on their own
+            sequences = studio_data
+                .sequences
+                .iter()
+                .filter(|s| !s.delta && !bones.is_empty())
+                .filter_map(|s| sequence::blend_sequence(s, &animations_by_desc, &m.bones))
+                .map(|s| {
+                    PyLoadedAnimation::from_sequence(s, &bones, target_fps, resample_animations)
+                })
+                .collect();
+
             animations = m
                 .animations
                 .into_iter()
-                .map(|a| PyLoadedAnimation::new(a, &bones, target_fps))
+                .map(|a| PyLoadedAnimation::new(a, &bones, target_fps, resample_animations))
                 .collect();
 
             rest_positions = BTreeMap::new();
         };
 
-        let mut meshes: Vec<_> = m.meshes.into_iter().map(PyLoadedMesh::new).collect();
+        // body parts and their models, in the order they appear in the mdl. blank models
+        // have no meshes, so they are read from the header to keep the model indices
+        let mut bodygroups: Vec<(String, Vec<String>)> = studio_data
+            .body_parts
+            .into_iter()
+            .map(|part| (part.name, part.models))
+            .collect();
+
+        let mut meshes: Vec<_> = m
+            .meshes
+            .into_iter()
+            .map(|mesh| {
+                let part = bodygroups
+                    .iter()
+                    .position(|(name, _)| *name == mesh.body_part_name)
+                    .unwrap_or_else(|| {
+                        bodygroups.push((mesh.body_part_name.clone(), Vec::new()));
+                        bodygroups.len() - 1
+                    });
+
+                let models = &mut bodygroups[part].1;
+                let model = models
+                    .iter()
+                    .position(|name| *name == mesh.name)
+                    .unwrap_or_else(|| {
+                        models.push(mesh.name.clone());
+                        models.len() - 1
+                    });
+
+                let mut mesh = PyLoadedMesh::new(mesh);
+                mesh.bodygroup = Some((part, model));
+                mesh
+            })
+            .collect();
+
+        let single_solid = collision.len() == 1;
+
+        for solid in collision {
+            if solid.faces.is_empty() {
+                continue;
+            }
+
+            // vertices of solids attached to bones are relative to the bone
+            let bone = solid
+                .bone_name
+                .as_deref()
+                .and_then(|name| m.bones.iter().position(|b| b.name == name))
+                .or_else(|| single_solid.then_some(0))
+                .filter(|&i| i < bone_transforms.len());
+
+            let transform = bone.map_or(Affine3A::IDENTITY, |i| bone_transforms[i]);
+            let weight_bone = bone
+                .filter(|_| !bones.is_empty())
+                .and_then(|i| u8::try_from(i).ok());
+
+            meshes.push(PyLoadedMesh::collision(solid, transform, weight_bone));
+        }
 
         let mut used_mesh_names = BTreeSet::new();
 
@@ -107,21 +263,401 @@ impl PyModel {
             }
         }
 
+        let mut material_bindings = Vec::with_capacity(m.materials.len());
+
+        let materials: Vec<_> = m
+            .materials
+            .into_iter()
+            .enumerate()
+            .map(|(i, mat)| {
+                let texture_name = studio_data
+                    .texture_names
+                    .get(i)
+                    .cloned()
+                    .unwrap_or_default();
+
+                let (resolution, path) = resolve_material(
+                    &m.name,
+                    &texture_name,
+                    mat,
+                    &studio_data.material_dirs,
+                    material_index,
+                );
+
+                material_bindings.push(PyMaterialBinding {
+                    slot: i,
+                    name: texture_name,
+                    status: resolution.to_str(),
+                    path: path.clone(),
+                });
+                path
+            })
+            .collect();
+
+        let skin_families = &studio_data.skin_families;
+        let skins = (0..skin_families.len())
+            .filter_map(|family| studio::skin_materials(&materials, skin_families, family))
+            .collect();
+
+        // static props have no armature, so bone relative data is placed in model space
+        let bone_space = |bone: usize| {
+            if bones.is_empty() {
+                (None, bone_transforms[bone])
+            } else {
+                (Some(bone), Affine3A::IDENTITY)
+            }
+        };
+
+        let attachments = studio_data
+            .attachments
+            .into_iter()
+            .filter(|a| a.bone < bone_transforms.len())
+            .map(|a| {
+                let (parent, transform) = bone_space(a.bone);
+                PyLoadedAttachment::new(a, parent, transform)
+            })
+            .collect();
+
+        let hitbox_sets = studio_data
+            .hitbox_sets
+            .into_iter()
+            .map(|set| {
+                let hitboxes = set
+                    .hitboxes
+                    .into_iter()
+                    .filter(|h| h.bone < bone_transforms.len())
+                    .map(|h| {
+                        let (parent, transform) = bone_space(h.bone);
+                        PyHitbox::new(h, parent, transform)
+                    })
+                    .collect();
+
+                (set.name, hitboxes)
+            })
+            .collect();
+
         Self {
             name: m.name.into_string(),
             meshes,
-            materials: m
-                .materials
-                .into_iter()
-                .map(|mat| mat.map(GamePathBuf::into_string))
-                .collect(),
+            materials,
+            material_dirs: studio_data.material_dirs,
+            material_bindings,
+            skins,
             bones,
             animations,
+            sequences,
             rest_positions,
+            bodygroups,
+            attachments,
+            hitbox_sets,
+        }
+    }
+
+    /// Materials found in the fallback directory, which the model only refers to by
+    /// path, so the importer loads them afterwards.
+    pub fn fallback_materials(&self) -> impl Iterator<Item = &str> {
+        self.material_bindings
+            .iter()
+            .filter(|b| b.status == Resolution::Fallback.to_str())
+            .filter_map(|b| b.path.as_deref())
+    }
+
+    /// Materials which were found, directly or in the fallback directory.
+    pub fn resolved_materials(&self) -> impl Iterator<Item = &str> {
+        self.material_bindings
+            .iter()
+            .filter_map(|b| b.path.as_deref())
+    }
+}
+
+impl PyModel {
+    /// Creates a model from a GoldSrc model in its rest pose. The materials are named
+    /// after the embedded textures.
+    pub fn from_goldsrc(name: String, model: &GoldSrcModel) -> Self {
+        let mut bone_transforms: Vec<Affine3A> = Vec::with_capacity(model.bones.len());
+
+        for bone in &model.bones {
+            let [x, y, z] = bone.rotation;
+            let local = Affine3A::from_rotation_translation(
+                Quat::from_euler(EulerRot::ZYX, z, y, x),
+                bone.position.into(),
+            );
+
+            // parents are always before their children
+            let parent = bone.parent.and_then(|p| bone_transforms.get(p));
+            bone_transforms.push(parent.map_or(local, |&parent| parent * local));
+        }
+
+        let bones = model
+            .bones
+            .iter()
+            .map(|bone| PyLoadedBone {
+                name: bone.name.clone(),
+                parent_bone_index: bone.parent.filter(|&p| p < model.bones.len()),
+                position: bone.position,
+                rotation: bone.rotation,
+                jiggle: None,
+            })
+            .collect();
+
+        let mut bodygroups: Vec<(String, Vec<String>)> = Vec::new();
+
+        let meshes = model
+            .meshes
+            .iter()
+            .map(|mesh| {
+                let part = bodygroups
+                    .iter()
+                    .position(|(name, _)| *name == mesh.body_part)
+                    .unwrap_or_else(|| {
+                        bodygroups.push((mesh.body_part.clone(), Vec::new()));
+                        bodygroups.len() - 1
+                    });
+
+                let models = &mut bodygroups[part].1;
+                models.push(mesh.name.clone());
+
+                let mut loaded = PyLoadedMesh::goldsrc(mesh, model, &bone_transforms);
+                loaded.bodygroup = Some((part, models.len() - 1));
+                loaded
+            })
+            .collect();
+
+        let materials: Vec<_> = model
+            .textures
+            .iter()
+            .map(|texture| Some(goldsrc::material_name(&name, &texture.name)))
+            .collect();
+
+        let material_bindings = model
+            .textures
+            .iter()
+            .zip(&materials)
+            .enumerate()
+            .map(|(slot, (texture, path))| PyMaterialBinding {
+                slot,
+                name: texture.name.clone(),
+                status: Resolution::Found.to_str(),
+                path: path.clone(),
+            })
+            .collect();
+
+        let skins = (0..model.skin_families.len())
+            .filter_map(|family| studio::skin_materials(&materials, &model.skin_families, family))
+            .collect();
+
+        Self {
+            name,
+            meshes,
+            materials,
+            material_dirs: Vec::new(),
+            material_bindings,
+            skins,
+            bones,
+            animations: Vec::new(),
+            sequences: Vec::new(),
+            rest_positions: BTreeMap::new(),
+            bodygroups,
+            attachments: Vec::new(),
+            hitbox_sets: Vec::new(),
+        }
+    }
+}
+
+impl PyModel {
+    /// Creates a model from the model and animations of a dmx. The materials are named
+    /// after the `mtlName` of the face sets, and searched like the materials of mdls.
+    pub fn from_dmx(
+        name: String,
+        model: &DmxModel,
+        file_system: &OpenFileSystem,
+        target_fps: f32,
+        resample: bool,
+    ) -> Self {
+        let bones: Vec<_> = model
+            .joints
+            .iter()
+            .map(|joint| PyLoadedBone {
+                name: joint.name.clone(),
+                parent_bone_index: joint.parent,
+                position: joint.position.to_array(),
+                rotation: rot_to_euler(&joint.orientation),
+                jiggle: None,
+            })
+            .collect();
+
+        let meshes = model.meshes.iter().map(PyLoadedMesh::dmx).collect();
+
+        let model_name = GamePathBuf::from(name.clone());
+        let mut material_index = None;
+        let mut material_bindings = Vec::with_capacity(model.materials.len());
+
+        let materials: Vec<_> = model
+            .materials
+            .iter()
+            .enumerate()
+            .map(|(slot, material)| {
+                let texture_name = material_name(material, ".vmt");
+                let found = (!texture_name.is_empty())
+                    .then(|| GamePathBuf::from(material.clone()))
+                    .filter(|path| file_system.open_file(path).is_ok());
+
+                // the index is only read if a material is missing
+                let index = found.is_none().then(|| {
+                    &*material_index.get_or_insert_with(|| MaterialIndex::read(file_system))
+                });
+
+                let (resolution, path) =
+                    resolve_material(&model_name, &texture_name, found, &[], index);
+
+                material_bindings.push(PyMaterialBinding {
+                    slot,
+                    name: texture_name,
+                    status: resolution.to_str(),
+                    path: path.clone(),
+                });
+                path
+            })
+            .collect();
+
+        let animations = model
+            .animations
+            .iter()
+            .map(|animation| {
+                let mut data: BTreeMap<usize, BoneAnimationData> = BTreeMap::new();
+
+                for (&joint, rotations) in &animation.rotations {
+                    data.entry(joint)
+                        .or_insert_with(empty_bone_animation)
+                        .rotation = AnimationData::Animated(rotations.clone());
+                }
+
+                for (&joint, positions) in &animation.positions {
+                    data.entry(joint)
+                        .or_insert_with(empty_bone_animation)
+                        .position = AnimationData::Animated(positions.clone());
+                }
+
+                PyLoadedAnimation::from_parts(
+                    animation.name.clone(),
+                    data,
+                    animation.fps,
+                    false,
+                    &bones,
+                    target_fps,
+                    resample,
+                )
+            })
+            .collect();
+
+        Self {
+            name,
+            meshes,
+            skins: vec![materials.clone()],
+            materials,
+            material_dirs: Vec::new(),
+            material_bindings,
+            bones,
+            animations,
+            sequences: Vec::new(),
+            rest_positions: BTreeMap::new(),
+            bodygroups: Vec::new(),
+            attachments: Vec::new(),
+            hitbox_sets: Vec::new(),
+        }
+    }
+}
+
+fn empty_bone_animation() -> BoneAnimationData {
+    BoneAnimationData {
+        rotation: AnimationData::None,
+        position: AnimationData::None,
+    }
+}
+
+/// How a material slot of a model was resolved to a material.
+#[pyclass(module = "plumber", name = "MaterialBinding")]
+pub struct PyMaterialBinding {
+    slot: usize,
+    name: String,
+    status: &'static str,
+    path: Option<String>,
+}
+
+#[pymethods]
+impl PyMaterialBinding {
+    fn slot(&self) -> usize {
+        self.slot
+    }
+
+    /// Name of the material in the model, without a directory.
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// `FOUND`, `FALLBACK` or `MISSING`. Missing materials get a placeholder.
+    fn status(&self) -> &str {
+        self.status
+    }
+
+    fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+}
+
+fn resolve_material(
+    model_name: &GamePathBuf,
+    texture_name: &str,
+    material: Option<GamePathBuf>,
+    material_dirs: &[String],
+    material_index: Option<&MaterialIndex>,
+) -> (Resolution, Option<String>) {
+    if let Some(material) = material {
+        return (Resolution::Found, Some(material.into_string()));
+    }
+
+    match material_index.and_then(|index| index.find(texture_name)) {
+        Some(path) => {
+            warn!(
+                "model `{model_name}`: material `{texture_name}` not found in $cdmaterials, \
+                using `{path}`"
+            );
+            (Resolution::Fallback, Some(path.to_owned()))
+        }
+        None => {
+            warn!(
+                "model `{model_name}`: material `{texture_name}` not found in $cdmaterials \
+                ({})",
+                material_dirs.join(", ")
+            );
+            (Resolution::Missing, None)
         }
     }
 }
 
+fn bone_transforms(bones: &[LoadedBone]) -> Vec<Affine3A> {
+    let mut transforms: Vec<Affine3A> = Vec::with_capacity(bones.len());
+
+    for bone in bones {
+        let rotation = Quat::from_euler(
+            EulerRot::ZYX,
+            bone.rotation[2],
+            bone.rotation[1],
+            bone.rotation[0],
+        );
+        let local = Affine3A::from_rotation_translation(rotation, bone.position.into());
+
+        let transform = match bone.parent_bone_index.and_then(|i| transforms.get(i)) {
+            Some(parent) => *parent * local,
+            None => local,
+        };
+
+        transforms.push(transform);
+    }
+
+    transforms
+}
+
 fn apply_animation_first_frame(
     animation: &LoadedAnimation,
     bones: &[PyLoadedBone],
@@ -157,8 +693,10 @@ fn rot_to_euler(rot: &Quat) -> [f32; 3] {
 #[pyclass(module = "plumber", name = "LoadedMesh")]
 pub struct PyLoadedMesh {
     name: String,
-    vertices: Vec<mdl::Vertex>,
-    faces: Vec<mdl::Face>,
+    normals: Vec<[f32; 3]>,
+    material_indices: Vec<usize>,
+    collision: bool,
+    bodygroup: Option<(usize, usize)>,
     flat_vertices: Vec<f32>,
     flat_polygon_vertice_indices: Vec<usize>,
     flat_loop_uvs: Vec<f32>,
@@ -176,19 +714,19 @@ impl PyLoadedMesh {
     }
 
     fn loops_len(&self) -> usize {
-        self.faces.len() * 3
+        self.material_indices.len() * 3
     }
 
     fn polygons_len(&self) -> usize {
-        self.faces.len()
+        self.material_indices.len()
     }
 
     fn polygon_loop_totals<'p>(&self, py: Python<'p>) -> &'p PyList {
-        PyList::new(py, itertools::repeat_n(3, self.faces.len()))
+        PyList::new(py, itertools::repeat_n(3, self.material_indices.len()))
     }
 
     fn polygon_loop_starts<'p>(&self, py: Python<'p>) -> &'p PyList {
-        PyList::new(py, (0..self.faces.len()).map(|i| i * 3))
+        PyList::new(py, (0..self.material_indices.len()).map(|i| i * 3))
     }
 
     fn polygon_vertices(&mut self) -> Vec<usize> {
@@ -196,20 +734,28 @@ impl PyLoadedMesh {
     }
 
     fn polygon_material_indices<'p>(&self, py: Python<'p>) -> &'p PyList {
-        PyList::new(py, self.faces.iter().map(|f| f.material_index))
+        PyList::new(py, &self.material_indices)
     }
 
     fn loop_uvs(&mut self) -> Vec<f32> {
         mem::take(&mut self.flat_loop_uvs)
     }
 
-    fn normals<'p>(&mut self, py: Python<'p>) -> &'p PyList {
-        PyList::new(py, self.vertices.iter().map(|v| v.normal.as_ref()))
+    fn normals(&mut self) -> Vec<[f32; 3]> {
+        mem::take(&mut self.normals)
     }
 
     fn weight_groups(&mut self) -> BTreeMap<u8, BTreeMap<usize, f32>> {
         mem::take(&mut self.weight_groups)
     }
+
+    fn is_collision(&self) -> bool {
+        self.collision
+    }
+
+    fn bodygroup(&self) -> Option<(usize, usize)> {
+        self.bodygroup
+    }
 }
 
 impl PyLoadedMesh {
@@ -258,14 +804,170 @@ impl PyLoadedMesh {
 
         Self {
             name,
-            vertices: mesh.vertices,
-            faces: mesh.faces,
+            normals: mesh.vertices.iter().map(|v| v.normal).collect(),
+            material_indices: mesh.faces.iter().map(|f| f.material_index).collect(),
+            collision: false,
+            bodygroup: None,
             flat_vertices,
             flat_polygon_vertice_indices,
             flat_loop_uvs,
             weight_groups,
         }
     }
+
+    fn goldsrc(mesh: &GoldSrcMesh, model: &GoldSrcModel, bone_transforms: &[Affine3A]) -> Self {
+        let bone_transform = |bone: usize| {
+            bone_transforms
+                .get(bone)
+                .copied()
+                .unwrap_or(Affine3A::IDENTITY)
+        };
+
+        let vertices: Vec<Vec3> = mesh
+            .vertices
+            .iter()
+            .map(|&(position, bone)| bone_transform(bone).transform_point3(position.into()))
+            .collect();
+
+        // normals are indexed separately, so they are averaged per vertex
+        let mut normals = vec![Vec3::ZERO; vertices.len()];
+        let mut flat_polygon_vertice_indices = Vec::with_capacity(mesh.triangles.len() * 3);
+        let mut flat_loop_uvs = Vec::with_capacity(mesh.triangles.len() * 6);
+        let mut material_indices = Vec::with_capacity(mesh.triangles.len());
+
+        for triangle in &mesh.triangles {
+            let texture_index = model.texture_index(triangle.skin_ref);
+            let size = model.textures.get(texture_index).map_or([1.0; 2], |t| {
+                [t.width.max(1) as f32, t.height.max(1) as f32]
+            });
+
+            material_indices.push(texture_index);
+
+            // face vertices in Blender are in opposite winding order
+            for corner in triangle.vertices.iter().rev() {
+                if let (Some(normal), Some(&(value, bone))) = (
+                    normals.get_mut(corner.vertex),
+                    mesh.normals.get(corner.normal),
+                ) {
+                    *normal += bone_transform(bone).transform_vector3(value.into());
+                }
+
+                flat_polygon_vertice_indices.push(corner.vertex);
+                flat_loop_uvs.extend([
+                    f32::from(corner.s) / size[0],
+                    1.0 - f32::from(corner.t) / size[1],
+                ]);
+            }
+        }
+
+        let mut weight_groups: BTreeMap<u8, BTreeMap<usize, f32>> = BTreeMap::new();
+
+        for (vertex_index, &(_, bone)) in mesh.vertices.iter().enumerate() {
+            if let Ok(bone) = u8::try_from(bone) {
+                weight_groups
+                    .entry(bone)
+                    .or_default()
+                    .insert(vertex_index, 1.0);
+            }
+        }
+
+        Self {
+            name: mesh.name.clone(),
+            normals: normals
+                .into_iter()
+                .map(|n| n.normalize_or_zero().to_array())
+                .collect(),
+            material_indices,
+            collision: false,
+            bodygroup: None,
+            flat_vertices: vertices.iter().flat_map(Vec3::to_array).collect(),
+            flat_polygon_vertice_indices,
+            flat_loop_uvs,
+            weight_groups,
+        }
+    }
+
+    fn dmx(mesh: &DmxMesh) -> Self {
+        let mut weight_groups: BTreeMap<u8, BTreeMap<usize, f32>> = BTreeMap::new();
+
+        for (vertex_index, weights) in mesh.weights.iter().enumerate() {
+            for &(joint, weight) in weights {
+                if let Ok(joint) = u8::try_from(joint) {
+                    weight_groups
+                        .entry(joint)
+                        .or_default()
+                        .insert(vertex_index, weight);
+                }
+            }
+        }
+
+        Self {
+            name: mesh.name.clone(),
+            normals: mesh.normals.iter().map(Vec3::to_array).collect(),
+            material_indices: mesh.triangles.iter().map(|t| t.material).collect(),
+            collision: false,
+            bodygroup: None,
+            flat_vertices: mesh.positions.iter().flat_map(Vec3::to_array).collect(),
+            // dmx faces already have the winding and uv origin of Blender
+            flat_polygon_vertice_indices: mesh.triangles.iter().flat_map(|t| t.positions).collect(),
+            flat_loop_uvs: mesh
+                .triangles
+                .iter()
+                .flat_map(|t| t.uvs)
+                .flatten()
+                .collect(),
+            weight_groups,
+        }
+    }
+
+    fn collision(solid: CollisionSolid, transform: Affine3A, bone: Option<u8>) -> Self {
+        let vertices: Vec<Vec3> = solid
+            .vertices
+            .iter()
+            .map(|&v| transform.transform_point3(v.into()))
+            .collect();
+
+        // collision hulls are convex, so smooth vertex normals are good enough
+        let mut normals = vec![Vec3::ZERO; vertices.len()];
+
+        for face in &solid.faces {
+            let [a, b, c] = face.map(|i| vertices[i]);
+            let normal = (b - a).cross(c - a);
+
+            for &i in face {
+                normals[i] += normal;
+            }
+        }
+
+        let mut weight_groups = BTreeMap::new();
+
+        if let Some(bone) = bone {
+            weight_groups.insert(bone, (0..vertices.len()).map(|i| (i, 1.0)).collect());
+        }
+
+        Self {
+            name: solid.bone_name.map_or_else(
+                || "collision".to_owned(),
+                |name| format!("collision.{name}"),
+            ),
+            normals: normals
+                .into_iter()
+                .map(|n| n.normalize_or_zero().to_array())
+                .collect(),
+            material_indices: vec![0; solid.faces.len()],
+            collision: true,
+            bodygroup: None,
+            flat_vertices: vertices.iter().flat_map(Vec3::to_array).collect(),
+            flat_polygon_vertice_indices: solid
+                .faces
+                .iter()
+                .flat_map(|f| f.iter().rev())
+                .copied()
+                .collect(),
+            flat_loop_uvs: vec![0.0; solid.faces.len() * 6],
+            weight_groups,
+        }
+    }
 }
 
 #[derive(Default)]
@@ -403,7 +1105,12 @@ pub struct PyBoneAnimationData {
 }
 
 impl PyBoneAnimationData {
-    fn new(mut data: BoneAnimationData, bone: &PyLoadedBone, time_factor: f32) -> Self {
+    fn new(
+        mut data: BoneAnimationData,
+        bone: &PyLoadedBone,
+        time_factor: f32,
+        resample: bool,
+    ) -> Self {
         // Animations in MDL replace the bone's initial position and rotation.
         // In Blender, animations are applied on top of the bone's initial position and rotation.
         //
@@ -419,7 +1126,14 @@ impl PyBoneAnimationData {
                 for quaternion in &mut *quaternions {
                     rotation_to_delta(quaternion, bone);
                 }
-                PyAnimationRotationData::Animated(QuaternionData::new(quaternions, time_factor))
+
+                let data = if resample {
+                    let resampled = resample_frames(quaternions, time_factor, Quat::slerp);
+                    QuaternionData::new(&resampled, 1.0)
+                } else {
+                    QuaternionData::new(quaternions, time_factor)
+                };
+                PyAnimationRotationData::Animated(data)
             }
             AnimationData::None => PyAnimationRotationData::None,
         };
@@ -433,7 +1147,14 @@ impl PyBoneAnimationData {
                 for position in &mut *positions {
                     position_to_delta(position, bone);
                 }
-                PyAnimationPositionData::Animated(VectorData::new(positions, time_factor))
+
+                let data = if resample {
+                    let resampled = resample_frames(positions, time_factor, Vec3::lerp);
+                    VectorData::new(&resampled, 1.0)
+                } else {
+                    VectorData::new(positions, time_factor)
+                };
+                PyAnimationPositionData::Animated(data)
             }
             AnimationData::None => PyAnimationPositionData::None,
         };
@@ -442,6 +1163,34 @@ impl PyBoneAnimationData {
     }
 }
 
+/// Interpolates values at the source frame rate onto whole frames of the target frame rate.
+/// The frames cover the whole animation, so the last one holds the last value if the
+/// animation doesn't end on a whole target frame.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn resample_frames<T: Copy>(
+    values: &[T],
+    time_factor: f32,
+    interpolate: impl Fn(T, T, f32) -> T,
+) -> Vec<T> {
+    if values.len() < 2 || time_factor <= 0.0 {
+        return values.to_vec();
+    }
+
+    let last_source = values.len() - 1;
+    // the tolerance keeps rounding errors from adding a frame past an exact end
+    let end = last_source as f32 * time_factor;
+    let frame_count = (end - 1e-3).ceil().max(0.0) as usize + 1;
+
+    (0..frame_count)
+        .map(|frame| {
+            let time = (frame as f32 / time_factor).min(last_source as f32);
+            let index = (time.floor() as usize).min(last_source - 1);
+
+            interpolate(values[index], values[index + 1], time - index as f32)
+        })
+        .collect()
+}
+
 fn rotation_to_delta(quaternion: &mut Quat, bone: &PyLoadedBone) {
     let bone_quaternion = Quat::from_euler(
         EulerRot::ZYX,
@@ -490,15 +1239,17 @@ pub struct PyLoadedBone {
     parent_bone_index: Option<usize>,
     position: [f32; 3],
     rotation: [f32; 3],
+    jiggle: Option<PyJiggleBone>,
 }
 
 impl PyLoadedBone {
-    fn new(bone: LoadedBone) -> Self {
+    fn new(bone: &LoadedBone) -> Self {
         Self {
-            name: bone.name,
+            name: bone.name.clone(),
             parent_bone_index: bone.parent_bone_index,
             position: bone.position,
             rotation: bone.rotation,
+            jiggle: None,
         }
     }
 }
@@ -520,6 +1271,171 @@ impl PyLoadedBone {
     fn rotation(&self) -> [f32; 3] {
         self.rotation
     }
+
+    fn jiggle(&self) -> Option<PyJiggleBone> {
+        self.jiggle.clone()
+    }
+}
+
+#[pyclass(module = "plumber", name = "JiggleBone")]
+#[derive(Clone)]
+pub struct PyJiggleBone(JiggleBone);
+
+#[pymethods]
+impl PyJiggleBone {
+    fn is_flexible(&self) -> bool {
+        self.0.flexible
+    }
+
+    fn length(&self) -> f32 {
+        self.0.length
+    }
+
+    fn tip_mass(&self) -> f32 {
+        self.0.tip_mass
+    }
+
+    fn yaw_stiffness(&self) -> f32 {
+        self.0.yaw_stiffness
+    }
+
+    fn yaw_damping(&self) -> f32 {
+        self.0.yaw_damping
+    }
+
+    fn pitch_stiffness(&self) -> f32 {
+        self.0.pitch_stiffness
+    }
+
+    fn pitch_damping(&self) -> f32 {
+        self.0.pitch_damping
+    }
+
+    fn along_stiffness(&self) -> f32 {
+        self.0.along_stiffness
+    }
+
+    fn along_damping(&self) -> f32 {
+        self.0.along_damping
+    }
+}
+
+#[pyclass(module = "plumber", name = "LoadedAttachment")]
+pub struct PyLoadedAttachment {
+    name: String,
+    parent_bone_index: Option<usize>,
+    position: [f32; 3],
+    rotation: [f32; 3],
+}
+
+impl PyLoadedAttachment {
+    fn new(attachment: Attachment, parent_bone_index: Option<usize>, transform: Affine3A) -> Self {
+        let [x, y, z] = attachment.matrix;
+        let local = Affine3A::from_mat3_translation(
+            Mat3::from_cols(
+                Vec3::new(x[0], y[0], z[0]),
+                Vec3::new(x[1], y[1], z[1]),
+                Vec3::new(x[2], y[2], z[2]),
+            ),
+            Vec3::new(x[3], y[3], z[3]),
+        );
+
+        let (_, rotation, position) = (transform * local).to_scale_rotation_translation();
+
+        Self {
+            name: attachment.name,
+            parent_bone_index,
+            position: position.to_array(),
+            rotation: rot_to_euler(&rotation),
+        }
+    }
+}
+
+#[pymethods]
+impl PyLoadedAttachment {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn parent_bone_index(&self) -> Option<usize> {
+        self.parent_bone_index
+    }
+
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    fn rotation(&self) -> [f32; 3] {
+        self.rotation
+    }
+}
+
+#[pyclass(module = "plumber", name = "Hitbox")]
+pub struct PyHitbox {
+    name: String,
+    bone_index: usize,
+    parent_bone_index: Option<usize>,
+    group: i32,
+    min: [f32; 3],
+    max: [f32; 3],
+    position: [f32; 3],
+    rotation: [f32; 3],
+}
+
+impl PyHitbox {
+    fn new(hitbox: Hitbox, parent_bone_index: Option<usize>, transform: Affine3A) -> Self {
+        let (_, rotation, position) = transform.to_scale_rotation_translation();
+
+        Self {
+            name: hitbox.name,
+            bone_index: hitbox.bone,
+            parent_bone_index,
+            group: hitbox.group,
+            min: hitbox.min,
+            max: hitbox.max,
+            position: position.to_array(),
+            rotation: rot_to_euler(&rotation),
+        }
+    }
+}
+
+#[pymethods]
+impl PyHitbox {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn bone_index(&self) -> usize {
+        self.bone_index
+    }
+
+    fn parent_bone_index(&self) -> Option<usize> {
+        self.parent_bone_index
+    }
+
+    fn group(&self) -> i32 {
+        self.group
+    }
+
+    /// Minimum bounds relative to the bone.
+    fn min(&self) -> [f32; 3] {
+        self.min
+    }
+
+    /// Maximum bounds relative to the bone.
+    fn max(&self) -> [f32; 3] {
+        self.max
+    }
+
+    /// Position of the bone relative to the parent bone, or to the model if there is no parent.
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    /// Rotation of the bone relative to the parent bone, or to the model if there is no parent.
+    fn rotation(&self) -> [f32; 3] {
+        self.rotation
+    }
 }
 
 #[pyclass(module = "plumber", name = "LoadedAnimation")]
@@ -527,20 +1443,82 @@ pub struct PyLoadedAnimation {
     name: String,
     data: BTreeMap<usize, PyBoneAnimationData>,
     looping: bool,
+    frame_end: f32,
+    activity: Option<String>,
+    activity_weight: i32,
 }
 
 impl PyLoadedAnimation {
-    fn new(animation: LoadedAnimation, bones: &[PyLoadedBone], target_fps: f32) -> Self {
-        let time_factor = target_fps / animation.fps;
+    fn new(
+        animation: LoadedAnimation,
+        bones: &[PyLoadedBone],
+        target_fps: f32,
+        resample: bool,
+    ) -> Self {
+        let looping = animation.flags.contains(AnimationDescFlags::LOOPING);
+
+        Self::from_parts(
+            animation.name,
+            animation.data,
+            animation.fps,
+            looping,
+            bones,
+            target_fps,
+            resample,
+        )
+    }
+
+    fn from_sequence(
+        sequence: BlendedSequence,
+        bones: &[PyLoadedBone],
+        target_fps: f32,
+        resample: bool,
+    ) -> Self {
+        let mut animation = Self::from_parts(
+            sequence.name,
+            sequence.data,
+            sequence.fps,
+            sequence.looping,
+            bones,
+            target_fps,
+            resample,
+        );
+
+        animation.activity = sequence.activity;
+        animation.activity_weight = sequence.activity_weight;
+        animation
+    }
+
+    fn from_parts(
+        name: String,
+        data: BTreeMap<usize, BoneAnimationData>,
+        fps: f32,
+        looping: bool,
+        bones: &[PyLoadedBone],
+        target_fps: f32,
+        resample: bool,
+    ) -> Self {
+        let time_factor = target_fps / fps;
+        let last_frame = (sequence::frame_count(&data) - 1) as f32 * time_factor;
 
         Self {
-            name: animation.name,
-            data: animation
-                .data
+            name,
+            data: data
                 .into_iter()
-                .map(|(i, data)| (i, PyBoneAnimationData::new(data, &bones[i], time_factor)))
+                .map(|(i, data)| {
+                    let data = PyBoneAnimationData::new(data, &bones[i], time_factor, resample);
+                    (i, data)
+                })
                 .collect(),
-            looping: animation.flags.contains(AnimationDescFlags::LOOPING),
+            looping,
+            // keyframes start at frame 1
+            frame_end: if resample {
+                last_frame.floor()
+            } else {
+                last_frame
+            } + 1.0,
+            activity: None,
+            activity_weight: 0,
         }
     }
 }
@@ -558,6 +1536,18 @@ impl PyLoadedAnimation {
     fn looping(&self) -> bool {
         self.looping
     }
+
+    fn frame_end(&self) -> f32 {
+        self.frame_end
+    }
+
+    fn activity(&self) -> Option<&str> {
+        self.activity.as_deref()
+    }
+
+    fn activity_weight(&self) -> i32 {
+        self.activity_weight
+    }
 }
 
 #[pyclass(module = "plumber", name = "BoneRestData")]
@@ -576,3 +1566,135 @@ impl PyBoneRestData {
         self.position
     }
 }
+
+/// Summary of a sequence, read without decoding its animations, for listing the sequences
+/// of a model before importing it.
+#[pyclass(module = "plumber", name = "SequencePreview")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PySequencePreview {
+    name: String,
+    activity: Option<String>,
+    looping: bool,
+    delta: bool,
+    fps: f32,
+    frame_count: usize,
+    root_motion: Vec<(f32, [f32; 3])>,
+    events: Vec<(f32, String, String)>,
+}
+
+impl PySequencePreview {
+    /// Previews the sequences of an mdl file. The first animation of a blended sequence
+    /// determines its timing.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the data is not a valid mdl file.
+    pub fn read_all(data: &[u8]) -> Result<Vec<Self>, studio::StudioError> {
+        let animations = studio::animation_infos(data)?;
+
+        Ok(studio::sequences(data)?
+            .into_iter()
+            .map(|sequence| {
+                let animation = sequence.animations.first().and_then(|&i| animations.get(i));
+                let fps = animation
+                    .map(|a| a.fps)
+                    .filter(|&fps| fps > 0.0)
+                    .unwrap_or(30.0);
+                let frame_count = animation.map_or(1, |a| a.frame_count);
+                let duration = frame_count.saturating_sub(1) as f32 / fps;
+
+                // the movements are linear, the positions at their ends form the path
+                let root_motion = animation.map_or_else(Vec::new, |a| {
+                    std::iter::once((0.0, [0.0; 3]))
+                        .chain(a.movements.iter().map(|&(f, p)| (f as f32 / fps, p)))
+                        .collect()
+                });
+
+                let events = sequence
+                    .events
+                    .into_iter()
+                    .map(|e| (e.cycle * duration, e.name, e.options))
+                    .collect();
+
+                Self {
+                    name: sequence.label,
+                    activity: sequence.activity,
+                    looping: sequence.looping,
+                    delta: sequence.delta,
+                    fps,
+                    frame_count,
+                    root_motion,
+                    events,
+                }
+            })
+            .collect())
+    }
+}
+
+#[pymethods]
+impl PySequencePreview {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn activity(&self) -> Option<&str> {
+        self.activity.as_deref()
+    }
+
+    fn looping(&self) -> bool {
+        self.looping
+    }
+
+    /// Delta sequences are layered on top of others, and aren't imported as actions.
+    fn delta(&self) -> bool {
+        self.delta
+    }
+
+    fn fps(&self) -> f32 {
+        self.fps
+    }
+
+    fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// Duration in seconds.
+    fn duration(&self) -> f32 {
+        self.frame_count.saturating_sub(1) as f32 / self.fps
+    }
+
+    /// Path of the root bone as `(time, position)`, with positions in model units
+    /// relative to the start of the sequence.
+    fn root_motion(&self) -> Vec<(f32, [f32; 3])> {
+        self.root_motion.clone()
+    }
+
+    /// Events of the sequence as `(time, name, options)`.
+    fn events(&self) -> Vec<(f32, String, String)> {
+        self.events.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resample_to_last_frame() {
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let values: Vec<f32> = (0..10).map(|v| v as f32).collect();
+
+        // 10 frames at 30 fps end between the frames at 24 fps, after 7.2 frames
+        let resampled = resample_frames(&values, 0.8, lerp);
+        assert_eq!(resampled.len(), 9);
+        assert!((resampled[1] - 1.25).abs() < 1e-5);
+        assert!((resampled[8] - 9.0).abs() < f32::EPSILON);
+
+        // an end on a whole frame isn't repeated
+        let resampled = resample_frames(&values, 2.0, lerp);
+        assert_eq!(resampled.len(), 19);
+        assert!((resampled[18] - 9.0).abs() < f32::EPSILON);
+
+        assert_eq!(resample_frames(&values[..1], 0.8, lerp), [0.0]);
+    }
+}
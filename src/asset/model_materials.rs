@@ -0,0 +1,137 @@
+//! Searches for model materials which the model loader couldn't find in the
+//! `$cdmaterials` directories, as ported models often reference moved materials.
+//! Materials found this way aren't loaded along with the model, so they are only
+//! linked by name to materials imported otherwise.
+
+use std::collections::BTreeMap;
+
+use plumber_core::fs::{DirEntryType, GamePathBuf, OpenFileSystem, ReadDir};
+
+use crate::game_path;
+
+/// Directory searched for materials that weren't found in the `$cdmaterials` directories.
+const FALLBACK_DIR: &str = "materials/models";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Found in one of the `$cdmaterials` directories.
+    Found,
+    /// Found by searching the fallback directory.
+    Fallback,
+    Missing,
+}
+
+impl Resolution {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Resolution::Found => "FOUND",
+            Resolution::Fallback => "FALLBACK",
+            Resolution::Missing => "MISSING",
+        }
+    }
+}
+
+/// Materials in the fallback directory, by their file name.
+#[derive(Debug, Clone, Default)]
+pub struct MaterialIndex(BTreeMap<String, Vec<String>>);
+
+impl MaterialIndex {
+    pub fn new(paths: impl IntoIterator<Item = String>) -> Self {
+        let mut index = Self::default();
+
+        for path in paths {
+            let path = normalize(&path);
+
+            if let Some(stem) = path.strip_suffix(".vmt") {
+                let name = stem.rsplit('/').next().unwrap_or(stem).to_owned();
+                index.0.entry(name).or_default().push(path);
+            }
+        }
+
+        // sorting keeps the chosen material the same regardless of the search path order
+        for paths in index.0.values_mut() {
+            paths.sort();
+            paths.dedup();
+        }
+
+        index
+    }
+
+    /// Lists the materials in the fallback directory of the file system.
+    pub fn read(file_system: &OpenFileSystem) -> Self {
+        let mut paths = Vec::new();
+        collect_vmts(
+            file_system.read_dir(&GamePathBuf::from(FALLBACK_DIR)),
+            &mut paths,
+        );
+
+        Self::new(paths)
+    }
+
+    /// Finds a material by its name in the model. Materials also matching the
+    /// subdirectories of the name are preferred.
+    pub fn find(&self, texture_name: &str) -> Option<&str> {
+        let name = normalize(texture_name);
+        let name = name.strip_suffix(".vmt").unwrap_or(&name);
+        let file_name = name.rsplit('/').next().unwrap_or(name);
+
+        let candidates = self.0.get(file_name)?;
+        let suffix = format!("/{name}.vmt");
+
+        candidates
+            .iter()
+            .find(|p| p.ends_with(&suffix))
+            .or_else(|| candidates.first())
+            .map(String::as_str)
+    }
+}
+
+fn collect_vmts(read_dir: ReadDir, paths: &mut Vec<String>) {
+    for entry in read_dir.flatten() {
+        match entry.entry_type() {
+            DirEntryType::File => {
+                if entry.name().as_str().ends_with(".vmt") {
+                    paths.push(entry.path().as_str().to_owned());
+                }
+            }
+            DirEntryType::Directory => collect_vmts(entry.read_dir(), paths),
+        }
+    }
+}
+
+fn normalize(path: &str) -> String {
+    game_path::normalize(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_fallback_materials() {
+        let index = MaterialIndex::new(
+            [
+                "materials/models/props_c17/metal01.vmt",
+                "materials/models/ported/crate/wood.vmt",
+                "materials/models/ported/wood.vmt",
+                "materials/models/ported/wood_normal.vtf",
+            ]
+            .map(str::to_owned),
+        );
+
+        assert_eq!(
+            index.find("Metal01"),
+            Some("materials/models/props_c17/metal01.vmt")
+        );
+        assert_eq!(
+            index.find("crate\\wood"),
+            Some("materials/models/ported/crate/wood.vmt")
+        );
+        assert_eq!(
+            index.find("wood"),
+            Some("materials/models/ported/crate/wood.vmt")
+        );
+        assert_eq!(index.find("wood_normal"), None);
+        assert_eq!(index.find(""), None);
+    }
+}
@@ -1,18 +1,17 @@
 use std::mem;
 
 use glam::Vec3;
-use plumber_core::vmf::{
-    builder::{BuiltOverlay, BuiltOverlayFace},
-    entities::BaseEntity,
-};
+use plumber_core::vmf::{builder::BuiltOverlay, entities::BaseEntity};
 use pyo3::{prelude::*, types::PyList};
 
+use crate::decals::Decal;
+
 #[pyclass(module = "plumber", name = "BuiltOverlay")]
 pub struct PyBuiltOverlay {
     pub id: i32,
     position: [f32; 3],
     scale: [f32; 3],
-    faces: Vec<BuiltOverlayFace>,
+    face_sizes: Vec<usize>,
     material: String,
     flat_vertices: Vec<f32>,
     flat_polygon_vertice_indices: Vec<usize>,
@@ -38,15 +37,15 @@ impl PyBuiltOverlay {
     }
 
     fn loops_len(&self) -> usize {
-        self.faces.iter().map(|f| f.vertice_indices.len()).sum()
+        self.face_sizes.iter().sum()
     }
 
     fn polygons_len(&self) -> usize {
-        self.faces.len()
+        self.face_sizes.len()
     }
 
     fn polygon_loop_totals<'p>(&self, py: Python<'p>) -> &'p PyList {
-        PyList::new(py, self.faces.iter().map(|f| f.vertice_indices.len()))
+        PyList::new(py, &self.face_sizes)
     }
 
     fn polygon_loop_starts<'p>(&self, py: Python<'p>) -> &'p PyList {
@@ -54,9 +53,9 @@ impl PyBuiltOverlay {
 
         PyList::new(
             py,
-            self.faces.iter().map(|f| {
+            self.face_sizes.iter().map(|size| {
                 let acc_before = acc;
-                acc += f.vertice_indices.len();
+                acc += size;
                 acc_before
             }),
         )
@@ -101,11 +100,48 @@ impl PyBuiltOverlay {
             id: overlay.overlay.entity().id,
             position: overlay.position.into(),
             scale: [overlay.scale, overlay.scale, overlay.scale],
-            faces: overlay.faces,
+            face_sizes: overlay
+                .faces
+                .iter()
+                .map(|f| f.vertice_indices.len())
+                .collect(),
             material: overlay.material.into_string(),
             flat_vertices,
             flat_polygon_vertice_indices,
             flat_loop_uvs,
         }
     }
+
+    /// Decals are imported as overlays, since they are built the same way.
+    pub fn from_decal(decal: Decal, scale: f32) -> Self {
+        let face_sizes: Vec<_> = decal.faces.iter().map(|f| f.vertices.len()).collect();
+
+        let flat_vertices = decal
+            .faces
+            .iter()
+            .flat_map(|f| &f.vertices)
+            .flat_map(Vec3::to_array)
+            .collect();
+
+        let flat_polygon_vertice_indices = (0..face_sizes.iter().sum()).collect();
+
+        let flat_loop_uvs = decal
+            .faces
+            .iter()
+            .flat_map(|f| &f.uvs)
+            // blender has inverted v axis compared to Source
+            .flat_map(|uv| [uv[0], 1.0 - uv[1]])
+            .collect();
+
+        Self {
+            id: decal.id,
+            position: (decal.origin * scale).into(),
+            scale: [scale; 3],
+            face_sizes,
+            material: decal.material,
+            flat_vertices,
+            flat_polygon_vertice_indices,
+            flat_loop_uvs,
+        }
+    }
 }
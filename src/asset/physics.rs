@@ -0,0 +1,232 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    bytes::{self, LeBytes},
+    keyvalues,
+};
+
+/// Conversion factor from IVP physics units (meters) to Source units (inches).
+const METERS_TO_INCHES: f32 = 1.0 / 0.0254;
+
+const COMPACT_SURFACE_SIZE: usize = 48;
+const COMPACT_LEDGE_SIZE: usize = 16;
+const COMPACT_TRIANGLE_SIZE: usize = 16;
+const COMPACT_POINT_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhyError {
+    UnexpectedEof,
+    InvalidOffset,
+}
+
+impl Display for PhyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PhyError::UnexpectedEof => f.write_str("unexpected end of file"),
+            PhyError::InvalidOffset => f.write_str("invalid offset"),
+        }
+    }
+}
+
+impl std::error::Error for PhyError {}
+
+/// A single convex collection of a `.phy` file, with vertices in Source units.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CollisionSolid {
+    /// The bone name from the text section, if present.
+    pub bone_name: Option<String>,
+    pub vertices: Vec<[f32; 3]>,
+    pub faces: Vec<[usize; 3]>,
+}
+
+fn read_le<T: LeBytes>(data: &[u8], offset: usize) -> Result<T, PhyError> {
+    bytes::read_le(data, offset).ok_or(PhyError::UnexpectedEof)
+}
+
+fn relative(base: usize, offset: i32) -> Result<usize, PhyError> {
+    usize::try_from(i64::try_from(base).map_err(|_| PhyError::InvalidOffset)? + i64::from(offset))
+        .map_err(|_| PhyError::InvalidOffset)
+}
+
+/// Parses the collision solids of a `.phy` file.
+///
+/// # Errors
+///
+/// Returns `Err` if the file is truncated or contains invalid offsets.
+pub fn parse_phy(data: &[u8]) -> Result<Vec<CollisionSolid>, PhyError> {
+    let header_size =
+        usize::try_from(read_le::<i32>(data, 0)?).map_err(|_| PhyError::InvalidOffset)?;
+    let solid_count = read_le::<i32>(data, 8)?;
+
+    let mut solids = Vec::new();
+    let mut offset = header_size;
+
+    for _ in 0..solid_count {
+        let size =
+            usize::try_from(read_le::<i32>(data, offset)?).map_err(|_| PhyError::InvalidOffset)?;
+        let start = offset + 4;
+        let solid_data = data
+            .get(start..start + size)
+            .ok_or(PhyError::UnexpectedEof)?;
+
+        solids.push(parse_solid(solid_data)?);
+        offset = start + size;
+    }
+
+    if let Some(text) = data.get(offset..) {
+        apply_text_section(&mut solids, text);
+    }
+
+    Ok(solids)
+}
+
+fn parse_solid(data: &[u8]) -> Result<CollisionSolid, PhyError> {
+    let surface = if data.starts_with(b"VPHY") {
+        // model type 0 is a compact surface, other types (MOPP) are not supported
+        if read_le::<u16>(data, 6)? != 0 {
+            return Ok(CollisionSolid::default());
+        }
+
+        28
+    } else {
+        0
+    };
+
+    let ledgetree_root = relative(surface, read_le::<i32>(data, surface + 32)?)?;
+
+    let mut solid = CollisionSolid::default();
+    let mut vertex_indices = BTreeMap::new();
+    let mut ledge = surface + COMPACT_SURFACE_SIZE;
+
+    while ledge < ledgetree_root {
+        let points = relative(ledge, read_le::<i32>(data, ledge)?)?;
+        // the upper 24 bits hold the ledge size divided by 16
+        let size = usize::try_from(read_le::<u32>(data, ledge + 8)? >> 8)
+            .map_err(|_| PhyError::InvalidOffset)?
+            * 16;
+        let triangle_count = usize::from(read_le::<u16>(data, ledge + 12)?);
+
+        for triangle in 0..triangle_count {
+            let triangle_offset = ledge + COMPACT_LEDGE_SIZE + triangle * COMPACT_TRIANGLE_SIZE;
+            let mut face = [0; 3];
+
+            for (edge, index) in face.iter_mut().enumerate() {
+                let point = usize::from(read_le::<u16>(data, triangle_offset + 4 + edge * 4)?);
+                let point_offset = points + point * COMPACT_POINT_SIZE;
+
+                *index = if let Some(&index) = vertex_indices.get(&point_offset) {
+                    index
+                } else {
+                    let x = read_le::<f32>(data, point_offset)?;
+                    let y = read_le::<f32>(data, point_offset + 4)?;
+                    let z = read_le::<f32>(data, point_offset + 8)?;
+
+                    let index = solid.vertices.len();
+                    solid.vertices.push([
+                        x * METERS_TO_INCHES,
+                        z * METERS_TO_INCHES,
+                        -y * METERS_TO_INCHES,
+                    ]);
+                    vertex_indices.insert(point_offset, index);
+                    index
+                };
+            }
+
+            solid.faces.push(face);
+        }
+
+        if size == 0 {
+            return Err(PhyError::InvalidOffset);
+        }
+
+        ledge += size;
+    }
+
+    Ok(solid)
+}
+
+fn apply_text_section(solids: &mut [CollisionSolid], text: &[u8]) {
+    let text = String::from_utf8_lossy(text);
+    let Ok(entries) = keyvalues::parse(text.trim_end_matches('\0')) else {
+        return;
+    };
+
+    for (key, value) in &entries {
+        if !key.eq_ignore_ascii_case("solid") {
+            continue;
+        }
+
+        let Some(block) = value.as_block() else {
+            continue;
+        };
+
+        let find = |name: &str| {
+            block
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .and_then(|(_, v)| v.as_value())
+        };
+
+        if let (Some(index), Some(name)) = (find("index"), find("name")) {
+            if let Some(solid) = index.parse::<usize>().ok().and_then(|i| solids.get_mut(i)) {
+                solid.bone_name = Some(name.to_owned());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_i32(data: &mut Vec<u8>, value: i32) {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[test]
+    fn parse_single_triangle() {
+        let mut solid = Vec::new();
+        // compact surface header, ledgetree root right after one ledge
+        solid.extend_from_slice(&[0; 32]);
+        push_i32(&mut solid, 80);
+        solid.extend_from_slice(&[0; 12]);
+        // ledge, points after the triangle
+        push_i32(&mut solid, 32);
+        push_i32(&mut solid, 0);
+        push_i32(&mut solid, 2 << 8);
+        solid.extend_from_slice(&1_u16.to_le_bytes());
+        solid.extend_from_slice(&[0; 2]);
+        // triangle
+        push_i32(&mut solid, 0);
+        for point in [0, 1, 2] {
+            push_i32(&mut solid, point);
+        }
+        // points
+        for point in [[0.0, 0.0, 0.0], [0.0254, 0.0, 0.0], [0.0, 0.0254, 0.0_f32]] {
+            for c in point {
+                solid.extend_from_slice(&c.to_le_bytes());
+            }
+            solid.extend_from_slice(&[0; 4]);
+        }
+
+        let mut data = Vec::new();
+        push_i32(&mut data, 16);
+        push_i32(&mut data, 0);
+        push_i32(&mut data, 1);
+        push_i32(&mut data, 0);
+        push_i32(&mut data, i32::try_from(solid.len()).unwrap());
+        data.extend_from_slice(&solid);
+        data.extend_from_slice(b"solid {\n\"index\" \"0\"\n\"name\" \"root\"\n}\n\0");
+
+        let solids = parse_phy(&data).unwrap();
+
+        assert_eq!(solids.len(), 1);
+        assert_eq!(solids[0].bone_name.as_deref(), Some("root"));
+        assert_eq!(solids[0].faces, vec![[0, 1, 2]]);
+        assert!((solids[0].vertices[1][0] - 1.0).abs() < 1e-4);
+        assert!((solids[0].vertices[2][2] + 1.0).abs() < 1e-4);
+    }
+}
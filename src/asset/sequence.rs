@@ -0,0 +1,206 @@
+//! Builds the animations which the game actually plays from model sequences.
+//!
+//! A sequence refers to a grid of animation descriptions which are blended by pose parameters.
+//! Since pose parameters don't exist in Blender, sequences are blended at their default pose,
+//! the center of the blend grid.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use glam::{EulerRot, Quat, Vec3};
+
+use plumber_core::{
+    asset_mdl::{LoadedAnimation, LoadedBone},
+    mdl::{AnimationData, BoneAnimationData},
+};
+
+use super::studio::Sequence;
+
+pub struct BlendedSequence {
+    pub name: String,
+    pub activity: Option<String>,
+    pub activity_weight: i32,
+    pub looping: bool,
+    pub fps: f32,
+    pub data: BTreeMap<usize, BoneAnimationData>,
+}
+
+/// Blends the animations of a sequence.
+/// `animations` maps animation description indices to the loaded animations.
+///
+/// Returns `None` if none of the animations the sequence refers to are loaded.
+pub fn blend_sequence(
+    sequence: &Sequence,
+    animations: &[Option<&LoadedAnimation>],
+    bones: &[LoadedBone],
+) -> Option<BlendedSequence> {
+    let weighted: Vec<(f32, &LoadedAnimation)> = grid_weights(sequence.blend_size)
+        .into_iter()
+        .filter_map(|(cell, weight)| {
+            let index = *sequence.animations.get(cell)?;
+            animations
+                .get(index)
+                .copied()
+                .flatten()
+                .map(|a| (weight, a))
+        })
+        .collect();
+
+    let fps = weighted.first()?.1.fps;
+
+    let frames = weighted
+        .iter()
+        .map(|(_, a)| frame_count(&a.data))
+        .max()
+        .unwrap_or(1);
+
+    let animated_bones: BTreeSet<usize> = weighted
+        .iter()
+        .flat_map(|(_, a)| a.data.keys().copied())
+        .filter(|&bone| bone < bones.len())
+        .collect();
+
+    let data = animated_bones
+        .into_iter()
+        .map(|bone| {
+            let tracks: Vec<_> = weighted
+                .iter()
+                .map(|(weight, a)| (*weight, a.data.get(&bone)))
+                .collect();
+
+            let rest = &bones[bone];
+            let rest_rotation = Quat::from_euler(
+                EulerRot::ZYX,
+                rest.rotation[2],
+                rest.rotation[1],
+                rest.rotation[0],
+            );
+            let rest_position = Vec3::from(rest.position);
+
+            let rotation = blend_track(
+                tracks.iter().map(|(w, d)| (*w, d.map(|d| &d.rotation))),
+                frames,
+                rest_rotation,
+                blend_rotations,
+            );
+
+            let position = blend_track(
+                tracks.iter().map(|(w, d)| (*w, d.map(|d| &d.position))),
+                frames,
+                rest_position,
+                |samples| samples.iter().fold(Vec3::ZERO, |sum, &(w, p)| sum + p * w),
+            );
+
+            (bone, BoneAnimationData { rotation, position })
+        })
+        .collect();
+
+    Some(BlendedSequence {
+        name: sequence.label.clone(),
+        activity: sequence.activity.clone(),
+        activity_weight: sequence.activity_weight,
+        looping: sequence.looping,
+        fps,
+        data,
+    })
+}
+
+/// Returns the amount of frames in the animation data.
+pub fn frame_count(data: &BTreeMap<usize, BoneAnimationData>) -> usize {
+    data.values()
+        .flat_map(|d| [track_len(&d.rotation), track_len(&d.position)])
+        .max()
+        .unwrap_or(1)
+        .max(1)
+}
+
+fn track_len<T>(track: &AnimationData<T>) -> usize {
+    match track {
+        AnimationData::Animated(values) => values.len(),
+        AnimationData::Constant(_) | AnimationData::None => 1,
+    }
+}
+
+/// Weights of the blend grid cells at the center of the grid.
+fn grid_weights(size: [usize; 2]) -> Vec<(usize, f32)> {
+    let center = |n: usize| -> Vec<(usize, f32)> {
+        match n {
+            0 => Vec::new(),
+            n if n % 2 == 1 => vec![(n / 2, 1.0)],
+            n => vec![(n / 2 - 1, 0.5), (n / 2, 0.5)],
+        }
+    };
+
+    let mut weights = Vec::new();
+
+    for (y, y_weight) in center(size[1]) {
+        for &(x, x_weight) in &center(size[0]) {
+            weights.push((y * size[0] + x, x_weight * y_weight));
+        }
+    }
+
+    weights
+}
+
+fn blend_track<'a, T: Copy + 'a>(
+    tracks: impl Iterator<Item = (f32, Option<&'a AnimationData<T>>)>,
+    frames: usize,
+    rest: T,
+    blend: impl Fn(&[(f32, T)]) -> T,
+) -> AnimationData<T> {
+    let tracks: Vec<_> = tracks.collect();
+
+    // animations without data for the bone keep it at the rest pose
+    if tracks
+        .iter()
+        .all(|(_, t)| matches!(t, None | Some(AnimationData::None)))
+    {
+        return AnimationData::None;
+    }
+
+    let animated = tracks
+        .iter()
+        .any(|(_, t)| matches!(t, Some(AnimationData::Animated(_))));
+
+    let sample = |frame: usize| {
+        let samples: Vec<(f32, T)> = tracks
+            .iter()
+            .map(|&(weight, track)| {
+                let value = match track {
+                    Some(AnimationData::Constant(value)) => *value,
+                    Some(AnimationData::Animated(values)) => values
+                        .get(frame)
+                        .or_else(|| values.last())
+                        .copied()
+                        .unwrap_or(rest),
+                    Some(AnimationData::None) | None => rest,
+                };
+
+                (weight, value)
+            })
+            .collect();
+
+        blend(&samples)
+    };
+
+    if animated {
+        AnimationData::Animated((0..frames).map(sample).collect())
+    } else {
+        AnimationData::Constant(sample(0))
+    }
+}
+
+fn blend_rotations(samples: &[(f32, Quat)]) -> Quat {
+    let Some(&(_, reference)) = samples.first() else {
+        return Quat::IDENTITY;
+    };
+
+    let sum = samples
+        .iter()
+        .fold(Quat::from_xyzw(0.0, 0.0, 0.0, 0.0), |sum, &(weight, q)| {
+            // keep the quaternions in the same hemisphere so they don't cancel out
+            let q = if q.dot(reference) < 0.0 { -q } else { q };
+            sum + q * weight
+        });
+
+    sum.normalize()
+}
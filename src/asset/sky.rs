@@ -1,15 +1,109 @@
 use std::f32::consts::{FRAC_PI_2, PI};
-use std::io::Cursor;
+use std::{io::Cursor, mem, str::FromStr};
 
 use float_ord::FloatOrd;
-use image::{ImageBuffer, ImageOutputFormat, Pixel, Rgba32FImage, RgbaImage};
-use pyo3::prelude::*;
+use image::{imageops, ImageBuffer, ImageOutputFormat, Pixel, Rgba32FImage, RgbaImage};
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use plumber_core::asset_vmt::skybox::SkyBoxData;
+
+use crate::{
+    game_path::material_name,
+    keyvalues::{self, KeyValue},
+    kv_utils::block_value,
+};
+
+use super::{
+    cubemap::{read_hdr_vtf, CubemapFaces},
+    material::{correct_hdr_colors, correct_ldr_colors, ColorCorrection},
+};
+
+/// Suffixes of the face textures of a skybox, in the order of [`SkyboxFace`].
+const FACE_SUFFIXES: [&str; 6] = ["lf", "rt", "up", "dn", "ft", "bk"];
+
+/// Material parameters of hdr sky textures, and if the texture is compressed hdr.
+const HDR_TEXTURE_PARAMETERS: [(&str, bool); 3] = [
+    ("$hdrcompressedtexture", true),
+    ("$hdrbasetexture", false),
+    ("$basetexture", false),
+];
+
+/// Layout of the images a skybox is imported as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkyProjection {
+    /// An equirectangular panorama, usable as a world texture.
+    Equi,
+    /// The faces as separate images.
+    Faces,
+    /// The faces in a horizontal cross, four faces wide and three tall.
+    Cross,
+}
+
+impl FromStr for SkyProjection {
+    type Err = PyErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "EQUI" => Ok(Self::Equi),
+            "FACES" => Ok(Self::Faces),
+            "CROSS" => Ok(Self::Cross),
+            _ => Err(PyValueError::new_err("invalid sky projection")),
+        }
+    }
+}
+
+impl Default for SkyProjection {
+    fn default() -> Self {
+        Self::Equi
+    }
+}
 
-use plumber_core::asset_vmt::skybox::{SkyBox, SkyBoxData};
+impl SkyProjection {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Equi => "EQUI",
+            Self::Faces => "FACES",
+            Self::Cross => "CROSS",
+        }
+    }
+}
+
+/// Returns the faces of a loaded skybox. Sdr skyboxes are replaced with their hdr faces
+/// if those are found with `read`, which isn't called for skyboxes already loaded as hdr.
+pub fn skybox_faces(
+    name: &str,
+    data: SkyBoxData,
+    read: impl FnMut(&str) -> Option<Vec<u8>>,
+) -> CubemapFaces {
+    match data {
+        SkyBoxData::Hdr(faces) => CubemapFaces::Hdr(faces),
+        SkyBoxData::Sdr(faces) => {
+            read_hdr_faces(name, read).map_or(CubemapFaces::Sdr(faces), CubemapFaces::Hdr)
+        }
+    }
+}
+
+/// Applies the texture color correction to the faces of a skybox.
+pub fn correct_colors(faces: &mut CubemapFaces, correction: ColorCorrection) {
+    match faces {
+        CubemapFaces::Sdr(images) => {
+            for image in images {
+                correct_ldr_colors(image, correction);
+            }
+        }
+        CubemapFaces::Hdr(images) => {
+            for image in images {
+                correct_hdr_colors(image, correction);
+            }
+        }
+    }
+}
 
+/// A skybox converted into a single image, either equirectangular or a cross.
 #[pyclass(module = "plumber", name = "SkyEqui")]
 pub struct PySkyEqui {
     pub name: String,
+    projection: SkyProjection,
     width: u32,
     height: u32,
     format: &'static str,
@@ -22,6 +116,11 @@ impl PySkyEqui {
         &self.name
     }
 
+    /// `EQUI` or `CROSS`.
+    fn projection(&self) -> &str {
+        self.projection.to_str()
+    }
+
     fn width(&self) -> u32 {
         self.width
     }
@@ -40,37 +139,25 @@ impl PySkyEqui {
 }
 
 impl PySkyEqui {
-    pub fn new(skybox: SkyBox, out_height: Option<u32>) -> Self {
-        let equi = to_equi(skybox.data, out_height);
-
-        let mut data = Vec::new();
-        let format;
-        let width;
-        let height;
-
-        match equi {
-            Equi::Hdr(image) => {
-                width = image.width();
-                height = image.height();
-
-                image
-                    .write_to(&mut Cursor::new(&mut data), ImageOutputFormat::OpenExr)
-                    .unwrap();
-                format = "exr";
-            }
-            Equi::Sdr(image) => {
-                width = image.width();
-                height = image.height();
-
-                image
-                    .write_to(&mut Cursor::new(&mut data), ImageOutputFormat::Tga)
-                    .unwrap();
-                format = "tga";
-            }
-        }
+    /// Converts the faces into a cross if `projection` is [`SkyProjection::Cross`],
+    /// and into an equirectangular image otherwise.
+    pub fn new(
+        name: String,
+        faces: CubemapFaces,
+        projection: SkyProjection,
+        out_height: Option<u32>,
+    ) -> Self {
+        let (projection, image) = if projection == SkyProjection::Cross {
+            (SkyProjection::Cross, to_cross(faces))
+        } else {
+            (SkyProjection::Equi, cubemap_to_equi(faces, out_height))
+        };
+        let (width, height) = image.dimensions();
+        let (format, data) = image.encode();
 
         Self {
-            name: skybox.name.into_string(),
+            name,
+            projection,
             width,
             height,
             format,
@@ -79,6 +166,194 @@ impl PySkyEqui {
     }
 }
 
+/// The faces of a skybox as separate images.
+#[pyclass(module = "plumber", name = "SkyFaces")]
+pub struct PySkyFaces {
+    pub name: String,
+    faces: Vec<PySkyFace>,
+}
+
+#[pymethods]
+impl PySkyFaces {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn faces(&mut self) -> Vec<PySkyFace> {
+        mem::take(&mut self.faces)
+    }
+}
+
+impl PySkyFaces {
+    pub fn new(name: String, faces: CubemapFaces) -> Self {
+        let images: Vec<SkyImage> = match faces {
+            CubemapFaces::Sdr(images) => images.into_iter().map(SkyImage::Sdr).collect(),
+            CubemapFaces::Hdr(images) => images.into_iter().map(SkyImage::Hdr).collect(),
+        };
+
+        let faces = images
+            .iter()
+            .zip(FACE_SUFFIXES)
+            .map(|(image, suffix)| {
+                let (width, height) = image.dimensions();
+                let (format, data) = image.encode();
+
+                PySkyFace {
+                    name: format!("{name}{suffix}"),
+                    suffix,
+                    width,
+                    height,
+                    format,
+                    data,
+                }
+            })
+            .collect();
+
+        Self { name, faces }
+    }
+}
+
+#[pyclass(module = "plumber", name = "SkyFace")]
+pub struct PySkyFace {
+    name: String,
+    suffix: &'static str,
+    width: u32,
+    height: u32,
+    format: &'static str,
+    data: Vec<u8>,
+}
+
+#[pymethods]
+impl PySkyFace {
+    /// Name of the face texture, the sky name followed by the suffix.
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// `rt`, `lf`, `bk`, `ft`, `up` or `dn`, as in the names of sky textures.
+    fn suffix(&self) -> &str {
+        self.suffix
+    }
+
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn format(&self) -> &str {
+        self.format
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// Reads the hdr faces of a skybox from the formats which don't load as hdr otherwise:
+/// float and compressed hdr VTFs, and loose `.pfm` images. Returns `None` unless all
+/// faces are found in one of these formats, and stops reading at the first missing face.
+fn read_hdr_faces(
+    name: &str,
+    mut read: impl FnMut(&str) -> Option<Vec<u8>>,
+) -> Option<[Rgba32FImage; 6]> {
+    let faces: Vec<Rgba32FImage> = FACE_SUFFIXES
+        .iter()
+        .map(|suffix| {
+            let vmt = read(&format!("materials/skybox/{name}_hdr{suffix}.vmt"))
+                .or_else(|| read(&format!("materials/skybox/{name}{suffix}.vmt")))?;
+            let (texture, compressed) = hdr_texture(&String::from_utf8_lossy(&vmt))?;
+
+            read(&format!("materials/{texture}.vtf"))
+                .and_then(|data| read_hdr_vtf(&data, compressed))
+                .or_else(|| read(&format!("materials/{texture}.pfm")).and_then(|d| read_pfm(&d)))
+        })
+        .collect::<Option<_>>()?;
+
+    faces.try_into().ok()
+}
+
+/// Finds the hdr texture of a sky material, without the extension.
+fn hdr_texture(vmt: &str) -> Option<(String, bool)> {
+    let root = keyvalues::parse_raw(vmt).ok()?;
+
+    HDR_TEXTURE_PARAMETERS
+        .iter()
+        .find_map(|&(parameter, compressed)| {
+            let texture = material_name(shader_value(&root, parameter)?, ".vtf");
+
+            Some((texture, compressed))
+        })
+}
+
+/// Finds a parameter of the shader block, ignoring its fallback and proxy blocks.
+fn shader_value<'a>(root: &'a [(String, KeyValue)], key: &str) -> Option<&'a str> {
+    let (_, shader) = root.first()?;
+
+    block_value(shader.as_block()?, key)
+}
+
+/// Reads a portable float map, which hdr skyboxes are made from.
+fn read_pfm(data: &[u8]) -> Option<Rgba32FImage> {
+    // the header is three lines: the channel count, the size and the scale
+    let mut header = Vec::with_capacity(4);
+    let mut offset = 0;
+
+    while header.len() < 4 {
+        let start = offset
+            + data
+                .get(offset..)?
+                .iter()
+                .position(|b| !b.is_ascii_whitespace())?;
+        let end = start
+            + data
+                .get(start..)?
+                .iter()
+                .position(u8::is_ascii_whitespace)?;
+
+        header.push(std::str::from_utf8(&data[start..end]).ok()?);
+        offset = end + 1;
+    }
+
+    let channels = match header[0] {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return None,
+    };
+    let width: u32 = header[1].parse().ok()?;
+    let height: u32 = header[2].parse().ok()?;
+    let scale: f32 = header[3].parse().ok()?;
+
+    let row_size = usize::try_from(width).ok()? * channels * 4;
+    let rows = data.get(offset..offset + row_size * usize::try_from(height).ok()?)?;
+
+    // rows are stored from the bottom up, and a negative scale means little endian
+    let pixels = rows
+        .chunks_exact(row_size)
+        .rev()
+        .flat_map(|row| row.chunks_exact(channels * 4))
+        .flat_map(|pixel| {
+            let mut values = pixel.chunks_exact(4).map(|b| {
+                let bytes = [b[0], b[1], b[2], b[3]];
+                let value = if scale < 0.0 {
+                    f32::from_le_bytes(bytes)
+                } else {
+                    f32::from_be_bytes(bytes)
+                };
+                value * scale.abs()
+            });
+
+            let r = values.next().unwrap_or_default();
+            let [g, b] = [values.next().unwrap_or(r), values.next().unwrap_or(r)];
+            [r, g, b, 1.0]
+        })
+        .collect();
+
+    Rgba32FImage::from_raw(width, height, pixels)
+}
+
 /// Returns a 3D vector pointing to the corresponding pixel location inside a sphere.
 fn spherical_vector(x: u32, y: u32, width: u32, height: u32) -> [f32; 3] {
     let theta = (2.0 * x as f32 / width as f32 - 1.0) * PI;
@@ -164,16 +439,84 @@ fn equi_coords_to_skybox(
     (face, pixel_coords)
 }
 
-pub enum Equi {
+/// A converted sky image.
+pub enum SkyImage {
     Hdr(Rgba32FImage),
     Sdr(RgbaImage),
 }
 
-pub fn to_equi(skybox: SkyBoxData, out_height: Option<u32>) -> Equi {
-    match skybox {
-        SkyBoxData::Sdr(images) => Equi::Sdr(to_equi_inner(&images, out_height)),
-        SkyBoxData::Hdr(images) => Equi::Hdr(to_equi_inner(&images, out_height)),
+impl SkyImage {
+    pub fn dimensions(&self) -> (u32, u32) {
+        match self {
+            SkyImage::Hdr(image) => image.dimensions(),
+            SkyImage::Sdr(image) => image.dimensions(),
+        }
+    }
+
+    /// Encodes the image as exr if it's hdr and tga otherwise,
+    /// returning the format and the data.
+    pub fn encode(&self) -> (&'static str, Vec<u8>) {
+        let mut data = Vec::new();
+
+        match self {
+            SkyImage::Hdr(image) => {
+                image
+                    .write_to(&mut Cursor::new(&mut data), ImageOutputFormat::OpenExr)
+                    .unwrap();
+                ("exr", data)
+            }
+            SkyImage::Sdr(image) => {
+                image
+                    .write_to(&mut Cursor::new(&mut data), ImageOutputFormat::Tga)
+                    .unwrap();
+                ("tga", data)
+            }
+        }
+    }
+}
+
+/// Converts the faces of a cubemap texture, like the ones built for compiled maps.
+pub fn cubemap_to_equi(faces: CubemapFaces, out_height: Option<u32>) -> SkyImage {
+    match faces {
+        CubemapFaces::Sdr(images) => SkyImage::Sdr(to_equi_inner(&images, out_height)),
+        CubemapFaces::Hdr(images) => SkyImage::Hdr(to_equi_inner(&images, out_height)),
+    }
+}
+
+/// Arranges the faces into a horizontal cross, with the top and bottom faces
+/// above and below the second face.
+pub fn to_cross(faces: CubemapFaces) -> SkyImage {
+    match faces {
+        CubemapFaces::Sdr(images) => SkyImage::Sdr(to_cross_inner(&images)),
+        CubemapFaces::Hdr(images) => SkyImage::Hdr(to_cross_inner(&images)),
+    }
+}
+
+/// Cells of the faces in the cross, in the order of [`SkyboxFace`], so that the
+/// edges of adjacent faces meet without rotating them.
+const CROSS_CELLS: [(u32, u32); 6] = [(3, 1), (1, 1), (1, 0), (1, 2), (2, 1), (0, 1)];
+
+fn to_cross_inner<P: Pixel>(
+    images: &[ImageBuffer<P, Vec<P::Subpixel>>; 6],
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let cubemap_dim = images
+        .iter()
+        .flat_map(|i| [i.width(), i.height()])
+        .max()
+        .expect("iterator cannot be empty");
+
+    let mut cross = ImageBuffer::new(cubemap_dim * 4, cubemap_dim * 3);
+
+    for (image, (column, row)) in images.iter().zip(CROSS_CELLS) {
+        imageops::replace(
+            &mut cross,
+            image,
+            i64::from(column * cubemap_dim),
+            i64::from(row * cubemap_dim),
+        );
     }
+
+    cross
 }
 
 trait SubPixelLerp {
@@ -252,3 +595,60 @@ where
 {
     a.map2(b, |a, b| a.lerp(b, factor))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arrange_cross() {
+        let faces = [0, 1, 2, 3, 4, 5].map(|i| RgbaImage::from_pixel(2, 2, image::Rgba([i; 4])));
+        let cross = to_cross_inner(&faces);
+
+        assert_eq!(cross.dimensions(), (8, 6));
+        // top row, middle row from left to right, and bottom row
+        let cells = [(2, 0), (0, 2), (2, 2), (4, 2), (6, 2), (2, 4)];
+        let values = cells.map(|(x, y)| cross.get_pixel(x, y)[0]);
+        assert_eq!(values, [2, 5, 1, 4, 0, 3]);
+        assert_eq!(cross.get_pixel(0, 0)[3], 0);
+    }
+
+    #[test]
+    fn read_pfm_image() {
+        let mut data = b"PF\n2 1\n-2.0\n".to_vec();
+        for value in [0.5f32, 1.0, 2.0, 4.0, 8.0, 16.0] {
+            data.extend(value.to_le_bytes());
+        }
+
+        let image = read_pfm(&data).unwrap();
+        assert_eq!(image.dimensions(), (2, 1));
+        let pixel = image.get_pixel(1, 0).0;
+        assert!(pixel
+            .iter()
+            .zip([8.0, 16.0, 32.0, 1.0])
+            .all(|(a, b)| (a - b).abs() < 1e-6));
+
+        assert!(read_pfm(b"P6\n2 1\n255\n").is_none());
+        assert!(read_pfm(&data[..data.len() - 1]).is_none());
+    }
+
+    #[test]
+    fn find_hdr_texture() {
+        assert_eq!(
+            hdr_texture(
+                r#"Sky { $basetexture skybox/day_hdrup $hdrcompressedtexture "Skybox\Day_HDRup.vtf" }"#
+            ),
+            Some(("skybox/day_hdrup".to_owned(), true))
+        );
+        assert_eq!(
+            hdr_texture("Sky { $basetexture skybox/dayup }"),
+            Some(("skybox/dayup".to_owned(), false))
+        );
+        assert_eq!(
+            hdr_texture(
+                "Sky { $basetexture skybox/dayup \"Sky_dx8\" { $hdrbasetexture skybox/other } }"
+            ),
+            Some(("skybox/dayup".to_owned(), false))
+        );
+    }
+}
@@ -0,0 +1,510 @@
+//! Ambient sound and soundscape entities, imported as speakers playing the sound file
+//! they refer to. The file is found either directly by its path or through the sound
+//! scripts and soundscapes of the game.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    mem,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use log::warn;
+use pyo3::{prelude::*, types::PyBytes};
+
+use plumber_core::{
+    fs::{GamePathBuf, OpenFileSystem},
+    vmf::entities::{BaseEntity, PointEntity, Unknown},
+};
+
+use crate::{
+    game_path,
+    keyvalues::{self, ParseError},
+    kv_utils::{block_value, child_block},
+};
+
+use super::weather::Properties;
+
+pub const AMBIENT_CLASS: &str = "ambient_generic";
+pub const SOUNDSCAPE_CLASS: &str = "env_soundscape";
+
+const GAME_SOUNDS_MANIFEST: &str = "scripts/game_sounds_manifest.txt";
+const SOUNDSCAPES_MANIFEST: &str = "scripts/soundscapes_manifest.txt";
+
+/// Radius of ambient sounds which don't set one, in units.
+const DEFAULT_AMBIENT_RADIUS: f32 = 1250.0;
+/// Soundscapes can play other soundscapes, which are followed up to this depth.
+const MAX_SOUNDSCAPE_DEPTH: usize = 8;
+
+/// Characters prefixed to wave paths in scripts to control how they are mixed.
+const WAVE_PREFIXES: &[char] = &[
+    '*', '#', '@', '<', '>', '^', ')', '(', '}', '$', '~', '&', '?', '.',
+];
+
+/// Returns if the entity class is an ambient sound or a soundscape.
+pub fn is_sound_class(class_name: &str) -> bool {
+    [AMBIENT_CLASS, SOUNDSCAPE_CLASS]
+        .iter()
+        .any(|c| class_name.eq_ignore_ascii_case(c))
+}
+
+/// A wave file played by a sound script or a soundscape.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptSound {
+    /// Path of the file, relative to the game directory.
+    pub path: String,
+    pub volume: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Soundscape {
+    looping: Vec<ScriptSound>,
+    /// Sound script names of looping sounds, resolved when the soundscape is used.
+    looping_scripts: Vec<(String, f32)>,
+    nested: Vec<String>,
+}
+
+/// Sound scripts and soundscapes of the game, by their lowercase name.
+#[derive(Debug, Clone, Default)]
+pub struct SoundScripts {
+    sounds: BTreeMap<String, ScriptSound>,
+    soundscapes: BTreeMap<String, Soundscape>,
+}
+
+impl SoundScripts {
+    /// Reads the scripts listed in the manifests of the file system. Scripts which
+    /// can't be read are skipped.
+    pub fn read(file_system: &OpenFileSystem) -> Self {
+        let mut scripts = Self::default();
+
+        for path in manifest_files(file_system, GAME_SOUNDS_MANIFEST) {
+            if let Some(text) = read_text(file_system, &path) {
+                if let Err(error) = scripts.add_sound_script(&text) {
+                    warn!("sound script `{path}`: {error}");
+                }
+            }
+        }
+
+        for path in manifest_files(file_system, SOUNDSCAPES_MANIFEST) {
+            if let Some(text) = read_text(file_system, &path) {
+                if let Err(error) = scripts.add_soundscapes(&text) {
+                    warn!("soundscapes `{path}`: {error}");
+                }
+            }
+        }
+
+        scripts
+    }
+
+    /// Adds the sounds of a sound script. Sounds already added are kept, like the
+    /// game keeps the first definition.
+    pub fn add_sound_script(&mut self, text: &str) -> Result<(), ParseError> {
+        for (name, value) in keyvalues::parse_raw(text)? {
+            let Some(block) = value.as_block() else {
+                continue;
+            };
+
+            let wave = block_value(block, "wave").or_else(|| {
+                child_block(block, "rndwave").and_then(|waves| block_value(waves, "wave"))
+            });
+
+            if let Some(path) = wave.and_then(wave_path) {
+                self.sounds
+                    .entry(name.to_ascii_lowercase())
+                    .or_insert(ScriptSound {
+                        path,
+                        volume: block_value(block, "volume").map_or(1.0, parse_volume),
+                    });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds the soundscapes of a soundscape file.
+    pub fn add_soundscapes(&mut self, text: &str) -> Result<(), ParseError> {
+        for (name, value) in keyvalues::parse_raw(text)? {
+            let Some(block) = value.as_block() else {
+                continue;
+            };
+
+            let mut soundscape = Soundscape::default();
+
+            for (key, value) in block {
+                let Some(rule) = value.as_block() else {
+                    continue;
+                };
+                let volume = block_value(rule, "volume").map_or(1.0, parse_volume);
+
+                if key.eq_ignore_ascii_case("playlooping") {
+                    if let Some(path) = block_value(rule, "wave").and_then(wave_path) {
+                        soundscape.looping.push(ScriptSound { path, volume });
+                    } else if let Some(script) = block_value(rule, "soundname") {
+                        soundscape
+                            .looping_scripts
+                            .push((script.to_ascii_lowercase(), volume));
+                    }
+                } else if key.eq_ignore_ascii_case("playsoundscape") {
+                    if let Some(nested) = block_value(rule, "name") {
+                        soundscape.nested.push(nested.to_ascii_lowercase());
+                    }
+                }
+            }
+
+            self.soundscapes
+                .entry(name.to_ascii_lowercase())
+                .or_insert(soundscape);
+        }
+
+        Ok(())
+    }
+
+    /// Resolves the sound played by an ambient sound, which is either a path to a file
+    /// or the name of a sound script.
+    pub fn sound(&self, name: &str) -> Option<ScriptSound> {
+        if is_sound_file(name) {
+            return wave_path(name).map(|path| ScriptSound { path, volume: 1.0 });
+        }
+
+        self.sounds.get(&name.trim().to_ascii_lowercase()).cloned()
+    }
+
+    /// Resolves the first looping sound of a soundscape, which is usually its ambience.
+    pub fn soundscape(&self, name: &str) -> Option<ScriptSound> {
+        self.soundscape_at_depth(&name.trim().to_ascii_lowercase(), 0)
+    }
+
+    fn soundscape_at_depth(&self, name: &str, depth: usize) -> Option<ScriptSound> {
+        if depth > MAX_SOUNDSCAPE_DEPTH {
+            return None;
+        }
+
+        let soundscape = self.soundscapes.get(name)?;
+
+        soundscape
+            .looping
+            .first()
+            .cloned()
+            .or_else(|| {
+                soundscape
+                    .looping_scripts
+                    .iter()
+                    .find_map(|(script, volume)| {
+                        self.sounds.get(script).map(|sound| ScriptSound {
+                            path: sound.path.clone(),
+                            volume: sound.volume * volume,
+                        })
+                    })
+            })
+            .or_else(|| {
+                soundscape
+                    .nested
+                    .iter()
+                    .find_map(|nested| self.soundscape_at_depth(nested, depth + 1))
+            })
+    }
+}
+
+fn read_text(file_system: &OpenFileSystem, path: &str) -> Option<String> {
+    let data = file_system.read(&game_path::game_path(path).into()).ok()?;
+
+    Some(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// Lists the script files of a manifest. Every value of the manifest names a file.
+fn manifest_files(file_system: &OpenFileSystem, manifest: &str) -> Vec<String> {
+    let Some(text) = read_text(file_system, manifest) else {
+        return Vec::new();
+    };
+
+    let root = match keyvalues::parse_raw(&text) {
+        Ok(root) => root,
+        Err(error) => {
+            warn!("manifest `{manifest}`: {error}");
+            return Vec::new();
+        }
+    };
+
+    root.iter()
+        .filter_map(|(_, value)| value.as_block())
+        .flatten()
+        .filter_map(|(_, value)| value.as_value())
+        .map(str::to_owned)
+        .collect()
+}
+
+fn is_sound_file(name: &str) -> bool {
+    let name = name.trim().to_ascii_lowercase();
+
+    [".wav", ".mp3", ".ogg"].iter().any(|e| name.ends_with(e))
+}
+
+/// Returns the path of a wave in a script, relative to the game directory. Sentences,
+/// which are prefixed with `!`, don't refer to a file.
+fn wave_path(wave: &str) -> Option<String> {
+    let wave = wave.trim();
+
+    if wave.starts_with('!') {
+        return None;
+    }
+
+    let wave = game_path::normalize(wave.trim_start_matches(WAVE_PREFIXES));
+
+    if wave.is_empty() {
+        return None;
+    }
+
+    Some(format!("sound/{wave}"))
+}
+
+/// Parses the volume of a script, which can be a `min, max` range or `VOL_NORM`.
+fn parse_volume(value: &str) -> f32 {
+    let parts: Vec<f32> = value
+        .split(',')
+        .filter_map(|p| {
+            let p = p.trim();
+            if p.eq_ignore_ascii_case("vol_norm") {
+                Some(1.0)
+            } else {
+                p.parse().ok()
+            }
+        })
+        .collect();
+
+    if parts.is_empty() {
+        1.0
+    } else {
+        #[allow(clippy::cast_precision_loss)]
+        let count = parts.len() as f32;
+        parts.iter().sum::<f32>() / count
+    }
+}
+
+/// Paths of the sound files which were already read, so that each file is only read
+/// and sent with the first entity playing it.
+#[derive(Debug, Clone, Default)]
+pub struct SentSounds(Arc<Mutex<BTreeSet<String>>>);
+
+impl SentSounds {
+    /// Returns `false` if the file was already sent.
+    fn send(&self, path: &str) -> bool {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(path.to_owned())
+    }
+}
+
+#[pyclass(module = "plumber", name = "Sound")]
+pub struct PySound {
+    pub id: i32,
+    kind: &'static str,
+    name: String,
+    path: Option<String>,
+    data: Option<Vec<u8>>,
+    position: [f32; 3],
+    volume: f32,
+    radius: Option<f32>,
+    enabled: bool,
+    properties: BTreeMap<String, String>,
+}
+
+#[pymethods]
+impl PySound {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn kind(&self) -> &str {
+        self.kind
+    }
+
+    /// Sound script, file or soundscape the entity refers to.
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Path of the resolved sound file, if it was found.
+    fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// Contents of the sound file, only for the first sound playing it.
+    fn data<'py>(&mut self, py: Python<'py>) -> Option<&'py PyBytes> {
+        self.data.take().map(|data| PyBytes::new(py, &data))
+    }
+
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    /// Volume from 0 to 1.
+    fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// Distance the sound is audible within, `None` if it's heard everywhere.
+    fn radius(&self) -> Option<f32> {
+        self.radius
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn properties(&mut self) -> BTreeMap<String, String> {
+        mem::take(&mut self.properties)
+    }
+}
+
+impl PySound {
+    pub fn new(
+        entity: &Unknown,
+        scale: f32,
+        scripts: &SoundScripts,
+        sent: &SentSounds,
+        file_system: &OpenFileSystem,
+    ) -> Self {
+        let properties = Properties::new(entity);
+        let spawn_flags = properties
+            .get("spawnflags")
+            .and_then(|v| v.parse::<i32>().ok())
+            .unwrap_or(0);
+
+        let is_soundscape = entity
+            .entity()
+            .class_name
+            .eq_ignore_ascii_case(SOUNDSCAPE_CLASS);
+
+        let (kind, sound, name, volume, radius, enabled) = if is_soundscape {
+            let name = properties.get("soundscape").unwrap_or_default().to_owned();
+            let radius = properties.f32_or("radius", 128.0);
+
+            (
+                "SOUNDSCAPE",
+                scripts.soundscape(&name),
+                name,
+                1.0,
+                // a negative radius makes the soundscape audible everywhere
+                (radius >= 0.0).then_some(radius * scale),
+                !properties.bool_or("startdisabled", false),
+            )
+        } else {
+            let name = properties.get("message").unwrap_or_default().to_owned();
+            let radius = properties.f32_or("radius", DEFAULT_AMBIENT_RADIUS);
+
+            (
+                "AMBIENT",
+                scripts.sound(&name),
+                name,
+                (properties.f32_or("health", 10.0) / 10.0).clamp(0.0, 1.0),
+                // flag 1 plays the sound everywhere
+                (spawn_flags & 1 == 0).then_some(radius * scale),
+                // flag 16 starts the sound silent
+                spawn_flags & 16 == 0,
+            )
+        };
+
+        let data = sound
+            .as_ref()
+            .filter(|s| sent.send(&s.path))
+            .and_then(|sound| {
+                file_system
+                    .read(&GamePathBuf::from(sound.path.as_str()).into())
+                    .map_err(|error| warn!("sound `{}`: could not read: {error}", sound.path))
+                    .ok()
+            });
+
+        if sound.is_none() && !name.is_empty() {
+            warn!("sound `{name}`: could not resolve the sound file");
+        }
+
+        Self {
+            id: entity.entity().id,
+            kind,
+            path: sound.as_ref().map(|s| s.path.clone()),
+            volume: volume * sound.as_ref().map_or(1.0, |s| s.volume),
+            name,
+            data,
+            position: (entity.origin().unwrap_or_default() * scale).into(),
+            radius,
+            enabled,
+            properties: properties.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn send_sound_files_once() {
+        let sent = SentSounds::default();
+        assert!(sent.send("sound/ambient/wind1.wav"));
+        assert!(sent.clone().send("sound/ambient/wind2.wav"));
+        assert!(!sent.send("sound/ambient/wind1.wav"));
+    }
+
+    #[test]
+    fn resolve_sound_scripts() {
+        let mut scripts = SoundScripts::default();
+
+        scripts
+            .add_sound_script(
+                r#"
+                "Ambient.Wind"
+                {
+                    "channel" "CHAN_STATIC"
+                    "volume" "0.4, 0.6"
+                    "wave" ")Ambient\Wind_Loop.wav"
+                }
+                "Ambient.Random"
+                {
+                    "volume" "VOL_NORM"
+                    "rndwave" { "wave" "^ambient/random1.wav" "wave" "ambient/random2.wav" }
+                }
+                "Npc.Sentence" { "wave" "!HG_ALERT" }
+                "#,
+            )
+            .unwrap();
+
+        scripts
+            .add_soundscapes(
+                r#"
+                "outdoors"
+                {
+                    "dsp" "1"
+                    "playlooping" { "volume" "0.5" "soundname" "Ambient.Wind" }
+                    "playrandom" { "rndwave" { "wave" "ambient/birds.wav" } }
+                }
+                "city"
+                {
+                    "playsoundscape" { "name" "outdoors" "volume" "1" }
+                }
+                "#,
+            )
+            .unwrap();
+
+        let wind = scripts.sound("ambient.wind").unwrap();
+        assert_eq!(wind.path, "sound/ambient/wind_loop.wav");
+        assert!((wind.volume - 0.5).abs() < 1e-6);
+
+        assert_eq!(
+            scripts.sound("Ambient.Random").unwrap().path,
+            "sound/ambient/random1.wav"
+        );
+        assert_eq!(scripts.sound("Npc.Sentence"), None);
+        assert_eq!(
+            scripts.sound("*Ambient\\Machines\\Hum.WAV").unwrap().path,
+            "sound/ambient/machines/hum.wav"
+        );
+
+        let city = scripts.soundscape("City").unwrap();
+        assert_eq!(city.path, "sound/ambient/wind_loop.wav");
+        assert!((city.volume - 0.25).abs() < 1e-6);
+        assert_eq!(scripts.soundscape("missing"), None);
+
+        assert!(is_sound_class("Ambient_Generic"));
+        assert!(!is_sound_class("env_sprite"));
+    }
+}
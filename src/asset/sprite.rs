@@ -0,0 +1,236 @@
+//! Sprite and spotlight entities, imported as glow cards with their sprite material.
+//! Spotlights also get a placeholder for their volumetric beam.
+
+use std::{collections::BTreeMap, mem};
+
+use pyo3::prelude::*;
+
+use plumber_core::vmf::entities::{AngledEntity, BaseEntity, PointEntity, Unknown};
+
+use crate::game_path::material_name;
+
+use super::weather::Properties;
+
+pub const SPRITE_CLASS: &str = "env_sprite";
+pub const SPOTLIGHT_CLASS: &str = "point_spotlight";
+
+/// Material used by sprites which don't set one.
+const DEFAULT_SPRITE_MATERIAL: &str = "sprites/glow01.vmt";
+/// Materials the game uses for the halo and beam of spotlights.
+const SPOTLIGHT_HALO_MATERIAL: &str = "sprites/light_glow03.vmt";
+const SPOTLIGHT_BEAM_MATERIAL: &str = "sprites/glow_test02.vmt";
+
+/// Width of a sprite at scale 1 in units. The actual width depends on the resolution
+/// of the sprite texture, which is commonly 64 pixels for glows.
+const SPRITE_SIZE: f32 = 64.0;
+/// Width of the halo of spotlights in units.
+const SPOTLIGHT_HALO_SIZE: f32 = 60.0;
+
+/// Returns if the entity class is a sprite or a spotlight.
+pub fn is_sprite_class(class_name: &str) -> bool {
+    [SPRITE_CLASS, SPOTLIGHT_CLASS]
+        .iter()
+        .any(|c| class_name.eq_ignore_ascii_case(c))
+}
+
+/// Returns the path of the material of a sprite model. Sprites can refer to their
+/// material with the legacy `.spr` extension.
+fn sprite_material(model: &str) -> String {
+    let model = material_name(model, ".vmt");
+    let model = model.strip_suffix(".spr").unwrap_or(&model);
+
+    format!("materials/{model}.vmt")
+}
+
+#[pyclass(module = "plumber", name = "Sprite")]
+pub struct PySprite {
+    pub id: i32,
+    kind: &'static str,
+    position: [f32; 3],
+    rotation: [f32; 3],
+    material: String,
+    size: f32,
+    color: [f32; 3],
+    alpha: f32,
+    render_mode: i32,
+    hdr_color_scale: f32,
+    glow_proxy_size: f32,
+    enabled: bool,
+    beam: Option<(String, f32, f32)>,
+    properties: BTreeMap<String, String>,
+}
+
+#[pymethods]
+impl PySprite {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn kind(&self) -> &str {
+        self.kind
+    }
+
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    fn rotation(&self) -> [f32; 3] {
+        self.rotation
+    }
+
+    fn material(&self) -> &str {
+        &self.material
+    }
+
+    /// Width of the glow card.
+    fn size(&self) -> f32 {
+        self.size
+    }
+
+    fn color(&self) -> [f32; 3] {
+        self.color
+    }
+
+    /// Opacity from 0 to 1.
+    fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    fn render_mode(&self) -> i32 {
+        self.render_mode
+    }
+
+    /// Brightness multiplier of the sprite with hdr lighting.
+    fn hdr_color_scale(&self) -> f32 {
+        self.hdr_color_scale
+    }
+
+    /// Size of the area tested for visibility, which fades the glow when occluded.
+    fn glow_proxy_size(&self) -> f32 {
+        self.glow_proxy_size
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Material of the volumetric beam, if the entity has one.
+    fn beam_material(&self) -> Option<&str> {
+        self.beam.as_ref().map(|(material, _, _)| material.as_str())
+    }
+
+    /// Length of the beam along the local x axis.
+    fn beam_length(&self) -> Option<f32> {
+        self.beam.as_ref().map(|&(_, length, _)| length)
+    }
+
+    /// Width of the beam at its origin.
+    fn beam_width(&self) -> Option<f32> {
+        self.beam.as_ref().map(|&(_, _, width)| width)
+    }
+
+    fn properties(&mut self) -> BTreeMap<String, String> {
+        mem::take(&mut self.properties)
+    }
+}
+
+impl PySprite {
+    pub fn new(entity: &Unknown, scale: f32) -> Self {
+        let properties = Properties::new(entity);
+        let rotation = entity.angles().unwrap_or_default();
+        let spawn_flags = properties
+            .get("spawnflags")
+            .and_then(|v| v.parse::<i32>().ok())
+            .unwrap_or(0);
+
+        let is_spotlight = entity
+            .entity()
+            .class_name
+            .eq_ignore_ascii_case(SPOTLIGHT_CLASS);
+
+        let (kind, material, size, beam) = if is_spotlight {
+            let beam = (
+                sprite_material(SPOTLIGHT_BEAM_MATERIAL),
+                properties.f32_or("spotlightlength", 500.0) * scale,
+                properties.f32_or("spotlightwidth", 50.0) * scale,
+            );
+
+            (
+                "SPOTLIGHT",
+                sprite_material(SPOTLIGHT_HALO_MATERIAL),
+                SPOTLIGHT_HALO_SIZE * scale,
+                Some(beam),
+            )
+        } else {
+            let sprite_scale = Some(properties.f32_or("scale", 1.0))
+                .filter(|s| *s > 0.0)
+                .unwrap_or(1.0);
+
+            (
+                "SPRITE",
+                sprite_material(properties.get("model").unwrap_or(DEFAULT_SPRITE_MATERIAL)),
+                SPRITE_SIZE * sprite_scale * scale,
+                None,
+            )
+        };
+
+        // sprites without a name can't be turned on, so they always start on
+        let enabled = spawn_flags & 1 != 0
+            || (!is_spotlight && properties.get("targetname").unwrap_or_default().is_empty());
+
+        Self {
+            id: entity.entity().id,
+            kind,
+            position: (entity.origin().unwrap_or_default() * scale).into(),
+            rotation: [
+                rotation[2].to_radians(),
+                rotation[0].to_radians(),
+                rotation[1].to_radians(),
+            ],
+            material,
+            size,
+            color: properties.color_or("rendercolor", [1.0; 3]),
+            alpha: (properties.f32_or("renderamt", 255.0) / 255.0).clamp(0.0, 1.0),
+            render_mode: properties
+                .get("rendermode")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+            hdr_color_scale: properties.f32_or("hdrcolorscale", 1.0),
+            glow_proxy_size: properties.f32_or("glowproxysize", 2.0) * scale,
+            enabled,
+            beam,
+            properties: properties.0,
+        }
+    }
+
+    /// Materials of the glow card and the beam, which the importer loads afterwards.
+    pub fn materials(&self) -> impl Iterator<Item = &str> {
+        [Some(self.material.as_str()), self.beam_material()]
+            .into_iter()
+            .flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_sprite_materials() {
+        assert_eq!(
+            sprite_material("sprites/glow01.spr"),
+            "materials/sprites/glow01.vmt"
+        );
+        assert_eq!(
+            sprite_material("Materials\\Sprites\\Light_Glow03.vmt"),
+            "materials/sprites/light_glow03.vmt"
+        );
+        assert_eq!(
+            sprite_material("sprites/redglow1"),
+            "materials/sprites/redglow1.vmt"
+        );
+
+        assert!(is_sprite_class("Point_Spotlight"));
+        assert!(!is_sprite_class("env_sun"));
+    }
+}
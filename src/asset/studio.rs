@@ -0,0 +1,878 @@
+//! Reads parts of the studio model (`.mdl`) header which are not exposed by the model loader.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::bytes::{self, LeBytes};
+
+const NUM_BONES: usize = 156;
+const BONE_INDEX: usize = 160;
+const NUM_HITBOX_SETS: usize = 172;
+const HITBOX_SET_INDEX: usize = 176;
+const NUM_LOCAL_ANIM: usize = 180;
+const LOCAL_ANIM_INDEX: usize = 184;
+const NUM_LOCAL_SEQ: usize = 188;
+const LOCAL_SEQ_INDEX: usize = 192;
+const NUM_TEXTURES: usize = 204;
+const TEXTURE_INDEX: usize = 208;
+const NUM_CD_TEXTURES: usize = 212;
+const CD_TEXTURE_INDEX: usize = 216;
+const NUM_SKIN_REF: usize = 220;
+const NUM_SKIN_FAMILIES: usize = 224;
+const SKIN_INDEX: usize = 228;
+const NUM_BODY_PARTS: usize = 232;
+const BODY_PART_INDEX: usize = 236;
+const NUM_LOCAL_ATTACHMENTS: usize = 240;
+const LOCAL_ATTACHMENT_INDEX: usize = 244;
+
+const BONE_SIZE: usize = 216;
+const BONE_PROC_TYPE: usize = 164;
+const BONE_PROC_INDEX: usize = 168;
+const PROC_TYPE_JIGGLE: i32 = 5;
+
+const JIGGLE_IS_FLEXIBLE: i32 = 0x01;
+
+const TEXTURE_SIZE: usize = 64;
+
+const BODY_PART_SIZE: usize = 16;
+const MODEL_SIZE: usize = 148;
+
+const HITBOX_SET_SIZE: usize = 12;
+const HITBOX_SIZE: usize = 68;
+
+const ATTACHMENT_SIZE: usize = 92;
+
+const ANIM_DESC_SIZE: usize = 100;
+const SEQUENCE_SIZE: usize = 212;
+
+const MOVEMENT_SIZE: usize = 44;
+const EVENT_SIZE: usize = 80;
+
+const SEQUENCE_LOOPING: i32 = 0x0001;
+const SEQUENCE_DELTA: i32 = 0x0004;
+
+/// Events of this type are named, older events only have a number.
+const EVENT_NEW_STYLE: i32 = 1 << 10;
+
+/// Data read directly from the mdl file.
+#[derive(Debug, Clone, Default)]
+pub struct StudioData {
+    pub skin_families: Vec<Vec<usize>>,
+    pub attachments: Vec<Attachment>,
+    pub jiggle_bones: BTreeMap<usize, JiggleBone>,
+    pub hitbox_sets: Vec<HitboxSet>,
+    pub animation_names: Vec<String>,
+    pub sequences: Vec<Sequence>,
+    pub texture_names: Vec<String>,
+    pub material_dirs: Vec<String>,
+    pub body_parts: Vec<BodyPart>,
+}
+
+impl StudioData {
+    /// Reads each section separately, so that a section which can't be read only drops
+    /// its own data. Returns the errors of the sections with their names.
+    pub fn read(data: &[u8]) -> (Self, Vec<(&'static str, StudioError)>) {
+        let mut errors = Vec::new();
+
+        if let Err(error) = check_header(data) {
+            errors.push(("header", error));
+            return (Self::default(), errors);
+        }
+
+        let studio_data = Self {
+            skin_families: section(&mut errors, "skin families", skin_families(data)),
+            attachments: section(&mut errors, "attachments", attachments(data)),
+            jiggle_bones: section(&mut errors, "jiggle bones", jiggle_bones(data)),
+            hitbox_sets: section(&mut errors, "hitbox sets", hitbox_sets(data)),
+            animation_names: section(&mut errors, "animation names", animation_names(data)),
+            sequences: section(&mut errors, "sequences", sequences(data)),
+            texture_names: section(&mut errors, "texture names", texture_names(data)),
+            material_dirs: section(&mut errors, "material directories", material_dirs(data)),
+            body_parts: section(&mut errors, "body parts", body_parts(data)),
+        };
+
+        (studio_data, errors)
+    }
+}
+
+/// Returns the data of a section, or records its error and returns nothing.
+fn section<T: Default>(
+    errors: &mut Vec<(&'static str, StudioError)>,
+    name: &'static str,
+    result: Result<T, StudioError>,
+) -> T {
+    result.unwrap_or_else(|error| {
+        errors.push((name, error));
+        T::default()
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attachment {
+    pub name: String,
+    pub bone: usize,
+    /// Transform relative to the bone, as a row-major 3x4 matrix.
+    pub matrix: [[f32; 4]; 3],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StudioError {
+    InvalidHeader,
+    UnexpectedEof,
+}
+
+impl Display for StudioError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            StudioError::InvalidHeader => f.write_str("invalid mdl header"),
+            StudioError::UnexpectedEof => f.write_str("unexpected end of file"),
+        }
+    }
+}
+
+impl std::error::Error for StudioError {}
+
+pub(super) fn read_le<T: LeBytes>(data: &[u8], offset: usize) -> Result<T, StudioError> {
+    bytes::read_le(data, offset).ok_or(StudioError::UnexpectedEof)
+}
+
+pub(super) fn read_usize(data: &[u8], offset: usize) -> Result<usize, StudioError> {
+    usize::try_from(read_le::<i32>(data, offset)?).map_err(|_| StudioError::InvalidHeader)
+}
+
+pub(super) fn read_vector(data: &[u8], offset: usize) -> Result<[f32; 3], StudioError> {
+    Ok([
+        read_f32_at(data, offset, 0)?,
+        read_f32_at(data, offset, 1)?,
+        read_f32_at(data, offset, 2)?,
+    ])
+}
+
+fn read_f32_at(data: &[u8], offset: usize, index: usize) -> Result<f32, StudioError> {
+    read_le::<f32>(data, offset + index * 4)
+}
+
+pub(super) fn read_string(data: &[u8], offset: usize) -> Result<String, StudioError> {
+    let bytes = data.get(offset..).ok_or(StudioError::UnexpectedEof)?;
+    let end = bytes
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(StudioError::UnexpectedEof)?;
+
+    Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+}
+
+fn check_header(data: &[u8]) -> Result<(), StudioError> {
+    if data.starts_with(b"IDST") {
+        Ok(())
+    } else {
+        Err(StudioError::InvalidHeader)
+    }
+}
+
+/// Reads the material names of the model, relative to the `$cdmaterials` directories.
+///
+/// # Errors
+///
+/// Returns `Err` if the data is not a valid mdl file.
+pub fn texture_names(data: &[u8]) -> Result<Vec<String>, StudioError> {
+    check_header(data)?;
+
+    let count = read_usize(data, NUM_TEXTURES)?;
+    let index = read_usize(data, TEXTURE_INDEX)?;
+
+    (0..count)
+        .map(|i| {
+            let offset = index + i * TEXTURE_SIZE;
+            read_string(data, offset + read_usize(data, offset)?)
+        })
+        .collect()
+}
+
+/// Reads the `$cdmaterials` directories the materials are searched from, in order.
+///
+/// # Errors
+///
+/// Returns `Err` if the data is not a valid mdl file.
+pub fn material_dirs(data: &[u8]) -> Result<Vec<String>, StudioError> {
+    check_header(data)?;
+
+    let count = read_usize(data, NUM_CD_TEXTURES)?;
+    let index = read_usize(data, CD_TEXTURE_INDEX)?;
+
+    (0..count)
+        .map(|i| read_string(data, read_usize(data, index + i * 4)?))
+        .collect()
+}
+
+/// Reads the skin family table. Each family maps skin references to texture indices.
+///
+/// # Errors
+///
+/// Returns `Err` if the data is not a valid mdl file.
+pub fn skin_families(data: &[u8]) -> Result<Vec<Vec<usize>>, StudioError> {
+    check_header(data)?;
+
+    let skin_ref_count = read_usize(data, NUM_SKIN_REF)?;
+    let family_count = read_usize(data, NUM_SKIN_FAMILIES)?;
+    let skin_index = read_usize(data, SKIN_INDEX)?;
+
+    (0..family_count)
+        .map(|family| {
+            (0..skin_ref_count)
+                .map(|skin_ref| {
+                    let offset = skin_index + (family * skin_ref_count + skin_ref) * 2;
+                    read_le::<u16>(data, offset).map(usize::from)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Reads the attachment points of the model.
+///
+/// # Errors
+///
+/// Returns `Err` if the data is not a valid mdl file.
+pub fn attachments(data: &[u8]) -> Result<Vec<Attachment>, StudioError> {
+    check_header(data)?;
+
+    let count = read_usize(data, NUM_LOCAL_ATTACHMENTS)?;
+    let index = read_usize(data, LOCAL_ATTACHMENT_INDEX)?;
+
+    (0..count)
+        .map(|i| {
+            let offset = index + i * ATTACHMENT_SIZE;
+
+            // the name offset is relative to the attachment
+            let name_offset = read_usize(data, offset)?;
+            let name = read_string(data, offset + name_offset)?;
+            let bone = read_usize(data, offset + 8)?;
+
+            let mut matrix = [[0.0; 4]; 3];
+
+            for (r, row) in matrix.iter_mut().enumerate() {
+                for (c, value) in row.iter_mut().enumerate() {
+                    *value = read_le::<f32>(data, offset + 12 + (r * 4 + c) * 4)?;
+                }
+            }
+
+            Ok(Attachment { name, bone, matrix })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BodyPart {
+    pub name: String,
+    /// Names of the models of the body part, including blank models without meshes.
+    pub models: Vec<String>,
+}
+
+/// Reads the body parts of the model with the names of their models.
+///
+/// # Errors
+///
+/// Returns `Err` if the data is not a valid mdl file.
+pub fn body_parts(data: &[u8]) -> Result<Vec<BodyPart>, StudioError> {
+    check_header(data)?;
+
+    let count = read_usize(data, NUM_BODY_PARTS)?;
+    let index = read_usize(data, BODY_PART_INDEX)?;
+
+    (0..count)
+        .map(|i| {
+            let offset = index + i * BODY_PART_SIZE;
+
+            // the name and model offsets are relative to the body part
+            let name = read_string(data, offset + read_usize(data, offset)?)?;
+            let model_count = read_usize(data, offset + 4)?;
+            let model_index = offset + read_usize(data, offset + 12)?;
+
+            let models = (0..model_count)
+                .map(|model| read_string(data, model_index + model * MODEL_SIZE))
+                .collect::<Result<_, _>>()?;
+
+            Ok(BodyPart { name, models })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JiggleBone {
+    pub flexible: bool,
+    pub length: f32,
+    pub tip_mass: f32,
+    pub yaw_stiffness: f32,
+    pub yaw_damping: f32,
+    pub pitch_stiffness: f32,
+    pub pitch_damping: f32,
+    pub along_stiffness: f32,
+    pub along_damping: f32,
+}
+
+/// Reads the `$jigglebone` parameters of procedural bones, keyed by bone index.
+///
+/// # Errors
+///
+/// Returns `Err` if the data is not a valid mdl file.
+pub fn jiggle_bones(data: &[u8]) -> Result<BTreeMap<usize, JiggleBone>, StudioError> {
+    check_header(data)?;
+
+    let count = read_usize(data, NUM_BONES)?;
+    let index = read_usize(data, BONE_INDEX)?;
+
+    let mut bones = BTreeMap::new();
+
+    for bone in 0..count {
+        let offset = index + bone * BONE_SIZE;
+
+        if read_le::<i32>(data, offset + BONE_PROC_TYPE)? != PROC_TYPE_JIGGLE {
+            continue;
+        }
+
+        // the procedural data offset is relative to the bone
+        let offset = offset + read_usize(data, offset + BONE_PROC_INDEX)?;
+        let flags = read_le::<i32>(data, offset)?;
+        let offset = offset + 4;
+
+        bones.insert(
+            bone,
+            JiggleBone {
+                flexible: flags & JIGGLE_IS_FLEXIBLE != 0,
+                length: read_f32_at(data, offset, 0)?,
+                tip_mass: read_f32_at(data, offset, 1)?,
+                yaw_stiffness: read_f32_at(data, offset, 2)?,
+                yaw_damping: read_f32_at(data, offset, 3)?,
+                pitch_stiffness: read_f32_at(data, offset, 4)?,
+                pitch_damping: read_f32_at(data, offset, 5)?,
+                along_stiffness: read_f32_at(data, offset, 6)?,
+                along_damping: read_f32_at(data, offset, 7)?,
+            },
+        );
+    }
+
+    Ok(bones)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HitboxSet {
+    pub name: String,
+    pub hitboxes: Vec<Hitbox>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hitbox {
+    pub name: String,
+    pub bone: usize,
+    pub group: i32,
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+/// Reads the hitbox sets of the model. Hitbox bounds are relative to their bones.
+///
+/// # Errors
+///
+/// Returns `Err` if the data is not a valid mdl file.
+pub fn hitbox_sets(data: &[u8]) -> Result<Vec<HitboxSet>, StudioError> {
+    check_header(data)?;
+
+    let count = read_usize(data, NUM_HITBOX_SETS)?;
+    let index = read_usize(data, HITBOX_SET_INDEX)?;
+
+    (0..count)
+        .map(|i| {
+            let offset = index + i * HITBOX_SET_SIZE;
+
+            // name and hitbox offsets are relative to the set
+            let name = read_string(data, offset + read_usize(data, offset)?)?;
+            let hitbox_count = read_usize(data, offset + 4)?;
+            let hitbox_index = offset + read_usize(data, offset + 8)?;
+
+            let hitboxes = (0..hitbox_count)
+                .map(|i| {
+                    let offset = hitbox_index + i * HITBOX_SIZE;
+
+                    // the name is optional, and relative to the hitbox
+                    let name = match read_usize(data, offset + 32)? {
+                        0 => String::new(),
+                        name_offset => read_string(data, offset + name_offset)?,
+                    };
+
+                    Ok(Hitbox {
+                        name,
+                        bone: read_usize(data, offset)?,
+                        group: read_le::<i32>(data, offset + 4)?,
+                        min: read_vector(data, offset + 8)?,
+                        max: read_vector(data, offset + 20)?,
+                    })
+                })
+                .collect::<Result<_, _>>()?;
+
+            Ok(HitboxSet { name, hitboxes })
+        })
+        .collect()
+}
+
+/// Reads the names of the animation descriptions, in the order sequences refer to them.
+///
+/// # Errors
+///
+/// Returns `Err` if the data is not a valid mdl file.
+pub fn animation_names(data: &[u8]) -> Result<Vec<String>, StudioError> {
+    check_header(data)?;
+
+    let count = read_usize(data, NUM_LOCAL_ANIM)?;
+    let index = read_usize(data, LOCAL_ANIM_INDEX)?;
+
+    (0..count)
+        .map(|i| {
+            let offset = index + i * ANIM_DESC_SIZE;
+            read_string(data, offset + read_usize(data, offset + 4)?)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationInfo {
+    pub fps: f32,
+    pub frame_count: usize,
+    /// End frames of the linear movements of the root bone, and the positions at them
+    /// relative to the start of the animation.
+    pub movements: Vec<(usize, [f32; 3])>,
+}
+
+/// Reads the frame rates, lengths and root bone movements of the animation descriptions,
+/// without decoding the animations.
+///
+/// # Errors
+///
+/// Returns `Err` if the data is not a valid mdl file.
+pub fn animation_infos(data: &[u8]) -> Result<Vec<AnimationInfo>, StudioError> {
+    check_header(data)?;
+
+    let count = read_usize(data, NUM_LOCAL_ANIM)?;
+    let index = read_usize(data, LOCAL_ANIM_INDEX)?;
+
+    (0..count)
+        .map(|i| {
+            let offset = index + i * ANIM_DESC_SIZE;
+            let movement_count = read_usize(data, offset + 20)?;
+            let movement_index = offset + read_usize(data, offset + 24)?;
+
+            let movements = (0..movement_count)
+                .map(|m| {
+                    let movement = movement_index + m * MOVEMENT_SIZE;
+                    Ok((
+                        read_usize(data, movement)?,
+                        read_vector(data, movement + 32)?,
+                    ))
+                })
+                .collect::<Result<_, _>>()?;
+
+            Ok(AnimationInfo {
+                fps: read_le::<f32>(data, offset + 8)?,
+                frame_count: read_usize(data, offset + 16)?,
+                movements,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SequenceEvent {
+    /// Position of the event in the sequence, from 0 to 1.
+    pub cycle: f32,
+    /// Name of the event, or its number for old events, like `AE_CL_PLAYSOUND` or `5004`.
+    pub name: String,
+    pub options: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sequence {
+    pub label: String,
+    pub activity: Option<String>,
+    pub activity_weight: i32,
+    pub looping: bool,
+    /// Delta sequences are meant to be layered on top of other sequences.
+    pub delta: bool,
+    /// Size of the blend grid, as `[x, y]`.
+    pub blend_size: [usize; 2],
+    /// Animation description indices of the blend grid, in rows of `blend_size[0]`.
+    pub animations: Vec<usize>,
+    pub events: Vec<SequenceEvent>,
+}
+
+/// Reads the sequences of the model.
+///
+/// # Errors
+///
+/// Returns `Err` if the data is not a valid mdl file.
+pub fn sequences(data: &[u8]) -> Result<Vec<Sequence>, StudioError> {
+    check_header(data)?;
+
+    let count = read_usize(data, NUM_LOCAL_SEQ)?;
+    let index = read_usize(data, LOCAL_SEQ_INDEX)?;
+
+    (0..count)
+        .map(|i| {
+            let offset = index + i * SEQUENCE_SIZE;
+
+            // string and array offsets are relative to the sequence
+            let label = read_string(data, offset + read_usize(data, offset + 4)?)?;
+            let activity = read_string(data, offset + read_usize(data, offset + 8)?)?;
+            let flags = read_le::<i32>(data, offset + 12)?;
+            let activity_weight = read_le::<i32>(data, offset + 20)?;
+            let event_count = read_usize(data, offset + 24)?;
+            let event_index = offset + read_usize(data, offset + 28)?;
+            let anim_index = offset + read_usize(data, offset + 60)?;
+            let blend_size = [
+                read_usize(data, offset + 68)?.max(1),
+                read_usize(data, offset + 72)?.max(1),
+            ];
+
+            let animations = (0..blend_size[0] * blend_size[1])
+                .map(|i| read_le::<u16>(data, anim_index + i * 2).map(usize::from))
+                .collect::<Result<_, _>>()?;
+
+            let events = (0..event_count)
+                .map(|e| read_event(data, event_index + e * EVENT_SIZE))
+                .collect::<Result<_, _>>()?;
+
+            Ok(Sequence {
+                label,
+                activity: (!activity.is_empty()).then_some(activity),
+                activity_weight,
+                looping: flags & SEQUENCE_LOOPING != 0,
+                delta: flags & SEQUENCE_DELTA != 0,
+                blend_size,
+                animations,
+                events,
+            })
+        })
+        .collect()
+}
+
+fn read_event(data: &[u8], offset: usize) -> Result<SequenceEvent, StudioError> {
+    let event = read_le::<i32>(data, offset + 4)?;
+    let kind = read_le::<i32>(data, offset + 8)?;
+
+    let name = if kind & EVENT_NEW_STYLE == 0 {
+        event.to_string()
+    } else {
+        read_string(data, offset + read_usize(data, offset + 76)?)?
+    };
+
+    // the options are a fixed size buffer, which may not be terminated
+    let options = data
+        .get(offset + 12..offset + 76)
+        .ok_or(StudioError::UnexpectedEof)?;
+    let end = options
+        .iter()
+        .position(|&b| b == 0)
+        .unwrap_or(options.len());
+
+    Ok(SequenceEvent {
+        cycle: read_le::<f32>(data, offset)?,
+        name,
+        options: String::from_utf8_lossy(&options[..end]).into_owned(),
+    })
+}
+
+/// Remaps a material list, indexed by texture, from the default skin to another skin family.
+pub fn skin_materials<T: Clone>(
+    materials: &[T],
+    families: &[Vec<usize>],
+    family: usize,
+) -> Option<Vec<T>> {
+    let default = families.first()?;
+    let skin = families.get(family)?;
+
+    let mut remapped = materials.to_vec();
+
+    for (&from, &to) in default.iter().zip(skin) {
+        if let (Some(slot), Some(material)) = (remapped.get_mut(from), materials.get(to)) {
+            *slot = material.clone();
+        }
+    }
+
+    Some(remapped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_skin_families() {
+        let mut data = vec![0; 240];
+        data[..4].copy_from_slice(b"IDST");
+        data[NUM_SKIN_REF..NUM_SKIN_REF + 4].copy_from_slice(&2_i32.to_le_bytes());
+        data[NUM_SKIN_FAMILIES..NUM_SKIN_FAMILIES + 4].copy_from_slice(&2_i32.to_le_bytes());
+        data[SKIN_INDEX..SKIN_INDEX + 4].copy_from_slice(&240_i32.to_le_bytes());
+
+        for index in [0_u16, 1, 2, 1] {
+            data.extend_from_slice(&index.to_le_bytes());
+        }
+
+        let families = skin_families(&data).unwrap();
+        assert_eq!(families, vec![vec![0, 1], vec![2, 1]]);
+
+        let materials = ["a", "b", "c"];
+        assert_eq!(
+            skin_materials(&materials, &families, 1),
+            Some(vec!["c", "b", "c"])
+        );
+        assert_eq!(skin_materials(&materials, &families, 2), None);
+    }
+
+    #[test]
+    fn read_sections_separately() {
+        let mut data = vec![0; 256];
+        data[..4].copy_from_slice(b"IDST");
+        data[NUM_SKIN_REF..NUM_SKIN_REF + 4].copy_from_slice(&1_i32.to_le_bytes());
+        data[NUM_SKIN_FAMILIES..NUM_SKIN_FAMILIES + 4].copy_from_slice(&1_i32.to_le_bytes());
+        data[SKIN_INDEX..SKIN_INDEX + 4].copy_from_slice(&256_i32.to_le_bytes());
+        data.extend_from_slice(&0_u16.to_le_bytes());
+
+        // the attachments point past the end of the data
+        data[NUM_LOCAL_ATTACHMENTS..NUM_LOCAL_ATTACHMENTS + 4]
+            .copy_from_slice(&1_i32.to_le_bytes());
+        data[LOCAL_ATTACHMENT_INDEX..LOCAL_ATTACHMENT_INDEX + 4]
+            .copy_from_slice(&1000_i32.to_le_bytes());
+
+        let (studio_data, errors) = StudioData::read(&data);
+        assert_eq!(studio_data.skin_families, vec![vec![0]]);
+        assert!(studio_data.attachments.is_empty());
+        assert_eq!(errors, [("attachments", StudioError::UnexpectedEof)]);
+
+        let (_, errors) = StudioData::read(&data[4..]);
+        assert_eq!(errors, [("header", StudioError::InvalidHeader)]);
+    }
+
+    #[test]
+    fn read_material_names() {
+        let mut data = vec![0; 240];
+        data[..4].copy_from_slice(b"IDST");
+        data[NUM_TEXTURES..NUM_TEXTURES + 4].copy_from_slice(&1_i32.to_le_bytes());
+        data[TEXTURE_INDEX..TEXTURE_INDEX + 4].copy_from_slice(&240_i32.to_le_bytes());
+        data[NUM_CD_TEXTURES..NUM_CD_TEXTURES + 4].copy_from_slice(&2_i32.to_le_bytes());
+        data[CD_TEXTURE_INDEX..CD_TEXTURE_INDEX + 4].copy_from_slice(&304_i32.to_le_bytes());
+
+        // the texture name index is relative to the texture
+        let mut texture = vec![0; TEXTURE_SIZE];
+        texture[..4].copy_from_slice(&72_i32.to_le_bytes());
+        data.extend_from_slice(&texture);
+
+        for offset in [317_i32, 331] {
+            data.extend_from_slice(&offset.to_le_bytes());
+        }
+
+        data.extend_from_slice(b"body\0models/crate/\0models/props/\0");
+
+        assert_eq!(texture_names(&data).unwrap(), ["body"]);
+        assert_eq!(
+            material_dirs(&data).unwrap(),
+            ["models/crate/", "models/props/"]
+        );
+    }
+
+    #[test]
+    fn read_body_parts() {
+        let mut data = vec![0; 248];
+        data[..4].copy_from_slice(b"IDST");
+        data[NUM_BODY_PARTS..NUM_BODY_PARTS + 4].copy_from_slice(&1_i32.to_le_bytes());
+        data[BODY_PART_INDEX..BODY_PART_INDEX + 4].copy_from_slice(&248_i32.to_le_bytes());
+
+        // the models follow the body part, and the name follows the models
+        let name_offset = BODY_PART_SIZE + 2 * MODEL_SIZE;
+        for value in [i32::try_from(name_offset).unwrap(), 2, 1, 16] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        for name in [&b"helmet"[..], b"blank"] {
+            let mut model = vec![0; MODEL_SIZE];
+            model[..name.len()].copy_from_slice(name);
+            data.extend_from_slice(&model);
+        }
+        data.extend_from_slice(b"head\0");
+
+        assert_eq!(
+            body_parts(&data).unwrap(),
+            vec![BodyPart {
+                name: "head".to_owned(),
+                models: vec!["helmet".to_owned(), "blank".to_owned()],
+            }]
+        );
+    }
+
+    #[test]
+    fn read_attachments() {
+        let mut data = vec![0; 248];
+        data[..4].copy_from_slice(b"IDST");
+        data[NUM_LOCAL_ATTACHMENTS..NUM_LOCAL_ATTACHMENTS + 4]
+            .copy_from_slice(&1_i32.to_le_bytes());
+        data[LOCAL_ATTACHMENT_INDEX..LOCAL_ATTACHMENT_INDEX + 4]
+            .copy_from_slice(&248_i32.to_le_bytes());
+
+        let mut attachment = vec![0; ATTACHMENT_SIZE];
+        attachment[..4].copy_from_slice(&92_i32.to_le_bytes());
+        attachment[8..12].copy_from_slice(&3_i32.to_le_bytes());
+
+        let matrix = [
+            [1.0_f32, 0.0, 0.0, 4.0],
+            [0.0, 1.0, 0.0, 5.0],
+            [0.0, 0.0, 1.0, 6.0],
+        ];
+
+        for (i, value) in matrix.iter().flatten().enumerate() {
+            attachment[12 + i * 4..16 + i * 4].copy_from_slice(&value.to_le_bytes());
+        }
+
+        data.extend_from_slice(&attachment);
+        data.extend_from_slice(b"muzzle\0");
+
+        let attachments = attachments(&data).unwrap();
+        assert_eq!(
+            attachments,
+            vec![Attachment {
+                name: "muzzle".to_owned(),
+                bone: 3,
+                matrix,
+            }]
+        );
+    }
+
+    #[test]
+    fn read_jiggle_bones() {
+        let mut data = vec![0; 248];
+        data[..4].copy_from_slice(b"IDST");
+        data[NUM_BONES..NUM_BONES + 4].copy_from_slice(&2_i32.to_le_bytes());
+        data[BONE_INDEX..BONE_INDEX + 4].copy_from_slice(&248_i32.to_le_bytes());
+
+        let mut bones = vec![0; BONE_SIZE * 2];
+        let second = BONE_SIZE + BONE_PROC_TYPE;
+        bones[second..second + 4].copy_from_slice(&PROC_TYPE_JIGGLE.to_le_bytes());
+        let second = BONE_SIZE + BONE_PROC_INDEX;
+        bones[second..second + 4].copy_from_slice(&216_i32.to_le_bytes());
+        data.extend_from_slice(&bones);
+
+        data.extend_from_slice(&JIGGLE_IS_FLEXIBLE.to_le_bytes());
+        for value in [10.0_f32, 1.0, 100.0, 0.5, 90.0, 0.4, 80.0, 0.3] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let jiggle_bones = jiggle_bones(&data).unwrap();
+        assert_eq!(jiggle_bones.len(), 1);
+        assert_eq!(
+            jiggle_bones.get(&1),
+            Some(&JiggleBone {
+                flexible: true,
+                length: 10.0,
+                tip_mass: 1.0,
+                yaw_stiffness: 100.0,
+                yaw_damping: 0.5,
+                pitch_stiffness: 90.0,
+                pitch_damping: 0.4,
+                along_stiffness: 80.0,
+                along_damping: 0.3,
+            })
+        );
+    }
+
+    #[test]
+    fn read_hitbox_sets() {
+        let mut data = vec![0; 248];
+        data[..4].copy_from_slice(b"IDST");
+        data[NUM_HITBOX_SETS..NUM_HITBOX_SETS + 4].copy_from_slice(&1_i32.to_le_bytes());
+        data[HITBOX_SET_INDEX..HITBOX_SET_INDEX + 4].copy_from_slice(&248_i32.to_le_bytes());
+
+        // set name follows the single hitbox
+        let set_name_offset = HITBOX_SET_SIZE + HITBOX_SIZE;
+        for value in [i32::try_from(set_name_offset).unwrap(), 1, 12] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        for value in [2_i32, 3] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [-1.0_f32, -2.0, -3.0, 1.0, 2.0, 3.0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&[0; HITBOX_SIZE - 32]);
+        data.extend_from_slice(b"default\0");
+
+        assert_eq!(
+            hitbox_sets(&data).unwrap(),
+            vec![HitboxSet {
+                name: "default".to_owned(),
+                hitboxes: vec![Hitbox {
+                    name: String::new(),
+                    bone: 2,
+                    group: 3,
+                    min: [-1.0, -2.0, -3.0],
+                    max: [1.0, 2.0, 3.0],
+                }],
+            }]
+        );
+    }
+
+    #[test]
+    fn read_sequences() {
+        let mut data = vec![0; 248];
+        data[..4].copy_from_slice(b"IDST");
+        data[NUM_LOCAL_SEQ..NUM_LOCAL_SEQ + 4].copy_from_slice(&1_i32.to_le_bytes());
+        data[LOCAL_SEQ_INDEX..LOCAL_SEQ_INDEX + 4].copy_from_slice(&248_i32.to_le_bytes());
+
+        let mut sequence = vec![0; SEQUENCE_SIZE];
+        let mut write = |offset: usize, value: i32| {
+            sequence[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        };
+
+        // anim indices, label, activity and events follow the sequence
+        write(4, 216);
+        write(8, 221);
+        write(12, SEQUENCE_LOOPING);
+        write(20, 1);
+        write(24, 1);
+        write(28, 232);
+        write(60, 212);
+        write(68, 2);
+        write(72, 1);
+
+        data.extend_from_slice(&sequence);
+        for index in [3_u16, 4] {
+            data.extend_from_slice(&index.to_le_bytes());
+        }
+        data.extend_from_slice(b"idle\0ACT_IDLE\0\0\0");
+
+        let mut event = vec![0; EVENT_SIZE];
+        event[..4].copy_from_slice(&0.5_f32.to_le_bytes());
+        event[8..12].copy_from_slice(&EVENT_NEW_STYLE.to_le_bytes());
+        event[12..16].copy_from_slice(b"left");
+        event[76..80].copy_from_slice(&80_i32.to_le_bytes());
+        data.extend_from_slice(&event);
+        data.extend_from_slice(b"AE_STEP\0");
+
+        assert_eq!(
+            sequences(&data).unwrap(),
+            vec![Sequence {
+                label: "idle".to_owned(),
+                activity: Some("ACT_IDLE".to_owned()),
+                activity_weight: 1,
+                looping: true,
+                delta: false,
+                blend_size: [2, 1],
+                animations: vec![3, 4],
+                events: vec![SequenceEvent {
+                    cycle: 0.5,
+                    name: "AE_STEP".to_owned(),
+                    options: "left".to_owned(),
+                }],
+            }]
+        );
+    }
+}
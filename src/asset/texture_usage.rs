@@ -0,0 +1,63 @@
+//! Tracks which of the converted textures are used by the imported materials. Textures
+//! whose materials failed to build are converted for nothing, and are reported as orphans.
+
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+#[derive(Debug, Default)]
+struct Usage {
+    emitted: BTreeSet<String>,
+    referenced: BTreeSet<String>,
+}
+
+/// Texture usage shared between the asset handler and the importer.
+#[derive(Debug, Clone, Default)]
+pub struct TextureUsage(Arc<Mutex<Usage>>);
+
+impl TextureUsage {
+    fn usage(&self) -> MutexGuard<'_, Usage> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Returns `false` if the texture was already emitted.
+    pub fn emit(&self, texture: &str) -> bool {
+        self.usage().emitted.insert(texture.to_owned())
+    }
+
+    pub fn reference<'a>(&self, textures: impl IntoIterator<Item = &'a str>) {
+        self.usage()
+            .referenced
+            .extend(textures.into_iter().map(str::to_owned));
+    }
+
+    /// Textures which were converted, but aren't used by any imported material.
+    pub fn orphans(&self) -> Vec<String> {
+        let usage = self.usage();
+
+        usage
+            .emitted
+            .difference(&usage.referenced)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_orphans() {
+        let usage = TextureUsage::default();
+        assert!(usage.emit("materials/brick/brickwall001.vtf"));
+        assert!(usage.emit("materials/metal/metalwall001.vtf"));
+        assert!(!usage.emit("materials/brick/brickwall001.vtf"));
+        usage.reference(["materials/brick/brickwall001.vtf", "materials/missing.vtf"]);
+
+        assert_eq!(usage.orphans(), ["materials/metal/metalwall001.vtf"]);
+    }
+}
@@ -0,0 +1,74 @@
+use std::mem;
+
+use pyo3::prelude::*;
+
+use crate::track_paths::TrackPath;
+
+#[pyclass(module = "plumber", name = "TrackPath")]
+pub struct PyTrackPath {
+    id: i32,
+    class_name: String,
+    name: String,
+    speed: f32,
+    closed: bool,
+    point_names: Vec<String>,
+    point_positions: Vec<[f32; 3]>,
+    point_speeds: Vec<f32>,
+}
+
+#[pymethods]
+impl PyTrackPath {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn class_name(&self) -> &str {
+        &self.class_name
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    fn closed(&self) -> bool {
+        self.closed
+    }
+
+    fn point_names(&mut self) -> Vec<String> {
+        mem::take(&mut self.point_names)
+    }
+
+    fn point_positions(&mut self) -> Vec<[f32; 3]> {
+        mem::take(&mut self.point_positions)
+    }
+
+    fn point_speeds(&mut self) -> Vec<f32> {
+        mem::take(&mut self.point_speeds)
+    }
+}
+
+impl PyTrackPath {
+    pub fn new(path: TrackPath, scale: f32) -> Self {
+        let (point_names, (point_positions, point_speeds)) = path
+            .points
+            .into_iter()
+            .map(|p| (p.name, (p.position.map(|c| c * scale), p.speed * scale)))
+            .unzip();
+
+        Self {
+            id: path.id,
+            class_name: path.class_name,
+            name: path.name,
+            // speeds are in units per second, so they are scaled like distances
+            speed: path.speed * scale,
+            closed: path.closed,
+            point_names,
+            point_positions,
+            point_speeds,
+        }
+    }
+}
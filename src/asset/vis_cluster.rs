@@ -0,0 +1,108 @@
+use std::mem;
+
+use pyo3::{prelude::*, types::PyList};
+
+use crate::bsp::vis::VisCluster;
+
+/// Corners of a box, by whether they are at the maximum on each axis.
+const BOX_CORNERS: [[bool; 3]; 8] = [
+    [false, false, false],
+    [true, false, false],
+    [true, true, false],
+    [false, true, false],
+    [false, false, true],
+    [true, false, true],
+    [true, true, true],
+    [false, true, true],
+];
+
+/// Faces of a box as indices into [`BOX_CORNERS`], facing outwards.
+const BOX_FACES: [[usize; 4]; 6] = [
+    [0, 3, 2, 1],
+    [4, 5, 6, 7],
+    [0, 1, 5, 4],
+    [2, 3, 7, 6],
+    [1, 2, 6, 5],
+    [0, 4, 7, 3],
+];
+
+/// A visibility cluster of a compiled map, as a mesh of the bounding boxes of its leaves.
+#[pyclass(module = "plumber", name = "VisCluster")]
+pub struct PyVisCluster {
+    index: usize,
+    scale: [f32; 3],
+    boxes_len: usize,
+    flat_vertices: Vec<f32>,
+    visible_clusters: Vec<usize>,
+}
+
+#[pymethods]
+impl PyVisCluster {
+    fn index(&self) -> usize {
+        self.index
+    }
+
+    fn scale(&self) -> [f32; 3] {
+        self.scale
+    }
+
+    /// Clusters potentially visible from this cluster, including itself.
+    fn visible_clusters(&mut self) -> Vec<usize> {
+        mem::take(&mut self.visible_clusters)
+    }
+
+    fn vertices(&mut self) -> Vec<f32> {
+        mem::take(&mut self.flat_vertices)
+    }
+
+    fn loops_len(&self) -> usize {
+        self.boxes_len * BOX_FACES.len() * 4
+    }
+
+    fn polygons_len(&self) -> usize {
+        self.boxes_len * BOX_FACES.len()
+    }
+
+    fn polygon_loop_totals<'p>(&self, py: Python<'p>) -> &'p PyList {
+        PyList::new(py, (0..self.polygons_len()).map(|_| 4))
+    }
+
+    fn polygon_loop_starts<'p>(&self, py: Python<'p>) -> &'p PyList {
+        PyList::new(py, (0..self.polygons_len()).map(|i| i * 4))
+    }
+
+    fn polygon_vertices(&self) -> Vec<usize> {
+        (0..self.boxes_len)
+            .flat_map(|b| {
+                BOX_FACES
+                    .iter()
+                    .flatten()
+                    .map(move |&corner| b * BOX_CORNERS.len() + corner)
+            })
+            .collect()
+    }
+}
+
+impl PyVisCluster {
+    pub fn new(cluster: VisCluster, scale: f32) -> Self {
+        let flat_vertices = cluster
+            .leaf_bounds
+            .iter()
+            .flat_map(|(mins, maxs)| {
+                BOX_CORNERS.iter().flat_map(move |corner| {
+                    (0..3).map(move |axis| {
+                        f32::from(if corner[axis] { maxs[axis] } else { mins[axis] })
+                    })
+                })
+            })
+            .collect();
+
+        Self {
+            index: cluster.index,
+            scale: [scale, scale, scale],
+            boxes_len: cluster.leaf_bounds.len(),
+            flat_vertices,
+            visible_clusters: cluster.visible,
+        }
+    }
+}
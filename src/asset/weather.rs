@@ -0,0 +1,338 @@
+//! Wind, precipitation and fog entities, exported as data so that matching effects
+//! can be set up in Blender. None of them have any geometry of their own.
+
+use std::{collections::BTreeMap, mem};
+
+use pyo3::prelude::*;
+
+use plumber_core::vmf::entities::{AngledEntity, BaseEntity, PointEntity, Unknown};
+
+use super::utils::srgb_to_linear;
+
+pub const WIND_CLASS: &str = "env_wind";
+pub const PRECIPITATION_CLASS: &str = "func_precipitation";
+pub const FOG_CONTROLLER_CLASS: &str = "env_fog_controller";
+
+/// Returns if the entity class is one of the weather entities.
+pub fn is_weather_class(class_name: &str) -> bool {
+    [WIND_CLASS, PRECIPITATION_CLASS, FOG_CONTROLLER_CLASS]
+        .iter()
+        .any(|c| class_name.eq_ignore_ascii_case(c))
+}
+
+/// Keyvalues of an entity, looked up case-insensitively.
+pub(crate) struct Properties(pub(crate) BTreeMap<String, String>);
+
+impl Properties {
+    pub(crate) fn new(entity: &Unknown) -> Self {
+        Self(
+            entity
+                .entity()
+                .properties
+                .iter()
+                .map(|(k, v)| (k.as_str().to_owned(), v.clone()))
+                .collect(),
+        )
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.trim())
+    }
+
+    pub(crate) fn f32_or(&self, key: &str, default: f32) -> f32 {
+        self.get(key)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default)
+    }
+
+    pub(crate) fn bool_or(&self, key: &str, default: bool) -> bool {
+        self.get(key)
+            .and_then(|v| v.parse::<i32>().ok())
+            .map_or(default, |v| v != 0)
+    }
+
+    pub(crate) fn color_or(&self, key: &str, default: [f32; 3]) -> [f32; 3] {
+        self.get(key).and_then(parse_color).unwrap_or(default)
+    }
+}
+
+/// Parses a color keyvalue as linear rgb. Trailing values, like brightness, are ignored.
+fn parse_color(value: &str) -> Option<[f32; 3]> {
+    let mut components = value.split_ascii_whitespace().map(str::parse::<f32>);
+
+    let mut color = [0.0; 3];
+    for c in &mut color {
+        *c = srgb_to_linear(components.next()?.ok()? / 255.0);
+    }
+
+    Some(color)
+}
+
+#[pyclass(module = "plumber", name = "Wind")]
+pub struct PyWind {
+    pub id: i32,
+    position: [f32; 3],
+    direction: f32,
+    speed: [f32; 2],
+    gust_speed: [f32; 2],
+    gust_delay: [f32; 2],
+    gust_duration: f32,
+    gust_direction_change: f32,
+    properties: BTreeMap<String, String>,
+}
+
+#[pymethods]
+impl PyWind {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    /// Yaw of the wind in radians.
+    fn direction(&self) -> f32 {
+        self.direction
+    }
+
+    fn min_speed(&self) -> f32 {
+        self.speed[0]
+    }
+
+    fn max_speed(&self) -> f32 {
+        self.speed[1]
+    }
+
+    fn min_gust_speed(&self) -> f32 {
+        self.gust_speed[0]
+    }
+
+    fn max_gust_speed(&self) -> f32 {
+        self.gust_speed[1]
+    }
+
+    fn min_gust_delay(&self) -> f32 {
+        self.gust_delay[0]
+    }
+
+    fn max_gust_delay(&self) -> f32 {
+        self.gust_delay[1]
+    }
+
+    fn gust_duration(&self) -> f32 {
+        self.gust_duration
+    }
+
+    /// Maximum change of direction during gusts in radians.
+    fn gust_direction_change(&self) -> f32 {
+        self.gust_direction_change
+    }
+
+    fn properties(&mut self) -> BTreeMap<String, String> {
+        mem::take(&mut self.properties)
+    }
+}
+
+impl PyWind {
+    pub fn new(entity: &Unknown, scale: f32) -> Self {
+        let properties = Properties::new(entity);
+
+        // speeds are in units per second, so they are scaled like distances
+        Self {
+            id: entity.entity().id,
+            position: (entity.origin().unwrap_or_default() * scale).into(),
+            direction: entity.angles().unwrap_or_default()[1].to_radians(),
+            speed: [
+                properties.f32_or("minwind", 20.0) * scale,
+                properties.f32_or("maxwind", 50.0) * scale,
+            ],
+            gust_speed: [
+                properties.f32_or("mingust", 100.0) * scale,
+                properties.f32_or("maxgust", 250.0) * scale,
+            ],
+            gust_delay: [
+                properties.f32_or("mingustdelay", 10.0),
+                properties.f32_or("maxgustdelay", 20.0),
+            ],
+            gust_duration: properties.f32_or("gustduration", 5.0),
+            gust_direction_change: properties.f32_or("gustdirchange", 20.0).to_radians(),
+            properties: properties.0,
+        }
+    }
+}
+
+#[pyclass(module = "plumber", name = "Precipitation")]
+pub struct PyPrecipitation {
+    pub id: i32,
+    kind: &'static str,
+    density: f32,
+    color: [f32; 3],
+    properties: BTreeMap<String, String>,
+}
+
+#[pymethods]
+impl PyPrecipitation {
+    /// Id of the brush entity holding the precipitation volume.
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn kind(&self) -> &str {
+        self.kind
+    }
+
+    /// Density from 0 to 1.
+    fn density(&self) -> f32 {
+        self.density
+    }
+
+    fn color(&self) -> [f32; 3] {
+        self.color
+    }
+
+    fn properties(&mut self) -> BTreeMap<String, String> {
+        mem::take(&mut self.properties)
+    }
+}
+
+impl PyPrecipitation {
+    pub fn new(entity: &Unknown) -> Self {
+        let properties = Properties::new(entity);
+
+        let kind = properties
+            .get("preciptype")
+            .and_then(|v| v.parse().ok())
+            .map_or("RAIN", precipitation_kind);
+
+        Self {
+            id: entity.entity().id,
+            kind,
+            // the density is stored in the render amount as a percentage
+            density: (properties.f32_or("renderamt", 100.0) / 100.0).clamp(0.0, 1.0),
+            color: properties.color_or("rendercolor", [1.0; 3]),
+            properties: properties.0,
+        }
+    }
+}
+
+fn precipitation_kind(precip_type: i32) -> &'static str {
+    match precip_type {
+        1 => "SNOW",
+        2 => "ASH",
+        3 => "SNOWFALL",
+        4 => "PARTICLE_RAIN",
+        5 => "PARTICLE_ASH",
+        6 => "PARTICLE_RAINSTORM",
+        7 => "PARTICLE_SNOW",
+        _ => "RAIN",
+    }
+}
+
+#[pyclass(module = "plumber", name = "FogController")]
+pub struct PyFogController {
+    pub id: i32,
+    position: [f32; 3],
+    enabled: bool,
+    color: [f32; 3],
+    secondary_color: Option<[f32; 3]>,
+    start: f32,
+    end: f32,
+    max_density: f32,
+    far_z: Option<f32>,
+    properties: BTreeMap<String, String>,
+}
+
+#[pymethods]
+impl PyFogController {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn color(&self) -> [f32; 3] {
+        self.color
+    }
+
+    /// The color used when looking along the fog direction, if fog blending is enabled.
+    fn secondary_color(&self) -> Option<[f32; 3]> {
+        self.secondary_color
+    }
+
+    fn start(&self) -> f32 {
+        self.start
+    }
+
+    fn end(&self) -> f32 {
+        self.end
+    }
+
+    fn max_density(&self) -> f32 {
+        self.max_density
+    }
+
+    /// Distance after which nothing is rendered, if any.
+    fn far_z(&self) -> Option<f32> {
+        self.far_z
+    }
+
+    fn properties(&mut self) -> BTreeMap<String, String> {
+        mem::take(&mut self.properties)
+    }
+}
+
+impl PyFogController {
+    pub fn new(entity: &Unknown, scale: f32) -> Self {
+        let properties = Properties::new(entity);
+
+        let color = properties.color_or("fogcolor", [1.0; 3]);
+        let secondary_color = properties
+            .bool_or("fogblend", false)
+            .then(|| properties.color_or("fogcolor2", color));
+
+        Self {
+            id: entity.entity().id,
+            position: (entity.origin().unwrap_or_default() * scale).into(),
+            enabled: properties.bool_or("fogenable", false),
+            color,
+            secondary_color,
+            start: properties.f32_or("fogstart", 500.0) * scale,
+            end: properties.f32_or("fogend", 2000.0) * scale,
+            max_density: properties.f32_or("fogmaxdensity", 1.0),
+            // a negative far z disables it
+            far_z: Some(properties.f32_or("farz", -1.0))
+                .filter(|z| *z > 0.0)
+                .map(|z| z * scale),
+            properties: properties.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_weather_values() {
+        assert_eq!(parse_color("255 0 255 200"), Some([1.0, 0.0, 1.0]));
+        assert_eq!(parse_color("255 0"), None);
+        assert_eq!(parse_color("white"), None);
+
+        assert_eq!(precipitation_kind(0), "RAIN");
+        assert_eq!(precipitation_kind(7), "PARTICLE_SNOW");
+        assert_eq!(precipitation_kind(-1), "RAIN");
+
+        assert!(is_weather_class("ENV_Wind"));
+        assert!(!is_weather_class("env_sun"));
+    }
+}
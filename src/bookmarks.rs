@@ -0,0 +1,154 @@
+//! Persists the bookmarked directories and the recent imports of the file browser, so that
+//! they are kept across sessions and blend files. They are stored per game as json in the
+//! config directory of the user.
+
+use std::{env, fs, io, path::PathBuf};
+
+use serde_json::{json, Value};
+use tracing::warn;
+
+/// Directory in the config directory where the states are stored.
+const CONFIG_DIR: &str = "plumber";
+const MAX_RECENT: usize = 20;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BrowserState {
+    pub bookmarks: Vec<String>,
+    /// Game paths of recent imports, the most recent first.
+    pub recent: Vec<String>,
+}
+
+impl BrowserState {
+    /// Loads the state of a game. Missing or invalid states are empty.
+    pub fn load(game: &str) -> Self {
+        let Some(path) = state_path(game) else {
+            return Self::default();
+        };
+
+        let Ok(json) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+
+        Self::from_json(&json).unwrap_or_else(|| {
+            warn!("file browser state `{}` is invalid", path.display());
+            Self::default()
+        })
+    }
+
+    /// Saves the state of a game.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the config directory is unknown or the file can't be written.
+    pub fn save(&self, game: &str) -> io::Result<()> {
+        let path = state_path(game)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "config directory not found"))?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // written to a temporary file first so that a crash can't corrupt the state
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, self.to_json())?;
+        fs::rename(temp_path, path)
+    }
+
+    pub fn set_bookmarked(&mut self, path: &str, bookmarked: bool) {
+        self.bookmarks.retain(|b| b != path);
+
+        if bookmarked {
+            self.bookmarks.push(path.to_owned());
+        }
+    }
+
+    pub fn add_recent(&mut self, path: &str) {
+        self.recent.retain(|r| r != path);
+        self.recent.insert(0, path.to_owned());
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    fn to_json(&self) -> String {
+        let value = json!({
+            "bookmarks": self.bookmarks,
+            "recent": self.recent,
+        });
+
+        format!("{value:#}\n")
+    }
+
+    /// Reads an object of string arrays. Unknown keys are ignored.
+    fn from_json(json: &str) -> Option<Self> {
+        let value: Value = serde_json::from_str(json).ok()?;
+        value.as_object()?;
+
+        let strings = |key| value.get(key).map_or(Some(Vec::new()), to_strings);
+
+        Some(Self {
+            bookmarks: strings("bookmarks")?,
+            recent: strings("recent")?,
+        })
+    }
+}
+
+/// Returns the strings of an array, or `None` if the value isn't an array of strings.
+fn to_strings(value: &Value) -> Option<Vec<String>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|v| v.as_str().map(str::to_owned))
+        .collect()
+}
+
+/// The config directory of the user, like `%APPDATA%` on Windows.
+fn config_dir() -> Option<PathBuf> {
+    let dir = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+
+    dir.map(|dir| dir.join(CONFIG_DIR))
+}
+
+fn state_path(game: &str) -> Option<PathBuf> {
+    // game names can contain characters which are invalid in file names
+    let file_name: String = game
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+
+    config_dir().map(|dir| dir.join("browser").join(format!("{file_name}.json")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_json() {
+        let mut state = BrowserState::default();
+        state.set_bookmarked("models/props_junk", true);
+        state.set_bookmarked("materials/\"quoted\"\\dir", true);
+        state.add_recent("maps/a.vmf");
+        state.add_recent("maps/b.vmf");
+        state.add_recent("maps/a.vmf");
+
+        assert_eq!(state.recent, ["maps/a.vmf", "maps/b.vmf"]);
+        assert_eq!(BrowserState::from_json(&state.to_json()), Some(state));
+
+        assert_eq!(
+            BrowserState::from_json(r#"{"recent": [], "version": [], "bookmarks": ["aä"]}"#),
+            Some(BrowserState {
+                bookmarks: vec!["aä".to_owned()],
+                recent: Vec::new(),
+            })
+        );
+        assert_eq!(BrowserState::from_json("{}"), Some(BrowserState::default()));
+        assert_eq!(BrowserState::from_json(r#"{"bookmarks": ["a", []]}"#), None);
+    }
+}
@@ -0,0 +1,157 @@
+//! Reads the keyvalues deciding whether brush entities collide, which the built brushes
+//! don't keep, so that solid geometry can be told apart from triggers and volumes.
+
+use std::collections::HashMap;
+
+use crate::{keyvalues::KeyValue, kv_utils::block_value};
+
+/// Brush entities which never collide, by class name or class name prefix.
+const NON_SOLID_CLASSES: &[&str] = &[
+    "trigger_",
+    "func_areaportal",
+    "func_bomb_target",
+    "func_buyzone",
+    "func_dustcloud",
+    "func_dustmotes",
+    "func_hostage_rescue",
+    "func_illusionary",
+    "func_nav_",
+    "func_occluder",
+    "func_precipitation",
+    "func_smokevolume",
+    "func_viscluster",
+];
+
+/// `Solidity` of `func_brush`.
+const SOLIDITY_NEVER: i32 = 1;
+const SOLIDITY_ALWAYS: i32 = 2;
+/// `func_wall_toggle` spawnflag which starts it invisible and non-solid.
+const WALL_TOGGLE_STARTS_INVISIBLE: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BrushSolidity {
+    /// Whether the brush collides when the map starts.
+    pub solid: bool,
+    /// `Solidity` of `func_brush`, 0 toggling with the entity, 1 never and 2 always solid.
+    pub solidity: Option<i32>,
+    pub start_disabled: bool,
+    pub spawnflags: u32,
+    pub collision_group: Option<i32>,
+}
+
+impl BrushSolidity {
+    fn new(class_name: &str, entity: &[(String, KeyValue)]) -> Self {
+        let int = |key| block_value(entity, key).and_then(|v| v.trim().parse::<i32>().ok());
+
+        let solidity = int("solidity");
+        let start_disabled = int("startdisabled").is_some_and(|v| v != 0);
+        let spawnflags = block_value(entity, "spawnflags")
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(0);
+
+        let solid = if is_non_solid_class(class_name) {
+            false
+        } else if class_name.eq_ignore_ascii_case("func_brush") {
+            match solidity {
+                Some(SOLIDITY_NEVER) => false,
+                Some(SOLIDITY_ALWAYS) => true,
+                _ => !start_disabled,
+            }
+        } else if class_name.eq_ignore_ascii_case("func_wall_toggle") {
+            spawnflags & WALL_TOGGLE_STARTS_INVISIBLE == 0
+        } else {
+            true
+        };
+
+        Self {
+            solid,
+            solidity,
+            start_disabled,
+            spawnflags,
+            collision_group: int("collisiongroup"),
+        }
+    }
+}
+
+/// The solidity of each brush entity of a vmf, by entity id.
+#[derive(Debug, Clone, Default)]
+pub struct BrushSolidities {
+    entities: HashMap<i32, BrushSolidity>,
+}
+
+impl BrushSolidities {
+    pub fn from_vmf(vmf: &[(String, KeyValue)]) -> Self {
+        let entities = vmf
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case("entity"))
+            .filter_map(|(_, v)| v.as_block())
+            .filter(|entity| {
+                entity
+                    .iter()
+                    .any(|(k, v)| k.eq_ignore_ascii_case("solid") && v.as_block().is_some())
+            })
+            .filter_map(|entity| {
+                let id = block_value(entity, "id")?.trim().parse().ok()?;
+                let class_name = block_value(entity, "classname")?;
+
+                Some((id, BrushSolidity::new(class_name, entity)))
+            })
+            .collect();
+
+        Self { entities }
+    }
+
+    /// Returns the solidity of a brush entity, by its class if it isn't in the vmf,
+    /// like the world.
+    pub fn get(&self, id: i32, class_name: &str) -> BrushSolidity {
+        self.entities
+            .get(&id)
+            .copied()
+            .unwrap_or_else(|| BrushSolidity {
+                solid: !is_non_solid_class(class_name),
+                ..BrushSolidity::default()
+            })
+    }
+}
+
+fn is_non_solid_class(class_name: &str) -> bool {
+    let class_name = class_name.to_ascii_lowercase();
+    NON_SOLID_CLASSES.iter().any(|c| class_name.starts_with(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::keyvalues;
+
+    #[test]
+    fn read_brush_solidity() {
+        let vmf = keyvalues::parse_raw(
+            r#"
+            entity { "id" "2" "classname" "func_brush" "Solidity" "0" "StartDisabled" "1" solid { "id" "3" } }
+            entity { "id" "4" "classname" "func_brush" "Solidity" "2" "StartDisabled" "1" solid { "id" "5" } }
+            entity { "id" "6" "classname" "trigger_multiple" "spawnflags" "4097" solid { "id" "7" } }
+            entity { "id" "8" "classname" "func_wall_toggle" "spawnflags" "1" solid { "id" "9" } }
+            entity { "id" "10" "classname" "func_physbox" "collisiongroup" "1" solid { "id" "11" } }
+            entity { "id" "12" "classname" "info_target" "origin" "0 0 0" }
+            "#,
+        )
+        .unwrap();
+
+        let solidities = BrushSolidities::from_vmf(&vmf);
+
+        let toggled = solidities.get(2, "func_brush");
+        assert!(!toggled.solid);
+        assert!(toggled.start_disabled);
+        assert_eq!(toggled.solidity, Some(0));
+
+        assert!(solidities.get(4, "func_brush").solid);
+        assert_eq!(solidities.get(6, "trigger_multiple").spawnflags, 4097);
+        assert!(!solidities.get(6, "trigger_multiple").solid);
+        assert!(!solidities.get(8, "func_wall_toggle").solid);
+        assert_eq!(solidities.get(10, "func_physbox").collision_group, Some(1));
+        assert!(solidities.get(1, "worldspawn").solid);
+        assert!(!solidities.get(12, "trigger_once").solid);
+    }
+}
@@ -0,0 +1,95 @@
+//! Reads the cubemap samples of a compiled map, which replace the `env_cubemap`
+//! entities, and finds the textures built for them in the pakfile.
+
+use std::borrow::Cow;
+
+use super::{
+    pakfile::{pakfile, PakEntry},
+    read_le, Bsp, BspError, LUMP_CUBEMAPS,
+};
+
+const CUBEMAP_SAMPLE_SIZE: usize = 16;
+
+/// A cubemap sample and the textures built for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cubemap<'a> {
+    pub origin: [i32; 3],
+    /// Resolution of the cubemap as a power of two exponent plus one, 0 being the default.
+    pub size: i32,
+    /// The cubemap texture built with ldr lighting.
+    pub vtf: Option<Cow<'a, [u8]>>,
+    /// The cubemap texture built with hdr lighting.
+    pub hdr_vtf: Option<Cow<'a, [u8]>>,
+}
+
+/// Returns the cubemap samples of the map with their textures, if the map has been
+/// built with cubemaps.
+///
+/// # Errors
+///
+/// Returns `Err` if the cubemap or pakfile lump is invalid.
+pub fn cubemaps<'a>(bsp: &Bsp<'a>) -> Result<Vec<Cubemap<'a>>, BspError> {
+    let entries = pakfile(bsp)?;
+
+    bsp.records(LUMP_CUBEMAPS, CUBEMAP_SAMPLE_SIZE)?
+        .map(|c| {
+            let origin = [
+                read_le::<i32>(c, 0)?,
+                read_le::<i32>(c, 4)?,
+                read_le::<i32>(c, 8)?,
+            ];
+
+            Ok(Cubemap {
+                origin,
+                size: read_le::<i32>(c, 12)?,
+                vtf: find_texture(&entries, origin, ".vtf"),
+                hdr_vtf: find_texture(&entries, origin, ".hdr.vtf"),
+            })
+        })
+        .collect()
+}
+
+/// Finds a cubemap texture, stored as `materials/maps/<map>/c<x>_<y>_<z>.vtf`.
+fn find_texture<'a>(
+    entries: &[PakEntry<'a>],
+    origin: [i32; 3],
+    suffix: &str,
+) -> Option<Cow<'a, [u8]>> {
+    let [x, y, z] = origin;
+    let file_name = format!("/c{x}_{y}_{z}{suffix}");
+
+    entries
+        .iter()
+        .find(|e| {
+            let name = e.name.to_ascii_lowercase();
+            name.starts_with("materials/maps/") && name.ends_with(&file_name)
+        })
+        .and_then(PakEntry::data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::bsp::pakfile::{read_entries, tests::write_zip};
+
+    #[test]
+    fn find_cubemap_textures() {
+        let zip = write_zip(&[
+            ("materials/maps/test/c-64_0_128.vtf", b"ldr"),
+            ("Materials/Maps/Test/C-64_0_128.hdr.vtf", b"hdr"),
+            ("materials/maps/test/c0_0_0.hdr.vtf", b"other"),
+        ]);
+        let entries = read_entries(&zip).unwrap();
+
+        assert_eq!(
+            find_texture(&entries, [-64, 0, 128], ".vtf"),
+            Some(Cow::Borrowed(&b"ldr"[..]))
+        );
+        assert_eq!(
+            find_texture(&entries, [-64, 0, 128], ".hdr.vtf"),
+            Some(Cow::Borrowed(&b"hdr"[..]))
+        );
+        assert_eq!(find_texture(&entries, [0, 0, 0], ".vtf"), None);
+    }
+}
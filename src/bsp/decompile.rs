@@ -0,0 +1,770 @@
+//! Reconstructs a VMF from a compiled map, so maps without their source files
+//! can be imported through the VMF importer.
+//!
+//! Only entities, static and detail props and brush planes are recovered. The brushes are rebuilt from the collision
+//! brushes the compiler keeps, so optimized faces, displacements and face-specific lighting
+//! settings are lost.
+
+use std::collections::BTreeSet;
+
+use crate::{keyvalues::KeyValue, vmf_repair};
+
+use super::{
+    detail_props::{self, DetailModel, DetailSpriteQuads},
+    pakfile,
+    static_props::{self, StaticProp},
+    Brush, BrushSide, Bsp, BspError, Plane, TexInfo,
+};
+
+type Block = Vec<(String, KeyValue)>;
+
+const CONTENTS_DETAIL: i32 = 0x0800_0000;
+
+/// Distance of the plane points from each other.
+const PLANE_POINT_SPREAD: f64 = 64.0;
+
+const FALLBACK_MATERIAL: &str = "TOOLS/TOOLSNODRAW";
+
+/// The decompiled VMF text and a description of everything which couldn't be recovered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decompiled {
+    pub text: String,
+    /// Detail sprites, which VMFs can't represent.
+    pub detail_sprites: Option<DetailSpriteQuads>,
+    pub warnings: Vec<String>,
+}
+
+/// Decompiles a bsp file into VMF text.
+///
+/// # Errors
+///
+/// Returns `Err` if the bsp is invalid or uses an unsupported format.
+pub fn decompile(data: &[u8]) -> Result<Decompiled, BspError> {
+    let bsp = Bsp::parse(data)?;
+
+    let mut decompiler = Decompiler {
+        planes: bsp.planes()?,
+        tex_infos: bsp.tex_infos()?,
+        tex_data_names: bsp.tex_data_names()?,
+        brushes: bsp.brushes()?,
+        brush_sides: bsp.brush_sides()?,
+        pak_files: BTreeSet::new(),
+        next_id: 1,
+        warnings: Vec::new(),
+    };
+
+    match pakfile::pakfile(&bsp) {
+        Ok(entries) => {
+            decompiler.pak_files = entries
+                .iter()
+                .map(|e| e.name.to_ascii_lowercase())
+                .collect();
+        }
+        Err(error) => decompiler
+            .warnings
+            .push(format!("pakfile could not be read: {error}")),
+    }
+
+    let model_brushes = model_brushes(&bsp)?;
+    let entities = bsp.entities()?;
+
+    let mut root = Block::new();
+    root.push(("versioninfo".to_owned(), version_info(bsp.revision)));
+    root.push(("visgroups".to_owned(), KeyValue::Block(Vec::new())));
+
+    let mut world = Block::new();
+    let mut detail_solids = Vec::new();
+    world.push(("id".to_owned(), value(&decompiler.id())));
+    world.push(("mapversion".to_owned(), value(&bsp.revision)));
+
+    let world_entity = entities
+        .iter()
+        .find(|e| class_name(e) == Some("worldspawn"));
+
+    for key_value in world_entity.into_iter().flatten() {
+        world.push((key_value.0.clone(), value(&key_value.1)));
+    }
+
+    if let Some(brushes) = model_brushes.first() {
+        for &brush in brushes {
+            let Some(solid) = decompiler.solid(brush, &Transform::IDENTITY) else {
+                continue;
+            };
+
+            // the compiler merges detail brushes into the world
+            if decompiler.brushes[brush].contents & CONTENTS_DETAIL == 0 {
+                world.push(("solid".to_owned(), solid));
+            } else {
+                detail_solids.push(("solid".to_owned(), solid));
+            }
+        }
+    }
+
+    root.push(("world".to_owned(), KeyValue::Block(world)));
+
+    if !detail_solids.is_empty() {
+        let mut detail = vec![
+            ("id".to_owned(), value(&decompiler.id())),
+            ("classname".to_owned(), value("func_detail")),
+        ];
+        detail.append(&mut detail_solids);
+        root.push(("entity".to_owned(), KeyValue::Block(detail)));
+    }
+
+    for entity in &entities {
+        if class_name(entity) == Some("worldspawn") {
+            continue;
+        }
+
+        let block = decompiler.entity(entity, &model_brushes);
+        root.push(("entity".to_owned(), KeyValue::Block(block)));
+    }
+
+    match static_props::static_props(&bsp) {
+        Ok(props) => {
+            for (index, prop) in props.iter().enumerate() {
+                for block in decompiler.static_prop(prop, index) {
+                    root.push(("entity".to_owned(), KeyValue::Block(block)));
+                }
+            }
+        }
+        Err(error) => decompiler
+            .warnings
+            .push(format!("static props could not be read: {error}")),
+    }
+
+    let mut detail_sprites = None;
+
+    match detail_props::detail_props(&bsp) {
+        Ok(props) => {
+            for prop in &props.models {
+                let block = decompiler.detail_model(prop);
+                root.push(("entity".to_owned(), KeyValue::Block(block)));
+            }
+
+            if !props.sprites.is_empty() {
+                let material = world_entity
+                    .and_then(|e| {
+                        e.iter()
+                            .find(|(k, _)| k.eq_ignore_ascii_case("detailmaterial"))
+                    })
+                    .map_or(detail_props::DEFAULT_MATERIAL, |(_, v)| v.as_str());
+
+                detail_sprites = Some(detail_props::sprite_quads(&props.sprites, material));
+            }
+        }
+        Err(error) => decompiler
+            .warnings
+            .push(format!("detail props could not be read: {error}")),
+    }
+
+    let mut text = String::new();
+    vmf_repair::write_block(&mut text, &root, 0);
+
+    Ok(Decompiled {
+        text,
+        detail_sprites,
+        warnings: decompiler.warnings,
+    })
+}
+
+/// Returns the brushes of every brush model, found by walking the leaves of their trees.
+fn model_brushes(bsp: &Bsp) -> Result<Vec<BTreeSet<usize>>, BspError> {
+    let nodes = bsp.nodes()?;
+    let leaves = bsp.leaves()?;
+    let leaf_brushes = bsp.leaf_brushes()?;
+
+    let models = bsp
+        .model_head_nodes()?
+        .into_iter()
+        .map(|head_node| {
+            let mut brushes = BTreeSet::new();
+            let mut stack = vec![i32::try_from(head_node).unwrap_or(i32::MAX)];
+
+            while let Some(child) = stack.pop() {
+                if let Ok(node) = usize::try_from(child) {
+                    if let Some(node) = nodes.get(node) {
+                        stack.extend(node.children);
+                    }
+                } else if let Some(leaf) =
+                    usize::try_from(-1 - child).ok().and_then(|l| leaves.get(l))
+                {
+                    brushes.extend(
+                        leaf_brushes
+                            .iter()
+                            .skip(leaf.first_brush)
+                            .take(leaf.brush_count)
+                            .copied(),
+                    );
+                }
+            }
+
+            brushes
+        })
+        .collect();
+
+    Ok(models)
+}
+
+struct Decompiler {
+    planes: Vec<Plane>,
+    tex_infos: Vec<TexInfo>,
+    tex_data_names: Vec<String>,
+    brushes: Vec<Brush>,
+    brush_sides: Vec<BrushSide>,
+    /// Lowercase paths of the files in the pakfile.
+    pak_files: BTreeSet<String>,
+    next_id: u64,
+    warnings: Vec<String>,
+}
+
+impl Decompiler {
+    fn id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    fn entity(&mut self, entity: &[(String, String)], model_brushes: &[BTreeSet<usize>]) -> Block {
+        let mut block = vec![("id".to_owned(), value(&self.id()))];
+        let mut connections = Block::new();
+        let mut model = None;
+
+        for (key, v) in entity {
+            if key.eq_ignore_ascii_case("model") {
+                if let Some(index) = v.strip_prefix('*') {
+                    model = Some(index);
+                    continue;
+                }
+            }
+
+            if is_output(key, v) {
+                connections.push((key.clone(), value(v)));
+            } else {
+                block.push((key.clone(), value(v)));
+            }
+        }
+
+        if !connections.is_empty() {
+            block.push(("connections".to_owned(), KeyValue::Block(connections)));
+        }
+
+        if let Some(index) = model {
+            match index
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| model_brushes.get(i))
+            {
+                Some(brushes) => {
+                    // brush entity geometry is stored relative to the entity
+                    let transform = Transform::new(
+                        vector_value(entity, "origin").unwrap_or_default(),
+                        vector_value(entity, "angles").unwrap_or_default(),
+                    );
+
+                    for &brush in brushes {
+                        if let Some(solid) = self.solid(brush, &transform) {
+                            block.push(("solid".to_owned(), solid));
+                        }
+                    }
+                }
+                None => self.warnings.push(format!(
+                    "{} refers to nonexistent brush model `*{index}`",
+                    describe(entity)
+                )),
+            }
+        }
+
+        block
+    }
+
+    /// Converts a static prop into a `prop_static` entity,
+    /// and an `info_lighting` entity if the prop uses a custom lighting origin.
+    /// The baked vertex lighting file of the prop is kept in `vertexlighting`,
+    /// which isn't a Hammer keyvalue.
+    fn static_prop(&mut self, prop: &StaticProp, index: usize) -> Vec<Block> {
+        let [pitch, yaw, roll] = prop.angles.map(f64::from).map(number);
+        let [x, y, z] = prop.origin.map(f64::from).map(number);
+
+        let mut block = vec![
+            ("id".to_owned(), value(&self.id())),
+            ("classname".to_owned(), value("prop_static")),
+            ("origin".to_owned(), value(&format!("{x} {y} {z}"))),
+            ("angles".to_owned(), value(&format!("{pitch} {yaw} {roll}"))),
+            ("model".to_owned(), value(&prop.model)),
+            ("skin".to_owned(), value(&prop.skin)),
+            ("solid".to_owned(), value(&prop.solid)),
+            ("fademindist".to_owned(), value(&prop.fade_min_distance)),
+            ("fademaxdist".to_owned(), value(&prop.fade_max_distance)),
+            ("fadescale".to_owned(), value(&prop.forced_fade_scale)),
+        ];
+
+        for (flag, key) in [
+            (static_props::FLAG_NO_SHADOW, "disableshadows"),
+            (static_props::FLAG_IGNORE_NORMALS, "ignorenormals"),
+            (static_props::FLAG_SCREEN_SPACE_FADE, "screenspacefade"),
+            (
+                static_props::FLAG_NO_PER_VERTEX_LIGHTING,
+                "disablevertexlighting",
+            ),
+            (static_props::FLAG_NO_SELF_SHADOWING, "disableselfshadowing"),
+        ] {
+            let enabled = u8::from(prop.flags & flag != 0);
+            block.push((key.to_owned(), value(&enabled)));
+        }
+
+        if let Some([min, max]) = prop.dx_levels {
+            block.push(("mindxlevel".to_owned(), value(&min)));
+            block.push(("maxdxlevel".to_owned(), value(&max)));
+        }
+
+        if let Some(levels) = prop.cpu_gpu_levels {
+            for (key, level) in ["mincpulevel", "maxcpulevel", "mingpulevel", "maxgpulevel"]
+                .into_iter()
+                .zip(levels)
+            {
+                block.push((key.to_owned(), value(&level)));
+            }
+        }
+
+        if let Some([r, g, b, a]) = prop.diffuse_modulation {
+            block.push(("rendercolor".to_owned(), value(&format!("{r} {g} {b}"))));
+            block.push(("renderamt".to_owned(), value(&a)));
+        }
+
+        if (prop.uniform_scale - 1.0).abs() > f32::EPSILON {
+            block.push(("uniformscale".to_owned(), value(&prop.uniform_scale)));
+        }
+
+        // vrad writes the hdr and ldr lighting separately, hdr is preferred when both exist
+        if let Some(vhv) = [format!("sp_hdr_{index}.vhv"), format!("sp_{index}.vhv")]
+            .into_iter()
+            .find(|name| self.pak_files.contains(name))
+        {
+            block.push(("vertexlighting".to_owned(), value(&vhv)));
+        }
+
+        if prop.flags & static_props::FLAG_USE_LIGHTING_ORIGIN == 0 {
+            return vec![block];
+        }
+
+        let name = format!("static_prop_lighting_{index}");
+        let [x, y, z] = prop.lighting_origin.map(f64::from).map(number);
+
+        block.push(("lightingorigin".to_owned(), value(&name)));
+
+        let lighting = vec![
+            ("id".to_owned(), value(&self.id())),
+            ("classname".to_owned(), value("info_lighting")),
+            ("targetname".to_owned(), value(&name)),
+            ("origin".to_owned(), value(&format!("{x} {y} {z}"))),
+        ];
+
+        vec![block, lighting]
+    }
+
+    /// Converts a detail model into a `prop_static` entity without collision or shadows,
+    /// matching how they are rendered in game.
+    fn detail_model(&mut self, prop: &DetailModel) -> Block {
+        let [pitch, yaw, roll] = prop.angles.map(f64::from).map(number);
+        let [x, y, z] = prop.origin.map(f64::from).map(number);
+
+        vec![
+            ("id".to_owned(), value(&self.id())),
+            ("classname".to_owned(), value("prop_static")),
+            ("origin".to_owned(), value(&format!("{x} {y} {z}"))),
+            ("angles".to_owned(), value(&format!("{pitch} {yaw} {roll}"))),
+            ("model".to_owned(), value(&prop.model)),
+            ("solid".to_owned(), value(&0)),
+            ("disableshadows".to_owned(), value(&1)),
+        ]
+    }
+
+    fn solid(&mut self, brush: usize, transform: &Transform) -> Option<KeyValue> {
+        let brush = *self.brushes.get(brush)?;
+
+        let sides: Vec<BrushSide> = self
+            .brush_sides
+            .iter()
+            .skip(brush.first_side)
+            .take(brush.side_count)
+            // bevel planes are only added for collision
+            .filter(|s| !s.bevel)
+            .copied()
+            .collect();
+
+        if sides.len() < 4 {
+            return None;
+        }
+
+        let mut solid = vec![("id".to_owned(), value(&self.id()))];
+
+        for side in sides {
+            let Some(plane) = self.planes.get(side.plane).copied() else {
+                self.warnings.push(format!(
+                    "brush side refers to nonexistent plane {}",
+                    side.plane
+                ));
+                continue;
+            };
+
+            let block = self.side(plane, side.tex_info, transform);
+            solid.push(("side".to_owned(), KeyValue::Block(block)));
+        }
+
+        Some(KeyValue::Block(solid))
+    }
+
+    fn side(&mut self, plane: Plane, tex_info: Option<usize>, transform: &Transform) -> Block {
+        let normal = transform.rotate(plane.normal.map(f64::from));
+        let dist = f64::from(plane.dist) + dot(normal, transform.origin);
+
+        let id = self.id();
+        let tex_info = tex_info.and_then(|t| self.tex_infos.get(t));
+
+        let material = tex_info
+            .and_then(|t| t.tex_data)
+            .and_then(|t| self.tex_data_names.get(t))
+            .map_or(FALLBACK_MATERIAL, String::as_str);
+
+        let axes = tex_info.map_or_else(
+            || default_axes(normal),
+            |t| t.texture_vecs.map(|v| texture_axis(v, transform)),
+        );
+
+        vec![
+            ("id".to_owned(), value(&id)),
+            ("plane".to_owned(), value(&plane_points(normal, dist))),
+            ("material".to_owned(), value(material)),
+            ("uaxis".to_owned(), value(&axes[0])),
+            ("vaxis".to_owned(), value(&axes[1])),
+            ("rotation".to_owned(), value(&0)),
+            ("lightmapscale".to_owned(), value(&16)),
+            ("smoothing_groups".to_owned(), value(&0)),
+        ]
+    }
+}
+
+/// A rotation, in Source angles, followed by a translation.
+pub(super) struct Transform {
+    origin: [f64; 3],
+    /// Columns of the rotation matrix.
+    axes: [[f64; 3]; 3],
+}
+
+impl Transform {
+    const IDENTITY: Self = Self {
+        origin: [0.0; 3],
+        axes: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+    };
+
+    pub(super) fn new(origin: [f64; 3], [pitch, yaw, roll]: [f64; 3]) -> Self {
+        let (sp, cp) = pitch.to_radians().sin_cos();
+        let (sy, cy) = yaw.to_radians().sin_cos();
+        let (sr, cr) = roll.to_radians().sin_cos();
+
+        Self {
+            origin,
+            axes: [
+                [cp * cy, cp * sy, -sp],
+                [sr * sp * cy - cr * sy, sr * sp * sy + cr * cy, sr * cp],
+                [cr * sp * cy + sr * sy, cr * sp * sy - sr * cy, cr * cp],
+            ],
+        }
+    }
+
+    fn rotate(&self, v: [f64; 3]) -> [f64; 3] {
+        let [x, y, z] = self.axes;
+        [0, 1, 2].map(|i| x[i] * v[0] + y[i] * v[1] + z[i] * v[2])
+    }
+
+    pub(super) fn apply(&self, v: [f64; 3]) -> [f64; 3] {
+        let rotated = self.rotate(v);
+        [0, 1, 2].map(|i| rotated[i] + self.origin[i])
+    }
+}
+
+/// Returns three points on the plane in the winding order the VMF parser expects,
+/// `(p0 - p1) x (p2 - p1)` pointing out of the brush.
+fn plane_points(normal: [f64; 3], dist: f64) -> String {
+    let reference = if normal[2].abs() < 0.9 {
+        [0.0, 0.0, 1.0]
+    } else {
+        [1.0, 0.0, 0.0]
+    };
+
+    let u = normalize(cross(normal, reference));
+    let v = cross(normal, u);
+
+    let origin = normal.map(|n| n * dist);
+    let offset = |axis: [f64; 3]| [0, 1, 2].map(|i| origin[i] + axis[i] * PLANE_POINT_SPREAD);
+
+    let [p0, p1, p2] = [offset(u), origin, offset(v)].map(|p| {
+        let p = p.map(number);
+        format!("({} {} {})", p[0], p[1], p[2])
+    });
+
+    format!("{p0} {p1} {p2}")
+}
+
+/// Converts a texture vector in texels per unit into a VMF texture axis.
+fn texture_axis(vec: [f32; 4], transform: &Transform) -> String {
+    let axis = transform.rotate([vec[0], vec[1], vec[2]].map(f64::from));
+    let length = dot(axis, axis).sqrt();
+
+    if length == 0.0 {
+        return "[1 0 0 0] 0.25".to_owned();
+    }
+
+    let scale = 1.0 / length;
+    let offset = f64::from(vec[3]) - dot(axis, transform.origin);
+    let [x, y, z] = axis.map(|a| number(a * scale));
+
+    format!("[{x} {y} {z} {}] {}", number(offset), number(scale))
+}
+
+fn default_axes(normal: [f64; 3]) -> [String; 2] {
+    let u = if normal[2].abs() < 0.9 {
+        "[1 0 0 0] 0.25"
+    } else {
+        "[0 1 0 0] 0.25"
+    };
+
+    [u.to_owned(), "[0 0 -1 0] 0.25".to_owned()]
+}
+
+fn is_output(key: &str, value: &str) -> bool {
+    // outputs are stored as keyvalues with 5 comma or escape separated parameters
+    (key.starts_with("On") || key.starts_with("Out"))
+        && [',', '\x1b']
+            .iter()
+            .any(|&separator| value.matches(separator).count() == 4)
+}
+
+fn class_name(entity: &[(String, String)]) -> Option<&str> {
+    entity
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("classname"))
+        .map(|(_, v)| v.as_str())
+}
+
+fn describe(entity: &[(String, String)]) -> String {
+    let target_name = entity
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("targetname"))
+        .map(|(_, v)| v.as_str());
+
+    match (class_name(entity), target_name) {
+        (Some(class), Some(name)) => format!("entity `{name}` ({class})"),
+        (Some(class), None) => format!("entity {class}"),
+        (None, _) => "entity".to_owned(),
+    }
+}
+
+fn vector_value(entity: &[(String, String)], key: &str) -> Option<[f64; 3]> {
+    let value = entity.iter().find(|(k, _)| k.eq_ignore_ascii_case(key))?;
+    let mut parts = value.1.split_whitespace().map(|p| p.parse().ok());
+
+    Some([parts.next()??, parts.next()??, parts.next()??])
+}
+
+fn version_info(revision: i32) -> KeyValue {
+    KeyValue::Block(vec![
+        ("editorversion".to_owned(), value(&400)),
+        ("editorbuild".to_owned(), value(&0)),
+        ("mapversion".to_owned(), value(&revision)),
+        ("formatversion".to_owned(), value(&100)),
+        ("prefab".to_owned(), value(&0)),
+    ])
+}
+
+fn value<T: ToString + ?Sized>(v: &T) -> KeyValue {
+    KeyValue::Value(v.to_string())
+}
+
+/// Formats a number without float noise, since the compiler snaps most values anyway.
+fn number(n: f64) -> String {
+    let rounded = (n * 1000.0).round() / 1000.0;
+
+    if rounded == 0.0 {
+        "0".to_owned()
+    } else {
+        rounded.to_string()
+    }
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(v: [f64; 3]) -> [f64; 3] {
+    let length = dot(v, v).sqrt();
+    v.map(|c| c / length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super as bsp, *};
+
+    fn parse_points(points: &str) -> Vec<[f64; 3]> {
+        points
+            .split(['(', ')'])
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(|p| {
+                let v: Vec<f64> = p.split(' ').map(|c| c.parse().unwrap()).collect();
+                [v[0], v[1], v[2]]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plane_points_wind_outwards() {
+        for (normal, dist) in [
+            ([0.0, 0.0, 1.0], 64.0),
+            ([0.0, 0.0, -1.0], 0.0),
+            ([1.0, 0.0, 0.0], -32.0),
+            ([0.6, 0.0, 0.8], 10.0),
+        ] {
+            let points = parse_points(&plane_points(normal, dist));
+            let sub = |a: [f64; 3], b: [f64; 3]| [0, 1, 2].map(|i| a[i] - b[i]);
+
+            let computed = normalize(cross(sub(points[0], points[1]), sub(points[2], points[1])));
+
+            for i in 0..3 {
+                assert!((computed[i] - normal[i]).abs() < 1e-3, "{normal:?}");
+            }
+            for point in &points {
+                assert!((dot(*point, normal) - dist).abs() < 1e-2, "{normal:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn translate_texture_axis() {
+        let transform = Transform::new([16.0, 0.0, 0.0], [0.0, 0.0, 0.0]);
+
+        assert_eq!(
+            texture_axis([0.25, 0.0, 0.0, 8.0], &transform),
+            "[1 0 0 4] 4"
+        );
+        assert_eq!(
+            texture_axis([0.0, 0.5, 0.0, 0.0], &Transform::IDENTITY),
+            "[0 1 0 0] 2"
+        );
+    }
+
+    fn build_bsp(lumps: &[(usize, Vec<u8>)]) -> Vec<u8> {
+        let mut data = b"VBSP".to_vec();
+        data.extend(20_i32.to_le_bytes());
+        data.resize(bsp::HEADER_SIZE, 0);
+
+        for (index, lump) in lumps {
+            let info = 8 + index * 16;
+            let offset = i32::try_from(data.len()).unwrap();
+            let length = i32::try_from(lump.len()).unwrap();
+
+            data[info..info + 4].copy_from_slice(&offset.to_le_bytes());
+            data[info + 4..info + 8].copy_from_slice(&length.to_le_bytes());
+            data[info + 8..info + 12].copy_from_slice(&1_i32.to_le_bytes());
+            data.extend(lump);
+        }
+
+        data
+    }
+
+    fn f32_bytes(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    fn u16_bytes(values: &[u16]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    fn int_bytes(values: &[i32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn decompile_cube() {
+        let mut planes = Vec::new();
+        for (normal, dist) in [
+            ([1.0, 0.0, 0.0], 64.0),
+            ([-1.0, 0.0, 0.0], 0.0),
+            ([0.0, 1.0, 0.0], 64.0),
+            ([0.0, -1.0, 0.0], 0.0),
+            ([0.0, 0.0, 1.0], 64.0),
+            ([0.0, 0.0, -1.0], 0.0),
+        ] {
+            planes.extend(f32_bytes(&normal));
+            planes.extend(f32_bytes(&[dist]));
+            planes.extend(int_bytes(&[0]));
+        }
+
+        let mut sides = Vec::new();
+        for plane in 0..6 {
+            sides.extend(u16_bytes(&[plane, 0, 0xffff, 0]));
+        }
+
+        let mut tex_info = f32_bytes(&[0.25, 0.0, 0.0, 0.0, 0.0, 0.0, -0.25, 0.0]);
+        tex_info.extend(vec![0; 32]);
+        tex_info.extend(int_bytes(&[0, 0]));
+
+        let mut leaves = vec![0; 64];
+        leaves[26..28].copy_from_slice(&1_u16.to_le_bytes());
+
+        let mut node = int_bytes(&[0, -1, -2]);
+        node.resize(32, 0);
+
+        // a single model with the head node 0
+        let model = vec![0; 48];
+
+        let data = build_bsp(&[
+            (
+                bsp::LUMP_ENTITIES,
+                b"{\n\"classname\" \"worldspawn\"\n}\n{\n\"classname\" \"func_door\"\n\"model\" \"*1\"\n}\n"
+                    .to_vec(),
+            ),
+            (bsp::LUMP_PLANES, planes),
+            (bsp::LUMP_TEXDATA, vec![0; 32]),
+            (bsp::LUMP_NODES, node),
+            (bsp::LUMP_TEXINFO, tex_info),
+            (bsp::LUMP_LEAFS, leaves),
+            (bsp::LUMP_MODELS, model),
+            (bsp::LUMP_LEAFBRUSHES, vec![0, 0]),
+            (bsp::LUMP_BRUSHES, int_bytes(&[0, 6, 1])),
+            (bsp::LUMP_BRUSHSIDES, sides),
+            (bsp::LUMP_TEXDATA_STRING_DATA, b"DEV/DEV_MEASUREGENERIC01\0".to_vec()),
+            (bsp::LUMP_TEXDATA_STRING_TABLE, int_bytes(&[0])),
+        ]);
+
+        let decompiled = decompile(&data).unwrap();
+
+        assert_eq!(decompiled.text.matches("side\n").count(), 6);
+        assert!(decompiled
+            .text
+            .contains("\"material\" \"DEV/DEV_MEASUREGENERIC01\""));
+        assert!(decompiled.text.contains("\"uaxis\" \"[1 0 0 0] 4\""));
+        assert!(decompiled.text.contains("\"classname\" \"func_door\""));
+        assert_eq!(
+            decompiled.warnings,
+            vec!["entity func_door refers to nonexistent brush model `*1`".to_owned()]
+        );
+    }
+
+    #[test]
+    fn detect_outputs() {
+        assert!(is_output("OnTrigger", "door,Open,,0,-1"));
+        assert!(is_output("OnTrigger", "door\x1bOpen\x1b\x1b0\x1b-1"));
+        assert!(!is_output("origin", "0 0 0"));
+        assert!(!is_output("OnlyOnce", "1"));
+    }
+}
@@ -0,0 +1,284 @@
+//! Reads the detail prop game lump (`dprp`), which holds the sprites and models
+//! the compiler scatters on displacements.
+//!
+//! Sprites show a region of the `detailmaterial` of the world and are rebuilt as quads,
+//! since their geometry only exists at runtime.
+
+use crate::game_path::material_name;
+
+use super::{
+    decompile::Transform, read_le, read_usize, read_vector, Bsp, BspError, LUMP_GAME_LUMP,
+};
+
+pub const GAME_LUMP_ID: [u8; 4] = *b"dprp";
+
+const MIN_VERSION: u16 = 4;
+const MAX_VERSION: u16 = 5;
+
+const NAME_SIZE: usize = 128;
+const SPRITE_DICT_SIZE: usize = 32;
+const PROP_SIZE: usize = 52;
+
+const KIND_MODEL: u8 = 0;
+const KIND_SPRITE: u8 = 1;
+const KIND_SHAPE_CROSS: u8 = 2;
+const KIND_SHAPE_TRI: u8 = 3;
+
+/// The material detail sprites use if the world doesn't set `detailmaterial`.
+pub const DEFAULT_MATERIAL: &str = "detail/detailsprites";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpriteDict {
+    /// Upper left corner of the sprite in world units, relative to its origin.
+    pub upper_left: [f32; 2],
+    pub lower_right: [f32; 2],
+    /// Upper left corner of the sprite on the detail material.
+    pub tex_upper_left: [f32; 2],
+    pub tex_lower_right: [f32; 2],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpriteShape {
+    Flat,
+    /// Two sprites crossing each other.
+    Cross,
+    /// Three sprites around the origin.
+    Tri,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetailModel {
+    pub model: String,
+    pub origin: [f32; 3],
+    pub angles: [f32; 3],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetailSprite {
+    pub dict: SpriteDict,
+    pub shape: SpriteShape,
+    pub origin: [f32; 3],
+    pub angles: [f32; 3],
+    pub scale: f32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DetailProps {
+    pub models: Vec<DetailModel>,
+    pub sprites: Vec<DetailSprite>,
+}
+
+/// Quads of detail sprites, ready to be built into a mesh.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetailSpriteQuads {
+    pub material: String,
+    /// Four vertices per quad.
+    pub vertices: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+}
+
+/// Reads the detail props of a map. Returns empty lists if the map has no detail props.
+pub fn detail_props(bsp: &Bsp) -> Result<DetailProps, BspError> {
+    let Some((version, data)) = bsp.game_lump(GAME_LUMP_ID)? else {
+        return Ok(DetailProps::default());
+    };
+
+    if !(MIN_VERSION..=MAX_VERSION).contains(&version) {
+        return Err(BspError::UnsupportedLumpVersion(
+            LUMP_GAME_LUMP,
+            version.into(),
+        ));
+    }
+
+    let name_count = read_usize(data, 0)?;
+    let names = (0..name_count)
+        .map(|i| {
+            let offset = 4 + i * NAME_SIZE;
+            let name = data
+                .get(offset..offset + NAME_SIZE)
+                .ok_or(BspError::UnexpectedEof)?;
+            let end = name.iter().position(|&b| b == 0).unwrap_or(NAME_SIZE);
+
+            Ok(String::from_utf8_lossy(&name[..end]).into_owned())
+        })
+        .collect::<Result<Vec<_>, BspError>>()?;
+
+    let sprites_offset = 4 + name_count * NAME_SIZE;
+    let sprite_count = read_usize(data, sprites_offset)?;
+    let sprite_dicts = (0..sprite_count)
+        .map(|i| {
+            let offset = sprites_offset + 4 + i * SPRITE_DICT_SIZE;
+            let point = |o: usize| {
+                Ok::<_, BspError>([read_le::<f32>(data, o)?, read_le::<f32>(data, o + 4)?])
+            };
+
+            Ok(SpriteDict {
+                upper_left: point(offset)?,
+                lower_right: point(offset + 8)?,
+                tex_upper_left: point(offset + 16)?,
+                tex_lower_right: point(offset + 24)?,
+            })
+        })
+        .collect::<Result<Vec<_>, BspError>>()?;
+
+    let props_offset = sprites_offset + 4 + sprite_count * SPRITE_DICT_SIZE;
+    let prop_count = read_usize(data, props_offset)?;
+    let props_data = data
+        .get(props_offset + 4..)
+        .ok_or(BspError::UnexpectedEof)?;
+
+    let mut props = DetailProps::default();
+
+    if prop_count == 0 {
+        return Ok(props);
+    }
+
+    // newer versions append fields, which are skipped
+    let size = props_data.len() / prop_count;
+    if size < PROP_SIZE {
+        return Err(BspError::InvalidLump(LUMP_GAME_LUMP));
+    }
+
+    for p in props_data.chunks_exact(size).take(prop_count) {
+        let origin = read_vector(p, 0)?;
+        let angles = read_vector(p, 12)?;
+        let index = usize::from(read_le::<u16>(p, 24)?);
+
+        let shape = match p[44] {
+            KIND_MODEL => {
+                let model = names
+                    .get(index)
+                    .cloned()
+                    .ok_or(BspError::InvalidLump(LUMP_GAME_LUMP))?;
+
+                props.models.push(DetailModel {
+                    model,
+                    origin,
+                    angles,
+                });
+                continue;
+            }
+            KIND_SPRITE => SpriteShape::Flat,
+            KIND_SHAPE_CROSS => SpriteShape::Cross,
+            KIND_SHAPE_TRI => SpriteShape::Tri,
+            _ => return Err(BspError::InvalidLump(LUMP_GAME_LUMP)),
+        };
+
+        let dict = *sprite_dicts
+            .get(index)
+            .ok_or(BspError::InvalidLump(LUMP_GAME_LUMP))?;
+
+        props.sprites.push(DetailSprite {
+            dict,
+            shape,
+            origin,
+            angles,
+            scale: read_le::<f32>(p, 48)?,
+        });
+    }
+
+    Ok(props)
+}
+
+/// Returns the path of the detail material of a world, like `detail/detailsprites`,
+/// which is also the name of the imported material.
+pub fn material_path(material: &str) -> String {
+    format!("materials/{}.vmt", material_name(material, ".vmt"))
+}
+
+/// Builds the quads of detail sprites.
+///
+/// Sprites which face the camera in game keep the angles they were placed with,
+/// and the sprites of shapes are rotated around the origin instead of bent.
+pub fn sprite_quads(sprites: &[DetailSprite], material: &str) -> DetailSpriteQuads {
+    let mut quads = DetailSpriteQuads {
+        material: material_path(material),
+        vertices: Vec::new(),
+        uvs: Vec::new(),
+    };
+
+    for sprite in sprites {
+        let yaws: &[f32] = match sprite.shape {
+            SpriteShape::Flat => &[0.0],
+            SpriteShape::Cross => &[0.0, 90.0],
+            SpriteShape::Tri => &[0.0, 60.0, 120.0],
+        };
+
+        let dict = sprite.dict;
+        let [left, top] = dict.upper_left.map(|v| v * sprite.scale);
+        let [right, bottom] = dict.lower_right.map(|v| v * sprite.scale);
+        let [u_left, v_top] = dict.tex_upper_left;
+        let [u_right, v_bottom] = dict.tex_lower_right;
+
+        for &yaw in yaws {
+            let [pitch, base_yaw, roll] = sprite.angles.map(f64::from);
+            let transform = Transform::new(
+                sprite.origin.map(f64::from),
+                [pitch, base_yaw + f64::from(yaw), roll],
+            );
+
+            // the sprite spans the right and up axes, facing forward
+            for ([x, y], uv) in [
+                ([left, bottom], [u_left, v_bottom]),
+                ([right, bottom], [u_right, v_bottom]),
+                ([right, top], [u_right, v_top]),
+                ([left, top], [u_left, v_top]),
+            ] {
+                let point = transform.apply([0.0, -f64::from(x), f64::from(y)]);
+
+                #[allow(clippy::cast_possible_truncation)]
+                quads.vertices.push(point.map(|c| c as f32));
+                // blender has inverted v axis compared to Source
+                quads.uvs.push([uv[0], 1.0 - uv[1]]);
+            }
+        }
+    }
+
+    quads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sprite(shape: SpriteShape) -> DetailSprite {
+        DetailSprite {
+            dict: SpriteDict {
+                upper_left: [-8.0, 16.0],
+                lower_right: [8.0, 0.0],
+                tex_upper_left: [0.0, 0.0],
+                tex_lower_right: [0.5, 0.25],
+            },
+            shape,
+            origin: [100.0, 0.0, 0.0],
+            angles: [0.0, 0.0, 0.0],
+            scale: 1.0,
+        }
+    }
+
+    #[test]
+    fn build_sprite_quads() {
+        let quads = sprite_quads(&[sprite(SpriteShape::Flat)], DEFAULT_MATERIAL);
+
+        assert_eq!(quads.material, "materials/detail/detailsprites.vmt");
+        assert_eq!(
+            quads.vertices,
+            vec![
+                [100.0, 8.0, 0.0],
+                [100.0, -8.0, 0.0],
+                [100.0, -8.0, 16.0],
+                [100.0, 8.0, 16.0],
+            ]
+        );
+        assert_eq!(
+            quads.uvs,
+            vec![[0.0, 0.75], [0.5, 0.75], [0.5, 1.0], [0.0, 1.0]]
+        );
+
+        let quads = sprite_quads(
+            &[sprite(SpriteShape::Cross), sprite(SpriteShape::Tri)],
+            DEFAULT_MATERIAL,
+        );
+        assert_eq!(quads.vertices.len(), 4 * 5);
+    }
+}
@@ -0,0 +1,480 @@
+//! Reads the lumps of compiled Source maps (`.bsp`) which are needed to import them.
+
+pub mod cubemaps;
+pub mod decompile;
+pub mod detail_props;
+pub mod pakfile;
+pub mod static_props;
+pub mod vis;
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::bytes::{self, LeBytes};
+
+const HEADER_LUMPS: usize = 8;
+const NUM_LUMPS: usize = 64;
+const LUMP_INFO_SIZE: usize = 16;
+const HEADER_SIZE: usize = HEADER_LUMPS + NUM_LUMPS * LUMP_INFO_SIZE + 4;
+
+const MIN_VERSION: i32 = 19;
+const MAX_VERSION: i32 = 21;
+
+pub const LUMP_ENTITIES: usize = 0;
+pub const LUMP_PLANES: usize = 1;
+pub const LUMP_TEXDATA: usize = 2;
+pub const LUMP_VISIBILITY: usize = 4;
+pub const LUMP_NODES: usize = 5;
+pub const LUMP_TEXINFO: usize = 6;
+pub const LUMP_LEAFS: usize = 10;
+pub const LUMP_MODELS: usize = 14;
+pub const LUMP_LEAFBRUSHES: usize = 17;
+pub const LUMP_BRUSHES: usize = 18;
+pub const LUMP_BRUSHSIDES: usize = 19;
+pub const LUMP_GAME_LUMP: usize = 35;
+pub const LUMP_PAKFILE: usize = 40;
+pub const LUMP_CUBEMAPS: usize = 42;
+pub const LUMP_TEXDATA_STRING_DATA: usize = 43;
+pub const LUMP_TEXDATA_STRING_TABLE: usize = 44;
+
+const PLANE_SIZE: usize = 20;
+const TEXDATA_SIZE: usize = 32;
+const TEXDATA_NAME: usize = 12;
+const NODE_SIZE: usize = 32;
+const TEXINFO_SIZE: usize = 72;
+const TEXINFO_FLAGS: usize = 64;
+const TEXINFO_TEXDATA: usize = 68;
+const LEAF_SIZE_V0: usize = 56;
+const LEAF_SIZE_V1: usize = 32;
+const LEAF_CLUSTER: usize = 4;
+const LEAF_MINS: usize = 8;
+const LEAF_FIRST_BRUSH: usize = 24;
+const MODEL_SIZE: usize = 48;
+const MODEL_HEAD_NODE: usize = 36;
+const BRUSH_SIZE: usize = 12;
+const BRUSHSIDE_SIZE: usize = 8;
+const GAME_LUMP_SIZE: usize = 16;
+const GAME_LUMP_COMPRESSED: u16 = 0x1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BspError {
+    InvalidHeader,
+    UnsupportedVersion(i32),
+    UnexpectedEof,
+    CompressedLump(usize),
+    InvalidLump(usize),
+    UnsupportedLumpVersion(usize, i32),
+}
+
+impl Display for BspError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BspError::InvalidHeader => f.write_str("invalid bsp header"),
+            BspError::UnsupportedVersion(v) => write!(f, "unsupported bsp version {v}"),
+            BspError::UnexpectedEof => f.write_str("unexpected end of file"),
+            BspError::CompressedLump(l) => {
+                write!(f, "lump {l} is compressed, which is unsupported")
+            }
+            BspError::InvalidLump(l) => write!(f, "lump {l} is invalid"),
+            BspError::UnsupportedLumpVersion(l, v) => {
+                write!(f, "lump {l} has unsupported version {v}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BspError {}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct LumpInfo {
+    offset: usize,
+    length: usize,
+    version: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: [f32; 3],
+    pub dist: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TexInfo {
+    /// Texture axes in texels per unit, with the offset as the last component.
+    pub texture_vecs: [[f32; 4]; 2],
+    pub flags: i32,
+    pub tex_data: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Node {
+    /// Child nodes, negative values refer to leaves as `-1 - leaf`.
+    pub children: [i32; 2],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Leaf {
+    /// The visibility cluster, `None` for leaves in solid space.
+    pub cluster: Option<usize>,
+    pub mins: [i16; 3],
+    pub maxs: [i16; 3],
+    pub first_brush: usize,
+    pub brush_count: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Brush {
+    pub first_side: usize,
+    pub side_count: usize,
+    pub contents: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BrushSide {
+    pub plane: usize,
+    pub tex_info: Option<usize>,
+    pub bevel: bool,
+}
+
+/// A parsed bsp header, referring to the lumps in the file data.
+pub struct Bsp<'a> {
+    data: &'a [u8],
+    pub version: i32,
+    pub revision: i32,
+    lumps: [LumpInfo; NUM_LUMPS],
+}
+
+impl<'a> Bsp<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, BspError> {
+        if !data.starts_with(b"VBSP") {
+            return Err(BspError::InvalidHeader);
+        }
+
+        let version = read_le::<i32>(data, 4)?;
+        if !(MIN_VERSION..=MAX_VERSION).contains(&version) {
+            return Err(BspError::UnsupportedVersion(version));
+        }
+
+        // Left 4 Dead 2 stores the version first in the lump info,
+        // otherwise the entity lump offset is the first value, which is never in the header
+        let swapped = version == MAX_VERSION
+            && usize::try_from(read_le::<i32>(data, HEADER_LUMPS)?)
+                .map_or(true, |o| o < HEADER_SIZE);
+
+        let mut lumps = [LumpInfo::default(); NUM_LUMPS];
+
+        for (i, lump) in lumps.iter_mut().enumerate() {
+            let info = HEADER_LUMPS + i * LUMP_INFO_SIZE;
+
+            let (version, offset, length) = if swapped {
+                (
+                    read_le::<i32>(data, info)?,
+                    read_usize(data, info + 4)?,
+                    read_usize(data, info + 8)?,
+                )
+            } else {
+                (
+                    read_le::<i32>(data, info + 8)?,
+                    read_usize(data, info)?,
+                    read_usize(data, info + 4)?,
+                )
+            };
+
+            *lump = LumpInfo {
+                offset,
+                length,
+                version,
+            };
+        }
+
+        Ok(Self {
+            data,
+            version,
+            revision: read_le::<i32>(data, HEADER_SIZE - 4)?,
+            lumps,
+        })
+    }
+
+    pub fn lump(&self, index: usize) -> Result<&'a [u8], BspError> {
+        let info = self.lumps[index];
+
+        let lump = self
+            .data
+            .get(info.offset..info.offset + info.length)
+            .ok_or(BspError::UnexpectedEof)?;
+
+        if lump.starts_with(b"LZMA") {
+            return Err(BspError::CompressedLump(index));
+        }
+
+        Ok(lump)
+    }
+
+    pub fn lump_version(&self, index: usize) -> i32 {
+        self.lumps[index].version
+    }
+
+    fn records(
+        &self,
+        index: usize,
+        size: usize,
+    ) -> Result<impl Iterator<Item = &'a [u8]>, BspError> {
+        let lump = self.lump(index)?;
+
+        if lump.len() % size != 0 {
+            return Err(BspError::InvalidLump(index));
+        }
+
+        Ok(lump.chunks_exact(size))
+    }
+
+    /// Returns the keyvalues of every entity, in order.
+    pub fn entities(&self) -> Result<Vec<Vec<(String, String)>>, BspError> {
+        let lump = self.lump(LUMP_ENTITIES)?;
+        parse_entities(&String::from_utf8_lossy(lump)).ok_or(BspError::InvalidLump(LUMP_ENTITIES))
+    }
+
+    pub fn planes(&self) -> Result<Vec<Plane>, BspError> {
+        self.records(LUMP_PLANES, PLANE_SIZE)?
+            .map(|p| {
+                Ok(Plane {
+                    normal: read_vector(p, 0)?,
+                    dist: read_le::<f32>(p, 12)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the material names of the texture data entries.
+    pub fn tex_data_names(&self) -> Result<Vec<String>, BspError> {
+        let string_data = self.lump(LUMP_TEXDATA_STRING_DATA)?;
+        let string_table: Vec<usize> = self
+            .records(LUMP_TEXDATA_STRING_TABLE, 4)?
+            .map(|o| read_usize(o, 0))
+            .collect::<Result<_, _>>()?;
+
+        self.records(LUMP_TEXDATA, TEXDATA_SIZE)?
+            .map(|t| {
+                let offset = string_table
+                    .get(read_usize(t, TEXDATA_NAME)?)
+                    .copied()
+                    .ok_or(BspError::InvalidLump(LUMP_TEXDATA))?;
+
+                read_string(string_data, offset)
+            })
+            .collect()
+    }
+
+    pub fn tex_infos(&self) -> Result<Vec<TexInfo>, BspError> {
+        self.records(LUMP_TEXINFO, TEXINFO_SIZE)?
+            .map(|t| {
+                let axis = |i: usize| -> Result<[f32; 4], BspError> {
+                    Ok([
+                        read_le::<f32>(t, i * 16)?,
+                        read_le::<f32>(t, i * 16 + 4)?,
+                        read_le::<f32>(t, i * 16 + 8)?,
+                        read_le::<f32>(t, i * 16 + 12)?,
+                    ])
+                };
+
+                Ok(TexInfo {
+                    texture_vecs: [axis(0)?, axis(1)?],
+                    flags: read_le::<i32>(t, TEXINFO_FLAGS)?,
+                    tex_data: usize::try_from(read_le::<i32>(t, TEXINFO_TEXDATA)?).ok(),
+                })
+            })
+            .collect()
+    }
+
+    pub fn nodes(&self) -> Result<Vec<Node>, BspError> {
+        self.records(LUMP_NODES, NODE_SIZE)?
+            .map(|n| {
+                Ok(Node {
+                    children: [read_le::<i32>(n, 4)?, read_le::<i32>(n, 8)?],
+                })
+            })
+            .collect()
+    }
+
+    pub fn leaves(&self) -> Result<Vec<Leaf>, BspError> {
+        // version 0 leaves include ambient lighting
+        let size = if self.lump_version(LUMP_LEAFS) == 0 {
+            LEAF_SIZE_V0
+        } else {
+            LEAF_SIZE_V1
+        };
+
+        self.records(LUMP_LEAFS, size)?
+            .map(|l| {
+                let vector = |offset: usize| -> Result<[i16; 3], BspError> {
+                    Ok([
+                        read_le::<i16>(l, offset)?,
+                        read_le::<i16>(l, offset + 2)?,
+                        read_le::<i16>(l, offset + 4)?,
+                    ])
+                };
+
+                Ok(Leaf {
+                    cluster: usize::try_from(read_le::<i16>(l, LEAF_CLUSTER)?).ok(),
+                    mins: vector(LEAF_MINS)?,
+                    maxs: vector(LEAF_MINS + 6)?,
+                    first_brush: read_le::<u16>(l, LEAF_FIRST_BRUSH)?.into(),
+                    brush_count: read_le::<u16>(l, LEAF_FIRST_BRUSH + 2)?.into(),
+                })
+            })
+            .collect()
+    }
+
+    pub fn leaf_brushes(&self) -> Result<Vec<usize>, BspError> {
+        self.records(LUMP_LEAFBRUSHES, 2)?
+            .map(|b| Ok(read_le::<u16>(b, 0)?.into()))
+            .collect()
+    }
+
+    /// Returns the head nodes of the brush models. The first model is the world.
+    pub fn model_head_nodes(&self) -> Result<Vec<usize>, BspError> {
+        self.records(LUMP_MODELS, MODEL_SIZE)?
+            .map(|m| read_usize(m, MODEL_HEAD_NODE))
+            .collect()
+    }
+
+    pub fn brushes(&self) -> Result<Vec<Brush>, BspError> {
+        self.records(LUMP_BRUSHES, BRUSH_SIZE)?
+            .map(|b| {
+                Ok(Brush {
+                    first_side: read_usize(b, 0)?,
+                    side_count: read_usize(b, 4)?,
+                    contents: read_le::<i32>(b, 8)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the version and data of a game lump, identified by its four-character code.
+    pub fn game_lump(&self, id: [u8; 4]) -> Result<Option<(u16, &'a [u8])>, BspError> {
+        let lump = self.lump(LUMP_GAME_LUMP)?;
+        if lump.is_empty() {
+            return Ok(None);
+        }
+
+        let count = read_usize(lump, 0)?;
+
+        for i in 0..count {
+            let info = 4 + i * GAME_LUMP_SIZE;
+
+            if read_le::<i32>(lump, info)? != i32::from_be_bytes(id) {
+                continue;
+            }
+
+            if read_le::<u16>(lump, info + 4)? & GAME_LUMP_COMPRESSED != 0 {
+                return Err(BspError::CompressedLump(LUMP_GAME_LUMP));
+            }
+
+            let version = read_le::<u16>(lump, info + 6)?;
+            // game lump offsets are relative to the file
+            let offset = read_usize(lump, info + 8)?;
+            let length = read_usize(lump, info + 12)?;
+
+            let data = self
+                .data
+                .get(offset..offset + length)
+                .ok_or(BspError::UnexpectedEof)?;
+
+            return Ok(Some((version, data)));
+        }
+
+        Ok(None)
+    }
+
+    pub fn brush_sides(&self) -> Result<Vec<BrushSide>, BspError> {
+        self.records(LUMP_BRUSHSIDES, BRUSHSIDE_SIZE)?
+            .map(|s| {
+                Ok(BrushSide {
+                    plane: read_le::<u16>(s, 0)?.into(),
+                    tex_info: usize::try_from(read_le::<i16>(s, 2)?).ok(),
+                    // newer games store a `thin` flag in the upper byte
+                    bevel: s[6] != 0,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Parses the entity lump, a sequence of unnamed blocks of quoted keyvalues.
+fn parse_entities(text: &str) -> Option<Vec<Vec<(String, String)>>> {
+    let mut entities = Vec::new();
+    let mut current: Option<Vec<(String, String)>> = None;
+    let mut key: Option<String> = None;
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if current.is_none() => current = Some(Vec::new()),
+            '}' if key.is_none() => entities.push(current.take()?),
+            '"' => {
+                let string: String = chars.by_ref().take_while(|&c| c != '"').collect();
+
+                match key.take() {
+                    Some(key) => current.as_mut()?.push((key, string)),
+                    None => key = Some(string),
+                }
+            }
+            c if c.is_whitespace() || c == '\0' => {}
+            _ => return None,
+        }
+    }
+
+    if current.is_some() || key.is_some() {
+        return None;
+    }
+
+    Some(entities)
+}
+
+fn read_le<T: LeBytes>(data: &[u8], offset: usize) -> Result<T, BspError> {
+    bytes::read_le(data, offset).ok_or(BspError::UnexpectedEof)
+}
+
+fn read_usize(data: &[u8], offset: usize) -> Result<usize, BspError> {
+    usize::try_from(read_le::<i32>(data, offset)?).map_err(|_| BspError::InvalidHeader)
+}
+
+fn read_vector(data: &[u8], offset: usize) -> Result<[f32; 3], BspError> {
+    Ok([
+        read_le::<f32>(data, offset)?,
+        read_le::<f32>(data, offset + 4)?,
+        read_le::<f32>(data, offset + 8)?,
+    ])
+}
+
+fn read_string(data: &[u8], offset: usize) -> Result<String, BspError> {
+    let bytes = data.get(offset..).ok_or(BspError::UnexpectedEof)?;
+    let end = bytes
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(BspError::UnexpectedEof)?;
+
+    Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_entity_lump() {
+        let text = "{\n\"classname\" \"worldspawn\"\n\"skyname\" \"sky_day01_01\"\n}\n\
+            {\n\"classname\" \"light\"\n\"_light\" \"255 255 255 200\"\n}\n\0";
+
+        let entities = parse_entities(text).unwrap();
+        assert_eq!(entities.len(), 2);
+        assert_eq!(
+            entities[1],
+            vec![
+                ("classname".to_owned(), "light".to_owned()),
+                ("_light".to_owned(), "255 255 255 200".to_owned()),
+            ]
+        );
+
+        assert_eq!(parse_entities("{ \"classname\" }"), None);
+    }
+}
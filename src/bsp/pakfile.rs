@@ -0,0 +1,307 @@
+//! Reads the pakfile lump, a zip archive of the files embedded into the map,
+//! like the cubemaps built for it.
+//!
+//! The compiler stores the files uncompressed, but some games, like CS:GO and TF2,
+//! compress them with lzma when the map is repacked. Entries with other compression
+//! methods are skipped with a warning.
+
+use std::{
+    borrow::Cow,
+    fs,
+    io::{self, Cursor},
+    path::Path,
+};
+
+use lzma_rs::decompress::{Options, UnpackedSize};
+use tracing::warn;
+
+use super::{read_usize, Bsp, BspError, LUMP_PAKFILE};
+use crate::{bytes::read_le, game_path};
+
+const END_OF_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+const DIRECTORY_ENTRY_SIGNATURE: u32 = 0x0201_4b50;
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+
+const END_OF_DIRECTORY_SIZE: usize = 22;
+const DIRECTORY_ENTRY_SIZE: usize = 46;
+const LOCAL_HEADER_SIZE: usize = 30;
+/// The directory end record is followed by a comment of at most this size.
+const MAX_COMMENT_SIZE: usize = 0xFFFF;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_LZMA: u16 = 14;
+/// Size of the version and the properties size preceding the lzma properties.
+const LZMA_HEADER_SIZE: usize = 4;
+/// Size of the lzma properties, the literal and position bits and the dictionary size.
+const LZMA_PROPERTIES_SIZE: u16 = 5;
+
+/// A file of the pakfile. Compressed files are decompressed when their data is read, so
+/// that listing the files doesn't decompress all of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PakEntry<'a> {
+    /// Path of the file, with forward slashes.
+    pub name: &'a str,
+    data: &'a [u8],
+    /// Decompressed size of lzma compressed files, `None` for stored files.
+    lzma_size: Option<usize>,
+}
+
+impl<'a> PakEntry<'a> {
+    /// Returns the data of the file, decompressed if it's compressed.
+    /// `None` if the compressed data is invalid.
+    pub fn data(&self) -> Option<Cow<'a, [u8]>> {
+        match self.lzma_size {
+            Some(size) => decompress_lzma(self.data, size).map(Cow::Owned),
+            None => Some(Cow::Borrowed(self.data)),
+        }
+    }
+}
+
+/// Returns the files of the pakfile. Missing pakfiles have no files.
+///
+/// # Errors
+///
+/// Returns `Err` if the zip archive is invalid.
+pub fn pakfile<'a>(bsp: &Bsp<'a>) -> Result<Vec<PakEntry<'a>>, BspError> {
+    let lump = bsp.lump(LUMP_PAKFILE)?;
+
+    if lump.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    read_entries(lump).ok_or(BspError::InvalidLump(LUMP_PAKFILE))
+}
+
+/// Extracts the files into `target_dir`, with normalized paths so that they can be
+/// found on case-sensitive file systems. Files with paths outside of the directory are
+/// skipped. Returns the number of extracted files.
+///
+/// # Errors
+///
+/// Returns `Err` if writing the files fails.
+pub fn extract(entries: &[PakEntry], target_dir: &Path) -> io::Result<usize> {
+    let mut extracted = 0;
+
+    for entry in entries {
+        let path = game_path::normalize(entry.name);
+
+        if !game_path::is_safe_to_extract(&path) {
+            continue;
+        }
+
+        let Some(data) = entry.data() else {
+            warn!(
+                "pakfile entry `{}`: invalid lzma data, skipping",
+                entry.name
+            );
+            continue;
+        };
+
+        let target = target_dir.join(path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(target, data)?;
+        extracted += 1;
+    }
+
+    Ok(extracted)
+}
+
+pub(super) fn read_entries(zip: &[u8]) -> Option<Vec<PakEntry<'_>>> {
+    let search_start = zip
+        .len()
+        .saturating_sub(END_OF_DIRECTORY_SIZE + MAX_COMMENT_SIZE);
+    let end = (search_start..=zip.len().checked_sub(END_OF_DIRECTORY_SIZE)?)
+        .rev()
+        .find(|&o| read_le::<u32>(zip, o) == Some(END_OF_DIRECTORY_SIGNATURE))?;
+
+    let count = read_le::<u16>(zip, end + 10)?;
+    // the offsets are relative to the start of the lump
+    let mut offset = read_usize(zip, end + 16).ok()?;
+    let mut entries = Vec::with_capacity(count.into());
+
+    for _ in 0..count {
+        if read_le::<u32>(zip, offset)? != DIRECTORY_ENTRY_SIGNATURE {
+            return None;
+        }
+
+        let method = read_le::<u16>(zip, offset + 10)?;
+        let compressed_size = read_usize(zip, offset + 20).ok()?;
+        let size = read_usize(zip, offset + 24).ok()?;
+        let name_len = usize::from(read_le::<u16>(zip, offset + 28)?);
+        let extra_len = usize::from(read_le::<u16>(zip, offset + 30)?);
+        let comment_len = usize::from(read_le::<u16>(zip, offset + 32)?);
+        let header = read_usize(zip, offset + 42).ok()?;
+
+        let name_start = offset + DIRECTORY_ENTRY_SIZE;
+        let name = std::str::from_utf8(zip.get(name_start..name_start + name_len)?).ok()?;
+        offset = name_start + name_len + extra_len + comment_len;
+
+        if method != METHOD_STORED && method != METHOD_LZMA {
+            warn!("pakfile entry `{name}`: unsupported compression method {method}, skipping");
+            continue;
+        }
+
+        if read_le::<u32>(zip, header)? != LOCAL_HEADER_SIGNATURE {
+            return None;
+        }
+
+        // the local header can have a different extra field than the directory
+        let data_start = header
+            + LOCAL_HEADER_SIZE
+            + usize::from(read_le::<u16>(zip, header + 26)?)
+            + usize::from(read_le::<u16>(zip, header + 28)?);
+
+        entries.push(PakEntry {
+            name,
+            data: zip.get(data_start..data_start + compressed_size)?,
+            lzma_size: (method == METHOD_LZMA).then_some(size),
+        });
+    }
+
+    Some(entries)
+}
+
+/// Decompresses an lzma entry. Zip stores the lzma properties after a small header, and
+/// the decompressed size only in the directory.
+fn decompress_lzma(data: &[u8], size: usize) -> Option<Vec<u8>> {
+    if read_le::<u16>(data, 2)? != LZMA_PROPERTIES_SIZE {
+        return None;
+    }
+
+    // the size isn't trusted to preallocate more than the compressed data,
+    // the output grows if it really is larger
+    let mut output = Vec::with_capacity(size.min(data.len()));
+    let options = Options {
+        unpacked_size: UnpackedSize::UseProvided(Some(size as u64)),
+        ..Options::default()
+    };
+
+    lzma_rs::lzma_decompress_with_options(
+        &mut Cursor::new(data.get(LZMA_HEADER_SIZE..)?),
+        &mut output,
+        &options,
+    )
+    .ok()?;
+
+    Some(output)
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// Writes a zip archive of stored files.
+    pub fn write_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = Vec::new();
+        let mut directory = Vec::new();
+
+        for &(name, data) in files {
+            let header = u32::try_from(zip.len()).unwrap();
+            let name_len = u16::try_from(name.len()).unwrap();
+            let size = u32::try_from(data.len()).unwrap();
+
+            zip.extend(LOCAL_HEADER_SIGNATURE.to_le_bytes());
+            zip.extend([0; 14]);
+            zip.extend(size.to_le_bytes());
+            zip.extend(size.to_le_bytes());
+            zip.extend(name_len.to_le_bytes());
+            zip.extend([0; 2]);
+            zip.extend(name.as_bytes());
+            zip.extend(data);
+
+            directory.extend(DIRECTORY_ENTRY_SIGNATURE.to_le_bytes());
+            directory.extend([0; 16]);
+            directory.extend(size.to_le_bytes());
+            directory.extend(size.to_le_bytes());
+            directory.extend(name_len.to_le_bytes());
+            directory.extend([0; 12]);
+            directory.extend(header.to_le_bytes());
+            directory.extend(name.as_bytes());
+        }
+
+        let directory_offset = u32::try_from(zip.len()).unwrap();
+        let count = u16::try_from(files.len()).unwrap();
+        zip.extend(&directory);
+
+        zip.extend(END_OF_DIRECTORY_SIGNATURE.to_le_bytes());
+        zip.extend([0; 4]);
+        zip.extend(count.to_le_bytes());
+        zip.extend(count.to_le_bytes());
+        zip.extend(u32::try_from(directory.len()).unwrap().to_le_bytes());
+        zip.extend(directory_offset.to_le_bytes());
+        zip.extend([0; 2]);
+
+        zip
+    }
+
+    #[test]
+    fn read_stored_entries() {
+        let zip = write_zip(&[
+            ("materials/maps/test/c0_0_64.vtf", b"VTF\0"),
+            ("maps/test_particles.txt", b"particles"),
+        ]);
+
+        let entries = read_entries(&zip).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                PakEntry {
+                    name: "materials/maps/test/c0_0_64.vtf",
+                    data: b"VTF\0",
+                    lzma_size: None,
+                },
+                PakEntry {
+                    name: "maps/test_particles.txt",
+                    data: b"particles",
+                    lzma_size: None,
+                },
+            ]
+        );
+        assert_eq!(entries[1].data(), Some(Cow::Borrowed(&b"particles"[..])));
+
+        assert_eq!(read_entries(&zip[..zip.len() - 4]), None);
+    }
+
+    #[test]
+    fn extract_entries() {
+        let zip = write_zip(&[
+            ("Materials\\Maps\\Test\\C0_0_64.vtf", b"VTF\0"),
+            ("../escape.txt", b"no"),
+        ]);
+        let entries = read_entries(&zip).unwrap();
+
+        let dir = std::env::temp_dir().join("plumber_pakfile_test");
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(extract(&entries, &dir).unwrap(), 1);
+        assert_eq!(
+            fs::read(dir.join("materials/maps/test/c0_0_64.vtf")).unwrap(),
+            b"VTF\0"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn decompress_lzma_entry() {
+        // zip lzma version 9.20, then the properties and the stream
+        let mut data = vec![9, 20, 5, 0, 0x5d, 0x00, 0x00, 0x80, 0x00];
+        data.extend([
+            0x00, 0x31, 0x9d, 0x48, 0x67, 0xe0, 0x9e, 0x28, 0x82, 0xa7, 0xf5, 0x7f, 0x91, 0x9c,
+            0x6f, 0xff, 0xff, 0xe6, 0x29, 0x00, 0x00,
+        ]);
+
+        let entry = PakEntry {
+            name: "materials/maps/test/c0_0_64.vtf",
+            data: &data,
+            lzma_size: Some(64),
+        };
+        assert_eq!(entry.data(), Some(Cow::Owned(b"cubemap ".repeat(8))));
+
+        data[2] = 4;
+        assert_eq!(decompress_lzma(&data, 64), None);
+    }
+}
@@ -0,0 +1,226 @@
+//! Reads the static prop game lump (`sprp`), covering the lump versions 4 to 13.
+//!
+//! Version 10 is used by both Source 2013 and CS:GO with different layouts,
+//! which are told apart by the bsp version.
+
+use super::{read_le, read_usize, read_vector, Bsp, BspError, LUMP_GAME_LUMP};
+
+pub const GAME_LUMP_ID: [u8; 4] = *b"sprp";
+
+const MIN_VERSION: u16 = 4;
+const MAX_VERSION: u16 = 13;
+
+const NAME_SIZE: usize = 128;
+
+pub const FLAG_USE_LIGHTING_ORIGIN: u32 = 0x02;
+pub const FLAG_IGNORE_NORMALS: u32 = 0x08;
+pub const FLAG_NO_SHADOW: u32 = 0x10;
+pub const FLAG_SCREEN_SPACE_FADE: u32 = 0x20;
+pub const FLAG_NO_PER_VERTEX_LIGHTING: u32 = 0x40;
+pub const FLAG_NO_SELF_SHADOWING: u32 = 0x80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    /// Versions 4 to 7, which gained fields one at a time.
+    Dx(u16),
+    /// Source 2013 version 10, which is version 6 with 32-bit flags and lightmap resolution.
+    Source2013,
+    /// Versions 8 to 10, using CPU and GPU levels.
+    CpuGpu(u16),
+    /// CS:GO version 11 and later, with uniform scale.
+    UniformScale,
+}
+
+impl Layout {
+    fn new(version: u16, bsp_version: i32) -> Self {
+        match version {
+            4..=7 => Self::Dx(version),
+            10 if bsp_version < 21 => Self::Source2013,
+            8..=10 => Self::CpuGpu(version),
+            _ => Self::UniformScale,
+        }
+    }
+
+    fn min_size(self) -> usize {
+        match self {
+            Layout::Dx(4) => 56,
+            Layout::Dx(5) => 60,
+            Layout::Dx(6) => 64,
+            Layout::Dx(_) | Layout::CpuGpu(8) => 68,
+            Layout::CpuGpu(9) => 72,
+            Layout::Source2013 | Layout::CpuGpu(_) | Layout::UniformScale => 76,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticProp {
+    pub model: String,
+    pub origin: [f32; 3],
+    pub angles: [f32; 3],
+    pub solid: u8,
+    pub flags: u32,
+    pub skin: i32,
+    pub fade_min_distance: f32,
+    pub fade_max_distance: f32,
+    pub lighting_origin: [f32; 3],
+    pub forced_fade_scale: f32,
+    pub dx_levels: Option<[u16; 2]>,
+    /// Minimum and maximum CPU levels, followed by minimum and maximum GPU levels.
+    pub cpu_gpu_levels: Option<[u8; 4]>,
+    pub diffuse_modulation: Option<[u8; 4]>,
+    pub uniform_scale: f32,
+}
+
+/// Reads the static props of a map. Returns an empty list if the map has no static props.
+pub fn static_props(bsp: &Bsp) -> Result<Vec<StaticProp>, BspError> {
+    let Some((version, data)) = bsp.game_lump(GAME_LUMP_ID)? else {
+        return Ok(Vec::new());
+    };
+
+    if !(MIN_VERSION..=MAX_VERSION).contains(&version) {
+        return Err(BspError::UnsupportedLumpVersion(
+            LUMP_GAME_LUMP,
+            version.into(),
+        ));
+    }
+
+    let layout = Layout::new(version, bsp.version);
+
+    let name_count = read_usize(data, 0)?;
+    let names = (0..name_count)
+        .map(|i| {
+            let offset = 4 + i * NAME_SIZE;
+            let name = data
+                .get(offset..offset + NAME_SIZE)
+                .ok_or(BspError::UnexpectedEof)?;
+            let end = name.iter().position(|&b| b == 0).unwrap_or(NAME_SIZE);
+
+            Ok(String::from_utf8_lossy(&name[..end]).into_owned())
+        })
+        .collect::<Result<Vec<_>, BspError>>()?;
+
+    let leaves_offset = 4 + name_count * NAME_SIZE;
+    let leaf_count = read_usize(data, leaves_offset)?;
+
+    let props_offset = leaves_offset + 4 + leaf_count * 2;
+    let prop_count = read_usize(data, props_offset)?;
+    let props_data = data
+        .get(props_offset + 4..)
+        .ok_or(BspError::UnexpectedEof)?;
+
+    if prop_count == 0 {
+        return Ok(Vec::new());
+    }
+
+    // some games append fields to the known layouts, which are skipped
+    let size = props_data.len() / prop_count;
+    if size < layout.min_size() {
+        return Err(BspError::InvalidLump(LUMP_GAME_LUMP));
+    }
+
+    props_data
+        .chunks_exact(size)
+        .take(prop_count)
+        .map(|p| read_prop(p, layout, &names))
+        .collect()
+}
+
+fn read_prop(p: &[u8], layout: Layout, names: &[String]) -> Result<StaticProp, BspError> {
+    let model = names
+        .get(usize::from(read_le::<u16>(p, 24)?))
+        .cloned()
+        .ok_or(BspError::InvalidLump(LUMP_GAME_LUMP))?;
+
+    let mut prop = StaticProp {
+        model,
+        origin: read_vector(p, 0)?,
+        angles: read_vector(p, 12)?,
+        solid: p[30],
+        flags: p[31].into(),
+        skin: read_le::<i32>(p, 32)?,
+        fade_min_distance: read_le::<f32>(p, 36)?,
+        fade_max_distance: read_le::<f32>(p, 40)?,
+        lighting_origin: read_vector(p, 44)?,
+        forced_fade_scale: 1.0,
+        dx_levels: None,
+        cpu_gpu_levels: None,
+        diffuse_modulation: None,
+        uniform_scale: 1.0,
+    };
+
+    if layout != Layout::Dx(4) {
+        prop.forced_fade_scale = read_le::<f32>(p, 56)?;
+    }
+
+    let diffuse_offset = match layout {
+        Layout::Dx(4 | 5) => None,
+        Layout::Dx(version) => {
+            prop.dx_levels = Some([read_le::<u16>(p, 60)?, read_le::<u16>(p, 62)?]);
+            (version == 7).then_some(64)
+        }
+        Layout::Source2013 => {
+            prop.dx_levels = Some([read_le::<u16>(p, 60)?, read_le::<u16>(p, 62)?]);
+            prop.flags = read_le(p, 64)?;
+            Some(72)
+        }
+        Layout::CpuGpu(_) | Layout::UniformScale => {
+            prop.cpu_gpu_levels = Some([p[60], p[61], p[62], p[63]]);
+            Some(64)
+        }
+    };
+
+    if let Some(offset) = diffuse_offset {
+        prop.diffuse_modulation = Some([p[offset], p[offset + 1], p[offset + 2], p[offset + 3]]);
+    }
+
+    if layout == Layout::UniformScale {
+        prop.uniform_scale = read_le::<f32>(p, 72)?;
+    }
+
+    Ok(prop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prop_data(size: usize) -> Vec<u8> {
+        let mut data = vec![0; size];
+        data[0..4].copy_from_slice(&16.0_f32.to_le_bytes());
+        data[30] = 6;
+        data[31] = 0x12;
+        data
+    }
+
+    #[test]
+    fn read_prop_versions() {
+        let names = ["models/props/crate.mdl".to_owned()];
+
+        let prop = read_prop(&prop_data(56), Layout::new(4, 19), &names).unwrap();
+        assert_eq!(
+            prop.origin.map(f32::to_bits),
+            [16.0, 0.0, 0.0].map(f32::to_bits)
+        );
+        assert_eq!(prop.solid, 6);
+        assert_eq!(prop.flags, FLAG_USE_LIGHTING_ORIGIN | FLAG_NO_SHADOW);
+        assert_eq!(prop.dx_levels, None);
+
+        let mut data = prop_data(76);
+        data[64..68].copy_from_slice(&0x40_u32.to_le_bytes());
+        data[72..76].copy_from_slice(&[255, 128, 0, 255]);
+
+        let prop = read_prop(&data, Layout::new(10, 20), &names).unwrap();
+        assert_eq!(prop.flags, FLAG_NO_PER_VERTEX_LIGHTING);
+        assert_eq!(prop.dx_levels, Some([0, 0]));
+        assert_eq!(prop.diffuse_modulation, Some([255, 128, 0, 255]));
+
+        let mut data = prop_data(76);
+        data[72..76].copy_from_slice(&2.0_f32.to_le_bytes());
+
+        let prop = read_prop(&data, Layout::new(11, 21), &names).unwrap();
+        assert!((prop.uniform_scale - 2.0).abs() < f32::EPSILON);
+        assert_eq!(prop.cpu_gpu_levels, Some([0; 4]));
+        assert_eq!(prop.flags, 0x12);
+    }
+}
@@ -0,0 +1,114 @@
+//! Reads the visibility clusters of a compiled map, with the bounds of their leaves and
+//! the clusters they can see, so that the vis partitioning can be inspected.
+
+use super::{read_usize, Bsp, BspError, LUMP_VISIBILITY};
+
+/// A visibility cluster, made of the leaves which vvis treats as one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VisCluster {
+    pub index: usize,
+    /// Minimum and maximum corners of the leaves of the cluster.
+    pub leaf_bounds: Vec<([i16; 3], [i16; 3])>,
+    /// Clusters potentially visible from this cluster, including itself.
+    pub visible: Vec<usize>,
+}
+
+/// Returns the visibility clusters of the map. Maps compiled without vvis have a
+/// single cluster which sees itself.
+///
+/// # Errors
+///
+/// Returns `Err` if the leaf or visibility lump is invalid.
+pub fn vis_clusters(bsp: &Bsp) -> Result<Vec<VisCluster>, BspError> {
+    let leaves = bsp.leaves()?;
+    let lump = bsp.lump(LUMP_VISIBILITY)?;
+
+    let count = if lump.is_empty() {
+        leaves
+            .iter()
+            .filter_map(|l| l.cluster)
+            .max()
+            .map_or(0, |c| c + 1)
+    } else {
+        read_usize(lump, 0)?
+    };
+
+    let mut clusters: Vec<VisCluster> = (0..count)
+        .map(|index| {
+            let visible = if lump.is_empty() {
+                (0..count).collect()
+            } else {
+                // the potentially visible set comes first, followed by the audible set
+                let offset = read_usize(lump, 4 + index * 8)?;
+                decompress_pvs(lump, offset, count)?
+            };
+
+            Ok(VisCluster {
+                index,
+                leaf_bounds: Vec::new(),
+                visible,
+            })
+        })
+        .collect::<Result<_, BspError>>()?;
+
+    for leaf in &leaves {
+        if let Some(cluster) = leaf.cluster.and_then(|c| clusters.get_mut(c)) {
+            cluster.leaf_bounds.push((leaf.mins, leaf.maxs));
+        }
+    }
+
+    Ok(clusters)
+}
+
+/// Decompresses a cluster bit vector, in which each zero byte is followed by the
+/// number of zero bytes it stands for.
+fn decompress_pvs(lump: &[u8], mut offset: usize, count: usize) -> Result<Vec<usize>, BspError> {
+    let byte_count = count.div_ceil(8);
+    let mut visible = Vec::new();
+    let mut byte_index = 0;
+
+    while byte_index < byte_count {
+        let byte = *lump
+            .get(offset)
+            .ok_or(BspError::InvalidLump(LUMP_VISIBILITY))?;
+        offset += 1;
+
+        if byte == 0 {
+            let zeros = *lump
+                .get(offset)
+                .ok_or(BspError::InvalidLump(LUMP_VISIBILITY))?;
+            offset += 1;
+            byte_index += usize::from(zeros);
+            continue;
+        }
+
+        for bit in 0..8 {
+            let cluster = byte_index * 8 + bit;
+            if byte & (1 << bit) != 0 && cluster < count {
+                visible.push(cluster);
+            }
+        }
+
+        byte_index += 1;
+    }
+
+    Ok(visible)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decompress_visible_clusters() {
+        // clusters 0 and 2, 16 zero bits, then cluster 25
+        let lump = [0xFF, 0b0000_0101, 0, 2, 0b0000_0010];
+
+        assert_eq!(decompress_pvs(&lump, 1, 26).unwrap(), [0, 2, 25]);
+        assert_eq!(decompress_pvs(&lump, 1, 3).unwrap(), [0, 2]);
+        assert_eq!(
+            decompress_pvs(&lump, 2, 26),
+            Err(BspError::InvalidLump(LUMP_VISIBILITY))
+        );
+    }
+}
@@ -0,0 +1,47 @@
+//! Reads little-endian values at offsets of byte buffers, which all the binary formats
+//! read here use. Each format maps a value past the end of its data to its own error.
+
+use std::mem;
+
+/// A number stored as little-endian bytes.
+pub trait LeBytes: Sized {
+    fn from_le_slice(bytes: &[u8]) -> Option<Self>;
+}
+
+macro_rules! impl_le_bytes {
+    ($($ty:ty),*) => {
+        $(
+            impl LeBytes for $ty {
+                fn from_le_slice(bytes: &[u8]) -> Option<Self> {
+                    Some(Self::from_le_bytes(bytes.try_into().ok()?))
+                }
+            }
+        )*
+    };
+}
+
+impl_le_bytes!(u8, u16, i16, u32, i32, u64, f32);
+
+/// Reads a value at `offset`, or returns `None` if the data ends before it.
+pub fn read_le<T: LeBytes>(data: &[u8], offset: usize) -> Option<T> {
+    let end = offset.checked_add(mem::size_of::<T>())?;
+    T::from_le_slice(data.get(offset..end)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_values() {
+        let data = [0x01, 0x02, 0x03, 0x04, 0x00, 0x00, 0x80, 0x3f];
+
+        assert_eq!(read_le::<u16>(&data, 0), Some(0x0201));
+        assert_eq!(read_le::<u32>(&data, 0), Some(0x0403_0201));
+        assert_eq!(read_le::<i16>(&data, 6), Some(0x3f80));
+        assert_eq!(read_le::<f32>(&data, 4), Some(1.0));
+        assert_eq!(read_le::<u64>(&data, 0), Some(0x3f80_0000_0403_0201));
+        assert_eq!(read_le::<u32>(&data, 5), None);
+        assert_eq!(read_le::<u8>(&data, usize::MAX), None);
+    }
+}
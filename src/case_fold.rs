@@ -0,0 +1,123 @@
+//! Case-insensitive lookups in directory search paths. Content made on Windows refers to
+//! files with any case, and game paths are looked up in lowercase, but directories are
+//! case-sensitive on Linux.
+//!
+//! A directory is indexed once per session, and its files with uppercase characters in
+//! their path are linked in a mirror directory by their lowercase path. The mirror is
+//! searched after the directory itself, so exact matches are still preferred.
+
+use std::{
+    fs, io,
+    os::unix,
+    path::{Path, PathBuf},
+};
+
+/// Links the files of `dir` with uppercase characters in their path into `target_dir`
+/// by their lowercase path. If several files only differ by case, the first one found
+/// is used. Links from a previous session are kept, and the ones whose file has been
+/// removed are deleted. Returns the number of newly linked files.
+///
+/// # Errors
+///
+/// Returns `Err` if reading the directory or creating the links fails.
+pub fn mirror(dir: &Path, target_dir: &Path) -> io::Result<usize> {
+    // the links must not depend on the working directory
+    let dir = &dir.canonicalize()?;
+    let mut linked = 0;
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(relative_dir) = pending.pop() {
+        for entry in fs::read_dir(dir.join(&relative_dir))? {
+            let entry = entry?;
+            let relative = relative_dir.join(entry.file_name());
+
+            if entry.file_type()?.is_dir() {
+                pending.push(relative);
+                continue;
+            }
+
+            let Some(relative_str) = relative.to_str() else {
+                continue;
+            };
+            let lower = relative_str.to_ascii_lowercase();
+
+            if lower == relative_str {
+                continue;
+            }
+
+            let link = target_dir.join(lower);
+            if link.symlink_metadata().is_ok() {
+                continue;
+            }
+
+            if let Some(parent) = link.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            unix::fs::symlink(dir.join(&relative), link)?;
+            linked += 1;
+        }
+    }
+
+    remove_dangling_links(target_dir)?;
+
+    Ok(linked)
+}
+
+fn remove_dangling_links(target_dir: &Path) -> io::Result<()> {
+    let mut pending = vec![target_dir.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_symlink() && fs::metadata(entry.path()).is_err() {
+                fs::remove_file(entry.path())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn mirror_mixed_case() {
+        let root = env::temp_dir().join("plumber_case_fold_test");
+        let _ = fs::remove_dir_all(&root);
+
+        let dir = root.join("game");
+        fs::create_dir_all(dir.join("Materials/Brick")).unwrap();
+        fs::write(dir.join("Materials/Brick/BrickWall001.vmt"), b"mixed").unwrap();
+        fs::create_dir_all(dir.join("models")).unwrap();
+        fs::write(dir.join("models/crate.mdl"), b"lower").unwrap();
+
+        let target_dir = root.join("mirror");
+        assert_eq!(mirror(&dir, &target_dir).unwrap(), 1);
+
+        assert_eq!(
+            fs::read(target_dir.join("materials/brick/brickwall001.vmt")).unwrap(),
+            b"mixed"
+        );
+        assert!(!target_dir.join("models").exists());
+
+        // links are kept between sessions, and removed with their file
+        assert_eq!(mirror(&dir, &target_dir).unwrap(), 0);
+        fs::remove_file(dir.join("Materials/Brick/BrickWall001.vmt")).unwrap();
+        mirror(&dir, &target_dir).unwrap();
+        assert!(target_dir
+            .join("materials/brick/brickwall001.vmt")
+            .symlink_metadata()
+            .is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
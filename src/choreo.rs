@@ -0,0 +1,310 @@
+//! Reads choreography scenes (`.vcd`), which time the lines, gestures and looks of the
+//! actors of a scripted sequence.
+//!
+//! Scenes are made of nested blocks like keyvalues, but the statements before a block
+//! are whole lines of words instead of single keys.
+
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    vec::IntoIter,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChoreoError {
+    UnexpectedEof,
+    UnexpectedClose(usize),
+}
+
+impl Display for ChoreoError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedEof => f.write_str("unexpected end of file"),
+            Self::UnexpectedClose(line) => write!(f, "unexpected `}}` on line {line}"),
+        }
+    }
+}
+
+impl Error for ChoreoError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChoreoEvent {
+    /// Type of the event, like `speak`, `gesture` or `lookat`.
+    pub kind: String,
+    pub name: String,
+    pub actor: Option<String>,
+    pub channel: Option<String>,
+    /// Start time in seconds.
+    pub start: f32,
+    /// End time in seconds, `None` for instantaneous events.
+    pub end: Option<f32>,
+    /// Sound of speak events, sequence of gestures or target of looks.
+    pub parameter: String,
+    pub parameter2: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ChoreoScene {
+    pub actors: Vec<String>,
+    /// Events sorted by their start time.
+    pub events: Vec<ChoreoEvent>,
+    pub fps: Option<u32>,
+}
+
+/// A line of words, with the block following it.
+#[derive(Debug)]
+struct Statement {
+    words: Vec<String>,
+    children: Vec<Statement>,
+}
+
+impl Statement {
+    fn keyword(&self) -> &str {
+        self.words.first().map_or("", String::as_str)
+    }
+
+    fn word(&self, index: usize) -> &str {
+        self.words.get(index).map_or("", String::as_str)
+    }
+
+    fn child(&self, keyword: &str) -> Option<&Statement> {
+        self.children
+            .iter()
+            .find(|c| c.keyword().eq_ignore_ascii_case(keyword))
+    }
+}
+
+/// Parses a scene.
+///
+/// # Errors
+///
+/// Returns `Err` if the blocks of the scene aren't balanced.
+pub fn parse(text: &str) -> Result<ChoreoScene, ChoreoError> {
+    let mut tokens = tokenize(text).into_iter();
+    let statements = parse_block(&mut tokens, false)?;
+
+    let mut scene = ChoreoScene::default();
+
+    for statement in &statements {
+        match statement.keyword().to_ascii_lowercase().as_str() {
+            "event" => scene.events.push(event(statement, None, None)),
+            "actor" => {
+                let actor = statement.word(1).to_owned();
+
+                for channel in statement
+                    .children
+                    .iter()
+                    .filter(|c| c.keyword().eq_ignore_ascii_case("channel"))
+                {
+                    for child in channel
+                        .children
+                        .iter()
+                        .filter(|c| c.keyword().eq_ignore_ascii_case("event"))
+                    {
+                        scene
+                            .events
+                            .push(event(child, Some(&actor), Some(channel.word(1))));
+                    }
+                }
+
+                scene.actors.push(actor);
+            }
+            "fps" => scene.fps = statement.word(1).parse().ok(),
+            _ => {}
+        }
+    }
+
+    scene.events.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    Ok(scene)
+}
+
+fn event(statement: &Statement, actor: Option<&str>, channel: Option<&str>) -> ChoreoEvent {
+    let value = |keyword: &str| {
+        statement
+            .child(keyword)
+            .map_or_else(String::new, |c| c.word(1).to_owned())
+    };
+
+    let time = statement.child("time");
+    let time_value = |index: usize| {
+        time.and_then(|t| t.word(index).parse::<f32>().ok())
+            .unwrap_or(-1.0)
+    };
+
+    let start = time_value(1).max(0.0);
+    let end = time_value(2);
+
+    ChoreoEvent {
+        kind: statement.word(1).to_ascii_lowercase(),
+        name: statement.word(2).to_owned(),
+        actor: actor.map(str::to_owned),
+        channel: channel.map(str::to_owned),
+        start,
+        // a negative end marks an instantaneous event
+        end: (end >= start).then_some(end),
+        parameter: value("param"),
+        parameter2: value("param2"),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String, usize),
+    Open,
+    Close(usize),
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let mut chars = line.char_indices().peekable();
+
+        while let Some((start, c)) = chars.next() {
+            match c {
+                '/' if line[start..].starts_with("//") => break,
+                '{' => tokens.push(Token::Open),
+                '}' => tokens.push(Token::Close(line_number)),
+                '"' => {
+                    let mut word = String::new();
+                    for (_, c) in chars.by_ref() {
+                        if c == '"' {
+                            break;
+                        }
+                        word.push(c);
+                    }
+                    tokens.push(Token::Word(word, line_number));
+                }
+                c if c.is_whitespace() => {}
+                _ => {
+                    let mut end = line.len();
+                    while let Some(&(i, c)) = chars.peek() {
+                        if c.is_whitespace() || c == '{' || c == '}' || c == '"' {
+                            end = i;
+                            break;
+                        }
+                        chars.next();
+                    }
+                    tokens.push(Token::Word(line[start..end].to_owned(), line_number));
+                }
+            }
+        }
+    }
+
+    tokens
+}
+
+fn parse_block(tokens: &mut IntoIter<Token>, nested: bool) -> Result<Vec<Statement>, ChoreoError> {
+    let mut statements: Vec<Statement> = Vec::new();
+    let mut current_line = None;
+
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Word(word, line) => {
+                // words on the same line continue the statement
+                match statements.last_mut() {
+                    Some(statement) if current_line == Some(line) => statement.words.push(word),
+                    _ => statements.push(Statement {
+                        words: vec![word],
+                        children: Vec::new(),
+                    }),
+                }
+                current_line = Some(line);
+            }
+            Token::Open => {
+                let children = parse_block(tokens, true)?;
+
+                match statements.last_mut() {
+                    Some(statement) if statement.children.is_empty() => {
+                        statement.children = children;
+                    }
+                    _ => statements.push(Statement {
+                        words: Vec::new(),
+                        children,
+                    }),
+                }
+                current_line = None;
+            }
+            Token::Close(line) => {
+                if nested {
+                    return Ok(statements);
+                }
+
+                return Err(ChoreoError::UnexpectedClose(line));
+            }
+        }
+    }
+
+    if nested {
+        Err(ChoreoError::UnexpectedEof)
+    } else {
+        Ok(statements)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_scene() {
+        let scene = parse(
+            r#"
+            // Choreo version 1
+            event stoppoint "end"
+            {
+              time 4.000000 -1.000000
+              param "noaction"
+            }
+            actor "Alyx"
+            {
+              channel "audio"
+              {
+                event speak "alyx_hello"
+                {
+                  time 0.500000 2.250000
+                  param "Alyx.Hello"
+                  fixedlength
+                }
+              }
+              channel "look"
+              {
+                event lookat "!player"
+                {
+                  time 0.000000 3.000000
+                  param "!player"
+                }
+              }
+              faceposermodel "models\alyx.mdl"
+            }
+            fps 60
+            snap off
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(scene.actors, ["Alyx"]);
+        assert_eq!(scene.fps, Some(60));
+        assert_eq!(scene.events.len(), 3);
+
+        let look = &scene.events[0];
+        assert_eq!(look.kind, "lookat");
+        assert_eq!(look.channel.as_deref(), Some("look"));
+        assert_eq!(look.end, Some(3.0));
+
+        let speak = &scene.events[1];
+        assert_eq!(speak.name, "alyx_hello");
+        assert_eq!(speak.actor.as_deref(), Some("Alyx"));
+        assert_eq!((speak.start, speak.end), (0.5, Some(2.25)));
+        assert_eq!(speak.parameter, "Alyx.Hello");
+
+        let stop = &scene.events[2];
+        assert_eq!(stop.actor, None);
+        assert_eq!(stop.end, None);
+
+        assert_eq!(parse("actor \"a\"\n{\n"), Err(ChoreoError::UnexpectedEof));
+        assert_eq!(parse("}"), Err(ChoreoError::UnexpectedClose(1)));
+    }
+}
@@ -0,0 +1,136 @@
+//! Reads the output connections of entities, which the entity parser doesn't keep,
+//! so that the interactions between entities can be reconstructed.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::keyvalues::KeyValue;
+
+/// An output of an entity, firing an input of its targets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Connection {
+    pub target: String,
+    pub input: String,
+    pub parameter: String,
+    /// Delay in seconds before the input is fired.
+    pub delay: f32,
+    /// How many times the output fires, -1 is unlimited.
+    pub times_to_fire: i32,
+}
+
+/// Connections by their output name.
+pub type EntityConnections = BTreeMap<String, Vec<Connection>>;
+
+/// Parses a `target,input,parameter,delay,times` connection. Newer games separate the
+/// values with an escape character instead of commas.
+pub fn parse_connection(value: &str) -> Option<Connection> {
+    let separator = if value.contains('\x1b') { '\x1b' } else { ',' };
+    let mut values = value.split(separator).map(str::trim);
+
+    let target = values.next().filter(|t| !t.is_empty())?.to_owned();
+    let input = values.next()?.to_owned();
+    let parameter = values.next().unwrap_or_default().to_owned();
+    let delay = values.next().and_then(|v| v.parse().ok()).unwrap_or(0.0);
+    let times_to_fire = values.next().and_then(|v| v.parse().ok()).unwrap_or(-1);
+
+    Some(Connection {
+        target,
+        input,
+        parameter,
+        delay,
+        times_to_fire,
+    })
+}
+
+/// Collects the connections of the entities of a vmf by entity id.
+/// Entities without any connections are left out.
+pub fn vmf_connections(vmf: &[(String, KeyValue)]) -> HashMap<i32, EntityConnections> {
+    let mut connections = HashMap::new();
+
+    let entities = vmf
+        .iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case("entity"))
+        .filter_map(|(_, v)| v.as_block());
+
+    for entity in entities {
+        let Some(id) = entity
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("id"))
+            .and_then(|(_, v)| v.as_value())
+            .and_then(|v| v.trim().parse().ok())
+        else {
+            continue;
+        };
+
+        let mut outputs = EntityConnections::new();
+
+        let blocks = entity
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case("connections"))
+            .filter_map(|(_, v)| v.as_block());
+
+        for (output, value) in blocks.flatten() {
+            if let Some(connection) = value.as_value().and_then(parse_connection) {
+                outputs.entry(output.clone()).or_default().push(connection);
+            }
+        }
+
+        if !outputs.is_empty() {
+            connections.insert(id, outputs);
+        }
+    }
+
+    connections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::keyvalues;
+
+    #[test]
+    fn read_connections() {
+        let vmf = keyvalues::parse_raw(
+            "
+            entity
+            {
+                \"id\" \"12\"
+                \"classname\" \"logic_auto\"
+                connections
+                {
+                    \"OnMapSpawn\" \"door,Open,,1.5,-1\"
+                    \"OnMapSpawn\" \"relay\x1bTrigger\x1b\x1b0\x1b1\"
+                    \"OnMultiNewMap\" \",Broken\"
+                }
+            }
+            entity { \"id\" \"13\" \"classname\" \"info_target\" }
+            ",
+        )
+        .unwrap();
+
+        let connections = vmf_connections(&vmf);
+        assert_eq!(connections.len(), 1);
+
+        let outputs = &connections[&12];
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(
+            outputs["OnMapSpawn"],
+            [
+                Connection {
+                    target: "door".to_owned(),
+                    input: "Open".to_owned(),
+                    parameter: String::new(),
+                    delay: 1.5,
+                    times_to_fire: -1,
+                },
+                Connection {
+                    target: "relay".to_owned(),
+                    input: "Trigger".to_owned(),
+                    parameter: String::new(),
+                    delay: 0.0,
+                    times_to_fire: 1,
+                },
+            ]
+        );
+    }
+}
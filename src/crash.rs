@@ -0,0 +1,144 @@
+//! Writes a crash report when the importer panics. Panics otherwise only print a short
+//! message to the console, which is often not visible, so the reports make crashes
+//! actionable.
+
+use std::{
+    any::Any,
+    backtrace::Backtrace,
+    env,
+    fmt::Display,
+    fs::{self, File},
+    io::{self, Write},
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    process,
+    sync::{Mutex, MutexGuard},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use pyo3::{panic::PanicException, PyResult};
+
+use crate::watchdog;
+
+/// Directory in the temporary directory where the reports are written.
+const REPORT_DIR: &str = "plumber_crashes";
+
+/// Description of the current import, like the game and the settings.
+static IMPORT_CONTEXT: Mutex<Option<String>> = Mutex::new(None);
+static LAST_REPORT: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // the panicking thread may have held the lock
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Installs the panic hook, keeping the previous hook which prints the panic.
+pub fn install_hook() {
+    let previous = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        match write_report(info) {
+            Ok(path) => {
+                eprintln!(
+                    "[Plumber] [ERROR] crash report written to `{}`",
+                    path.display()
+                );
+                *lock(&LAST_REPORT) = Some(path);
+            }
+            Err(error) => eprintln!("[Plumber] [ERROR] crash report could not be written: {error}"),
+        }
+
+        previous(info);
+    }));
+}
+
+/// Sets the description of the current import, which is included in crash reports.
+pub fn set_import_context(context: String) {
+    *lock(&IMPORT_CONTEXT) = Some(context);
+}
+
+/// Path of the latest crash report of this session.
+pub fn last_report() -> Option<PathBuf> {
+    lock(&LAST_REPORT).clone()
+}
+
+/// Runs `f`, turning a panic into a Python exception which names the crash report.
+pub fn catch_panic<T>(f: impl FnOnce() -> T) -> PyResult<T> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        let report = last_report().map_or_else(
+            || "no crash report was written".to_owned(),
+            |path| format!("crash report: {}", path.display()),
+        );
+
+        PanicException::new_err(format!(
+            "Plumber crashed: {} ({report})",
+            panic_message(payload.as_ref())
+        ))
+    })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown error")
+}
+
+fn write_report(info: &dyn Display) -> io::Result<PathBuf> {
+    let dir = env::temp_dir().join(REPORT_DIR);
+    fs::create_dir_all(&dir)?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let path = dir.join(format!("crash_{timestamp}_{}.txt", process::id()));
+
+    let mut file = File::create(&path)?;
+
+    writeln!(file, "Plumber {} crashed", env!("CARGO_PKG_VERSION"))?;
+    writeln!(
+        file,
+        "thread `{}`: {info}",
+        thread::current().name().unwrap_or("<unnamed>")
+    )?;
+
+    writeln!(file, "\nassets being imported:")?;
+    match watchdog::try_active() {
+        Some(assets) => {
+            for asset in assets {
+                writeln!(file, "  {asset}")?;
+            }
+        }
+        None => writeln!(file, "  <unavailable>")?,
+    }
+
+    // the context is never locked while something can panic, so this can't deadlock
+    if let Some(context) = lock(&IMPORT_CONTEXT).as_deref() {
+        writeln!(file, "\n{context}")?;
+    }
+
+    writeln!(file, "\nbacktrace:\n{}", Backtrace::force_capture())?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_panic_messages() {
+        let payload: Box<dyn Any + Send> = Box::new("static message");
+        assert_eq!(panic_message(payload.as_ref()), "static message");
+
+        let payload: Box<dyn Any + Send> = Box::new(format!("formatted {}", 1));
+        assert_eq!(panic_message(payload.as_ref()), "formatted 1");
+
+        let payload: Box<dyn Any + Send> = Box::new(1);
+        assert_eq!(panic_message(payload.as_ref()), "unknown error");
+    }
+}
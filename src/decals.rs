@@ -0,0 +1,259 @@
+//! Projects `infodecal` entities onto the world brush faces they are placed on, as the
+//! game does when loading the map.
+//!
+//! Decal materials aren't loaded along with the map, so the importer loads them
+//! afterwards.
+
+use std::collections::HashMap;
+
+use glam::Vec3;
+
+use crate::{
+    game_path::material_name,
+    keyvalues::{self, KeyValue},
+    kv_utils::{block_value, nested_value, parse_vector},
+    smoothing_groups::polygon_normal,
+    world_faces::{clip, world_faces, Face},
+};
+
+const DECAL_CLASS: &str = "infodecal";
+
+/// Faces at most this far from the decal origin receive the decal, in units.
+const PROJECTION_DISTANCE: f32 = 4.0;
+/// Offset of the decal from the face, so that it doesn't flicker with the face.
+const SURFACE_OFFSET: f32 = 0.1;
+/// Size of decals with an unreadable texture, in units.
+const DEFAULT_SIZE: [f32; 2] = [64.0; 2];
+
+/// A decal projected onto the faces it covers.
+#[derive(Debug, Clone)]
+pub struct Decal {
+    pub id: i32,
+    /// Path of the material, like `materials/decals/crater01.vmt`.
+    pub material: String,
+    pub origin: Vec3,
+    pub faces: Vec<DecalFace>,
+}
+
+/// The part of a decal on a single brush face.
+#[derive(Debug, Clone)]
+pub struct DecalFace {
+    /// Vertices relative to the decal origin.
+    pub vertices: Vec<Vec3>,
+    pub uvs: Vec<[f32; 2]>,
+}
+
+/// Returns the path of the material of a decal texture keyvalue.
+pub fn material_path(texture: &str) -> String {
+    format!("materials/{}.vmt", material_name(texture, ".vmt"))
+}
+
+/// Finds the base texture and the scale of a decal material. Patch materials are
+/// searched too, since they usually only replace the base texture.
+pub fn vmt_base_texture(vmt: &str) -> Option<(String, f32)> {
+    let root = keyvalues::parse_raw(vmt).ok()?;
+
+    let base_texture = nested_value(&root, "$basetexture")?;
+    let scale = nested_value(&root, "$decalscale")
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|s: &f32| *s > 0.0)
+        .unwrap_or(1.0);
+
+    Some((
+        format!("materials/{}.vtf", material_name(base_texture, ".vtf")),
+        scale,
+    ))
+}
+
+/// Projects the decals of a vmf. `texture_size` returns the size of a decal texture
+/// in units, and is called once per texture.
+pub fn vmf_decals(
+    vmf: &[(String, KeyValue)],
+    mut texture_size: impl FnMut(&str) -> Option<[f32; 2]>,
+) -> Vec<Decal> {
+    let entities: Vec<_> = vmf
+        .iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case("entity"))
+        .filter_map(|(_, v)| v.as_block())
+        .filter(|e| {
+            block_value(e, "classname").is_some_and(|c| c.eq_ignore_ascii_case(DECAL_CLASS))
+        })
+        .collect();
+
+    if entities.is_empty() {
+        return Vec::new();
+    }
+
+    let faces = world_faces(vmf);
+    let mut sizes = HashMap::new();
+
+    entities
+        .into_iter()
+        .filter_map(|entity| {
+            let id = block_value(entity, "id")?.trim().parse().ok()?;
+            let texture = block_value(entity, "texture")?;
+            let origin = parse_vector(block_value(entity, "origin")?)?;
+
+            let size = *sizes
+                .entry(texture.to_ascii_lowercase())
+                .or_insert_with(|| texture_size(texture).unwrap_or(DEFAULT_SIZE));
+
+            let decal_faces: Vec<_> = faces
+                .iter()
+                .filter_map(|face| project(face, origin, size))
+                .collect();
+
+            (!decal_faces.is_empty()).then(|| Decal {
+                id,
+                material: material_path(texture),
+                origin,
+                faces: decal_faces,
+            })
+        })
+        .collect()
+}
+
+/// Projects a decal onto a face, clipped to the face.
+fn project(face: &Face, origin: Vec3, size: [f32; 2]) -> Option<DecalFace> {
+    let offset = face.normal.dot(origin) - face.distance;
+    if offset.abs() > PROJECTION_DISTANCE {
+        return None;
+    }
+
+    let center = origin - face.normal * offset;
+    let (s, t) = decal_basis(face.normal);
+    let [half_width, half_height] = size.map(|s| s / 2.0);
+
+    let mut vertices: Vec<Vec3> = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+        .iter()
+        .map(|&(x, y)| center + s * (x * half_width) + t * (y * half_height))
+        .collect();
+
+    // faces point along the face normal
+    if polygon_normal(vertices.iter().copied()).is_some_and(|n| n.dot(face.normal) < 0.0) {
+        vertices.reverse();
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let face_center = face.vertices.iter().copied().sum::<Vec3>() / face.vertices.len() as f32;
+    let next = face.vertices.iter().cycle().skip(1);
+
+    for (&a, &b) in face.vertices.iter().zip(next) {
+        let Some(mut edge_normal) = (b - a).cross(face.normal).try_normalize() else {
+            continue;
+        };
+
+        if edge_normal.dot(face_center - a) > 0.0 {
+            edge_normal = -edge_normal;
+        }
+
+        vertices = clip(&vertices, edge_normal, edge_normal.dot(a));
+    }
+
+    if vertices.len() < 3 {
+        return None;
+    }
+
+    let uvs = vertices
+        .iter()
+        .map(|&v| {
+            let v = v - center;
+            [v.dot(s) / size[0] + 0.5, v.dot(t) / size[1] + 0.5]
+        })
+        .collect();
+
+    let vertices = vertices
+        .into_iter()
+        .map(|v| v + face.normal * SURFACE_OFFSET - origin)
+        .collect();
+
+    Some(DecalFace { vertices, uvs })
+}
+
+/// Returns the texture axes of a decal on a surface, like the game does.
+/// Decals on floors and ceilings are aligned to the x axis, others are upright.
+fn decal_basis(normal: Vec3) -> (Vec3, Vec3) {
+    let (s, t) = if normal.z.abs() > std::f32::consts::FRAC_1_SQRT_2 {
+        let t = Vec3::X.cross(normal);
+        (normal.cross(t), t)
+    } else {
+        let s = normal.cross(-Vec3::Z);
+        (s, s.cross(normal))
+    };
+
+    (
+        s.try_normalize().unwrap_or(Vec3::X),
+        t.try_normalize().unwrap_or(-Vec3::Z),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::world_faces::EPSILON;
+
+    #[test]
+    fn project_decals() {
+        let vmf = keyvalues::parse_raw(
+            r#"
+            world
+            {
+                solid
+                {
+                    side { "plane" "(-64 64 0) (64 64 0) (64 -64 0)" }
+                    side { "plane" "(-64 -64 -16) (64 -64 -16) (64 64 -16)" }
+                    side { "plane" "(-64 64 0) (-64 -64 0) (-64 -64 -16)" }
+                    side { "plane" "(64 64 -16) (64 -64 -16) (64 -64 0)" }
+                    side { "plane" "(64 64 0) (-64 64 0) (-64 64 -16)" }
+                    side { "plane" "(64 -64 -16) (-64 -64 -16) (-64 -64 0)" }
+                }
+            }
+            entity
+            {
+                "id" "5"
+                "classname" "infodecal"
+                "texture" "Decals\Crater01"
+                "origin" "56 0 1"
+            }
+            entity
+            {
+                "id" "6"
+                "classname" "infodecal"
+                "texture" "decals/floating"
+                "origin" "0 0 256"
+            }
+            "#,
+        )
+        .unwrap();
+
+        let mut requested = Vec::new();
+        let decals = vmf_decals(&vmf, |texture| {
+            requested.push(texture.to_owned());
+            Some([32.0, 32.0])
+        });
+        assert_eq!(requested.len(), 2);
+
+        // the floating decal doesn't touch any face
+        assert_eq!(decals.len(), 1);
+        let decal = &decals[0];
+        assert_eq!(decal.id, 5);
+        assert_eq!(decal.material, "materials/decals/crater01.vmt");
+
+        // the decal is clipped to the edge of the top face
+        assert_eq!(decal.faces.len(), 1);
+        let face = &decal.faces[0];
+        assert!(face.vertices.iter().all(|v| v.x <= 8.0 + EPSILON));
+        assert!(face.vertices.iter().all(|v| (v.z + 0.9).abs() < EPSILON));
+        assert!(face
+            .uvs
+            .iter()
+            .all(|[u, v]| (0.0..=0.75 + EPSILON).contains(u) && (0.0..=1.0).contains(v)));
+
+        let vmt = "patch { include \"materials/x.vmt\" insert { $basetexture Decals\\Crater01.vtf $decalscale 0.5 } }";
+        assert_eq!(
+            vmt_base_texture(vmt),
+            Some(("materials/decals/crater01.vtf".to_owned(), 0.5))
+        );
+    }
+}
@@ -0,0 +1,740 @@
+//! Scatters detail props on the displacements of a vmf, like the compiler does. The
+//! material of a displacement names a detail type with `%detailtype`, which is looked
+//! up in the detail types of the map (`detail.vbsp`).
+//!
+//! The compiler places the props randomly, so the scattered props only match the
+//! compiled map in their density and distribution, not in their exact positions.
+
+use std::collections::BTreeMap;
+
+use glam::Vec3;
+
+use crate::{
+    asset::material::patch,
+    bsp::detail_props::{DetailModel, DetailSprite, SpriteDict, SpriteShape, DEFAULT_MATERIAL},
+    keyvalues::{self, KeyValue, ParseError},
+    kv_utils::{block_value, child_block},
+    world_faces::{displacement_corners, solid_windings, SideWinding},
+};
+
+/// The detail types the compiler uses if the world doesn't set `detailvbsp`.
+pub const DEFAULT_DETAIL_TYPES: &str = "detail.vbsp";
+
+/// Densities are given per this many square units.
+const DENSITY_AREA: f32 = 1_000_000.0;
+/// Surfaces closer to vertical than this are treated as flat for upright props.
+const VERTICAL_EPSILON: f32 = 0.999;
+
+#[derive(Debug, Clone, PartialEq)]
+enum DetailKind {
+    Sprite {
+        dict: SpriteDict,
+        shape: SpriteShape,
+        random_scale: f32,
+    },
+    Model(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct DetailEntry {
+    /// Cumulative probability of this and the previous entries of the group.
+    amount: f32,
+    kind: DetailKind,
+    /// Whether the prop ignores the slope of the surface.
+    upright: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct DetailGroup {
+    /// Displacement alpha the group is placed at.
+    alpha: f32,
+    entries: Vec<DetailEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct DetailType {
+    density: f32,
+    /// Groups sorted by their alpha.
+    groups: Vec<DetailGroup>,
+}
+
+/// Detail types of a map, by their lowercase name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DetailTypes(BTreeMap<String, DetailType>);
+
+impl DetailTypes {
+    /// Parses a `detail.vbsp` file. Entries which are neither sprites nor models are
+    /// left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the file isn't valid keyvalues.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let root = keyvalues::parse_raw(text)?;
+        let mut types = Self::default();
+
+        for (name, detail_type) in root
+            .iter()
+            .filter_map(|(_, v)| v.as_block())
+            .flatten()
+            .filter_map(|(k, v)| Some((k, v.as_block()?)))
+        {
+            let density = block_value(detail_type, "density")
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(0.0);
+
+            let mut groups: Vec<DetailGroup> = detail_type
+                .iter()
+                .filter_map(|(_, v)| v.as_block())
+                .map(parse_group)
+                .collect();
+            groups.sort_by(|a, b| a.alpha.total_cmp(&b.alpha));
+
+            types
+                .0
+                .insert(name.to_ascii_lowercase(), DetailType { density, groups });
+        }
+
+        Ok(types)
+    }
+}
+
+fn parse_group(group: &[(String, KeyValue)]) -> DetailGroup {
+    let mut amount = 0.0;
+    let mut entries = Vec::new();
+
+    for entry in group.iter().filter_map(|(_, v)| v.as_block()) {
+        let float = |key: &str, default: f32| {
+            block_value(entry, key)
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(default)
+        };
+
+        let kind = if let Some(model) = block_value(entry, "model") {
+            DetailKind::Model(model.trim().to_owned())
+        } else if let (Some(sprite), Some(size)) = (
+            block_value(entry, "sprite"),
+            block_value(entry, "spritesize"),
+        ) {
+            let Some(dict) = sprite_dict(sprite, size) else {
+                continue;
+            };
+
+            let shape = match block_value(entry, "sprite_shape").map(str::trim) {
+                Some(s) if s.eq_ignore_ascii_case("cross") => SpriteShape::Cross,
+                Some(s) if s.eq_ignore_ascii_case("tri") => SpriteShape::Tri,
+                _ => SpriteShape::Flat,
+            };
+
+            DetailKind::Sprite {
+                dict,
+                shape,
+                random_scale: float("spriterandomscale", 0.0),
+            }
+        } else {
+            continue;
+        };
+
+        amount += float("amount", 1.0);
+
+        entries.push(DetailEntry {
+            amount,
+            kind,
+            upright: block_value(entry, "upright")
+                .and_then(|v| v.trim().parse::<i32>().ok())
+                .is_some_and(|v| v != 0),
+        });
+    }
+
+    DetailGroup {
+        alpha: block_value(group, "alpha")
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(1.0),
+        entries,
+    }
+}
+
+/// Parses the `x y width height texture_size` texture region and the
+/// `origin_u origin_v width height` size of a sprite.
+fn sprite_dict(sprite: &str, size: &str) -> Option<SpriteDict> {
+    let parse = |value: &str| -> Option<Vec<f32>> {
+        value
+            .split_ascii_whitespace()
+            .map(|v| v.parse().ok())
+            .collect()
+    };
+
+    let sprite = parse(sprite)?;
+    let size = parse(size)?;
+
+    let &[x, y, width, height, texture_size] = sprite.get(..5)? else {
+        return None;
+    };
+    let &[origin_u, origin_v, size_width, size_height] = size.get(..4)? else {
+        return None;
+    };
+
+    if texture_size <= 0.0 {
+        return None;
+    }
+
+    let origin_x = size_width * origin_u;
+    let origin_y = size_height * origin_v;
+
+    // half a texel is left out on each side, so that neighboring sprites don't bleed in
+    Some(SpriteDict {
+        upper_left: [-origin_x, size_height - origin_y],
+        lower_right: [size_width - origin_x, -origin_y],
+        tex_upper_left: [(x + 0.5) / texture_size, (y + 0.5) / texture_size],
+        tex_lower_right: [
+            (x + width - 0.5) / texture_size,
+            (y + height - 0.5) / texture_size,
+        ],
+    })
+}
+
+/// Finds the detail type of a material, following the includes of patch materials.
+/// `read` returns the text of a material path, as written in the `include` parameter.
+pub fn vmt_detail_type(
+    path: &str,
+    read: impl FnMut(&str) -> Result<String, String>,
+) -> Option<String> {
+    let patched = patch::resolve(path, read).ok()?;
+
+    patched
+        .parameters
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("%detailtype"))
+        .and_then(|(_, v)| v.as_value())
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_owned)
+}
+
+/// Detail props scattered on a single displacement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DispScatter {
+    pub side_id: i32,
+    pub detail_type: String,
+    pub sprites: Vec<DetailSprite>,
+    pub models: Vec<DetailModel>,
+}
+
+/// Detail props scattered on the displacements of a map.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DetailScatter {
+    /// Material the sprites are cut from.
+    pub material: String,
+    pub displacements: Vec<DispScatter>,
+}
+
+/// Scatters detail props on the displacements of the world and detail brushes, which
+/// are the ones the compiler considers. `read_detail_types` reads a detail types file
+/// by its path, and `material_detail_type` returns the detail type of a material.
+pub fn vmf_detail_scatter(
+    vmf: &[(String, KeyValue)],
+    read_detail_types: impl FnOnce(&str) -> Option<DetailTypes>,
+    mut material_detail_type: impl FnMut(&str) -> Option<String>,
+) -> DetailScatter {
+    let world = child_block(vmf, "world").unwrap_or_default();
+
+    let material = block_value(world, "detailmaterial")
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .unwrap_or(DEFAULT_MATERIAL);
+
+    let mut scatter = DetailScatter {
+        material: material.to_owned(),
+        displacements: Vec::new(),
+    };
+
+    let solids: Vec<_> = vmf
+        .iter()
+        .filter(|(k, v)| {
+            k.eq_ignore_ascii_case("world")
+                || (k.eq_ignore_ascii_case("entity")
+                    && v.as_block()
+                        .and_then(|e| block_value(e, "classname"))
+                        .is_some_and(|c| c.eq_ignore_ascii_case("func_detail")))
+        })
+        .filter_map(|(_, v)| v.as_block())
+        .flatten()
+        .filter(|(k, _)| k.eq_ignore_ascii_case("solid"))
+        .filter_map(|(_, v)| v.as_block())
+        .collect();
+
+    let windings: Vec<_> = solids
+        .into_iter()
+        .flat_map(solid_windings)
+        .filter(|w| {
+            w.side
+                .iter()
+                .any(|(k, v)| k.eq_ignore_ascii_case("dispinfo") && v.as_block().is_some())
+        })
+        .collect();
+
+    if windings.is_empty() {
+        return scatter;
+    }
+
+    let detail_types_path = block_value(world, "detailvbsp")
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .unwrap_or(DEFAULT_DETAIL_TYPES);
+
+    let Some(detail_types) = read_detail_types(detail_types_path) else {
+        return scatter;
+    };
+
+    let mut material_types: BTreeMap<String, Option<String>> = BTreeMap::new();
+
+    for winding in windings {
+        let material = block_value(winding.side, "material")
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        let Some(type_name) = material_types
+            .entry(material)
+            .or_insert_with_key(|material| material_detail_type(material))
+            .clone()
+        else {
+            continue;
+        };
+
+        let Some(detail_type) = detail_types.0.get(&type_name.to_ascii_lowercase()) else {
+            continue;
+        };
+
+        let side_id = block_value(winding.side, "id")
+            .and_then(|id| id.trim().parse().ok())
+            .unwrap_or(0);
+
+        let Some(displacement) = Displacement::new(&winding) else {
+            continue;
+        };
+
+        #[allow(clippy::cast_sign_loss)]
+        let mut random = Random::new(side_id as u32);
+        let (sprites, models) = displacement.scatter(detail_type, &mut random);
+
+        if !sprites.is_empty() || !models.is_empty() {
+            scatter.displacements.push(DispScatter {
+                side_id,
+                detail_type: type_name,
+                sprites,
+                models,
+            });
+        }
+    }
+
+    scatter
+}
+
+/// The built surface of a displacement.
+struct Displacement {
+    /// Vertices per row and column.
+    size: usize,
+    positions: Vec<Vec3>,
+    /// Blend alphas from 0 to 1.
+    alphas: Vec<f32>,
+    normal: Vec3,
+}
+
+impl Displacement {
+    fn new(winding: &SideWinding) -> Option<Self> {
+        let dispinfo = child_block(winding.side, "dispinfo")?;
+
+        if winding.vertices.len() != 4 {
+            return None;
+        }
+
+        let power: u32 = block_value(dispinfo, "power")?.trim().parse().ok()?;
+        if !(1..=4).contains(&power) {
+            return None;
+        }
+        let size = (1 << power) + 1;
+
+        let corners = displacement_corners(&winding.vertices, dispinfo)?;
+        let elevation: f32 = block_value(dispinfo, "elevation")
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(0.0);
+
+        let normals = rows(dispinfo, "normals", size, 3);
+        let distances = rows(dispinfo, "distances", size, 1);
+        let offsets = rows(dispinfo, "offsets", size, 3);
+        let alphas = rows(dispinfo, "alphas", size, 1);
+
+        let vector = |values: &Option<Vec<f32>>, index: usize| {
+            values.as_ref().map_or(Vec3::ZERO, |v| {
+                Vec3::from_slice(&v[index * 3..index * 3 + 3])
+            })
+        };
+        let scalar =
+            |values: &Option<Vec<f32>>, index: usize| values.as_ref().map_or(0.0, |v| v[index]);
+
+        let step = 1.0 / (size - 1) as f32;
+        let mut positions = Vec::with_capacity(size * size);
+
+        for row in 0..size {
+            let v = row as f32 * step;
+            let left = corners[0].lerp(corners[1], v);
+            let right = corners[3].lerp(corners[2], v);
+
+            for column in 0..size {
+                let u = column as f32 * step;
+                let index = row * size + column;
+
+                positions.push(
+                    left.lerp(right, u)
+                        + vector(&normals, index) * scalar(&distances, index)
+                        + vector(&offsets, index)
+                        + winding.normal * elevation,
+                );
+            }
+        }
+
+        Some(Self {
+            size,
+            positions,
+            alphas: (0..size * size)
+                .map(|i| (scalar(&alphas, i) / 255.0).clamp(0.0, 1.0))
+                .collect(),
+            normal: winding.normal,
+        })
+    }
+
+    /// Returns the corner indices of the triangles of the displacement.
+    fn triangles(&self) -> impl Iterator<Item = [usize; 3]> + '_ {
+        (0..self.size - 1)
+            .flat_map(move |row| (0..self.size - 1).map(move |column| row * self.size + column))
+            .flat_map(move |corner| {
+                let right = corner + 1;
+                let below = corner + self.size;
+
+                [[corner, right, below + 1], [corner, below + 1, below]]
+            })
+    }
+
+    fn scatter(
+        &self,
+        detail_type: &DetailType,
+        random: &mut Random,
+    ) -> (Vec<DetailSprite>, Vec<DetailModel>) {
+        let mut sprites = Vec::new();
+        let mut models = Vec::new();
+
+        for triangle in self.triangles() {
+            let [p0, p1, p2] = triangle.map(|i| self.positions[i]);
+            let [a0, a1, a2] = triangle.map(|i| self.alphas[i]);
+
+            let cross = (p1 - p0).cross(p2 - p0);
+            let Some(mut normal) = cross.try_normalize() else {
+                continue;
+            };
+            if normal.dot(self.normal) < 0.0 {
+                normal = -normal;
+            }
+
+            // the fraction of a sample is placed by chance
+            let samples = detail_type.density * cross.length() / 2.0 / DENSITY_AREA;
+            let mut count = samples.floor();
+            if random.sample() < samples - count {
+                count += 1.0;
+            }
+
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            for _ in 0..count as usize {
+                let (mut u, mut v) = (random.sample(), random.sample());
+                if u + v > 1.0 {
+                    (u, v) = (1.0 - u, 1.0 - v);
+                }
+
+                let origin = (p0 + (p1 - p0) * u + (p2 - p0) * v).to_array();
+                let alpha = a0 + (a1 - a0) * u + (a2 - a0) * v;
+
+                let Some(entry) = detail_type
+                    .select_group(alpha, random)
+                    .and_then(|g| g.select_entry(random))
+                else {
+                    continue;
+                };
+
+                let yaw = random.sample() * 360.0;
+
+                match &entry.kind {
+                    &DetailKind::Sprite {
+                        dict,
+                        shape,
+                        random_scale,
+                    } => sprites.push(DetailSprite {
+                        dict,
+                        shape,
+                        origin,
+                        angles: [0.0, yaw, 0.0],
+                        scale: 1.0 + (random.sample() * 2.0 - 1.0) * random_scale,
+                    }),
+                    DetailKind::Model(model) => models.push(DetailModel {
+                        model: model.clone(),
+                        origin,
+                        angles: if entry.upright || normal.z > VERTICAL_EPSILON {
+                            [0.0, yaw, 0.0]
+                        } else {
+                            // pitches the up axis of the model onto the normal
+                            [
+                                normal.z.clamp(-1.0, 1.0).acos().to_degrees(),
+                                normal.y.atan2(normal.x).to_degrees(),
+                                0.0,
+                            ]
+                        },
+                    }),
+                }
+            }
+        }
+
+        (sprites, models)
+    }
+}
+
+impl DetailType {
+    /// Picks the group for a displacement alpha, randomly choosing between the groups
+    /// around the alpha by how close it is to them.
+    fn select_group(&self, alpha: f32, random: &mut Random) -> Option<&DetailGroup> {
+        let last = self.groups.len().checked_sub(1)?;
+
+        let start = (0..last)
+            .find(|&i| alpha < self.groups[i + 1].alpha)
+            .unwrap_or(last);
+        let end = (start + 1).min(last);
+
+        if start == end {
+            return self.groups.get(start);
+        }
+
+        let range = self.groups[end].alpha - self.groups[start].alpha;
+        // the groups are sorted, so the range is never negative
+        let fraction = if range > 0.0 {
+            (alpha - self.groups[start].alpha) / range
+        } else {
+            0.0
+        };
+
+        self.groups.get(if random.sample() < fraction {
+            end
+        } else {
+            start
+        })
+    }
+}
+
+impl DetailGroup {
+    /// Picks an entry by the amounts, or `None` if the amounts add up to less than 1.
+    fn select_entry(&self, random: &mut Random) -> Option<&DetailEntry> {
+        let value = random.sample();
+
+        self.entries.iter().find(|e| value <= e.amount)
+    }
+}
+
+/// Parses the `row0`, `row1`... values of a dispinfo block, with `components` numbers
+/// per vertex. Returns `None` if the block is missing or malformed.
+fn rows(
+    dispinfo: &[(String, KeyValue)],
+    key: &str,
+    size: usize,
+    components: usize,
+) -> Option<Vec<f32>> {
+    let block = child_block(dispinfo, key)?;
+
+    let mut values = Vec::with_capacity(size * size * components);
+
+    for row in 0..size {
+        let row = block_value(block, &format!("row{row}"))?;
+        let len = values.len();
+
+        values.extend(
+            row.split_ascii_whitespace()
+                .filter_map(|v| v.parse::<f32>().ok())
+                .take(size * components),
+        );
+
+        if values.len() != len + size * components {
+            return None;
+        }
+    }
+
+    Some(values)
+}
+
+/// A small deterministic random generator, so that imports of the same map scatter
+/// the same props.
+struct Random(u32);
+
+impl Random {
+    fn new(seed: u32) -> Self {
+        // xorshift gets stuck on zero
+        Self(seed.wrapping_mul(0x9e37_79b9) | 1)
+    }
+
+    /// Returns a number from 0 to 1.
+    fn sample(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DETAIL_TYPES: &str = r#"
+        detail.vbsp
+        {
+            Grass
+            {
+                density 200000.0
+                Group1
+                {
+                    alpha 0
+                    Model1
+                    {
+                        sprite "0 0 64 64 512"
+                        spritesize "0.5 0 32 32"
+                        spriterandomscale 0.2
+                        amount 1
+                    }
+                }
+                Group2
+                {
+                    alpha 1
+                    Model1 { model "models/rock.mdl" amount 1 upright 1 }
+                }
+            }
+        }
+    "#;
+
+    fn vmf(alphas: &str) -> Vec<(String, KeyValue)> {
+        keyvalues::parse_raw(&format!(
+            r#"
+            world
+            {{
+                detailmaterial "detail/detailsprites_grass"
+                solid
+                {{
+                    side {{ id 1 plane "(-64 64 0) (64 64 0) (64 -64 0)" material "Nature/Blend_Grass"
+                        dispinfo
+                        {{
+                            power 2
+                            startposition "[-64 -64 0]"
+                            elevation 0
+                            alphas {{ {alphas} }}
+                        }}
+                    }}
+                    side {{ id 2 plane "(-64 -64 -16) (64 -64 -16) (64 64 -16)" }}
+                    side {{ id 3 plane "(-64 64 0) (-64 -64 0) (-64 -64 -16)" }}
+                    side {{ id 4 plane "(64 64 -16) (64 -64 -16) (64 -64 0)" }}
+                    side {{ id 5 plane "(64 64 0) (-64 64 0) (-64 64 -16)" }}
+                    side {{ id 6 plane "(64 -64 -16) (-64 -64 -16) (-64 -64 0)" }}
+                }}
+            }}
+            "#
+        ))
+        .unwrap()
+    }
+
+    fn alpha_rows(alpha: u8) -> String {
+        (0..5)
+            .map(|i| format!("row{i} \"{}\"\n", format!("{alpha} ").repeat(5)))
+            .collect()
+    }
+
+    #[test]
+    fn scatter_detail_props() {
+        let types = DetailTypes::parse(DETAIL_TYPES).unwrap();
+        let grass = &types.0["grass"];
+        assert_eq!(grass.groups.len(), 2);
+        assert_eq!(
+            grass.groups[0].entries[0].kind,
+            DetailKind::Sprite {
+                dict: SpriteDict {
+                    upper_left: [-16.0, 32.0],
+                    lower_right: [16.0, 0.0],
+                    tex_upper_left: [0.5 / 512.0, 0.5 / 512.0],
+                    tex_lower_right: [63.5 / 512.0, 63.5 / 512.0],
+                },
+                shape: SpriteShape::Flat,
+                random_scale: 0.2,
+            }
+        );
+
+        let material_type = |material: &str| {
+            assert_eq!(material, "nature/blend_grass");
+            Some("Grass".to_owned())
+        };
+
+        let scatter = vmf_detail_scatter(
+            &vmf(&alpha_rows(0)),
+            |path| {
+                assert_eq!(path, DEFAULT_DETAIL_TYPES);
+                Some(types.clone())
+            },
+            material_type,
+        );
+
+        assert_eq!(scatter.material, "detail/detailsprites_grass");
+        assert_eq!(scatter.displacements.len(), 1);
+
+        let displacement = &scatter.displacements[0];
+        assert_eq!(displacement.side_id, 1);
+        assert_eq!(displacement.detail_type, "Grass");
+        assert!(displacement.models.is_empty());
+        // 128 * 128 units at 0.2 props per square unit, give or take the fractional samples
+        assert!((3200..=3300).contains(&displacement.sprites.len()));
+
+        for sprite in &displacement.sprites {
+            let [x, y, z] = sprite.origin;
+            assert!((-64.0..=64.0).contains(&x) && (-64.0..=64.0).contains(&y));
+            assert!(z.abs() < 1e-3);
+            assert!((0.8..=1.2).contains(&sprite.scale));
+        }
+
+        let scatter = vmf_detail_scatter(
+            &vmf(&alpha_rows(255)),
+            |_| Some(types.clone()),
+            material_type,
+        );
+        let displacement = &scatter.displacements[0];
+        assert!(displacement.sprites.is_empty());
+        assert!(displacement
+            .models
+            .iter()
+            .all(|m| m.model == "models/rock.mdl" && m.angles[0].abs() < 1e-6));
+
+        let read = |path: &str| {
+            Ok::<_, String>(
+                match path {
+                    "nature/grass" => {
+                        "LightmappedGeneric { $basetexture x \"%detailtype\" \"Grass\" }"
+                    }
+                    "nature/grass_patch" => {
+                        "patch { include nature/grass insert { $color \"[1 0 0]\" } }"
+                    }
+                    _ => "LightmappedGeneric { $basetexture x }",
+                }
+                .to_owned(),
+            )
+        };
+
+        assert_eq!(
+            vmt_detail_type("nature/grass", read),
+            Some("Grass".to_owned())
+        );
+        assert_eq!(
+            vmt_detail_type("nature/grass_patch", read),
+            Some("Grass".to_owned())
+        );
+        assert_eq!(vmt_detail_type("nature/rock", read), None);
+    }
+}
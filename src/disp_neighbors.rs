@@ -0,0 +1,600 @@
+//! Finds the displacements whose edges touch, like the compiler does for the dispinfo
+//! neighbors, so that the seams between them can be stitched. The built displacements
+//! don't line up exactly on shared edges, especially when their powers differ.
+//!
+//! Faces of the built geometry are matched to the displacements by their material, by
+//! lying over the base face of the displacement and by being within the height range of
+//! the displacement from its plane, so that stacked displacements aren't confused.
+
+use std::collections::HashMap;
+
+use glam::Vec3;
+
+use crate::{
+    keyvalues::KeyValue,
+    kv_utils::{block_value, child_block},
+    world_faces::{displacement_corners, solid_windings, Face},
+};
+
+/// Tolerance for base face edges to be on the same line.
+const EDGE_EPSILON: f32 = 0.1;
+/// Maximum distance of a built vertex from the shared edge, on the base face plane.
+const EDGE_DISTANCE: f32 = 0.5;
+/// Vertices of both displacements closer than this along the shared edge are welded.
+const MATCH_DISTANCE: f32 = 0.1;
+/// Tolerance for a built face to be within the height range of a displacement.
+const HEIGHT_EPSILON: f32 = 1.0;
+
+/// The base face of a displacement.
+struct Displacement {
+    side_id: i32,
+    face: Face,
+    /// Farthest distance of the displaced surface from the base face.
+    height: f32,
+}
+
+/// A shared part of the edges of two displacements.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DispNeighbor {
+    pub side_id: i32,
+    /// Index of the edge, starting from the `startposition` corner of the base face.
+    pub edge: usize,
+    pub neighbor_side_id: i32,
+    pub neighbor_edge: usize,
+    pub start: Vec3,
+    pub end: Vec3,
+}
+
+#[derive(Default)]
+pub struct DispNeighbors {
+    displacements: Vec<Displacement>,
+    /// Displacement indices by lowercase material.
+    materials: HashMap<String, Vec<usize>>,
+    /// Displacement indices of each neighbor relationship.
+    neighbors: Vec<(usize, usize, DispNeighbor)>,
+}
+
+/// Vertices and faces of a mesh to stitch, in vmf units.
+pub struct StitchMesh<'a> {
+    pub vertices: Vec<Vec3>,
+    pub faces: Vec<&'a [usize]>,
+    pub materials: Vec<&'a str>,
+}
+
+/// A built vertex on a shared edge, by mesh and vertex index.
+#[derive(Debug, Clone, Copy)]
+struct EdgePoint {
+    key: (usize, usize),
+    along: f32,
+}
+
+impl DispNeighbors {
+    /// Collects the displacements of the world and entity solids of a vmf, and finds
+    /// their neighbors.
+    pub fn from_vmf(vmf: &[(String, KeyValue)]) -> Self {
+        let solids = vmf
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case("world") || k.eq_ignore_ascii_case("entity"))
+            .filter_map(|(_, v)| v.as_block())
+            .flatten()
+            .filter(|(k, _)| k.eq_ignore_ascii_case("solid"))
+            .filter_map(|(_, v)| v.as_block());
+
+        let mut neighbors = Self::default();
+
+        for winding in solids.flat_map(solid_windings) {
+            let Some(dispinfo) = child_block(winding.side, "dispinfo") else {
+                continue;
+            };
+
+            let material = block_value(winding.side, "material")
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+
+            neighbors
+                .materials
+                .entry(material.clone())
+                .or_default()
+                .push(neighbors.displacements.len());
+
+            // edges are numbered from the start corner, like in the dispinfo
+            let vertices =
+                displacement_corners(&winding.vertices, dispinfo).unwrap_or(winding.vertices);
+
+            neighbors.displacements.push(Displacement {
+                side_id: block_value(winding.side, "id")
+                    .and_then(|id| id.trim().parse().ok())
+                    .unwrap_or(0),
+                face: Face {
+                    normal: winding.normal,
+                    distance: winding.distance,
+                    vertices,
+                    material,
+                },
+                height: displacement_height(dispinfo),
+            });
+        }
+
+        for (i, a) in neighbors.displacements.iter().enumerate() {
+            for (j, b) in neighbors.displacements.iter().enumerate().skip(i + 1) {
+                for (edge, neighbor_edge, start, end) in shared_edges(&a.face, &b.face) {
+                    neighbors.neighbors.push((
+                        i,
+                        j,
+                        DispNeighbor {
+                            side_id: a.side_id,
+                            edge,
+                            neighbor_side_id: b.side_id,
+                            neighbor_edge,
+                            start,
+                            end,
+                        },
+                    ));
+                }
+            }
+        }
+
+        neighbors
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.neighbors.is_empty()
+    }
+
+    pub fn has_displacements(&self) -> bool {
+        !self.displacements.is_empty()
+    }
+
+    pub fn neighbors(&self) -> impl Iterator<Item = &DispNeighbor> {
+        self.neighbors.iter().map(|(_, _, n)| n)
+    }
+
+    /// Moves the vertices of displacements on shared edges to match their neighbors.
+    /// Vertices of both displacements at the same point are moved to their average,
+    /// and the rest onto the edge of the neighbor, which closes the T-junctions of
+    /// displacements of different powers.
+    ///
+    /// Returns the vertex each vertex is welded to for each mesh, since vertices in the
+    /// same mesh can be merged.
+    pub fn stitch(&self, meshes: &mut [StitchMesh]) -> Vec<Vec<usize>> {
+        let mut remaps: Vec<Vec<usize>> = meshes
+            .iter()
+            .map(|m| (0..m.vertices.len()).collect())
+            .collect();
+
+        if self.is_empty() {
+            return remaps;
+        }
+
+        let members = self.displacement_vertices(meshes);
+
+        let mut edges = Vec::with_capacity(self.neighbors.len());
+        let mut groups = UnionFind::default();
+
+        for &(a, b, neighbor) in &self.neighbors {
+            let a_points = self.edge_points(meshes, &members[a], a, &neighbor);
+            let b_points = self.edge_points(meshes, &members[b], b, &neighbor);
+
+            for point in &a_points {
+                if let Some(other) = b_points
+                    .iter()
+                    .find(|p| (p.along - point.along).abs() < MATCH_DISTANCE)
+                {
+                    groups.union(point.key, other.key);
+                }
+            }
+
+            edges.push((a_points, b_points));
+        }
+
+        // matched vertices are moved to their average first, so that the rest are
+        // moved onto the final edges
+        let grouped: Vec<(usize, usize)> = groups.parents.keys().copied().collect();
+        let mut sums: HashMap<(usize, usize), (Vec3, f32)> = HashMap::new();
+
+        for &key in &grouped {
+            let sum = sums.entry(groups.find(key)).or_insert((Vec3::ZERO, 0.0));
+            sum.0 += meshes[key.0].vertices[key.1];
+            sum.1 += 1.0;
+        }
+
+        for key in grouped {
+            let root = groups.find(key);
+            let (sum, count) = sums[&root];
+            meshes[key.0].vertices[key.1] = sum / count;
+
+            // only vertices in the same mesh can be merged
+            if root.0 == key.0 {
+                remaps[key.0][key.1] = root.1;
+            }
+        }
+
+        let mut targets = Vec::new();
+
+        for (a_points, b_points) in &edges {
+            for (points, other) in [(a_points, b_points), (b_points, a_points)] {
+                for point in points {
+                    if groups.parents.contains_key(&point.key) {
+                        continue;
+                    }
+
+                    if let Some(target) = interpolate(meshes, other, point.along) {
+                        targets.push((point.key, target));
+                    }
+                }
+            }
+        }
+
+        for ((m, v), target) in targets {
+            meshes[m].vertices[v] = target;
+        }
+
+        remaps
+    }
+
+    /// Returns which faces of each mesh lie over a displacement, so that the faces split
+    /// from displacements can be told apart from the rest of the geometry.
+    pub fn displacement_faces(&self, meshes: &[StitchMesh]) -> Vec<Vec<bool>> {
+        meshes
+            .iter()
+            .map(|mesh| {
+                mesh.faces
+                    .iter()
+                    .zip(&mesh.materials)
+                    .map(|(face, material)| self.face_displacement(mesh, face, material).is_some())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Returns the vertices of the faces over each displacement.
+    fn displacement_vertices(&self, meshes: &[StitchMesh]) -> Vec<Vec<(usize, usize)>> {
+        let mut members = vec![Vec::new(); self.displacements.len()];
+
+        for (m, mesh) in meshes.iter().enumerate() {
+            for (face, material) in mesh.faces.iter().zip(&mesh.materials) {
+                if let Some(d) = self.face_displacement(mesh, face, material) {
+                    members[d].extend(face.iter().map(|&v| (m, v)));
+                }
+            }
+        }
+
+        for vertices in &mut members {
+            vertices.sort_unstable();
+            vertices.dedup();
+        }
+
+        members
+    }
+
+    /// Returns the displacement a face lies over. The closest displacement is used if
+    /// the faces of several overlap.
+    fn face_displacement(
+        &self,
+        mesh: &StitchMesh,
+        face: &[usize],
+        material: &str,
+    ) -> Option<usize> {
+        let candidates = self.materials.get(&material.to_ascii_lowercase())?;
+
+        if face.is_empty() {
+            return None;
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let center = face.iter().map(|&v| mesh.vertices[v]).sum::<Vec3>() / face.len() as f32;
+
+        candidates
+            .iter()
+            .map(|&d| {
+                let displacement = &self.displacements[d];
+                let base = &displacement.face;
+                let height = (base.normal.dot(center) - base.distance).abs();
+                (d, displacement, height)
+            })
+            .filter(|(_, displacement, height)| {
+                *height <= displacement.height + HEIGHT_EPSILON
+                    && displacement
+                        .face
+                        .contains(project(&displacement.face, center))
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .map(|(d, _, _)| d)
+    }
+
+    /// Returns the vertices of a displacement on the shared edge, sorted along it.
+    fn edge_points(
+        &self,
+        meshes: &[StitchMesh],
+        members: &[(usize, usize)],
+        displacement: usize,
+        neighbor: &DispNeighbor,
+    ) -> Vec<EdgePoint> {
+        let base = &self.displacements[displacement].face;
+        let length = neighbor.start.distance(neighbor.end);
+        let direction = (neighbor.end - neighbor.start) / length;
+
+        let mut points: Vec<EdgePoint> = members
+            .iter()
+            .filter_map(|&(m, v)| {
+                let offset = project(base, meshes[m].vertices[v]) - neighbor.start;
+                let along = offset.dot(direction);
+
+                ((offset - direction * along).length() < EDGE_DISTANCE
+                    && along > -MATCH_DISTANCE
+                    && along < length + MATCH_DISTANCE)
+                    .then_some(EdgePoint { key: (m, v), along })
+            })
+            .collect();
+
+        points.sort_by(|a, b| a.along.total_cmp(&b.along));
+        points
+    }
+}
+
+/// Returns how far the surface of a displacement can be from its base face, from its
+/// elevation and the largest of its distances and offsets.
+fn displacement_height(dispinfo: &[(String, KeyValue)]) -> f32 {
+    let elevation = block_value(dispinfo, "elevation")
+        .and_then(|v| v.trim().parse::<f32>().ok())
+        .unwrap_or(0.0);
+
+    let distance = dispinfo_rows(dispinfo, "distances")
+        .flatten()
+        .fold(0.0_f32, |max, d| max.max(d.abs()));
+
+    let offset = dispinfo_rows(dispinfo, "offsets")
+        .flat_map(|row| {
+            row.chunks_exact(3)
+                .map(|v| Vec3::new(v[0], v[1], v[2]).length())
+                .collect::<Vec<_>>()
+        })
+        .fold(0.0_f32, f32::max);
+
+    elevation.abs() + distance + offset
+}
+
+/// Returns the values of each row of a dispinfo block, like `distances`.
+fn dispinfo_rows<'a>(
+    dispinfo: &'a [(String, KeyValue)],
+    name: &'a str,
+) -> impl Iterator<Item = Vec<f32>> + 'a {
+    dispinfo
+        .iter()
+        .filter(move |(k, _)| k.eq_ignore_ascii_case(name))
+        .filter_map(|(_, v)| v.as_block())
+        .flatten()
+        .filter_map(|(_, v)| v.as_value())
+        .map(|row| {
+            row.split_ascii_whitespace()
+                .filter_map(|v| v.parse().ok())
+                .collect()
+        })
+}
+
+/// Returns the overlapping parts of the edges of two base faces, with the edge indices.
+fn shared_edges(a: &Face, b: &Face) -> Vec<(usize, usize, Vec3, Vec3)> {
+    let mut shared = Vec::new();
+
+    for (a_index, (&a_start, &a_end)) in edges(&a.vertices).enumerate() {
+        let length = a_start.distance(a_end);
+        if length < EDGE_EPSILON {
+            continue;
+        }
+
+        let direction = (a_end - a_start) / length;
+        let on_line = |p: Vec3| {
+            let offset = p - a_start;
+            (offset - direction * offset.dot(direction)).length() < EDGE_EPSILON
+        };
+
+        for (b_index, (&b_start, &b_end)) in edges(&b.vertices).enumerate() {
+            if !on_line(b_start) || !on_line(b_end) {
+                continue;
+            }
+
+            let (b_min, b_max) = {
+                let s = (b_start - a_start).dot(direction);
+                let e = (b_end - a_start).dot(direction);
+                (s.min(e), s.max(e))
+            };
+
+            let from = b_min.max(0.0);
+            let to = b_max.min(length);
+
+            if to - from > EDGE_EPSILON {
+                shared.push((
+                    a_index,
+                    b_index,
+                    a_start + direction * from,
+                    a_start + direction * to,
+                ));
+            }
+        }
+    }
+
+    shared
+}
+
+fn edges(vertices: &[Vec3]) -> impl Iterator<Item = (&Vec3, &Vec3)> {
+    vertices.iter().zip(vertices.iter().cycle().skip(1))
+}
+
+/// Projects a point onto the plane of a face.
+fn project(face: &Face, point: Vec3) -> Vec3 {
+    point - face.normal * (face.normal.dot(point) - face.distance)
+}
+
+/// Returns the position along the edge on the polyline through the points.
+fn interpolate(meshes: &[StitchMesh], points: &[EdgePoint], along: f32) -> Option<Vec3> {
+    let next = points.partition_point(|p| p.along <= along);
+    if next == 0 || next == points.len() {
+        return None;
+    }
+
+    let (a, b) = (points[next - 1], points[next]);
+    let fac = (along - a.along) / (b.along - a.along).max(f32::EPSILON);
+
+    Some(meshes[a.key.0].vertices[a.key.1].lerp(meshes[b.key.0].vertices[b.key.1], fac))
+}
+
+#[derive(Default)]
+struct UnionFind {
+    parents: HashMap<(usize, usize), (usize, usize)>,
+}
+
+impl UnionFind {
+    fn find(&mut self, key: (usize, usize)) -> (usize, usize) {
+        let parent = *self.parents.entry(key).or_insert(key);
+
+        if parent == key {
+            return key;
+        }
+
+        let root = self.find(parent);
+        self.parents.insert(key, root);
+        root
+    }
+
+    fn union(&mut self, a: (usize, usize), b: (usize, usize)) {
+        let (a, b) = (self.find(a), self.find(b));
+
+        // the smallest key is the root, so that vertices are welded to the first one
+        if a < b {
+            self.parents.insert(b, a);
+        } else if b < a {
+            self.parents.insert(a, b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::keyvalues;
+
+    #[test]
+    fn stitch_neighbors() {
+        let vmf = keyvalues::parse_raw(
+            r#"
+            world
+            {
+                solid
+                {
+                    side { "id" "1" "plane" "(0 64 0) (64 64 0) (64 0 0)" "material" "NATURE/GRASS" dispinfo { "power" "2" distances { "row0" "0 4 -2" } } }
+                    side { "id" "2" "plane" "(0 0 -16) (64 0 -16) (64 64 -16)" }
+                    side { "id" "3" "plane" "(0 64 0) (0 0 0) (0 0 -16)" }
+                    side { "id" "4" "plane" "(64 64 -16) (64 0 -16) (64 0 0)" }
+                    side { "id" "5" "plane" "(64 64 0) (0 64 0) (0 64 -16)" }
+                    side { "id" "6" "plane" "(64 0 -16) (0 0 -16) (0 0 0)" }
+                }
+                solid
+                {
+                    side { "id" "7" "plane" "(64 64 0) (128 64 0) (128 0 0)" "material" "nature/grass" dispinfo { "power" "3" "elevation" "2" offsets { "row0" "0 0 8 0 0 0" } } }
+                    side { "id" "8" "plane" "(64 0 -16) (128 0 -16) (128 64 -16)" }
+                    side { "id" "9" "plane" "(64 64 0) (64 0 0) (64 0 -16)" }
+                    side { "id" "10" "plane" "(128 64 -16) (128 0 -16) (128 0 0)" }
+                    side { "id" "11" "plane" "(128 64 0) (64 64 0) (64 64 -16)" }
+                    side { "id" "12" "plane" "(128 0 -16) (64 0 -16) (64 0 0)" }
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+        let neighbors = DispNeighbors::from_vmf(&vmf);
+        let neighbor = neighbors.neighbors().next().unwrap();
+        assert_eq!(neighbors.neighbors().count(), 1);
+        assert_eq!((neighbor.side_id, neighbor.neighbor_side_id), (1, 7));
+        assert!((neighbor.start.x - 64.0).abs() < 0.01);
+        assert!((neighbor.end.x - 64.0).abs() < 0.01);
+
+        // the left quad ends at height 4 on the edge, and the right one is split at the
+        // middle of the edge with a vertex at height 10
+        let left = [
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(64.0, 0.0, 4.0),
+            Vec3::new(64.0, 64.0, 4.0),
+            Vec3::new(0.0, 64.0, 0.0),
+        ];
+        let right = [
+            Vec3::new(64.0, 0.0, 0.0),
+            Vec3::new(128.0, 0.0, 0.0),
+            Vec3::new(128.0, 64.0, 0.0),
+            Vec3::new(64.0, 64.0, 0.0),
+            Vec3::new(64.0, 32.0, 10.0),
+        ];
+
+        // a face of the same material far above the displacements isn't stitched
+        let above = [
+            Vec3::new(32.0, 0.0, 64.0),
+            Vec3::new(64.0, 0.0, 64.0),
+            Vec3::new(64.0, 64.0, 64.0),
+        ];
+
+        let mut meshes = [StitchMesh {
+            vertices: left.iter().chain(&right).chain(&above).copied().collect(),
+            faces: vec![&[0, 1, 2, 3][..], &[4, 5, 8], &[8, 5, 6, 7], &[9, 10, 11]],
+            materials: vec!["nature/grass"; 4],
+        }];
+
+        let remaps = neighbors.stitch(&mut meshes);
+        let vertices = &meshes[0].vertices;
+
+        assert_eq!(vertices[1], Vec3::new(64.0, 0.0, 2.0));
+        assert_eq!(vertices[4], Vec3::new(64.0, 0.0, 2.0));
+        assert_eq!(remaps[0][4], 1);
+        assert_eq!(remaps[0][7], 2);
+        assert!(vertices[8].distance(Vec3::new(64.0, 32.0, 2.0)) < 0.01);
+        assert_eq!(vertices[10], above[1]);
+        assert_eq!(remaps[0][10], 10);
+
+        assert_eq!(
+            neighbors.displacement_faces(&meshes),
+            [[true, true, true, false]]
+        );
+    }
+
+    #[test]
+    fn number_edges_from_start_position() {
+        let vmf = |left_start: &str, right_start: &str| {
+            keyvalues::parse_raw(&format!(
+                r#"
+                world
+                {{
+                    solid
+                    {{
+                        side {{ "id" "1" "plane" "(0 64 0) (64 64 0) (64 0 0)" dispinfo {{ "power" "2" "startposition" "[{left_start}]" }} }}
+                        side {{ "id" "2" "plane" "(0 0 -16) (64 0 -16) (64 64 -16)" }}
+                        side {{ "id" "3" "plane" "(0 64 0) (0 0 0) (0 0 -16)" }}
+                        side {{ "id" "4" "plane" "(64 64 -16) (64 0 -16) (64 0 0)" }}
+                        side {{ "id" "5" "plane" "(64 64 0) (0 64 0) (0 64 -16)" }}
+                        side {{ "id" "6" "plane" "(64 0 -16) (0 0 -16) (0 0 0)" }}
+                    }}
+                    solid
+                    {{
+                        side {{ "id" "7" "plane" "(64 64 0) (128 64 0) (128 0 0)" dispinfo {{ "power" "2" "startposition" "[{right_start}]" }} }}
+                        side {{ "id" "8" "plane" "(64 0 -16) (128 0 -16) (128 64 -16)" }}
+                        side {{ "id" "9" "plane" "(64 64 0) (64 0 0) (64 0 -16)" }}
+                        side {{ "id" "10" "plane" "(128 64 -16) (128 0 -16) (128 0 0)" }}
+                        side {{ "id" "11" "plane" "(128 64 0) (64 64 0) (64 64 -16)" }}
+                        side {{ "id" "12" "plane" "(128 0 -16) (64 0 -16) (64 0 0)" }}
+                    }}
+                }}
+                "#
+            ))
+            .unwrap()
+        };
+
+        let edges = |vmf: &[(String, KeyValue)]| {
+            let neighbors = DispNeighbors::from_vmf(vmf);
+            let neighbor = *neighbors.neighbors().next().unwrap();
+            (neighbor.edge, neighbor.neighbor_edge)
+        };
+
+        // the corners go clockwise from the start, so the left edge is the first one
+        assert_eq!(edges(&vmf("0 0 0", "64 0 0")), (2, 0));
+        assert_eq!(edges(&vmf("64 64 0", "128 64 0")), (0, 2));
+    }
+}
@@ -0,0 +1,507 @@
+//! Reads DataModel exchange files (`.dmx`), which Source Filmmaker and studiomdl use for
+//! models and animations. Only the binary encoding is supported, which the tools write
+//! by default.
+//!
+//! Source Filmmaker session files refer to their models by the compiled `.mdl` instead
+//! of holding the meshes, so only the bones and the animations of their models are
+//! imported.
+
+pub mod model;
+
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+/// Ticks per second of time attributes.
+const TIME_RESOLUTION: f32 = 10_000.0;
+/// Index of a referenced element which is in another file.
+const EXTERNAL_ELEMENT: i32 = -2;
+const MAX_ENCODING_VERSION: u32 = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DmxError {
+    InvalidHeader,
+    UnsupportedEncoding(String, u32),
+    UnexpectedEof,
+    InvalidAttributeType(u8),
+    MissingModel,
+    InvalidTimeFrame,
+}
+
+impl Display for DmxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidHeader => f.write_str("invalid dmx header"),
+            Self::UnsupportedEncoding(encoding, version) => {
+                write!(f, "unsupported dmx encoding {encoding} {version}")
+            }
+            Self::UnexpectedEof => f.write_str("unexpected end of file"),
+            Self::InvalidAttributeType(t) => write!(f, "invalid attribute type {t}"),
+            Self::MissingModel => f.write_str("dmx has no model, skeleton or animation set"),
+            Self::InvalidTimeFrame => f.write_str("invalid frame rate or time frame"),
+        }
+    }
+}
+
+impl Error for DmxError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Attribute {
+    /// Index of the element, `None` if the reference is null or external.
+    Element(Option<usize>),
+    Int(i32),
+    Float(f32),
+    Bool(bool),
+    String(String),
+    Binary(Vec<u8>),
+    /// Time in seconds.
+    Time(f32),
+    Color([u8; 4]),
+    Vector2([f32; 2]),
+    Vector3([f32; 3]),
+    Vector4([f32; 4]),
+    QAngle([f32; 3]),
+    Quaternion([f32; 4]),
+    Matrix([f32; 16]),
+    Array(Vec<Attribute>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    pub class: String,
+    pub name: String,
+    pub attributes: Vec<(String, Attribute)>,
+}
+
+impl Element {
+    pub fn get(&self, name: &str) -> Option<&Attribute> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, a)| a)
+    }
+
+    pub fn element(&self, name: &str) -> Option<usize> {
+        match self.get(name)? {
+            Attribute::Element(index) => *index,
+            _ => None,
+        }
+    }
+
+    pub fn int(&self, name: &str) -> Option<i32> {
+        match self.get(name)? {
+            Attribute::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn string(&self, name: &str) -> Option<&str> {
+        match self.get(name)? {
+            Attribute::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn time(&self, name: &str) -> Option<f32> {
+        match self.get(name)? {
+            Attribute::Time(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn vector3(&self, name: &str) -> Option<[f32; 3]> {
+        match self.get(name)? {
+            Attribute::Vector3(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn quaternion(&self, name: &str) -> Option<[f32; 4]> {
+        match self.get(name)? {
+            Attribute::Quaternion(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the values of an array attribute, or an empty slice if there is none.
+    pub fn array(&self, name: &str) -> &[Attribute] {
+        match self.get(name) {
+            Some(Attribute::Array(values)) => values,
+            _ => &[],
+        }
+    }
+
+    pub fn elements(&self, name: &str) -> impl Iterator<Item = usize> + '_ {
+        self.array(name).iter().filter_map(|a| match a {
+            Attribute::Element(index) => *index,
+            _ => None,
+        })
+    }
+
+    pub fn ints(&self, name: &str) -> Vec<i32> {
+        self.array(name)
+            .iter()
+            .filter_map(|a| match a {
+                Attribute::Int(value) => Some(*value),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn floats(&self, name: &str) -> Vec<f32> {
+        self.array(name)
+            .iter()
+            .filter_map(|a| match a {
+                Attribute::Float(value) => Some(*value),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// The elements of a file. The first element is the root.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dmx {
+    pub elements: Vec<Element>,
+}
+
+impl Dmx {
+    /// # Errors
+    ///
+    /// Returns `Err` if the file isn't a binary dmx or is invalid.
+    pub fn read(data: &[u8]) -> Result<Self, DmxError> {
+        let (encoding, version, body) = header(data)?;
+
+        if encoding != "binary" || !(1..=MAX_ENCODING_VERSION).contains(&version) {
+            return Err(DmxError::UnsupportedEncoding(encoding.to_owned(), version));
+        }
+
+        BinaryReader {
+            data: body,
+            offset: 0,
+            version,
+            strings: Vec::new(),
+        }
+        .read()
+    }
+
+    pub fn root(&self) -> Option<&Element> {
+        self.elements.first()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Element> {
+        self.elements.get(index)
+    }
+}
+
+/// Reads the header comment, like `<!-- dmx encoding binary 5 format model 22 -->`,
+/// returning the encoding, its version and the data after the header.
+fn header(data: &[u8]) -> Result<(&str, u32, &[u8]), DmxError> {
+    let end = data
+        .windows(3)
+        .position(|w| w == b"-->")
+        .ok_or(DmxError::InvalidHeader)?;
+
+    let header = std::str::from_utf8(&data[..end]).map_err(|_| DmxError::InvalidHeader)?;
+    let mut words = header
+        .trim_start_matches("<!--")
+        .split_ascii_whitespace()
+        .skip_while(|&w| w != "encoding")
+        .skip(1);
+
+    let encoding = words.next().ok_or(DmxError::InvalidHeader)?;
+    let version = words
+        .next()
+        .and_then(|v| v.parse().ok())
+        .ok_or(DmxError::InvalidHeader)?;
+
+    // the header line is terminated by a newline and a null
+    let mut body = &data[end + 3..];
+    while let Some((b'\n' | b'\r' | 0, rest)) = body.split_first() {
+        body = rest;
+    }
+
+    Ok((encoding, version, body))
+}
+
+struct BinaryReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    version: u32,
+    strings: Vec<String>,
+}
+
+impl BinaryReader<'_> {
+    fn read(mut self) -> Result<Dmx, DmxError> {
+        if self.version >= 2 {
+            let count = if self.version >= 4 {
+                self.count()?
+            } else {
+                usize::try_from(self.i16()?).map_err(|_| DmxError::UnexpectedEof)?
+            };
+
+            self.strings = (0..count)
+                .map(|_| self.string())
+                .collect::<Result<_, _>>()?;
+        }
+
+        let count = self.count()?;
+        let mut elements = Vec::with_capacity(count.min(self.data.len()));
+
+        for _ in 0..count {
+            let class = self.table_string(self.version >= 2)?;
+            let name = self.table_string(self.version >= 4)?;
+            // the id isn't needed, since elements are referred to by index
+            self.bytes(16)?;
+
+            elements.push(Element {
+                class,
+                name,
+                attributes: Vec::new(),
+            });
+        }
+
+        for element in &mut elements {
+            let count = self.count()?;
+
+            for _ in 0..count {
+                let name = self.table_string(self.version >= 2)?;
+                let kind = self.u8()?;
+                let value = self.attribute(kind)?;
+
+                element.attributes.push((name, value));
+            }
+        }
+
+        Ok(Dmx { elements })
+    }
+
+    fn attribute(&mut self, kind: u8) -> Result<Attribute, DmxError> {
+        match kind {
+            1..=14 => self.value(kind, false),
+            15..=28 => {
+                let count = self.count()?;
+                let values = (0..count)
+                    .map(|_| self.value(kind - 14, true))
+                    .collect::<Result<_, _>>()?;
+
+                Ok(Attribute::Array(values))
+            }
+            _ => Err(DmxError::InvalidAttributeType(kind)),
+        }
+    }
+
+    fn value(&mut self, kind: u8, in_array: bool) -> Result<Attribute, DmxError> {
+        Ok(match kind {
+            1 => {
+                let index = self.i32()?;
+
+                if index == EXTERNAL_ELEMENT {
+                    // the id of the external element
+                    self.string()?;
+                }
+
+                Attribute::Element(usize::try_from(index).ok())
+            }
+            2 => Attribute::Int(self.i32()?),
+            3 => Attribute::Float(self.f32()?),
+            4 => Attribute::Bool(self.u8()? != 0),
+            // strings in arrays are never in the string table
+            5 => Attribute::String(self.table_string(self.version >= 4 && !in_array)?),
+            6 => {
+                let len = self.count()?;
+                Attribute::Binary(self.bytes(len)?.to_vec())
+            }
+            7 => Attribute::Time(self.i32()? as f32 / TIME_RESOLUTION),
+            8 => Attribute::Color(self.byte_array()?),
+            9 => Attribute::Vector2(self.floats()?),
+            10 => Attribute::Vector3(self.floats()?),
+            11 => Attribute::Vector4(self.floats()?),
+            12 => Attribute::QAngle(self.floats()?),
+            13 => Attribute::Quaternion(self.floats()?),
+            14 => Attribute::Matrix(self.floats()?),
+            _ => return Err(DmxError::InvalidAttributeType(kind)),
+        })
+    }
+
+    /// Reads a string from the string table if `from_table`, or else inline.
+    fn table_string(&mut self, from_table: bool) -> Result<String, DmxError> {
+        if !from_table {
+            return self.string();
+        }
+
+        let index = if self.version >= 5 {
+            usize::try_from(self.i32()?).ok()
+        } else {
+            usize::try_from(self.i16()?).ok()
+        };
+
+        Ok(index
+            .and_then(|i| self.strings.get(i))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn string(&mut self) -> Result<String, DmxError> {
+        let rest = self
+            .data
+            .get(self.offset..)
+            .ok_or(DmxError::UnexpectedEof)?;
+        let len = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(DmxError::UnexpectedEof)?;
+
+        self.offset += len + 1;
+
+        Ok(String::from_utf8_lossy(&rest[..len]).into_owned())
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&[u8], DmxError> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + len)
+            .ok_or(DmxError::UnexpectedEof)?;
+        self.offset += len;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, DmxError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn i16(&mut self) -> Result<i16, DmxError> {
+        let b = self.bytes(2)?;
+        Ok(i16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn i32(&mut self) -> Result<i32, DmxError> {
+        let b = self.bytes(4)?;
+        Ok(i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn count(&mut self) -> Result<usize, DmxError> {
+        usize::try_from(self.i32()?).map_err(|_| DmxError::UnexpectedEof)
+    }
+
+    fn f32(&mut self) -> Result<f32, DmxError> {
+        let b = self.bytes(4)?;
+        Ok(f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn floats<const N: usize>(&mut self) -> Result<[f32; N], DmxError> {
+        let mut values = [0.0; N];
+        for value in &mut values {
+            *value = self.f32()?;
+        }
+
+        Ok(values)
+    }
+
+    fn byte_array<const N: usize>(&mut self) -> Result<[u8; N], DmxError> {
+        let mut values = [0; N];
+        values.copy_from_slice(self.bytes(N)?);
+
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// Writes elements in binary encoding 5, with the class, name, attribute names and
+    /// string values in the string table.
+    pub fn write_dmx(elements: &[(&str, &str, Vec<(&str, u8, Vec<u8>)>)]) -> Vec<u8> {
+        let mut strings: Vec<String> = Vec::new();
+        let mut index = |s: &str| {
+            let i = strings.iter().position(|t| t == s).unwrap_or_else(|| {
+                strings.push(s.to_owned());
+                strings.len() - 1
+            });
+            i32::try_from(i).unwrap().to_le_bytes()
+        };
+
+        let mut headers = Vec::new();
+        let mut bodies = Vec::new();
+
+        for (class, name, attributes) in elements {
+            headers.extend(index(class));
+            headers.extend(index(name));
+            headers.extend([0; 16]);
+
+            bodies.extend(i32::try_from(attributes.len()).unwrap().to_le_bytes());
+            for (name, kind, value) in attributes {
+                bodies.extend(index(name));
+                bodies.push(*kind);
+
+                if *kind == 5 {
+                    bodies.extend(index(std::str::from_utf8(value).unwrap()));
+                } else {
+                    bodies.extend(value);
+                }
+            }
+        }
+
+        let mut data = b"<!-- dmx encoding binary 5 format model 22 -->\n\0".to_vec();
+        data.extend(i32::try_from(strings.len()).unwrap().to_le_bytes());
+        for s in &strings {
+            data.extend(s.as_bytes());
+            data.push(0);
+        }
+        data.extend(i32::try_from(elements.len()).unwrap().to_le_bytes());
+        data.extend(headers);
+        data.extend(bodies);
+        data
+    }
+
+    pub fn floats(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    pub fn array(values: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = i32::try_from(values.len()).unwrap().to_le_bytes().to_vec();
+        data.extend(values.iter().flatten());
+        data
+    }
+
+    #[test]
+    fn read_binary_dmx() {
+        let data = write_dmx(&[
+            (
+                "DmElement",
+                "root",
+                vec![
+                    ("model", 1, 1_i32.to_le_bytes().to_vec()),
+                    (
+                        "external",
+                        1,
+                        [(-2_i32).to_le_bytes().as_slice(), b"id\0"].concat(),
+                    ),
+                    ("weights", 17, array(&[floats(&[0.5]), floats(&[0.25])])),
+                ],
+            ),
+            (
+                "DmeModel",
+                "crate",
+                vec![("time", 7, 5000_i32.to_le_bytes().to_vec())],
+            ),
+        ]);
+
+        let dmx = Dmx::read(&data).unwrap();
+        let root = dmx.root().unwrap();
+
+        assert_eq!(root.element("model"), Some(1));
+        assert_eq!(root.element("external"), None);
+        assert_eq!(root.floats("weights"), [0.5, 0.25]);
+        assert_eq!(dmx.get(1).unwrap().name, "crate");
+        assert_eq!(dmx.get(1).unwrap().time("time"), Some(0.5));
+
+        assert_eq!(
+            Dmx::read(b"<!-- dmx encoding keyvalues2 1 format model 1 -->\n"),
+            Err(DmxError::UnsupportedEncoding("keyvalues2".to_owned(), 1))
+        );
+    }
+}
@@ -0,0 +1,1048 @@
+//! Extracts the skeleton, meshes and animations of a model dmx. The meshes are in the
+//! `DmeModel` hierarchy, and the animations are `DmeChannel`s driving the transforms
+//! of its joints.
+//!
+//! Source Filmmaker sessions have a `DmeGameModel` for each animation set of their film
+//! clips instead, which only has the bones of the compiled model it refers to.
+
+use std::collections::{BTreeMap, HashMap};
+
+use glam::{Affine3A, Quat, Vec3};
+use tracing::warn;
+
+use crate::game_path::material_name;
+
+use super::{Attribute, Dmx, DmxError, Element};
+
+const DEFAULT_FRAME_RATE: f32 = 30.0;
+/// Limits the frames sampled from the time frame of a clip, which comes from the file.
+const MAX_FRAMES: f32 = 65_536.0;
+/// Limits the nesting of film clips, in case their references form a cycle.
+const MAX_CLIP_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DmxJoint {
+    pub name: String,
+    pub parent: Option<usize>,
+    /// Transform relative to the parent joint.
+    pub position: Vec3,
+    pub orientation: Quat,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DmxTriangle {
+    pub positions: [usize; 3],
+    pub uvs: [[f32; 2]; 3],
+    pub material: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DmxMesh {
+    pub name: String,
+    /// Positions in model space.
+    pub positions: Vec<Vec3>,
+    /// Normal of each position.
+    pub normals: Vec<Vec3>,
+    pub triangles: Vec<DmxTriangle>,
+    /// Joint weights of each position.
+    pub weights: Vec<Vec<(usize, f32)>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DmxAnimation {
+    pub name: String,
+    pub fps: f32,
+    /// Frames of the local transforms of the animated joints.
+    pub rotations: BTreeMap<usize, Vec<Quat>>,
+    pub positions: BTreeMap<usize, Vec<Vec3>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DmxModel {
+    /// Name of the animation set of a session model.
+    pub animation_set: Option<String>,
+    pub joints: Vec<DmxJoint>,
+    pub meshes: Vec<DmxMesh>,
+    /// Paths of the materials, like `materials/models/crate.vmt`.
+    pub materials: Vec<String>,
+    pub animations: Vec<DmxAnimation>,
+}
+
+/// Extracts the model of the root's `model` or `skeleton`, or the models of the
+/// animation sets of a session's `activeClip`.
+///
+/// # Errors
+///
+/// Returns `Err` if the dmx has no model, or if it's a session without animation sets.
+pub fn models(dmx: &Dmx) -> Result<Vec<DmxModel>, DmxError> {
+    let root = dmx.root().ok_or(DmxError::MissingModel)?;
+
+    if let Some(clip) = root.element("activeClip") {
+        let models = session_models(dmx, clip);
+
+        return if models.is_empty() {
+            Err(DmxError::MissingModel)
+        } else {
+            Ok(models)
+        };
+    }
+
+    let model_index = root
+        .element("model")
+        .or_else(|| root.element("skeleton"))
+        .ok_or(DmxError::MissingModel)?;
+
+    let mut builder = ModelBuilder::new(dmx);
+
+    if let Some(model) = dmx.get(model_index) {
+        for (i, joint) in model.elements("jointList").enumerate() {
+            builder.joint_indices.insert(joint, i);
+        }
+
+        builder.model.joints = model
+            .elements("jointList")
+            .filter_map(|joint| dmx.get(joint))
+            .map(|joint| DmxJoint {
+                name: joint.name.clone(),
+                parent: None,
+                position: Vec3::ZERO,
+                orientation: Quat::IDENTITY,
+            })
+            .collect();
+    }
+
+    builder.visit(model_index, None, Affine3A::IDENTITY, 0);
+
+    if let Some(list) = root.element("animationList").and_then(|i| dmx.get(i)) {
+        for clip in list.elements("animations").filter_map(|i| dmx.get(i)) {
+            builder.push_animation(clip, DEFAULT_FRAME_RATE);
+        }
+    }
+
+    Ok(vec![builder.model])
+}
+
+/// Extracts the models of the animation sets of a session, animated by the channels
+/// clips of the film clips they are in.
+fn session_models(dmx: &Dmx, active_clip: usize) -> Vec<DmxModel> {
+    let fps = dmx
+        .root()
+        .and_then(|root| root.element("settings"))
+        .and_then(|i| dmx.get(i))
+        .and_then(|settings| settings.element("renderSettings"))
+        .and_then(|i| dmx.get(i))
+        .and_then(frame_rate)
+        .unwrap_or(DEFAULT_FRAME_RATE);
+
+    let mut models = Vec::new();
+    film_clip_models(dmx, active_clip, fps, 0, &mut models);
+    models
+}
+
+fn film_clip_models(dmx: &Dmx, index: usize, fps: f32, depth: usize, models: &mut Vec<DmxModel>) {
+    if depth > MAX_CLIP_DEPTH {
+        return;
+    }
+
+    let Some(clip) = dmx.get(index) else {
+        return;
+    };
+
+    // the channels clips are in the tracks of the clip's track groups
+    let channels_clips: Vec<&Element> = clip
+        .elements("trackGroups")
+        .filter_map(|i| dmx.get(i))
+        .flat_map(|group| group.elements("tracks"))
+        .filter_map(|i| dmx.get(i))
+        .flat_map(|track| track.elements("children"))
+        .filter_map(|i| dmx.get(i))
+        .filter(|child| child.class == "DmeChannelsClip")
+        .collect();
+
+    for animation_set in clip.elements("animationSets").filter_map(|i| dmx.get(i)) {
+        let Some(game_model) = animation_set.element("gameModel") else {
+            continue;
+        };
+
+        let mut builder = ModelBuilder::new(dmx);
+        builder.model.animation_set = Some(animation_set.name.clone());
+
+        if let Some(model) = dmx.get(game_model) {
+            // the bones are the transforms of the dags in the hierarchy of the game model
+            for transform in model.elements("bones") {
+                let name = dmx
+                    .get(transform)
+                    .map_or_else(String::new, |t| t.name.clone());
+                let (position, orientation) = dmx
+                    .get(transform)
+                    .map_or((Vec3::ZERO, Quat::IDENTITY), transform_parts);
+
+                // bones outside of the hierarchy are still animated
+                builder
+                    .bone_joints
+                    .insert(transform, builder.model.joints.len());
+                builder
+                    .transform_joints
+                    .insert(transform, builder.model.joints.len());
+                builder.model.joints.push(DmxJoint {
+                    name,
+                    parent: None,
+                    position,
+                    orientation,
+                });
+            }
+        }
+
+        builder.visit(game_model, None, Affine3A::IDENTITY, 0);
+
+        for channels_clip in &channels_clips {
+            builder.push_animation(channels_clip, fps);
+        }
+
+        models.push(builder.model);
+    }
+
+    // the shots of a movie are in the tracks of its sub clip track group
+    let sub_clips: Vec<usize> = clip
+        .element("subClipTrackGroup")
+        .and_then(|i| dmx.get(i))
+        .into_iter()
+        .flat_map(|group| group.elements("tracks"))
+        .filter_map(|i| dmx.get(i))
+        .flat_map(|track| track.elements("children"))
+        .collect();
+
+    for sub_clip in sub_clips {
+        film_clip_models(dmx, sub_clip, fps, depth + 1, models);
+    }
+}
+
+struct ModelBuilder<'a> {
+    dmx: &'a Dmx,
+    model: DmxModel,
+    /// Joint index of each joint element.
+    joint_indices: HashMap<usize, usize>,
+    /// Joint index of each bone transform of a game model, before the dags are visited.
+    bone_joints: HashMap<usize, usize>,
+    /// Joint index of each transform element of a joint.
+    transform_joints: HashMap<usize, usize>,
+}
+
+impl<'a> ModelBuilder<'a> {
+    fn new(dmx: &'a Dmx) -> Self {
+        Self {
+            dmx,
+            model: DmxModel::default(),
+            joint_indices: HashMap::new(),
+            bone_joints: HashMap::new(),
+            transform_joints: HashMap::new(),
+        }
+    }
+
+    /// Adds the animation of a clip, unless it's invalid or doesn't animate any joint.
+    fn push_animation(&mut self, clip: &Element, default_fps: f32) {
+        match self.animation(clip, default_fps) {
+            Ok(animation) if animation.rotations.is_empty() && animation.positions.is_empty() => {}
+            Ok(animation) => self.model.animations.push(animation),
+            Err(error) => warn!("animation `{}`: {error}", clip.name),
+        }
+    }
+    fn visit(&mut self, index: usize, parent_joint: Option<usize>, parent: Affine3A, depth: usize) {
+        // references can form cycles in malformed files
+        if depth > 256 {
+            return;
+        }
+
+        let Some(element) = self.dmx.get(index) else {
+            return;
+        };
+
+        let transform = element.element("transform");
+        let (position, orientation) = transform
+            .and_then(|t| self.dmx.get(t))
+            .map_or((Vec3::ZERO, Quat::IDENTITY), transform_parts);
+        let world = parent * Affine3A::from_rotation_translation(orientation, position);
+
+        let mut joint = parent_joint;
+
+        let known_joint = self
+            .joint_indices
+            .get(&index)
+            .or_else(|| transform.and_then(|t| self.bone_joints.get(&t)))
+            .copied();
+
+        if element.class == "DmeJoint" || known_joint.is_some() {
+            let joint_index = known_joint.unwrap_or_else(|| {
+                self.model.joints.push(DmxJoint {
+                    name: element.name.clone(),
+                    parent: None,
+                    position: Vec3::ZERO,
+                    orientation: Quat::IDENTITY,
+                });
+                self.model.joints.len() - 1
+            });
+            self.joint_indices.insert(index, joint_index);
+
+            let data = &mut self.model.joints[joint_index];
+            data.parent = parent_joint;
+            data.position = position;
+            data.orientation = orientation;
+
+            if let Some(transform) = transform {
+                self.transform_joints.insert(transform, joint_index);
+            }
+
+            joint = Some(joint_index);
+        }
+
+        if let Some(shape) = element.element("shape").and_then(|s| self.dmx.get(s)) {
+            if let Some(mesh) = self.mesh(&element.name, shape, world, joint) {
+                self.model.meshes.push(mesh);
+            }
+        }
+
+        let children: Vec<usize> = element.elements("children").collect();
+        for child in children {
+            self.visit(child, joint, world, depth + 1);
+        }
+    }
+
+    fn mesh(
+        &mut self,
+        name: &str,
+        mesh: &Element,
+        transform: Affine3A,
+        parent_joint: Option<usize>,
+    ) -> Option<DmxMesh> {
+        let data = mesh
+            .element("currentState")
+            .or_else(|| mesh.elements("baseStates").next())
+            .and_then(|i| self.dmx.get(i))?;
+
+        let positions: Vec<Vec3> = vectors3(vertex_field(data, &["positions", "position$0"]))
+            .into_iter()
+            .map(|p| transform.transform_point3(p))
+            .collect();
+        let position_indices = data_ints(data, &["positionsIndices", "position$0Indices"]);
+        let normals = vectors3(vertex_field(data, &["normals", "normal$0"]));
+        let normal_indices = data_ints(data, &["normalsIndices", "normal$0Indices"]);
+        let uvs = vectors2(vertex_field(data, &["textureCoordinates", "texcoord$0"]));
+        let uv_indices = data_ints(data, &["textureCoordinatesIndices", "texcoord$0Indices"]);
+
+        let position = |corner: usize| {
+            position_indices
+                .get(corner)
+                .and_then(|&i| usize::try_from(i).ok())
+                .filter(|&i| i < positions.len())
+        };
+
+        let mut position_normals = vec![Vec3::ZERO; positions.len()];
+        let mut triangles = Vec::new();
+
+        for face_set in mesh.elements("faceSets").filter_map(|i| self.dmx.get(i)) {
+            let material_name = face_set
+                .element("material")
+                .and_then(|m| self.dmx.get(m))
+                .and_then(|m| m.string("mtlName"))
+                .map(|name| format!("materials/{}.vmt", material_name(name, ".vmt")))
+                .unwrap_or_default();
+
+            let material = self
+                .model
+                .materials
+                .iter()
+                .position(|m| *m == material_name)
+                .unwrap_or_else(|| {
+                    self.model.materials.push(material_name);
+                    self.model.materials.len() - 1
+                });
+
+            let faces = face_set.ints("faces");
+
+            for polygon in faces.split(|&c| c < 0) {
+                let corners: Vec<usize> = polygon
+                    .iter()
+                    .filter_map(|&c| usize::try_from(c).ok())
+                    .collect();
+
+                for &corner in &corners {
+                    let normal = normal_indices
+                        .get(corner)
+                        .and_then(|&i| usize::try_from(i).ok())
+                        .and_then(|i| normals.get(i));
+
+                    if let (Some(p), Some(&normal)) = (position(corner), normal) {
+                        if position_normals[p] == Vec3::ZERO {
+                            position_normals[p] =
+                                transform.transform_vector3(normal).normalize_or_zero();
+                        }
+                    }
+                }
+
+                let uv = |corner: usize| {
+                    uv_indices
+                        .get(corner)
+                        .and_then(|&i| usize::try_from(i).ok())
+                        .and_then(|i| uvs.get(i))
+                        .copied()
+                        .unwrap_or_default()
+                };
+
+                // polygons are triangulated as fans
+                for pair in corners.windows(2).skip(1) {
+                    let triangle = [corners[0], pair[0], pair[1]];
+
+                    if let [Some(a), Some(b), Some(c)] = triangle.map(position) {
+                        triangles.push(DmxTriangle {
+                            positions: [a, b, c],
+                            uvs: triangle.map(uv),
+                            material,
+                        });
+                    }
+                }
+            }
+        }
+
+        let weights = self.weights(data, positions.len(), parent_joint);
+
+        Some(DmxMesh {
+            name: name.to_owned(),
+            positions,
+            normals: position_normals,
+            triangles,
+            weights,
+        })
+    }
+
+    /// Reads the joint weights of each position. Meshes without weights follow the
+    /// joint they are parented to.
+    fn weights(
+        &self,
+        data: &Element,
+        position_count: usize,
+        parent_joint: Option<usize>,
+    ) -> Vec<Vec<(usize, f32)>> {
+        let count = data
+            .int("jointCount")
+            .and_then(|c| usize::try_from(c).ok())
+            .unwrap_or(0);
+        let weights = data_floats(data, &["jointWeights", "blendweights$0"]);
+        let indices = data_ints(data, &["jointIndices", "blendindices$0"]);
+
+        if count == 0 || weights.len() < position_count * count || indices.len() < weights.len() {
+            let weight = parent_joint.map(|j| vec![(j, 1.0)]).unwrap_or_default();
+            return vec![weight; position_count];
+        }
+
+        (0..position_count)
+            .map(|p| {
+                (p * count..(p + 1) * count)
+                    .filter(|&i| weights[i] > 0.0)
+                    .filter_map(|i| {
+                        let joint = usize::try_from(indices[i]).ok()?;
+                        (joint < self.model.joints.len()).then_some((joint, weights[i]))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn animation(&self, clip: &Element, default_fps: f32) -> Result<DmxAnimation, DmxError> {
+        let fps = frame_rate(clip).unwrap_or(default_fps).max(1.0);
+
+        if !fps.is_finite() {
+            return Err(DmxError::InvalidTimeFrame);
+        }
+
+        let time_frame = clip.element("timeFrame").and_then(|t| self.dmx.get(t));
+        let start = time_frame
+            .and_then(|t| t.time("start").or_else(|| t.time("startTime")))
+            .unwrap_or(0.0);
+        let duration =
+            time_frame.and_then(|t| t.time("duration").or_else(|| t.time("durationTime")));
+
+        let mut tracks = Vec::new();
+
+        for channel in clip.elements("channels").filter_map(|i| self.dmx.get(i)) {
+            let Some(&joint) = channel
+                .element("toElement")
+                .and_then(|e| self.transform_joints.get(&e))
+            else {
+                continue;
+            };
+
+            let Some(layer) = channel
+                .element("log")
+                .and_then(|l| self.dmx.get(l))
+                .and_then(|log| log.elements("layers").next())
+                .and_then(|l| self.dmx.get(l))
+            else {
+                continue;
+            };
+
+            let times: Vec<f32> = layer
+                .array("times")
+                .iter()
+                .filter_map(|t| match t {
+                    Attribute::Time(time) => Some(*time),
+                    _ => None,
+                })
+                .collect();
+
+            tracks.push((
+                joint,
+                channel.string("toAttribute").unwrap_or_default(),
+                times,
+                layer,
+            ));
+        }
+
+        let duration = duration.unwrap_or_else(|| {
+            tracks
+                .iter()
+                .filter_map(|(_, _, times, _)| times.last())
+                .fold(0.0_f32, |a, &b| a.max(b - start))
+        });
+
+        let frames = duration.max(0.0) * fps;
+
+        if !start.is_finite() || !frames.is_finite() {
+            return Err(DmxError::InvalidTimeFrame);
+        }
+
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let frame_count = frames.round().min(MAX_FRAMES) as usize + 1;
+        let frame_times: Vec<f32> = (0..frame_count).map(|f| start + f as f32 / fps).collect();
+
+        let mut rotations = BTreeMap::new();
+        let mut positions = BTreeMap::new();
+
+        for (joint, attribute, times, layer) in tracks {
+            match attribute {
+                "orientation" => {
+                    let values: Vec<Quat> = layer
+                        .array("values")
+                        .iter()
+                        .filter_map(|v| match v {
+                            Attribute::Quaternion(q) => Some(Quat::from_array(*q)),
+                            _ => None,
+                        })
+                        .collect();
+
+                    if let Some(frames) = sample(&times, &values, &frame_times, Quat::slerp) {
+                        rotations.insert(joint, frames);
+                    }
+                }
+                "position" => {
+                    let values: Vec<Vec3> = layer
+                        .array("values")
+                        .iter()
+                        .filter_map(|v| match v {
+                            Attribute::Vector3(p) => Some(Vec3::from(*p)),
+                            _ => None,
+                        })
+                        .collect();
+
+                    if let Some(frames) = sample(&times, &values, &frame_times, Vec3::lerp) {
+                        positions.insert(joint, frames);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(DmxAnimation {
+            name: clip.name.clone(),
+            fps,
+            rotations,
+            positions,
+        })
+    }
+}
+
+/// Interpolates the keys of a log layer at each frame time. Times outside of the keys
+/// hold the first or the last value.
+fn sample<T: Copy>(
+    times: &[f32],
+    values: &[T],
+    frame_times: &[f32],
+    interpolate: impl Fn(T, T, f32) -> T,
+) -> Option<Vec<T>> {
+    let len = times.len().min(values.len());
+    if len == 0 {
+        return None;
+    }
+
+    let (times, values) = (&times[..len], &values[..len]);
+
+    Some(
+        frame_times
+            .iter()
+            .map(|&time| {
+                let next = times.partition_point(|&t| t <= time);
+
+                if next == 0 {
+                    values[0]
+                } else if next == len {
+                    values[len - 1]
+                } else {
+                    let (t0, t1) = (times[next - 1], times[next]);
+                    let fac = if t1 > t0 {
+                        (time - t0) / (t1 - t0)
+                    } else {
+                        0.0
+                    };
+                    interpolate(values[next - 1], values[next], fac)
+                }
+            })
+            .collect(),
+    )
+}
+
+fn frame_rate(element: &Element) -> Option<f32> {
+    match element.get("frameRate")? {
+        Attribute::Int(rate) => Some(*rate as f32),
+        Attribute::Float(rate) => Some(*rate),
+        _ => None,
+    }
+}
+
+fn transform_parts(transform: &Element) -> (Vec3, Quat) {
+    (
+        transform.vector3("position").map_or(Vec3::ZERO, Vec3::from),
+        transform
+            .quaternion("orientation")
+            .map_or(Quat::IDENTITY, |q| Quat::from_array(q).normalize()),
+    )
+}
+
+/// Returns the first of the attributes which exists, since newer files name the vertex
+/// fields like `position$0`.
+fn vertex_field<'a>(data: &'a Element, names: &[&str]) -> &'a [Attribute] {
+    names
+        .iter()
+        .map(|name| data.array(name))
+        .find(|values| !values.is_empty())
+        .unwrap_or_default()
+}
+
+fn data_ints(data: &Element, names: &[&str]) -> Vec<i32> {
+    names
+        .iter()
+        .map(|name| data.ints(name))
+        .find(|values| !values.is_empty())
+        .unwrap_or_default()
+}
+
+fn data_floats(data: &Element, names: &[&str]) -> Vec<f32> {
+    names
+        .iter()
+        .map(|name| data.floats(name))
+        .find(|values| !values.is_empty())
+        .unwrap_or_default()
+}
+
+fn vectors3(values: &[Attribute]) -> Vec<Vec3> {
+    values
+        .iter()
+        .filter_map(|v| match v {
+            Attribute::Vector3(v) => Some(Vec3::from(*v)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn vectors2(values: &[Attribute]) -> Vec<[f32; 2]> {
+    values
+        .iter()
+        .filter_map(|v| match v {
+            Attribute::Vector2(v) => Some(*v),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dmx::tests::{array, floats, write_dmx};
+
+    fn element(index: i32) -> Vec<u8> {
+        index.to_le_bytes().to_vec()
+    }
+
+    fn ints(values: &[i32]) -> Vec<u8> {
+        array(
+            &values
+                .iter()
+                .map(|v| v.to_le_bytes().to_vec())
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn extract_model() {
+        let quat = floats(&[0.0, 0.0, 0.0, 1.0]);
+        let data = write_dmx(&[
+            // 0
+            (
+                "DmElement",
+                "root",
+                vec![("model", 1, element(1)), ("animationList", 1, element(12))],
+            ),
+            // 1
+            (
+                "DmeModel",
+                "model",
+                vec![
+                    ("children", 15, array(&[element(2), element(4)])),
+                    ("jointList", 15, array(&[element(2)])),
+                ],
+            ),
+            // 2
+            ("DmeJoint", "root_bone", vec![("transform", 1, element(3))]),
+            // 3
+            (
+                "DmeTransform",
+                "root_bone",
+                vec![
+                    ("position", 10, floats(&[0.0, 0.0, 1.0])),
+                    ("orientation", 13, quat.clone()),
+                ],
+            ),
+            // 4
+            (
+                "DmeDag",
+                "body",
+                vec![("transform", 1, element(5)), ("shape", 1, element(6))],
+            ),
+            // 5
+            (
+                "DmeTransform",
+                "body",
+                vec![("position", 10, floats(&[1.0, 0.0, 0.0]))],
+            ),
+            // 6
+            (
+                "DmeMesh",
+                "body",
+                vec![
+                    ("currentState", 1, element(7)),
+                    ("faceSets", 15, array(&[element(8)])),
+                ],
+            ),
+            // 7
+            (
+                "DmeVertexData",
+                "bind",
+                vec![
+                    (
+                        "positions",
+                        24,
+                        array(&[
+                            floats(&[0.0, 0.0, 0.0]),
+                            floats(&[1.0, 0.0, 0.0]),
+                            floats(&[1.0, 1.0, 0.0]),
+                            floats(&[0.0, 1.0, 0.0]),
+                        ]),
+                    ),
+                    ("positionsIndices", 16, ints(&[0, 1, 2, 3])),
+                    ("jointCount", 2, 1_i32.to_le_bytes().to_vec()),
+                    (
+                        "jointWeights",
+                        17,
+                        array(&[
+                            floats(&[1.0]),
+                            floats(&[1.0]),
+                            floats(&[1.0]),
+                            floats(&[0.0]),
+                        ]),
+                    ),
+                    ("jointIndices", 16, ints(&[0, 0, 0, 0])),
+                ],
+            ),
+            // 8
+            (
+                "DmeFaceSet",
+                "set",
+                vec![
+                    ("material", 1, element(9)),
+                    ("faces", 16, ints(&[0, 1, 2, 3, -1])),
+                ],
+            ),
+            // 9
+            (
+                "DmeMaterial",
+                "crate",
+                vec![("mtlName", 5, b"models/Crate".to_vec())],
+            ),
+            // 10
+            (
+                "DmeChannel",
+                "root_bone_p",
+                vec![
+                    ("toElement", 1, element(3)),
+                    ("toAttribute", 5, b"position".to_vec()),
+                    ("log", 1, element(11)),
+                ],
+            ),
+            // 11
+            (
+                "DmeVector3Log",
+                "log",
+                vec![("layers", 15, array(&[element(14)]))],
+            ),
+            // 12
+            (
+                "DmeAnimationList",
+                "animations",
+                vec![("animations", 15, array(&[element(13)]))],
+            ),
+            // 13
+            (
+                "DmeChannelsClip",
+                "wave",
+                vec![
+                    ("channels", 15, array(&[element(10)])),
+                    ("frameRate", 2, 2_i32.to_le_bytes().to_vec()),
+                ],
+            ),
+            // 14
+            (
+                "DmeVector3LogLayer",
+                "layer",
+                vec![
+                    ("times", 21, ints(&[0, 10_000])),
+                    (
+                        "values",
+                        24,
+                        array(&[floats(&[0.0, 0.0, 0.0]), floats(&[0.0, 0.0, 2.0])]),
+                    ),
+                ],
+            ),
+        ]);
+
+        let dmx = Dmx::read(&data).unwrap();
+        let model = models(&dmx).unwrap().remove(0);
+
+        assert_eq!(model.joints.len(), 1);
+        assert_eq!(model.materials, ["materials/models/crate.vmt"]);
+        assert_eq!(model.joints[0].position, Vec3::new(0.0, 0.0, 1.0));
+
+        let mesh = &model.meshes[0];
+        assert_eq!(mesh.positions[1], Vec3::new(2.0, 0.0, 0.0));
+        assert_eq!(mesh.triangles.len(), 2);
+        assert_eq!(mesh.triangles[1].positions, [0, 2, 3]);
+        assert_eq!(mesh.weights[0], [(0, 1.0)]);
+        assert!(mesh.weights[3].is_empty());
+
+        let animation = &model.animations[0];
+        assert_eq!(animation.name, "wave");
+        assert!(animation.rotations.is_empty());
+        assert_eq!(
+            animation.positions[&0],
+            [
+                Vec3::ZERO,
+                Vec3::new(0.0, 0.0, 1.0),
+                Vec3::new(0.0, 0.0, 2.0)
+            ]
+        );
+    }
+    #[test]
+    fn extract_session_models() {
+        let data = write_dmx(&[
+            // 0
+            ("DmElement", "session", vec![("activeClip", 1, element(1))]),
+            // 1
+            (
+                "DmeFilmClip",
+                "movie",
+                vec![("subClipTrackGroup", 1, element(2))],
+            ),
+            // 2
+            (
+                "DmeTrackGroup",
+                "subClipTrackGroup",
+                vec![("tracks", 15, array(&[element(3)]))],
+            ),
+            // 3
+            (
+                "DmeTrack",
+                "Film",
+                vec![("children", 15, array(&[element(4)]))],
+            ),
+            // 4
+            (
+                "DmeFilmClip",
+                "shot1",
+                vec![
+                    ("animationSets", 15, array(&[element(5)])),
+                    ("trackGroups", 15, array(&[element(10)])),
+                ],
+            ),
+            // 5
+            (
+                "DmeAnimationSet",
+                "heavy",
+                vec![("gameModel", 1, element(6))],
+            ),
+            // 6
+            (
+                "DmeGameModel",
+                "heavy",
+                vec![
+                    ("bones", 15, array(&[element(8)])),
+                    ("children", 15, array(&[element(7)])),
+                ],
+            ),
+            // 7
+            ("DmeDag", "bip_pelvis", vec![("transform", 1, element(8))]),
+            // 8
+            (
+                "DmeTransform",
+                "bip_pelvis",
+                vec![("position", 10, floats(&[0.0, 0.0, 1.0]))],
+            ),
+            // 9
+            (
+                "DmeChannel",
+                "bip_pelvis_p",
+                vec![
+                    ("toElement", 1, element(8)),
+                    ("toAttribute", 5, b"position".to_vec()),
+                    ("log", 1, element(13)),
+                ],
+            ),
+            // 10
+            (
+                "DmeTrackGroup",
+                "channelTrackGroup",
+                vec![("tracks", 15, array(&[element(11)]))],
+            ),
+            // 11
+            (
+                "DmeTrack",
+                "animSetEditorChannels",
+                vec![("children", 15, array(&[element(12)]))],
+            ),
+            // 12
+            (
+                "DmeChannelsClip",
+                "heavy",
+                vec![
+                    ("channels", 15, array(&[element(9)])),
+                    ("frameRate", 2, 2_i32.to_le_bytes().to_vec()),
+                ],
+            ),
+            // 13
+            (
+                "DmeVector3Log",
+                "log",
+                vec![("layers", 15, array(&[element(14)]))],
+            ),
+            // 14
+            (
+                "DmeVector3LogLayer",
+                "layer",
+                vec![
+                    ("times", 21, ints(&[0, 10_000])),
+                    (
+                        "values",
+                        24,
+                        array(&[floats(&[0.0, 0.0, 1.0]), floats(&[0.0, 0.0, 3.0])]),
+                    ),
+                ],
+            ),
+        ]);
+
+        let models = models(&Dmx::read(&data).unwrap()).unwrap();
+        assert_eq!(models.len(), 1);
+
+        let model = &models[0];
+        assert_eq!(model.animation_set.as_deref(), Some("heavy"));
+        assert!(model.meshes.is_empty());
+        assert_eq!(model.joints.len(), 1);
+        assert_eq!(model.joints[0].name, "bip_pelvis");
+        assert_eq!(model.joints[0].position, Vec3::new(0.0, 0.0, 1.0));
+
+        let animation = &model.animations[0];
+        assert_eq!(animation.name, "heavy");
+        assert_eq!(
+            animation.positions[&0],
+            [
+                Vec3::new(0.0, 0.0, 1.0),
+                Vec3::new(0.0, 0.0, 2.0),
+                Vec3::new(0.0, 0.0, 3.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn limit_animation_frames() {
+        let clip = |frame_rate: f32| {
+            write_dmx(&[
+                // 0
+                (
+                    "DmeChannelsClip",
+                    "clip",
+                    vec![
+                        ("channels", 15, array(&[element(1)])),
+                        ("frameRate", 3, frame_rate.to_le_bytes().to_vec()),
+                    ],
+                ),
+                // 1
+                (
+                    "DmeChannel",
+                    "channel",
+                    vec![
+                        ("toElement", 1, element(2)),
+                        ("toAttribute", 5, b"position".to_vec()),
+                        ("log", 1, element(3)),
+                    ],
+                ),
+                // 2
+                ("DmeTransform", "bone", vec![]),
+                // 3
+                (
+                    "DmeVector3Log",
+                    "log",
+                    vec![("layers", 15, array(&[element(4)]))],
+                ),
+                // 4
+                (
+                    "DmeVector3LogLayer",
+                    "layer",
+                    vec![
+                        ("times", 21, ints(&[0, 10_000])),
+                        (
+                            "values",
+                            24,
+                            array(&[floats(&[0.0, 0.0, 0.0]), floats(&[0.0, 0.0, 1.0])]),
+                        ),
+                    ],
+                ),
+            ])
+        };
+
+        let dmx = Dmx::read(&clip(f32::MAX)).unwrap();
+        let mut builder = ModelBuilder::new(&dmx);
+        builder.transform_joints.insert(2, 0);
+
+        let animation = builder
+            .animation(dmx.get(0).unwrap(), DEFAULT_FRAME_RATE)
+            .unwrap();
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let max_frames = MAX_FRAMES as usize;
+        assert_eq!(animation.positions[&0].len(), max_frames + 1);
+
+        let dmx = Dmx::read(&clip(f32::INFINITY)).unwrap();
+        let mut builder = ModelBuilder::new(&dmx);
+        builder.transform_joints.insert(2, 0);
+
+        assert_eq!(
+            builder.animation(dmx.get(0).unwrap(), DEFAULT_FRAME_RATE),
+            Err(DmxError::InvalidTimeFrame)
+        );
+    }
+}
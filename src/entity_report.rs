@@ -0,0 +1,215 @@
+//! Lists the entities of a vmf with their keyvalues as CSV or JSON, for statistics and
+//! audits of maps with external tools. Nothing is built, so large maps are fast to report.
+
+use std::{collections::BTreeSet, fmt::Write as _};
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    keyvalues::KeyValue,
+    kv_utils::{block_value, parse_vector},
+};
+
+/// Keyvalues which have their own columns.
+const OWN_KEYS: &[&str] = &["classname", "id", "origin"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportedEntity {
+    pub class_name: String,
+    pub id: String,
+    pub origin: Option<[f32; 3]>,
+    /// Number of brushes of a brush entity.
+    pub solids: usize,
+    /// Keyvalues other than the class name, id and origin, in the order of the vmf.
+    pub properties: Vec<(String, String)>,
+}
+
+impl ReportedEntity {
+    fn new(block: &[(String, KeyValue)]) -> Self {
+        let properties = block
+            .iter()
+            .filter(|(k, _)| !OWN_KEYS.iter().any(|own| k.eq_ignore_ascii_case(own)))
+            .filter_map(|(k, v)| Some((k.to_ascii_lowercase(), v.as_value()?.to_owned())))
+            .collect();
+
+        Self {
+            class_name: block_value(block, "classname")
+                .unwrap_or_default()
+                .to_owned(),
+            id: block_value(block, "id").unwrap_or_default().to_owned(),
+            origin: block_value(block, "origin")
+                .and_then(parse_vector)
+                .map(Into::into),
+            solids: block
+                .iter()
+                .filter(|(k, v)| k.eq_ignore_ascii_case("solid") && v.as_block().is_some())
+                .count(),
+            properties,
+        }
+    }
+
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Returns the world and the entities of a vmf, in the order of the vmf.
+pub fn entities(vmf: &[(String, KeyValue)]) -> Vec<ReportedEntity> {
+    vmf.iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case("world") || k.eq_ignore_ascii_case("entity"))
+        .filter_map(|(_, v)| v.as_block())
+        .map(ReportedEntity::new)
+        .collect()
+}
+
+/// Writes the entities as CSV with a column for each of `keys`, or for every keyvalue
+/// of the entities if `keys` is `None`. Missing keyvalues are empty.
+pub fn to_csv(entities: &[ReportedEntity], keys: Option<&[String]>) -> String {
+    let keys: Vec<String> = keys.map_or_else(
+        || {
+            entities
+                .iter()
+                .flat_map(|e| e.properties.iter().map(|(k, _)| k.clone()))
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect()
+        },
+        <[String]>::to_vec,
+    );
+
+    let mut csv = String::new();
+
+    let header = [
+        "classname",
+        "id",
+        "origin_x",
+        "origin_y",
+        "origin_z",
+        "solids",
+    ]
+    .into_iter()
+    .chain(keys.iter().map(String::as_str));
+    write_row(&mut csv, header);
+
+    for entity in entities {
+        let origin = entity.origin.map(|o| o.map(|c| c.to_string()));
+        let [x, y, z] = origin.as_ref().map_or(["", "", ""], |o| {
+            [o[0].as_str(), o[1].as_str(), o[2].as_str()]
+        });
+        let solids = entity.solids.to_string();
+
+        let row = [entity.class_name.as_str(), &entity.id, x, y, z, &solids]
+            .into_iter()
+            .chain(keys.iter().map(|k| entity.property(k).unwrap_or_default()));
+        write_row(&mut csv, row);
+    }
+
+    csv
+}
+
+/// Writes the entities as a JSON array of objects, with all of their keyvalues.
+/// The origin and the solid count are numbers, while the keyvalues are strings since
+/// they aren't typed.
+pub fn to_json(entities: &[ReportedEntity]) -> String {
+    let entities: Vec<Value> = entities
+        .iter()
+        .map(|entity| {
+            let mut fields = Map::new();
+            fields.insert("classname".to_owned(), json!(entity.class_name));
+            fields.insert("id".to_owned(), json!(entity.id));
+
+            if let Some(origin) = entity.origin {
+                fields.insert("origin".to_owned(), json!(origin));
+            }
+
+            fields.insert("solids".to_owned(), json!(entity.solids));
+            fields.insert(
+                "properties".to_owned(),
+                entity
+                    .properties
+                    .iter()
+                    .map(|(k, v)| (k.clone(), json!(v)))
+                    .collect(),
+            );
+
+            Value::Object(fields)
+        })
+        .collect();
+
+    format!("{:#}\n", Value::Array(entities))
+}
+
+fn write_row<'a>(csv: &mut String, fields: impl IntoIterator<Item = &'a str>) {
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            csv.push(',');
+        }
+
+        if field.contains([',', '"', '\n', '\r']) {
+            let _ = write!(csv, "\"{}\"", field.replace('"', "\"\""));
+        } else {
+            csv.push_str(field);
+        }
+    }
+
+    csv.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::keyvalues;
+
+    #[test]
+    fn report_entities() {
+        let vmf = keyvalues::parse_raw(
+            r#"
+            versioninfo { "editorversion" "400" }
+            world { "id" "1" "classname" "worldspawn" "skyname" "sky_day01_01" solid { "id" "2" } }
+            entity
+            {
+                "id" "3"
+                "classname" "info_target"
+                "targetname" "a, b"
+                "origin" "1 2.5 -3"
+                editor { "color" "220 30 220" }
+            }
+            "#,
+        )
+        .unwrap();
+
+        let entities = entities(&vmf);
+        assert_eq!(entities.len(), 2);
+        assert_eq!(entities[0].solids, 1);
+        assert_eq!(entities[1].origin, Some([1.0, 2.5, -3.0]));
+        assert_eq!(entities[1].properties.len(), 1);
+
+        assert_eq!(
+            to_csv(&entities, Some(&["targetname".to_owned()])),
+            "classname,id,origin_x,origin_y,origin_z,solids,targetname\n\
+             worldspawn,1,,,,1,\n\
+             info_target,3,1,2.5,-3,0,\"a, b\"\n"
+        );
+        assert!(to_csv(&entities, None)
+            .starts_with("classname,id,origin_x,origin_y,origin_z,solids,skyname,targetname\n"));
+
+        let mut row = String::new();
+        write_row(&mut row, ["say \"hi\"", "x"]);
+        assert_eq!(row, "\"say \"\"hi\"\"\",x\n");
+
+        let json: Value = serde_json::from_str(&to_json(&entities)).unwrap();
+        assert_eq!(json[1]["origin"], json!([1.0, 2.5, -3.0]));
+        assert_eq!(json[0]["solids"], json!(1));
+        assert_eq!(
+            json.as_array().unwrap()[1]
+                .get("properties")
+                .and_then(|p| p.get("targetname"))
+                .and_then(Value::as_str),
+            Some("a, b")
+        );
+    }
+}
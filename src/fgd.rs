@@ -0,0 +1,595 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display, Formatter},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub enum FgdError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, ParseError),
+}
+
+impl Display for FgdError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FgdError::Io(path, error) => write!(f, "`{}`: {error}", path.display()),
+            FgdError::Parse(path, error) => write!(f, "`{}`: {error}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for FgdError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: &'static str,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClassKind {
+    Base,
+    Point,
+    Solid,
+}
+
+impl ClassKind {
+    fn from_declaration(declaration: &str) -> Self {
+        if declaration.eq_ignore_ascii_case("baseclass") {
+            ClassKind::Base
+        } else if declaration.eq_ignore_ascii_case("solidclass") {
+            ClassKind::Solid
+        } else {
+            // PointClass, NPCClass, KeyFrameClass, MoveClass, FilterClass...
+            ClassKind::Point
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ClassKind::Base => "base",
+            ClassKind::Point => "point",
+            ClassKind::Solid => "brush",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FgdProperty {
+    pub name: String,
+    pub value_type: String,
+    pub default: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModelSource {
+    /// The model is specified in the class definition.
+    Path(String),
+    /// The model is read from the entity's `model` keyvalue.
+    Keyvalue,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FgdClass {
+    pub kind: ClassKind,
+    pub bases: Vec<String>,
+    pub color: Option<[u8; 3]>,
+    pub model: Option<ModelSource>,
+    pub properties: Vec<FgdProperty>,
+}
+
+/// A class with all of its base classes merged in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedClass<'a> {
+    pub kind: ClassKind,
+    pub color: Option<[u8; 3]>,
+    pub model: Option<&'a ModelSource>,
+    pub properties: BTreeMap<String, &'a FgdProperty>,
+}
+
+/// Entity definitions parsed from a game's FGD files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fgd {
+    classes: BTreeMap<String, FgdClass>,
+}
+
+impl Fgd {
+    /// Reads an FGD file, following any `@include`s relative to its directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if a file can't be read or parsed.
+    pub fn from_file(path: &Path) -> Result<Self, FgdError> {
+        let mut fgd = Self::default();
+        let mut visited = BTreeSet::new();
+
+        fgd.load_file(path, &mut visited)?;
+
+        Ok(fgd)
+    }
+
+    fn load_file(&mut self, path: &Path, visited: &mut BTreeSet<PathBuf>) -> Result<(), FgdError> {
+        if !visited.insert(path.to_path_buf()) {
+            return Ok(());
+        }
+
+        let data = fs::read(path).map_err(|e| FgdError::Io(path.to_path_buf(), e))?;
+        let text = String::from_utf8_lossy(&data);
+        let tokens = tokenize(&text).map_err(|e| FgdError::Parse(path.to_path_buf(), e))?;
+
+        let mut includes = Vec::new();
+        self.parse_tokens(&tokens, &mut includes);
+
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+        for include in includes {
+            self.load_file(&directory.join(include), visited)?;
+        }
+
+        Ok(())
+    }
+
+    /// Parses FGD text. Any `@include`s are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the text contains unterminated strings.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut fgd = Self::default();
+        fgd.parse_tokens(&tokenize(text)?, &mut Vec::new());
+
+        Ok(fgd)
+    }
+
+    pub fn class(&self, class_name: &str) -> Option<&FgdClass> {
+        self.classes.get(&class_name.to_ascii_lowercase())
+    }
+
+    /// Resolves a class and its base classes. Properties in derived classes override base ones.
+    pub fn resolve(&self, class_name: &str) -> Option<ResolvedClass<'_>> {
+        let class = self.class(class_name)?;
+
+        let mut resolved = ResolvedClass {
+            kind: class.kind,
+            color: None,
+            model: None,
+            properties: BTreeMap::new(),
+        };
+
+        self.merge_into(class, &mut resolved, &mut BTreeSet::new());
+
+        Some(resolved)
+    }
+
+    fn merge_into<'a>(
+        &'a self,
+        class: &'a FgdClass,
+        resolved: &mut ResolvedClass<'a>,
+        visited: &mut BTreeSet<*const FgdClass>,
+    ) {
+        if !visited.insert(class) {
+            return;
+        }
+
+        resolved.color = resolved.color.or(class.color);
+        resolved.model = resolved.model.or(class.model.as_ref());
+
+        for property in &class.properties {
+            resolved
+                .properties
+                .entry(property.name.to_ascii_lowercase())
+                .or_insert(property);
+        }
+
+        for base in &class.bases {
+            if let Some(base) = self.class(base) {
+                self.merge_into(base, resolved, visited);
+            }
+        }
+    }
+
+    fn parse_tokens<'a>(&mut self, tokens: &'a [Token], includes: &mut Vec<&'a str>) {
+        let mut parser = Parser { tokens, pos: 0 };
+
+        while let Some(token) = parser.next() {
+            let Token::Word(word) = token else {
+                continue;
+            };
+
+            let Some(declaration) = word.strip_prefix('@') else {
+                continue;
+            };
+
+            if declaration.eq_ignore_ascii_case("include") {
+                if let Some(Token::String(path)) = parser.next() {
+                    includes.push(path);
+                }
+            } else if declaration.to_ascii_lowercase().ends_with("class") {
+                if let Some((name, class)) = parser.class(ClassKind::from_declaration(declaration))
+                {
+                    self.classes.insert(name.to_ascii_lowercase(), class);
+                }
+            } else {
+                // @mapsize, @MaterialExclusion, @AutoVisGroup...
+                parser.skip_declaration();
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    String(String),
+    Punct(char),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        line += 1;
+                        break;
+                    }
+                }
+            }
+            '"' => {
+                let mut string = String::new();
+
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\n') | None => {
+                            return Err(ParseError {
+                                line,
+                                message: "unterminated string",
+                            })
+                        }
+                        Some(c) => string.push(c),
+                    }
+                }
+
+                tokens.push(Token::String(string));
+            }
+            '(' | ')' | '[' | ']' | '=' | ':' | ',' | '+' => tokens.push(Token::Punct(c)),
+            c if c.is_whitespace() || c == '\u{feff}' => {}
+            c => {
+                let mut word = String::from(c);
+
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "()[]=:,+\"".contains(c) {
+                        break;
+                    }
+
+                    word.push(c);
+                    chars.next();
+                }
+
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<&'a Token> {
+        self.tokens.get(self.pos + n)
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.peek()?;
+        self.pos += 1;
+        Some(token)
+    }
+
+    fn eat(&mut self, punct: char) -> bool {
+        if self.peek() == Some(&Token::Punct(punct)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Skips tokens until the matching closing bracket. Assumes the opening one was consumed.
+    fn skip_until_close(&mut self, open: char, close: char) {
+        let mut depth = 1;
+
+        while let Some(token) = self.next() {
+            match token {
+                Token::Punct(c) if *c == open => depth += 1,
+                Token::Punct(c) if *c == close => {
+                    depth -= 1;
+
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn skip_declaration(&mut self) {
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Word(word) if word.starts_with('@') => return,
+                Token::Punct('(') => {
+                    self.pos += 1;
+                    self.skip_until_close('(', ')');
+                }
+                Token::Punct('[') => {
+                    self.pos += 1;
+                    self.skip_until_close('[', ']');
+                    return;
+                }
+                _ => self.pos += 1,
+            }
+        }
+    }
+
+    /// Reads a possibly `+`-concatenated string.
+    fn string(&mut self) -> Option<String> {
+        let Some(Token::String(first)) = self.peek() else {
+            return None;
+        };
+        self.pos += 1;
+
+        let mut string = first.clone();
+
+        while self.peek() == Some(&Token::Punct('+')) {
+            if let Some(Token::String(next)) = self.peek_nth(1) {
+                string.push_str(next);
+                self.pos += 2;
+            } else {
+                break;
+            }
+        }
+
+        Some(string)
+    }
+
+    fn arguments(&mut self) -> Vec<&'a Token> {
+        let mut arguments = Vec::new();
+
+        if !self.eat('(') {
+            return arguments;
+        }
+
+        while let Some(token) = self.next() {
+            if token == &Token::Punct(')') {
+                break;
+            }
+
+            arguments.push(token);
+        }
+
+        arguments
+    }
+
+    fn class(&mut self, kind: ClassKind) -> Option<(&'a str, FgdClass)> {
+        let mut class = FgdClass {
+            kind,
+            bases: Vec::new(),
+            color: None,
+            model: None,
+            properties: Vec::new(),
+        };
+
+        // class header, e.g. `base(Targetname) color(255 0 0) studio() =`
+        loop {
+            match self.next()? {
+                Token::Punct('=') => break,
+                Token::Word(helper) => {
+                    let arguments = self.arguments();
+                    apply_helper(&mut class, helper, &arguments);
+                }
+                _ => {}
+            }
+        }
+
+        let Some(Token::Word(name)) = self.next() else {
+            return None;
+        };
+
+        if self.eat(':') {
+            self.string();
+        }
+
+        if !self.eat('[') {
+            return Some((name, class));
+        }
+
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Punct(']') => {
+                    self.pos += 1;
+                    break;
+                }
+                Token::Word(word)
+                    if (word.eq_ignore_ascii_case("input")
+                        || word.eq_ignore_ascii_case("output"))
+                        && matches!(self.peek_nth(1), Some(Token::Word(_))) =>
+                {
+                    self.pos += 2;
+                    self.arguments();
+
+                    if self.eat(':') {
+                        self.string();
+                    }
+                }
+                Token::Word(property_name) => {
+                    self.pos += 1;
+
+                    if let Some(property) = self.property(property_name) {
+                        class.properties.push(property);
+                    }
+                }
+                _ => self.pos += 1,
+            }
+        }
+
+        Some((name, class))
+    }
+
+    fn property(&mut self, name: &str) -> Option<FgdProperty> {
+        let value_type = match self.arguments().first() {
+            Some(Token::Word(value_type)) => value_type.to_ascii_lowercase(),
+            _ => return None,
+        };
+
+        // modifiers such as `readonly` or `report`
+        while matches!(self.peek(), Some(Token::Word(_)))
+            && self.peek_nth(1) != Some(&Token::Punct('('))
+        {
+            self.pos += 1;
+        }
+
+        // display name, default value, description; any of them can be empty
+        let mut fields = Vec::new();
+
+        while self.eat(':') {
+            let field = match self.peek() {
+                Some(Token::String(_)) => self.string(),
+                Some(Token::Word(word)) if self.peek_nth(1) != Some(&Token::Punct('(')) => {
+                    self.pos += 1;
+                    Some(word.clone())
+                }
+                _ => None,
+            };
+
+            fields.push(field);
+        }
+
+        if self.eat('=') && self.eat('[') {
+            self.skip_until_close('[', ']');
+        }
+
+        let default = if value_type == "flags" {
+            None
+        } else {
+            fields.get(1).cloned().flatten()
+        };
+
+        Some(FgdProperty {
+            name: name.to_owned(),
+            value_type,
+            default,
+        })
+    }
+}
+
+fn apply_helper(class: &mut FgdClass, helper: &str, arguments: &[&Token]) {
+    let helper = helper.to_ascii_lowercase();
+
+    match helper.as_str() {
+        "base" => {
+            class.bases.extend(arguments.iter().filter_map(|t| match t {
+                Token::Word(base) => Some(base.clone()),
+                _ => None,
+            }));
+        }
+        "color" => {
+            let components: Vec<u8> = arguments
+                .iter()
+                .filter_map(|t| match t {
+                    Token::Word(c) => c.parse().ok(),
+                    _ => None,
+                })
+                .collect();
+
+            if let [r, g, b] = components[..] {
+                class.color = Some([r, g, b]);
+            }
+        }
+        "studio" | "studioprop" => {
+            class.model = Some(match arguments.first() {
+                Some(Token::String(path)) => ModelSource::Path(path.clone()),
+                _ => ModelSource::Keyvalue,
+            });
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_with_bases() {
+        let text = r#"
+            @mapsize(-16384, 16384)
+
+            @BaseClass = Targetname
+            [
+                targetname(target_source) : "Name" : : "The name."
+                input Kill(void) : "Removes the entity."
+                output OnUser1(void) : "Fired " +
+                    "by FireUser1."
+            ]
+
+            @PointClass base(Targetname) color(255 128 0) studio("models/editor/camera.mdl") = point_camera : "Camera"
+            [
+                FOV(float) : "Field of view" : 90 : "Field of view in degrees"
+                spawnflags(flags) =
+                [
+                    1 : "Start Off" : 0
+                ]
+                rendercolor(color255) : "Color" : "255 255 255"
+            ]
+
+            @SolidClass base(Targetname) = func_button []
+        "#;
+
+        let fgd = Fgd::parse(text).unwrap();
+
+        let camera = fgd.resolve("POINT_CAMERA").unwrap();
+        assert_eq!(camera.kind, ClassKind::Point);
+        assert_eq!(camera.color, Some([255, 128, 0]));
+        assert_eq!(
+            camera.model,
+            Some(&ModelSource::Path("models/editor/camera.mdl".to_owned()))
+        );
+        assert_eq!(camera.properties["fov"].default.as_deref(), Some("90"));
+        assert_eq!(camera.properties["spawnflags"].default, None);
+        assert_eq!(
+            camera.properties["rendercolor"].default.as_deref(),
+            Some("255 255 255")
+        );
+        assert_eq!(camera.properties["targetname"].value_type, "target_source");
+        assert_eq!(camera.properties["targetname"].default, None);
+
+        let button = fgd.resolve("func_button").unwrap();
+        assert_eq!(button.kind, ClassKind::Solid);
+        assert!(button.properties.contains_key("targetname"));
+    }
+}
@@ -1,15 +1,20 @@
 use std::{
     cmp::Ordering,
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet, HashSet},
+    env,
     fs::{self, File},
-    io::{BufRead, BufReader, Write},
+    hash::{Hash, Hasher},
+    io::{BufRead, BufReader, Read, Write},
     path::{Path as StdPath, PathBuf as StdPathBuf},
     time::Instant,
 };
 
 use pyo3::{
-    exceptions::{PyIOError, PyTypeError, PyUnicodeDecodeError, PyValueError},
+    exceptions::{PyIOError, PyIndexError, PyTypeError, PyUnicodeDecodeError, PyValueError},
     prelude::*,
+    types::{PyDict, PyList},
 };
+use serde_json::{json, Value};
 use tracing::{error, info, warn};
 
 use plumber_core::{
@@ -20,14 +25,65 @@ use plumber_core::{
     steam::Libraries,
 };
 
+use crate::{
+    asset::{material::rad::TexLights, model::PySequencePreview},
+    bookmarks::BrowserState,
+    bsp, game_path,
+    glob::Glob,
+    gma,
+    keyvalues::{self, KeyValue},
+    particles,
+    steam::{self, LibraryImage, WorkshopItem},
+    wad,
+};
+
+#[cfg(target_os = "linux")]
+use crate::case_fold;
+#[cfg(feature = "respawn_vpk")]
+use crate::respawn_vpk;
+
+/// Directory in the temporary directory where addon archives are extracted.
+const GMA_CACHE_DIR: &str = "plumber_gma";
+/// Directory in the temporary directory where Respawn vpks are extracted.
+#[cfg(feature = "respawn_vpk")]
+const RESPAWN_VPK_CACHE_DIR: &str = "plumber_respawn_vpk";
+/// Directory in the temporary directory where map pakfiles are extracted.
+const PAKFILE_CACHE_DIR: &str = "plumber_pakfile";
+/// Directory in the temporary directory where GoldSrc texture archives are extracted.
+const WAD_CACHE_DIR: &str = "plumber_wad";
+/// Directory in the temporary directory where mixed-case files are linked.
+#[cfg(target_os = "linux")]
+const CASE_FOLDED_CACHE_DIR: &str = "plumber_case_folded";
+/// The mirror directories of the directories indexed in this session.
+#[cfg(target_os = "linux")]
+static CASE_FOLDED_DIRS: std::sync::Mutex<BTreeMap<StdPathBuf, StdPathBuf>> =
+    std::sync::Mutex::new(BTreeMap::new());
+/// Written after an archive is fully extracted.
+const EXTRACTED_MARKER: &str = ".plumber_extracted";
+
+/// The archives mounted in this session by the directory they are extracted into, so
+/// that search paths and profiles keep referring to the archive instead.
+static MOUNTED_ARCHIVES: std::sync::Mutex<BTreeMap<StdPathBuf, (&'static str, String)>> =
+    std::sync::Mutex::new(BTreeMap::new());
+
 #[pyclass(module = "plumber", name = "FileSystem")]
 pub struct PyFileSystem {
     pub file_system: FileSystem,
+    app_id: Option<u32>,
+    install_dir: Option<StdPathBuf>,
+    icon_path: Option<StdPathBuf>,
+    header_path: Option<StdPathBuf>,
 }
 
 impl From<FileSystem> for PyFileSystem {
     fn from(file_system: FileSystem) -> Self {
-        Self { file_system }
+        Self {
+            file_system,
+            app_id: None,
+            install_dir: None,
+            icon_path: None,
+            header_path: None,
+        }
     }
 }
 
@@ -37,36 +93,69 @@ impl From<PyFileSystem> for FileSystem {
     }
 }
 
+impl PyFileSystem {
+    fn with_search_paths_replaced(&self, search_paths: Vec<SearchPath>) -> Self {
+        Self {
+            file_system: FileSystem {
+                name: self.file_system.name.clone(),
+                search_paths,
+            },
+            app_id: self.app_id,
+            install_dir: self.install_dir.clone(),
+            icon_path: self.icon_path.clone(),
+            header_path: self.header_path.clone(),
+        }
+    }
+}
+
 #[pymethods]
 impl PyFileSystem {
     #[new]
     fn new(name: String, search_paths: Vec<(&str, &str)>) -> PyResult<Self> {
-        Ok(Self {
-            file_system: FileSystem {
-                name,
-                search_paths: search_paths
-                    .into_iter()
-                    .map(to_search_path)
-                    .collect::<PyResult<_>>()?,
-            },
-        })
+        Ok(FileSystem {
+            name,
+            search_paths: search_paths
+                .into_iter()
+                .map(to_search_path)
+                .collect::<PyResult<_>>()?,
+        }
+        .into())
     }
 
     #[staticmethod]
     fn empty() -> Self {
-        Self {
-            file_system: FileSystem {
-                name: "None".to_owned(),
-                search_paths: Vec::new(),
-            },
+        FileSystem {
+            name: "None".to_owned(),
+            search_paths: Vec::new(),
         }
+        .into()
     }
 
     fn name(&self) -> &str {
         &self.file_system.name
     }
 
-    fn search_paths(&self) -> PyResult<Vec<(&str, &str)>> {
+    /// Steam app id of the game, if the file system was discovered from a Steam library.
+    fn app_id(&self) -> Option<u32> {
+        self.app_id
+    }
+
+    /// Install directory of the game, if the file system was discovered from a Steam library.
+    fn install_dir(&self) -> Option<&str> {
+        self.install_dir.as_deref().and_then(StdPath::to_str)
+    }
+
+    /// Icon of the game cached by the Steam client, if found.
+    fn icon_path(&self) -> Option<&str> {
+        self.icon_path.as_deref().and_then(StdPath::to_str)
+    }
+
+    /// Header image of the game cached by the Steam client, if found.
+    fn header_path(&self) -> Option<&str> {
+        self.header_path.as_deref().and_then(StdPath::to_str)
+    }
+
+    fn search_paths(&self) -> PyResult<Vec<(&'static str, String)>> {
         self.file_system
             .search_paths
             .iter()
@@ -79,9 +168,93 @@ impl PyFileSystem {
 
         Ok(Self {
             file_system: self.file_system.with_search_paths(vec![path]),
+            app_id: self.app_id,
+            install_dir: self.install_dir.clone(),
+            icon_path: self.icon_path.clone(),
+            header_path: self.header_path.clone(),
         })
     }
 
+    /// Returns a copy of the file system with a search path inserted at `index`, so that
+    /// it's searched before the search paths after it.
+    fn with_inserted_search_path(&self, index: usize, search_path: (&str, &str)) -> PyResult<Self> {
+        let mut search_paths = self.file_system.search_paths.clone();
+
+        if index > search_paths.len() {
+            return Err(PyIndexError::new_err("search path index out of range"));
+        }
+
+        search_paths.insert(index, to_search_path(search_path)?);
+
+        Ok(self.with_search_paths_replaced(search_paths))
+    }
+
+    fn without_search_path(&self, index: usize) -> PyResult<Self> {
+        let mut search_paths = self.file_system.search_paths.clone();
+        check_search_path_index(&search_paths, index)?;
+        search_paths.remove(index);
+
+        Ok(self.with_search_paths_replaced(search_paths))
+    }
+
+    /// Returns a copy of the file system with the search path at `index` moved to `new_index`.
+    fn with_moved_search_path(&self, index: usize, new_index: usize) -> PyResult<Self> {
+        let mut search_paths = self.file_system.search_paths.clone();
+        check_search_path_index(&search_paths, index)?;
+        check_search_path_index(&search_paths, new_index)?;
+
+        let search_path = search_paths.remove(index);
+        search_paths.insert(new_index, search_path);
+
+        Ok(self.with_search_paths_replaced(search_paths))
+    }
+
+    /// Serializes the name and the search paths into a pretty-printed json profile.
+    /// Archives which are mounted by extracting them are saved as the archives, not as the
+    /// directories they were extracted into.
+    fn to_profile(&self) -> PyResult<String> {
+        profile_to_json(&self.file_system)
+    }
+
+    #[staticmethod]
+    fn from_profile(json: &str) -> PyResult<Self> {
+        profile_from_json(json).map(Self::from)
+    }
+
+    /// Returns a copy of the file system where directory and wildcard search paths are also
+    /// searched case-insensitively. Directories are case-insensitive on Windows and macOS
+    /// already, so the copy is identical there.
+    fn with_case_insensitive_dirs(&self) -> PyResult<Self> {
+        let mut search_paths = Vec::with_capacity(self.file_system.search_paths.len());
+
+        for search_path in &self.file_system.search_paths {
+            search_paths.push(search_path.clone());
+
+            #[cfg(target_os = "linux")]
+            match search_path {
+                SearchPath::Directory(dir) if dir.is_dir() => {
+                    search_paths.push(SearchPath::Directory(mount_case_folded(dir)?));
+                }
+                SearchPath::Wildcard(dir) if dir.is_dir() => {
+                    // every subdirectory of a wildcard, like `custom/*`, is a search path
+                    let mut subdirs = fs::read_dir(dir)?
+                        .filter_map(Result::ok)
+                        .map(|entry| entry.path())
+                        .filter(|path| path.is_dir())
+                        .collect::<Vec<_>>();
+                    subdirs.sort();
+
+                    for subdir in subdirs {
+                        search_paths.push(SearchPath::Directory(mount_case_folded(&subdir)?));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(self.with_search_paths_replaced(search_paths))
+    }
+
     fn browse(&self) -> PyResult<PyFileBrowser> {
         let opened = self
             .file_system
@@ -90,9 +263,87 @@ impl PyFileSystem {
 
         Ok(PyFileBrowser {
             file_system: opened,
+            game: self.file_system.name.clone(),
         })
     }
 
+    /// Parses `KeyValues` text into a dict. Duplicate keys are collected into a list.
+    /// The untyped parser is used, since `plumber_core` only parses the files it has types
+    /// for.
+    #[staticmethod]
+    fn parse_vdf(py: Python, text: &str) -> PyResult<PyObject> {
+        let parsed = keyvalues::parse(text).map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+        Ok(keyvalues_to_dict(py, &parsed)?.into())
+    }
+
+    /// Reads and parses a `KeyValues` file from this file system.
+    fn read_vdf(&self, py: Python, path: &str) -> PyResult<PyObject> {
+        let opened = self
+            .file_system
+            .open()
+            .map_err(|e| PyIOError::new_err(e.to_string()))?;
+
+        let mut data = Vec::new();
+        opened
+            .open_file(&GamePathBuf::from(path))?
+            .read_to_end(&mut data)?;
+
+        let parsed = keyvalues::parse(&String::from_utf8_lossy(&data))
+            .map_err(|e| PyValueError::new_err(format!("`{path}`: {e}")))?;
+
+        Ok(keyvalues_to_dict(py, &parsed)?.into())
+    }
+
+    /// Previews the sequences of a model without importing it. The model is read from the
+    /// os file system if `from_game` is false.
+    fn sequence_previews(&self, path: &str, from_game: bool) -> PyResult<Vec<PySequencePreview>> {
+        let data = if from_game {
+            let opened = self
+                .file_system
+                .open()
+                .map_err(|e| PyIOError::new_err(e.to_string()))?;
+
+            opened
+                .read(&game_path::game_path(path).into())
+                .map_err(|e| PyIOError::new_err(format!("`{path}`: {e}")))?
+        } else {
+            fs::read(path)?
+        };
+
+        PySequencePreview::read_all(&data)
+            .map_err(|e| PyValueError::new_err(format!("`{path}`: {e}")))
+    }
+
+    /// Reads the texture lights of `lights.rad` and `lights_custom.rad`, as
+    /// `material: (linear color, brightness)`.
+    fn texture_lights(&self) -> PyResult<BTreeMap<String, ([f32; 3], f32)>> {
+        let opened = self
+            .file_system
+            .open()
+            .map_err(|e| PyIOError::new_err(e.to_string()))?;
+
+        let texlights = TexLights::read(&opened);
+
+        Ok(texlights
+            .into_inner()
+            .into_iter()
+            .map(|(material, light)| (material, (light.color, light.brightness)))
+            .collect())
+    }
+
+    fn particle_manifest(&self, map_name: Option<&str>) -> PyResult<Vec<(String, bool)>> {
+        let opened = self
+            .file_system
+            .open()
+            .map_err(|e| PyIOError::new_err(e.to_string()))?;
+
+        Ok(particles::read_manifest(&opened, map_name)?
+            .into_iter()
+            .map(|entry| (entry.path, entry.preload))
+            .collect())
+    }
+
     fn extract(&self, path: &str, is_dir: bool, target_path: &str) -> PyResult<()> {
         let start = Instant::now();
         info!("opening file system of game `{}`...", self.file_system.name);
@@ -128,6 +379,30 @@ impl PyFileSystem {
     }
 }
 
+fn keyvalues_to_dict<'p>(py: Python<'p>, entries: &[(String, KeyValue)]) -> PyResult<&'p PyDict> {
+    let dict = PyDict::new(py);
+
+    for (key, value) in entries {
+        let value: PyObject = match value {
+            KeyValue::Value(v) => v.into_py(py),
+            KeyValue::Block(b) => keyvalues_to_dict(py, b)?.into(),
+        };
+
+        match dict.get_item(key) {
+            None => dict.set_item(key, value)?,
+            Some(existing) => {
+                if let Ok(list) = existing.downcast::<PyList>() {
+                    list.append(value)?;
+                } else {
+                    dict.set_item(key, PyList::new(py, [existing.into(), value]))?;
+                }
+            }
+        }
+    }
+
+    Ok(dict)
+}
+
 fn extract_file(file: GameFile, file_path: &str, target_path: &StdPath) -> PyResult<()> {
     let mut target_file = File::create(target_path)?;
 
@@ -202,21 +477,307 @@ fn to_search_path(search_path: (&str, &str)) -> PyResult<SearchPath> {
 
     match kind {
         "DIR" => Ok(SearchPath::Directory(StdPathBuf::from(path))),
-        "VPK" => Ok(SearchPath::Vpk(StdPathBuf::from(path))),
+        "VPK" => mount_vpk(StdPath::new(path)),
         "WILDCARD" => Ok(SearchPath::Wildcard(StdPathBuf::from(path))),
+        // archives are mounted through their extracted files
+        "GMA" => mount_gma(StdPath::new(path)).map(SearchPath::Directory),
+        "BSP" => mount_bsp_pakfile(StdPath::new(path)).map(SearchPath::Directory),
+        "WAD" => mount_wad(StdPath::new(path)).map(SearchPath::Directory),
         _ => Err(PyTypeError::new_err("invalid search path enum value")),
     }
 }
 
-fn from_search_path(search_path: &SearchPath) -> PyResult<(&str, &str)> {
+#[cfg(not(feature = "respawn_vpk"))]
+#[allow(clippy::unnecessary_wraps)]
+fn mount_vpk(path: &StdPath) -> PyResult<SearchPath> {
+    Ok(SearchPath::Vpk(path.to_owned()))
+}
+
+/// Mounts a vpk, extracting it into a cache directory if it's a Respawn vpk, which the
+/// file system can't read.
+#[cfg(feature = "respawn_vpk")]
+fn mount_vpk(path: &StdPath) -> PyResult<SearchPath> {
+    if !respawn_vpk::is_respawn_vpk(path) {
+        return Ok(SearchPath::Vpk(path.to_owned()));
+    }
+
+    mount_extracted(path, "VPK", RESPAWN_VPK_CACHE_DIR, |target_dir| {
+        let start = Instant::now();
+        let vpk =
+            respawn_vpk::RespawnVpk::open(path).map_err(|e| PyIOError::new_err(e.to_string()))?;
+
+        let extracted = vpk
+            .extract(target_dir)
+            .map_err(|e| PyIOError::new_err(e.to_string()))?;
+
+        info!(
+            "extracted {extracted} files of respawn vpk `{}` in {:.2} s",
+            path.display(),
+            start.elapsed().as_secs_f32()
+        );
+
+        Ok(())
+    })
+    .map(SearchPath::Directory)
+}
+
+/// Extracts a Garry's Mod addon archive into a cache directory, which can be mounted.
+fn mount_gma(path: &StdPath) -> PyResult<StdPathBuf> {
+    mount_extracted(path, "GMA", GMA_CACHE_DIR, |target_dir| {
+        let start = Instant::now();
+        let gma = gma::open(path).map_err(|e| PyIOError::new_err(e.to_string()))?;
+        let name = gma.name.clone();
+
+        let extracted = gma
+            .extract(target_dir)
+            .map_err(|e| PyIOError::new_err(e.to_string()))?;
+
+        info!(
+            "extracted {extracted} files of addon `{name}` in {:.2} s",
+            start.elapsed().as_secs_f32()
+        );
+
+        Ok(())
+    })
+}
+
+/// Extracts the files embedded in the pakfile of a compiled map into a cache directory,
+/// which can be mounted. The file system only mounts directories and vpks, so the
+/// pakfile can't be mounted in memory.
+pub fn mount_bsp_pakfile(path: &StdPath) -> PyResult<StdPathBuf> {
+    mount_extracted(path, "BSP", PAKFILE_CACHE_DIR, |target_dir| {
+        let start = Instant::now();
+        let bytes = fs::read(path)?;
+
+        let entries = bsp::Bsp::parse(&bytes)
+            .and_then(|bsp| bsp::pakfile::pakfile(&bsp))
+            .map_err(|e| PyIOError::new_err(e.to_string()))?;
+        let extracted = bsp::pakfile::extract(&entries, target_dir)?;
+
+        info!(
+            "extracted {extracted} embedded files of map `{}` in {:.2} s",
+            path.display(),
+            start.elapsed().as_secs_f32()
+        );
+
+        Ok(())
+    })
+}
+
+/// Extracts the textures of a GoldSrc texture archive into a cache directory as loose
+/// images with materials, which can be mounted.
+fn mount_wad(path: &StdPath) -> PyResult<StdPathBuf> {
+    mount_extracted(path, "WAD", WAD_CACHE_DIR, |target_dir| {
+        let start = Instant::now();
+        let bytes = fs::read(path)?;
+        let extracted = wad::extract(&bytes, target_dir)?;
+
+        info!(
+            "extracted {extracted} textures of wad `{}` in {:.2} s",
+            path.display(),
+            start.elapsed().as_secs_f32()
+        );
+
+        Ok(())
+    })
+}
+
+/// Links the mixed-case files of a directory by their lowercase path into a cache
+/// directory, which is searched after the directory. A directory is indexed only on
+/// its first mount in a session, since indexing a large directory is slow.
+#[cfg(target_os = "linux")]
+fn mount_case_folded(dir: &StdPath) -> PyResult<StdPathBuf> {
+    let mut mounted = CASE_FOLDED_DIRS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+    if let Some(target_dir) = mounted.get(dir) {
+        return Ok(target_dir.clone());
+    }
+
+    let mut hasher = DefaultHasher::new();
+    dir.hash(&mut hasher);
+
+    let name = dir
+        .file_name()
+        .map_or_else(|| "root".into(), |s| s.to_string_lossy());
+    let target_dir = env::temp_dir()
+        .join(CASE_FOLDED_CACHE_DIR)
+        .join(format!("{name}_{:016x}", hasher.finish()));
+
+    fs::create_dir_all(&target_dir)?;
+
+    let start = Instant::now();
+    let linked = case_fold::mirror(dir, &target_dir)
+        .map_err(|e| PyIOError::new_err(format!("`{}`: {e}", dir.display())))?;
+
+    info!(
+        "linked {linked} new mixed-case files of `{}` in {:.2} s",
+        dir.display(),
+        start.elapsed().as_secs_f32()
+    );
+
+    mounted.insert(dir.to_path_buf(), target_dir.clone());
+
+    Ok(target_dir)
+}
+
+/// Extracts an archive with `extract` into a directory in `cache_dir`, keyed by the path
+/// and the modification of the archive. The archive is extracted again only if it has
+/// changed, and the extractions of its previous versions and of archives which no
+/// longer exist are removed. The directory is
+/// registered as a search path of `kind`, so that it's listed and saved as the archive.
+fn mount_extracted(
+    path: &StdPath,
+    kind: &'static str,
+    cache_dir: &str,
+    extract: impl FnOnce(&StdPath) -> PyResult<()>,
+) -> PyResult<StdPathBuf> {
+    let target_dir = extract_archive(path, cache_dir, extract)?;
+
+    if let Some(archive) = path.to_str() {
+        mounted_archives().insert(target_dir.clone(), (kind, archive.to_owned()));
+    }
+
+    Ok(target_dir)
+}
+
+fn extract_archive(
+    path: &StdPath,
+    cache_dir: &str,
+    extract: impl FnOnce(&StdPath) -> PyResult<()>,
+) -> PyResult<StdPathBuf> {
+    let metadata = fs::metadata(path)?;
+
+    let mut path_hasher = DefaultHasher::new();
+    path.hash(&mut path_hasher);
+
+    let mut version_hasher = DefaultHasher::new();
+    metadata.len().hash(&mut version_hasher);
+    metadata.modified().ok().hash(&mut version_hasher);
+
+    let stem = path
+        .file_stem()
+        .map_or_else(|| "archive".into(), |s| s.to_string_lossy());
+    let cache_dir = env::temp_dir().join(cache_dir);
+    let prefix = format!("{stem}_{:016x}_", path_hasher.finish());
+    let target_dir = cache_dir.join(format!("{prefix}{:016x}", version_hasher.finish()));
+    let marker = target_dir.join(EXTRACTED_MARKER);
+
+    if marker.is_file() {
+        return Ok(target_dir);
+    }
+
+    // a previous extraction was interrupted, or the archive has changed since
+    if let Ok(read_dir) = fs::read_dir(&cache_dir) {
+        for entry in read_dir.filter_map(Result::ok) {
+            if entry.file_name().to_string_lossy().starts_with(&prefix)
+                || is_stale_extraction(&entry.path())
+            {
+                if let Err(error) = fs::remove_dir_all(entry.path()) {
+                    warn!("could not remove `{}`: {error}", entry.path().display());
+                }
+            }
+        }
+    }
+
+    fs::create_dir_all(&target_dir)?;
+    extract(&target_dir).map_err(|e| PyIOError::new_err(format!("`{}`: {e}", path.display())))?;
+    // the marker records the archive, so that the extraction is removed with it
+    fs::write(marker, path.to_string_lossy().as_bytes())?;
+
+    Ok(target_dir)
+}
+
+/// Returns if an extraction is of an archive which no longer exists. Extractions mounted
+/// in this session are kept.
+fn is_stale_extraction(dir: &StdPath) -> bool {
+    if mounted_archives().contains_key(dir) {
+        return false;
+    }
+
+    fs::read_to_string(dir.join(EXTRACTED_MARKER))
+        .is_ok_and(|archive| !StdPath::new(&archive).is_file())
+}
+
+fn check_search_path_index(search_paths: &[SearchPath], index: usize) -> PyResult<()> {
+    if index < search_paths.len() {
+        Ok(())
+    } else {
+        Err(PyIndexError::new_err("search path index out of range"))
+    }
+}
+
+fn profile_to_json(file_system: &FileSystem) -> PyResult<String> {
+    let search_paths = file_system
+        .search_paths
+        .iter()
+        .map(|search_path| {
+            let (kind, path) = from_search_path(search_path)?;
+            Ok(json!([kind, path]))
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let profile = json!({
+        "name": file_system.name,
+        "search_paths": search_paths,
+    });
+
+    Ok(format!("{profile:#}\n"))
+}
+
+fn profile_from_json(json: &str) -> PyResult<FileSystem> {
+    let invalid = || PyValueError::new_err("invalid file system profile");
+    let profile: Value = serde_json::from_str(json).map_err(|_| invalid())?;
+
+    let name = profile
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or_else(invalid)?;
+    let search_paths = profile
+        .get("search_paths")
+        .and_then(Value::as_array)
+        .ok_or_else(invalid)?
+        .iter()
+        .map(
+            |search_path| match search_path.as_array().map(Vec::as_slice) {
+                Some([Value::String(kind), Value::String(path)]) => to_search_path((kind, path)),
+                _ => Err(invalid()),
+            },
+        )
+        .collect::<PyResult<_>>()?;
+
+    Ok(FileSystem {
+        name: name.to_owned(),
+        search_paths,
+    })
+}
+
+/// Returns the kind and the path of a search path. Directories which archives are
+/// extracted into are returned as the archive.
+pub fn from_search_path(search_path: &SearchPath) -> PyResult<(&'static str, String)> {
+    if let SearchPath::Directory(path) = search_path {
+        if let Some(archive) = mounted_archives().get(path) {
+            return Ok(archive.clone());
+        }
+    }
+
     match search_path {
         SearchPath::Vpk(path) => path.to_str().map(|path| ("VPK", path)),
         SearchPath::Directory(path) => path.to_str().map(|path| ("DIR", path)),
         SearchPath::Wildcard(path) => path.to_str().map(|path| ("WILDCARD", path)),
     }
+    .map(|(kind, path)| (kind, path.to_owned()))
     .ok_or_else(|| PyUnicodeDecodeError::new_err("search path is not valid utf8"))
 }
 
+fn mounted_archives() -> std::sync::MutexGuard<'static, BTreeMap<StdPathBuf, (&'static str, String)>>
+{
+    MOUNTED_ARCHIVES
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
 pub fn discover() -> Vec<PyFileSystem> {
     let libraries = match Libraries::discover() {
         Ok(libraries) => libraries,
@@ -226,18 +787,106 @@ pub fn discover() -> Vec<PyFileSystem> {
         }
     };
 
-    libraries
-        .apps()
-        .source()
-        .filesystems()
-        .filter_map(|r| match r {
-            Ok(f) => Some(f.into()),
+    let mut discovered: Vec<PyFileSystem> = Vec::new();
+
+    for app in libraries.apps().source() {
+        let app = match app {
+            Ok(app) => app,
             Err(e) => {
                 warn!("could not discover a game: {}", e);
-                None
+                continue;
             }
-        })
-        .collect()
+        };
+
+        let file_system = match FileSystem::from_app(&app) {
+            Ok(file_system) => file_system,
+            Err(e) => {
+                warn!("could not discover a game: {}", e);
+                continue;
+            }
+        };
+
+        // the same install can be found through multiple library folders or symlinks
+        let install_dir = fs::canonicalize(&app.install_dir).unwrap_or(app.install_dir);
+
+        if let Some(existing) = discovered
+            .iter_mut()
+            .find(|f| f.install_dir.as_ref() == Some(&install_dir))
+        {
+            info!(
+                "game `{}` was discovered multiple times, merging",
+                file_system.name
+            );
+            merge_search_paths(&mut existing.file_system, file_system);
+            continue;
+        }
+
+        discovered.push(PyFileSystem {
+            file_system,
+            app_id: Some(app.app_id),
+            install_dir: Some(install_dir),
+            icon_path: None,
+            header_path: None,
+        });
+    }
+
+    // the images are cached by the client, which may have no apps in its own library
+    let mut library_roots = steam::steam_roots();
+
+    for install_dir in discovered.iter().filter_map(|f| f.install_dir.as_deref()) {
+        if let Some(root) = steam::library_root(install_dir) {
+            if !library_roots.iter().any(|r| r == root) {
+                library_roots.push(root.to_path_buf());
+            }
+        }
+    }
+
+    for file_system in &mut discovered {
+        if let Some(app_id) = file_system.app_id {
+            file_system.icon_path =
+                steam::library_image(&library_roots, app_id, LibraryImage::Icon);
+            file_system.header_path =
+                steam::library_image(&library_roots, app_id, LibraryImage::Header);
+        }
+    }
+
+    discovered
+}
+
+/// Appends the search paths of `other` which `target` doesn't have yet, so that
+/// `target` keeps priority.
+pub fn merge_search_paths(target: &mut FileSystem, other: FileSystem) {
+    for search_path in other.search_paths {
+        let key = search_path_key(&search_path);
+
+        if !target
+            .search_paths
+            .iter()
+            .any(|existing| search_path_key(existing) == key)
+        {
+            target.search_paths.push(search_path);
+        }
+    }
+}
+
+/// Hashes the search paths of a file system, identifying where its assets are loaded from.
+pub fn hash_search_paths(file_system: &FileSystem, hasher: &mut impl Hasher) {
+    for search_path in &file_system.search_paths {
+        search_path_key(search_path).hash(hasher);
+    }
+}
+
+fn search_path_key(search_path: &SearchPath) -> (&'static str, StdPathBuf) {
+    let (kind, path) = match search_path {
+        SearchPath::Vpk(path) => ("VPK", path),
+        SearchPath::Directory(path) => ("DIR", path),
+        SearchPath::Wildcard(path) => ("WILDCARD", path),
+    };
+
+    (
+        kind,
+        fs::canonicalize(path).unwrap_or_else(|_| path.clone()),
+    )
 }
 
 pub fn from_gameinfo(path: &str) -> PyResult<PyFileSystem> {
@@ -253,19 +902,296 @@ pub fn from_gameinfo(path: &str) -> PyResult<PyFileSystem> {
     Ok(file_system.into())
 }
 
+/// Directories of an extracted asset tree, used to find its root.
+const ASSET_DIRS: [&str; 6] = [
+    "materials",
+    "models",
+    "maps",
+    "sound",
+    "particles",
+    "scripts",
+];
+
+/// Mounts a plain directory of extracted assets, which has no gameinfo.txt.
+/// Selecting one of the asset directories mounts the directory containing it.
+pub fn from_directory(path: &str) -> PyResult<PyFileSystem> {
+    let mut root = StdPath::new(path);
+
+    if !root.is_dir() {
+        return Err(PyIOError::new_err(format!("`{path}` is not a directory")));
+    }
+
+    let is_asset_dir = |dir: &StdPath| {
+        dir.file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| ASSET_DIRS.iter().any(|d| n.eq_ignore_ascii_case(d)))
+    };
+
+    if is_asset_dir(root) {
+        root = root.parent().unwrap_or(root);
+    }
+
+    let name = root
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("Extracted assets")
+        .to_owned();
+
+    let entries: Vec<_> = fs::read_dir(root)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .collect();
+
+    if !entries.iter().any(|e| e.is_dir() && is_asset_dir(e)) {
+        warn!(
+            "directory `{}` doesn't contain any asset directories",
+            root.display()
+        );
+    }
+
+    let mut search_paths = vec![SearchPath::Directory(root.to_path_buf())];
+
+    // extracted trees sometimes come with the packages they were extracted from
+    let mut vpks: Vec<_> = entries
+        .into_iter()
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.to_ascii_lowercase().ends_with("_dir.vpk"))
+        })
+        .collect();
+    vpks.sort();
+
+    search_paths.extend(vpks.into_iter().map(SearchPath::Vpk));
+
+    Ok(FileSystem { name, search_paths }.into())
+}
+
+/// Asset directories containing each importable file type, in which the root of the
+/// asset tree of a loose file is searched.
+const ASSET_ROOT_DIRS: [(&str, &str); 4] = [
+    ("mdl", "models"),
+    ("vmt", "materials"),
+    ("vtf", "materials"),
+    ("vmf", "maps"),
+];
+
+/// Walks up from an asset to the directory containing `target_path`, which is the
+/// root of a typical Source asset directory structure.
+pub fn detect_asset_root<'a>(asset_path: &'a str, target_path: &str) -> Option<&'a StdPath> {
+    let mut asset_path = StdPath::new(asset_path);
+
+    loop {
+        asset_path = asset_path.parent()?;
+
+        if asset_path.ends_with(target_path) {
+            return asset_path.parent();
+        }
+    }
+}
+
+/// Mounts the asset tree a loose asset file is in, so that it can be imported along
+/// with the assets it depends on. A file outside any asset tree gets an empty file system.
+pub fn from_asset_path(path: &str) -> PyResult<PyFileSystem> {
+    let extension = StdPath::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+
+    let target_path = ASSET_ROOT_DIRS
+        .iter()
+        .find(|(e, _)| extension.eq_ignore_ascii_case(e))
+        .map(|(_, dir)| *dir)
+        .ok_or_else(|| PyValueError::new_err(format!("`{path}` is not an importable asset")))?;
+
+    if let Some(root) = detect_asset_root(path, target_path) {
+        info!("detected asset root `{}`", root.display());
+        from_directory(path_to_str(root)?)
+    } else {
+        warn!("`{path}` is not inside a `{target_path}` directory, dependencies won't be found");
+        Ok(PyFileSystem::empty())
+    }
+}
+
+pub fn find_workshop_item(
+    install_dir: &str,
+    app_id: u32,
+    workshop_id: u64,
+) -> Option<PyWorkshopItem> {
+    let item = steam::workshop_item(StdPath::new(install_dir), app_id, workshop_id)?;
+
+    info!(
+        "found workshop item {} with {} maps and {} addons",
+        workshop_id,
+        item.maps.len(),
+        item.addons.len()
+    );
+
+    Some(PyWorkshopItem(item))
+}
+
+/// Lists the downloaded workshop items of a game which contain maps, so that one can be
+/// picked to be mounted.
+pub fn workshop_map_items(install_dir: &str, app_id: u32) -> Vec<PyWorkshopItem> {
+    steam::workshop_items(StdPath::new(install_dir), app_id)
+        .into_iter()
+        .filter(|item| !item.maps.is_empty())
+        .map(PyWorkshopItem)
+        .collect()
+}
+
+#[pyclass(module = "plumber", name = "WorkshopItem")]
+pub struct PyWorkshopItem(WorkshopItem);
+
+#[pymethods]
+impl PyWorkshopItem {
+    fn dir(&self) -> PyResult<&str> {
+        path_to_str(&self.0.dir)
+    }
+
+    /// Search paths which mount the files of the item into a game file system.
+    fn search_paths(&self) -> PyResult<Vec<(&'static str, String)>> {
+        self.0
+            .mount_dirs
+            .iter()
+            .map(|dir| Ok(("DIR", path_to_str(dir)?)))
+            .collect()
+    }
+
+    fn maps(&self) -> PyResult<Vec<&str>> {
+        self.0.maps.iter().map(|p| path_to_str(p)).collect()
+    }
+
+    /// Garry's Mod addon archives in the item.
+    fn addons(&self) -> PyResult<Vec<&str>> {
+        self.0.addons.iter().map(|p| path_to_str(p)).collect()
+    }
+}
+
+fn path_to_str(path: &StdPath) -> PyResult<&str> {
+    path.to_str()
+        .ok_or_else(|| PyUnicodeDecodeError::new_err("path is not valid utf8"))
+}
+
 #[pyclass(module = "plumber", name = "FileBrowser")]
 pub struct PyFileBrowser {
     file_system: OpenFileSystem,
+    /// Name of the game, which the browser state is stored by.
+    game: String,
 }
 
 #[pymethods]
 impl PyFileBrowser {
-    fn read_dir(&self, dir: String) -> PyResult<Vec<PyFileBrowserEntry>> {
+    /// Lists the entries of a directory, directories first. Files are filtered by their
+    /// `extensions` and all entries by a case-insensitive `search` in their name, and
+    /// then at most `limit` entries starting from `offset` are returned, along with the
+    /// number of filtered entries for paginating them. An offset past the last entry
+    /// is clamped to the start of the last page.
+    #[args(
+        dir,
+        extensions = "None",
+        search = "None",
+        offset = "0",
+        limit = "None"
+    )]
+    fn read_dir(
+        &self,
+        dir: &str,
+        extensions: Option<Vec<String>>,
+        search: Option<&str>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> PyResult<(usize, Vec<PyFileBrowserEntry>)> {
+        let filter = EntryFilter::new(extensions, search);
+        let entries = self.filtered_entries(dir, &filter)?;
+        let total = entries.len();
+        let limit = limit.unwrap_or(usize::MAX).max(1);
+
+        Ok((
+            total,
+            entries
+                .into_iter()
+                .skip(page_offset(offset, limit, total))
+                .take(limit)
+                .collect(),
+        ))
+    }
+
+    /// Finds the files matching a glob pattern like `models/props_junk/*.mdl`, in all
+    /// search paths. `**` matches any number of directories. At most `limit` paths are
+    /// returned, sorted.
+    #[args(pattern, limit = "None")]
+    fn find(&self, pattern: &str, limit: Option<usize>) -> PyResult<Vec<String>> {
+        let glob = Glob::new(pattern);
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut found = BTreeSet::new();
+        let mut pending = vec![glob.base_dir()];
+        // directories in several search paths are listed once, with the files of each
+        let mut visited: HashSet<String> = pending.iter().cloned().collect();
+
+        'walk: while let Some(dir) = pending.pop().filter(|_| limit > 0) {
+            for res in self.file_system.read_dir(&GamePathBuf::from(dir)) {
+                let entry = res?;
+                let path = game_path::normalize(entry.path().as_str());
+
+                if entry.entry_type().is_directory() {
+                    if glob.can_match_in(&path) && visited.insert(path.clone()) {
+                        pending.push(path);
+                    }
+                } else if glob.matches(&path) {
+                    found.insert(path);
+
+                    if found.len() >= limit {
+                        break 'walk;
+                    }
+                }
+            }
+        }
+
+        Ok(found.into_iter().collect())
+    }
+
+    /// Bookmarked directories of the game, in the order they were bookmarked.
+    fn bookmarks(&self) -> Vec<String> {
+        BrowserState::load(&self.game).bookmarks
+    }
+
+    fn set_bookmarked(&self, path: &str, bookmarked: bool) -> PyResult<()> {
+        let mut state = BrowserState::load(&self.game);
+        state.set_bookmarked(path, bookmarked);
+        state.save(&self.game)?;
+        Ok(())
+    }
+
+    /// Recently imported files of the game, the most recent first.
+    fn recent_imports(&self) -> Vec<String> {
+        BrowserState::load(&self.game).recent
+    }
+
+    fn add_recent_import(&self, path: &str) -> PyResult<()> {
+        let mut state = BrowserState::load(&self.game);
+        state.add_recent(path);
+        state.save(&self.game)?;
+        Ok(())
+    }
+}
+
+impl PyFileBrowser {
+    fn filtered_entries(
+        &self,
+        dir: &str,
+        filter: &EntryFilter,
+    ) -> PyResult<Vec<PyFileBrowserEntry>> {
+        let dir = GamePathBuf::from(dir.to_owned());
         let mut entries = Vec::new();
 
-        for res in self.file_system.read_dir(&GamePathBuf::from(dir)) {
+        for res in self.file_system.read_dir(&dir) {
             let entry = res?;
 
+            if !filter.matches(entry.name(), entry.entry_type()) {
+                continue;
+            }
+
             entries.push(PyFileBrowserEntry {
                 name: entry.name().to_string(),
                 path: entry.path().to_path_buf(),
@@ -289,6 +1215,51 @@ impl PyFileBrowser {
     }
 }
 
+/// Filters the entries listed by the file browser.
+#[derive(Debug, Default)]
+struct EntryFilter {
+    /// Lowercase extensions of the listed files, with the dot.
+    extensions: Vec<String>,
+    /// Lowercase text the names of the listed entries contain.
+    search: String,
+}
+
+impl EntryFilter {
+    fn new(extensions: Option<Vec<String>>, search: Option<&str>) -> Self {
+        let extensions = extensions
+            .unwrap_or_default()
+            .into_iter()
+            .map(|e| {
+                let e = e.to_ascii_lowercase();
+                if e.starts_with('.') {
+                    e
+                } else {
+                    format!(".{e}")
+                }
+            })
+            .collect();
+
+        Self {
+            extensions,
+            search: search.unwrap_or_default().to_ascii_lowercase(),
+        }
+    }
+
+    fn matches(&self, name: &str, kind: &DirEntryType) -> bool {
+        let name = name.to_ascii_lowercase();
+
+        name.contains(&self.search)
+            && (kind.is_directory()
+                || self.extensions.is_empty()
+                || self.extensions.iter().any(|e| name.ends_with(e.as_str())))
+    }
+}
+
+/// Clamps the offset of a page of entries to the start of the last page.
+fn page_offset(offset: usize, limit: usize, total: usize) -> usize {
+    offset.min(total.saturating_sub(1) / limit * limit)
+}
+
 #[pyclass(module = "plumber", name = "FileBrowserEntry")]
 #[derive(PartialEq, Eq)]
 pub struct PyFileBrowserEntry {
@@ -314,3 +1285,58 @@ impl PyFileBrowserEntry {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_entries() {
+        let filter = EntryFilter::new(Some(vec!["MDL".to_owned(), ".vmt".to_owned()]), None);
+        assert!(filter.matches("Crate.MDL", &DirEntryType::File));
+        assert!(filter.matches("crate.vmt", &DirEntryType::File));
+        assert!(!filter.matches("crate.vtf", &DirEntryType::File));
+        assert!(filter.matches("props", &DirEntryType::Directory));
+
+        let filter = EntryFilter::new(None, Some("Junk"));
+        assert!(filter.matches("props_junk", &DirEntryType::Directory));
+        assert!(filter.matches("junk01.mdl", &DirEntryType::File));
+        assert!(!filter.matches("props_c17", &DirEntryType::Directory));
+    }
+
+    #[test]
+    fn clamp_page_offset() {
+        assert_eq!(page_offset(100, 100, 250), 100);
+        assert_eq!(page_offset(300, 100, 250), 200);
+        assert_eq!(page_offset(200, 100, 200), 100);
+        assert_eq!(page_offset(100, 100, 0), 0);
+    }
+
+    #[test]
+    fn round_trip_profile() {
+        let file_system = FileSystem {
+            name: "Custom \"mod\"".to_owned(),
+            search_paths: vec![
+                SearchPath::Directory(StdPathBuf::from("C:\\mods\\custom")),
+                SearchPath::Vpk(StdPathBuf::from("hl2/hl2_misc.vpk")),
+                SearchPath::Wildcard(StdPathBuf::from("custom/*")),
+            ],
+        };
+
+        let read = profile_from_json(&profile_to_json(&file_system).unwrap()).unwrap();
+        assert_eq!(read.name, file_system.name);
+        assert_eq!(
+            read.search_paths
+                .iter()
+                .map(|p| from_search_path(p).unwrap())
+                .collect::<Vec<_>>(),
+            [
+                ("DIR", "C:\\mods\\custom".to_owned()),
+                ("VPK", "hl2/hl2_misc.vpk".to_owned()),
+                ("WILDCARD", "custom/*".to_owned()),
+            ]
+        );
+
+        assert!(profile_from_json(r#"{"name": "a", "search_paths": [["DIR"]]}"#).is_err());
+    }
+}
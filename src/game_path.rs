@@ -0,0 +1,70 @@
+//! Normalizes game paths read from VMTs, MDLs and VMFs. Content authored on Windows
+//! often uses backslashes, doubled slashes and inconsistent case, which break lookups
+//! in vpks and on case-sensitive file systems.
+
+use std::path::{Component, Path};
+
+use plumber_core::fs::GamePathBuf;
+
+/// Uses forward slashes, removes empty and `.` components and lowercases the path.
+pub fn normalize(path: &str) -> String {
+    path.trim()
+        .replace('\\', "/")
+        .to_ascii_lowercase()
+        .split('/')
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub fn game_path(path: &str) -> GamePathBuf {
+    GamePathBuf::from(normalize(path))
+}
+
+/// Returns the path of a material or a texture relative to the `materials` directory,
+/// without the `extension`, like materials and textures are referred to.
+pub fn material_name(path: &str, extension: &str) -> String {
+    let path = normalize(path);
+    let path = path.strip_prefix("materials/").unwrap_or(&path);
+
+    path.strip_suffix(extension).unwrap_or(path).to_owned()
+}
+
+/// Returns if an archived file can be extracted to the path without escaping the
+/// target directory.
+pub fn is_safe_to_extract(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_paths() {
+        assert_eq!(
+            normalize(" Models\\Props//./Crate.MDL "),
+            "models/props/crate.mdl"
+        );
+        assert_eq!(normalize("/materials/brick/"), "materials/brick");
+        assert_eq!(
+            material_name("Materials\\Brick\\BrickWall001.vmt", ".vmt"),
+            "brick/brickwall001"
+        );
+        assert_eq!(
+            material_name("brick/brickwall001", ".vtf"),
+            "brick/brickwall001"
+        );
+    }
+
+    #[test]
+    fn reject_escaping_paths() {
+        assert!(is_safe_to_extract("materials/brick.vmt"));
+        assert!(!is_safe_to_extract("../escape.txt"));
+        assert!(!is_safe_to_extract("/etc/passwd"));
+        assert!(!is_safe_to_extract(""));
+    }
+}
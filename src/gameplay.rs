@@ -0,0 +1,70 @@
+//! Recognizes the entities marking gameplay locations, such as spawn points and
+//! objective zones, so that they can be labeled in overviews of a map.
+
+/// An entity marking a gameplay location. Point entities are imported as empties and
+/// brush entities as volumes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameplayMarker {
+    PlayerStart,
+    TerroristSpawn,
+    CounterTerroristSpawn,
+    Hostage,
+    HostageRescue,
+    Bombsite,
+}
+
+impl GameplayMarker {
+    pub fn from_class_name(class_name: &str) -> Option<Self> {
+        Some(match class_name.to_ascii_lowercase().as_str() {
+            "info_player_start" | "info_player_deathmatch" => Self::PlayerStart,
+            "info_player_terrorist" => Self::TerroristSpawn,
+            "info_player_counterterrorist" => Self::CounterTerroristSpawn,
+            "hostage_entity" | "info_hostage_spawn" => Self::Hostage,
+            "func_hostage_rescue" | "info_hostage_rescue" => Self::HostageRescue,
+            "func_bomb_target" | "info_bomb_target" => Self::Bombsite,
+            _ => return None,
+        })
+    }
+
+    /// Name of the marker kind, stored on the imported objects.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::PlayerStart => "player_start",
+            Self::TerroristSpawn => "terrorist_spawn",
+            Self::CounterTerroristSpawn => "counterterrorist_spawn",
+            Self::Hostage => "hostage",
+            Self::HostageRescue => "hostage_rescue",
+            Self::Bombsite => "bombsite",
+        }
+    }
+
+    /// Label shown on the imported objects.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::PlayerStart => "Player Start",
+            Self::TerroristSpawn => "T Spawn",
+            Self::CounterTerroristSpawn => "CT Spawn",
+            Self::Hostage => "Hostage",
+            Self::HostageRescue => "Hostage Rescue",
+            Self::Bombsite => "Bombsite",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognize_gameplay_markers() {
+        assert_eq!(
+            GameplayMarker::from_class_name("Info_Player_Terrorist"),
+            Some(GameplayMarker::TerroristSpawn)
+        );
+        assert_eq!(
+            GameplayMarker::from_class_name("func_bomb_target").map(GameplayMarker::label),
+            Some("Bombsite")
+        );
+        assert_eq!(GameplayMarker::from_class_name("info_target"), None);
+    }
+}
@@ -0,0 +1,155 @@
+//! Glob patterns for finding game files, like `models/props_junk/*.mdl`. `*` and `?`
+//! match within a path component, and a `**` component matches any number of
+//! directories. Matching is case-insensitive, like game paths are.
+
+use crate::game_path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    components: Vec<String>,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Self {
+        Self {
+            components: game_path::normalize(pattern)
+                .split('/')
+                .filter(|c| !c.is_empty())
+                .map(str::to_owned)
+                .collect(),
+        }
+    }
+
+    /// The directory containing all matches, the components before the first wildcard.
+    pub fn base_dir(&self) -> String {
+        let literal = self
+            .components
+            .iter()
+            .take(self.components.len().saturating_sub(1))
+            .take_while(|c| !has_wildcard(c))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+
+        literal.join("/")
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        let path = game_path::normalize(path);
+        let path: Vec<&str> = path.split('/').collect();
+
+        match_components(&self.components, &path)
+    }
+
+    /// Whether files in the directory or its subdirectories can match.
+    pub fn can_match_in(&self, dir: &str) -> bool {
+        let dir = game_path::normalize(dir);
+        let dir: Vec<&str> = dir.split('/').filter(|c| !c.is_empty()).collect();
+
+        match_dir(&self.components, &dir)
+    }
+}
+
+fn has_wildcard(component: &str) -> bool {
+    component.contains(['*', '?'])
+}
+
+fn match_components(pattern: &[String], path: &[&str]) -> bool {
+    wildcard_match(
+        pattern,
+        path,
+        |p| p == "**",
+        |p, component| match_component(p.as_bytes(), component.as_bytes()),
+    )
+}
+
+fn match_dir(pattern: &[String], dir: &[&str]) -> bool {
+    for (i, component) in dir.iter().enumerate() {
+        match pattern.get(i) {
+            Some(p) if p == "**" => return true,
+            // the last component matches files, not directories
+            Some(p) if i + 1 < pattern.len() => {
+                if !match_component(p.as_bytes(), component.as_bytes()) {
+                    return false;
+                }
+            }
+            _ => return false,
+        }
+    }
+
+    true
+}
+
+fn match_component(pattern: &[u8], name: &[u8]) -> bool {
+    wildcard_match(pattern, name, |&p| p == b'*', |&p, &c| p == b'?' || p == c)
+}
+
+/// Matches `text` against `pattern`, where wildcards match any run of elements and other
+/// elements match one element each. Only the last wildcard is backtracked to, which is
+/// enough since it can absorb anything an earlier one could, so the matching is quadratic
+/// at worst instead of exponential.
+fn wildcard_match<P, T>(
+    pattern: &[P],
+    text: &[T],
+    is_wildcard: impl Fn(&P) -> bool,
+    matches: impl Fn(&P, &T) -> bool,
+) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // the last wildcard and the text position it was tried at, to backtrack to
+    let mut wildcard = None;
+
+    while t < text.len() {
+        if p < pattern.len() && is_wildcard(&pattern[p]) {
+            wildcard = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && matches(&pattern[p], &text[t]) {
+            p += 1;
+            t += 1;
+        } else if let Some((wildcard_p, wildcard_t)) = wildcard {
+            p = wildcard_p + 1;
+            t = wildcard_t + 1;
+            wildcard = Some((wildcard_p, wildcard_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(is_wildcard)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_patterns() {
+        let glob = Glob::new("Models/Props_Junk/*.mdl");
+        assert_eq!(glob.base_dir(), "models/props_junk");
+        assert!(glob.matches("models/props_junk/wood_crate001a.mdl"));
+        assert!(!glob.matches("models/props_junk/wood_crate001a.vvd"));
+        assert!(!glob.matches("models/props_junk/gibs/wood_gib01a.mdl"));
+        assert!(!glob.can_match_in("models/props_junk/gibs"));
+
+        let glob = Glob::new("models/**/crate??.mdl");
+        assert_eq!(glob.base_dir(), "models");
+        assert!(glob.matches("models/crate01.mdl"));
+        assert!(glob.matches("models/props/junk/crate02.mdl"));
+        assert!(!glob.matches("models/props/crate1.mdl"));
+        assert!(glob.can_match_in("models/props/junk"));
+        assert!(Glob::new("models/**").can_match_in("models/props"));
+
+        let glob = Glob::new("materials/*/brickwall001.vmt");
+        assert_eq!(glob.base_dir(), "materials");
+        assert!(glob.can_match_in("materials/brick"));
+        assert!(!glob.can_match_in("materials/brick/old"));
+    }
+
+    #[test]
+    fn match_pathological_patterns() {
+        let name = "a".repeat(64);
+
+        assert!(!Glob::new(&format!("{}b", "a*".repeat(32))).matches(&name));
+        assert!(Glob::new(&"a*".repeat(32)).matches(&name));
+        assert!(!Glob::new(&format!("{}x", "**/".repeat(32))).matches(&"a/".repeat(64)));
+        assert!(Glob::new("a/**/**/b/**/c").matches("a/x/b/y/z/c"));
+    }
+}
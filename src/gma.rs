@@ -0,0 +1,242 @@
+//! Reads Garry's Mod addon archives (`.gma`), which workshop items are downloaded as.
+//! The archives are extracted into a directory to be mounted, since the file system
+//! only supports directories and vpks.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+};
+
+use crate::game_path;
+
+const MAGIC: &[u8; 4] = b"GMAD";
+const MAX_VERSION: u8 = 3;
+
+#[derive(Debug)]
+pub enum GmaError {
+    InvalidHeader,
+    UnsupportedVersion(u8),
+    Io(io::Error),
+}
+
+impl Display for GmaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            GmaError::InvalidHeader => f.write_str("invalid gma header"),
+            GmaError::UnsupportedVersion(v) => write!(f, "unsupported gma version {v}"),
+            GmaError::Io(e) => write!(f, "io error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for GmaError {}
+
+impl From<io::Error> for GmaError {
+    fn from(e: io::Error) -> Self {
+        GmaError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GmaEntry {
+    /// Path of the file in the game file system, like `models/props/crate.mdl`.
+    pub path: String,
+    pub size: u64,
+}
+
+/// An archive whose header has been read. The file data follows in the entry order.
+pub struct Gma<R> {
+    pub name: String,
+    pub entries: Vec<GmaEntry>,
+    reader: R,
+}
+
+impl<R: BufRead> Gma<R> {
+    /// Reads the header and the file index of an archive.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the header is invalid or reading fails.
+    pub fn read(mut reader: R) -> Result<Self, GmaError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+
+        if &magic != MAGIC {
+            return Err(GmaError::InvalidHeader);
+        }
+
+        let version = read_u8(&mut reader)?;
+        if version > MAX_VERSION {
+            return Err(GmaError::UnsupportedVersion(version));
+        }
+
+        // steam id and timestamp
+        read_u64(&mut reader)?;
+        read_u64(&mut reader)?;
+
+        if version > 1 {
+            // required content, terminated by an empty string
+            while !read_string(&mut reader)?.is_empty() {}
+        }
+
+        let name = read_string(&mut reader)?;
+        // description and author
+        read_string(&mut reader)?;
+        read_string(&mut reader)?;
+        // addon version
+        read_u32(&mut reader)?;
+
+        let mut entries = Vec::new();
+
+        while read_u32(&mut reader)? != 0 {
+            let path = game_path::normalize(&read_string(&mut reader)?);
+            let size = read_u64(&mut reader)?;
+            // crc
+            read_u32(&mut reader)?;
+
+            entries.push(GmaEntry { path, size });
+        }
+
+        Ok(Self {
+            name,
+            entries,
+            reader,
+        })
+    }
+
+    /// Extracts the files into `target_dir`. Files with paths outside of the directory
+    /// are skipped. Returns the number of extracted files.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if reading the archive or writing the files fails.
+    pub fn extract(mut self, target_dir: &Path) -> Result<usize, GmaError> {
+        let mut extracted = 0;
+
+        for entry in &self.entries {
+            let mut data = (&mut self.reader).take(entry.size);
+
+            if !game_path::is_safe_to_extract(&entry.path) {
+                io::copy(&mut data, &mut io::sink())?;
+                continue;
+            }
+
+            let target = target_dir.join(&entry.path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            let copied = io::copy(&mut data, &mut File::create(target)?)?;
+            if copied != entry.size {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+
+            extracted += 1;
+        }
+
+        Ok(extracted)
+    }
+}
+
+/// Opens an archive from the os file system.
+///
+/// # Errors
+///
+/// Returns `Err` if the file can't be opened or its header is invalid.
+pub fn open(path: &Path) -> Result<Gma<BufReader<File>>, GmaError> {
+    Gma::read(BufReader::new(File::open(path)?))
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_string(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut buf = Vec::new();
+    reader.read_until(0, &mut buf)?;
+
+    if buf.pop() != Some(0) {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(MAGIC);
+        data.push(3);
+        data.extend_from_slice(&[0; 16]);
+        data.extend_from_slice(b"\0Test Addon\0description\0author\0");
+        data.extend_from_slice(&1_u32.to_le_bytes());
+
+        for (i, (path, contents)) in files.iter().enumerate() {
+            data.extend_from_slice(&(u32::try_from(i).unwrap() + 1).to_le_bytes());
+            data.extend_from_slice(path.as_bytes());
+            data.push(0);
+            data.extend_from_slice(&(contents.len() as u64).to_le_bytes());
+            data.extend_from_slice(&0_u32.to_le_bytes());
+        }
+
+        data.extend_from_slice(&0_u32.to_le_bytes());
+
+        for (_, contents) in files {
+            data.extend_from_slice(contents);
+        }
+
+        data
+    }
+
+    #[test]
+    fn extract_archive() {
+        let data = archive(&[
+            ("Models/Crate.mdl", b"IDST"),
+            ("../escape.txt", b"no"),
+            ("materials/crate.vmt", b"VertexLitGeneric {}"),
+        ]);
+
+        let gma = Gma::read(data.as_slice()).unwrap();
+        assert_eq!(gma.name, "Test Addon");
+        assert_eq!(gma.entries[0].path, "models/crate.mdl");
+
+        let dir = std::env::temp_dir().join("plumber_gma_test");
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(gma.extract(&dir).unwrap(), 2);
+        assert_eq!(fs::read(dir.join("models/crate.mdl")).unwrap(), b"IDST");
+        assert_eq!(
+            fs::read(dir.join("materials/crate.vmt")).unwrap(),
+            b"VertexLitGeneric {}"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reject_invalid_header() {
+        assert!(matches!(
+            Gma::read(&b"VPK\0"[..]),
+            Err(GmaError::InvalidHeader)
+        ));
+    }
+}
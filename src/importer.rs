@@ -1,43 +1,117 @@
 use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    hash::{Hash, Hasher},
+    io::{self, Read, Write},
+    mem,
     path::{Path as StdPath, PathBuf as StdPathBuf},
     str::FromStr,
-    time::Instant,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
-use crossbeam_channel::Receiver;
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
 use pyo3::{
-    exceptions::{PyIOError, PyRuntimeError, PyTypeError},
+    exceptions::{PyIOError, PyRuntimeError, PyTypeError, PyValueError},
     prelude::*,
     types::PyDict,
 };
-use tracing::{debug, debug_span, error, info};
+use tracing::{debug, debug_span, error, info, warn};
 
 use plumber_core::{
     asset_core::Executor,
     asset_mdl::MdlConfig,
     asset_vmf::{BrushSetting, VmfConfig},
     asset_vtf::VtfConfig,
-    fs::{GamePathBuf, OpenFileSystem, OpenSearchPath, PathBuf},
+    fs::{GamePathBuf, OpenFileSystem, OpenSearchPath, PathBuf, SearchPath},
     vmf::{
         builder::{GeometrySettings, InvisibleSolids, MergeSolids},
-        vmf::Vmf,
+        vmf::{Entity, Vmf},
     },
 };
 
 use crate::{
     asset::{
-        material::{MaterialConfig, TextureFormat, TextureInterpolation},
+        choreo::PyChoreoScene,
+        cubemap::PyCubemap,
+        detail::{PyDetailScatter, PyDetailSprites},
+        entities::PyLoadedProp,
+        errors::{ErrorLog, PyAssetError},
+        goldsrc::{self, GoldSrcModel},
+        material::{
+            self, build_vmat_material, BaseAlpha, HeightMaps, Material, MaterialBatchConfig,
+            MaterialConfig, TargetVersion, Texture, TextureFormat, TextureInterpolation,
+        },
+        material_filter::MaterialFilter,
+        material_requests::MaterialRequests,
+        model::PyModel,
+        overlay::PyBuiltOverlay,
+        sky::SkyProjection,
+        sound::SentSounds,
+        texture_usage::TextureUsage,
+        track::PyTrackPath,
+        vis_cluster::PyVisCluster,
         BlenderAssetHandler, HandlerSettings, Message,
     },
-    filesystem::PyFileSystem,
+    brush_solidity::BrushSolidities,
+    bsp, choreo,
+    connections::{self, EntityConnections},
+    crash, decals,
+    detail_scatter::{self, DetailScatter, DetailTypes},
+    disp_neighbors::DispNeighbors,
+    dmx::{self, Dmx},
+    entity_report,
+    fgd::Fgd,
+    filesystem::{self, PyFileSystem},
+    game_path,
+    gameplay::GameplayMarker,
+    keyvalues::{self, KeyValue},
+    lighting_origins::LightingOrigins,
+    lightmap_uvs::LightmapSides,
+    lookups::{self, LookupLog, PathLookup},
+    ragdoll_groups::RagdollGroups,
+    scan::{self, ScanManifest},
+    skybox::SkyboxArea,
+    smoothing_groups::SmoothingGroups,
+    source2::{
+        vmat::{TextureRole, Vmat},
+        vtex,
+    },
+    track_paths, vmf_repair, vtf_header,
+    wad::{self, WadTexture},
+    watchdog::{self, Watch},
 };
 
+const DEFAULT_ASSET_TIMEOUT: Duration = Duration::from_secs(60);
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(1);
+
 #[pyclass(module = "plumber", name = "Importer")]
 pub struct PyImporter {
     material_config: MaterialConfig,
+    handler_settings: HandlerSettings,
+    threads: usize,
     executor: Option<Executor<BlenderAssetHandler>>,
     receiver: Receiver<Message>,
     callback_obj: PyObject,
+    asset_timeout: Duration,
+    target_fps: f32,
+    resample_animations: bool,
+    checkpoint: Option<File>,
+    skip_assets: HashSet<String>,
+    smoothing_groups: SmoothingGroups,
+    brush_solidities: BrushSolidities,
+    disp_neighbors: DispNeighbors,
+    lightmap_sides: Option<LightmapSides>,
+    entity_connections: HashMap<i32, EntityConnections>,
+    skybox_area: Option<SkyboxArea>,
+    ragdoll_groups: RagdollGroups,
+    lighting_origins: LightingOrigins,
+    errors: ErrorLog,
+    texture_usage: TextureUsage,
+    lookups: LookupLog,
+    material_requests: MaterialRequests,
+    file_system: OpenFileSystem,
+    search_paths: Vec<SearchPath>,
 }
 
 #[pymethods]
@@ -56,8 +130,27 @@ impl PyImporter {
             file_system.file_system.name
         );
 
-        let mut opened = file_system
-            .file_system
+        let mut merged = file_system.file_system.clone();
+
+        // assets of other games are searched after the main game, in the given order
+        if let Some(fallbacks) = kwargs
+            .and_then(|kwargs| kwargs.get_item("fallback_file_systems"))
+            .filter(|fallbacks| !fallbacks.is_none())
+        {
+            for fallback in fallbacks.extract::<Vec<PyRef<PyFileSystem>>>()? {
+                info!(
+                    "using game `{}` as a fallback file system",
+                    fallback.file_system.name
+                );
+                filesystem::merge_search_paths(&mut merged, fallback.file_system.clone());
+            }
+        }
+
+        // the map specific search paths added below are part of the scope as well
+        let mut material_cache_scope = DefaultHasher::new();
+        filesystem::hash_search_paths(&merged, &mut material_cache_scope);
+
+        let mut opened = merged
             .open()
             .map_err(|e| PyIOError::new_err(e.to_string()))?;
 
@@ -67,6 +160,11 @@ impl PyImporter {
         );
 
         let mut settings = HandlerSettings::default();
+        let mut asset_timeout = DEFAULT_ASSET_TIMEOUT;
+        let mut checkpoint = None;
+        let mut skip_assets = HashSet::new();
+        let mut include_materials = Vec::new();
+        let mut exclude_materials = Vec::new();
 
         if let Some(kwargs) = kwargs {
             for (key, value) in kwargs {
@@ -81,24 +179,77 @@ impl PyImporter {
                     "sun_factor" => settings.light.sun_factor = value.extract()?,
                     "ambient_factor" => settings.light.ambient_factor = value.extract()?,
                     "import_sky_camera" => settings.import_sky_camera = value.extract()?,
+                    "import_weather" => settings.import_weather = value.extract()?,
+                    "import_sprites" => settings.import_sprites = value.extract()?,
+                    "import_sounds" => settings.import_sounds = value.extract()?,
                     "sky_equi_height" => settings.sky_equi_height = value.extract()?,
-                    "scale" => settings.scale = value.extract()?,
+                    "sky_projection" => {
+                        settings.sky_projection = SkyProjection::from_str(value.extract()?)?;
+                    }
+                    "scale" => {
+                        settings.scale = value.extract()?;
+                        settings.material.scale = settings.scale;
+                    }
                     "target_fps" => settings.target_fps = value.extract()?,
+                    "resample_animations" => settings.resample_animations = value.extract()?,
                     "remove_animations" => settings.remove_animations = value.extract()?,
+                    "asset_timeout" => {
+                        asset_timeout = Duration::try_from_secs_f32(value.extract()?)
+                            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+                    }
+                    "import_physics" => settings.import_physics = value.extract()?,
+                    "import_attachments" => settings.import_attachments = value.extract()?,
+                    "import_hitboxes" => settings.import_hitboxes = value.extract()?,
                     "simple_materials" => settings.material.simple_materials = value.extract()?,
                     "allow_culling" => settings.material.allow_culling = value.extract()?,
                     "editor_materials" => settings.material.editor_materials = value.extract()?,
+                    "emission_strength" => {
+                        settings.material.emission_strength = value.extract()?;
+                    }
                     "texture_format" => {
                         settings.material.texture_format =
                             TextureFormat::from_str(value.extract()?)?;
                     }
+                    "texture_max_resolution" => {
+                        settings.material.texture_max_resolution = value.extract()?;
+                    }
+                    "texture_brightness" => {
+                        settings.material.texture_color_correction.brightness = value.extract()?;
+                    }
+                    "texture_gamma" => {
+                        settings.material.texture_color_correction.gamma = value.extract()?;
+                    }
+                    "height_maps" => {
+                        settings.material.height_maps = HeightMaps::from_str(value.extract()?)?;
+                    }
+                    "ssbump_height" => {
+                        settings.material.ssbump_height = value.extract()?;
+                    }
+                    "base_alpha" => {
+                        settings.material.base_alpha = BaseAlpha::from_str(value.extract()?)?;
+                    }
                     "texture_interpolation" => {
                         settings.material.texture_interpolation =
                             TextureInterpolation::from_str(value.extract()?)?;
                     }
+                    "target_version" => {
+                        settings.material.target_version =
+                            TargetVersion::from_blender_version(value.extract()?);
+                    }
                     "import_unknown_entities" => {
                         settings.import_unknown_entities = value.extract()?;
                     }
+                    // merged before opening the file system
+                    "fallback_file_systems" => {}
+                    "include_materials" => include_materials = value.extract()?,
+                    "exclude_materials" => exclude_materials = value.extract()?,
+                    "fgd_path" => {
+                        let fgd_path: &str = value.extract()?;
+                        let fgd = Fgd::from_file(StdPath::new(fgd_path))
+                            .map_err(|e| PyIOError::new_err(e.to_string()))?;
+
+                        settings.fgd = Some(Arc::new(fgd));
+                    }
                     "vmf_path" => {
                         // Map data path is detected here since when opening a vmf
                         // from game files, it needs to be determined after
@@ -107,10 +258,12 @@ impl PyImporter {
                         // to the importer.
 
                         let file_path_string: &str = value.extract()?;
+                        file_path_string.hash(&mut material_cache_scope);
                         detect_embedded_files_path(file_path_string, &mut opened);
                     }
                     "map_data_path" => {
                         let map_data_path: &str = value.extract()?;
+                        map_data_path.hash(&mut material_cache_scope);
                         let map_data_path = StdPathBuf::from(map_data_path);
 
                         info!(
@@ -120,14 +273,31 @@ impl PyImporter {
 
                         opened.add_open_search_path(OpenSearchPath::Directory(map_data_path));
                     }
+                    "checkpoint_path" => {
+                        let checkpoint_path: &str = value.extract()?;
+
+                        checkpoint = Some(
+                            OpenOptions::new()
+                                .create(true)
+                                .append(true)
+                                .open(checkpoint_path)
+                                .map_err(|e| PyIOError::new_err(e.to_string()))?,
+                        );
+                    }
+                    "skip_assets" => {
+                        let entries: Vec<String> = value.extract()?;
+                        skip_assets = entries.into_iter().collect();
+                    }
                     "root_search" => {
                         // If an asset was imported from the os file system, tries to detect
                         // if the directory structure matches a typical Source game asset directory structure
                         // to use the root of the directory structure as an additional search path.
 
                         let (asset_path, target_path): (&str, &str) = value.extract()?;
+                        asset_path.hash(&mut material_cache_scope);
 
-                        if let Some(search_path) = detect_local_search_path(asset_path, target_path)
+                        if let Some(search_path) =
+                            filesystem::detect_asset_root(asset_path, target_path)
                         {
                             info!(
                                 "detected local asset searh path `{}`",
@@ -146,24 +316,57 @@ impl PyImporter {
             }
         }
 
+        settings.material_filter = MaterialFilter::new(include_materials, exclude_materials);
+        material::set_cache_scope(material_cache_scope.finish());
+
+        // the fgd is left out, since it would make the report huge
+        crash::set_import_context(format!(
+            "game: {}\nsettings: {:#?}",
+            file_system.file_system.name,
+            HandlerSettings {
+                fgd: None,
+                ..settings.clone()
+            }
+        ));
+
         let material_config = MaterialConfig {
             settings: settings.material,
         };
+        let (target_fps, resample_animations) = (settings.target_fps, settings.resample_animations);
 
         let (sender, receiver) = crossbeam_channel::bounded(256);
-        let handler = BlenderAssetHandler { sender, settings };
-        let executor = Some(Executor::new_with_threads(
-            handler,
-            opened,
-            threads_suggestion,
-        ));
 
-        Ok(Self {
+        let mut importer = Self {
             material_config,
-            executor,
+            handler_settings: settings,
+            threads: threads_suggestion,
+            executor: None,
             receiver,
             callback_obj,
-        })
+            asset_timeout,
+            target_fps,
+            resample_animations,
+            checkpoint,
+            skip_assets,
+            smoothing_groups: SmoothingGroups::default(),
+            brush_solidities: BrushSolidities::default(),
+            disp_neighbors: DispNeighbors::default(),
+            lightmap_sides: None,
+            entity_connections: HashMap::new(),
+            skybox_area: None,
+            ragdoll_groups: RagdollGroups::default(),
+            lighting_origins: LightingOrigins::default(),
+            errors: ErrorLog::default(),
+            texture_usage: TextureUsage::default(),
+            lookups: LookupLog::default(),
+            material_requests: MaterialRequests::default(),
+            file_system: opened,
+            search_paths: merged.search_paths,
+        };
+
+        importer.executor = Some(importer.new_executor(sender));
+
+        Ok(importer)
     }
 
     #[args(path, from_game, kwargs = "**")]
@@ -177,6 +380,16 @@ impl PyImporter {
         let executor = self.consume()?;
 
         let mut import_brushes = true;
+        let mut import_track_paths = false;
+        let mut lightmap_uvs = false;
+        let mut stitch_displacements = false;
+        let mut import_decals = false;
+        let mut assemble_skybox = false;
+        let mut merge_ragdolls = false;
+        let mut import_cubemaps = false;
+        let mut import_vis_clusters = false;
+        let mut import_detail_props = false;
+        let mut gameplay_markers = false;
         let mut geometry_settings = GeometrySettings::default();
 
         let mut settings = VmfConfig::new(self.material_config);
@@ -215,6 +428,36 @@ impl PyImporter {
                     "import_sky" => {
                         settings.import_skybox = value.extract()?;
                     }
+                    "import_track_paths" => {
+                        import_track_paths = value.extract()?;
+                    }
+                    "lightmap_uvs" => {
+                        lightmap_uvs = value.extract()?;
+                    }
+                    "stitch_displacements" => {
+                        stitch_displacements = value.extract()?;
+                    }
+                    "import_decals" => {
+                        import_decals = value.extract()?;
+                    }
+                    "assemble_skybox" => {
+                        assemble_skybox = value.extract()?;
+                    }
+                    "merge_ragdolls" => {
+                        merge_ragdolls = value.extract()?;
+                    }
+                    "import_cubemaps" => {
+                        import_cubemaps = value.extract()?;
+                    }
+                    "import_vis_clusters" => {
+                        import_vis_clusters = value.extract()?;
+                    }
+                    "import_detail_props" => {
+                        import_detail_props = value.extract()?;
+                    }
+                    "gameplay_markers" => {
+                        gameplay_markers = value.extract()?;
+                    }
                     "scale" => {
                         settings.scale = value.extract()?;
                     }
@@ -232,22 +475,330 @@ impl PyImporter {
         let start = Instant::now();
         info!("importing vmf `{}`...", path);
 
+        let is_bsp = path.to_ascii_lowercase().ends_with(".bsp");
+
         let path: PathBuf = if from_game {
-            GamePathBuf::from(path).into()
+            game_path::game_path(path).into()
         } else {
             StdPathBuf::from(path).into()
         };
 
-        let bytes = executor.fs().read(&path)?;
-        let vmf = Vmf::from_bytes(&bytes).map_err(|e| PyIOError::new_err(e.to_string()))?;
+        let mut bytes = executor.fs().read(&path)?;
+        let mut detail_sprites = None;
+        let mut cubemaps = Vec::new();
+        let mut vis_clusters = Vec::new();
+
+        // compiled maps are imported through a vmf reconstructed from their brushes
+        if is_bsp {
+            if import_cubemaps {
+                // the cubemap textures are converted here, since the bsp isn't kept
+                match bsp::Bsp::parse(&bytes).and_then(|b| bsp::cubemaps::cubemaps(&b)) {
+                    Ok(samples) => {
+                        cubemaps = samples
+                            .iter()
+                            .enumerate()
+                            .map(|(i, c)| PyCubemap::new(i, c, settings.scale, None))
+                            .collect();
+                    }
+                    Err(error) => warn!("bsp `{path}`: cubemaps could not be read: {error}"),
+                }
+            }
+
+            if import_vis_clusters {
+                match bsp::Bsp::parse(&bytes).and_then(|b| bsp::vis::vis_clusters(&b)) {
+                    Ok(clusters) => {
+                        vis_clusters = clusters
+                            .into_iter()
+                            .map(|c| PyVisCluster::new(c, settings.scale))
+                            .collect();
+                    }
+                    Err(error) => warn!("bsp `{path}`: vis clusters could not be read: {error}"),
+                }
+            }
+
+            let decompiled =
+                bsp::decompile::decompile(&bytes).map_err(|e| PyIOError::new_err(e.to_string()))?;
+
+            for warning in &decompiled.warnings {
+                warn!("bsp `{path}`: {warning}");
+            }
+
+            if settings.import_props {
+                detail_sprites = decompiled.detail_sprites.map(|s| {
+                    self.material_requests.request(&s.material);
+                    PyDetailSprites::new(s, settings.scale)
+                });
+            }
+
+            bytes = decompiled.text.into_bytes();
+        }
+
+        let repaired;
+        let mut vmf = match Vmf::from_bytes(&bytes) {
+            Ok(vmf) => vmf,
+            Err(error) => {
+                // decompiled and very old vmfs are often slightly malformed
+                warn!("vmf `{path}` could not be parsed ({error}), trying to repair it...");
+
+                repaired = vmf_repair::repair(&String::from_utf8_lossy(&bytes))
+                    .map_err(|e| PyIOError::new_err(format!("{error} (repair failed: {e})")))?;
+
+                for warning in &repaired.warnings {
+                    warn!("vmf `{path}`: {warning}");
+                }
+
+                Vmf::from_bytes(repaired.text.as_bytes())
+                    .map_err(|e| PyIOError::new_err(e.to_string()))?
+            }
+        };
+
+        let mut track_paths = Vec::new();
+        let mut map_decals = Vec::new();
+        let mut detail_scatter = DetailScatter::default();
+        self.smoothing_groups = SmoothingGroups::default();
+        self.brush_solidities = BrushSolidities::default();
+        self.disp_neighbors = DispNeighbors::default();
+        self.entity_connections.clear();
+        self.skybox_area = None;
+        self.ragdoll_groups = RagdollGroups::default();
+        self.lighting_origins = LightingOrigins::default();
+        // faces which can't be matched to a side are still laid out with the default axes
+        self.lightmap_sides = (import_brushes && lightmap_uvs).then(LightmapSides::default);
+
+        // the built geometry and entities don't keep everything, so some data is read
+        // from the vmf directly
+        let import_entities = settings.import_other_entities;
+
+        if import_track_paths
+            || import_brushes
+            || import_entities
+            || import_decals
+            || assemble_skybox
+            || merge_ragdolls
+            || import_detail_props
+        {
+            match keyvalues::parse_raw(&String::from_utf8_lossy(&bytes)) {
+                Ok(root) => {
+                    if import_track_paths {
+                        // trains only know their first track, so the whole map is needed
+                        track_paths = track_paths::track_paths(&root);
+                    }
+
+                    if import_brushes {
+                        self.smoothing_groups = SmoothingGroups::from_vmf(&root);
+                        self.brush_solidities = BrushSolidities::from_vmf(&root);
+                    }
+
+                    if import_brushes && stitch_displacements {
+                        self.disp_neighbors = DispNeighbors::from_vmf(&root);
+                    }
+
+                    if let Some(sides) = &mut self.lightmap_sides {
+                        *sides = LightmapSides::from_vmf(&root);
+                    }
+
+                    if import_entities {
+                        self.entity_connections = connections::vmf_connections(&root);
+                    }
+
+                    if import_decals {
+                        map_decals =
+                            decals::vmf_decals(&root, |texture| decal_size(executor.fs(), texture));
+
+                        for decal in &map_decals {
+                            self.material_requests.request(&decal.material);
+                        }
+                    }
+
+                    if merge_ragdolls {
+                        self.ragdoll_groups = RagdollGroups::from_vmf(&root);
+                    }
+
+                    if import_detail_props {
+                        detail_scatter = detail_scatter::vmf_detail_scatter(
+                            &root,
+                            |path| detail_types(executor.fs(), path),
+                            |material| material_detail_type(executor.fs(), material),
+                        );
+
+                        if detail_scatter
+                            .displacements
+                            .iter()
+                            .any(|d| !d.sprites.is_empty())
+                        {
+                            self.material_requests
+                                .request(&bsp::detail_props::material_path(
+                                    &detail_scatter.material,
+                                ));
+                        }
+                    }
+
+                    if assemble_skybox {
+                        self.skybox_area = SkyboxArea::from_vmf(&root, settings.scale);
+
+                        if self.skybox_area.is_none() {
+                            warn!("vmf `{path}` has no enclosed 3d skybox to assemble");
+                        }
+                    }
+                }
+                Err(error) => warn!("vmf `{path}` could not be read for extra data: {error}"),
+            }
+        }
+
+        let scale = settings.scale;
+
+        // GoldSrc models aren't supported by the asset pipeline, so their props are
+        // imported here
+        let goldsrc_props = if settings.import_props {
+            self.lighting_origins = LightingOrigins::new(
+                vmf.entities.iter().filter_map(|entity| {
+                    Some((
+                        entity_property(entity, "targetname")?,
+                        entity_property(entity, "origin")?,
+                    ))
+                }),
+                settings.scale,
+            );
+
+            self.take_goldsrc_props(&mut vmf)
+        } else {
+            Vec::new()
+        };
+
+        // bomb and hostage zones are tool textured, so their brushes are imported separately
+        // for the markers, since invisible solids may be skipped
+        let gameplay_vmf = (gameplay_markers && import_brushes)
+            .then(|| take_gameplay_brushes(&mut vmf))
+            .flatten();
+
+        crash::catch_panic(|| executor.process(settings, vmf, || self.process_assets(py)))?;
+
+        if let Some(gameplay_vmf) = gameplay_vmf {
+            self.import_gameplay_brushes(py, gameplay_vmf, geometry_settings, scale)?;
+        }
+
+        self.import_goldsrc_props(py, goldsrc_props, scale);
+        self.import_requested_materials(py)?;
 
-        executor.process(settings, vmf, || self.process_assets(py));
+        if let Some(sprites) = detail_sprites {
+            let callback_ref = self.callback_obj.as_ref(py);
+
+            if let Err(err) = callback_ref.call_method1("detail_sprites", (sprites,)) {
+                err.print(py);
+                error!("Detail sprite importing errored: {}", err);
+            }
+        }
+
+        if !detail_scatter.displacements.is_empty() {
+            let callback_ref = self.callback_obj.as_ref(py);
+            let scatter = PyDetailScatter::new(detail_scatter, scale);
+
+            if let Err(err) = callback_ref.call_method1("detail_scatter", (scatter,)) {
+                err.print(py);
+                error!("Detail prop importing errored: {}", err);
+            }
+        }
+
+        for cubemap in cubemaps {
+            let callback_ref = self.callback_obj.as_ref(py);
+
+            if let Err(err) = callback_ref.call_method1("cubemap", (cubemap,)) {
+                err.print(py);
+                error!("Cubemap importing errored: {}", err);
+            }
+        }
+
+        for vis_cluster in vis_clusters {
+            let callback_ref = self.callback_obj.as_ref(py);
+
+            if let Err(err) = callback_ref.call_method1("vis_cluster", (vis_cluster,)) {
+                err.print(py);
+                error!("Vis cluster importing errored: {}", err);
+            }
+        }
+
+        for track_path in track_paths {
+            let callback_ref = self.callback_obj.as_ref(py);
+            let track_path = PyTrackPath::new(track_path, scale);
+
+            if let Err(err) = callback_ref.call_method1("track_path", (track_path,)) {
+                err.print(py);
+                error!("Track path importing errored: {}", err);
+            }
+        }
+
+        for decal in map_decals {
+            let callback_ref = self.callback_obj.as_ref(py);
+            let overlay = PyBuiltOverlay::from_decal(decal, scale);
+
+            if let Err(err) = callback_ref.call_method1("overlay", (overlay,)) {
+                err.print(py);
+                error!("Decal importing errored: {}", err);
+            }
+        }
 
         info!("vmf imported in {:.2} s", start.elapsed().as_secs_f32());
 
         Ok(())
     }
 
+    /// Lists the materials, textures, models and skybox of the vmf, and which of them
+    /// are missing from the file system, without importing anything.
+    fn scan_vmf(&self, path: &str, from_game: bool) -> PyResult<ScanManifest> {
+        let executor = self
+            .executor
+            .as_ref()
+            .ok_or_else(|| PyRuntimeError::new_err("Importer already consumed"))?;
+
+        let start = Instant::now();
+        info!("scanning vmf `{}`...", path);
+
+        let root = self.read_vmf_root(path, from_game)?;
+        let manifest = scan::scan(&root, executor.fs());
+
+        info!("vmf scanned in {:.2} s", start.elapsed().as_secs_f32());
+
+        Ok(manifest)
+    }
+
+    /// Lists the entities of the vmf with their keyvalues, as `csv` or `json`, without
+    /// importing anything. The CSV has a column for each of `keys`, or for every keyvalue
+    /// if they aren't given.
+    #[args(path, from_game, format = "\"csv\"", keys = "None")]
+    fn entity_report(
+        &self,
+        path: &str,
+        from_game: bool,
+        format: &str,
+        keys: Option<Vec<String>>,
+    ) -> PyResult<String> {
+        let root = self.read_vmf_root(path, from_game)?;
+        let entities = entity_report::entities(&root);
+
+        match format {
+            "csv" => Ok(entity_report::to_csv(&entities, keys.as_deref())),
+            "json" => Ok(entity_report::to_json(&entities)),
+            _ => Err(PyValueError::new_err(format!(
+                "invalid entity report format `{format}`"
+            ))),
+        }
+    }
+
+    /// Lists the displacements of the vmf whose edges touch, as the side id and edge
+    /// index of both displacements. Edges are numbered from the `startposition` corner.
+    fn displacement_neighbors(
+        &self,
+        path: &str,
+        from_game: bool,
+    ) -> PyResult<Vec<(i32, usize, i32, usize)>> {
+        let root = self.read_vmf_root(path, from_game)?;
+
+        Ok(DispNeighbors::from_vmf(&root)
+            .neighbors()
+            .map(|n| (n.side_id, n.edge, n.neighbor_side_id, n.neighbor_edge))
+            .collect())
+    }
+
     #[args(path, from_game, kwargs = "**")]
     fn import_mdl(
         &mut self,
@@ -256,10 +807,15 @@ impl PyImporter {
         from_game: bool,
         kwargs: Option<&PyDict>,
     ) -> PyResult<()> {
+        // GoldSrc models aren't supported by the asset pipeline, so they are read here
+        if self.is_goldsrc_mdl(path, from_game) {
+            return self.import_goldsrc_mdl(py, path, from_game, kwargs);
+        }
+
         let executor = self.consume()?;
 
         let path = if from_game {
-            GamePathBuf::from(path).into()
+            game_path::game_path(path).into()
         } else {
             StdPathBuf::from(path).into()
         };
@@ -269,20 +825,120 @@ impl PyImporter {
         let start = Instant::now();
         info!("importing mdl `{}`...", path);
 
-        executor
-            .depend_on(settings, path, || self.process_assets(py))
+        let _watch = Watch::new(format!("mdl `{path}`"));
+
+        crash::catch_panic(|| executor.depend_on(settings, path, || self.process_assets(py)))?
             .map_err(|e| PyIOError::new_err(e.to_string()))?;
+        self.import_requested_materials(py)?;
 
         info!("mdl imported in {:.2} s", start.elapsed().as_secs_f32());
 
         Ok(())
     }
 
+    /// Imports the events of a choreography scene (`.vcd`), so that its timing can be
+    /// reconstructed.
+    fn import_vcd(&mut self, py: Python, path: &str, from_game: bool) -> PyResult<()> {
+        self.consume()?;
+
+        let data = self.read_file(path, from_game)?;
+
+        let start = Instant::now();
+        info!("importing vcd `{path}`...");
+
+        let scene = choreo::parse(&String::from_utf8_lossy(&data))
+            .map_err(|e| PyIOError::new_err(format!("`{path}`: {e}")))?;
+
+        let name = StdPath::new(path)
+            .file_stem()
+            .map_or_else(|| path.to_owned(), |n| n.to_string_lossy().into_owned());
+
+        let scene = PyChoreoScene::new(name, scene);
+
+        if let Err(err) = self
+            .callback_obj
+            .as_ref(py)
+            .call_method1("choreo_scene", (scene,))
+        {
+            err.print(py);
+            error!("Asset importing errored: {}", err);
+        }
+
+        info!("vcd imported in {:.2} s", start.elapsed().as_secs_f32());
+
+        Ok(())
+    }
+
+    /// Imports the model and animations of a binary model dmx, like the model sources of
+    /// studiomdl and Source Filmmaker. Session files only refer to compiled models, so
+    /// the bones and the animations of each of their animation sets are imported.
+    #[args(path, from_game, import_animations = "true")]
+    fn import_dmx(
+        &mut self,
+        py: Python,
+        path: &str,
+        from_game: bool,
+        import_animations: bool,
+    ) -> PyResult<()> {
+        self.consume()?;
+
+        let data = self.read_file(path, from_game)?;
+
+        let start = Instant::now();
+        info!("importing dmx `{path}`...");
+
+        let models = Dmx::read(&data)
+            .and_then(|dmx| dmx::model::models(&dmx))
+            .map_err(|e| PyIOError::new_err(format!("`{path}`: {e}")))?;
+
+        let name = if from_game {
+            game_path::normalize(path)
+        } else {
+            StdPath::new(path)
+                .file_name()
+                .map_or_else(|| path.to_owned(), |n| n.to_string_lossy().to_lowercase())
+        };
+
+        for mut model in models {
+            if !import_animations {
+                model.animations.clear();
+            }
+
+            let name = model
+                .animation_set
+                .as_ref()
+                .map_or_else(|| name.clone(), |set| format!("{name}/{set}"));
+
+            let model = PyModel::from_dmx(
+                name,
+                &model,
+                &self.file_system,
+                self.target_fps,
+                self.resample_animations,
+            );
+
+            for material in model.resolved_materials() {
+                self.material_requests.request(material);
+            }
+
+            if let Err(err) = self.callback_obj.as_ref(py).call_method1("model", (model,)) {
+                err.print(py);
+                error!("Asset importing errored: {}", err);
+            }
+        }
+
+        self.import_requested_materials(py)?;
+
+        info!("dmx imported in {:.2} s", start.elapsed().as_secs_f32());
+
+        Ok(())
+    }
+
     fn import_vmt(&mut self, py: Python, path: &str, from_game: bool) -> PyResult<()> {
         let executor = self.consume()?;
 
         let path = if from_game {
-            GamePathBuf::from(path).into()
+            game_path::game_path(path).into()
         } else {
             StdPathBuf::from(path).into()
         };
@@ -290,9 +946,10 @@ impl PyImporter {
         let start = Instant::now();
         info!("importing vmt `{}`...", path);
 
-        executor
-            .depend_on(self.material_config, path, || self.process_assets(py))
-            .map_err(|e| PyIOError::new_err(e.to_string()))?;
+        crash::catch_panic(|| {
+            executor.depend_on(self.material_config, path, || self.process_assets(py))
+        })?
+        .map_err(|e| PyIOError::new_err(e.to_string()))?;
 
         info!("vmt imported in {:.2} s", start.elapsed().as_secs_f32());
 
@@ -303,7 +960,7 @@ impl PyImporter {
         let executor = self.consume()?;
 
         let path = if from_game {
-            GamePathBuf::from(path).into()
+            game_path::game_path(path).into()
         } else {
             StdPathBuf::from(path).into()
         };
@@ -311,45 +968,570 @@ impl PyImporter {
         let start = Instant::now();
         info!("importing vtf `{}`...", path);
 
-        executor.process(VtfConfig, path, || self.process_assets(py));
+        let _watch = Watch::new(format!("vtf `{path}`"));
+
+        crash::catch_panic(|| executor.process(VtfConfig, path, || self.process_assets(py)))?;
 
         info!("vtf imported in {:.2} s", start.elapsed().as_secs_f32());
 
         Ok(())
     }
 
-    fn import_assets(&mut self, py: Python) {
+    /// Imports the textures of a GoldSrc texture archive (`.wad`), named like the maps
+    /// refer to them.
+    fn import_wad(&mut self, py: Python, path: &str, from_game: bool) -> PyResult<()> {
+        // the textures are decoded here, but the importer is consumed like on other imports
+        self.consume()?;
+
+        let data = self.read_file(path, from_game)?;
+
+        let start = Instant::now();
+        info!("importing wad `{path}`...");
+
+        let entries =
+            wad::entries(&data).map_err(|e| PyIOError::new_err(format!("`{path}`: {e}")))?;
+        let settings = &self.material_config.settings;
+        let callback_ref = self.callback_obj.as_ref(py);
+
+        for entry in entries.iter().filter(|entry| entry.is_texture()) {
+            let texture = match wad::read_texture(&data, entry) {
+                Ok(texture) => texture,
+                Err(error) => {
+                    error!("wad `{path}`: {error}");
+                    self.errors
+                        .push("texture", Some(entry.name.clone()), error.to_string());
+                    continue;
+                }
+            };
+
+            let texture = Texture::from_wad(
+                &texture,
+                settings.texture_format,
+                settings.texture_max_resolution,
+                settings.texture_color_correction.enabled(),
+            );
+
+            if let Err(err) = callback_ref.call_method1("texture", (texture,)) {
+                err.print(py);
+                error!("Asset importing errored: {}", err);
+            }
+        }
+
+        info!("wad imported in {:.2} s", start.elapsed().as_secs_f32());
+
+        Ok(())
+    }
+
+    /// Imports a Source 2 material source (`.vmat`) or compiled material (`.vmat_c`)
+    /// with its textures, which may be source images or compiled textures.
+    fn import_vmat(&mut self, py: Python, path: &str, from_game: bool) -> PyResult<()> {
+        self.consume()?;
+
+        let data = self.read_file(path, from_game)?;
+
+        let start = Instant::now();
+        info!("importing vmat `{path}`...");
+
+        let compiled = path.to_ascii_lowercase().ends_with(".vmat_c");
+        let source_path = if compiled {
+            &path[..path.len() - 2]
+        } else {
+            path
+        };
+
+        let vmat = if compiled {
+            Vmat::read_compiled(&data)
+        } else {
+            Vmat::parse(&String::from_utf8_lossy(&data))
+        }
+        .map_err(|e| PyIOError::new_err(format!("`{path}`: {e}")))?;
+        let settings = self.material_config.settings;
+        let callback_ref = self.callback_obj.as_ref(py);
+
+        let mut loaded = BTreeMap::new();
+
+        for role in TextureRole::ALL {
+            let Some(texture_path) = vmat.texture(role) else {
+                continue;
+            };
+
+            let name = source2_texture_name(texture_path);
+            let color_correction = settings
+                .texture_color_correction
+                .enabled()
+                .filter(|_| role == TextureRole::Color);
+
+            let texture = self
+                .read_source2_texture(texture_path, path, from_game)
+                .and_then(|data| {
+                    if texture_path.to_ascii_lowercase().ends_with(".vtex") {
+                        let texture = vtex::read(&data).map_err(|e| e.to_string())?;
+                        Texture::from_vtex(
+                            name.to_string(),
+                            &texture,
+                            settings.texture_format,
+                            settings.texture_max_resolution,
+                            color_correction,
+                        )
+                        .ok_or_else(|| "unsupported texture data".to_owned())
+                    } else {
+                        Texture::from_source_image(
+                            name.to_string(),
+                            &data,
+                            settings.texture_format,
+                            settings.texture_max_resolution,
+                            color_correction,
+                        )
+                        .ok_or_else(|| "unsupported image".to_owned())
+                    }
+                });
+
+            match texture {
+                Ok(texture) => {
+                    if let Err(err) = callback_ref.call_method1("texture", (texture,)) {
+                        err.print(py);
+                        error!("Asset importing errored: {}", err);
+                    }
+
+                    loaded.insert(role, name);
+                }
+                Err(error) => {
+                    error!("vmat `{path}`: texture `{texture_path}`: {error}");
+                    self.errors
+                        .push("texture", Some(texture_path.to_owned()), error);
+                }
+            }
+        }
+
+        let data = build_vmat_material(&vmat, settings, |role| loaded.get(&role).cloned());
+
+        let name = if from_game {
+            game_path::material_name(source_path, ".vmat")
+        } else {
+            StdPath::new(source_path)
+                .file_stem()
+                .map_or_else(|| path.to_owned(), |s| s.to_string_lossy().into_owned())
+        };
+        let material = Material::new(
+            &game_path::game_path(&name).into(),
+            data,
+            settings.texture_format,
+        );
+
+        if let Err(err) = callback_ref.call_method1("material", (material,)) {
+            err.print(py);
+            error!("Asset importing errored: {}", err);
+        }
+
+        info!("vmat imported in {:.2} s", start.elapsed().as_secs_f32());
+
+        Ok(())
+    }
+
+    /// Imports a compiled Source 2 texture (`.vtex_c`).
+    fn import_vtex(&mut self, py: Python, path: &str, from_game: bool) -> PyResult<()> {
+        self.consume()?;
+
+        let data = self.read_file(path, from_game)?;
+
+        let start = Instant::now();
+        info!("importing vtex `{path}`...");
+
+        let texture =
+            vtex::read(&data).map_err(|e| PyIOError::new_err(format!("`{path}`: {e}")))?;
+        let settings = &self.material_config.settings;
+
+        let name = if from_game {
+            source2_texture_name(path).to_string()
+        } else {
+            source2_texture_name(
+                &StdPath::new(path)
+                    .file_name()
+                    .map_or_else(|| path.to_owned(), |s| s.to_string_lossy().into_owned()),
+            )
+            .to_string()
+        };
+
+        // like a standalone `.vtf`, it's unknown if the texture is a color texture
+        let texture = Texture::from_vtex(
+            name,
+            &texture,
+            settings.texture_format,
+            settings.texture_max_resolution,
+            None,
+        )
+        .ok_or_else(|| PyIOError::new_err(format!("`{path}`: unsupported texture data")))?;
+
+        if let Err(err) = self
+            .callback_obj
+            .as_ref(py)
+            .call_method1("texture", (texture,))
+        {
+            err.print(py);
+            error!("Asset importing errored: {}", err);
+        }
+
+        info!("vtex imported in {:.2} s", start.elapsed().as_secs_f32());
+
+        Ok(())
+    }
+
+    fn import_assets(&mut self, py: Python) -> PyResult<()> {
         // drop the importer, causing the asset channel to disconnect
         // if we don't do this, process_assets will hang forever waiting for new assets to be sent
         self.executor = None;
 
-        self.process_assets(py);
+        crash::catch_panic(|| self.process_assets(py))
+    }
+
+    /// Errors of the assets which couldn't be imported, in the order they happened.
+    fn errors(&self) -> Vec<PyAssetError> {
+        self.errors.to_vec()
+    }
+
+    /// Number of errors of each asset kind.
+    fn error_counts(&self) -> BTreeMap<&'static str, usize> {
+        self.errors.counts()
+    }
+
+    /// Textures which were converted, but aren't used by any imported material,
+    /// usually because the materials failed to build.
+    fn orphan_textures(&self) -> Vec<String> {
+        self.texture_usage.orphans()
+    }
+
+    /// Which search paths contain the files of the imported assets. Each search path is
+    /// opened again, so this can be slow with many vpks.
+    fn search_path_report(&self) -> PyResult<Vec<PathLookup>> {
+        lookups::report(&self.lookups, &self.search_paths, &self.file_system)
     }
 }
 
 impl PyImporter {
+    fn new_executor(&self, sender: Sender<Message>) -> Executor<BlenderAssetHandler> {
+        let handler = BlenderAssetHandler {
+            sender,
+            settings: self.handler_settings.clone(),
+            file_system: self.file_system.clone(),
+            model_material_index: Arc::default(),
+            sound_scripts: Arc::default(),
+            sent_sounds: SentSounds::default(),
+            errors: self.errors.clone(),
+            texture_usage: self.texture_usage.clone(),
+            lookups: self.lookups.clone(),
+            material_requests: self.material_requests.clone(),
+        };
+
+        Executor::new_with_threads(handler, self.file_system.clone(), self.threads)
+    }
+
     fn consume(&mut self) -> PyResult<Executor<BlenderAssetHandler>> {
         self.executor
             .take()
             .ok_or_else(|| PyRuntimeError::new_err("Importer already consumed"))
     }
 
+    /// Reads and parses a vmf, decompiling it first if it's a compiled map.
+    fn read_vmf_root(&self, path: &str, from_game: bool) -> PyResult<Vec<(String, KeyValue)>> {
+        let mut bytes = self.read_file(path, from_game)?;
+
+        if path.to_ascii_lowercase().ends_with(".bsp") {
+            bytes = bsp::decompile::decompile(&bytes)
+                .map_err(|e| PyIOError::new_err(e.to_string()))?
+                .text
+                .into_bytes();
+        }
+
+        keyvalues::parse_raw(&String::from_utf8_lossy(&bytes))
+            .map_err(|e| PyIOError::new_err(e.to_string()))
+    }
+
+    fn read_file(&self, path: &str, from_game: bool) -> PyResult<Vec<u8>> {
+        if from_game {
+            self.file_system
+                .read(&game_path::game_path(path).into())
+                .map_err(|e| PyIOError::new_err(format!("`{path}`: {e}")))
+        } else {
+            Ok(fs::read(path)?)
+        }
+    }
+
+    /// Reads the start of a file, like the header of a model, without reading the rest.
+    fn read_file_start(&self, path: &str, from_game: bool, len: usize) -> io::Result<Vec<u8>> {
+        let mut data = vec![0; len];
+
+        if from_game {
+            self.file_system
+                .open_file(&game_path::game_path(path))?
+                .read_exact(&mut data)?;
+        } else {
+            File::open(path)?.read_exact(&mut data)?;
+        }
+
+        Ok(data)
+    }
+
+    /// Whether a model is a GoldSrc model, which is detected from its header only.
+    fn is_goldsrc_mdl(&self, path: &str, from_game: bool) -> bool {
+        self.read_file_start(path, from_game, goldsrc::HEADER_LEN)
+            .is_ok_and(|header| goldsrc::is_goldsrc(&header))
+    }
+
+    /// Reads a GoldSrc model with its textures, which models with many textures store in
+    /// a companion model.
+    fn read_goldsrc_mdl(&self, path: &str, from_game: bool) -> PyResult<GoldSrcModel> {
+        let data = self.read_file(path, from_game)?;
+        let mut model =
+            GoldSrcModel::read(&data).map_err(|e| PyIOError::new_err(format!("`{path}`: {e}")))?;
+
+        if model.textures.is_empty() {
+            if let Some(stem) = path.strip_suffix(".mdl") {
+                let texture_path = format!("{stem}t.mdl");
+
+                match self.read_file(&texture_path, from_game) {
+                    Ok(texture_data) => {
+                        if let Err(error) = model.read_external_textures(&texture_data) {
+                            warn!("goldsrc mdl `{texture_path}`: {error}");
+                        }
+                    }
+                    Err(error) => warn!("textures of goldsrc mdl `{path}`: {error}"),
+                }
+            }
+        }
+
+        Ok(model)
+    }
+
+    /// Sends the textures and the model of a GoldSrc model to the callback.
+    fn send_goldsrc_mdl(&self, py: Python, name: String, model: &GoldSrcModel) {
+        let settings = &self.material_config.settings;
+        let callback_ref = self.callback_obj.as_ref(py);
+
+        // the textures are sent first, so that the model finds their materials
+        for texture in &model.textures {
+            let texture = Texture::from_wad(
+                &WadTexture {
+                    name: goldsrc::material_name(&name, &texture.name),
+                    ..texture.clone()
+                },
+                settings.texture_format,
+                settings.texture_max_resolution,
+                settings.texture_color_correction.enabled(),
+            );
+
+            if let Err(err) = callback_ref.call_method1("texture_material", (texture,)) {
+                err.print(py);
+                error!("Asset importing errored: {}", err);
+            }
+        }
+
+        let model = PyModel::from_goldsrc(name, model);
+
+        if let Err(err) = callback_ref.call_method1("model", (model,)) {
+            err.print(py);
+            error!("Asset importing errored: {}", err);
+        }
+    }
+
+    /// Takes the props with GoldSrc models out of the vmf, since the asset pipeline can't
+    /// load their models. Only the header of each model is read to detect them.
+    fn take_goldsrc_props(&self, vmf: &mut Vmf) -> Vec<(Entity, String)> {
+        let mut is_goldsrc: HashMap<String, bool> = HashMap::new();
+        let mut props = Vec::new();
+
+        vmf.entities.retain(|entity| {
+            if !entity.class_name.to_ascii_lowercase().starts_with("prop_") {
+                return true;
+            }
+
+            let Some(model) = entity_property(entity, "model").map(game_path::normalize) else {
+                return true;
+            };
+
+            let goldsrc = *is_goldsrc
+                .entry(model.clone())
+                .or_insert_with_key(|model| self.is_goldsrc_mdl(model, true));
+
+            if goldsrc {
+                props.push((entity.clone(), model));
+            }
+
+            !goldsrc
+        });
+
+        props
+    }
+
+    /// Imports the props with GoldSrc models, and each of their models once.
+    fn import_goldsrc_props(&self, py: Python, props: Vec<(Entity, String)>, scale: f32) {
+        let mut models = HashSet::new();
+
+        for (entity, model) in props {
+            if models.insert(model.clone()) {
+                match self.read_goldsrc_mdl(&model, true) {
+                    Ok(data) => self.send_goldsrc_mdl(py, model.clone(), &data),
+                    Err(error) => {
+                        error!("goldsrc mdl `{model}`: {error}");
+                        self.errors
+                            .push("model", Some(model.clone()), error.to_string());
+                    }
+                }
+            }
+
+            let mut prop = PyLoadedProp::from_goldsrc(&entity, model, scale);
+            self.prepare_prop(&mut prop);
+
+            if let Err(err) = self.callback_obj.as_ref(py).call_method1("prop", (prop,)) {
+                err.print(py);
+                error!("Asset importing errored: {}", err);
+            }
+        }
+    }
+
+    /// Applies the data read from the vmf directly to a prop.
+    fn prepare_prop(&self, prop: &mut PyLoadedProp) {
+        if let Some(area) = &self.skybox_area {
+            prop.apply_skybox_area(area);
+        }
+
+        prop.apply_ragdoll_groups(&self.ragdoll_groups);
+        prop.apply_lighting_origins(&self.lighting_origins);
+    }
+
+    /// Reads a texture of a Source 2 material. Compiled textures are referred to by
+    /// their source name. Loose materials look for the texture relative to each of
+    /// their parent directories, since the content root isn't known.
+    fn read_source2_texture(
+        &self,
+        texture_path: &str,
+        vmat_path: &str,
+        from_game: bool,
+    ) -> Result<Vec<u8>, String> {
+        let mut texture_path = game_path::normalize(texture_path);
+        if texture_path.ends_with(".vtex") {
+            texture_path.push_str("_c");
+        }
+
+        if !from_game {
+            let loose = StdPath::new(vmat_path)
+                .ancestors()
+                .skip(1)
+                .map(|dir| dir.join(&texture_path))
+                .find(|path| path.is_file());
+
+            if let Some(loose) = loose {
+                return fs::read(loose).map_err(|e| e.to_string());
+            }
+        }
+
+        self.file_system
+            .read(&GamePathBuf::from(texture_path).into())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Imports a GoldSrc model. The settings are the same as for Source models, but
+    /// GoldSrc animations aren't read, so `import_animations` has no effect.
+    fn import_goldsrc_mdl(
+        &mut self,
+        py: Python,
+        path: &str,
+        from_game: bool,
+        kwargs: Option<&PyDict>,
+    ) -> PyResult<()> {
+        self.mdl_settings(kwargs)?;
+        self.consume()?;
+
+        let start = Instant::now();
+        info!("importing goldsrc mdl `{path}`...");
+
+        let model = self.read_goldsrc_mdl(path, from_game)?;
+
+        let name = if from_game {
+            game_path::normalize(path)
+        } else {
+            StdPath::new(path)
+                .file_name()
+                .map_or_else(|| path.to_owned(), |n| n.to_string_lossy().to_lowercase())
+        };
+
+        self.send_goldsrc_mdl(py, name, &model);
+
+        info!(
+            "goldsrc mdl imported in {:.2} s",
+            start.elapsed().as_secs_f32()
+        );
+
+        Ok(())
+    }
+
     fn process_assets(&self, py: Python) {
         let callback_ref = self.callback_obj.as_ref(py);
+        let mut last_check = Instant::now();
+        let mut last_received = Instant::now();
+
+        loop {
+            if last_check.elapsed() >= WATCHDOG_INTERVAL {
+                last_check = Instant::now();
+                self.report_overdue_assets();
+            }
+
+            let asset = match self.receiver.recv_timeout(WATCHDOG_INTERVAL) {
+                Ok(asset) => {
+                    last_received = Instant::now();
+                    asset
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    // the workers can't be interrupted, so the import stops waiting for
+                    // the assets which hang once nothing else has been received either
+                    if last_received.elapsed() >= self.asset_timeout
+                        && watchdog::any_overdue(self.asset_timeout)
+                    {
+                        error!("stopping the import, since the remaining assets are hanging");
+                        break;
+                    }
+
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
 
-        for asset in &self.receiver {
             let kind = asset.kind();
-            let id = asset.id();
+            let id = asset.id().to_string();
 
             let _asset_span = debug_span!("asset", kind, %id).entered();
 
+            // checkpoint entries identify completed assets when resuming an interrupted import
+            let entry = format!("{kind}\t{id}");
+
+            if self.skip_assets.contains(&entry) {
+                debug!("skipping already imported asset");
+                continue;
+            }
+
             let result = match asset {
                 Message::Material(material) => callback_ref.call_method1("material", (material,)),
                 Message::Texture(texture) => callback_ref.call_method1("texture", (texture,)),
                 Message::Model(model) => callback_ref.call_method1("model", (model,)),
-                Message::Brush(brush) => callback_ref.call_method1("brush", (brush,)),
+                Message::Brush(mut brush) => {
+                    if let Some(area) = &self.skybox_area {
+                        brush.apply_skybox_area(area);
+                    }
+
+                    brush.apply_disp_neighbors(&self.disp_neighbors);
+                    brush.apply_smoothing_groups(&self.smoothing_groups);
+                    brush.apply_brush_solidities(&self.brush_solidities);
+
+                    if let Some(sides) = &self.lightmap_sides {
+                        brush.apply_lightmap_uvs(sides);
+                    }
+
+                    callback_ref.call_method1("brush", (brush,))
+                }
                 Message::Overlay(overlay) => callback_ref.call_method1("overlay", (overlay,)),
-                Message::Prop(prop) => callback_ref.call_method1("prop", (prop,)),
+                Message::Prop(mut prop) => {
+                    self.prepare_prop(&mut prop);
+                    callback_ref.call_method1("prop", (prop,))
+                }
                 Message::Light(light) => callback_ref.call_method1("light", (light,)),
                 Message::SpotLight(light) => callback_ref.call_method1("spot_light", (light,)),
                 Message::EnvLight(light) => callback_ref.call_method1("env_light", (light,)),
@@ -357,18 +1539,111 @@ impl PyImporter {
                     callback_ref.call_method1("sky_camera", (sky_camera,))
                 }
                 Message::SkyEqui(sky_equi) => callback_ref.call_method1("sky_equi", (sky_equi,)),
-                Message::UnknownEntity(entity) => {
+                Message::SkyFaces(sky_faces) => {
+                    callback_ref.call_method1("sky_faces", (sky_faces,))
+                }
+                Message::UnknownEntity(mut entity) => {
+                    if let Some(connections) = self.entity_connections.get(&entity.id) {
+                        entity.set_connections(connections.clone());
+                    }
+
                     callback_ref.call_method1("unknown_entity", (entity,))
                 }
+                Message::Wind(wind) => callback_ref.call_method1("wind", (wind,)),
+                Message::Precipitation(precipitation) => {
+                    callback_ref.call_method1("precipitation", (precipitation,))
+                }
+                Message::FogController(fog) => callback_ref.call_method1("fog_controller", (fog,)),
+                Message::Sprite(sprite) => callback_ref.call_method1("sprite", (sprite,)),
+                Message::Sound(sound) => callback_ref.call_method1("sound", (sound,)),
             };
 
-            if let Err(err) = result {
-                err.print(py);
-                error!("Asset importing errored: {}", err);
+            match result {
+                Err(err) => {
+                    err.print(py);
+                    error!("Asset importing errored: {}", err);
+                }
+                // assets are only checkpointed once the callback acknowledges them as
+                // completely imported, not if they are finished after the import
+                Ok(acknowledged) if acknowledged.is_true().unwrap_or(false) => {
+                    if let Some(mut checkpoint) = self.checkpoint.as_ref() {
+                        if let Err(err) = writeln!(checkpoint, "{entry}") {
+                            warn!("could not write import checkpoint: {err}");
+                        }
+                    }
+                }
+                Ok(_) => debug!("asset not acknowledged, leaving it out of the checkpoint"),
             }
         }
     }
 
+    /// Imports the brushes of gameplay zones including their invisible solids.
+    fn import_gameplay_brushes(
+        &mut self,
+        py: Python,
+        vmf: Vmf,
+        mut geometry_settings: GeometrySettings,
+        scale: f32,
+    ) -> PyResult<()> {
+        geometry_settings.invisible_solids(InvisibleSolids::Import);
+
+        let mut settings = VmfConfig::new(self.material_config);
+        settings.brushes = BrushSetting::Import(geometry_settings);
+        settings.import_overlays = false;
+        settings.import_props = false;
+        settings.import_other_entities = false;
+        settings.import_skybox = false;
+        settings.scale = scale;
+
+        let (sender, receiver) = crossbeam_channel::bounded(256);
+        let executor = self.new_executor(sender);
+        self.receiver = receiver;
+
+        crash::catch_panic(|| executor.process(settings, vmf, || self.process_assets(py)))
+    }
+
+    /// Loads the materials which the imported assets only referred to by name. The
+    /// executor of the import is consumed by then, and it's only done once all its
+    /// assets are sent, so the materials are loaded in one batch by a new executor and
+    /// channel. Materials which the import already loaded are skipped.
+    fn import_requested_materials(&mut self, py: Python) -> PyResult<()> {
+        let paths: Vec<PathBuf> = self
+            .material_requests
+            .take()
+            .into_iter()
+            .filter(|material| !self.lookups.contains(material))
+            .map(|material| game_path::game_path(&material).into())
+            .collect();
+
+        if paths.is_empty() {
+            return Ok(());
+        }
+
+        let (sender, receiver) = crossbeam_channel::bounded(256);
+        let executor = self.new_executor(sender);
+        self.receiver = receiver;
+
+        let settings = MaterialBatchConfig {
+            material: self.material_config,
+        };
+
+        crash::catch_panic(|| executor.process(settings, paths, || self.process_assets(py)))
+    }
+
+    /// Reports the assets which have been processing for longer than the timeout, so
+    /// that the hanging files are listed even if the import is stopped.
+    fn report_overdue_assets(&self) {
+        for (name, elapsed) in watchdog::overdue(self.asset_timeout) {
+            let error = format!(
+                "processing for {:.0} s, the file may be corrupt",
+                elapsed.as_secs_f32()
+            );
+
+            error!("{name}: {error}");
+            self.errors.push("timeout", Some(name), error);
+        }
+    }
+
     fn mdl_settings(&self, kwargs: Option<&PyDict>) -> PyResult<MdlConfig<MaterialConfig>> {
         let mut settings = MdlConfig::new(self.material_config);
 
@@ -385,57 +1660,134 @@ impl PyImporter {
     }
 }
 
+/// Takes the brush entities of gameplay zones out of the vmf, into a vmf without the world
+/// solids. Returns `None` if the vmf has none.
+fn take_gameplay_brushes(vmf: &mut Vmf) -> Option<Vmf> {
+    let (gameplay, entities): (Vec<_>, Vec<_>) = mem::take(&mut vmf.entities)
+        .into_iter()
+        .partition(|entity| {
+            !entity.solids.is_empty()
+                && GameplayMarker::from_class_name(&entity.class_name).is_some()
+        });
+    vmf.entities = entities;
+
+    if gameplay.is_empty() {
+        return None;
+    }
+
+    let world_solids = mem::take(&mut vmf.world.solids);
+    let mut gameplay_vmf = vmf.clone();
+    vmf.world.solids = world_solids;
+    gameplay_vmf.entities = gameplay;
+
+    Some(gameplay_vmf)
+}
+
+fn entity_property<'a>(entity: &'a Entity, key: &str) -> Option<&'a str> {
+    entity
+        .properties
+        .iter()
+        .find(|(k, _)| k.as_str().eq_ignore_ascii_case(key))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Reads the size of a decal in units from its material and base texture.
+fn decal_size(file_system: &OpenFileSystem, texture: &str) -> Option<[f32; 2]> {
+    let vmt = file_system
+        .read(&game_path::game_path(&decals::material_path(texture)).into())
+        .map_err(|error| warn!("decal `{texture}`: could not read material: {error}"))
+        .ok()?;
+
+    let (base_texture, scale) = decals::vmt_base_texture(&String::from_utf8_lossy(&vmt))?;
+
+    // only the header is read, since decal textures can be large
+    let mut header = [0; vtf_header::SIZE_LEN];
+
+    file_system
+        .open_file(&game_path::game_path(&base_texture))
+        .and_then(|mut file| file.read_exact(&mut header))
+        .map_err(|error| warn!("decal `{texture}`: could not read texture: {error}"))
+        .ok()?;
+
+    vtf_header::size(&header).map(|size| size.map(|s| f32::from(s) * scale))
+}
+
+fn detail_types(file_system: &OpenFileSystem, path: &str) -> Option<DetailTypes> {
+    let text = file_system
+        .read(&game_path::game_path(path).into())
+        .map_err(|error| warn!("detail types `{path}`: could not read: {error}"))
+        .ok()?;
+
+    DetailTypes::parse(&String::from_utf8_lossy(&text))
+        .map_err(|error| warn!("detail types `{path}`: could not parse: {error}"))
+        .ok()
+}
+
+fn material_detail_type(file_system: &OpenFileSystem, material: &str) -> Option<String> {
+    // missing materials are already reported when the brushes are built
+    detail_scatter::vmt_detail_type(material, |path| {
+        file_system
+            .read(&game_path::game_path(&decals::material_path(path)).into())
+            .map(|vmt| String::from_utf8_lossy(&vmt).into_owned())
+            .map_err(|e| e.to_string())
+    })
+}
+
 fn detect_embedded_files_path(file_path_string: &str, opened: &mut OpenFileSystem) {
     let file_path: PathBuf = if StdPath::new(file_path_string).is_absolute() {
         StdPathBuf::from(file_path_string).into()
     } else {
-        GamePathBuf::from(file_path_string).into()
+        game_path::game_path(file_path_string).into()
     };
 
     // Ignore errors for now, the error will be shown anyway when the vmf file is actually read later.
-    if let Ok(file_info) = opened.open_file_with_info(&file_path) {
-        let map_data_path = if let Some(search_path) = file_info.search_path {
-            // Map data path can only be added when the vmf is not in a vpk file
-            if let OpenSearchPath::Directory(search_dir) = search_path {
-                // Remove the extension from the vmf path to get the map data path
-                if let Some((map_data_path_part, _extension)) = file_path_string.rsplit_once('.') {
-                    let map_data_path = search_dir.join(map_data_path_part);
-                    map_data_path.is_dir().then_some(map_data_path)
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        } else {
-            // Vmf is being imported from the file system, just create the path directly
-            if let Some((map_data_path, _extension)) = file_path_string.rsplit_once('.') {
-                let map_data_path = StdPathBuf::from(map_data_path);
-                map_data_path.is_dir().then_some(map_data_path)
-            } else {
-                None
-            }
-        };
+    let Ok(file_info) = opened.open_file_with_info(&file_path) else {
+        return;
+    };
 
-        if let Some(map_data_path) = map_data_path {
-            info!(
-                "vmf embedded files path detected as `{}`",
-                map_data_path.display()
-            );
+    // Remove the extension from the vmf path to get the map data path
+    let Some((map_path, _extension)) = file_path_string.rsplit_once('.') else {
+        return;
+    };
 
-            opened.add_open_search_path(OpenSearchPath::Directory(map_data_path));
-        }
+    let map_path = match file_info.search_path {
+        // Map data path can only be added when the vmf is not in a vpk file
+        Some(OpenSearchPath::Directory(search_dir)) => search_dir.join(map_path),
+        Some(_) => return,
+        // Vmf is being imported from the file system, just create the path directly
+        None => StdPathBuf::from(map_path),
+    };
+
+    if map_path.is_dir() {
+        info!(
+            "vmf embedded files path detected as `{}`",
+            map_path.display()
+        );
+
+        opened.add_open_search_path(OpenSearchPath::Directory(map_path.clone()));
     }
-}
 
-fn detect_local_search_path<'a>(asset_path: &'a str, target_path: &str) -> Option<&'a StdPath> {
-    let mut asset_path = StdPath::new(asset_path);
+    // Files embedded into the compiled map are used if the vmf sits next to its bsp
+    let mut bsp_path = map_path.into_os_string();
+    bsp_path.push(".bsp");
+    let bsp_path = StdPathBuf::from(bsp_path);
 
-    loop {
-        asset_path = asset_path.parent()?;
+    if bsp_path.is_file() {
+        match filesystem::mount_bsp_pakfile(&bsp_path) {
+            Ok(pakfile_path) => {
+                info!("bsp pakfile detected as `{}`", bsp_path.display());
 
-        if asset_path.ends_with(target_path) {
-            return asset_path.parent();
+                opened.add_open_search_path(OpenSearchPath::Directory(pakfile_path));
+            }
+            Err(error) => warn!("bsp pakfile `{}`: {error}", bsp_path.display()),
         }
     }
 }
+
+/// Name of a texture of a Source 2 material, its path without the extension.
+fn source2_texture_name(path: &str) -> GamePathBuf {
+    let path = game_path::normalize(path);
+    let path = path.strip_suffix("_c").unwrap_or(&path);
+
+    GamePathBuf::from(path.rsplit_once('.').map_or(path, |(stem, _)| stem))
+}
@@ -0,0 +1,275 @@
+//! Parses `KeyValues` text into an untyped tree, which keeps the order and duplicates of
+//! the keys.
+//!
+//! `plumber_core` has its own parser, but it only deserializes into the types it defines,
+//! like vmfs and vmts, through serde. Manifests, patch materials, the blocks of vmfs
+//! which `plumber_core` drops, and arbitrary files read from Python have no such types,
+//! and depend on the order of the keys or on repeated keys, which serde maps lose.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    iter::Peekable,
+    str::CharIndices,
+};
+
+/// A value in a `KeyValues` document, either a plain string or a nested block.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum KeyValue {
+    Value(String),
+    Block(Vec<(String, KeyValue)>),
+}
+
+impl KeyValue {
+    pub fn as_block(&self) -> Option<&[(String, KeyValue)]> {
+        match self {
+            KeyValue::Value(_) => None,
+            KeyValue::Block(b) => Some(b),
+        }
+    }
+
+    pub fn as_value(&self) -> Option<&str> {
+        match self {
+            KeyValue::Value(v) => Some(v),
+            KeyValue::Block(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: &'static str,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    String(String),
+    Condition,
+    Open,
+    Close,
+}
+
+struct Tokenizer<'a> {
+    chars: Peekable<CharIndices<'a>>,
+    line: usize,
+    escapes: bool,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(text: &'a str, escapes: bool) -> Self {
+        Self {
+            chars: text.char_indices().peekable(),
+            line: 1,
+            escapes,
+        }
+    }
+
+    fn error(&self, message: &'static str) -> ParseError {
+        ParseError {
+            line: self.line,
+            message,
+        }
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(&(_, c)) = self.chars.peek() {
+            if c == '\n' {
+                self.line += 1;
+                self.chars.next();
+            } else if c.is_whitespace() || c == '\u{feff}' {
+                self.chars.next();
+            } else if c == '/' {
+                let mut lookahead = self.chars.clone();
+                lookahead.next();
+
+                if !matches!(lookahead.peek(), Some((_, '/'))) {
+                    return;
+                }
+
+                for (_, c) in self.chars.by_ref() {
+                    if c == '\n' {
+                        self.line += 1;
+                        break;
+                    }
+                }
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, ParseError> {
+        self.skip_whitespace_and_comments();
+
+        let Some((_, c)) = self.chars.next() else {
+            return Ok(None);
+        };
+
+        match c {
+            '{' => Ok(Some(Token::Open)),
+            '}' => Ok(Some(Token::Close)),
+            '[' => {
+                for (_, c) in self.chars.by_ref() {
+                    match c {
+                        ']' => return Ok(Some(Token::Condition)),
+                        '\n' => return Err(self.error("unterminated conditional")),
+                        _ => {}
+                    }
+                }
+
+                Err(self.error("unterminated conditional"))
+            }
+            '"' => {
+                let mut string = String::new();
+
+                while let Some((_, c)) = self.chars.next() {
+                    match c {
+                        '"' => return Ok(Some(Token::String(string))),
+                        '\\' if self.escapes => match self.chars.peek() {
+                            Some(&(_, 'n')) => {
+                                self.chars.next();
+                                string.push('\n');
+                            }
+                            Some(&(_, 't')) => {
+                                self.chars.next();
+                                string.push('\t');
+                            }
+                            Some(&(_, c @ ('\\' | '"'))) => {
+                                self.chars.next();
+                                string.push(c);
+                            }
+                            _ => string.push('\\'),
+                        },
+                        '\n' => {
+                            self.line += 1;
+                            string.push(c);
+                        }
+                        _ => string.push(c),
+                    }
+                }
+
+                Err(self.error("unterminated string"))
+            }
+            c => {
+                let mut string = String::from(c);
+
+                while let Some(&(_, c)) = self.chars.peek() {
+                    if c.is_whitespace() || matches!(c, '"' | '{' | '}' | '[') {
+                        break;
+                    }
+
+                    string.push(c);
+                    self.chars.next();
+                }
+
+                Ok(Some(Token::String(string)))
+            }
+        }
+    }
+
+    /// Returns the next token which is not a platform conditional.
+    fn next_significant(&mut self) -> Result<Option<Token>, ParseError> {
+        loop {
+            let token = self.next_token()?;
+
+            if token != Some(Token::Condition) {
+                return Ok(token);
+            }
+        }
+    }
+}
+
+fn parse_block(
+    tokenizer: &mut Tokenizer,
+    top_level: bool,
+) -> Result<Vec<(String, KeyValue)>, ParseError> {
+    let mut entries = Vec::new();
+
+    loop {
+        let key = match tokenizer.next_significant()? {
+            Some(Token::String(key)) => key,
+            Some(Token::Close) if !top_level => return Ok(entries),
+            Some(Token::Close) => return Err(tokenizer.error("unexpected `}`")),
+            Some(Token::Open) => return Err(tokenizer.error("expected a key, got `{`")),
+            Some(Token::Condition) => unreachable!("conditions are skipped"),
+            None if top_level => return Ok(entries),
+            None => return Err(tokenizer.error("unexpected end of file, expected `}`")),
+        };
+
+        let value = match tokenizer.next_significant()? {
+            Some(Token::String(value)) => KeyValue::Value(value),
+            Some(Token::Open) => KeyValue::Block(parse_block(tokenizer, false)?),
+            Some(Token::Close) | None => return Err(tokenizer.error("key is missing a value")),
+            Some(Token::Condition) => unreachable!("conditions are skipped"),
+        };
+
+        entries.push((key, value));
+    }
+}
+
+/// Parses a `KeyValues` (VDF) document, keeping the original order and any duplicate keys.
+/// Platform conditionals such as `[$WIN32]` are ignored.
+pub fn parse(text: &str) -> Result<Vec<(String, KeyValue)>, ParseError> {
+    parse_block(&mut Tokenizer::new(text, true), true)
+}
+
+/// Like [`parse`], but backslashes are kept as-is, as in VMF files.
+pub fn parse_raw(text: &str) -> Result<Vec<(String, KeyValue)>, ParseError> {
+    parse_block(&mut Tokenizer::new(text, false), true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_nested() {
+        let text = r#"
+            // comment
+            "root"
+            {
+                key "value" [$WIN32]
+                "nested" { "a" "1" "a" "2" }
+                "escaped" "say \"hi\""
+            }
+        "#;
+
+        let parsed = parse(text).unwrap();
+
+        assert_eq!(
+            parsed,
+            vec![(
+                "root".to_owned(),
+                KeyValue::Block(vec![
+                    ("key".to_owned(), KeyValue::Value("value".to_owned())),
+                    (
+                        "nested".to_owned(),
+                        KeyValue::Block(vec![
+                            ("a".to_owned(), KeyValue::Value("1".to_owned())),
+                            ("a".to_owned(), KeyValue::Value("2".to_owned())),
+                        ])
+                    ),
+                    (
+                        "escaped".to_owned(),
+                        KeyValue::Value("say \"hi\"".to_owned())
+                    ),
+                ])
+            )]
+        );
+    }
+
+    #[test]
+    fn parse_unclosed_block() {
+        let error = parse("\"root\"\n{\n\"key\" \"value\"\n").unwrap_err();
+
+        assert_eq!(error.line, 4);
+    }
+}
@@ -0,0 +1,133 @@
+//! Reads values of the raw vmf and vmt `KeyValues`, for the features which need more of
+//! the files than the loaders keep.
+
+use glam::Vec3;
+
+use crate::keyvalues::KeyValue;
+
+/// Material parameters which reference textures.
+pub const TEXTURE_PARAMETERS: [&str; 14] = [
+    "$basetexture",
+    "$basetexture2",
+    "$bumpmap",
+    "$bumpmap2",
+    "$normalmap",
+    "$detail",
+    "$envmapmask",
+    "$blendmodulatetexture",
+    "$selfillummask",
+    "$phongexponenttexture",
+    "$lightwarptexture",
+    "$dudvmap",
+    "$iris",
+    "$ambientoccltexture",
+];
+
+/// Returns a value directly in a block, like a keyvalue of an entity or a side.
+pub fn block_value<'a>(block: &'a [(String, KeyValue)], key: &str) -> Option<&'a str> {
+    block
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .and_then(|(_, v)| v.as_value())
+}
+
+/// Returns a block directly in a block, like the `dispinfo` of a side.
+pub fn child_block<'a>(
+    block: &'a [(String, KeyValue)],
+    key: &str,
+) -> Option<&'a [(String, KeyValue)]> {
+    block
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .and_then(|(_, v)| v.as_block())
+}
+
+/// Returns the first value in a block or in the blocks nested in it, like a parameter
+/// of a material under its shader.
+pub fn nested_value<'a>(block: &'a [(String, KeyValue)], key: &str) -> Option<&'a str> {
+    block.iter().find_map(|(k, v)| match v {
+        KeyValue::Value(value) => k.eq_ignore_ascii_case(key).then_some(value.as_str()),
+        KeyValue::Block(block) => nested_value(block, key),
+    })
+}
+
+/// Parses a `x y z` keyvalue.
+pub fn parse_vector(value: &str) -> Option<Vec3> {
+    let mut values = value.split_ascii_whitespace().map(str::parse::<f32>);
+
+    Some(Vec3::new(
+        values.next()?.ok()?,
+        values.next()?.ok()?,
+        values.next()?.ok()?,
+    ))
+}
+
+/// Parses a `(x y z) (x y z) (x y z)` plane to its outward normal and distance.
+pub fn parse_plane(plane: &str) -> Option<(Vec3, f32)> {
+    let mut values = plane
+        .split(|c: char| c == '(' || c == ')' || c.is_ascii_whitespace())
+        .filter(|v| !v.is_empty())
+        .map(str::parse::<f32>);
+
+    let mut points = [Vec3::ZERO; 3];
+    for point in &mut points {
+        *point = Vec3::new(
+            values.next()?.ok()?,
+            values.next()?.ok()?,
+            values.next()?.ok()?,
+        );
+    }
+
+    let [p0, p1, p2] = points;
+    let normal = (p0 - p1).cross(p2 - p1).try_normalize()?;
+
+    Some((normal, normal.dot(p0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::keyvalues;
+
+    #[test]
+    fn find_values() {
+        let root = keyvalues::parse_raw(
+            r#"
+            "LightmappedGeneric"
+            {
+                "$basetexture" "brick/brickwall001"
+                "dispinfo" { "power" "3" }
+                ">=dx90" { "$bumpmap" "brick/brickwall001_normal" }
+            }
+            "#,
+        )
+        .unwrap();
+
+        let shader = root[0].1.as_block().unwrap();
+        assert_eq!(
+            block_value(shader, "$BaseTexture"),
+            Some("brick/brickwall001")
+        );
+        assert_eq!(block_value(shader, "$bumpmap"), None);
+        assert_eq!(block_value(shader, "dispinfo"), None);
+        assert_eq!(
+            child_block(shader, "dispinfo").and_then(|b| block_value(b, "power")),
+            Some("3")
+        );
+        assert_eq!(
+            nested_value(&root, "$bumpmap"),
+            Some("brick/brickwall001_normal")
+        );
+    }
+
+    #[test]
+    fn parse_values() {
+        assert_eq!(parse_vector("1 -2 3.5"), Some(Vec3::new(1.0, -2.0, 3.5)));
+        assert_eq!(parse_vector("1 2"), None);
+
+        let (normal, distance) = parse_plane("(0 64 16) (64 64 16) (64 0 16)").unwrap();
+        assert_eq!(normal, Vec3::Z);
+        assert!((distance - 16.0).abs() < 1e-6);
+    }
+}
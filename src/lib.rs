@@ -7,70 +7,165 @@
 #![allow(clippy::module_name_repetitions)]
 
 mod asset;
+mod bookmarks;
+mod brush_solidity;
+mod bsp;
+mod bytes;
+#[cfg(target_os = "linux")]
+mod case_fold;
+mod choreo;
+mod connections;
+mod crash;
+mod decals;
+mod detail_scatter;
+mod disp_neighbors;
+mod dmx;
+mod entity_report;
+mod fgd;
 mod filesystem;
+mod game_path;
+mod gameplay;
+mod glob;
+mod gma;
 mod importer;
+mod keyvalues;
+mod kv_utils;
+mod lighting_origins;
+mod lightmap_uvs;
+mod lookups;
+mod particles;
+mod ragdoll_groups;
+#[cfg(feature = "respawn_vpk")]
+mod respawn_vpk;
+mod scan;
+mod skybox;
+mod smoothing_groups;
+mod source2;
+mod steam;
+mod track_paths;
+mod vmf_repair;
+mod vtf_header;
+mod wad;
+mod watchdog;
+mod world_faces;
 
-use std::fmt;
+use std::{env, fmt, sync::OnceLock};
 
-use pyo3::prelude::*;
-use tracing::{error, info, Event, Subscriber};
+use pyo3::{
+    exceptions::{PyRuntimeError, PyValueError},
+    prelude::*,
+    types::PyBytes,
+};
+use tracing::{error, info, level_filters::LevelFilter, Event, Subscriber};
 use tracing_subscriber::{
+    filter::Targets,
     fmt::{format, FmtContext, FormatEvent, FormatFields},
     prelude::*,
     registry::LookupSpan,
+    reload, Registry,
 };
 
 use crate::{
     asset::{
         brush::{PyBuiltBrushEntity, PyBuiltSolid, PyMergedSolids},
+        choreo::{PyChoreoEvent, PyChoreoScene},
+        cubemap::PyCubemap,
+        detail::{PyDetailScatter, PyDetailSprites},
         entities::{PyEnvLight, PyLight, PyLoadedProp, PySkyCamera, PySpotLight, PyUnknownEntity},
+        errors::PyAssetError,
         material::{
-            BuiltMaterialData, BuiltNode, BuiltNodeSocketRef, Material, Texture, TextureRef,
+            AnimatedVec, BuiltMaterialData, BuiltNode, BuiltNodeGroup, BuiltNodeSocketRef,
+            Material, NodeTreeRef, Texture, TextureRef,
         },
         model::{
-            PyBoneAnimationData, PyBoneRestData, PyLoadedAnimation, PyLoadedBone, PyLoadedMesh,
-            PyModel, QuaternionData, VectorData,
+            PyBoneAnimationData, PyBoneRestData, PyHitbox, PyJiggleBone, PyLoadedAnimation,
+            PyLoadedAttachment, PyLoadedBone, PyLoadedMesh, PyMaterialBinding, PyModel,
+            PySequencePreview, QuaternionData, VectorData,
         },
         overlay::PyBuiltOverlay,
-        sky::PySkyEqui,
+        sky::{PySkyEqui, PySkyFace, PySkyFaces},
+        sound::PySound,
+        sprite::PySprite,
+        track::PyTrackPath,
+        vis_cluster::PyVisCluster,
+        weather::{PyFogController, PyPrecipitation, PyWind},
     },
-    filesystem::{PyFileBrowser, PyFileBrowserEntry, PyFileSystem},
+    filesystem::{PyFileBrowser, PyFileBrowserEntry, PyFileSystem, PyWorkshopItem},
     importer::PyImporter,
+    lookups::PathLookup,
+    scan::{ScanManifest, ScannedFile},
 };
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Environment variable with the initial log filter, like `debug` or `info,plumber=debug`.
+const LOG_FILTER_VAR: &str = "PLUMBER_LOG";
+
+#[cfg(feature = "trace")]
+const DEFAULT_LOG_LEVEL: LevelFilter = LevelFilter::DEBUG;
+#[cfg(not(feature = "trace"))]
+const DEFAULT_LOG_LEVEL: LevelFilter = LevelFilter::INFO;
+
+static LOG_FILTER: OnceLock<reload::Handle<Targets, Registry>> = OnceLock::new();
+
 #[pymodule]
 fn plumber(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<PyFileSystem>()?;
     m.add_class::<PyFileBrowser>()?;
     m.add_class::<PyFileBrowserEntry>()?;
+    m.add_class::<PyWorkshopItem>()?;
     m.add_class::<PySkyEqui>()?;
+    m.add_class::<PySkyFaces>()?;
+    m.add_class::<PySkyFace>()?;
+    m.add_class::<PyCubemap>()?;
+    m.add_class::<PyVisCluster>()?;
     m.add_class::<Texture>()?;
     m.add_class::<Material>()?;
     m.add_class::<BuiltMaterialData>()?;
     m.add_class::<BuiltNode>()?;
     m.add_class::<BuiltNodeSocketRef>()?;
+    m.add_class::<BuiltNodeGroup>()?;
     m.add_class::<TextureRef>()?;
+    m.add_class::<NodeTreeRef>()?;
+    m.add_class::<AnimatedVec>()?;
     m.add_class::<QuaternionData>()?;
     m.add_class::<VectorData>()?;
     m.add_class::<PyBoneAnimationData>()?;
     m.add_class::<PyBoneRestData>()?;
     m.add_class::<PyLoadedAnimation>()?;
+    m.add_class::<PySequencePreview>()?;
     m.add_class::<PyLoadedBone>()?;
+    m.add_class::<PyJiggleBone>()?;
+    m.add_class::<PyLoadedAttachment>()?;
+    m.add_class::<PyHitbox>()?;
     m.add_class::<PyLoadedMesh>()?;
+    m.add_class::<PyMaterialBinding>()?;
     m.add_class::<PyModel>()?;
     m.add_class::<PyMergedSolids>()?;
     m.add_class::<PyBuiltSolid>()?;
     m.add_class::<PyBuiltBrushEntity>()?;
     m.add_class::<PyBuiltOverlay>()?;
+    m.add_class::<PyDetailSprites>()?;
+    m.add_class::<PyDetailScatter>()?;
+    m.add_class::<PyTrackPath>()?;
+    m.add_class::<PyChoreoScene>()?;
+    m.add_class::<PyChoreoEvent>()?;
     m.add_class::<PyLoadedProp>()?;
     m.add_class::<PyLight>()?;
     m.add_class::<PySpotLight>()?;
     m.add_class::<PyEnvLight>()?;
     m.add_class::<PySkyCamera>()?;
     m.add_class::<PyUnknownEntity>()?;
+    m.add_class::<PyWind>()?;
+    m.add_class::<PyPrecipitation>()?;
+    m.add_class::<PyFogController>()?;
+    m.add_class::<PySprite>()?;
+    m.add_class::<PySound>()?;
     m.add_class::<PyImporter>()?;
+    m.add_class::<PyAssetError>()?;
+    m.add_class::<ScanManifest>()?;
+    m.add_class::<ScannedFile>()?;
+    m.add_class::<PathLookup>()?;
 
     #[pyfn(m)]
     fn discover_filesystems() -> Vec<PyFileSystem> {
@@ -82,6 +177,36 @@ fn plumber(_py: Python, m: &PyModule) -> PyResult<()> {
         filesystem::from_gameinfo(path)
     }
 
+    #[pyfn(m)]
+    fn filesystem_from_directory(path: &str) -> PyResult<PyFileSystem> {
+        filesystem::from_directory(path)
+    }
+
+    #[pyfn(m)]
+    fn filesystem_from_asset_path(path: &str) -> PyResult<PyFileSystem> {
+        filesystem::from_asset_path(path)
+    }
+
+    #[pyfn(m)]
+    fn find_workshop_item(
+        install_dir: &str,
+        app_id: u32,
+        workshop_id: u64,
+    ) -> Option<PyWorkshopItem> {
+        filesystem::find_workshop_item(install_dir, app_id, workshop_id)
+    }
+
+    #[pyfn(m)]
+    fn workshop_map_items(install_dir: &str, app_id: u32) -> Vec<PyWorkshopItem> {
+        filesystem::workshop_map_items(install_dir, app_id)
+    }
+
+    #[pyfn(m)]
+    fn build_cubemap_vtf(py: Python, faces: Vec<&[u8]>) -> PyResult<PyObject> {
+        let data = asset::cubemap::build_cubemap_vtf(&faces)?;
+        Ok(PyBytes::new(py, &data).into())
+    }
+
     #[pyfn(m)]
     fn log_error(error: &str) {
         error!("{}", error);
@@ -92,12 +217,34 @@ fn plumber(_py: Python, m: &PyModule) -> PyResult<()> {
         info!("{}", info);
     }
 
+    /// Sets the log filter, in the same format as the `PLUMBER_LOG` environment variable.
+    /// An empty filter restores the initial one.
+    #[pyfn(m)]
+    fn set_log_filter(filter: &str) -> PyResult<()> {
+        let targets = if filter.trim().is_empty() {
+            initial_log_filter()
+        } else {
+            filter
+                .parse()
+                .map_err(|e| PyValueError::new_err(format!("invalid log filter `{filter}`: {e}")))?
+        };
+
+        reload_log_filter(targets)
+    }
+
+    /// Path of the latest crash report of this session.
+    #[pyfn(m)]
+    fn last_crash_report() -> Option<String> {
+        crash::last_report().map(|path| path.display().to_string())
+    }
+
     #[pyfn(m)]
     fn version() -> &'static str {
         VERSION
     }
 
     initialize_logger();
+    crash::install_hook();
 
     Ok(())
 }
@@ -126,21 +273,48 @@ where
     }
 }
 
+fn initial_log_filter() -> Targets {
+    env::var(LOG_FILTER_VAR)
+        .ok()
+        .and_then(|filter| {
+            filter
+                .parse()
+                .map_err(|e| eprintln!("[Plumber] [WARN] invalid {LOG_FILTER_VAR} `{filter}`: {e}"))
+                .ok()
+        })
+        .unwrap_or_else(|| Targets::new().with_default(DEFAULT_LOG_LEVEL))
+}
+
+fn reload_log_filter(targets: Targets) -> PyResult<()> {
+    LOG_FILTER
+        .get()
+        .ok_or_else(|| PyRuntimeError::new_err("logging is not enabled in this build"))?
+        .reload(targets)
+        .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+}
+
 fn initialize_logger() {
     let layer = tracing_subscriber::fmt::layer().event_format(PlumberLogFormatter);
+    let (filter, handle) = reload::Layer::new(initial_log_filter());
 
     #[cfg(feature = "trace")]
     {
         let registry = tracing_subscriber::registry()
+            .with(filter)
             .with(tracing_tracy::TracyLayer::new())
             .with(layer);
 
-        let _ = tracing::subscriber::set_global_default(registry);
+        if tracing::subscriber::set_global_default(registry).is_ok() {
+            let _ = LOG_FILTER.set(handle);
+        }
     }
 
     #[cfg(feature = "normal_logging")]
     {
-        let registry = tracing_subscriber::registry().with(layer);
-        let _ = tracing::subscriber::set_global_default(registry);
+        let registry = tracing_subscriber::registry().with(filter).with(layer);
+
+        if tracing::subscriber::set_global_default(registry).is_ok() {
+            let _ = LOG_FILTER.set(handle);
+        }
     }
 }
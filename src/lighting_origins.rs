@@ -0,0 +1,60 @@
+//! Resolves the `lightingorigin` of props to a position. Props are lit from the entity
+//! it names instead of their own origin, usually an `info_lighting`, but the entity
+//! parser only keeps the name.
+
+use std::collections::HashMap;
+
+use crate::kv_utils::parse_vector;
+
+/// The scaled origin of each named entity, by lowercase name.
+#[derive(Debug, Clone, Default)]
+pub struct LightingOrigins {
+    origins: HashMap<String, [f32; 3]>,
+}
+
+impl LightingOrigins {
+    /// Reads the origins of the entities, given as their `targetname` and `origin`.
+    pub fn new<'a>(entities: impl IntoIterator<Item = (&'a str, &'a str)>, scale: f32) -> Self {
+        let mut origins = HashMap::new();
+
+        for (name, origin) in entities {
+            let Some(origin) = parse_vector(origin).filter(|_| !name.is_empty()) else {
+                continue;
+            };
+
+            // the first entity of a name is used, like in game
+            origins
+                .entry(name.to_ascii_lowercase())
+                .or_insert_with(|| (origin * scale).into());
+        }
+
+        Self { origins }
+    }
+
+    pub fn position(&self, name: &str) -> Option<[f32; 3]> {
+        self.origins.get(&name.to_ascii_lowercase()).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_lighting_origins() {
+        let origins = LightingOrigins::new(
+            [
+                ("Lamp", "10 20 30"),
+                ("lamp", "0 0 0"),
+                ("", "1 1 1"),
+                ("broken", "1 1"),
+            ],
+            0.5,
+        );
+
+        assert_eq!(origins.position("LAMP"), Some([5.0, 10.0, 15.0]));
+        assert_eq!(origins.position(""), None);
+        assert_eq!(origins.position("broken"), None);
+        assert_eq!(origins.position("missing"), None);
+    }
+}
@@ -0,0 +1,322 @@
+//! Lays out lightmap uvs for brush faces like the compiler does, projecting each face
+//! with its texture axes at its lightmap scale, and packs the faces into a single atlas.
+//!
+//! Faces are matched to the vmf sides of their solid by their plane, like smoothing
+//! groups are, since coplanar sides of different solids can have different axes.
+
+use std::collections::HashMap;
+
+use glam::Vec3;
+
+use crate::{
+    keyvalues::KeyValue,
+    kv_utils::{block_value, parse_plane},
+    smoothing_groups::{
+        plane_key, polygon_normal, solid_contains, solid_sides, vmf_solids, PlaneKey,
+    },
+};
+
+/// Lightmap scale of sides which don't set one, in units per luxel.
+const DEFAULT_LIGHTMAP_SCALE: f32 = 16.0;
+/// Empty luxels around each face, so that faces don't bleed into each other.
+const PADDING: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LightmapAxes {
+    u: Vec3,
+    v: Vec3,
+    /// Units per luxel.
+    scale: f32,
+}
+
+impl LightmapAxes {
+    /// Axes used for faces which can't be matched to a side, aligned to the world
+    /// like new faces in Hammer.
+    fn world_aligned(normal: Vec3) -> Self {
+        let abs = normal.abs();
+
+        let (u, v) = if abs.z >= abs.x && abs.z >= abs.y {
+            (Vec3::X, -Vec3::Y)
+        } else if abs.x >= abs.y {
+            (Vec3::Y, -Vec3::Z)
+        } else {
+            (Vec3::X, -Vec3::Z)
+        };
+
+        Self {
+            u,
+            v,
+            scale: DEFAULT_LIGHTMAP_SCALE,
+        }
+    }
+}
+
+/// Lightmap axes of the vmf sides, by their plane.
+#[derive(Debug, Clone, Default)]
+pub struct LightmapSides {
+    /// Ids of the solids with a side on the plane, and the axes of the side.
+    planes: HashMap<PlaneKey, Vec<(i32, LightmapAxes)>>,
+    /// Planes of the solids, for finding the solid a face of merged solids is on.
+    solids: HashMap<i32, Vec<(Vec3, f32)>>,
+}
+
+impl LightmapSides {
+    /// Collects the lightmap axes of the world and entity solids of a vmf.
+    pub fn from_vmf(vmf: &[(String, KeyValue)]) -> Self {
+        let mut sides = Self::default();
+
+        for solid in vmf_solids(vmf) {
+            let Some(id) = block_value(solid, "id").and_then(|v| v.trim().parse().ok()) else {
+                continue;
+            };
+
+            let mut planes = Vec::new();
+
+            for side in solid_sides(solid) {
+                let Some((normal, distance)) = block_value(side, "plane").and_then(parse_plane)
+                else {
+                    continue;
+                };
+
+                planes.push((normal, distance));
+
+                let axes = block_value(side, "uaxis")
+                    .and_then(parse_axis)
+                    .zip(block_value(side, "vaxis").and_then(parse_axis));
+
+                let Some((u, v)) = axes else {
+                    continue;
+                };
+
+                // the compiler clamps the scale to at least one unit per luxel
+                let scale = block_value(side, "lightmapscale")
+                    .and_then(|v| v.trim().parse().ok())
+                    .unwrap_or(DEFAULT_LIGHTMAP_SCALE)
+                    .max(1.0);
+
+                sides
+                    .planes
+                    .entry(plane_key(normal, distance))
+                    .or_default()
+                    .push((id, LightmapAxes { u, v, scale }));
+            }
+
+            sides.solids.insert(id, planes);
+        }
+
+        sides
+    }
+
+    /// Returns the axes of a face on the plane, from the side of the solid if it's
+    /// known, or else from the solid containing the center of the face. Falls back to
+    /// any side on the plane.
+    fn find(
+        &self,
+        solid: Option<i32>,
+        normal: Vec3,
+        distance: f32,
+        center: Vec3,
+    ) -> Option<LightmapAxes> {
+        let sides = self.planes.get(&plane_key(normal, distance))?;
+
+        let side = match solid {
+            Some(solid) => sides.iter().find(|&&(id, _)| id == solid),
+            None => sides.iter().find(|&&(id, _)| {
+                self.solids
+                    .get(&id)
+                    .is_some_and(|planes| solid_contains(planes, center))
+            }),
+        };
+
+        side.or_else(|| sides.first()).map(|&(_, axes)| axes)
+    }
+}
+
+/// Parses the direction of a `[x y z offset] scale` texture axis. Lightmaps only use
+/// the direction, since the texture offset and scale don't affect them.
+fn parse_axis(axis: &str) -> Option<Vec3> {
+    let mut values = axis
+        .split(|c: char| c == '[' || c == ']' || c.is_ascii_whitespace())
+        .filter(|v| !v.is_empty())
+        .map(str::parse::<f32>);
+
+    Vec3::new(
+        values.next()?.ok()?,
+        values.next()?.ok()?,
+        values.next()?.ok()?,
+    )
+    .try_normalize()
+}
+
+/// Returns the flat loop uvs of the faces, given as their vertices in world units.
+/// Each face gets its own area of the atlas, so that the uvs don't overlap.
+/// The solid of the faces is `None` for merged solids.
+pub fn lightmap_uvs(sides: &LightmapSides, solid: Option<i32>, faces: &[Vec<Vec3>]) -> Vec<f32> {
+    // luxel coordinates of the face vertices, starting from 0
+    let mut face_luxels = Vec::with_capacity(faces.len());
+    let mut face_sizes = Vec::with_capacity(faces.len());
+
+    for vertices in faces {
+        let axes = polygon_normal(vertices.iter().copied()).map_or(
+            LightmapAxes::world_aligned(Vec3::Z),
+            |normal| {
+                #[allow(clippy::cast_precision_loss)]
+                let center = vertices.iter().sum::<Vec3>() / vertices.len() as f32;
+
+                sides
+                    .find(solid, normal, normal.dot(vertices[0]), center)
+                    .unwrap_or_else(|| LightmapAxes::world_aligned(normal))
+            },
+        );
+
+        let luxels: Vec<[f32; 2]> = vertices
+            .iter()
+            .map(|v| [v.dot(axes.u) / axes.scale, v.dot(axes.v) / axes.scale])
+            .collect();
+
+        let min = luxels
+            .iter()
+            .fold([f32::MAX; 2], |min, l| [min[0].min(l[0]), min[1].min(l[1])]);
+        let luxels: Vec<[f32; 2]> = luxels
+            .into_iter()
+            .map(|l| [l[0] - min[0], l[1] - min[1]])
+            .collect();
+
+        let extent = luxels
+            .iter()
+            .fold([0.0_f32; 2], |max, l| [max[0].max(l[0]), max[1].max(l[1])]);
+
+        face_sizes.push(extent.map(|e| e.ceil() + 1.0 + 2.0 * PADDING));
+        face_luxels.push(luxels);
+    }
+
+    let (offsets, atlas_size) = pack(&face_sizes);
+
+    face_luxels
+        .iter()
+        .zip(offsets)
+        .flat_map(|(luxels, offset)| {
+            luxels.iter().flat_map(move |l| {
+                let u = (offset[0] + PADDING + l[0]) / atlas_size;
+                let v = (offset[1] + PADDING + l[1]) / atlas_size;
+
+                // blender has inverted v axis compared to Source
+                [u, 1.0 - v]
+            })
+        })
+        .collect()
+}
+
+/// Packs the rectangles into rows of a square atlas, tallest first.
+/// Returns the offsets of the rectangles and the size of the atlas.
+fn pack(sizes: &[[f32; 2]]) -> (Vec<[f32; 2]>, f32) {
+    let area: f32 = sizes.iter().map(|[w, h]| w * h).sum();
+    let widest = sizes.iter().map(|[w, _]| *w).fold(0.0, f32::max);
+    let row_width = area.sqrt().ceil().max(widest);
+
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|&a, &b| sizes[b][1].total_cmp(&sizes[a][1]));
+
+    let mut offsets = vec![[0.0; 2]; sizes.len()];
+    let (mut x, mut y, mut row_height) = (0.0, 0.0, 0.0_f32);
+
+    for index in order {
+        let [width, height] = sizes[index];
+
+        if x + width > row_width {
+            x = 0.0;
+            y += row_height;
+            row_height = 0.0;
+        }
+
+        offsets[index] = [x, y];
+        x += width;
+        row_height = row_height.max(height);
+    }
+
+    // the atlas is square, so that luxels stay square in uv space
+    (offsets, row_width.max(y + row_height).max(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::keyvalues;
+
+    #[test]
+    fn layout_lightmap_uvs() {
+        let vmf = keyvalues::parse_raw(
+            r#"
+            world
+            {
+                solid
+                {
+                    "id" "1"
+                    side
+                    {
+                        "plane" "(0 0 64) (64 0 64) (64 -64 64)"
+                        "uaxis" "[1 0 0 0] 0.25"
+                        "vaxis" "[0 -1 0 0] 0.25"
+                        "lightmapscale" "32"
+                    }
+                    side
+                    {
+                        "plane" "(64 0 0) (64 0 64) (64 64 0)"
+                        "uaxis" "[0 1 0 0] 0.25"
+                        "vaxis" "[0 0 -1 0] 0.25"
+                    }
+                }
+                solid
+                {
+                    "id" "2"
+                    side
+                    {
+                        "plane" "(128 0 64) (192 0 64) (192 -64 64)"
+                        "uaxis" "[0 1 0 0] 0.25"
+                        "vaxis" "[1 0 0 0] 0.25"
+                        "lightmapscale" "8"
+                    }
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+        let sides = LightmapSides::from_vmf(&vmf);
+
+        // coplanar sides of different solids keep their own axes
+        let axes = sides.find(Some(1), Vec3::Z, 64.0, Vec3::ZERO).unwrap();
+        assert!((axes.scale - 32.0).abs() < f32::EPSILON);
+        let axes = sides.find(Some(2), Vec3::Z, 64.0, Vec3::ZERO).unwrap();
+        assert!((axes.scale - 8.0).abs() < f32::EPSILON);
+        assert_eq!(axes.u, Vec3::Y);
+
+        // faces of merged solids use the solid they're on
+        let center = Vec3::new(160.0, 32.0, 64.0);
+        let axes = sides.find(None, Vec3::Z, 64.0, center).unwrap();
+        assert!((axes.scale - 8.0).abs() < f32::EPSILON);
+
+        let square = |z: f32| {
+            vec![
+                Vec3::new(0.0, 0.0, z),
+                Vec3::new(64.0, 0.0, z),
+                Vec3::new(64.0, 64.0, z),
+                Vec3::new(0.0, 64.0, z),
+            ]
+        };
+
+        // the top face uses the side scale, the other one the default scale
+        let uvs = lightmap_uvs(&sides, Some(1), &[square(64.0), square(128.0)]);
+        assert_eq!(uvs.len(), 16);
+        assert!(uvs.iter().all(|uv| (0.0..=1.0).contains(uv)));
+
+        let top_width = (uvs[2] - uvs[0]).abs();
+        let other_width = (uvs[10] - uvs[8]).abs();
+        assert!((other_width - top_width * 2.0).abs() < 1e-6);
+
+        let (offsets, size) = pack(&[[2.0, 2.0], [4.0, 4.0], [2.0, 2.0], [2.0, 1.0]]);
+        assert!((size - 6.0).abs() < f32::EPSILON);
+        assert_eq!(offsets, [[4.0, 0.0], [0.0, 0.0], [0.0, 4.0], [2.0, 4.0]]);
+    }
+}
@@ -0,0 +1,212 @@
+//! Records the game paths of the assets loaded during an import, and reports which
+//! search paths contain them. The first search path containing a file is used, so an
+//! outdated addon can silently override the files of the game.
+
+use std::{
+    collections::BTreeSet,
+    path::Path as StdPath,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use plumber_core::fs::{FileSystem, OpenFileSystem, SearchPath};
+use pyo3::prelude::*;
+use tracing::warn;
+
+use crate::{filesystem, game_path};
+
+/// Looked up game paths, shared between the asset handler and the importer.
+#[derive(Debug, Clone, Default)]
+pub struct LookupLog(Arc<Mutex<BTreeSet<String>>>);
+
+impl LookupLog {
+    fn paths(&self) -> MutexGuard<'_, BTreeSet<String>> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Records a looked up path. Paths on the os file system are not recorded.
+    pub fn record(&self, path: &str) {
+        if !StdPath::new(path).is_absolute() {
+            self.paths().insert(game_path::normalize(path));
+        }
+    }
+
+    /// Records a looked up texture. Textures are named without their extension, but
+    /// the search paths contain the `.vtf` files.
+    pub fn record_texture(&self, name: &str) {
+        if name.to_ascii_lowercase().ends_with(".vtf") {
+            self.record(name);
+        } else {
+            self.record(&format!("{name}.vtf"));
+        }
+    }
+
+    /// Whether a path was looked up, like a material which was already loaded.
+    pub fn contains(&self, path: &str) -> bool {
+        self.paths().contains(&game_path::normalize(path))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[pyclass(module = "plumber", name = "PathLookup")]
+pub struct PathLookup {
+    path: String,
+    found: bool,
+    /// Search paths containing the file, in the order they are searched.
+    search_paths: Vec<(String, String)>,
+}
+
+#[pymethods]
+impl PathLookup {
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Whether the file was found, possibly in the files embedded into the map.
+    fn found(&self) -> bool {
+        self.found
+    }
+
+    /// The search path the file was read from, if it's one of the game's search paths.
+    fn served_by(&self) -> Option<(String, String)> {
+        self.search_paths.first().cloned()
+    }
+
+    /// Other search paths containing the file, which were ignored.
+    fn shadowed(&self) -> Vec<(String, String)> {
+        self.search_paths.iter().skip(1).cloned().collect()
+    }
+
+    fn is_conflict(&self) -> bool {
+        self.search_paths.len() > 1
+    }
+}
+
+/// Checks which of the search paths contain the looked up paths. `file_system` is the
+/// file system of the import, which can also contain the files embedded into the map.
+///
+/// # Errors
+///
+/// Returns `Err` if a search path is not valid unicode.
+pub fn report(
+    log: &LookupLog,
+    search_paths: &[SearchPath],
+    file_system: &OpenFileSystem,
+) -> PyResult<Vec<PathLookup>> {
+    let paths: Vec<String> = log.paths().iter().cloned().collect();
+
+    let mut lookups: Vec<PathLookup> = paths
+        .into_iter()
+        .map(|path| PathLookup {
+            found: file_system.open_file(&game_path::game_path(&path)).is_ok(),
+            path,
+            search_paths: Vec::new(),
+        })
+        .collect();
+
+    for search_path in search_paths {
+        let (kind, path) = filesystem::from_search_path(search_path)?;
+
+        // each search path is opened alone to see which files it contains
+        let single = FileSystem {
+            name: path.clone(),
+            search_paths: vec![search_path.clone()],
+        };
+        let opened = match single.open() {
+            Ok(opened) => opened,
+            Err(error) => {
+                warn!("search path `{path}` could not be opened: {error}");
+                continue;
+            }
+        };
+
+        for lookup in &mut lookups {
+            if opened
+                .open_file(&game_path::game_path(&lookup.path))
+                .is_ok()
+            {
+                lookup.search_paths.push((kind.to_owned(), path.clone()));
+            }
+        }
+    }
+
+    Ok(lookups)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use super::*;
+
+    fn search_dir(name: &str, files: &[&str]) -> PathBuf {
+        let dir = env::temp_dir().join("plumber_lookups_test").join(name);
+        let _ = fs::remove_dir_all(&dir);
+
+        for file in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn report_conflicts() {
+        let addon = search_dir("addon", &["materials/brick/brickwall001.vmt"]);
+        let game = search_dir(
+            "game",
+            &[
+                "materials/brick/brickwall001.vmt",
+                "materials/brick/brickwall001.vtf",
+                "models/props/crate.mdl",
+            ],
+        );
+
+        let search_paths = vec![
+            SearchPath::Directory(addon.clone()),
+            SearchPath::Directory(game.clone()),
+        ];
+        let file_system = FileSystem {
+            name: "test".to_owned(),
+            search_paths: search_paths.clone(),
+        }
+        .open()
+        .unwrap();
+
+        let log = LookupLog::default();
+        log.record("Materials\\Brick\\BrickWall001.vmt");
+        log.record_texture("materials/brick/brickwall001");
+        log.record("models/props/crate.mdl");
+        log.record("models/props/missing.mdl");
+
+        let lookups = report(&log, &search_paths, &file_system).unwrap();
+
+        let wall = &lookups[0];
+        assert_eq!(wall.path, "materials/brick/brickwall001.vmt");
+        assert!(wall.is_conflict());
+        assert_eq!(
+            wall.served_by(),
+            Some(("DIR".to_owned(), addon.to_str().unwrap().to_owned()))
+        );
+        assert_eq!(
+            wall.shadowed(),
+            [("DIR".to_owned(), game.to_str().unwrap().to_owned())]
+        );
+
+        let texture = &lookups[1];
+        assert_eq!(texture.path, "materials/brick/brickwall001.vtf");
+        assert!(texture.found && !texture.is_conflict());
+        assert_eq!(
+            texture.served_by(),
+            Some(("DIR".to_owned(), game.to_str().unwrap().to_owned()))
+        );
+
+        assert!(lookups[2].found && !lookups[2].is_conflict());
+        assert!(!lookups[3].found && lookups[3].served_by().is_none());
+
+        fs::remove_dir_all(addon.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,141 @@
+use std::io::Read;
+
+use pyo3::{exceptions::PyValueError, PyResult};
+use tracing::{debug, warn};
+
+use plumber_core::fs::{GamePathBuf, OpenFileSystem};
+
+use crate::keyvalues;
+
+const MANIFEST_PATH: &str = "particles/particles_manifest.txt";
+
+/// A particle system file listed in a particle manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub path: String,
+    /// `!file` entries are precached by the engine on startup.
+    pub preload: bool,
+}
+
+/// Lists the particle system files of a game, including the map-specific manifest if given.
+/// Games without a manifest get every `.pcf` file in the `particles` directory listed instead.
+pub fn read_manifest(
+    file_system: &OpenFileSystem,
+    map_name: Option<&str>,
+) -> PyResult<Vec<ManifestEntry>> {
+    let mut entries = match read_text(file_system, MANIFEST_PATH) {
+        Some(text) => parse_manifest(&text)
+            .map_err(|e| PyValueError::new_err(format!("`{MANIFEST_PATH}`: {e}")))?,
+        None => {
+            debug!("no particle manifest found, listing particle files instead");
+            list_particle_files(file_system)
+        }
+    };
+
+    if let Some(map_name) = map_name {
+        let map_manifest_path = format!("maps/{map_name}_particles.txt");
+
+        if let Some(text) = read_text(file_system, &map_manifest_path) {
+            match parse_manifest(&text) {
+                Ok(map_entries) => {
+                    for entry in map_entries {
+                        if !entries.iter().any(|e| e.path == entry.path) {
+                            entries.push(entry);
+                        }
+                    }
+                }
+                Err(err) => warn!("`{}`: {}", map_manifest_path, err),
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+fn read_text(file_system: &OpenFileSystem, path: &str) -> Option<String> {
+    let mut file = file_system.open_file(&GamePathBuf::from(path)).ok()?;
+    let mut data = Vec::new();
+
+    if let Err(err) = file.read_to_end(&mut data) {
+        warn!("could not read `{}`: {}", path, err);
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&data).into_owned())
+}
+
+fn parse_manifest(text: &str) -> Result<Vec<ManifestEntry>, keyvalues::ParseError> {
+    let parsed = keyvalues::parse(text)?;
+
+    Ok(parsed
+        .iter()
+        .filter_map(|(_, value)| value.as_block())
+        .flatten()
+        .filter_map(|(key, value)| {
+            let path = value.as_value()?;
+
+            let preload = if key.eq_ignore_ascii_case("!file") {
+                true
+            } else if key.eq_ignore_ascii_case("file") {
+                false
+            } else {
+                return None;
+            };
+
+            Some(ManifestEntry {
+                path: path.replace('\\', "/"),
+                preload,
+            })
+        })
+        .collect())
+}
+
+fn list_particle_files(file_system: &OpenFileSystem) -> Vec<ManifestEntry> {
+    let mut entries = Vec::new();
+
+    for res in file_system.read_dir(&GamePathBuf::from("particles")) {
+        match res {
+            Ok(entry) => {
+                let path = entry.path().as_str().to_owned();
+
+                if !entry.entry_type().is_directory() && path.to_ascii_lowercase().ends_with(".pcf")
+                {
+                    entries.push(ManifestEntry {
+                        path,
+                        preload: false,
+                    });
+                }
+            }
+            Err(err) => warn!("could not list particle files: {}", err),
+        }
+    }
+
+    entries.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+    entries.dedup();
+
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_particle_manifest() {
+        let text = r#"
+            particles_manifest
+            {
+                "!file" "particles/blood_impact.pcf"
+                "file" "particles\water_impact.pcf"
+                "file" "particles/console_only.pcf" [$X360]
+            }
+        "#;
+
+        let entries = parse_manifest(text).unwrap();
+
+        assert_eq!(entries.len(), 3);
+        assert!(entries[0].preload);
+        assert_eq!(entries[1].path, "particles/water_impact.pcf");
+        assert!(!entries[1].preload);
+    }
+}
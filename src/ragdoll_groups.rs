@@ -0,0 +1,162 @@
+//! Groups the props of the same model which are constrained together, like the parts of
+//! a vehicle or a ragdoll split into several entities, so that their armatures can be
+//! merged into one. The constraints aren't kept by the entity parser, so they are read
+//! from the vmf directly.
+
+use std::collections::HashMap;
+
+use crate::{game_path, keyvalues::KeyValue, kv_utils::block_value};
+
+/// Entities which constrain the entities named by their `attach1` and `attach2`.
+const CONSTRAINT_CLASSES: [&str; 7] = [
+    "phys_ballsocket",
+    "phys_constraint",
+    "phys_hinge",
+    "phys_lengthconstraint",
+    "phys_pulleyconstraint",
+    "phys_ragdollconstraint",
+    "phys_slideconstraint",
+];
+
+/// The group of each grouped prop by entity id. Props which aren't constrained to another
+/// prop of the same model are left out.
+#[derive(Debug, Clone, Default)]
+pub struct RagdollGroups {
+    groups: HashMap<i32, i32>,
+}
+
+struct Prop<'a> {
+    id: i32,
+    name: &'a str,
+    model: String,
+}
+
+impl RagdollGroups {
+    pub fn from_vmf(vmf: &[(String, KeyValue)]) -> Self {
+        let entities: Vec<&[(String, KeyValue)]> = vmf
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case("entity"))
+            .filter_map(|(_, v)| v.as_block())
+            .collect();
+
+        let props: Vec<Prop> = entities
+            .iter()
+            .filter_map(|entity| {
+                Some(Prop {
+                    id: block_value(entity, "id")?.trim().parse().ok()?,
+                    name: block_value(entity, "targetname").filter(|n| !n.is_empty())?,
+                    model: game_path::normalize(block_value(entity, "model")?),
+                })
+            })
+            .collect();
+
+        let mut parents: Vec<usize> = (0..props.len()).collect();
+
+        let constraints = entities.iter().filter(|entity| {
+            block_value(entity, "classname").is_some_and(|class| {
+                CONSTRAINT_CLASSES
+                    .iter()
+                    .any(|c| c.eq_ignore_ascii_case(class))
+            })
+        });
+
+        for constraint in constraints {
+            let (Some(attach1), Some(attach2)) = (
+                block_value(constraint, "attach1"),
+                block_value(constraint, "attach2"),
+            ) else {
+                continue;
+            };
+
+            // several entities can share a name, and are all constrained then
+            let named = |name: &str| {
+                props
+                    .iter()
+                    .enumerate()
+                    .filter(move |(_, p)| p.name.eq_ignore_ascii_case(name))
+                    .map(|(i, _)| i)
+                    .collect::<Vec<_>>()
+            };
+            let (first, second) = (named(attach1), named(attach2));
+
+            for &a in &first {
+                for &b in &second {
+                    if a != b && props[a].model == props[b].model {
+                        union(&mut parents, a, b);
+                    }
+                }
+            }
+        }
+
+        let mut members: HashMap<usize, Vec<i32>> = HashMap::new();
+        for (i, prop) in props.iter().enumerate() {
+            members
+                .entry(find(&mut parents, i))
+                .or_default()
+                .push(prop.id);
+        }
+
+        // groups are identified by their lowest entity id, which is stable across imports
+        let groups = members
+            .into_values()
+            .filter(|ids| ids.len() > 1)
+            .flat_map(|ids| {
+                let group = ids.iter().copied().min().unwrap_or_default();
+                ids.into_iter().map(move |id| (id, group))
+            })
+            .collect();
+
+        Self { groups }
+    }
+
+    /// The group of a prop, if it's constrained to another prop of the same model.
+    pub fn group(&self, id: i32) -> Option<i32> {
+        self.groups.get(&id).copied()
+    }
+}
+
+fn find(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+
+    parents[i] = root;
+    root
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parents, a), find(parents, b));
+    parents[a.max(b)] = a.min(b);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::keyvalues;
+
+    #[test]
+    fn group_constrained_props() {
+        let vmf = keyvalues::parse_raw(
+            r#"
+            entity { "id" "2" "classname" "prop_ragdoll" "targetname" "car_body" "model" "models/Car.mdl" }
+            entity { "id" "3" "classname" "prop_ragdoll" "targetname" "car_wheel" "model" "models/car.mdl" }
+            entity { "id" "4" "classname" "prop_ragdoll" "targetname" "car_wheel" "model" "models/car.mdl" }
+            entity { "id" "5" "classname" "prop_physics" "targetname" "crate" "model" "models/crate.mdl" }
+            entity { "id" "6" "classname" "prop_ragdoll" "targetname" "lone" "model" "models/car.mdl" }
+            entity { "id" "7" "classname" "phys_hinge" "attach1" "car_body" "attach2" "car_wheel" }
+            entity { "id" "8" "classname" "phys_ballsocket" "attach1" "car_body" "attach2" "crate" }
+            entity { "id" "9" "classname" "logic_relay" "attach1" "car_body" "attach2" "lone" }
+            "#,
+        )
+        .unwrap();
+
+        let groups = RagdollGroups::from_vmf(&vmf);
+        assert_eq!(groups.group(2), Some(2));
+        assert_eq!(groups.group(3), Some(2));
+        assert_eq!(groups.group(4), Some(2));
+        assert_eq!(groups.group(5), None);
+        assert_eq!(groups.group(6), None);
+    }
+}
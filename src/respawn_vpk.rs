@@ -0,0 +1,410 @@
+//! Reads the vpks of Respawn's games, like Titanfall and Apex Legends. Their entries are
+//! split into chunks which are usually LZHAM compressed, and only the directory file is
+//! named after its language.
+//!
+//! The file system can only search directories and Valve's vpks, so the archives are
+//! extracted into a directory to be mounted. Entries can't be read on demand until the
+//! file system supports other kinds of archives, so the extraction is kept between
+//! sessions and redone only when the directory file changes.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt::{self, Display, Formatter},
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use tracing::warn;
+
+use crate::{
+    bytes::{self, LeBytes},
+    game_path,
+};
+
+const MAGIC: u32 = 0x55AA_1234;
+const VERSION: (u16, u16) = (2, 3);
+const HEADER_SIZE: usize = 16;
+/// Follows the last chunk of an entry.
+const LAST_CHUNK: u16 = 0xFFFF;
+/// Dictionary size the entries are compressed with.
+const DICT_SIZE_LOG2: u32 = 20;
+/// Largest chunk size which is trusted from the directory. The games split the entries
+/// into chunks of 1 MiB, so larger chunks are from a corrupt directory.
+const MAX_CHUNK_SIZE: u64 = 16 << 20;
+/// Prefixes of directory files, which the data archives don't have.
+const LANGUAGES: &[&str] = &[
+    "english",
+    "french",
+    "german",
+    "italian",
+    "japanese",
+    "korean",
+    "polish",
+    "portuguese",
+    "russian",
+    "spanish",
+    "tchinese",
+    "schinese",
+    "mspanish",
+];
+
+#[derive(Debug)]
+pub enum RespawnVpkError {
+    InvalidHeader,
+    UnexpectedEof,
+    ChunkTooLarge(u64),
+    Decompression(String),
+    Io(io::Error),
+}
+
+impl Display for RespawnVpkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidHeader => f.write_str("invalid respawn vpk header"),
+            Self::UnexpectedEof => f.write_str("unexpected end of directory"),
+            Self::ChunkTooLarge(size) => write!(f, "chunk of {size} bytes is too large"),
+            Self::Decompression(e) => write!(f, "decompression failed: {e}"),
+            Self::Io(e) => write!(f, "io error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RespawnVpkError {}
+
+impl From<io::Error> for RespawnVpkError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Chunk {
+    offset: u64,
+    compressed_size: u64,
+    size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RespawnVpkEntry {
+    /// Path of the file in the game file system, like `models/props/crate.mdl`.
+    pub path: String,
+    archive_index: u16,
+    chunks: Vec<Chunk>,
+}
+
+/// A directory file whose entries have been read.
+pub struct RespawnVpk {
+    path: PathBuf,
+    pub entries: Vec<RespawnVpkEntry>,
+}
+
+impl RespawnVpk {
+    /// Reads the entries of a directory file.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the file can't be read or the directory is invalid.
+    pub fn open(path: &Path) -> Result<Self, RespawnVpkError> {
+        let data = fs::read(path)?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            entries: read_directory(&data)?,
+        })
+    }
+
+    /// Extracts the files into `target_dir`, decompressing their chunks. Files with paths
+    /// outside of the directory are skipped, and so are files which can't be read, with a
+    /// warning. Returns the number of extracted files.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the target directory can't be created.
+    pub fn extract(&self, target_dir: &Path) -> Result<usize, RespawnVpkError> {
+        fs::create_dir_all(target_dir)?;
+
+        let mut archives = HashMap::new();
+        let mut extracted = 0;
+
+        for entry in &self.entries {
+            if !game_path::is_safe_to_extract(&entry.path) {
+                continue;
+            }
+
+            let target = target_dir.join(&entry.path);
+
+            match self.extract_entry(entry, &mut archives, &target) {
+                Ok(()) => extracted += 1,
+                Err(error) => {
+                    warn!("respawn vpk entry `{}`: {error}, skipping", entry.path);
+                    // don't leave partially written files to be mounted
+                    let _ = fs::remove_file(&target);
+                }
+            }
+        }
+
+        Ok(extracted)
+    }
+
+    fn extract_entry(
+        &self,
+        entry: &RespawnVpkEntry,
+        archives: &mut HashMap<u16, File>,
+        target: &Path,
+    ) -> Result<(), RespawnVpkError> {
+        let archive = match archives.entry(entry.archive_index) {
+            Entry::Occupied(archive) => archive.into_mut(),
+            Entry::Vacant(vacant) => {
+                vacant.insert(File::open(archive_path(&self.path, entry.archive_index))?)
+            }
+        };
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = File::create(target)?;
+
+        for chunk in &entry.chunks {
+            if chunk.size > MAX_CHUNK_SIZE {
+                return Err(RespawnVpkError::ChunkTooLarge(chunk.size));
+            }
+
+            // the size isn't trusted to allocate more than the archive has
+            let mut data = Vec::new();
+            archive.seek(SeekFrom::Start(chunk.offset))?;
+            Read::by_ref(archive)
+                .take(chunk.compressed_size)
+                .read_to_end(&mut data)?;
+
+            if data.len() as u64 != chunk.compressed_size {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+
+            if chunk.compressed_size == chunk.size {
+                file.write_all(&data)?;
+            } else {
+                let size = usize::try_from(chunk.size).unwrap_or(0);
+                file.write_all(&decompress(&data, size)?)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns if the file is the directory of a Respawn vpk.
+pub fn is_respawn_vpk(path: &Path) -> bool {
+    let mut header = [0; 8];
+
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok()
+        && u32::from_le_bytes([header[0], header[1], header[2], header[3]]) == MAGIC
+        && (
+            u16::from_le_bytes([header[4], header[5]]),
+            u16::from_le_bytes([header[6], header[7]]),
+        ) == VERSION
+}
+
+/// Returns the path of a data archive of a directory file, which is named like
+/// `englishclient_mp_common.bsp.pak000_dir.vpk` while its archives are named like
+/// `client_mp_common.bsp.pak000_000.vpk`.
+fn archive_path(dir_path: &Path, index: u16) -> PathBuf {
+    let name = dir_path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let name = LANGUAGES
+        .iter()
+        .find_map(|language| name.strip_prefix(language))
+        .unwrap_or(&name);
+    let name = name.strip_suffix("_dir.vpk").unwrap_or(name);
+
+    dir_path.with_file_name(format!("{name}_{index:03}.vpk"))
+}
+
+fn read_directory(data: &[u8]) -> Result<Vec<RespawnVpkEntry>, RespawnVpkError> {
+    if read_le::<u32>(data, 0)? != MAGIC
+        || (read_le::<u16>(data, 4)?, read_le::<u16>(data, 6)?) != VERSION
+    {
+        return Err(RespawnVpkError::InvalidHeader);
+    }
+
+    let tree_size = read_le::<u32>(data, 8)? as usize;
+    let tree = data
+        .get(HEADER_SIZE..HEADER_SIZE + tree_size)
+        .ok_or(RespawnVpkError::UnexpectedEof)?;
+
+    let mut offset = 0;
+    let mut entries = Vec::new();
+
+    loop {
+        let extension = read_string(tree, &mut offset)?;
+        if extension.is_empty() {
+            break;
+        }
+
+        loop {
+            let dir = read_string(tree, &mut offset)?;
+            if dir.is_empty() {
+                break;
+            }
+
+            loop {
+                let name = read_string(tree, &mut offset)?;
+                if name.is_empty() {
+                    break;
+                }
+
+                // crc, then the preload size which is always zero
+                let archive_index = read_le::<u16>(tree, offset + 6)?;
+                offset += 8;
+
+                let mut chunks = Vec::new();
+
+                loop {
+                    // load and texture flags precede the chunk
+                    chunks.push(Chunk {
+                        offset: read_le::<u64>(tree, offset + 6)?,
+                        compressed_size: read_le::<u64>(tree, offset + 14)?,
+                        size: read_le::<u64>(tree, offset + 22)?,
+                    });
+                    offset += 30;
+
+                    let end = read_le::<u16>(tree, offset)?;
+                    offset += 2;
+
+                    if end == LAST_CHUNK {
+                        break;
+                    }
+                }
+
+                let path = if dir.trim().is_empty() {
+                    format!("{name}.{extension}")
+                } else {
+                    format!("{dir}/{name}.{extension}")
+                };
+
+                entries.push(RespawnVpkEntry {
+                    path: game_path::normalize(&path),
+                    archive_index,
+                    chunks,
+                });
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+fn decompress(data: &[u8], size: usize) -> Result<Vec<u8>, RespawnVpkError> {
+    let options = lzham::decompress::DecompressionOptions {
+        dict_size_log2: DICT_SIZE_LOG2,
+        ..Default::default()
+    };
+
+    let mut output = Vec::with_capacity(size);
+    let status =
+        lzham::decompress::decompress_with_options(&mut &data[..], &mut output, size, &options);
+
+    if !status.is_success() || output.len() != size {
+        return Err(RespawnVpkError::Decompression(format!("{status:?}")));
+    }
+
+    Ok(output)
+}
+
+fn read_string(data: &[u8], offset: &mut usize) -> Result<String, RespawnVpkError> {
+    let rest = data.get(*offset..).ok_or(RespawnVpkError::UnexpectedEof)?;
+    let len = rest
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(RespawnVpkError::UnexpectedEof)?;
+
+    *offset += len + 1;
+
+    Ok(String::from_utf8_lossy(&rest[..len]).into_owned())
+}
+
+fn read_le<T: LeBytes>(data: &[u8], offset: usize) -> Result<T, RespawnVpkError> {
+    bytes::read_le(data, offset).ok_or(RespawnVpkError::UnexpectedEof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directory(files: &[(&str, &str, &str, u64, u64)]) -> Vec<u8> {
+        let mut tree = Vec::new();
+
+        for (extension, dir, name, offset, size) in files {
+            for part in [extension, dir, name] {
+                tree.extend_from_slice(part.as_bytes());
+                tree.push(0);
+            }
+
+            tree.extend_from_slice(&[0; 6]);
+            tree.extend_from_slice(&0_u16.to_le_bytes());
+            tree.extend_from_slice(&[0; 6]);
+            tree.extend_from_slice(&offset.to_le_bytes());
+            tree.extend_from_slice(&size.to_le_bytes());
+            tree.extend_from_slice(&size.to_le_bytes());
+            tree.extend_from_slice(&LAST_CHUNK.to_le_bytes());
+            // end of the names and the directories of the extension
+            tree.extend_from_slice(&[0; 2]);
+        }
+
+        tree.push(0);
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&MAGIC.to_le_bytes());
+        data.extend_from_slice(&VERSION.0.to_le_bytes());
+        data.extend_from_slice(&VERSION.1.to_le_bytes());
+        data.extend_from_slice(&u32::try_from(tree.len()).unwrap().to_le_bytes());
+        data.extend_from_slice(&0_u32.to_le_bytes());
+        data.extend_from_slice(&tree);
+        data
+    }
+
+    #[test]
+    fn extract_stored_entries() {
+        let dir =
+            std::env::temp_dir().join(format!("plumber_respawn_vpk_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let dir_path = dir.join("englishclient_mp_common.bsp.pak000_dir.vpk");
+        fs::write(
+            &dir_path,
+            directory(&[
+                ("vmt", "Materials/Dev", "grid", 0, 4),
+                ("txt", " ", "readme", 4, 2),
+                // past the end of the archive
+                ("txt", " ", "truncated", 4, 64),
+            ]),
+        )
+        .unwrap();
+        fs::write(dir.join("client_mp_common.bsp.pak000_000.vpk"), b"gridhi").unwrap();
+
+        assert!(is_respawn_vpk(&dir_path));
+
+        let vpk = RespawnVpk::open(&dir_path).unwrap();
+        assert_eq!(vpk.entries[0].path, "materials/dev/grid.vmt");
+        assert_eq!(vpk.entries[1].path, "readme.txt");
+
+        let target = dir.join("extracted");
+        // the truncated entry is skipped
+        assert_eq!(vpk.extract(&target).unwrap(), 2);
+        assert!(!target.join("truncated.txt").exists());
+        assert_eq!(
+            fs::read(target.join("materials/dev/grid.vmt")).unwrap(),
+            b"grid"
+        );
+        assert_eq!(fs::read(target.join("readme.txt")).unwrap(), b"hi");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,357 @@
+//! Lists the files a vmf depends on without importing it, so that missing files can be
+//! found before a long import floods the log with errors.
+//!
+//! Materials, models and skybox textures referenced by the map are followed into the
+//! textures of the materials and the materials of the models, but nothing is built.
+
+use std::collections::BTreeMap;
+
+use plumber_core::fs::OpenFileSystem;
+use pyo3::prelude::*;
+
+use crate::{
+    asset::studio,
+    game_path::{self, material_name},
+    keyvalues::{self, KeyValue},
+    kv_utils::{block_value, TEXTURE_PARAMETERS},
+};
+
+/// Suffixes of the skybox materials, in the order the game loads them.
+const SKYBOX_SIDES: [&str; 6] = ["rt", "lf", "bk", "ft", "up", "dn"];
+
+/// Files of a model besides the mdl, which the model can't be loaded without.
+const MODEL_COMPANIONS: [&str; 2] = [".vvd", ".dx90.vtx"];
+
+/// Access to the files of the scanned map.
+pub trait Files {
+    fn read(&self, path: &str) -> Option<Vec<u8>>;
+
+    fn exists(&self, path: &str) -> bool;
+}
+
+impl Files for OpenFileSystem {
+    fn read(&self, path: &str) -> Option<Vec<u8>> {
+        OpenFileSystem::read(self, &game_path::game_path(path).into()).ok()
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.open_file(&game_path::game_path(path)).is_ok()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[pyclass(module = "plumber", name = "ScannedFile")]
+pub struct ScannedFile {
+    kind: &'static str,
+    path: String,
+    found: bool,
+    references: usize,
+}
+
+#[pymethods]
+impl ScannedFile {
+    /// Kind of the file, `material`, `texture`, `model` or `skybox`.
+    fn kind(&self) -> &str {
+        self.kind
+    }
+
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn found(&self) -> bool {
+        self.found
+    }
+
+    /// Number of times the file is referenced by the map or the other files.
+    fn references(&self) -> usize {
+        self.references
+    }
+}
+
+/// The files a map depends on, by their lowercase path.
+#[derive(Debug, Clone, Default)]
+#[pyclass(module = "plumber", name = "ScanManifest")]
+pub struct ScanManifest {
+    files: BTreeMap<String, ScannedFile>,
+}
+
+#[pymethods]
+impl ScanManifest {
+    fn files(&self) -> Vec<ScannedFile> {
+        self.files.values().cloned().collect()
+    }
+
+    fn missing(&self) -> Vec<ScannedFile> {
+        self.files.values().filter(|f| !f.found).cloned().collect()
+    }
+
+    /// Number of missing files of each kind.
+    fn missing_counts(&self) -> BTreeMap<&'static str, usize> {
+        let mut counts = BTreeMap::new();
+
+        for file in self.files.values().filter(|f| !f.found) {
+            *counts.entry(file.kind).or_default() += 1;
+        }
+
+        counts
+    }
+}
+
+struct Scanner<'a, F> {
+    files: &'a F,
+    manifest: ScanManifest,
+}
+
+impl<'a, F: Files> Scanner<'a, F> {
+    /// Adds a reference to a file. Returns if the file was not seen before and exists,
+    /// so that its own references should be scanned.
+    fn add(&mut self, kind: &'static str, path: String) -> bool {
+        if let Some(file) = self.manifest.files.get_mut(&path) {
+            file.references += 1;
+            return false;
+        }
+
+        let found = self.files.exists(&path);
+
+        self.manifest.files.insert(
+            path.clone(),
+            ScannedFile {
+                kind,
+                path,
+                found,
+                references: 1,
+            },
+        );
+
+        found
+    }
+
+    fn material(&mut self, kind: &'static str, name: &str) {
+        let path = format!("materials/{}.vmt", material_name(name, ".vmt"));
+
+        if self.add(kind, path.clone()) {
+            self.material_textures(&path);
+        }
+    }
+
+    fn material_textures(&mut self, path: &str) {
+        let Some(vmt) = self.files.read(path) else {
+            return;
+        };
+        let Ok(root) = keyvalues::parse_raw(&String::from_utf8_lossy(&vmt)) else {
+            return;
+        };
+
+        for (shader, block) in &root {
+            let Some(block) = block.as_block() else {
+                continue;
+            };
+
+            // patch materials include their base material
+            if shader.eq_ignore_ascii_case("patch") {
+                if let Some(include) = block_value(block, "include") {
+                    let include = game_path::normalize(include);
+
+                    if self.add("material", include.clone()) {
+                        self.material_textures(&include);
+                    }
+                }
+            }
+
+            self.block_textures(block);
+        }
+    }
+
+    fn block_textures(&mut self, block: &[(String, KeyValue)]) {
+        for (key, value) in block {
+            match value {
+                // patch parameters and fallbacks are nested
+                KeyValue::Block(nested) => self.block_textures(nested),
+                KeyValue::Value(texture) => {
+                    let is_texture = TEXTURE_PARAMETERS
+                        .iter()
+                        .any(|p| key.eq_ignore_ascii_case(p));
+
+                    // `_rt_` textures are rendered by the game
+                    if is_texture && !texture.is_empty() && !texture.starts_with("_rt_") {
+                        let path = format!("materials/{}.vtf", material_name(texture, ".vtf"));
+                        self.add("texture", path);
+                    }
+                }
+            }
+        }
+    }
+
+    fn model(&mut self, name: &str) {
+        let path = game_path::normalize(name);
+
+        if !self.add("model", path.clone()) {
+            return;
+        }
+
+        let stem = path.strip_suffix(".mdl").unwrap_or(&path);
+        for companion in MODEL_COMPANIONS {
+            self.add("model", format!("{stem}{companion}"));
+        }
+
+        let Some(mdl) = self.files.read(&path) else {
+            return;
+        };
+        let (Ok(textures), Ok(dirs)) = (studio::texture_names(&mdl), studio::material_dirs(&mdl))
+        else {
+            return;
+        };
+
+        for texture in textures {
+            // the first directory containing the material is used, like the game does
+            let candidates: Vec<_> = dirs
+                .iter()
+                .map(|dir| material_name(&format!("{dir}/{texture}"), ".vmt"))
+                .collect();
+
+            let name = candidates
+                .iter()
+                .find(|c| self.files.exists(&format!("materials/{c}.vmt")))
+                .or_else(|| candidates.first())
+                .cloned()
+                .unwrap_or_else(|| material_name(&texture, ".vmt"));
+
+            self.material("material", &name);
+        }
+    }
+
+    fn entity(&mut self, entity: &[(String, KeyValue)]) {
+        if let Some(model) = block_value(entity, "model").map(str::trim) {
+            let lower = model.to_ascii_lowercase();
+
+            if lower.ends_with(".mdl") {
+                self.model(model);
+            } else if lower.ends_with(".vmt") {
+                // sprites reference their material directly
+                self.material("material", model);
+            }
+        }
+
+        // overlays and decals
+        for key in ["material", "texture"] {
+            if let Some(material) = block_value(entity, key).filter(|m| !m.trim().is_empty()) {
+                self.material("material", material);
+            }
+        }
+
+        self.solids(entity);
+    }
+
+    /// Scans the materials of all brush sides in the block.
+    fn solids(&mut self, block: &[(String, KeyValue)]) {
+        for (key, value) in block {
+            let Some(nested) = value.as_block() else {
+                continue;
+            };
+
+            if key.eq_ignore_ascii_case("side") {
+                if let Some(material) = block_value(nested, "material") {
+                    self.material("material", material);
+                }
+            } else if key.eq_ignore_ascii_case("solid") {
+                self.solids(nested);
+            }
+        }
+    }
+}
+
+/// Scans the files referenced by a parsed vmf.
+pub fn scan(vmf: &[(String, KeyValue)], files: &impl Files) -> ScanManifest {
+    let mut scanner = Scanner {
+        files,
+        manifest: ScanManifest::default(),
+    };
+
+    for (key, value) in vmf {
+        let Some(block) = value.as_block() else {
+            continue;
+        };
+
+        if key.eq_ignore_ascii_case("world") {
+            if let Some(sky) = block_value(block, "skyname").filter(|s| !s.trim().is_empty()) {
+                for side in SKYBOX_SIDES {
+                    scanner.material("skybox", &format!("skybox/{}{side}", sky.trim()));
+                }
+            }
+
+            scanner.solids(block);
+        } else if key.eq_ignore_ascii_case("entity") {
+            scanner.entity(block);
+        }
+    }
+
+    scanner.manifest
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    impl Files for HashMap<&str, &str> {
+        fn read(&self, path: &str) -> Option<Vec<u8>> {
+            self.get(path).map(|f| f.as_bytes().to_vec())
+        }
+
+        fn exists(&self, path: &str) -> bool {
+            self.contains_key(path)
+        }
+    }
+
+    #[test]
+    fn scan_references() {
+        let vmf = keyvalues::parse_raw(
+            r#"
+            world
+            {
+                "classname" "worldspawn"
+                "skyname" "sky_day01_01"
+                solid
+                {
+                    side { "material" "BRICK/BrickWall001" }
+                    side { "material" "brick/brickwall001" }
+                    side { "material" "tools/toolsnodraw" }
+                }
+            }
+            entity
+            {
+                "classname" "prop_static"
+                "model" "models/props/crate.mdl"
+            }
+            "#,
+        )
+        .unwrap();
+
+        let files = HashMap::from([
+            (
+                "materials/brick/brickwall001.vmt",
+                r#"LightmappedGeneric { "$basetexture" "brick\brickwall001" "$bumpmap" "brick/brickwall001_normal" }"#,
+            ),
+            ("materials/brick/brickwall001.vtf", ""),
+            ("materials/skybox/sky_day01_01rt.vmt", "Sky {}"),
+        ]);
+
+        let manifest = scan(&vmf, &files);
+
+        let wall = &manifest.files["materials/brick/brickwall001.vmt"];
+        assert!(wall.found);
+        assert_eq!(wall.references, 2);
+
+        assert!(manifest.files["materials/brick/brickwall001.vtf"].found);
+        assert!(!manifest.files["materials/brick/brickwall001_normal.vtf"].found);
+        assert!(!manifest.files["materials/tools/toolsnodraw.vmt"].found);
+        assert!(manifest.files["materials/skybox/sky_day01_01rt.vmt"].found);
+        assert!(!manifest.files["models/props/crate.mdl"].found);
+
+        let missing = manifest.missing_counts();
+        assert_eq!(missing["skybox"], 5);
+        assert_eq!(missing["model"], 1);
+    }
+}
@@ -0,0 +1,166 @@
+//! Finds the area of the 3D skybox around the `sky_camera` entity, so that the brushes
+//! and props inside it can be assembled around the main map.
+
+use glam::Vec3;
+
+use crate::{
+    keyvalues::KeyValue,
+    kv_utils::{block_value, parse_vector},
+    world_faces::{world_faces, Face, EPSILON},
+};
+
+const SKY_CAMERA_CLASS: &str = "sky_camera";
+const SKYBOX_MATERIAL: &str = "tools/toolsskybox";
+
+const DIRECTIONS: [Vec3; 6] = [
+    Vec3::X,
+    Vec3::new(-1.0, 0.0, 0.0),
+    Vec3::Y,
+    Vec3::new(0.0, -1.0, 0.0),
+    Vec3::Z,
+    Vec3::new(0.0, 0.0, -1.0),
+];
+
+/// The box enclosing the 3D skybox, in vmf units.
+#[derive(Debug, Clone, Copy)]
+pub struct SkyboxArea {
+    min: Vec3,
+    max: Vec3,
+    /// Scale of the imported positions.
+    pub scale: f32,
+}
+
+impl SkyboxArea {
+    /// Finds the skybox area by casting rays from the sky camera to the skybox walls
+    /// around it. Walls without a skybox material are used if a direction never hits
+    /// one. Returns `None` if the map has no sky camera or it isn't enclosed.
+    pub fn from_vmf(vmf: &[(String, KeyValue)], scale: f32) -> Option<Self> {
+        let camera = vmf
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case("entity"))
+            .filter_map(|(_, v)| v.as_block())
+            .filter(|e| {
+                block_value(e, "classname")
+                    .is_some_and(|c| c.eq_ignore_ascii_case(SKY_CAMERA_CLASS))
+            })
+            .find_map(|e| block_value(e, "origin").and_then(parse_vector))?;
+
+        let faces = world_faces(vmf);
+        let mut min = camera;
+        let mut max = camera;
+
+        for direction in DIRECTIONS {
+            let distance = nearest_hit(&faces, camera, direction, true)
+                .or_else(|| nearest_hit(&faces, camera, direction, false))?;
+
+            let point = camera + direction * distance;
+            min = min.min(point);
+            max = max.max(point);
+        }
+
+        Some(Self { min, max, scale })
+    }
+
+    /// Returns if a point in vmf units is inside the skybox.
+    pub fn contains(&self, point: Vec3) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x)
+            && (self.min.y..=self.max.y).contains(&point.y)
+            && (self.min.z..=self.max.z).contains(&point.z)
+    }
+
+    /// Returns if an imported, scaled position is inside the skybox.
+    pub fn contains_scaled(&self, position: Vec3) -> bool {
+        self.contains(position / self.scale)
+    }
+}
+
+/// Returns the distance to the nearest face hit by a ray.
+fn nearest_hit(faces: &[Face], origin: Vec3, direction: Vec3, skybox_only: bool) -> Option<f32> {
+    faces
+        .iter()
+        .filter(|f| !skybox_only || f.material.eq_ignore_ascii_case(SKYBOX_MATERIAL))
+        .filter_map(|f| {
+            let denominator = f.normal.dot(direction);
+            if denominator.abs() < EPSILON {
+                return None;
+            }
+
+            let distance = (f.distance - f.normal.dot(origin)) / denominator;
+            (distance > 0.0 && f.contains(origin + direction * distance)).then_some(distance)
+        })
+        .min_by(f32::total_cmp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::keyvalues;
+
+    /// A hollow box of six walls with the given material.
+    fn hollow_box(min: [i32; 3], max: [i32; 3], material: &str) -> String {
+        let [x0, y0, z0] = min;
+        let [x1, y1, z1] = max;
+        let walls = [
+            // bottom, top
+            [x0, y0, z0 - 16, x1, y1, z0],
+            [x0, y0, z1, x1, y1, z1 + 16],
+            // sides
+            [x0 - 16, y0, z0, x0, y1, z1],
+            [x1, y0, z0, x1 + 16, y1, z1],
+            [x0, y0 - 16, z0, x1, y0, z1],
+            [x0, y1, z0, x1, y1 + 16, z1],
+        ];
+
+        walls
+            .iter()
+            .map(|&[ax, ay, az, bx, by, bz]| {
+                format!(
+                    r#"
+                    solid
+                    {{
+                        side {{ "plane" "({ax} {by} {bz}) ({bx} {by} {bz}) ({bx} {ay} {bz})" "material" "{material}" }}
+                        side {{ "plane" "({ax} {ay} {az}) ({bx} {ay} {az}) ({bx} {by} {az})" "material" "{material}" }}
+                        side {{ "plane" "({ax} {by} {bz}) ({ax} {ay} {bz}) ({ax} {ay} {az})" "material" "{material}" }}
+                        side {{ "plane" "({bx} {by} {az}) ({bx} {ay} {az}) ({bx} {ay} {bz})" "material" "{material}" }}
+                        side {{ "plane" "({bx} {by} {bz}) ({ax} {by} {bz}) ({ax} {by} {az})" "material" "{material}" }}
+                        side {{ "plane" "({bx} {ay} {az}) ({ax} {ay} {az}) ({ax} {ay} {bz})" "material" "{material}" }}
+                    }}
+                    "#
+                )
+            })
+            .collect::<Vec<_>>()
+            .concat()
+    }
+
+    #[test]
+    fn find_skybox_area() {
+        let vmf = format!(
+            r#"
+            world
+            {{
+                {}
+                {}
+            }}
+            entity
+            {{
+                "classname" "sky_camera"
+                "origin" "1000 0 64"
+                "scale" "16"
+            }}
+            "#,
+            hollow_box([900, -100, 0], [1100, 100, 128], "TOOLS/TOOLSSKYBOX"),
+            hollow_box([-512, -512, 0], [512, 512, 256], "dev/dev_measuregeneric01"),
+        );
+        let vmf = keyvalues::parse_raw(&vmf).unwrap();
+
+        let area = SkyboxArea::from_vmf(&vmf, 0.01).unwrap();
+        assert!(area.contains(Vec3::new(1090.0, -90.0, 10.0)));
+        assert!(area.contains_scaled(Vec3::new(10.0, 0.0, 0.5)));
+        assert!(!area.contains(Vec3::new(0.0, 0.0, 64.0)));
+        assert!(!area.contains(Vec3::new(1000.0, 0.0, 130.0)));
+
+        let no_camera = keyvalues::parse_raw("world { }").unwrap();
+        assert!(SkyboxArea::from_vmf(&no_camera, 1.0).is_none());
+    }
+}
@@ -0,0 +1,226 @@
+//! Looks up the smoothing groups of brush faces, which the built geometry doesn't keep.
+//!
+//! Faces are matched to the vmf sides of their solid by their plane. Faces of merged
+//! solids can't be traced back to their solid, so the solid is found by which one
+//! contains the face.
+
+use std::collections::HashMap;
+
+use glam::Vec3;
+
+use crate::{
+    keyvalues::KeyValue,
+    kv_utils::{block_value, parse_plane},
+};
+
+/// Resolution of the plane normal components in the lookup.
+const NORMAL_STEPS: f32 = 100.0;
+/// Resolution of the plane distance in the lookup, in units.
+const DISTANCE_STEPS: f32 = 8.0;
+/// How far outside of a solid a face may be and still be considered part of it, in units.
+const CONTAINS_EPSILON: f32 = 0.5;
+
+pub type PlaneKey = [i32; 4];
+
+/// Quantizes a plane, so that faces can be matched to the vmf sides they were built from.
+pub fn plane_key(normal: Vec3, distance: f32) -> PlaneKey {
+    #[allow(clippy::cast_possible_truncation)]
+    let quantize = |v: f32, steps: f32| (v * steps).round() as i32;
+
+    [
+        quantize(normal.x, NORMAL_STEPS),
+        quantize(normal.y, NORMAL_STEPS),
+        quantize(normal.z, NORMAL_STEPS),
+        quantize(distance, DISTANCE_STEPS),
+    ]
+}
+
+/// Smoothing groups of the sides of the solids with any smoothed sides.
+#[derive(Debug, Clone, Default)]
+pub struct SmoothingGroups {
+    /// Ids of the solids with a smoothed side on the plane, and the groups of the side.
+    planes: HashMap<PlaneKey, Vec<(i32, u32)>>,
+    /// Planes of the solids, for finding the solid a face of merged solids is on.
+    solids: HashMap<i32, Vec<(Vec3, f32)>>,
+}
+
+impl SmoothingGroups {
+    /// Collects the smoothing groups of the world and entity solids of a vmf.
+    pub fn from_vmf(vmf: &[(String, KeyValue)]) -> Self {
+        let mut groups = Self::default();
+
+        for solid in vmf_solids(vmf) {
+            let Some(id) = block_value(solid, "id").and_then(|v| v.trim().parse().ok()) else {
+                continue;
+            };
+
+            let sides: Vec<(Vec3, f32, u32)> = solid_sides(solid)
+                .filter_map(|side| {
+                    let (normal, distance) = block_value(side, "plane").and_then(parse_plane)?;
+                    let groups_value = block_value(side, "smoothing_groups")
+                        .and_then(|v| v.trim().parse().ok())
+                        .unwrap_or(0);
+
+                    Some((normal, distance, groups_value))
+                })
+                .collect();
+
+            if sides.iter().all(|&(_, _, groups_value)| groups_value == 0) {
+                continue;
+            }
+
+            for &(normal, distance, groups_value) in &sides {
+                if groups_value != 0 {
+                    groups
+                        .planes
+                        .entry(plane_key(normal, distance))
+                        .or_default()
+                        .push((id, groups_value));
+                }
+            }
+
+            groups.solids.insert(
+                id,
+                sides
+                    .into_iter()
+                    .map(|(normal, distance, _)| (normal, distance))
+                    .collect(),
+            );
+        }
+
+        groups
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.planes.is_empty()
+    }
+
+    /// Returns the smoothing groups of a face of the solid on the plane, 0 if it has none.
+    pub fn find_in_solid(&self, solid: i32, normal: Vec3, distance: f32) -> u32 {
+        self.planes
+            .get(&plane_key(normal, distance))
+            .and_then(|sides| sides.iter().find(|&&(id, _)| id == solid))
+            .map_or(0, |&(_, groups)| groups)
+    }
+
+    /// Returns the smoothing groups of a face of merged solids on the plane, from the
+    /// solid containing the center of the face. 0 if it has none.
+    pub fn find(&self, normal: Vec3, distance: f32, center: Vec3) -> u32 {
+        self.planes
+            .get(&plane_key(normal, distance))
+            .and_then(|sides| {
+                sides.iter().find(|&&(id, _)| {
+                    self.solids
+                        .get(&id)
+                        .is_some_and(|planes| solid_contains(planes, center))
+                })
+            })
+            .map_or(0, |&(_, groups)| groups)
+    }
+}
+
+/// Returns if the point is inside the solid bounded by the planes, or close to it.
+pub fn solid_contains(planes: &[(Vec3, f32)], point: Vec3) -> bool {
+    planes
+        .iter()
+        .all(|&(n, d)| n.dot(point) - d <= CONTAINS_EPSILON)
+}
+
+/// Returns the world and entity solids of a vmf.
+pub fn vmf_solids(vmf: &[(String, KeyValue)]) -> impl Iterator<Item = &[(String, KeyValue)]> + '_ {
+    vmf.iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case("world") || k.eq_ignore_ascii_case("entity"))
+        .filter_map(|(_, v)| v.as_block())
+        .flatten()
+        .filter(|(k, _)| k.eq_ignore_ascii_case("solid"))
+        .filter_map(|(_, v)| v.as_block())
+}
+
+/// Returns the sides of the world and entity solids of a vmf.
+pub fn vmf_sides(vmf: &[(String, KeyValue)]) -> impl Iterator<Item = &[(String, KeyValue)]> + '_ {
+    vmf_solids(vmf).flat_map(solid_sides)
+}
+
+pub fn solid_sides(
+    solid: &[(String, KeyValue)],
+) -> impl Iterator<Item = &[(String, KeyValue)]> + '_ {
+    solid
+        .iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case("side"))
+        .filter_map(|(_, v)| v.as_block())
+}
+
+/// Returns the normal of a polygon, using Newell's method so that it works for
+/// slightly non-planar polygons too.
+pub fn polygon_normal(vertices: impl Iterator<Item = Vec3> + Clone) -> Option<Vec3> {
+    let next = vertices.clone().cycle().skip(1);
+
+    vertices
+        .zip(next)
+        .fold(Vec3::ZERO, |normal, (a, b)| {
+            normal
+                + Vec3::new(
+                    (a.y - b.y) * (a.z + b.z),
+                    (a.z - b.z) * (a.x + b.x),
+                    (a.x - b.x) * (a.y + b.y),
+                )
+        })
+        .try_normalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::keyvalues;
+
+    #[test]
+    fn find_smoothing_groups() {
+        let vmf = keyvalues::parse_raw(
+            r#"
+            world
+            {
+                solid
+                {
+                    "id" "1"
+                    side { "plane" "(0 0 64) (64 0 64) (64 -64 64)" "smoothing_groups" "3" }
+                    side { "plane" "(0 -64 0) (64 -64 0) (64 0 0)" "smoothing_groups" "0" }
+                    side { "plane" "(0 -64 64) (0 -64 0) (0 0 0)" "smoothing_groups" "0" }
+                    side { "plane" "(64 -64 0) (64 -64 64) (64 0 64)" "smoothing_groups" "0" }
+                }
+                solid
+                {
+                    "id" "2"
+                    side { "plane" "(64 0 64) (128 0 64) (128 -64 64)" "smoothing_groups" "0" }
+                    side { "plane" "(64 -64 0) (128 -64 0) (128 0 0)" "smoothing_groups" "0" }
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+        let groups = SmoothingGroups::from_vmf(&vmf);
+
+        assert_eq!(groups.find_in_solid(1, Vec3::Z, 64.0), 3);
+        assert_eq!(groups.find_in_solid(1, -Vec3::Z, 0.0), 0);
+        assert_eq!(groups.find_in_solid(1, Vec3::Z, 128.0), 0);
+        // the coplanar top of the other solid isn't smoothed
+        assert_eq!(groups.find_in_solid(2, Vec3::Z, 64.0), 0);
+
+        assert_eq!(groups.find(Vec3::Z, 64.0, Vec3::new(32.0, 32.0, 64.0)), 3);
+        assert_eq!(groups.find(Vec3::Z, 64.0, Vec3::new(96.0, 32.0, 64.0)), 0);
+
+        let normal = polygon_normal(
+            [
+                Vec3::new(0.0, 0.0, 64.0),
+                Vec3::new(64.0, 0.0, 64.0),
+                Vec3::new(64.0, 64.0, 64.0),
+                Vec3::new(0.0, 64.0, 64.0),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+
+        assert!(normal.abs_diff_eq(Vec3::Z, 1e-6));
+    }
+}
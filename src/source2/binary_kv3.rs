@@ -0,0 +1,486 @@
+//! Parses binary KeyValues3, which compiled resources like materials store their data
+//! in. The legacy format (`VKV\x03`) stores the values in a single stream, while the
+//! versioned format stores them in sections by size, followed by their types. Versions
+//! newer than 3 and zstd compression aren't supported.
+
+use super::{kv3::Kv3Value, lz4, read_le, read_usize, Source2Error};
+
+const MAGIC_LEGACY: u32 = 0x0356_4B56;
+const MAGIC_V1: u32 = 0x4B56_3301;
+const MAGIC_V3: u32 = 0x4B56_3303;
+
+const ENCODING_UNCOMPRESSED: [u8; 16] = [
+    0x00, 0x05, 0x86, 0x1B, 0xD8, 0xF7, 0xC1, 0x40, 0xAD, 0x82, 0x75, 0xA4, 0x82, 0x67, 0xE7, 0x14,
+];
+const ENCODING_BLOCK_COMPRESSED: [u8; 16] = [
+    0x46, 0x1A, 0x79, 0x95, 0xBC, 0x95, 0x6C, 0x4F, 0xA7, 0x0B, 0x05, 0xBC, 0xA1, 0xB7, 0xDF, 0xD2,
+];
+const ENCODING_LZ4: [u8; 16] = [
+    0x8A, 0x34, 0x47, 0x68, 0xA1, 0x63, 0x5C, 0x4F, 0xA1, 0x97, 0x53, 0x80, 0x6F, 0xD9, 0xB1, 0x19,
+];
+
+const TYPE_NULL: u8 = 1;
+const TYPE_BOOLEAN: u8 = 2;
+const TYPE_INT64: u8 = 3;
+const TYPE_UINT64: u8 = 4;
+const TYPE_DOUBLE: u8 = 5;
+const TYPE_STRING: u8 = 6;
+const TYPE_ARRAY: u8 = 8;
+const TYPE_OBJECT: u8 = 9;
+const TYPE_ARRAY_TYPED: u8 = 10;
+const TYPE_INT32: u8 = 11;
+const TYPE_UINT32: u8 = 12;
+const TYPE_TRUE: u8 = 13;
+const TYPE_FALSE: u8 = 14;
+const TYPE_INT64_ZERO: u8 = 15;
+const TYPE_INT64_ONE: u8 = 16;
+const TYPE_DOUBLE_ZERO: u8 = 17;
+const TYPE_DOUBLE_ONE: u8 = 18;
+const TYPE_FLOAT: u8 = 19;
+const TYPE_INT32_AS_BYTE: u8 = 23;
+const TYPE_ARRAY_BYTE_LENGTH: u8 = 24;
+
+/// Parses a binary KeyValues3 document.
+///
+/// # Errors
+///
+/// Returns `Err` if the data isn't valid binary KeyValues3, or uses an unsupported
+/// version, compression or value type.
+pub fn parse(data: &[u8]) -> Result<Kv3Value, Source2Error> {
+    match read_le::<u32>(data, 0)? {
+        MAGIC_LEGACY => parse_legacy(data),
+        magic @ MAGIC_V1..=MAGIC_V3 => parse_versioned(data, magic - MAGIC_V1 + 1),
+        magic => Err(Source2Error::UnsupportedFormat(format!(
+            "binary kv3 {magic:#010x}"
+        ))),
+    }
+}
+
+fn parse_legacy(data: &[u8]) -> Result<Kv3Value, Source2Error> {
+    // the encoding is followed by the format, which doesn't affect parsing
+    let encoding = data.get(4..20).ok_or(Source2Error::UnexpectedEof)?;
+    let body = data.get(36..).ok_or(Source2Error::UnexpectedEof)?;
+
+    let data = if encoding == ENCODING_UNCOMPRESSED {
+        body.to_vec()
+    } else if encoding == ENCODING_BLOCK_COMPRESSED {
+        block_decompress(body)?
+    } else if encoding == ENCODING_LZ4 {
+        let compressed = body.get(4..).ok_or(Source2Error::UnexpectedEof)?;
+        lz4::decompress(compressed, read_usize(body, 0)?)?
+    } else {
+        return Err(Source2Error::UnsupportedFormat(
+            "binary kv3 encoding".to_owned(),
+        ));
+    };
+
+    let mut stream = Buffer {
+        data: &data,
+        pos: 0,
+    };
+    let count = stream.usize()?;
+    let strings = read_strings(&mut stream, count)?;
+
+    Reader {
+        sections: vec![stream],
+        strings,
+    }
+    .root()
+}
+
+fn parse_versioned(data: &[u8], version: u32) -> Result<Kv3Value, Source2Error> {
+    // skip the magic and the format
+    let mut header = Buffer { data, pos: 20 };
+
+    let compression = header.u32()?;
+    if version >= 2 {
+        // dictionary id and frame size
+        header.take(4)?;
+    }
+
+    let bytes_count = header.usize()?;
+    let ints_count = header.usize()?;
+    let eight_bytes_count = header.usize()?;
+    if version >= 2 {
+        // type, object and array counts
+        header.take(8)?;
+    }
+
+    let size = header.usize()?;
+    let mut compressed_size = data.len() - header.pos.min(data.len());
+    if version >= 2 {
+        compressed_size = header.usize()?;
+        let blob_count = header.u32()?;
+        header.take(4)?;
+
+        if blob_count != 0 {
+            return Err(Source2Error::UnsupportedFormat(
+                "binary kv3 blobs".to_owned(),
+            ));
+        }
+    }
+
+    let body = header.take(compressed_size)?;
+    let data = match compression {
+        0 => body
+            .get(..size)
+            .ok_or(Source2Error::UnexpectedEof)?
+            .to_vec(),
+        1 => lz4::decompress(body, size)?,
+        2 => {
+            return Err(Source2Error::UnsupportedFormat(
+                "zstd compressed binary kv3".to_owned(),
+            ))
+        }
+        _ => {
+            return Err(Source2Error::UnsupportedFormat(format!(
+                "binary kv3 compression {compression}"
+            )))
+        }
+    };
+
+    let ints_start = bytes_count.next_multiple_of(4);
+    let eight_bytes_start = (ints_start + ints_count * 4).next_multiple_of(8);
+
+    let mut ints = Buffer {
+        data: &data,
+        pos: ints_start,
+    };
+    let count = ints.usize()?;
+
+    // the types follow the strings
+    let mut types = Buffer {
+        data: &data,
+        pos: eight_bytes_start + eight_bytes_count * 8,
+    };
+    let strings = read_strings(&mut types, count)?;
+
+    Reader {
+        sections: vec![
+            Buffer {
+                data: &data,
+                pos: 0,
+            },
+            ints,
+            Buffer {
+                data: &data,
+                pos: eight_bytes_start,
+            },
+            types,
+        ],
+        strings,
+    }
+    .root()
+}
+
+/// Decompresses the LZ77 variant of the legacy format. Each 16 bit mask tells which of
+/// the following 16 entries are back references instead of literal bytes.
+fn block_decompress(data: &[u8]) -> Result<Vec<u8>, Source2Error> {
+    let header = read_le::<u32>(data, 0)?;
+    let size = (header & 0x00FF_FFFF) as usize;
+
+    // the data may be stored as is
+    if header & 0x8000_0000 != 0 {
+        return Ok(data.get(4..).unwrap_or_default().to_vec());
+    }
+
+    let mut output = Vec::with_capacity(size);
+    let mut pos = 4;
+
+    while output.len() < size {
+        let mask = read_le::<u16>(data, pos)?;
+        pos += 2;
+
+        for bit in 0..16 {
+            if mask & (1 << bit) == 0 {
+                output.push(*data.get(pos).ok_or(Source2Error::UnexpectedEof)?);
+                pos += 1;
+            } else {
+                let token = usize::from(read_le::<u16>(data, pos)?);
+                pos += 2;
+
+                let offset = (token >> 4) + 1;
+                let length = (token & 0xF) + 3;
+
+                if offset > output.len() {
+                    return Err(Source2Error::UnsupportedFormat(
+                        "binary kv3 block".to_owned(),
+                    ));
+                }
+
+                // the reference can overlap the bytes it's writing
+                let start = output.len() - offset;
+                for i in 0..length {
+                    output.push(output[start + i]);
+                }
+            }
+
+            if output.len() >= size {
+                break;
+            }
+        }
+    }
+
+    output.truncate(size);
+    Ok(output)
+}
+
+fn read_strings(buffer: &mut Buffer, count: usize) -> Result<Vec<String>, Source2Error> {
+    (0..count)
+        .map(|_| {
+            let rest = buffer.data.get(buffer.pos..).unwrap_or_default();
+            let len = rest
+                .iter()
+                .position(|&b| b == 0)
+                .ok_or(Source2Error::UnexpectedEof)?;
+            let string = String::from_utf8_lossy(buffer.take(len)?).into_owned();
+            buffer.take(1)?;
+            Ok(string)
+        })
+        .collect()
+}
+
+struct Buffer<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Buffer<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Source2Error> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or(Source2Error::UnexpectedEof)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Source2Error> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, Source2Error> {
+        self.array().map(|[b]| b)
+    }
+
+    fn u32(&mut self) -> Result<u32, Source2Error> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn usize(&mut self) -> Result<usize, Source2Error> {
+        self.u32().map(|v| v as usize)
+    }
+}
+
+/// Which section a value is read from. The legacy format has only one.
+#[derive(Clone, Copy)]
+enum Section {
+    Bytes,
+    Ints,
+    EightBytes,
+    Types,
+}
+
+struct Reader<'a> {
+    sections: Vec<Buffer<'a>>,
+    strings: Vec<String>,
+}
+
+impl<'a> Reader<'a> {
+    fn section(&mut self, section: Section) -> &mut Buffer<'a> {
+        let index = (section as usize).min(self.sections.len() - 1);
+        &mut self.sections[index]
+    }
+
+    fn root(mut self) -> Result<Kv3Value, Source2Error> {
+        let kind = self.kind()?;
+        self.value(kind)
+    }
+
+    /// Reads the type of a value, skipping its flag, like `resource`.
+    fn kind(&mut self) -> Result<u8, Source2Error> {
+        let types = self.section(Section::Types);
+        let kind = types.u8()?;
+
+        if kind & 0x80 != 0 {
+            types.u8()?;
+            Ok(kind & 0x3F)
+        } else {
+            Ok(kind)
+        }
+    }
+
+    /// Reads a string by its index, -1 being an empty string.
+    fn string(&mut self) -> Result<String, Source2Error> {
+        let index = i32::from_le_bytes(self.section(Section::Ints).array()?);
+
+        if index == -1 {
+            return Ok(String::new());
+        }
+
+        usize::try_from(index)
+            .ok()
+            .and_then(|i| self.strings.get(i))
+            .cloned()
+            .ok_or(Source2Error::UnexpectedEof)
+    }
+
+    /// Reads an element count, which can't exceed the size of the data since the
+    /// elements of an array may take no space.
+    fn count(&mut self, section: Section) -> Result<usize, Source2Error> {
+        let count = if let Section::Bytes = section {
+            usize::from(self.section(section).u8()?)
+        } else {
+            self.section(section).usize()?
+        };
+
+        if count > self.sections[0].data.len() {
+            return Err(Source2Error::UnexpectedEof);
+        }
+
+        Ok(count)
+    }
+
+    #[allow(clippy::cast_possible_wrap)]
+    fn value(&mut self, kind: u8) -> Result<Kv3Value, Source2Error> {
+        Ok(match kind {
+            TYPE_NULL => Kv3Value::Null,
+            TYPE_BOOLEAN => Kv3Value::Bool(self.section(Section::Bytes).u8()? != 0),
+            TYPE_TRUE => Kv3Value::Bool(true),
+            TYPE_FALSE => Kv3Value::Bool(false),
+            TYPE_INT64 => Kv3Value::Int(i64::from_le_bytes(
+                self.section(Section::EightBytes).array()?,
+            )),
+            TYPE_UINT64 => {
+                Kv3Value::Int(u64::from_le_bytes(self.section(Section::EightBytes).array()?) as i64)
+            }
+            TYPE_DOUBLE => Kv3Value::Double(f64::from_le_bytes(
+                self.section(Section::EightBytes).array()?,
+            )),
+            TYPE_INT64_ZERO => Kv3Value::Int(0),
+            TYPE_INT64_ONE => Kv3Value::Int(1),
+            TYPE_DOUBLE_ZERO => Kv3Value::Double(0.0),
+            TYPE_DOUBLE_ONE => Kv3Value::Double(1.0),
+            TYPE_INT32 => {
+                Kv3Value::Int(i32::from_le_bytes(self.section(Section::Ints).array()?).into())
+            }
+            TYPE_UINT32 => Kv3Value::Int(self.section(Section::Ints).u32()?.into()),
+            TYPE_INT32_AS_BYTE => Kv3Value::Int(self.section(Section::Bytes).u8()?.into()),
+            TYPE_FLOAT => {
+                Kv3Value::Double(f32::from_le_bytes(self.section(Section::Ints).array()?).into())
+            }
+            TYPE_STRING => Kv3Value::String(self.string()?),
+            TYPE_ARRAY => {
+                let count = self.count(Section::Ints)?;
+                Kv3Value::Array(
+                    (0..count)
+                        .map(|_| {
+                            let kind = self.kind()?;
+                            self.value(kind)
+                        })
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            TYPE_ARRAY_TYPED | TYPE_ARRAY_BYTE_LENGTH => {
+                let count = self.count(if kind == TYPE_ARRAY_TYPED {
+                    Section::Ints
+                } else {
+                    Section::Bytes
+                })?;
+                let kind = self.kind()?;
+                Kv3Value::Array(
+                    (0..count)
+                        .map(|_| self.value(kind))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            TYPE_OBJECT => {
+                let count = self.count(Section::Ints)?;
+                Kv3Value::Object(
+                    (0..count)
+                        .map(|_| {
+                            let key = self.string()?;
+                            let kind = self.kind()?;
+                            Ok((key, self.value(kind)?))
+                        })
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            _ => {
+                return Err(Source2Error::UnsupportedFormat(format!(
+                    "binary kv3 type {kind}"
+                )))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_versioned_and_legacy() {
+        let strings: &[&str] = &["shader", "vr_complex.vfx", "params", "metalness"];
+        // {shader = "vr_complex.vfx", params = [{metalness = 0.5 as float}, true]}
+        let ints: Vec<i32> = vec![4, 2, 0, 1, 2, 2, 1, 3, 0x3F00_0000];
+        let types = [
+            TYPE_OBJECT,
+            TYPE_STRING,
+            TYPE_ARRAY,
+            TYPE_OBJECT,
+            TYPE_FLOAT,
+            TYPE_TRUE,
+        ];
+
+        let mut body = Vec::new();
+        for int in &ints {
+            body.extend_from_slice(&int.to_le_bytes());
+        }
+        // the eight byte section is aligned
+        body.extend_from_slice(&[0; 4]);
+        for string in strings {
+            body.extend_from_slice(string.as_bytes());
+            body.push(0);
+        }
+        body.extend_from_slice(&types);
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&MAGIC_V1.to_le_bytes());
+        data.extend_from_slice(&[0; 16]);
+        for value in [0, 0, ints.len(), 0, body.len()] {
+            data.extend_from_slice(&u32::try_from(value).unwrap().to_le_bytes());
+        }
+        data.extend_from_slice(&body);
+
+        let root = parse(&data).unwrap();
+        assert_eq!(
+            root.get("shader").and_then(Kv3Value::as_str),
+            Some("vr_complex.vfx")
+        );
+        let params = root.get("params").and_then(Kv3Value::as_array).unwrap();
+        assert_eq!(
+            params[0].get("metalness").and_then(Kv3Value::as_f64),
+            Some(0.5)
+        );
+        assert_eq!(params[1], Kv3Value::Bool(true));
+
+        // {a = "a"} with the zeros before the type of the value repeated from 1 byte back
+        let mut data = Vec::new();
+        data.extend_from_slice(&MAGIC_LEGACY.to_le_bytes());
+        data.extend_from_slice(&ENCODING_BLOCK_COMPRESSED);
+        data.extend_from_slice(&[0; 16]);
+        data.extend_from_slice(&[20, 0, 0, 0]);
+        data.extend_from_slice(&0b0000_0100_0000_0000_u16.to_le_bytes());
+        data.extend_from_slice(&[1, 0, 0, 0, b'a', 0, TYPE_OBJECT, 1, 0, 0]);
+        data.extend_from_slice(&[0x02, 0x00]);
+        data.extend_from_slice(&[TYPE_STRING, 0, 0, 0, 0]);
+
+        assert_eq!(
+            parse(&data).unwrap(),
+            Kv3Value::Object(vec![("a".to_owned(), Kv3Value::String("a".to_owned()))])
+        );
+    }
+}
@@ -0,0 +1,364 @@
+//! Parses the text format of KeyValues3, which Source 2 uses for material sources and
+//! most other text files. Values prefixed with a flag, like `resource:"..."`, are
+//! parsed as the value without the flag.
+
+use super::Source2Error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kv3Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Double(f64),
+    String(String),
+    Array(Vec<Kv3Value>),
+    Object(Vec<(String, Kv3Value)>),
+}
+
+impl Kv3Value {
+    /// Finds a value of an object, case-insensitively.
+    pub fn get(&self, key: &str) -> Option<&Kv3Value> {
+        match self {
+            Kv3Value::Object(entries) => entries
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Kv3Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Kv3Value::Int(i) => Some(*i as f64),
+            Kv3Value::Double(d) => Some(*d),
+            Kv3Value::Bool(b) => Some(f64::from(u8::from(*b))),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Kv3Value]> {
+        match self {
+            Kv3Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, Kv3Value)]> {
+        match self {
+            Kv3Value::Object(entries) => Some(entries),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the text starts with the KeyValues3 header comment.
+pub fn is_kv3(text: &str) -> bool {
+    text.trim_start().starts_with("<!-- kv3")
+}
+
+/// Parses a KeyValues3 document, which is a single value after the header.
+///
+/// # Errors
+///
+/// Returns `Err` if the text isn't valid KeyValues3.
+pub fn parse(text: &str) -> Result<Kv3Value, Source2Error> {
+    let mut parser = Parser {
+        text,
+        pos: 0,
+        line: 1,
+    };
+
+    let value = parser.value()?;
+    parser.skip_whitespace()?;
+
+    if parser.pos < text.len() {
+        return Err(parser.error("unexpected content after the root value"));
+    }
+
+    Ok(value)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &'static str) -> Source2Error {
+        Source2Error::Parse {
+            line: self.line,
+            message,
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn advance(&mut self, len: usize) {
+        let end = (self.pos + len).min(self.text.len());
+        self.line += self.text[self.pos..end].matches('\n').count();
+        self.pos = end;
+    }
+
+    /// Skips past the end marker, failing if it's missing.
+    fn skip_past(&mut self, end: &str, message: &'static str) -> Result<&'a str, Source2Error> {
+        let len = self.rest().find(end).ok_or_else(|| self.error(message))?;
+        let start = self.pos;
+        self.advance(len + end.len());
+        Ok(&self.text[start..start + len])
+    }
+
+    fn skip_whitespace(&mut self) -> Result<(), Source2Error> {
+        loop {
+            let rest = self.rest();
+
+            if rest.starts_with("//") {
+                let len = rest.find('\n').unwrap_or(rest.len());
+                self.advance(len);
+            } else if rest.starts_with("/*") {
+                self.skip_past("*/", "unterminated comment")?;
+            } else if rest.starts_with("<!--") {
+                self.skip_past("-->", "unterminated header")?;
+            } else if self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+                self.advance(1);
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Kv3Value, Source2Error> {
+        self.skip_whitespace()?;
+
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string().map(Kv3Value::String),
+            Some(b'#') if self.rest().starts_with("#[") => self.binary_blob(),
+            Some(_) => {
+                let token = self.token();
+
+                // a flag prefixes the value, like `resource:"materials/dev.vmat"`
+                if self.peek() == Some(b':') && !token.is_empty() {
+                    self.advance(1);
+                    return self.value();
+                }
+
+                match token {
+                    "" => Err(self.error("expected a value")),
+                    "null" => Ok(Kv3Value::Null),
+                    "true" => Ok(Kv3Value::Bool(true)),
+                    "false" => Ok(Kv3Value::Bool(false)),
+                    _ => token
+                        .parse()
+                        .map(Kv3Value::Int)
+                        .or_else(|_| token.parse().map(Kv3Value::Double))
+                        .map_err(|_| self.error("invalid number")),
+                }
+            }
+            None => Err(self.error("unexpected end of file")),
+        }
+    }
+
+    /// Reads an unquoted identifier or number.
+    fn token(&mut self) -> &'a str {
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_alphanumeric() || "_.-+".contains(c)))
+            .unwrap_or(self.rest().len());
+
+        let start = self.pos;
+        self.advance(len);
+        &self.text[start..start + len]
+    }
+
+    fn string(&mut self) -> Result<String, Source2Error> {
+        if self.rest().starts_with("\"\"\"") {
+            self.advance(3);
+            return Ok(self
+                .skip_past("\"\"\"", "unterminated multiline string")?
+                .to_owned());
+        }
+
+        self.advance(1);
+        let mut string = String::new();
+
+        loop {
+            let rest = self.rest();
+            let len = rest
+                .find(['"', '\\'])
+                .ok_or_else(|| self.error("unterminated string"))?;
+            string.push_str(&rest[..len]);
+            let escaped = rest.as_bytes()[len] == b'\\';
+            self.advance(len + 1);
+
+            if !escaped {
+                return Ok(string);
+            }
+
+            match self.peek() {
+                Some(b'n') => string.push('\n'),
+                Some(b't') => string.push('\t'),
+                Some(c) => string.push(char::from(c)),
+                None => return Err(self.error("unterminated string")),
+            }
+            self.advance(1);
+        }
+    }
+
+    fn object(&mut self) -> Result<Kv3Value, Source2Error> {
+        self.advance(1);
+        let mut entries = Vec::new();
+
+        loop {
+            self.skip_whitespace()?;
+
+            match self.peek() {
+                Some(b'}') => {
+                    self.advance(1);
+                    return Ok(Kv3Value::Object(entries));
+                }
+                Some(b',') => {
+                    self.advance(1);
+                    continue;
+                }
+                Some(b'"') => {
+                    let key = self.string()?;
+                    entries.push((key, self.entry_value()?));
+                }
+                Some(_) => {
+                    let key = self.token().to_owned();
+                    if key.is_empty() {
+                        return Err(self.error("expected a key"));
+                    }
+                    entries.push((key, self.entry_value()?));
+                }
+                None => return Err(self.error("unterminated object")),
+            }
+        }
+    }
+
+    fn entry_value(&mut self) -> Result<Kv3Value, Source2Error> {
+        self.skip_whitespace()?;
+
+        if self.peek() != Some(b'=') {
+            return Err(self.error("expected `=`"));
+        }
+
+        self.advance(1);
+        self.value()
+    }
+
+    fn array(&mut self) -> Result<Kv3Value, Source2Error> {
+        self.advance(1);
+        let mut values = Vec::new();
+
+        loop {
+            self.skip_whitespace()?;
+
+            match self.peek() {
+                Some(b']') => {
+                    self.advance(1);
+                    return Ok(Kv3Value::Array(values));
+                }
+                Some(b',') => self.advance(1),
+                Some(_) => values.push(self.value()?),
+                None => return Err(self.error("unterminated array")),
+            }
+        }
+    }
+
+    /// Reads a `#[ 00 ff ]` blob as an array of its bytes.
+    fn binary_blob(&mut self) -> Result<Kv3Value, Source2Error> {
+        self.advance(2);
+        let hex = self.skip_past("]", "unterminated binary blob")?;
+
+        hex.split_ascii_whitespace()
+            .map(|byte| {
+                u8::from_str_radix(byte, 16)
+                    .map(|b| Kv3Value::Int(b.into()))
+                    .map_err(|_| self.error("invalid binary blob"))
+            })
+            .collect::<Result<_, _>>()
+            .map(Kv3Value::Array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_material() {
+        let text = r#"<!-- kv3 encoding:text:version{e21c7f3c-8a33-41c5-9977-a76d3a32aa0d} format:generic:version{7412167c-06e9-4698-aff2-e63eb59037e7} -->
+        {
+            // a comment
+            shader = "csgo_complex.vfx"
+            F_ALPHA_TEST = 1
+            g_flMetalness = 0.5
+            TextureColor = resource:"materials/Dev/grid_color.png"
+            g_vColorTint = [ 1.0, 0.5, 0.25, 0.0 ]
+            /* a block
+               comment */
+            Attributes = { "quoted key" = """multi
+line""" empty = null }
+            Blob = #[ 00 ff ]
+        }"#;
+
+        assert!(is_kv3(text));
+
+        let root = parse(text).unwrap();
+        assert_eq!(
+            root.get("shader").and_then(Kv3Value::as_str),
+            Some("csgo_complex.vfx")
+        );
+        assert_eq!(root.get("f_alpha_test"), Some(&Kv3Value::Int(1)));
+        assert_eq!(
+            root.get("g_flMetalness").and_then(Kv3Value::as_f64),
+            Some(0.5)
+        );
+        assert_eq!(
+            root.get("TextureColor").and_then(Kv3Value::as_str),
+            Some("materials/Dev/grid_color.png")
+        );
+        assert_eq!(
+            root.get("g_vColorTint")
+                .and_then(Kv3Value::as_array)
+                .map(<[_]>::len),
+            Some(4)
+        );
+
+        let attributes = root.get("Attributes").unwrap();
+        assert_eq!(
+            attributes.get("quoted key").and_then(Kv3Value::as_str),
+            Some("multi\nline")
+        );
+        assert_eq!(attributes.get("empty"), Some(&Kv3Value::Null));
+        assert_eq!(
+            root.get("Blob"),
+            Some(&Kv3Value::Array(vec![Kv3Value::Int(0), Kv3Value::Int(255)]))
+        );
+
+        assert_eq!(
+            parse("{\n key 1 }"),
+            Err(Source2Error::Parse {
+                line: 2,
+                message: "expected `=`"
+            })
+        );
+    }
+}
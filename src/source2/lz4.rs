@@ -0,0 +1,93 @@
+//! Decompresses LZ4 blocks, which compiled resources use for their mipmaps and
+//! binary KeyValues3. Blocks have no frame, so their decompressed size is stored
+//! separately.
+
+use super::Source2Error;
+
+/// Decompresses a block into exactly `size` bytes.
+///
+/// # Errors
+///
+/// Returns `Err` if the block is truncated, refers to data before its start, or doesn't
+/// decompress into `size` bytes.
+pub fn decompress(data: &[u8], size: usize) -> Result<Vec<u8>, Source2Error> {
+    let invalid = || Source2Error::UnsupportedFormat("lz4 block".to_owned());
+    let mut output = Vec::with_capacity(size);
+    let mut pos = 0;
+
+    loop {
+        let token = *data.get(pos).ok_or(Source2Error::UnexpectedEof)?;
+        pos += 1;
+
+        let literals = read_length(data, &mut pos, usize::from(token >> 4))?;
+        output.extend_from_slice(
+            data.get(pos..pos + literals)
+                .ok_or(Source2Error::UnexpectedEof)?,
+        );
+        pos += literals;
+
+        // the last sequence has only literals
+        if pos == data.len() {
+            break;
+        }
+
+        let offset = data
+            .get(pos..pos + 2)
+            .map(|b| usize::from(u16::from_le_bytes([b[0], b[1]])))
+            .ok_or(Source2Error::UnexpectedEof)?;
+        pos += 2;
+
+        if offset == 0 || offset > output.len() {
+            return Err(invalid());
+        }
+
+        let length = read_length(data, &mut pos, usize::from(token & 0xF))? + 4;
+        let start = output.len() - offset;
+
+        // the match can overlap the bytes it's writing
+        for i in 0..length {
+            output.push(output[start + i]);
+        }
+    }
+
+    if output.len() == size {
+        Ok(output)
+    } else {
+        Err(invalid())
+    }
+}
+
+fn read_length(data: &[u8], pos: &mut usize, nibble: usize) -> Result<usize, Source2Error> {
+    let mut length = nibble;
+
+    if nibble == 0xF {
+        loop {
+            let byte = *data.get(*pos).ok_or(Source2Error::UnexpectedEof)?;
+            *pos += 1;
+            length += usize::from(byte);
+
+            if byte != u8::MAX {
+                break;
+            }
+        }
+    }
+
+    Ok(length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decompress_overlapping_match() {
+        // "abc" as literals, then a match of 9 bytes from 3 bytes back, then "d"
+        let data = [0x35, b'a', b'b', b'c', 3, 0, 0x10, b'd'];
+
+        assert_eq!(decompress(&data, 13).unwrap(), b"abcabcabcabcd");
+        assert_eq!(
+            decompress(&data, 12),
+            Err(Source2Error::UnsupportedFormat("lz4 block".to_owned()))
+        );
+    }
+}
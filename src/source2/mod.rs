@@ -0,0 +1,50 @@
+//! Reads the formats of Source 2 games, like Counter-Strike 2 and Half-Life: Alyx, so
+//! that their materials and textures can be imported. Source 2 VPKs use the same
+//! directory format as version 2 Source VPKs, so they are opened by the file system.
+//!
+//! Compiled resources (`_c`) are a header followed by blocks identified by four-character
+//! codes. Textures and materials are decoded, other resources like models aren't.
+
+pub mod binary_kv3;
+pub mod kv3;
+mod lz4;
+pub mod resource;
+pub mod vmat;
+pub mod vtex;
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::bytes::{self, LeBytes};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source2Error {
+    InvalidHeader,
+    UnexpectedEof,
+    MissingBlock(&'static str),
+    UnsupportedFormat(String),
+    Parse { line: usize, message: &'static str },
+}
+
+impl Display for Source2Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Source2Error::InvalidHeader => f.write_str("invalid resource header"),
+            Source2Error::UnexpectedEof => f.write_str("unexpected end of file"),
+            Source2Error::MissingBlock(block) => write!(f, "resource has no `{block}` block"),
+            Source2Error::UnsupportedFormat(format) => write!(f, "unsupported format {format}"),
+            Source2Error::Parse { line, message } => {
+                write!(f, "line {line}: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Source2Error {}
+
+fn read_le<T: LeBytes>(data: &[u8], offset: usize) -> Result<T, Source2Error> {
+    bytes::read_le(data, offset).ok_or(Source2Error::UnexpectedEof)
+}
+
+fn read_usize(data: &[u8], offset: usize) -> Result<usize, Source2Error> {
+    read_le::<u32>(data, offset).map(|v| v as usize)
+}
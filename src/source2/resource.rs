@@ -0,0 +1,115 @@
+//! Reads the block directory of compiled resources.
+
+use super::{read_le, read_usize, Source2Error};
+
+const HEADER_SIZE: usize = 16;
+const BLOCK_SIZE: usize = 12;
+/// Header version of every known compiled resource.
+const HEADER_VERSION: u16 = 12;
+
+/// A block of a compiled resource, like `DATA` or `REDI`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Block<'a> {
+    pub kind: [u8; 4],
+    /// Offset of the block data from the start of the file.
+    pub offset: usize,
+    pub data: &'a [u8],
+}
+
+/// Reads the blocks of a compiled resource.
+///
+/// # Errors
+///
+/// Returns `Err` if the header is invalid or a block is out of bounds.
+pub fn blocks(data: &[u8]) -> Result<Vec<Block>, Source2Error> {
+    if data.len() < HEADER_SIZE || read_le::<u16>(data, 4)? != HEADER_VERSION {
+        return Err(Source2Error::InvalidHeader);
+    }
+
+    // offsets are relative to where they are stored
+    let block_index = 8 + read_usize(data, 8)?;
+    let count = read_usize(data, 12)?;
+
+    (0..count)
+        .map(|i| {
+            let entry = block_index + i * BLOCK_SIZE;
+            let kind = data
+                .get(entry..entry + 4)
+                .ok_or(Source2Error::UnexpectedEof)?;
+            let offset = entry + 4 + read_usize(data, entry + 4)?;
+            let size = read_usize(data, entry + 8)?;
+
+            Ok(Block {
+                kind: [kind[0], kind[1], kind[2], kind[3]],
+                offset,
+                data: data
+                    .get(offset..offset + size)
+                    .ok_or(Source2Error::UnexpectedEof)?,
+            })
+        })
+        .collect()
+}
+
+/// Finds a block of a kind.
+///
+/// # Errors
+///
+/// Returns `Err` if the resource has no such block.
+pub fn find<'a, 'b>(
+    blocks: &'b [Block<'a>],
+    kind: &'static str,
+) -> Result<&'b Block<'a>, Source2Error> {
+    blocks
+        .iter()
+        .find(|b| b.kind == kind.as_bytes())
+        .ok_or(Source2Error::MissingBlock(kind))
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// Writes a resource with the given blocks, in order.
+    pub fn write_resource(blocks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let count = u32::try_from(blocks.len()).unwrap();
+        let mut data = Vec::new();
+        data.extend_from_slice(&0_u32.to_le_bytes());
+        data.extend_from_slice(&HEADER_VERSION.to_le_bytes());
+        data.extend_from_slice(&0_u16.to_le_bytes());
+        data.extend_from_slice(&8_u32.to_le_bytes());
+        data.extend_from_slice(&count.to_le_bytes());
+
+        let mut block_offset = HEADER_SIZE + blocks.len() * BLOCK_SIZE;
+        for (i, (kind, block)) in blocks.iter().enumerate() {
+            let entry = HEADER_SIZE + i * BLOCK_SIZE;
+            data.extend_from_slice(*kind);
+            data.extend_from_slice(
+                &u32::try_from(block_offset - entry - 4)
+                    .unwrap()
+                    .to_le_bytes(),
+            );
+            data.extend_from_slice(&u32::try_from(block.len()).unwrap().to_le_bytes());
+            block_offset += block.len();
+        }
+
+        for (_, block) in blocks {
+            data.extend_from_slice(block);
+        }
+
+        data
+    }
+
+    #[test]
+    fn read_blocks() {
+        let data = write_resource(&[(b"RERL", b"refs"), (b"DATA", b"texture")]);
+
+        let blocks = blocks(&data).unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(find(&blocks, "DATA").unwrap().data, b"texture");
+        assert_eq!(find(&blocks, "DATA").unwrap().offset, HEADER_SIZE + 24 + 4);
+        assert_eq!(
+            find(&blocks, "REDI"),
+            Err(Source2Error::MissingBlock("REDI"))
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! Reads material sources (`.vmat`). Newer games write them as KeyValues3, while
+//! Half-Life: Alyx era materials use KeyValues with the parameters in a `Layer0` block.
+//! Compiled materials (`.vmat_c`) store the parameters in binary KeyValues3, grouped by
+//! their type.
+
+use std::collections::BTreeMap;
+
+use crate::keyvalues::{self, KeyValue};
+
+use super::{
+    binary_kv3,
+    kv3::{self, Kv3Value},
+    resource, Source2Error,
+};
+
+/// Parameter groups of compiled materials, with the key of their values.
+const COMPILED_PARAMS: [(&str, &str); 4] = [
+    ("m_intParams", "m_nValue"),
+    ("m_floatParams", "m_flValue"),
+    ("m_vectorParams", "m_value"),
+    ("m_textureParams", "m_pValue"),
+];
+
+/// Texture parameters of the standard shaders, with the source and compiled names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TextureRole {
+    Color,
+    Normal,
+    Roughness,
+    Metalness,
+}
+
+impl TextureRole {
+    pub const ALL: [Self; 4] = [Self::Color, Self::Normal, Self::Roughness, Self::Metalness];
+
+    fn parameters(self) -> [&'static str; 2] {
+        match self {
+            TextureRole::Color => ["TextureColor", "g_tColor"],
+            TextureRole::Normal => ["TextureNormal", "g_tNormal"],
+            TextureRole::Roughness => ["TextureRoughness", "g_tRoughness"],
+            TextureRole::Metalness => ["TextureMetalness", "g_tMetalness"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Vmat {
+    pub shader: String,
+    /// Parameter values as text, by lowercase name.
+    params: BTreeMap<String, String>,
+}
+
+impl Vmat {
+    /// # Errors
+    ///
+    /// Returns `Err` if the text isn't valid KeyValues3 or KeyValues.
+    pub fn parse(text: &str) -> Result<Self, Source2Error> {
+        let mut params = BTreeMap::new();
+
+        if kv3::is_kv3(text) {
+            let root = kv3::parse(text)?;
+
+            for (key, value) in root.as_object().unwrap_or_default() {
+                if let Some(value) = kv3_text(value) {
+                    params.insert(key.to_ascii_lowercase(), value);
+                }
+            }
+        } else {
+            let root = keyvalues::parse(text).map_err(|e| Source2Error::Parse {
+                line: e.line,
+                message: e.message,
+            })?;
+
+            let layer = root
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case("Layer0"))
+                .and_then(|(_, v)| v.as_block())
+                .unwrap_or(&root);
+
+            for (key, value) in layer {
+                if let KeyValue::Value(value) = value {
+                    params.insert(key.to_ascii_lowercase(), value.clone());
+                }
+            }
+        }
+
+        Ok(Self {
+            shader: params.remove("shader").unwrap_or_default(),
+            params,
+        })
+    }
+
+    /// Reads a compiled material, whose textures refer to compiled textures.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the resource has no `DATA` block or it isn't valid binary
+    /// KeyValues3.
+    pub fn read_compiled(data: &[u8]) -> Result<Self, Source2Error> {
+        let blocks = resource::blocks(data)?;
+        let root = binary_kv3::parse(resource::find(&blocks, "DATA")?.data)?;
+
+        Ok(Self::from_compiled(&root))
+    }
+
+    fn from_compiled(root: &Kv3Value) -> Self {
+        let mut params = BTreeMap::new();
+
+        for (group, value_key) in COMPILED_PARAMS {
+            let group = root
+                .get(group)
+                .and_then(Kv3Value::as_array)
+                .unwrap_or_default();
+
+            for param in group {
+                let name = param.get("m_name").and_then(Kv3Value::as_str);
+                let value = param.get(value_key).and_then(kv3_text);
+
+                if let (Some(name), Some(value)) = (name, value) {
+                    params.insert(name.to_ascii_lowercase(), value);
+                }
+            }
+        }
+
+        Self {
+            shader: root
+                .get("m_shaderName")
+                .and_then(Kv3Value::as_str)
+                .unwrap_or_default()
+                .to_owned(),
+            params,
+        }
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    pub fn float(&self, name: &str) -> Option<f32> {
+        self.param(name)?.trim().parse().ok()
+    }
+
+    /// Whether a feature flag like `F_ALPHA_TEST` is enabled.
+    pub fn flag(&self, name: &str) -> bool {
+        self.float(name).is_some_and(|v| v != 0.0)
+    }
+
+    /// Parses a vector like `[1.000 0.500 0.250 0.000]`, missing components being 0.
+    pub fn vector(&self, name: &str) -> Option<[f32; 4]> {
+        let mut values = self
+            .param(name)?
+            .trim_matches(|c: char| c == '[' || c == ']' || c.is_whitespace())
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|v| !v.is_empty())
+            .map(str::parse::<f32>);
+
+        let mut vector = [0.0; 4];
+        for component in &mut vector {
+            match values.next() {
+                Some(value) => *component = value.ok()?,
+                None => break,
+            }
+        }
+
+        Some(vector)
+    }
+
+    /// Path of a texture of the material, if it's set and not a default texture.
+    pub fn texture(&self, role: TextureRole) -> Option<&str> {
+        role.parameters()
+            .iter()
+            .find_map(|p| self.param(p))
+            .map(str::trim)
+            .filter(|p| !p.is_empty() && !p.starts_with('['))
+    }
+}
+
+/// Converts a value into the text KeyValues would store it as. Objects are skipped.
+fn kv3_text(value: &Kv3Value) -> Option<String> {
+    match value {
+        Kv3Value::Null | Kv3Value::Object(_) => None,
+        Kv3Value::Bool(b) => Some(u8::from(*b).to_string()),
+        Kv3Value::Int(i) => Some(i.to_string()),
+        Kv3Value::Double(d) => Some(d.to_string()),
+        Kv3Value::String(s) => Some(s.clone()),
+        Kv3Value::Array(values) => {
+            let values: Vec<String> = values.iter().filter_map(kv3_text).collect();
+            Some(format!("[{}]", values.join(" ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_vmat_formats() {
+        let kv3 = r#"<!-- kv3 encoding:text:version{e21c7f3c-8a33-41c5-9977-a76d3a32aa0d} format:generic:version{7412167c-06e9-4698-aff2-e63eb59037e7} -->
+        {
+            shader = "csgo_complex.vfx"
+            F_ALPHA_TEST = 1
+            TextureColor = "materials/dev/grid_color.png"
+            TextureNormal = "[0.5 0.5 1.0 0.0]"
+            g_vColorTint = [ 1.0, 0.5, 0.25 ]
+        }"#;
+
+        let vmat = Vmat::parse(kv3).unwrap();
+        assert_eq!(vmat.shader, "csgo_complex.vfx");
+        assert!(vmat.flag("f_alpha_test"));
+        assert!(!vmat.flag("F_TRANSLUCENT"));
+        assert_eq!(
+            vmat.texture(TextureRole::Color),
+            Some("materials/dev/grid_color.png")
+        );
+        assert_eq!(vmat.texture(TextureRole::Normal), None);
+        assert_eq!(vmat.vector("g_vColorTint"), Some([1.0, 0.5, 0.25, 0.0]));
+
+        let kv1 = r#"
+        "Layer0"
+        {
+            "shader" "vr_complex.vfx"
+            "g_flMetalness" "0.250"
+            "TextureRoughness" "materials/props/crate_rough.tga"
+        }"#;
+
+        let vmat = Vmat::parse(kv1).unwrap();
+        assert_eq!(vmat.shader, "vr_complex.vfx");
+        assert_eq!(vmat.float("g_flMetalness"), Some(0.25));
+        assert_eq!(
+            vmat.texture(TextureRole::Roughness),
+            Some("materials/props/crate_rough.tga")
+        );
+
+        let param = |name: &str, key: &str, value: Kv3Value| {
+            Kv3Value::Object(vec![
+                ("m_name".to_owned(), Kv3Value::String(name.to_owned())),
+                (key.to_owned(), value),
+            ])
+        };
+        let compiled = Kv3Value::Object(vec![
+            (
+                "m_shaderName".to_owned(),
+                Kv3Value::String("vr_complex.vfx".to_owned()),
+            ),
+            (
+                "m_intParams".to_owned(),
+                Kv3Value::Array(vec![param("F_ALPHA_TEST", "m_nValue", Kv3Value::Int(1))]),
+            ),
+            (
+                "m_textureParams".to_owned(),
+                Kv3Value::Array(vec![param(
+                    "g_tColor",
+                    "m_pValue",
+                    Kv3Value::String("materials/dev/grid_color_tga_1f2e3d4c.vtex".to_owned()),
+                )]),
+            ),
+        ]);
+
+        let vmat = Vmat::from_compiled(&compiled);
+        assert_eq!(vmat.shader, "vr_complex.vfx");
+        assert!(vmat.flag("F_ALPHA_TEST"));
+        assert_eq!(
+            vmat.texture(TextureRole::Color),
+            Some("materials/dev/grid_color_tga_1f2e3d4c.vtex")
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! Reads compiled textures (`.vtex_c`). The mipmaps follow the `DATA` block from the
+//! smallest to the largest, each with all of its faces and slices. Each mipmap may be
+//! LZ4 compressed separately.
+
+use std::borrow::Cow;
+
+use super::{lz4, read_le, read_usize, resource, Source2Error};
+
+const FLAG_CUBE_TEXTURE: u16 = 0x10;
+/// Extra data with the sizes of mipmaps, if they are compressed.
+const EXTRA_COMPRESSED_MIP_SIZE: u32 = 3;
+const EXTRA_DATA_SIZE: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VtexFormat {
+    Dxt1,
+    Dxt5,
+    I8,
+    Rgba8888,
+    PngRgba8888,
+    PngDxt5,
+    /// BC7, with a full color and alpha.
+    Bc7,
+    /// BC5, with two channels for normal maps.
+    Ati2n,
+    Ia88,
+    /// BC4, with a single channel.
+    Ati1n,
+    Bgra8888,
+}
+
+impl VtexFormat {
+    fn new(format: u8) -> Option<Self> {
+        Some(match format {
+            1 => Self::Dxt1,
+            2 => Self::Dxt5,
+            3 => Self::I8,
+            4 => Self::Rgba8888,
+            15 => Self::PngRgba8888,
+            17 => Self::PngDxt5,
+            19 => Self::Bc7,
+            20 => Self::Ati2n,
+            21 => Self::Ia88,
+            26 => Self::Ati1n,
+            27 => Self::Bgra8888,
+            _ => return None,
+        })
+    }
+
+    /// Size of a mipmap in bytes. PNG textures are stored whole, so they have no size.
+    fn mip_size(self, width: usize, height: usize) -> Option<usize> {
+        let blocks = width.div_ceil(4).max(1) * height.div_ceil(4).max(1);
+
+        match self {
+            Self::Dxt1 | Self::Ati1n => Some(blocks * 8),
+            Self::Dxt5 | Self::Bc7 | Self::Ati2n => Some(blocks * 16),
+            Self::I8 => Some(width * height),
+            Self::Ia88 => Some(width * height * 2),
+            Self::Rgba8888 | Self::Bgra8888 => Some(width * height * 4),
+            Self::PngRgba8888 | Self::PngDxt5 => None,
+        }
+    }
+}
+
+/// The first face of the largest mipmap of a texture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vtex<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub format: VtexFormat,
+    pub data: Cow<'a, [u8]>,
+}
+
+/// Reads a compiled texture.
+///
+/// # Errors
+///
+/// Returns `Err` if the resource is invalid, or the texture format or its compression
+/// is unsupported.
+pub fn read(data: &[u8]) -> Result<Vtex, Source2Error> {
+    let blocks = resource::blocks(data)?;
+    let block = resource::find(&blocks, "DATA")?;
+    let header = block.data;
+
+    let flags = read_le::<u16>(header, 2)?;
+    let width = read_le::<u16>(header, 20)?;
+    let height = read_le::<u16>(header, 22)?;
+    let depth = usize::from(read_le::<u16>(header, 24)?.max(1));
+    let format_id = *header.get(26).ok_or(Source2Error::UnexpectedEof)?;
+    let mip_count = usize::from(header.get(27).copied().unwrap_or(1).max(1));
+
+    let format = VtexFormat::new(format_id)
+        .ok_or_else(|| Source2Error::UnsupportedFormat(format!("vtex format {format_id}")))?;
+
+    let faces = if flags & FLAG_CUBE_TEXTURE == 0 { 1 } else { 6 };
+    let (w, h) = (usize::from(width), usize::from(height));
+
+    let image_data = data
+        .get(block.offset + block.data.len()..)
+        .ok_or(Source2Error::UnexpectedEof)?;
+
+    let data = match (format.mip_size(w, h), compressed_mip_sizes(header)?) {
+        (Some(size), Some(compressed_sizes)) => {
+            // the sizes are listed from the largest mipmap, which is stored last
+            let offset = compressed_sizes.iter().skip(1).sum::<usize>();
+            let compressed_size = *compressed_sizes
+                .first()
+                .ok_or(Source2Error::UnexpectedEof)?;
+            let compressed = image_data
+                .get(offset..offset + compressed_size)
+                .ok_or(Source2Error::UnexpectedEof)?;
+            let mip_size = size * depth * faces;
+
+            // mipmaps which don't get smaller are stored as is
+            if compressed_size >= mip_size {
+                Cow::Borrowed(compressed.get(..size).ok_or(Source2Error::UnexpectedEof)?)
+            } else {
+                let mut mip = lz4::decompress(compressed, mip_size)?;
+                mip.truncate(size);
+                Cow::Owned(mip)
+            }
+        }
+        (Some(size), None) => {
+            // the smaller mipmaps come first
+            let offset = (1..mip_count)
+                .filter_map(|mip| format.mip_size((w >> mip).max(1), (h >> mip).max(1)))
+                .sum::<usize>()
+                * depth
+                * faces;
+
+            Cow::Borrowed(
+                image_data
+                    .get(offset..offset + size)
+                    .ok_or(Source2Error::UnexpectedEof)?,
+            )
+        }
+        (None, _) => Cow::Borrowed(image_data),
+    };
+
+    Ok(Vtex {
+        width: width.into(),
+        height: height.into(),
+        format,
+        data,
+    })
+}
+
+/// Reads the compressed sizes of the mipmaps from the largest, if they are compressed.
+fn compressed_mip_sizes(header: &[u8]) -> Result<Option<Vec<usize>>, Source2Error> {
+    let extra_index = 32 + read_usize(header, 32)?;
+
+    for i in 0..read_usize(header, 36)? {
+        let entry = extra_index + i * EXTRA_DATA_SIZE;
+
+        if read_le::<u32>(header, entry)? != EXTRA_COMPRESSED_MIP_SIZE {
+            continue;
+        }
+
+        let offset = entry + 4 + read_usize(header, entry + 4)?;
+        if read_le::<u32>(header, offset)? != 1 {
+            return Ok(None);
+        }
+
+        let count = read_usize(header, offset + 8)?;
+
+        return (0..count)
+            .map(|mip| read_usize(header, offset + 12 + mip * 4))
+            .collect::<Result<_, _>>()
+            .map(Some);
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::source2::resource::tests::write_resource;
+
+    fn header(width: u16, height: u16, format: u8, mips: u8) -> Vec<u8> {
+        let mut header = vec![0; 40];
+        header[20..22].copy_from_slice(&width.to_le_bytes());
+        header[22..24].copy_from_slice(&height.to_le_bytes());
+        header[24..26].copy_from_slice(&1_u16.to_le_bytes());
+        header[26] = format;
+        header[27] = mips;
+        header[32..36].copy_from_slice(&8_u32.to_le_bytes());
+        header
+    }
+
+    #[test]
+    fn read_largest_mipmap() {
+        let mut data = write_resource(&[(b"DATA", &header(4, 2, 4, 3))]);
+        // 1x1 and 2x1 mipmaps, then the 4x2 texture
+        data.extend_from_slice(&[1; 4]);
+        data.extend_from_slice(&[2; 8]);
+        data.extend_from_slice(&[3; 32]);
+
+        let texture = read(&data).unwrap();
+        assert_eq!((texture.width, texture.height), (4, 2));
+        assert_eq!(texture.format, VtexFormat::Rgba8888);
+        assert_eq!(*texture.data, [3; 32]);
+
+        let data = write_resource(&[(b"DATA", &header(4, 4, 5, 1))]);
+        assert_eq!(
+            read(&data),
+            Err(Source2Error::UnsupportedFormat("vtex format 5".to_owned()))
+        );
+    }
+
+    #[test]
+    fn read_compressed_mipmaps() {
+        let mut header = header(4, 1, 4, 2);
+        // one extra data entry, right after the header
+        header[36..40].copy_from_slice(&1_u32.to_le_bytes());
+        header.extend_from_slice(&EXTRA_COMPRESSED_MIP_SIZE.to_le_bytes());
+        header.extend_from_slice(&8_u32.to_le_bytes());
+        header.extend_from_slice(&20_u32.to_le_bytes());
+        // compressed, then the number of mipmaps and their sizes from the largest
+        for value in [1_u32, 0, 2, 8, 8] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let mut data = write_resource(&[(b"DATA", &header)]);
+        // the 2x1 mipmap is stored as is
+        data.extend_from_slice(&[1; 8]);
+        // the 4x1 mipmap is 4 literals repeated by a match of 12 bytes
+        data.extend_from_slice(&[0x48, 2, 2, 2, 2, 4, 0, 0]);
+
+        let texture = read(&data).unwrap();
+        assert_eq!((texture.width, texture.height), (4, 1));
+        assert_eq!(*texture.data, [2; 16]);
+    }
+}
@@ -0,0 +1,266 @@
+//! Steam library data which isn't covered by the game discovery of `plumber_core`.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LibraryImage {
+    Icon,
+    Header,
+}
+
+/// Returns the Steam library folder an app is installed in,
+/// given an install directory like `<library>/steamapps/common/<game>`.
+pub fn library_root(install_dir: &Path) -> Option<&Path> {
+    let common = install_dir.parent()?;
+    let steamapps = common.parent()?;
+
+    if !common.file_name()?.eq_ignore_ascii_case("common")
+        || !steamapps.file_name()?.eq_ignore_ascii_case("steamapps")
+    {
+        return None;
+    }
+
+    steamapps.parent()
+}
+
+/// Returns the existing default installations of the Steam client. The client has its
+/// own library folder, which isn't found through the installed apps if it has none.
+pub fn steam_roots() -> Vec<PathBuf> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+
+    let candidates = [
+        env::var_os("ProgramFiles(x86)").map(|p| PathBuf::from(p).join("Steam")),
+        env::var_os("ProgramFiles").map(|p| PathBuf::from(p).join("Steam")),
+        home.as_ref().map(|h| h.join(".steam").join("steam")),
+        home.as_ref()
+            .map(|h| h.join(".local").join("share").join("Steam")),
+        home.as_ref()
+            .map(|h| h.join("Library").join("Application Support").join("Steam")),
+    ];
+
+    let mut roots: Vec<PathBuf> = Vec::new();
+
+    // `~/.steam/steam` usually links to another candidate
+    for root in candidates
+        .into_iter()
+        .flatten()
+        .filter_map(|c| fs::canonicalize(c).ok())
+    {
+        if root.is_dir() && !roots.contains(&root) {
+            roots.push(root);
+        }
+    }
+
+    roots
+}
+
+/// Finds an image of an app cached by the Steam client.
+/// Only the main Steam installation has the cache, so all known library roots are
+/// searched, which should include the Steam roots.
+pub fn library_image(roots: &[PathBuf], app_id: u32, kind: LibraryImage) -> Option<PathBuf> {
+    // newer clients store images in a directory per app
+    let candidates: &[String] = match kind {
+        LibraryImage::Icon => &[format!("{app_id}_icon.jpg")],
+        LibraryImage::Header => &[
+            format!("{app_id}/header.jpg"),
+            format!("{app_id}_header.jpg"),
+        ],
+    };
+
+    roots.iter().find_map(|root| {
+        let cache = root.join("appcache").join("librarycache");
+
+        candidates
+            .iter()
+            .map(|candidate| cache.join(candidate))
+            .find(|path| path.is_file())
+    })
+}
+
+/// A downloaded Steam Workshop item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkshopItem {
+    pub dir: PathBuf,
+    /// Directories which should be mounted for the files of the item to be found.
+    pub mount_dirs: Vec<PathBuf>,
+    pub maps: Vec<PathBuf>,
+    pub addons: Vec<PathBuf>,
+}
+
+/// Returns the directory of the downloaded workshop items of an app,
+/// given the install directory of the app to find the Steam library it's in.
+fn workshop_content_dir(install_dir: &Path, app_id: u32) -> Option<PathBuf> {
+    Some(
+        library_root(install_dir)?
+            .join("steamapps")
+            .join("workshop")
+            .join("content")
+            .join(app_id.to_string()),
+    )
+}
+
+/// Locates a downloaded workshop item of an app.
+pub fn workshop_item(install_dir: &Path, app_id: u32, workshop_id: u64) -> Option<WorkshopItem> {
+    let dir = workshop_content_dir(install_dir, app_id)?.join(workshop_id.to_string());
+
+    dir.is_dir().then(|| read_workshop_item(dir))
+}
+
+/// Lists all downloaded workshop items of an app, in the order of their ids.
+pub fn workshop_items(install_dir: &Path, app_id: u32) -> Vec<WorkshopItem> {
+    let Some(Ok(entries)) = workshop_content_dir(install_dir, app_id).map(fs::read_dir) else {
+        return Vec::new();
+    };
+
+    let mut dirs: Vec<(u64, PathBuf)> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .filter_map(|path| Some((path.file_name()?.to_str()?.parse().ok()?, path)))
+        .collect();
+
+    dirs.sort_unstable();
+
+    dirs.into_iter()
+        .map(|(_, dir)| read_workshop_item(dir))
+        .collect()
+}
+
+fn read_workshop_item(dir: PathBuf) -> WorkshopItem {
+    let mut item = WorkshopItem {
+        dir: dir.clone(),
+        mount_dirs: Vec::new(),
+        maps: Vec::new(),
+        addons: Vec::new(),
+    };
+
+    collect_workshop_files(&dir, &mut item);
+
+    // items which ship a game directory structure are mounted as is,
+    // maps in a `maps` directory need their parent mounted to be found in `maps/`
+    let mut mount_dirs = vec![dir];
+
+    for map in &item.maps {
+        let Some(parent) = map.parent() else {
+            continue;
+        };
+
+        let mount_dir = if parent
+            .file_name()
+            .is_some_and(|n| n.eq_ignore_ascii_case("maps"))
+        {
+            parent.parent().unwrap_or(parent)
+        } else {
+            parent
+        };
+
+        if !mount_dirs.iter().any(|d| d == mount_dir) {
+            mount_dirs.push(mount_dir.to_path_buf());
+        }
+    }
+
+    item.mount_dirs = mount_dirs;
+    item.maps.sort();
+    item.addons.sort();
+
+    item
+}
+
+fn collect_workshop_files(dir: &Path, item: &mut WorkshopItem) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if path.is_dir() {
+            collect_workshop_files(&path, item);
+            continue;
+        }
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("bsp") => item.maps.push(path),
+            Some(e) if e.eq_ignore_ascii_case("gma") => item.addons.push(path),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn find_library_images() {
+        let root = std::env::temp_dir().join(format!("plumber_steam_test_{}", std::process::id()));
+        let install_dir = root.join("steamapps").join("common").join("Half-Life 2");
+        let cache = root.join("appcache").join("librarycache");
+
+        fs::create_dir_all(&install_dir).unwrap();
+        fs::create_dir_all(cache.join("220")).unwrap();
+        fs::write(cache.join("220").join("header.jpg"), b"").unwrap();
+
+        assert_eq!(library_root(&install_dir), Some(root.as_path()));
+        assert_eq!(library_root(&root), None);
+
+        let roots = [root.clone()];
+        assert_eq!(
+            library_image(&roots, 220, LibraryImage::Header),
+            Some(cache.join("220").join("header.jpg"))
+        );
+        assert_eq!(library_image(&roots, 220, LibraryImage::Icon), None);
+
+        // the client caches the images in its own library, which may have no apps
+        let steam_cache = root.join("Steam").join("appcache").join("librarycache");
+        fs::create_dir_all(&steam_cache).unwrap();
+        fs::write(steam_cache.join("220_icon.jpg"), b"").unwrap();
+
+        let roots = [root.clone(), root.join("Steam")];
+        assert_eq!(
+            library_image(&roots, 220, LibraryImage::Icon),
+            Some(steam_cache.join("220_icon.jpg"))
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn find_workshop_item() {
+        let root =
+            std::env::temp_dir().join(format!("plumber_workshop_test_{}", std::process::id()));
+        let install_dir = root.join("steamapps").join("common").join("Counter-Strike");
+        let item_dir = root
+            .join("steamapps")
+            .join("workshop")
+            .join("content")
+            .join("730")
+            .join("123");
+
+        fs::create_dir_all(&install_dir).unwrap();
+        fs::create_dir_all(item_dir.join("maps")).unwrap();
+        fs::write(item_dir.join("maps").join("de_test.bsp"), b"").unwrap();
+        fs::write(item_dir.join("addon.GMA"), b"").unwrap();
+
+        let item = workshop_item(&install_dir, 730, 123).unwrap();
+        assert_eq!(item.dir, item_dir);
+        assert_eq!(item.mount_dirs, vec![item_dir.clone()]);
+        assert_eq!(item.maps, vec![item_dir.join("maps").join("de_test.bsp")]);
+        assert_eq!(item.addons, vec![item_dir.join("addon.GMA")]);
+
+        assert_eq!(workshop_item(&install_dir, 730, 456), None);
+
+        fs::create_dir_all(item_dir.with_file_name("45")).unwrap();
+        let items = workshop_items(&install_dir, 730);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1], item);
+        assert!(workshop_items(&install_dir, 220).is_empty());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
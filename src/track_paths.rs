@@ -0,0 +1,185 @@
+//! Resolves the `path_track` chains that trains follow, so their movement can be
+//! reconstructed along curves.
+
+use crate::keyvalues::KeyValue;
+
+const TRACK_CLASS: &str = "path_track";
+/// Entities which follow the chain starting from their `target`.
+const TRAIN_CLASSES: [&str; 2] = ["func_tracktrain", "func_train"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackPoint {
+    pub name: String,
+    pub position: [f32; 3],
+    /// New speed of the train from this point on, 0 keeps the current speed.
+    pub speed: f32,
+}
+
+/// The path of a train, following its `path_track` chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackPath {
+    pub id: i32,
+    pub class_name: String,
+    pub name: String,
+    /// Initial speed of the train.
+    pub speed: f32,
+    pub points: Vec<TrackPoint>,
+    /// If the last point leads back to one of the earlier points.
+    pub closed: bool,
+}
+
+struct Entity<'a> {
+    properties: &'a [(String, KeyValue)],
+}
+
+impl<'a> Entity<'a> {
+    fn get(&self, key: &str) -> Option<&'a str> {
+        self.properties
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .and_then(|(_, v)| v.as_value())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    }
+
+    fn class_is(&self, class_name: &str) -> bool {
+        self.get("classname")
+            .is_some_and(|c| c.eq_ignore_ascii_case(class_name))
+    }
+
+    fn f32(&self, key: &str) -> Option<f32> {
+        self.get(key).and_then(|v| v.parse().ok())
+    }
+
+    fn origin(&self) -> Option<[f32; 3]> {
+        let mut components = self.get("origin")?.split_ascii_whitespace();
+
+        let mut origin = [0.0; 3];
+        for c in &mut origin {
+            *c = components.next()?.parse().ok()?;
+        }
+
+        Some(origin)
+    }
+}
+
+/// Finds the paths of the trains in a vmf. Trains without a valid path are skipped.
+pub fn track_paths(vmf: &[(String, KeyValue)]) -> Vec<TrackPath> {
+    let entities: Vec<_> = vmf
+        .iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case("entity"))
+        .filter_map(|(_, v)| v.as_block())
+        .map(|properties| Entity { properties })
+        .collect();
+
+    let tracks: Vec<_> = entities
+        .iter()
+        .filter(|e| e.class_is(TRACK_CLASS))
+        .collect();
+
+    let find_track = |name: &str| {
+        tracks.iter().position(|t| {
+            t.get("targetname")
+                .is_some_and(|n| n.eq_ignore_ascii_case(name))
+        })
+    };
+
+    entities
+        .iter()
+        .filter(|e| TRAIN_CLASSES.iter().any(|c| e.class_is(c)))
+        .filter_map(|train| {
+            let mut visited = Vec::new();
+            let mut closed = false;
+            let mut next = train.get("target").and_then(find_track);
+
+            while let Some(index) = next {
+                if visited.contains(&index) {
+                    closed = true;
+                    break;
+                }
+
+                visited.push(index);
+                next = tracks[index].get("target").and_then(find_track);
+            }
+
+            // a single point isn't a path
+            if visited.len() < 2 {
+                return None;
+            }
+
+            let points = visited
+                .iter()
+                .map(|&index| {
+                    let track = tracks[index];
+
+                    TrackPoint {
+                        name: track.get("targetname").unwrap_or_default().to_owned(),
+                        position: track.origin().unwrap_or_default(),
+                        speed: track.f32("speed").unwrap_or(0.0),
+                    }
+                })
+                .collect();
+
+            Some(TrackPath {
+                id: train.get("id").and_then(|v| v.parse().ok()).unwrap_or(0),
+                class_name: train
+                    .get("classname")
+                    .unwrap_or_default()
+                    .to_ascii_lowercase(),
+                name: train.get("targetname").unwrap_or_default().to_owned(),
+                // `func_tracktrain` starts at `startspeed`, `func_train` at `speed`
+                speed: train
+                    .f32("startspeed")
+                    .or_else(|| train.f32("speed"))
+                    .unwrap_or(0.0),
+                points,
+                closed,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::keyvalues;
+
+    #[test]
+    fn resolve_track_chains() {
+        let vmf = keyvalues::parse_raw(
+            r#"
+            world { "id" "1" "classname" "worldspawn" }
+            entity { "id" "2" "classname" "func_tracktrain" "targetname" "lift" "target" "a" "startspeed" "64" }
+            entity { "id" "3" "classname" "path_track" "targetname" "a" "target" "b" "origin" "0 0 0" }
+            entity { "id" "4" "classname" "path_track" "targetname" "b" "target" "c" "origin" "0 0 128" "speed" "32" }
+            entity { "id" "5" "classname" "path_track" "targetname" "c" "target" "a" "origin" "128 0 128" }
+            entity { "id" "6" "classname" "func_train" "target" "d" }
+            entity { "id" "7" "classname" "path_track" "targetname" "d" "target" "missing" "origin" "0 0 0" }
+            "#,
+        )
+        .unwrap();
+
+        let paths = track_paths(&vmf);
+
+        assert_eq!(paths.len(), 1);
+
+        let path = &paths[0];
+        assert_eq!(path.id, 2);
+        assert_eq!(path.name, "lift");
+        assert!(path.closed);
+        assert_eq!(
+            path.points
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>(),
+            ["a", "b", "c"]
+        );
+        assert_eq!(
+            path.points[2].position.map(f32::to_bits),
+            [128.0, 0.0, 128.0].map(f32::to_bits)
+        );
+        assert!((path.speed - 64.0).abs() < f32::EPSILON);
+        assert!((path.points[1].speed - 32.0).abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,522 @@
+//! Best-effort repair of old or malformed VMF files, mostly produced by decompilers,
+//! so they can be parsed by the strict VMF parser.
+
+use std::fmt::Write;
+
+use crate::keyvalues::{self, KeyValue, ParseError};
+
+type Block = Vec<(String, KeyValue)>;
+
+/// The repaired VMF text and a description of every change made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repaired {
+    pub text: String,
+    pub warnings: Vec<String>,
+}
+
+/// Repairs a VMF file.
+///
+/// # Errors
+///
+/// Returns `Err` if the file is not valid `KeyValues` text.
+pub fn repair(text: &str) -> Result<Repaired, ParseError> {
+    let mut root = keyvalues::parse_raw(text)?;
+
+    let mut repairer = Repairer {
+        next_id: max_id(&root) + 1,
+        warnings: Vec::new(),
+    };
+
+    repairer.repair_root(&mut root);
+
+    let mut text = String::new();
+    write_block(&mut text, &root, 0);
+
+    Ok(Repaired {
+        text,
+        warnings: repairer.warnings,
+    })
+}
+
+fn max_id(block: &[(String, KeyValue)]) -> u64 {
+    block
+        .iter()
+        .map(|(key, value)| match value {
+            KeyValue::Value(v) if key.eq_ignore_ascii_case("id") => v.trim().parse().unwrap_or(0),
+            KeyValue::Value(_) => 0,
+            KeyValue::Block(b) => max_id(b),
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+fn is(key: &str, name: &str) -> bool {
+    key.eq_ignore_ascii_case(name)
+}
+
+fn value<'a>(block: &'a [(String, KeyValue)], key: &str) -> Option<&'a str> {
+    block
+        .iter()
+        .find(|(k, _)| is(k, key))
+        .and_then(|(_, v)| v.as_value())
+}
+
+fn has_block(block: &[(String, KeyValue)], key: &str) -> bool {
+    block
+        .iter()
+        .any(|(k, v)| is(k, key) && matches!(v, KeyValue::Block(_)))
+}
+
+fn block_mut<'a>(block: &'a mut Block, key: &str) -> Option<&'a mut Block> {
+    block.iter_mut().find_map(|(k, v)| match v {
+        KeyValue::Block(b) if is(k, key) => Some(b),
+        _ => None,
+    })
+}
+
+fn describe(kind: &str, block: &[(String, KeyValue)]) -> String {
+    match value(block, "id") {
+        Some(id) => format!("{kind} `{id}`"),
+        None => kind.to_owned(),
+    }
+}
+
+/// Default row contents of displacement data blocks.
+const DISP_ROWS: &[(&str, &str)] = &[
+    ("normals", "0 0 1"),
+    ("distances", "0"),
+    ("offsets", "0 0 0"),
+    ("offset_normals", "0 0 1"),
+    ("alphas", "0"),
+];
+
+struct Repairer {
+    next_id: u64,
+    warnings: Vec<String>,
+}
+
+impl Repairer {
+    fn warn(&mut self, message: String) {
+        self.warnings.push(message);
+    }
+
+    fn ensure_value(&mut self, block: &mut Block, kind: &str, key: &str, default: &str) {
+        if value(block, key).is_none() {
+            self.warn(format!(
+                "{}: missing `{key}`, using `{default}`",
+                describe(kind, block)
+            ));
+            block.push((key.to_owned(), KeyValue::Value(default.to_owned())));
+        }
+    }
+
+    fn ensure_id(&mut self, block: &mut Block, kind: &str) {
+        if value(block, "id").is_none() {
+            let id = self.next_id;
+            self.next_id += 1;
+
+            self.warn(format!("{kind} without an id, assigned `{id}`"));
+            block.insert(0, ("id".to_owned(), KeyValue::Value(id.to_string())));
+        }
+    }
+
+    fn ensure_editor(&mut self, block: &mut Block, kind: &str) {
+        if !has_block(block, "editor") {
+            self.warn(format!("{}: missing editor block", describe(kind, block)));
+            block.push((
+                "editor".to_owned(),
+                KeyValue::Block(vec![
+                    ("color".to_owned(), KeyValue::Value("0 255 0".to_owned())),
+                    ("visgroupshown".to_owned(), KeyValue::Value("1".to_owned())),
+                    (
+                        "visgroupautoshown".to_owned(),
+                        KeyValue::Value("1".to_owned()),
+                    ),
+                ]),
+            ));
+        }
+    }
+
+    /// Keeps only the last of duplicated plain values, except in blocks where duplicates are valid.
+    fn dedup_values(&mut self, block: &mut Block, kind: &str) {
+        if is(kind, "connections") || is(kind, "editor") {
+            return;
+        }
+
+        let mut index = 0;
+
+        while index < block.len() {
+            let (key, value) = &block[index];
+
+            let duplicated = matches!(value, KeyValue::Value(_))
+                && block[index + 1..]
+                    .iter()
+                    .any(|(k, v)| matches!(v, KeyValue::Value(_)) && is(k, key));
+
+            if duplicated {
+                let message = format!(
+                    "{}: duplicate key `{key}`, keeping the last value",
+                    describe(kind, block)
+                );
+                self.warn(message);
+                block.remove(index);
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    fn repair_root(&mut self, root: &mut Block) {
+        if !has_block(root, "versioninfo") {
+            self.warn("missing versioninfo block".to_owned());
+            root.insert(
+                0,
+                (
+                    "versioninfo".to_owned(),
+                    KeyValue::Block(
+                        [
+                            ("editorversion", "400"),
+                            ("editorbuild", "0"),
+                            ("mapversion", "0"),
+                            ("formatversion", "100"),
+                            ("prefab", "0"),
+                        ]
+                        .iter()
+                        .map(|&(k, v)| (k.to_owned(), KeyValue::Value(v.to_owned())))
+                        .collect(),
+                    ),
+                ),
+            );
+        }
+
+        for (key, value) in root.iter_mut() {
+            let KeyValue::Block(block) = value else {
+                continue;
+            };
+
+            if is(key, "world") || is(key, "entity") {
+                self.repair_entity(block, key);
+            } else {
+                self.dedup_values(block, key);
+            }
+        }
+    }
+
+    fn repair_entity(&mut self, entity: &mut Block, kind: &str) {
+        self.dedup_values(entity, kind);
+        self.ensure_id(entity, kind);
+
+        if !is(kind, "world") {
+            self.ensure_editor(entity, kind);
+        }
+
+        for (key, value) in entity.iter_mut() {
+            let KeyValue::Block(block) = value else {
+                continue;
+            };
+
+            if is(key, "solid") {
+                self.repair_solid(block);
+            } else if is(key, "hidden") {
+                // hidden solids and entities are wrapped in an extra block
+                for (key, value) in block.iter_mut() {
+                    if let (true, KeyValue::Block(solid)) = (is(key, "solid"), value) {
+                        self.repair_solid(solid);
+                    }
+                }
+            } else {
+                self.dedup_values(block, key);
+            }
+        }
+    }
+
+    fn repair_solid(&mut self, solid: &mut Block) {
+        self.dedup_values(solid, "solid");
+        self.ensure_id(solid, "solid");
+        self.ensure_editor(solid, "solid");
+
+        let solid_name = describe("solid", solid);
+
+        solid.retain(|(key, value)| match value {
+            KeyValue::Block(side) if is(key, "side") => {
+                value_exists(side, "plane") || {
+                    self.warnings
+                        .push(format!("{solid_name}: removed side without a plane"));
+                    false
+                }
+            }
+            _ => true,
+        });
+
+        for (key, value) in solid.iter_mut() {
+            if let (true, KeyValue::Block(side)) = (is(key, "side"), value) {
+                self.repair_side(side);
+            }
+        }
+    }
+
+    fn repair_side(&mut self, side: &mut Block) {
+        self.dedup_values(side, "side");
+        self.ensure_id(side, "side");
+
+        for (key, default) in [
+            ("material", "TOOLS/TOOLSNODRAW"),
+            ("uaxis", "[1 0 0 0] 0.25"),
+            ("vaxis", "[0 -1 0 0] 0.25"),
+            ("rotation", "0"),
+            ("lightmapscale", "16"),
+            ("smoothing_groups", "0"),
+        ] {
+            self.ensure_value(side, "side", key, default);
+        }
+
+        let side_name = describe("side", side);
+
+        let power = block_mut(side, "dispinfo")
+            .map(|dispinfo| value(dispinfo, "power").and_then(|p| p.trim().parse::<u32>().ok()));
+
+        match power {
+            None => {}
+            Some(Some(power @ 2..=4)) => {
+                if let Some(dispinfo) = block_mut(side, "dispinfo") {
+                    self.repair_dispinfo(dispinfo, &side_name, power);
+                }
+            }
+            Some(_) => {
+                self.warn(format!(
+                    "{side_name}: removed displacement with an invalid power"
+                ));
+                side.retain(|(key, value)| {
+                    !(is(key, "dispinfo") && matches!(value, KeyValue::Block(_)))
+                });
+            }
+        }
+    }
+
+    fn repair_dispinfo(&mut self, dispinfo: &mut Block, side_name: &str, power: u32) {
+        self.dedup_values(dispinfo, "dispinfo");
+
+        for (key, default) in [
+            ("startposition", "[0 0 0]"),
+            ("flags", "0"),
+            ("elevation", "0"),
+            ("subdiv", "0"),
+        ] {
+            self.ensure_value(dispinfo, "dispinfo", key, default);
+        }
+
+        let size = (1 << power) + 1;
+
+        for &(name, default) in DISP_ROWS {
+            let components = default.split_whitespace().count();
+            let default_row = vec![default; size].join(" ");
+            self.repair_rows(
+                dispinfo,
+                side_name,
+                name,
+                size,
+                size * components,
+                &default_row,
+            );
+        }
+
+        let quads = size - 1;
+        let default_tags = vec!["9"; quads * 2].join(" ");
+        self.repair_rows(
+            dispinfo,
+            side_name,
+            "triangle_tags",
+            quads,
+            quads * 2,
+            &default_tags,
+        );
+
+        if !has_block(dispinfo, "allowed_verts") {
+            dispinfo.push((
+                "allowed_verts".to_owned(),
+                KeyValue::Block(vec![(
+                    "10".to_owned(),
+                    KeyValue::Value(["-1"; 10].join(" ")),
+                )]),
+            ));
+        }
+    }
+
+    /// Makes sure a displacement data block has exactly `rows` rows of `values` numbers each.
+    fn repair_rows(
+        &mut self,
+        dispinfo: &mut Block,
+        side_name: &str,
+        name: &str,
+        rows: usize,
+        values: usize,
+        default_row: &str,
+    ) {
+        if !has_block(dispinfo, name) {
+            // missing data blocks are filled in silently, old vmfs don't have all of them
+            dispinfo.push((name.to_owned(), KeyValue::Block(Vec::new())));
+        }
+
+        let Some(block) = block_mut(dispinfo, name) else {
+            return;
+        };
+
+        let mut repaired = Vec::with_capacity(rows);
+        let mut changed = block.len() > rows;
+
+        for row in 0..rows {
+            let key = format!("row{row}");
+            let original = value(block, &key);
+
+            let row_value = if let Some(original) = original {
+                let mut numbers: Vec<&str> = original.split_whitespace().collect();
+                let defaults: Vec<&str> = default_row.split_whitespace().collect();
+
+                if numbers.len() != values {
+                    changed = true;
+                    numbers.truncate(values);
+                    numbers.extend_from_slice(&defaults[numbers.len()..]);
+                }
+
+                numbers.join(" ")
+            } else {
+                changed |= !block.is_empty();
+                default_row.to_owned()
+            };
+
+            repaired.push((key, KeyValue::Value(row_value)));
+        }
+
+        if changed {
+            self.warn(format!(
+                "{side_name}: displacement `{name}` has an unexpected size, it was resized"
+            ));
+        }
+
+        *block = repaired;
+    }
+}
+
+fn value_exists(block: &[(String, KeyValue)], key: &str) -> bool {
+    value(block, key).is_some()
+}
+
+pub fn write_block(out: &mut String, block: &[(String, KeyValue)], depth: usize) {
+    let indent = "\t".repeat(depth);
+
+    for (key, value) in block {
+        match value {
+            KeyValue::Value(v) => {
+                // vmf values have no escapes
+                let _ = writeln!(out, "{indent}\"{key}\" \"{}\"", v.replace('"', "'"));
+            }
+            KeyValue::Block(b) => {
+                let _ = writeln!(out, "{indent}{key}\n{indent}{{");
+                write_block(out, b, depth + 1);
+                let _ = writeln!(out, "{indent}}}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repair_decompiled() {
+        let text = r#"
+            world
+            {
+                "id" "1"
+                "classname" "worldspawn"
+                "classname" "worldspawn"
+                solid
+                {
+                    side
+                    {
+                        "id" "5"
+                        "plane" "(0 0 0) (1 0 0) (0 1 0)"
+                        "material" "maps\nuke\floor"
+                        dispinfo
+                        {
+                            "power" "2"
+                            normals
+                            {
+                                "row0" "0 0 1"
+                            }
+                        }
+                    }
+                    side
+                    {
+                        "material" "DEV/DEV_MEASUREGENERIC01"
+                    }
+                }
+            }
+        "#;
+
+        let repaired = repair(text).unwrap();
+        let root = keyvalues::parse_raw(&repaired.text).unwrap();
+
+        assert!(has_block(&root, "versioninfo"));
+
+        let world = root
+            .iter()
+            .find(|(k, _)| k == "world")
+            .unwrap()
+            .1
+            .as_block()
+            .unwrap();
+        assert_eq!(world.iter().filter(|(k, _)| k == "classname").count(), 1);
+
+        let solid = world
+            .iter()
+            .find(|(k, _)| k == "solid")
+            .unwrap()
+            .1
+            .as_block()
+            .unwrap();
+        assert_eq!(value(solid, "id"), Some("6"));
+        assert!(has_block(solid, "editor"));
+        assert_eq!(solid.iter().filter(|(k, _)| k == "side").count(), 1);
+
+        let side = solid
+            .iter()
+            .find(|(k, _)| k == "side")
+            .unwrap()
+            .1
+            .as_block()
+            .unwrap();
+        assert_eq!(value(side, "material"), Some("maps\\nuke\\floor"));
+        assert_eq!(value(side, "lightmapscale"), Some("16"));
+
+        let dispinfo = side
+            .iter()
+            .find(|(k, _)| k == "dispinfo")
+            .unwrap()
+            .1
+            .as_block()
+            .unwrap();
+        let normals = dispinfo
+            .iter()
+            .find(|(k, _)| k == "normals")
+            .unwrap()
+            .1
+            .as_block()
+            .unwrap();
+        assert_eq!(normals.len(), 5);
+        assert_eq!(
+            value(normals, "row0"),
+            Some("0 0 1 0 0 1 0 0 1 0 0 1 0 0 1")
+        );
+
+        let tags = dispinfo
+            .iter()
+            .find(|(k, _)| k == "triangle_tags")
+            .unwrap()
+            .1
+            .as_block()
+            .unwrap();
+        assert_eq!(tags.len(), 4);
+    }
+}
@@ -0,0 +1,97 @@
+//! Reads the header of `.vtf` textures. The size is at the start of the header, so it
+//! can be read without reading the rest of the texture.
+
+use crate::bytes::read_le;
+
+const SIGNATURE: &[u8; 4] = b"VTF\0";
+const MAJOR_VERSION: u32 = 7;
+
+/// Bytes at the start of a texture needed to read its size.
+pub const SIZE_LEN: usize = 20;
+/// Bytes at the start of a texture needed to read its image format.
+pub const FORMAT_LEN: usize = 56;
+
+/// The header fields of a texture needed to find and decode its images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VtfHeader {
+    pub minor_version: u32,
+    pub header_size: usize,
+    pub width: u32,
+    pub height: u32,
+    pub flags: u32,
+    pub frames: usize,
+    pub first_frame: u16,
+    pub format: i32,
+    pub mipmap_count: u32,
+    pub low_res_format: i32,
+    pub low_res_width: u32,
+    pub low_res_height: u32,
+    /// Depth of volume textures, 1 for other textures and before version 7.2.
+    pub depth: usize,
+}
+
+impl VtfHeader {
+    /// Reads the header, or returns `None` if the data isn't a supported texture.
+    pub fn read(data: &[u8]) -> Option<Self> {
+        if data.get(..4)? != SIGNATURE || read_le::<u32>(data, 4)? != MAJOR_VERSION {
+            return None;
+        }
+
+        let minor_version = read_le(data, 8)?;
+
+        Some(Self {
+            minor_version,
+            header_size: usize::try_from(read_le::<u32>(data, 12)?).ok()?,
+            width: read_le::<u16>(data, 16)?.into(),
+            height: read_le::<u16>(data, 18)?.into(),
+            flags: read_le(data, 20)?,
+            frames: read_le::<u16>(data, 24)?.into(),
+            first_frame: read_le(data, 26)?,
+            format: read_le(data, 52)?,
+            mipmap_count: read_le::<u8>(data, 56)?.into(),
+            low_res_format: read_le(data, 57)?,
+            low_res_width: read_le::<u8>(data, 61)?.into(),
+            low_res_height: read_le::<u8>(data, 62)?.into(),
+            depth: if minor_version >= 2 {
+                read_le::<u16>(data, 63)?.max(1).into()
+            } else {
+                1
+            },
+        })
+    }
+}
+
+/// Reads the size of a texture from the first [`SIZE_LEN`] bytes of it.
+pub fn size(data: &[u8]) -> Option<[u16; 2]> {
+    if data.get(..4)? != SIGNATURE {
+        return None;
+    }
+
+    Some([read_le(data, 16)?, read_le(data, 18)?])
+}
+
+/// Reads the image format of a texture from the first [`FORMAT_LEN`] bytes of it,
+/// without checking the rest of the header.
+pub fn format(data: &[u8]) -> Option<i32> {
+    if data.get(..4)? != SIGNATURE {
+        return None;
+    }
+
+    read_le(data, 52)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_size() {
+        let mut header = SIGNATURE.to_vec();
+        header.resize(16, 0);
+        header.extend([128, 0, 64, 0]);
+
+        assert_eq!(size(&header), Some([128, 64]));
+        assert_eq!(size(SIGNATURE), None);
+        assert_eq!(VtfHeader::read(&header), None);
+    }
+}
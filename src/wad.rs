@@ -0,0 +1,361 @@
+//! Reads GoldSrc texture archives (`.wad`), which Half-Life 1 era maps store their
+//! textures in. The textures are palettized, with a palette of 256 colors per texture.
+//!
+//! Archives are mounted by extracting each texture as a loose image with a material,
+//! so that maps and materials resolve them through the file system like other textures.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    fs,
+    io::{self, Cursor},
+    path::Path,
+};
+
+use image::{ImageOutputFormat, RgbaImage};
+use tracing::warn;
+
+use crate::{
+    bytes::{self, LeBytes},
+    game_path,
+};
+
+const MAGIC: &[u8; 4] = b"WAD3";
+const HEADER_SIZE: usize = 12;
+const DIR_ENTRY_SIZE: usize = 32;
+const MIP_HEADER_SIZE: usize = 40;
+const NAME_SIZE: usize = 16;
+/// Lump type of textures with mipmaps and a palette.
+const MIP_TEXTURE_TYPE: u8 = 0x43;
+pub const PALETTE_COLORS: usize = 256;
+/// Textures with names starting with this are transparent where the last palette
+/// color is used.
+const TRANSPARENT_PREFIX: char = '{';
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WadError {
+    InvalidHeader,
+    UnexpectedEof,
+    Compressed(String),
+    InvalidTexture(String),
+}
+
+impl Display for WadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            WadError::InvalidHeader => f.write_str("invalid wad header"),
+            WadError::UnexpectedEof => f.write_str("unexpected end of file"),
+            WadError::Compressed(name) => write!(f, "texture `{name}` is compressed"),
+            WadError::InvalidTexture(name) => write!(f, "texture `{name}` is invalid"),
+        }
+    }
+}
+
+impl std::error::Error for WadError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WadEntry {
+    pub name: String,
+    offset: usize,
+    size: usize,
+    kind: u8,
+    compression: u8,
+}
+
+impl WadEntry {
+    pub fn is_texture(&self) -> bool {
+        self.kind == MIP_TEXTURE_TYPE
+    }
+}
+
+/// A decoded texture, with the pixels as RGBA8 rows from the top.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WadTexture {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// Reads the directory of an archive.
+///
+/// # Errors
+///
+/// Returns `Err` if the header is invalid or the directory is truncated.
+pub fn entries(data: &[u8]) -> Result<Vec<WadEntry>, WadError> {
+    if data.len() < HEADER_SIZE || &data[..4] != MAGIC {
+        return Err(WadError::InvalidHeader);
+    }
+
+    let count = read_le::<u32>(data, 4)? as usize;
+    let dir_offset = read_le::<u32>(data, 8)? as usize;
+
+    (0..count)
+        .map(|i| {
+            let offset = dir_offset + i * DIR_ENTRY_SIZE;
+            let entry = data
+                .get(offset..offset + DIR_ENTRY_SIZE)
+                .ok_or(WadError::UnexpectedEof)?;
+
+            Ok(WadEntry {
+                name: read_name(&entry[16..]),
+                offset: read_le::<u32>(entry, 0)? as usize,
+                size: read_le::<u32>(entry, 4)? as usize,
+                kind: entry[12],
+                compression: entry[13],
+            })
+        })
+        .collect()
+}
+
+/// Decodes the full resolution mipmap of a texture entry.
+///
+/// # Errors
+///
+/// Returns `Err` if the entry is compressed or its data is invalid.
+pub fn read_texture(data: &[u8], entry: &WadEntry) -> Result<WadTexture, WadError> {
+    let invalid = || WadError::InvalidTexture(entry.name.clone());
+
+    if entry.compression != 0 {
+        return Err(WadError::Compressed(entry.name.clone()));
+    }
+
+    let lump = data
+        .get(entry.offset..entry.offset + entry.size)
+        .ok_or(WadError::UnexpectedEof)?;
+
+    if lump.len() < MIP_HEADER_SIZE {
+        return Err(invalid());
+    }
+
+    let width = read_le::<u32>(lump, 16)?;
+    let height = read_le::<u32>(lump, 20)?;
+    let pixel_offset = read_le::<u32>(lump, 24)? as usize;
+    let last_mip_offset = read_le::<u32>(lump, 36)? as usize;
+
+    let pixel_count = (width as usize)
+        .checked_mul(height as usize)
+        .filter(|&count| count > 0)
+        .ok_or_else(invalid)?;
+    let pixels = lump
+        .get(pixel_offset..pixel_offset + pixel_count)
+        .ok_or_else(invalid)?;
+
+    // the palette follows the smallest mipmap, which is an eighth of the size
+    let palette_offset = last_mip_offset + (width as usize / 8) * (height as usize / 8) + 2;
+    let palette = lump
+        .get(palette_offset..palette_offset + PALETTE_COLORS * 3)
+        .ok_or_else(invalid)?;
+
+    Ok(decode_palettized(
+        entry.name.clone(),
+        (width, height),
+        pixels,
+        palette,
+        entry.name.starts_with(TRANSPARENT_PREFIX),
+    ))
+}
+
+/// Decodes palettized pixels, which GoldSrc models embed their textures as too. If
+/// `transparent` is set, the last palette color is transparent.
+///
+/// # Panics
+///
+/// Panics if the palette has less than 256 colors.
+pub fn decode_palettized(
+    name: String,
+    (width, height): (u32, u32),
+    pixels: &[u8],
+    palette: &[u8],
+    transparent: bool,
+) -> WadTexture {
+    let mut rgba = Vec::with_capacity(pixels.len() * 4);
+
+    for &index in pixels {
+        let color = &palette[usize::from(index) * 3..][..3];
+        let alpha = if transparent && usize::from(index) == PALETTE_COLORS - 1 {
+            0
+        } else {
+            255
+        };
+
+        rgba.extend_from_slice(color);
+        rgba.push(alpha);
+    }
+
+    WadTexture {
+        name,
+        width,
+        height,
+        rgba,
+    }
+}
+
+/// Extracts the textures of an archive into `target_dir` as `materials/<name>.png`, with a
+/// `materials/<name>.vmt` using it, since maps refer to the textures by name only.
+/// Textures which can't be decoded are skipped. Returns the number of textures extracted.
+///
+/// # Errors
+///
+/// Returns `Err` if the directory of the archive is invalid or writing fails.
+pub fn extract(data: &[u8], target_dir: &Path) -> io::Result<usize> {
+    let entries = entries(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let materials_dir = target_dir.join("materials");
+    fs::create_dir_all(&materials_dir)?;
+
+    let mut extracted = 0;
+
+    for entry in entries.iter().filter(|entry| entry.is_texture()) {
+        if !game_path::is_safe_to_extract(&entry.name) || entry.name.contains(['*', '?']) {
+            warn!("texture `{}`: name can't be extracted", entry.name);
+            continue;
+        }
+
+        let texture = match read_texture(data, entry) {
+            Ok(texture) => texture,
+            Err(error) => {
+                warn!("{error}");
+                continue;
+            }
+        };
+
+        let image = RgbaImage::from_raw(texture.width, texture.height, texture.rgba)
+            .expect("wad texture should have a pixel for its whole size");
+        let mut png = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+        fs::write(materials_dir.join(format!("{}.png", texture.name)), png)?;
+        fs::write(
+            materials_dir.join(format!("{}.vmt", texture.name)),
+            material(&texture.name),
+        )?;
+
+        extracted += 1;
+    }
+
+    Ok(extracted)
+}
+
+/// The material of an extracted texture. The `.vtf` it refers to is missing, so the
+/// loose image is loaded instead.
+fn material(name: &str) -> String {
+    let alpha_test = if name.starts_with(TRANSPARENT_PREFIX) {
+        "\t\"$alphatest\" \"1\"\n"
+    } else {
+        ""
+    };
+
+    format!("\"LightmappedGeneric\"\n{{\n\t\"$basetexture\" \"{name}\"\n{alpha_test}}}\n")
+}
+
+fn read_le<T: LeBytes>(data: &[u8], offset: usize) -> Result<T, WadError> {
+    bytes::read_le(data, offset).ok_or(WadError::UnexpectedEof)
+}
+
+/// Reads a null-terminated name, lowercased since the names are case-insensitive.
+fn read_name(data: &[u8]) -> String {
+    let name = &data[..NAME_SIZE.min(data.len())];
+    let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+
+    String::from_utf8_lossy(&name[..end]).to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(name: &str) -> [u8; NAME_SIZE] {
+        let mut bytes = [0; NAME_SIZE];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        bytes
+    }
+
+    fn mip_texture(name_str: &str, width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+        let sizes = [1, 4, 16, 64].map(|d| (width * height / d) as usize);
+        let mut offset = MIP_HEADER_SIZE;
+        let mut lump = name(name_str).to_vec();
+        lump.extend_from_slice(&width.to_le_bytes());
+        lump.extend_from_slice(&height.to_le_bytes());
+
+        for size in sizes {
+            lump.extend_from_slice(&u32::try_from(offset).unwrap().to_le_bytes());
+            offset += size;
+        }
+
+        lump.extend_from_slice(pixels);
+        lump.resize(offset, 0);
+        lump.extend_from_slice(&256_u16.to_le_bytes());
+
+        for i in 0..=255_u8 {
+            lump.extend_from_slice(&[i, 255 - i, 7]);
+        }
+
+        lump
+    }
+
+    fn archive(names: &[&str]) -> Vec<u8> {
+        let pixels: Vec<u8> = (0..64).map(|i| if i == 0 { 255 } else { 1 }).collect();
+        let lumps: Vec<_> = names
+            .iter()
+            .map(|name| mip_texture(name, 8, 8, &pixels))
+            .collect();
+
+        let dir_offset = HEADER_SIZE + lumps.iter().map(Vec::len).sum::<usize>();
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&u32::try_from(names.len()).unwrap().to_le_bytes());
+        data.extend_from_slice(&u32::try_from(dir_offset).unwrap().to_le_bytes());
+        lumps.iter().for_each(|lump| data.extend_from_slice(lump));
+
+        let mut offset = HEADER_SIZE;
+        for (lump, lump_name) in lumps.iter().zip(names) {
+            let size = u32::try_from(lump.len()).unwrap().to_le_bytes();
+            data.extend_from_slice(&u32::try_from(offset).unwrap().to_le_bytes());
+            data.extend_from_slice(&size);
+            data.extend_from_slice(&size);
+            data.extend_from_slice(&[MIP_TEXTURE_TYPE, 0, 0, 0]);
+            data.extend_from_slice(&name(lump_name));
+            offset += lump.len();
+        }
+
+        data
+    }
+
+    #[test]
+    fn read_textures() {
+        let data = archive(&["{FENCE", "CRATE01"]);
+
+        let wad_entries = entries(&data).unwrap();
+        assert_eq!(wad_entries.len(), 2);
+        assert!(wad_entries.iter().all(WadEntry::is_texture));
+
+        let fence = read_texture(&data, &wad_entries[0]).unwrap();
+        assert_eq!(fence.name, "{fence");
+        assert_eq!((fence.width, fence.height), (8, 8));
+        assert_eq!(fence.rgba[..8], [255, 0, 7, 0, 1, 254, 7, 255]);
+
+        let crate_texture = read_texture(&data, &wad_entries[1]).unwrap();
+        assert_eq!(crate_texture.rgba[..4], [255, 0, 7, 255]);
+
+        assert_eq!(entries(b"WAD2"), Err(WadError::InvalidHeader));
+    }
+
+    #[test]
+    fn extract_textures() {
+        let dir = std::env::temp_dir().join("plumber_wad_test");
+        let _ = fs::remove_dir_all(&dir);
+
+        let data = archive(&["{FENCE", "CRATE01", "*WATER"]);
+        assert_eq!(extract(&data, &dir).unwrap(), 2);
+
+        let materials = dir.join("materials");
+        assert!(materials.join("crate01.png").is_file());
+        assert!(!materials.join("*water.png").exists());
+        assert_eq!(
+            fs::read_to_string(materials.join("{fence.vmt")).unwrap(),
+            "\"LightmappedGeneric\"\n{\n\t\"$basetexture\" \"{fence\"\n\t\"$alphatest\" \"1\"\n}\n"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+//! Tracks assets which are being processed, so that assets which take suspiciously long
+//! (usually because the file is corrupt) can be reported. The workers can't be
+//! interrupted, so the import stops waiting for them instead once nothing else is
+//! being imported.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, MutexGuard, TryLockError,
+    },
+    time::{Duration, Instant},
+};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+static ACTIVE: Mutex<BTreeMap<u64, Entry>> = Mutex::new(BTreeMap::new());
+
+struct Entry {
+    name: String,
+    started: Instant,
+    reported: bool,
+}
+
+fn active() -> MutexGuard<'static, BTreeMap<u64, Entry>> {
+    // a panicking worker must not disable the watchdog for everyone else
+    ACTIVE
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Marks an asset as being processed until the guard is dropped.
+#[must_use = "the asset is only watched while the guard is alive"]
+pub struct Watch {
+    id: u64,
+}
+
+impl Watch {
+    pub fn new(name: impl Into<String>) -> Self {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+
+        active().insert(
+            id,
+            Entry {
+                name: name.into(),
+                started: Instant::now(),
+                reported: false,
+            },
+        );
+
+        Self { id }
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        active().remove(&self.id);
+    }
+}
+
+/// Returns the assets which have been processing for longer than `timeout`
+/// and haven't been returned before, with their processing times.
+pub fn overdue(timeout: Duration) -> Vec<(String, Duration)> {
+    let now = Instant::now();
+
+    active()
+        .values_mut()
+        .filter(|entry| !entry.reported && now - entry.started >= timeout)
+        .map(|entry| {
+            entry.reported = true;
+            (entry.name.clone(), now - entry.started)
+        })
+        .collect()
+}
+
+/// Whether any asset has been processing for longer than `timeout`.
+pub fn any_overdue(timeout: Duration) -> bool {
+    let now = Instant::now();
+
+    active()
+        .values()
+        .any(|entry| now - entry.started >= timeout)
+}
+
+/// Returns the assets which are being processed, unless the list is locked.
+/// Used while panicking, when the panicking thread may hold the lock.
+pub fn try_active() -> Option<Vec<String>> {
+    let active = match ACTIVE.try_lock() {
+        Ok(active) => active,
+        Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+        Err(TryLockError::WouldBlock) => return None,
+    };
+
+    Some(active.values().map(|entry| entry.name.clone()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread};
+
+    use super::*;
+
+    #[test]
+    fn report_overdue_once() {
+        let finished = Watch::new("finished");
+        drop(finished);
+
+        let _stuck = Watch::new("stuck");
+
+        let reported = overdue(Duration::ZERO);
+        assert!(reported.iter().any(|(name, _)| name == "stuck"));
+        assert!(reported.iter().all(|(name, _)| name != "finished"));
+
+        assert!(overdue(Duration::ZERO)
+            .iter()
+            .all(|(name, _)| name != "stuck"));
+        assert!(any_overdue(Duration::ZERO));
+    }
+
+    #[test]
+    fn report_slow_handler() {
+        let (started_tx, started_rx) = mpsc::channel();
+        let (finish_tx, finish_rx) = mpsc::channel::<()>();
+
+        let handler = thread::spawn(move || {
+            let _watch = Watch::new("slow handler");
+            started_tx.send(()).unwrap();
+            let _ = finish_rx.recv();
+        });
+
+        started_rx.recv().unwrap();
+        thread::sleep(Duration::from_millis(20));
+
+        let reported = overdue(Duration::from_millis(10));
+        assert!(
+            reported
+                .iter()
+                .any(|(name, elapsed)| name == "slow handler"
+                    && *elapsed >= Duration::from_millis(10))
+        );
+
+        finish_tx.send(()).unwrap();
+        handler.join().unwrap();
+
+        assert!(try_active()
+            .unwrap()
+            .iter()
+            .all(|name| name != "slow handler"));
+    }
+}
@@ -0,0 +1,231 @@
+//! Rebuilds the faces of world solids from the planes of their vmf sides, for features
+//! which need the world geometry before it's built, or the faces merged solids lose.
+
+use glam::Vec3;
+
+use crate::{
+    keyvalues::KeyValue,
+    kv_utils::{block_value, parse_plane, parse_vector},
+};
+
+/// Brush entities which the compiler merges into the world.
+const WORLD_BRUSH_CLASSES: [&str; 1] = ["func_detail"];
+/// Half size of the initial winding of a face, larger than any map.
+const MAX_EXTENT: f32 = 65536.0;
+pub const EPSILON: f32 = 0.01;
+
+/// A convex face of a world solid.
+pub struct Face {
+    /// Outward normal of the face.
+    pub normal: Vec3,
+    pub distance: f32,
+    pub vertices: Vec<Vec3>,
+    pub material: String,
+}
+
+impl Face {
+    /// Returns if a point on the plane of the face is inside the face.
+    pub fn contains(&self, point: Vec3) -> bool {
+        #[allow(clippy::cast_precision_loss)]
+        let center = self.vertices.iter().copied().sum::<Vec3>() / self.vertices.len() as f32;
+        let next = self.vertices.iter().cycle().skip(1);
+
+        // the vertices may wind either way, so the point is compared to the center
+        self.vertices.iter().zip(next).all(|(&a, &b)| {
+            let edge_normal = (b - a).cross(self.normal);
+            let side = edge_normal.dot(point - a);
+            let center_side = edge_normal.dot(center - a);
+            side * center_side >= -EPSILON
+        })
+    }
+}
+
+/// Returns the corners of the base face of a displacement clockwise from the
+/// `startposition` of its dispinfo, as in the game. The edges of the displacement are
+/// numbered from the start corner.
+pub fn displacement_corners(
+    vertices: &[Vec3],
+    dispinfo: &[(String, KeyValue)],
+) -> Option<Vec<Vec3>> {
+    let start = block_value(dispinfo, "startposition")
+        .map(|v| v.trim_matches(|c: char| c == '[' || c == ']' || c.is_whitespace()))
+        .and_then(parse_vector)?;
+
+    let mut corners: Vec<Vec3> = vertices.iter().rev().copied().collect();
+    let start_index = (0..corners.len())
+        .min_by(|&a, &b| {
+            corners[a]
+                .distance_squared(start)
+                .total_cmp(&corners[b].distance_squared(start))
+        })
+        .unwrap_or(0);
+    corners.rotate_left(start_index);
+
+    Some(corners)
+}
+
+/// Rebuilds the faces of the world solids from their sides.
+pub fn world_faces(vmf: &[(String, KeyValue)]) -> Vec<Face> {
+    let brush_entities = vmf.iter().filter_map(|(k, v)| {
+        let block = v.as_block()?;
+
+        if k.eq_ignore_ascii_case("world") {
+            return Some(block);
+        }
+
+        let class_name = block_value(block, "classname")?;
+        (k.eq_ignore_ascii_case("entity")
+            && WORLD_BRUSH_CLASSES
+                .iter()
+                .any(|c| class_name.eq_ignore_ascii_case(c)))
+        .then_some(block)
+    });
+
+    let solids = brush_entities
+        .flatten()
+        .filter(|(k, _)| k.eq_ignore_ascii_case("solid"))
+        .filter_map(|(_, v)| v.as_block());
+
+    solids
+        .flat_map(solid_windings)
+        .map(|winding| Face {
+            normal: winding.normal,
+            distance: winding.distance,
+            vertices: winding.vertices,
+            material: block_value(winding.side, "material")
+                .unwrap_or_default()
+                .to_owned(),
+        })
+        .collect()
+}
+
+/// A side of a solid with the polygon it covers.
+pub struct SideWinding<'a> {
+    pub side: &'a [(String, KeyValue)],
+    /// Outward normal of the side.
+    pub normal: Vec3,
+    pub distance: f32,
+    pub vertices: Vec<Vec3>,
+}
+
+/// Clips the plane of each side of a solid by the other sides. Degenerate sides are
+/// left out.
+pub fn solid_windings(solid: &[(String, KeyValue)]) -> Vec<SideWinding<'_>> {
+    let sides: Vec<(&[(String, KeyValue)], Vec3, f32)> = solid
+        .iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case("side"))
+        .filter_map(|(_, v)| v.as_block())
+        .filter_map(|side| {
+            let (normal, distance) = block_value(side, "plane").and_then(parse_plane)?;
+            Some((side, normal, distance))
+        })
+        .collect();
+
+    let mut windings = Vec::new();
+
+    for (i, &(side, normal, distance)) in sides.iter().enumerate() {
+        let mut vertices = plane_winding(normal, distance);
+
+        for (j, &(_, other_normal, other_distance)) in sides.iter().enumerate() {
+            if i != j {
+                vertices = clip(&vertices, other_normal, other_distance);
+            }
+        }
+
+        if vertices.len() >= 3 {
+            windings.push(SideWinding {
+                side,
+                normal,
+                distance,
+                vertices,
+            });
+        }
+    }
+
+    windings
+}
+
+/// Returns a large square on the plane, which is then clipped to the face.
+fn plane_winding(normal: Vec3, distance: f32) -> Vec<Vec3> {
+    let up = if normal.z.abs() > 0.9 {
+        Vec3::X
+    } else {
+        Vec3::Z
+    };
+    let u = up.cross(normal).try_normalize().unwrap_or(Vec3::X) * MAX_EXTENT;
+    let v = normal.cross(u).try_normalize().unwrap_or(Vec3::Y) * MAX_EXTENT;
+    let center = normal * distance;
+
+    vec![
+        center - u - v,
+        center + u - v,
+        center + u + v,
+        center - u + v,
+    ]
+}
+
+/// Clips a convex polygon to the part behind the plane.
+pub fn clip(vertices: &[Vec3], normal: Vec3, distance: f32) -> Vec<Vec3> {
+    let mut clipped = Vec::with_capacity(vertices.len() + 1);
+    let next = vertices.iter().cycle().skip(1);
+
+    for (&a, &b) in vertices.iter().zip(next) {
+        let a_distance = normal.dot(a) - distance;
+        let b_distance = normal.dot(b) - distance;
+
+        if a_distance <= EPSILON {
+            clipped.push(a);
+        }
+
+        if (a_distance > EPSILON && b_distance < -EPSILON)
+            || (a_distance < -EPSILON && b_distance > EPSILON)
+        {
+            let fraction = a_distance / (a_distance - b_distance);
+            clipped.push(a + (b - a) * fraction);
+        }
+    }
+
+    clipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::keyvalues;
+
+    #[test]
+    fn rebuild_world_faces() {
+        let vmf = keyvalues::parse_raw(
+            r#"
+            world
+            {
+                solid
+                {
+                    side { "plane" "(-64 64 0) (64 64 0) (64 -64 0)" "material" "TOOLS/TOOLSSKYBOX" }
+                    side { "plane" "(-64 -64 -16) (64 -64 -16) (64 64 -16)" }
+                    side { "plane" "(-64 64 0) (-64 -64 0) (-64 -64 -16)" }
+                    side { "plane" "(64 64 -16) (64 -64 -16) (64 -64 0)" }
+                    side { "plane" "(64 64 0) (-64 64 0) (-64 64 -16)" }
+                    side { "plane" "(64 -64 -16) (-64 -64 -16) (-64 -64 0)" }
+                }
+            }
+            entity
+            {
+                "classname" "func_door"
+                solid { side { "plane" "(0 0 0) (1 0 0) (1 -1 0)" } }
+            }
+            "#,
+        )
+        .unwrap();
+
+        let faces = world_faces(&vmf);
+        assert_eq!(faces.len(), 6);
+        assert!(faces.iter().all(|f| f.vertices.len() == 4));
+
+        let top = &faces[0];
+        assert_eq!(top.material, "TOOLS/TOOLSSKYBOX");
+        assert!(top.contains(Vec3::new(64.0, 10.0, 0.0)));
+        assert!(!top.contains(Vec3::new(65.0, 10.0, 0.0)));
+    }
+}